@@ -0,0 +1,69 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use numberlink_solver::{encode_formula, parse_url, solve_numberlink, Encoding, SolverConfig};
+
+const CORPUS: &str = include_str!("corpus.txt");
+
+fn corpus() -> Vec<(&'static str, &'static str)> {
+    CORPUS.lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+
+            Some((words.next()?, words.next()?))
+        })
+        .collect()
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+
+    for (name, url) in corpus() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), url, |b, url| {
+            b.iter(|| parse_url(black_box(url.to_string())))
+        });
+    }
+
+    group.finish();
+}
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+
+    for (name, url) in corpus() {
+        let puzzle = parse_url(url.to_string()).unwrap();
+
+        for encoding in [Encoding::Log, Encoding::Direct].iter() {
+            let config = SolverConfig { encoding: *encoding, ..SolverConfig::default() };
+
+            group.bench_with_input(BenchmarkId::new(format!("{:?}", encoding), name), &puzzle, |b, puzzle| {
+                b.iter(|| encode_formula(black_box(puzzle), &config).unwrap())
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn solve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solve");
+
+    group.sample_size(20);
+
+    for (name, url) in corpus() {
+        let puzzle = parse_url(url.to_string()).unwrap();
+
+        for encoding in [Encoding::Log, Encoding::Direct].iter() {
+            let config = SolverConfig { encoding: *encoding, ..SolverConfig::default() };
+
+            group.bench_with_input(BenchmarkId::new(format!("{:?}", encoding), name), &puzzle, |b, puzzle| {
+                b.iter(|| solve_numberlink(black_box(puzzle), &config).unwrap())
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, parse, encode, solve);
+criterion_main!(benches);
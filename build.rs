@@ -0,0 +1,37 @@
+fn main() {
+    // Node.js のアドオンのリンクの設定
+    #[cfg(feature = "node")]
+    napi_build::setup();
+
+    // C のヘッダ (設定は cbindgen.toml)
+    #[cfg(feature = "capi")]
+    {
+        let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+
+        println!("cargo:rerun-if-changed=src/capi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", dir)).unwrap();
+
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(format!("{}/src/capi.rs", dir))
+            .generate()
+            .expect("cannot generate the C header")
+            .write_to_file(format!("{}/include/numberlink_solver.h", dir));
+    }
+
+    // gRPC のメッセージ, サービスとクライアント (OUT_DIR に numberlink.rs を書き, grpc.rs が include する)
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/numberlink.proto");
+
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().unwrap());
+
+        // crate は edition 2018 なので, TryInto を使う connect は作らない (Endpoint から Channel を作って new に渡す)
+        tonic_prost_build::configure()
+            .build_transport(false)
+            .compile_protos(&["proto/numberlink.proto"], &["proto"])
+            .expect("cannot compile proto/numberlink.proto");
+    }
+}
@@ -0,0 +1,38 @@
+/* cargo build --release --features capi
+ * cc examples/c/solve.c -Iinclude -Ltarget/release -lnumberlink_solver -o solve
+ * LD_LIBRARY_PATH=target/release ./solve 'https://puzz.link/p?numlin/...' */
+#include <stdio.h>
+
+#include "numberlink_solver.h"
+
+int main(int argc, char **argv) {
+    if (argc < 2) {
+        fprintf(stderr, "usage: %s PUZZLE\n", argv[0]);
+        return 2;
+    }
+
+    NumberlinkSolution *solution = numberlink_solve(argv[1], 0);
+
+    if (solution->status != NUMBERLINK_STATUS_SOLVED) {
+        fprintf(stderr, "%s\n", solution->message);
+        numberlink_solution_free(solution);
+        return 1;
+    }
+
+    for (size_t i = 0; i < solution->height; i++) {
+        for (size_t j = 0; j < solution->width; j++) {
+            printf(j == 0 ? "%zu" : " %zu", solution->cells[i*solution->width+j]);
+        }
+
+        printf("\n");
+    }
+
+    numberlink_solution_free(solution);
+
+    char *json = numberlink_solve_json(argv[1], 1000);
+
+    printf("%s\n", json);
+    numberlink_string_free(json);
+
+    return 0;
+}
@@ -0,0 +1,76 @@
+#ifndef NUMBERLINK_SOLVER_H
+#define NUMBERLINK_SOLVER_H
+
+/* cbindgen が src/capi.rs から作るファイル. 手で書き直さない */
+
+#include <stddef.h>
+#include <stdint.h>
+
+typedef enum NumberlinkStatus {
+  NUMBERLINK_STATUS_SOLVED = 0,
+  /**
+   * NULL か, UTF-8 でないか, 盤面として読めない
+   */
+  NUMBERLINK_STATUS_INVALID_INPUT = 1,
+  NUMBERLINK_STATUS_NO_SOLUTION = 2,
+  /**
+   * 時間切れ, 割り込みかメモリの上限
+   */
+  NUMBERLINK_STATUS_TIMEOUT = 3,
+  NUMBERLINK_STATUS_FAILED = 4,
+} NumberlinkStatus;
+
+typedef struct NumberlinkSolution {
+  enum NumberlinkStatus status;
+  size_t width;
+  size_t height;
+  /**
+   * 解けたとき, 行優先の width * height 個の, セルを通る線の数字 (穴は 0). ほかは NULL
+   */
+  size_t *cells;
+  /**
+   * 解けなかった理由 (解けたら NULL)
+   */
+  char *message;
+} NumberlinkSolution;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * 答えを solve --format json の 1 行と同じ JSON で返す (解けなければ {"error":"..."}). timeout_ms が 0 なら時間の制限なし.
+ * 返した文字列は numberlink_string_free で放す
+ *
+ * # Safety
+ * input は NULL か, NUL で終わる文字列
+ */
+char *numberlink_solve_json(const char *input,
+                            uint64_t timeout_ms);
+
+/**
+ * 解いた結果は status を見てから読む. 返したものは numberlink_solution_free で放す
+ *
+ * # Safety
+ * input は NULL か, NUL で終わる文字列
+ */
+struct NumberlinkSolution *numberlink_solve(const char *input,
+                                            uint64_t timeout_ms);
+
+/**
+ * # Safety
+ * solution は NULL か, numberlink_solve が返してまだ放していないもの
+ */
+void numberlink_solution_free(struct NumberlinkSolution *solution);
+
+/**
+ * # Safety
+ * s は NULL か, このライブラリが返してまだ放していない文字列
+ */
+void numberlink_string_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NUMBERLINK_SOLVER_H */
@@ -0,0 +1,207 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc as Shared;
+
+use varisat::config::SolverConfigUpdate;
+use varisat::solver::{Solver, SolverError};
+use varisat::{ExtendFormula, Lit, ProofFormat};
+
+use crate::monitor::{Control, Monitor};
+use crate::backtrack::SMALL_CELLS;
+use crate::predict::{predict, Difficulty};
+use crate::{sat_only, Error, Puzzle, SolverConfig, Tuning};
+
+pub use crate::monitor::Stop;
+
+#[cfg(feature = "cadical")]
+mod cadical;
+mod dimacs;
+#[cfg(not(target_arch = "wasm32"))]
+mod ipasir;
+#[cfg(feature = "minisat")]
+mod minisat;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backend {
+    Varisat,
+    // feature "cadical" / "minisat" を付けてビルドしたときだけ使える
+    Cadical,
+    Minisat,
+    // 実行時に読み込む IPASIR 互換の共有ライブラリ (kissat など)
+    Ipasir(PathBuf),
+    // DIMACS ファイルを最後の引数として渡して起動するコマンド (kissat -q など)
+    External(String),
+    // SAT を使わない深さ優先探索 (小さい盤面向け) と frontier 法の ZDD. どちらも solve_numberlink からだけ使える
+    Backtrack,
+    Zdd,
+    // 整数計画 (feature "ilp"). solve_numberlink と曲がる回数の最小化から使える
+    Ilp,
+    // 盤面ごとに select で選ぶ
+    Auto,
+}
+
+impl Backend {
+    // Session で使える (仮定や節の追加ができる) SAT ソルバか
+    pub fn is_sat(&self) -> bool {
+        !matches!(self, Backend::Backtrack | Backend::Zdd | Backend::Ilp)
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "varisat" => Ok(Backend::Varisat),
+            "cadical" => Ok(Backend::Cadical),
+            "minisat" => Ok(Backend::Minisat),
+            "backtrack" => Ok(Backend::Backtrack),
+            "zdd" => Ok(Backend::Zdd),
+            "ilp" => Ok(Backend::Ilp),
+            "auto" => Ok(Backend::Auto),
+            _ => Err(format!("unknown backend `{}` (expected auto, varisat, cadical, minisat, backtrack, zdd or ilp, or use --solver-lib or --solver-cmd)", s)),
+        }
+    }
+}
+
+// select に渡す, 何を求めるか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+    // 解を 1 つ (solve_numberlink)
+    Solve,
+    // Session から使う (仮定や節の追加がいる) ので SAT ソルバに限る
+    Session,
+    // 解を数える (count_numberlink)
+    Count,
+}
+
+// Backend::Auto の中身: 数えるなら ZDD, 小さい盤面は SAT を使わない探索, 難しそうな盤面は (この build にあれば) 速い cadical,
+// 残りは varisat. ZDD と探索は sat_only の規則や盤面を扱えないので, そのときは SAT ソルバから選ぶ
+// tuning を指定したときは (小さい盤面や数えるときも, 黙って無視しないよう) varisat のまま
+pub fn select(puzzle: &Puzzle, config: &SolverConfig, query: Query) -> Backend {
+    if config.tuning != Tuning::default() {
+        return Backend::Varisat;
+    }
+
+    let plain = sat_only(puzzle, config).is_none();
+
+    match query {
+        Query::Count if plain => return Backend::Zdd,
+        Query::Solve if plain && puzzle.cells.len() <= SMALL_CELLS => return Backend::Backtrack,
+        _ => {},
+    }
+
+    if cfg!(feature = "cadical") && predict(puzzle).difficulty >= Difficulty::Medium {
+        return Backend::Cadical;
+    }
+
+    Backend::Varisat
+}
+
+// config.backend のソルバを用意する (tuning は varisat のものなので, 他の backend では指定できない)
+pub(crate) fn open(puzzle: &Puzzle, config: &SolverConfig, control: &Shared<Control>) -> Result<Box<dyn SatBackend>, Error> {
+    let backend = match &config.backend {
+        Backend::Auto => select(puzzle, config, Query::Session),
+        backend => backend.clone(),
+    };
+
+    connect(&backend, config, control)
+}
+
+// Auto 以外の backend のソルバを用意する (盤面によらないので, Numberlink 以外のパズルからも使う)
+pub(crate) fn connect(backend: &Backend, config: &SolverConfig, control: &Shared<Control>) -> Result<Box<dyn SatBackend>, Error> {
+    if *backend != Backend::Varisat && config.tuning != Tuning::default() {
+        return Err(Error::InvalidTuning("the tuning options only apply to the varisat backend".to_string()));
+    }
+
+    match backend {
+        Backend::Varisat => Ok(Box::new(Varisat::new(config, control)?)),
+        #[cfg(feature = "cadical")]
+        Backend::Cadical => Ok(Box::new(cadical::Cadical::new(control))),
+        #[cfg(not(feature = "cadical"))]
+        Backend::Cadical => Err(Error::UnsupportedBackend("cadical".to_string())),
+        #[cfg(feature = "minisat")]
+        Backend::Minisat => Ok(Box::new(minisat::Minisat::new(control))),
+        #[cfg(not(feature = "minisat"))]
+        Backend::Minisat => Err(Error::UnsupportedBackend("minisat".to_string())),
+        #[cfg(not(target_arch = "wasm32"))]
+        Backend::Ipasir(path) => Ok(Box::new(ipasir::Ipasir::new(path, control)?)),
+        #[cfg(target_arch = "wasm32")]
+        Backend::Ipasir(_) => Err(Error::BackendLoad("shared libraries cannot be loaded on wasm32".to_string())),
+        Backend::External(command) => Ok(Box::new(dimacs::External::new(command, control))),
+        Backend::Backtrack | Backend::Zdd | Backend::Ilp => Err(Error::BackendFailed(format!("the {:?} backend only solves whole puzzles (no pins, regions or optimization)", backend).to_lowercase())),
+        Backend::Auto => unreachable!(),
+    }
+}
+
+// Session から SAT ソルバを使うための操作 (変数の番号は encode が決めるので, 節に出てきた番号をそのまま使う)
+pub trait SatBackend {
+    fn add_clause(&mut self, lits: &[Lit]);
+    // 次の solve 1 回だけに効く仮定
+    fn assume(&mut self, lits: &[Lit]);
+    // 打ち切られたときはその理由 (以降この backend は使わない)
+    fn solve(&mut self) -> Result<bool, Stop>;
+    // 直前の solve が SAT のときの割り当て (変数 i のリテラルが i 番目)
+    fn model(&self) -> Option<Vec<Lit>>;
+
+    // solve が Stop::Failed を返したときの理由
+    fn failure(&self) -> Option<String> {
+        None
+    }
+
+    // 直前の solve が仮定のもとで UNSAT のとき, 矛盾に使った仮定 (最小とは限らない. 分からなければ None)
+    fn failed(&self) -> Option<Vec<Lit>> {
+        None
+    }
+}
+
+pub struct Varisat {
+    solver: Solver<'static>,
+}
+
+impl Varisat {
+    pub(crate) fn new(config: &SolverConfig, control: &Shared<Control>) -> Result<Varisat, Error> {
+        let mut solver = Solver::new();
+        let tuning = SolverConfigUpdate {
+            vsids_decay: config.tuning.vsids_decay,
+            clause_activity_decay: config.tuning.clause_activity_decay,
+            reduce_locals_interval: config.tuning.reduce_locals_interval,
+            reduce_mids_interval: config.tuning.reduce_mids_interval,
+            luby_restart_interval_scale: config.tuning.luby_restart_interval_scale,
+        };
+
+        solver.config(&tuning).map_err(|e| Error::InvalidTuning(e.to_string()))?;
+
+        // varisat には走っている探索を止める手段がないので, 期限と中断の要求は証明の書き出し先で見る
+        if config.timeout.is_some() || config.max_memory.is_some() || config.progress.is_some() || !control.interrupts.is_empty() || !config.events.is_empty() {
+            solver.write_proof(Monitor::new(control.clone()), ProofFormat::Varisat);
+        }
+
+        Ok(Varisat { solver })
+    }
+}
+
+impl SatBackend for Varisat {
+    fn add_clause(&mut self, lits: &[Lit]) {
+        self.solver.add_clause(lits);
+    }
+
+    fn assume(&mut self, lits: &[Lit]) {
+        self.solver.assume(lits);
+    }
+
+    fn solve(&mut self) -> Result<bool, Stop> {
+        self.solver.solve().map_err(|e| match e {
+            SolverError::ProofIoError { cause } => Stop::of(&cause).unwrap_or(Stop::Timeout),
+            _ => Stop::Timeout,
+        })
+    }
+
+    fn model(&self) -> Option<Vec<Lit>> {
+        self.solver.model()
+    }
+
+    fn failed(&self) -> Option<Vec<Lit>> {
+        self.solver.failed_core().map(|core| core.to_vec())
+    }
+}
@@ -0,0 +1,99 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc as Shared;
+
+use varisat::{Lit, Var};
+
+use super::{SatBackend, Stop};
+use crate::monitor::{formula_memory, Control};
+
+pub struct Cadical {
+    solver: ::cadical::Solver<Callbacks>,
+    assumptions: Vec<i32>,
+    // 直前の solve の仮定 (failed で使う)
+    assumed: Vec<i32>,
+    vars: usize,
+    control: Shared<Control>,
+}
+
+// 期限と中断は terminate で, conflict 数とメモリの見積もりは学習節の通知で数える
+// (削除は通知されないので, メモリの見積もりは実際より多めになる)
+pub struct Callbacks {
+    control: Shared<Control>,
+    stop: Option<Stop>,
+}
+
+impl ::cadical::Callbacks for Callbacks {
+    fn terminate(&mut self) -> bool {
+        self.stop = self.control.check();
+
+        self.stop.is_some()
+    }
+
+    fn max_length(&self) -> i32 {
+        i32::MAX
+    }
+
+    fn learn(&mut self, clause: &[i32]) {
+        self.control.add_conflicts(1);
+        self.control.memory.fetch_add(formula_memory(1, clause.len() as u64, 0), Ordering::Relaxed);
+    }
+}
+
+impl Cadical {
+    pub(crate) fn new(control: &Shared<Control>) -> Cadical {
+        let mut solver = ::cadical::Solver::new();
+
+        solver.set_callbacks(Some(Callbacks { control: control.clone(), stop: None }));
+
+        Cadical { solver, assumptions: vec![], assumed: vec![], vars: 0, control: control.clone() }
+    }
+
+    fn see(&mut self, lit: Lit) -> i32 {
+        self.vars = self.vars.max(lit.index()+1);
+
+        lit.to_dimacs() as i32
+    }
+}
+
+impl SatBackend for Cadical {
+    fn add_clause(&mut self, lits: &[Lit]) {
+        let lits: Vec<i32> = lits.iter().map(|&lit| self.see(lit)).collect();
+
+        self.solver.add_clause(lits);
+    }
+
+    fn assume(&mut self, lits: &[Lit]) {
+        self.assumptions = lits.iter().map(|&lit| self.see(lit)).collect();
+    }
+
+    fn solve(&mut self) -> Result<bool, Stop> {
+        self.assumed = std::mem::take(&mut self.assumptions);
+
+        let res = self.solver.solve_with(self.assumed.iter().cloned());
+
+        match res {
+            Some(sat) => Ok(sat),
+            None => Err(self.solver.get_callbacks().and_then(|cbs| cbs.stop.take()).or_else(|| self.control.check()).unwrap_or(Stop::Timeout)),
+        }
+    }
+
+    fn model(&self) -> Option<Vec<Lit>> {
+        if self.solver.status() != Some(true) {
+            return None;
+        }
+
+        Some((0..self.vars).map(|index| {
+            let x = Var::from_index(index);
+
+            Lit::from_var(x, self.solver.value(x.to_dimacs() as i32).unwrap_or(false))
+        }).collect())
+    }
+
+    fn failed(&self) -> Option<Vec<Lit>> {
+        if self.solver.status() != Some(false) {
+            return None;
+        }
+
+        Some(self.assumed.iter().filter(|&&lit| self.solver.failed(lit)).map(|&lit| Lit::from_dimacs(lit as isize)).collect())
+    }
+}
@@ -0,0 +1,194 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc as Shared;
+use std::thread;
+use std::time::Duration;
+
+use varisat::{Lit, Var};
+
+use super::{SatBackend, Stop};
+use crate::monitor::Control;
+
+// 一時ファイルの名前が被らないように
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// 期限と中断を確かめる間隔
+const POLL: Duration = Duration::from_millis(10);
+
+// 節をためておき, solve のたびに DIMACS (仮定は単位節にする) を書き出して外部のソルバを起動する
+// 競技会の形式 (s SATISFIABLE / s UNSATISFIABLE と v 行) の出力を読む
+pub struct External {
+    command: Vec<String>,
+    // 節ごとに 0 で区切る
+    lits: Vec<i32>,
+    clauses: usize,
+    vars: usize,
+    assumptions: Vec<i32>,
+    model: Option<Vec<Lit>>,
+    failure: Option<String>,
+    control: Shared<Control>,
+}
+
+impl External {
+    pub(crate) fn new(command: &str, control: &Shared<Control>) -> External {
+        External {
+            command: command.split_whitespace().map(String::from).collect(),
+            lits: vec![],
+            clauses: 0,
+            vars: 0,
+            assumptions: vec![],
+            model: None,
+            failure: None,
+            control: control.clone(),
+        }
+    }
+
+    fn see(&mut self, lit: Lit) -> i32 {
+        self.vars = self.vars.max(lit.index()+1);
+
+        lit.to_dimacs() as i32
+    }
+
+    fn write_dimacs(&self, path: &Path) -> io::Result<()> {
+        let mut out = BufWriter::new(fs::File::create(path)?);
+
+        writeln!(out, "p cnf {} {}", self.vars, self.clauses+self.assumptions.len())?;
+
+        for clause in self.lits.split(|&lit| lit == 0).take(self.clauses) {
+            for lit in clause {
+                write!(out, "{} ", lit)?;
+            }
+
+            writeln!(out, "0")?;
+        }
+
+        for lit in &self.assumptions {
+            writeln!(out, "{} 0", lit)?;
+        }
+
+        out.flush()
+    }
+
+    // 終わるか止めるべきになるまで待ち, 標準出力を返す
+    fn run(&self, path: &Path) -> Result<String, Result<Stop, String>> {
+        let (program, args) = self.command.split_first().ok_or_else(|| Err("empty solver command".to_string()))?;
+        let mut child = Command::new(program).args(args).arg(path).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::null())
+            .spawn().map_err(|e| Err(format!("{}: {}", program, e)))?;
+
+        let mut stdout = child.stdout.take().unwrap();
+        let reader = thread::spawn(move || {
+            let mut text = String::new();
+
+            stdout.read_to_string(&mut text).map(|_| text)
+        });
+
+        loop {
+            if let Some(stop) = self.control.check() {
+                let _ = child.kill();
+                let _ = child.wait();
+
+                return Err(Ok(stop));
+            }
+
+            match child.try_wait() {
+                Ok(Some(_)) => break,
+                Ok(None) => thread::sleep(POLL),
+                Err(e) => return Err(Err(e.to_string())),
+            }
+        }
+
+        reader.join().unwrap().map_err(|e| Err(e.to_string()))
+    }
+
+    fn parse(&self, text: &str) -> Result<Option<Vec<Lit>>, String> {
+        let mut sat = None;
+        let mut model: Vec<Lit> = (0..self.vars).map(|index| Var::from_index(index).negative()).collect();
+
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+
+            match words.next() {
+                Some("s") => sat = match words.next() {
+                    Some("SATISFIABLE") => Some(true),
+                    Some("UNSATISFIABLE") => Some(false),
+                    _ => return Err(format!("solver gave up: {}", line)),
+                },
+                Some("v") => {
+                    for word in words {
+                        let lit: isize = word.parse().map_err(|_| format!("invalid value line: {}", line))?;
+
+                        if lit > 0 && (lit as usize) <= self.vars {
+                            model[lit as usize-1] = Lit::from_dimacs(lit);
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        match sat {
+            Some(true) => Ok(Some(model)),
+            Some(false) => Ok(None),
+            None => Err(format!("`{}` printed no `s` line", self.command.join(" "))),
+        }
+    }
+}
+
+impl SatBackend for External {
+    fn add_clause(&mut self, lits: &[Lit]) {
+        for &lit in lits {
+            let lit = self.see(lit);
+
+            self.lits.push(lit);
+        }
+
+        self.lits.push(0);
+        self.clauses += 1;
+    }
+
+    fn assume(&mut self, lits: &[Lit]) {
+        self.assumptions = lits.iter().map(|&lit| self.see(lit)).collect();
+    }
+
+    fn solve(&mut self) -> Result<bool, Stop> {
+        let name = format!("numberlink-{}-{}.cnf", process::id(), COUNTER.fetch_add(1, Ordering::Relaxed));
+        let path: PathBuf = env::temp_dir().join(name);
+
+        let res = self.write_dimacs(&path).map_err(|e| Err(e.to_string())).and_then(|_| self.run(&path));
+        let _ = fs::remove_file(&path);
+
+        self.assumptions.clear();
+        self.model = None;
+
+        let parsed = match res {
+            Ok(text) => self.parse(&text),
+            Err(Ok(stop)) => return Err(stop),
+            Err(Err(msg)) => Err(msg),
+        };
+
+        match parsed {
+            Ok(model) => {
+                self.model = model;
+
+                Ok(self.model.is_some())
+            },
+            Err(msg) => {
+                self.failure = Some(msg);
+
+                Err(Stop::Failed)
+            },
+        }
+    }
+
+    fn model(&self) -> Option<Vec<Lit>> {
+        self.model.clone()
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.failure.clone()
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::os::raw::{c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::sync::Arc as Shared;
+use std::sync::{Mutex, OnceLock};
+
+use libloading::Library;
+use varisat::{Lit, Var};
+
+use super::{SatBackend, Stop};
+use crate::monitor::Control;
+use crate::Error;
+
+type Terminate = extern "C" fn(*mut c_void) -> c_int;
+
+// IPASIR の関数 (Library を持っている間だけ有効)
+struct Api {
+    _lib: Library,
+    init: unsafe extern "C" fn() -> *mut c_void,
+    release: unsafe extern "C" fn(*mut c_void),
+    add: unsafe extern "C" fn(*mut c_void, c_int),
+    assume: unsafe extern "C" fn(*mut c_void, c_int),
+    solve: unsafe extern "C" fn(*mut c_void) -> c_int,
+    val: unsafe extern "C" fn(*mut c_void, c_int) -> c_int,
+    set_terminate: unsafe extern "C" fn(*mut c_void, *mut c_void, Option<Terminate>),
+}
+
+// 同じライブラリを Session ごとに開き直さない
+static LIBRARIES: OnceLock<Mutex<HashMap<PathBuf, Shared<Api>>>> = OnceLock::new();
+
+const SAT: c_int = 10;
+const UNSAT: c_int = 20;
+
+// 期限と中断だけを terminate で確かめる (ipasir_set_learn は対応していないソルバがあるので conflict 数は数えない)
+pub struct Ipasir {
+    api: Shared<Api>,
+    solver: *mut c_void,
+    vars: usize,
+    sat: bool,
+    control: Shared<Control>,
+}
+
+impl Ipasir {
+    pub(crate) fn new(path: &Path, control: &Shared<Control>) -> Result<Ipasir, Error> {
+        let api = load(path).map_err(|e| Error::BackendLoad(e.to_string()))?;
+        let solver = unsafe { (api.init)() };
+
+        unsafe { (api.set_terminate)(solver, Shared::as_ptr(control) as *mut c_void, Some(terminate)) };
+
+        Ok(Ipasir { api, solver, vars: 0, sat: false, control: control.clone() })
+    }
+
+    fn see(&mut self, lit: Lit) -> c_int {
+        self.vars = self.vars.max(lit.index()+1);
+
+        lit.to_dimacs() as c_int
+    }
+}
+
+extern "C" fn terminate(data: *mut c_void) -> c_int {
+    let control = unsafe { &*(data as *const Control) };
+
+    control.check().is_some() as c_int
+}
+
+fn load(path: &Path) -> Result<Shared<Api>, libloading::Error> {
+    let libraries = LIBRARIES.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut libraries = libraries.lock().unwrap();
+
+    if let Some(api) = libraries.get(path) {
+        return Ok(api.clone());
+    }
+
+    let api = unsafe {
+        let lib = Library::new(path)?;
+
+        Api {
+            init: *lib.get(b"ipasir_init\0")?,
+            release: *lib.get(b"ipasir_release\0")?,
+            add: *lib.get(b"ipasir_add\0")?,
+            assume: *lib.get(b"ipasir_assume\0")?,
+            solve: *lib.get(b"ipasir_solve\0")?,
+            val: *lib.get(b"ipasir_val\0")?,
+            set_terminate: *lib.get(b"ipasir_set_terminate\0")?,
+            _lib: lib,
+        }
+    };
+
+    Ok(libraries.entry(path.to_path_buf()).or_insert(Shared::new(api)).clone())
+}
+
+impl SatBackend for Ipasir {
+    fn add_clause(&mut self, lits: &[Lit]) {
+        for &lit in lits {
+            let lit = self.see(lit);
+
+            unsafe { (self.api.add)(self.solver, lit) };
+        }
+
+        unsafe { (self.api.add)(self.solver, 0) };
+    }
+
+    fn assume(&mut self, lits: &[Lit]) {
+        for &lit in lits {
+            let lit = self.see(lit);
+
+            unsafe { (self.api.assume)(self.solver, lit) };
+        }
+    }
+
+    fn solve(&mut self) -> Result<bool, Stop> {
+        let res = unsafe { (self.api.solve)(self.solver) };
+
+        self.sat = res == SAT;
+
+        match res {
+            SAT => Ok(true),
+            UNSAT => Ok(false),
+            _ => Err(self.control.check().unwrap_or(Stop::Timeout)),
+        }
+    }
+
+    fn model(&self) -> Option<Vec<Lit>> {
+        if !self.sat {
+            return None;
+        }
+
+        Some((0..self.vars).map(|index| {
+            let x = Var::from_index(index);
+
+            Lit::from_var(x, unsafe { (self.api.val)(self.solver, x.to_dimacs() as c_int) } > 0)
+        }).collect())
+    }
+}
+
+impl Drop for Ipasir {
+    fn drop(&mut self) {
+        unsafe { (self.api.release)(self.solver) };
+    }
+}
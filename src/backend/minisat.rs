@@ -0,0 +1,96 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc as Shared;
+
+use batsat::{lbool, ClauseKind, SolverInterface as _, SolverOpts};
+use varisat::{Lit, Var};
+
+use super::{SatBackend, Stop};
+use crate::events::Event;
+use crate::monitor::{formula_memory, Control};
+
+// MiniSat の Rust への移植 (batsat) を使う. C++ の MiniSat のバインディングは bindgen (libclang) が要るので使わない
+pub struct Minisat {
+    solver: batsat::Solver<Callbacks>,
+    assumptions: Vec<batsat::Lit>,
+    buf: Vec<batsat::Lit>,
+    sat: bool,
+    control: Shared<Control>,
+}
+
+pub struct Callbacks {
+    control: Shared<Control>,
+    restarts: u64,
+}
+
+impl batsat::Callbacks for Callbacks {
+    fn on_new_clause(&mut self, c: &[batsat::Lit], kind: ClauseKind) {
+        if let ClauseKind::Learnt = kind {
+            self.control.add_conflicts(1);
+            self.control.memory.fetch_add(formula_memory(1, c.len() as u64, 0), Ordering::Relaxed);
+        }
+    }
+
+    fn on_restart(&mut self) {
+        self.restarts += 1;
+        self.control.events.publish(&Event::Restart { restarts: self.restarts });
+    }
+
+    fn on_delete_clause(&mut self, c: &[batsat::Lit]) {
+        let bytes = formula_memory(1, c.len() as u64, 0);
+        let _ = self.control.memory.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |memory| Some(memory.saturating_sub(bytes)));
+    }
+
+    fn stop(&self) -> bool {
+        self.control.check().is_some()
+    }
+}
+
+impl Minisat {
+    pub(crate) fn new(control: &Shared<Control>) -> Minisat {
+        let solver = batsat::Solver::new(SolverOpts::default(), Callbacks { control: control.clone(), restarts: 0 });
+
+        Minisat { solver, assumptions: vec![], buf: vec![], sat: false, control: control.clone() }
+    }
+
+    fn lit(&mut self, lit: Lit) -> batsat::Lit {
+        let x = self.solver.var_of_int(lit.index() as u32);
+
+        batsat::Lit::new(x, lit.is_positive())
+    }
+}
+
+impl SatBackend for Minisat {
+    fn add_clause(&mut self, lits: &[Lit]) {
+        let mut buf = std::mem::take(&mut self.buf);
+
+        buf.clear();
+        buf.extend(lits.iter().map(|&lit| self.lit(lit)));
+        self.solver.add_clause_reuse(&mut buf);
+        self.buf = buf;
+    }
+
+    fn assume(&mut self, lits: &[Lit]) {
+        self.assumptions = lits.iter().map(|&lit| self.lit(lit)).collect();
+    }
+
+    fn solve(&mut self) -> Result<bool, Stop> {
+        let assumptions = std::mem::take(&mut self.assumptions);
+        let res = self.solver.solve_limited(&assumptions);
+
+        self.sat = res == lbool::TRUE;
+
+        if res == lbool::UNDEF {
+            return Err(self.control.check().unwrap_or(Stop::Timeout));
+        }
+
+        Ok(self.sat)
+    }
+
+    fn model(&self) -> Option<Vec<Lit>> {
+        if !self.sat {
+            return None;
+        }
+
+        Some(self.solver.get_model().iter().enumerate().map(|(index, &value)| Lit::from_var(Var::from_index(index), value == lbool::TRUE)).collect())
+    }
+}
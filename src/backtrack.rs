@@ -0,0 +1,177 @@
+use std::sync::atomic::Ordering;
+
+use crate::{gen_adjs, parse_field, sat_only, Error, Instant, Puzzle, Role, Sol, SolverConfig, Stats};
+
+// 期限と中断を確かめる間隔 (探索したノード数)
+const CHECK_NODES: u64 = 1 << 12;
+
+const NONE: usize = usize::MAX;
+
+// これ以下のセル数なら手がかりの置き方によらずすぐ終わる目安 (compare が検算に使う)
+pub const SMALL_CELLS: usize = 36;
+
+// SAT を使わない深さ優先探索. セルを行優先で見て出ていく arc を 1 本ずつ決める
+// 符号化と同じ条件 (空白の閉路も許す) で解くので, 小さい盤面では SAT の結果の検算に使える
+pub fn solve_backtrack(puzzle: &Puzzle, config: &SolverConfig) -> Result<Sol, Error> {
+    if puzzle.width == 0 || puzzle.height == 0 || puzzle.cells.len() != puzzle.width*puzzle.height {
+        return Err(Error::InvalidField);
+    }
+
+    if let Some(feature) = sat_only(puzzle, config) {
+        return Err(Error::BackendFailed(format!("the backtrack backend does not support {}", feature)));
+    }
+
+    let roles = parse_field(puzzle).ok_or(Error::InvalidField)?;
+    let adjs = gen_adjs(puzzle);
+    let n = puzzle.cells.len();
+
+    // セル w の入次数は, w と隣のセルのうち最後に出る arc を決めるものを決めた時点で確定する
+    let mut checks = vec![vec![]; n];
+
+    for (w, adj) in adjs.iter().enumerate() {
+        let last = adj.iter().map(|&(v, _)| v).fold(w, usize::max);
+
+        checks[last].push(w);
+    }
+
+    let mut search = Search {
+        puzzle,
+        config,
+        roles: &roles,
+        adjs: &adjs,
+        checks: &checks,
+        out: vec![NONE; n],
+        indeg: vec![0; n],
+        parent: (0..n).collect(),
+        label: puzzle.cells.clone(),
+        history: vec![],
+        start: Instant::now(),
+        nodes: 0,
+    };
+
+    match search.dfs(0) {
+        Ok(true) => Ok((0..n).filter(|&u| search.out[u] != NONE).map(|u| (puzzle.pos(u), puzzle.pos(search.out[u]))).collect()),
+        Ok(false) => Err(Error::NoSolution),
+        Err(e) => Err(e),
+    }
+}
+
+struct Search<'a> {
+    puzzle: &'a Puzzle,
+    config: &'a SolverConfig,
+    roles: &'a [Role],
+    adjs: &'a [Vec<(usize, usize)>],
+    checks: &'a [Vec<usize>],
+    out: Vec<usize>,
+    indeg: Vec<u8>,
+    // arc でつながったセルの union-find (戻せるように経路圧縮はしない) と, 各代表の数字 (0 は未定)
+    parent: Vec<usize>,
+    label: Vec<usize>,
+    history: Vec<(usize, usize)>,
+    start: Instant,
+    nodes: u64,
+}
+
+impl Search<'_> {
+    fn dfs(&mut self, u: usize) -> Result<bool, Error> {
+        if u == self.puzzle.cells.len() {
+            return Ok(true);
+        }
+
+        self.nodes += 1;
+
+        if self.nodes.is_multiple_of(CHECK_NODES) {
+            self.check()?;
+        }
+
+        if self.roles[u].degrees().0 == 0 {
+            return self.next(u);
+        }
+
+        let adjs = self.adjs;
+
+        for &(v, _) in &adjs[u] {
+            // (2) と, 始点には入らないこと
+            if self.indeg[v] > 0 || self.roles[v] == Role::Start || self.out[v] == u {
+                continue;
+            }
+
+            let mark = self.history.len();
+
+            if !self.union(u, v) {
+                continue;
+            }
+
+            self.out[u] = v;
+            self.indeg[v] += 1;
+
+            if self.next(u)? {
+                return Ok(true);
+            }
+
+            self.out[u] = NONE;
+            self.indeg[v] -= 1;
+            self.undo(mark);
+        }
+
+        Ok(false)
+    }
+
+    // 入次数が確定したセルを確かめてから次のセルへ
+    fn next(&mut self, u: usize) -> Result<bool, Error> {
+        let ok = self.checks[u].iter().all(|&w| self.indeg[w] as usize == self.roles[w].degrees().1);
+
+        Ok(ok && self.dfs(u+1)?)
+    }
+
+    fn find(&self, mut u: usize) -> usize {
+        while self.parent[u] != u {
+            u = self.parent[u];
+        }
+
+        u
+    }
+
+    // 違う数字どうしをつなぐなら false
+    fn union(&mut self, u: usize, v: usize) -> bool {
+        let (a, b) = (self.find(u), self.find(v));
+
+        if a == b {
+            return true;
+        }
+
+        if self.label[a] != 0 && self.label[b] != 0 && self.label[a] != self.label[b] {
+            return false;
+        }
+
+        self.history.push((b, self.label[a]));
+        self.parent[b] = a;
+        self.label[a] = self.label[a].max(self.label[b]);
+
+        true
+    }
+
+    fn undo(&mut self, mark: usize) {
+        while self.history.len() > mark {
+            let (b, label) = self.history.pop().unwrap();
+            let a = self.parent[b];
+
+            self.parent[b] = b;
+            self.label[a] = label;
+        }
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        let stats = || Stats { elapsed: self.start.elapsed(), ..Stats::default() };
+
+        if self.config.interrupt.as_ref().is_some_and(|interrupt| interrupt.load(Ordering::Relaxed)) {
+            return Err(Error::Interrupted(stats()));
+        }
+
+        if self.config.timeout.is_some_and(|timeout| self.start.elapsed() >= timeout) {
+            return Err(Error::Timeout(stats()));
+        }
+
+        Ok(())
+    }
+}
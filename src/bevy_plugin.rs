@@ -0,0 +1,196 @@
+use std::io;
+
+use bevy::app::{App, Plugin, Update};
+use bevy::asset::io::Reader;
+use bevy::asset::{Asset, AssetApp, AssetId, AssetLoader, AssetServer, Assets, LoadContext, LoadState};
+use bevy::ecs::message::{Message, MessageReader, MessageWriter};
+use bevy::ecs::resource::Resource;
+use bevy::ecs::schedule::IntoScheduleConfigs;
+use bevy::ecs::system::{Res, ResMut};
+use bevy::reflect::TypePath;
+use bevy::tasks::{block_on, poll_once, AsyncComputeTaskPool, Task};
+
+use crate::hint::{hint_with, Hint, PartialSolution};
+use crate::render::paths;
+use crate::{parse_puzzle, solve_numberlink, Error, Puzzle, SolverConfig, P};
+
+// ゲームに組み込むためのプラグイン. 盤面を Level のアセットとして読み込み,
+// SolveLevel と HintLevel のメッセージを受けると裏で解いて LevelSolved と LevelHinted で返す
+//
+//     App::new().add_plugins((MinimalPlugins, AssetPlugin::default(), NumberlinkPlugin::default()))
+#[derive(Default, Clone)]
+pub struct NumberlinkPlugin {
+    pub config: SolverConfig,
+}
+
+// 盤面のアセット. .numlin のファイルに parse_puzzle が読める形 (URL, テキスト, JSON) で書く
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct Level {
+    pub puzzle: Puzzle,
+}
+
+#[derive(Default, TypePath)]
+pub struct LevelLoader;
+
+// 盤面の答えを求める
+#[derive(Message, Debug, Clone)]
+pub struct SolveLevel {
+    pub level: AssetId<Level>,
+}
+
+// プレイヤーが描いた道 (セル列) から次に決まる 1 本を求める
+#[derive(Message, Debug, Clone)]
+pub struct HintLevel {
+    pub level: AssetId<Level>,
+    pub drawn: Vec<Vec<P>>,
+}
+
+// 数字ごとの道 (render::paths と同じ). 点の盤面は結んだ 2 点に同じ数字を書いた盤面の道
+#[derive(Message, Debug, Clone)]
+pub struct LevelSolved {
+    pub level: AssetId<Level>,
+    pub paths: Result<Vec<(usize, Vec<P>)>, Error>,
+}
+
+// もう決められる辺がなければ Ok(None) (hint::hint_with と同じ)
+#[derive(Message, Debug, Clone)]
+pub struct LevelHinted {
+    pub level: AssetId<Level>,
+    pub hint: Result<Option<Hint>, Error>,
+}
+
+// 読み込みを待っている求めと, 解いている最中の仕事
+#[derive(Resource)]
+struct Solver {
+    config: SolverConfig,
+    waiting: Vec<Request>,
+    tasks: Vec<Task<Answer>>,
+}
+
+enum Request {
+    Solve(SolveLevel),
+    Hint(HintLevel),
+}
+
+enum Answer {
+    Solved(LevelSolved),
+    Hinted(LevelHinted),
+}
+
+impl Plugin for NumberlinkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .add_message::<SolveLevel>()
+            .add_message::<HintLevel>()
+            .add_message::<LevelSolved>()
+            .add_message::<LevelHinted>()
+            .insert_resource(Solver { config: self.config.clone(), waiting: vec![], tasks: vec![] })
+            .add_systems(Update, (start, finish).chain());
+    }
+}
+
+impl AssetLoader for LevelLoader {
+    type Asset = Level;
+    type Settings = ();
+    type Error = io::Error;
+
+    async fn load(&self, reader: &mut dyn Reader, _: &(), _: &mut LoadContext<'_>) -> Result<Level, io::Error> {
+        let mut bytes = vec![];
+
+        reader.read_to_end(&mut bytes).await?;
+
+        let text = String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let puzzle = parse_puzzle(text.trim()).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, Error::InvalidUrl))?;
+
+        Ok(Level { puzzle })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["numlin"]
+    }
+}
+
+impl Request {
+    fn level(&self) -> AssetId<Level> {
+        match self {
+            Request::Solve(request) => request.level,
+            Request::Hint(request) => request.level,
+        }
+    }
+
+    // 読み込めなかった盤面は InvalidUrl
+    fn fail(self) -> Answer {
+        match self {
+            Request::Solve(SolveLevel { level }) => Answer::Solved(LevelSolved { level, paths: Err(Error::InvalidUrl) }),
+            Request::Hint(HintLevel { level, .. }) => Answer::Hinted(LevelHinted { level, hint: Err(Error::InvalidUrl) }),
+        }
+    }
+}
+
+// 読み込み済みの盤面の求めを計算用のスレッドに回す. まだ読み込み中なら次のフレームまで待つ
+fn start(
+    mut solver: ResMut<Solver>,
+    levels: Res<Assets<Level>>,
+    server: Res<AssetServer>,
+    mut solves: MessageReader<SolveLevel>,
+    mut hints: MessageReader<HintLevel>,
+    mut solved: MessageWriter<LevelSolved>,
+    mut hinted: MessageWriter<LevelHinted>,
+) {
+    let requests: Vec<Request> = solver.waiting.drain(..)
+        .chain(solves.read().cloned().map(Request::Solve))
+        .chain(hints.read().cloned().map(Request::Hint))
+        .collect();
+    let pool = AsyncComputeTaskPool::get();
+
+    for request in requests {
+        let puzzle = match levels.get(request.level()) {
+            Some(level) => level.puzzle.clone(),
+            None => {
+                match server.get_load_state(request.level()) {
+                    Some(LoadState::Failed(_)) | None => match request.fail() {
+                        Answer::Solved(answer) => { solved.write(answer); },
+                        Answer::Hinted(answer) => { hinted.write(answer); },
+                    },
+                    Some(_) => solver.waiting.push(request),
+                }
+
+                continue;
+            },
+        };
+        let config = solver.config.clone();
+
+        solver.tasks.push(pool.spawn(async move {
+            match request {
+                Request::Solve(SolveLevel { level }) => {
+                    let paths = solve_numberlink(&puzzle, &config).map(|sol| match puzzle.dots {
+                        true => paths(&puzzle.paired(&sol), &sol),
+                        false => paths(&puzzle, &sol),
+                    });
+
+                    Answer::Solved(LevelSolved { level, paths })
+                },
+                Request::Hint(HintLevel { level, drawn }) => {
+                    let hint = hint_with(&puzzle, &PartialSolution::from_paths(&drawn), &config);
+
+                    Answer::Hinted(LevelHinted { level, hint })
+                },
+            }
+        }));
+    }
+}
+
+fn finish(mut solver: ResMut<Solver>, mut solved: MessageWriter<LevelSolved>, mut hinted: MessageWriter<LevelHinted>) {
+    solver.tasks.retain_mut(|task| match block_on(poll_once(task)) {
+        Some(Answer::Solved(answer)) => {
+            solved.write(answer);
+            false
+        },
+        Some(Answer::Hinted(answer)) => {
+            hinted.write(answer);
+            false
+        },
+        None => true,
+    });
+}
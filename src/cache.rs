@@ -0,0 +1,70 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::canonical::{canonical_hash, canonical_with, fnv1a};
+use crate::transform::transform_sol;
+use crate::{Error, Puzzle, RuleSet, Sol};
+
+// 解いた答えをディスクに置いておき, 回転, 裏返しと数字の振り直しで移り合う盤面には解かずに同じ答えを返す.
+// 少し増えた盤面集を解き直すとき, 新しい盤面だけを解けばよい. 答えは代表の盤面の上で持つ
+//
+// ファイルは 1 問に 1 つ (DIR/代表の鍵-規則の鍵.json). 規則 (--coverage など) が違えば別の答え.
+// 解けないことも覚えるが, 時間切れなどは覚えない
+// 書きかけのファイルの名前 (同じプロセスの中でも重ならないように)
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+pub struct ResultCache {
+    dir: PathBuf,
+    rules: u64,
+}
+
+impl ResultCache {
+    // なければ作る
+    pub fn open(dir: &Path, rules: &RuleSet) -> Result<ResultCache, String> {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+        Ok(ResultCache { dir: dir.to_path_buf(), rules: fnv1a(&format!("{:?}", rules)) })
+    }
+
+    fn path(&self, puzzle: &Puzzle) -> PathBuf {
+        self.dir.join(format!("{}-{:016x}.json", canonical_hash(puzzle), self.rules))
+    }
+
+    // 覚えている答えを puzzle の向きに直したもの (解けないと覚えていれば NoSolution). 読めないファイルはないものとする
+    pub fn get(&self, puzzle: &Puzzle) -> Option<Result<Sol, Error>> {
+        let text = fs::read_to_string(self.path(puzzle)).ok()?;
+        let stored: Option<Sol> = serde_json::from_str(&text).ok()?;
+        let (canonical, t) = canonical_with(puzzle);
+
+        Some(match stored {
+            Some(sol) => {
+                let mut sol = transform_sol(&canonical, &sol, t.inverse());
+
+                // 符号化の変数の順 (セルの番号, 向き) に並べる
+                sol.sort_by_key(|&(p, q)| (puzzle.index(p), puzzle.direction(p, q)));
+
+                Ok(sol)
+            },
+            None => Err(Error::NoSolution),
+        })
+    }
+
+    // 答えか解けないことを覚える (ほかの誤りは何もしない). 並べて解いても壊れないよう, 別の名前で書いてから置き換える
+    pub fn put(&self, puzzle: &Puzzle, res: &Result<Sol, Error>) -> Result<(), String> {
+        let stored = match res {
+            Ok(sol) => {
+                let (_, t) = canonical_with(puzzle);
+
+                Some(transform_sol(puzzle, sol, t))
+            },
+            Err(Error::NoSolution) => None,
+            Err(_) => return Ok(()),
+        };
+        let path = self.path(puzzle);
+        let tmp = path.with_extension(format!("{}-{}.tmp", process::id(), WRITES.fetch_add(1, Ordering::SeqCst)));
+
+        fs::write(&tmp, serde_json::to_string(&stored).unwrap()).and_then(|_| fs::rename(&tmp, &path)).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
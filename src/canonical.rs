@@ -0,0 +1,47 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::render::render_puzzle_json;
+use crate::transform::{renumber, transform, Transform};
+use crate::Puzzle;
+
+// 回転と裏返し (8 通り) と数字の振り直しで移り合う盤面に共通の代表
+// 数字は行優先に現れる順に 1, 2, ... と振り直し, 8 通りのうち一番小さいもの (幅, 高さ, セルの並び, 壁, ... の辞書順) を選ぶ
+// 向きを変えると意味の変わる盤面 (ワープ, 層, 六角, グラフ, 一方通行) は数字の振り直しだけ
+pub fn canonical(puzzle: &Puzzle) -> Puzzle {
+    canonical_with(puzzle).0
+}
+
+// canonical と, 盤面をそれに移す変換 (解は transform_sol で移せる. 数字の振り直しは解を変えない)
+pub fn canonical_with(puzzle: &Puzzle) -> (Puzzle, Transform) {
+    Transform::ALL.iter().filter_map(|&t| transform(puzzle, t).map(|res| (renumber(&res), t))).min_by(|a, b| compare(&a.0, &b.0)).unwrap()
+}
+
+// canonical の JSON の FNV-1a (64 bit, 16 進 16 桁). 版や環境によらないので, 盤面をファイルやデータベースに
+// 書き留めるときの鍵に使う
+pub fn canonical_hash(puzzle: &Puzzle) -> String {
+    format!("{:016x}", fnv1a(&render_puzzle_json(&canonical(puzzle))))
+}
+
+pub(crate) fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+// 前に同じ代表の盤面がないものの番号 (元の順)
+pub fn dedup(puzzles: &[Puzzle]) -> Vec<usize> {
+    let mut first = HashMap::new();
+
+    (0..puzzles.len()).filter(|&k| *first.entry(canonical(&puzzles[k])).or_insert(k) == k).collect()
+}
+
+fn compare(a: &Puzzle, b: &Puzzle) -> Ordering {
+    (a.width, a.height).cmp(&(b.width, b.height))
+        .then_with(|| a.cells.cmp(&b.cells))
+        .then_with(|| a.walls.cmp(&b.walls))
+        .then_with(|| a.holes.cmp(&b.holes))
+        .then_with(|| a.bridges.cmp(&b.bridges))
+        .then_with(|| a.outside.cmp(&b.outside))
+        .then_with(|| a.waypoints.cmp(&b.waypoints))
+        .then_with(|| a.limits.cmp(&b.limits))
+        .then_with(|| a.costs.cmp(&b.costs))
+}
@@ -0,0 +1,127 @@
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+use std::time::Duration;
+
+use crate::render::{label_cells, render_error_line, render_line, Format};
+use crate::{parse_puzzle, solve_numberlink, Error, Puzzle, Sol, SolverConfig};
+
+// C から呼ぶ関数 (ヘッダは cbindgen が include/numberlink_solver.h に書く). このファイルの /// はヘッダのコメントになる
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberlinkStatus {
+    Solved = 0,
+    /// NULL か, UTF-8 でないか, 盤面として読めない
+    InvalidInput = 1,
+    NoSolution = 2,
+    /// 時間切れ, 割り込みかメモリの上限
+    Timeout = 3,
+    Failed = 4,
+}
+
+#[repr(C)]
+pub struct NumberlinkSolution {
+    pub status: NumberlinkStatus,
+    pub width: usize,
+    pub height: usize,
+    /// 解けたとき, 行優先の width * height 個の, セルを通る線の数字 (穴は 0). ほかは NULL
+    pub cells: *mut usize,
+    /// 解けなかった理由 (解けたら NULL)
+    pub message: *mut c_char,
+}
+
+/// 答えを solve --format json の 1 行と同じ JSON で返す (解けなければ {"error":"..."}). timeout_ms が 0 なら時間の制限なし.
+/// 返した文字列は numberlink_string_free で放す
+///
+/// # Safety
+/// input は NULL か, NUL で終わる文字列
+#[no_mangle]
+pub unsafe extern "C" fn numberlink_solve_json(input: *const c_char, timeout_ms: u64) -> *mut c_char {
+    let line = match read(input) {
+        Some(puzzle) => match solve(&puzzle, timeout_ms) {
+            Ok(sol) => render_line(&puzzle, &sol, Format::Json),
+            Err(e) => render_error_line(&e, Format::Json),
+        },
+        None => render_error_line(&Error::InvalidUrl, Format::Json),
+    };
+
+    to_c(line.trim_end())
+}
+
+/// 解いた結果は status を見てから読む. 返したものは numberlink_solution_free で放す
+///
+/// # Safety
+/// input は NULL か, NUL で終わる文字列
+#[no_mangle]
+pub unsafe extern "C" fn numberlink_solve(input: *const c_char, timeout_ms: u64) -> *mut NumberlinkSolution {
+    let puzzle = match read(input) {
+        Some(puzzle) => puzzle,
+        None => return failure(NumberlinkStatus::InvalidInput, 0, 0, &Error::InvalidUrl),
+    };
+    let (width, height) = (puzzle.width, puzzle.height);
+
+    let sol = match solve(&puzzle, timeout_ms) {
+        Ok(sol) => sol,
+        Err(e) => {
+            let status = match e {
+                Error::NoSolution => NumberlinkStatus::NoSolution,
+                Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_) => NumberlinkStatus::Timeout,
+                _ => NumberlinkStatus::Failed,
+            };
+
+            return failure(status, width, height, &e);
+        },
+    };
+    let cells = label_cells(&puzzle, &sol).cells.into_boxed_slice();
+
+    Box::into_raw(Box::new(NumberlinkSolution { status: NumberlinkStatus::Solved, width, height, cells: Box::into_raw(cells) as *mut usize, message: ptr::null_mut() }))
+}
+
+/// # Safety
+/// solution は NULL か, numberlink_solve が返してまだ放していないもの
+#[no_mangle]
+pub unsafe extern "C" fn numberlink_solution_free(solution: *mut NumberlinkSolution) {
+    if solution.is_null() {
+        return;
+    }
+
+    let solution = Box::from_raw(solution);
+
+    if !solution.cells.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(solution.cells, solution.width*solution.height)));
+    }
+
+    numberlink_string_free(solution.message);
+}
+
+/// # Safety
+/// s は NULL か, このライブラリが返してまだ放していない文字列
+#[no_mangle]
+pub unsafe extern "C" fn numberlink_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+unsafe fn read(input: *const c_char) -> Option<Puzzle> {
+    if input.is_null() {
+        return None;
+    }
+
+    parse_puzzle(CStr::from_ptr(input).to_str().ok()?.trim())
+}
+
+fn solve(puzzle: &Puzzle, timeout_ms: u64) -> Result<Sol, Error> {
+    let timeout = Some(Duration::from_millis(timeout_ms)).filter(|_| timeout_ms > 0);
+
+    solve_numberlink(puzzle, &SolverConfig { timeout, ..SolverConfig::default() })
+}
+
+fn failure(status: NumberlinkStatus, width: usize, height: usize, e: &Error) -> *mut NumberlinkSolution {
+    Box::into_raw(Box::new(NumberlinkSolution { status, width, height, cells: ptr::null_mut(), message: to_c(&e.to_string()) }))
+}
+
+// 盤面の文字列に NUL は入らない
+fn to_c(s: &str) -> *mut c_char {
+    CString::new(s.replace('\0', "")).unwrap().into_raw()
+}
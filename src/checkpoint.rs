@@ -0,0 +1,72 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::optimize::Optimized;
+use crate::{Puzzle, Sol};
+
+const HEADER: &str = "numberlink checkpoint";
+
+// 最適化の途中の最良解を書き出す (曲がる回数の上界はこの解から作り直せる)
+// 書きかけで落ちても前の checkpoint が残るよう, 一時ファイルに書いてから置き換える
+pub fn save(path: &Path, puzzle: &Puzzle, best: &Optimized) -> io::Result<()> {
+    let mut text = format!("{}\nsize {} {}\ncells", HEADER, puzzle.width, puzzle.height);
+
+    for num in &puzzle.cells {
+        text.push_str(&format!(" {}", num));
+    }
+
+    text.push_str(&format!("\nturns {}\n", best.turns));
+
+    for ((i, j), (k, l)) in &best.sol {
+        text.push_str(&format!("arc {} {} {} {}\n", i, j, k, l));
+    }
+
+    let tmp = path.with_extension("tmp");
+    let mut file = fs::File::create(&tmp)?;
+
+    file.write_all(text.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+// ファイルがなければ None, 別の問題の checkpoint なら InvalidData
+pub fn load(path: &Path, puzzle: &Puzzle) -> io::Result<Option<Sol>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), msg));
+    let mut lines = text.lines();
+
+    if lines.next() != Some(HEADER) {
+        return Err(invalid("not a checkpoint file"));
+    }
+
+    let mut size = None;
+    let mut cells = None;
+    let mut sol = vec![];
+
+    for line in lines {
+        let mut words = line.split_whitespace();
+        let key = words.next();
+        let values: Vec<usize> = words.map(|word| word.parse()).collect::<Result<_, _>>().map_err(|_| invalid(line))?;
+
+        match (key, values.as_slice()) {
+            (Some("size"), &[width, height]) => size = Some((width, height)),
+            (Some("cells"), _) => cells = Some(values),
+            (Some("turns"), &[_]) => {},
+            (Some("arc"), &[i, j, k, l]) => sol.push(((i, j), (k, l))),
+            (None, _) => {},
+            _ => return Err(invalid(line)),
+        }
+    }
+
+    if size != Some((puzzle.width, puzzle.height)) || cells.as_ref() != Some(&puzzle.cells) {
+        return Err(invalid("checkpoint is for a different puzzle"));
+    }
+
+    Ok(Some(sol))
+}
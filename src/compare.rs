@@ -0,0 +1,77 @@
+use std::thread;
+
+use crate::{solve_numberlink, Error, Instant, Puzzle, Session, Sol, SolverConfig, Stats};
+
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub name: String,
+    pub result: Result<Sol, Error>,
+    pub stats: Stats,
+}
+
+impl Outcome {
+    // SAT なら Some(true), UNSAT なら Some(false), 答えが出なければ None
+    pub fn answer(&self) -> Option<bool> {
+        match self.result {
+            Ok(_) => Some(true),
+            Err(Error::NoSolution) => Some(false),
+            Err(_) => None,
+        }
+    }
+}
+
+// 同じ盤面を全設定で同時に解く (互いに打ち切らない)
+pub fn compare(puzzle: &Puzzle, trials: &[(String, SolverConfig)]) -> Vec<Outcome> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = trials.iter().map(|(name, config)| scope.spawn(move || {
+            let (result, stats) = if !config.backend.is_sat() {
+                let start = Instant::now();
+                let result = solve_numberlink(puzzle, config);
+
+                (result, Stats { elapsed: start.elapsed(), ..Stats::default() })
+            } else {
+                match Session::new(puzzle, config) {
+                    Ok(mut session) => {
+                        let result = session.solve();
+
+                        (result, session.stats())
+                    },
+                    Err(e) => (Err(e), Stats::default()),
+                }
+            };
+
+            Outcome { name: name.clone(), result, stats }
+        })).collect();
+
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    })
+}
+
+// 答えが出たものどうしで SAT/UNSAT が一致するか
+pub fn agree(outcomes: &[Outcome]) -> bool {
+    let mut answers = outcomes.iter().filter_map(Outcome::answer);
+    let first = answers.next();
+
+    answers.all(|answer| Some(answer) == first)
+}
+
+pub fn render_outcomes(label: &str, outcomes: &[Outcome]) -> String {
+    render_with(label, agree(outcomes), outcomes)
+}
+
+// crosscheck は答えの正しさも一致の判定に入れる
+pub(crate) fn render_with(label: &str, agreed: bool, outcomes: &[Outcome]) -> String {
+    let mut res = format!("{}: {}", label, if agreed { "agree" } else { "DISAGREE" });
+
+    for outcome in outcomes {
+        let answer = match &outcome.result {
+            Ok(_) => "sat".to_string(),
+            Err(Error::NoSolution) => "unsat".to_string(),
+            Err(e) => e.to_string(),
+        };
+
+        res.push_str(&format!(" | {} {:.3}s {}", outcome.name, outcome.stats.elapsed.as_secs_f64(), answer));
+    }
+
+    res
+}
@@ -0,0 +1,247 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use numberlink_solver::backend::Backend;
+use numberlink_solver::constraints::parse_constraints;
+use numberlink_solver::optimize::Objective;
+use numberlink_solver::render::{Format, Theme};
+use numberlink_solver::{SolverConfig, Wrap};
+
+pub const CONFIG_FILE: &str = "numberlink.toml";
+pub const ENV_PREFIX: &str = "NUMBERLINK_";
+pub const KEYS: [&str; 36] = [
+    "all-optimal", "backend", "checkpoint", "constraints", "coverage", "crossing", "deterministic", "diagonal", "encoding", "fallback", "format", "jobs", "lengths", "live", "loops", "max-memory", "nets", "no-2x2", "no-adjacent", "no-touch", "objective", "phase", "presolve", "regions", "result-cache", "solver-cmd", "solver-lib", "status-interval", "timeout", "theme", "wrap",
+    "vsids-decay", "clause-activity-decay", "reduce-locals-interval", "reduce-mids-interval", "luby-restart-interval-scale",
+];
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub solver: SolverConfig,
+    pub format: Format,
+    pub theme: Theme,
+    // 指定がなければ 1 (--backend auto で 1 問だけのときは難しさを見て決める)
+    pub jobs: Option<usize>,
+    pub objective: Objective,
+    // 0 以外なら最良の値の解をこの数まですべて出す
+    pub all_optimal: usize,
+    // 0 以外なら盤面をこの列数ずつの帯に分けて解く
+    pub regions: usize,
+    // --objective turns の途中の最良解をここに書き出し, 次に起動したときはそこから続ける
+    pub checkpoint: Option<PathBuf>,
+    // この間隔で途中経過を stderr に出す
+    pub status_interval: Option<Duration>,
+    // 並列に解いても毎回同じ出力にする
+    pub deterministic: bool,
+    // 読み込んだ盤面の端をつなぐ (トーラスなど)
+    pub wrap: Wrap,
+    // 読み込んだ四角い盤面で斜めにも進める
+    pub diagonal: bool,
+    // 1 問だけ解くとき, 探索の途中の盤面を stderr に描き続ける
+    pub live: bool,
+    // 1 問だけ解いて時間切れになったら, 貪欲につないだところまでを出す
+    pub fallback: bool,
+    // 何問も解くとき, 答えをこのディレクトリに置いて同じ代表の盤面は解かない
+    pub result_cache: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            solver: SolverConfig { backend: Backend::Auto, ..SolverConfig::default() },
+            format: Format::Debug,
+            theme: Theme::Plain,
+            jobs: None,
+            objective: Objective::None,
+            all_optimal: 0,
+            regions: 0,
+            checkpoint: None,
+            status_interval: None,
+            deterministic: false,
+            wrap: Wrap::None,
+            diagonal: false,
+            live: false,
+            fallback: false,
+            result_cache: None,
+        }
+    }
+}
+
+impl Config {
+    // 優先順位: デフォルト < 設定ファイル < 環境変数 (< コマンドライン引数)
+    pub fn load(path: Option<PathBuf>) -> Result<Self, String> {
+        let mut config = Config::default();
+
+        let path = path.or_else(|| env::var_os(format!("{}CONFIG", ENV_PREFIX)).map(PathBuf::from));
+
+        match path {
+            Some(path) => config.apply_file(&path)?,
+            None => {
+                if let Some(path) = default_path() {
+                    config.apply_file(&path)?;
+                }
+            }
+        }
+
+        config.apply_env()?;
+
+        Ok(config)
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "all-optimal" => self.all_optimal = value.parse().map_err(|_| format!("invalid number of solutions `{}`", value))?,
+            "backend" => self.solver.backend = value.parse()?,
+            "checkpoint" => self.checkpoint = Some(PathBuf::from(value)).filter(|path| !path.as_os_str().is_empty()),
+            "deterministic" => self.deterministic = parse_bool(key, value)?,
+            "encoding" => self.solver.encoding = value.parse()?,
+            "fallback" => self.fallback = parse_bool(key, value)?,
+            "format" => self.format = value.parse()?,
+            "theme" => self.theme = value.parse()?,
+            "wrap" => self.wrap = value.parse()?,
+            "diagonal" => self.diagonal = parse_bool(key, value)?,
+            "crossing" => self.solver.rules.crossing = value.parse()?,
+            "jobs" => self.jobs = Some(parse_jobs(value)?),
+            "lengths" => self.solver.rules.lengths = parse_bool(key, value)?,
+            "live" => self.live = parse_bool(key, value)?,
+            "nets" => self.solver.rules.nets = value.parse()?,
+            "constraints" => self.solver.constraints = parse_constraints(value)?,
+            "coverage" => self.solver.rules.coverage = parse_bool(key, value)?,
+            "loops" => self.solver.rules.loops = parse_bool(key, value)?,
+            "no-2x2" => self.solver.rules.no_square = parse_bool(key, value)?,
+            "no-touch" => self.solver.rules.no_touch = parse_bool(key, value)?,
+            "no-adjacent" => self.solver.rules.no_adjacent = parse_bool(key, value)?,
+            "objective" => self.objective = value.parse()?,
+            "phase" => self.solver.phase = value.parse()?,
+            "presolve" => self.solver.presolve = parse_bool(key, value)?,
+            "regions" => self.regions = value.parse().map_err(|_| format!("invalid region width `{}`", value))?,
+            "result-cache" => self.result_cache = Some(PathBuf::from(value)).filter(|path| !path.as_os_str().is_empty()),
+            "solver-cmd" => self.solver.backend = Backend::External(value.to_string()),
+            "solver-lib" => self.solver.backend = Backend::Ipasir(PathBuf::from(value)),
+            "status-interval" => self.status_interval = parse_secs(key, value)?,
+            "timeout" => self.solver.timeout = parse_secs(key, value)?,
+            "max-memory" => self.solver.max_memory = parse_memory(value)?,
+            "vsids-decay" => self.solver.tuning.vsids_decay = Some(parse_number(key, value)?),
+            "clause-activity-decay" => self.solver.tuning.clause_activity_decay = Some(parse_number(key, value)?),
+            "reduce-locals-interval" => self.solver.tuning.reduce_locals_interval = Some(parse_number(key, value)?),
+            "reduce-mids-interval" => self.solver.tuning.reduce_mids_interval = Some(parse_number(key, value)?),
+            "luby-restart-interval-scale" => self.solver.tuning.luby_restart_interval_scale = Some(parse_number(key, value)?),
+            _ => return Err(format!("unknown setting `{}`", key)),
+        }
+
+        Ok(())
+    }
+
+    fn apply_file(&mut self, path: &PathBuf) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let table: toml::Table = text.parse().map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        for (key, value) in table {
+            let value = match value {
+                toml::Value::String(s) => s,
+                toml::Value::Integer(n) => n.to_string(),
+                toml::Value::Float(x) => x.to_string(),
+                _ => return Err(format!("{}: `{}` must be a string or a number", path.display(), key)),
+            };
+
+            self.set(&key, &value).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_env(&mut self) -> Result<(), String> {
+        for key in KEYS.iter() {
+            let name = format!("{}{}", ENV_PREFIX, key.to_uppercase().replace('-', "_"));
+
+            if let Ok(value) = env::var(&name) {
+                self.set(key, &value).map_err(|e| format!("{}: {}", name, e))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// カレントディレクトリ, ~/.config/numberlink の順に探す
+fn default_path() -> Option<PathBuf> {
+    let local = PathBuf::from(CONFIG_FILE);
+
+    if local.is_file() {
+        return Some(local);
+    }
+
+    let home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    let global = home.join("numberlink").join(CONFIG_FILE);
+
+    if global.is_file() {
+        Some(global)
+    } else {
+        None
+    }
+}
+
+// 秒数, 0 はなし
+fn parse_secs(key: &str, value: &str) -> Result<Option<Duration>, String> {
+    let secs: f64 = parse_number(key, value)?;
+
+    if !secs.is_finite() || secs < 0.0 {
+        return Err(format!("invalid {} `{}`", key.replace('-', " "), value));
+    }
+
+    if secs == 0.0 {
+        Ok(None)
+    } else {
+        Ok(Some(Duration::from_secs_f64(secs)))
+    }
+}
+
+// 512M, 2G のような接尾辞付き (なしならバイト), 0 は上限なし
+fn parse_memory(value: &str) -> Result<Option<u64>, String> {
+    let invalid = || format!("invalid memory size `{}`", value);
+    let (digits, unit) = match value.char_indices().last() {
+        Some((pos, c)) if c.is_ascii_alphabetic() => (&value[..pos], c.to_ascii_uppercase()),
+        _ => (value, 'B'),
+    };
+
+    let shift = match unit {
+        'B' => 0,
+        'K' => 10,
+        'M' => 20,
+        'G' => 30,
+        'T' => 40,
+        _ => return Err(invalid()),
+    };
+
+    let size: u64 = digits.parse().map_err(|_| invalid())?;
+    let bytes = size.checked_mul(1 << shift).ok_or_else(invalid)?;
+
+    if bytes == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(bytes))
+    }
+}
+
+// 範囲は varisat が解く前に確かめる
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid {} `{}`", key.replace('-', " "), value))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err(format!("invalid {} `{}` (expected true or false)", key, value)),
+    }
+}
+
+fn parse_jobs(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => Err(format!("invalid number of jobs `{}`", value)),
+    }
+}
@@ -0,0 +1,154 @@
+use std::str::FromStr;
+
+use varisat::{ExtendFormula, Lit};
+
+use crate::{find_arc_var, gen_adjs, Error, Puzzle, Sol, P};
+
+// --constraints の制約. `;` か改行で区切って並べ, # から行末までは読まない
+//
+//     forbid cell (R,C)             (R, C) を線が通らない
+//     require cell (R,C)            (R, C) を線が通る
+//     forbid edge (R,C)-(R,C)       隣り合う 2 セルの間を線が通らない
+//     require edge (R,C)-(R,C)      その間を線が通る
+//     maxlen N = L                  数字 N の線が通るセルは L 個以下 (盤面の limits と同じ)
+//
+// 行と列は 0 から数える
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constraint {
+    // セルを線が通るか
+    Cell(P, bool),
+    // 2 セルの間 (どちら向きでも) を線が通るか
+    Edge(P, P, bool),
+    MaxLen(usize, usize),
+}
+
+impl FromStr for Constraint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid constraint `{}` (expected forbid|require cell (R,C), forbid|require edge (R,C)-(R,C) or maxlen N = L)", s);
+        let num = |token: &str| token.parse::<usize>().map_err(|_| invalid());
+        let used = |verb: &str| match verb {
+            "forbid" => Ok(false),
+            "require" => Ok(true),
+            _ => Err(invalid()),
+        };
+
+        match tokens(s)[..] {
+            [verb, "cell", "(", r, ",", c, ")"] => Ok(Constraint::Cell((num(r)?, num(c)?), used(verb)?)),
+            [verb, "edge", "(", r1, ",", c1, ")", "-", "(", r2, ",", c2, ")"] => Ok(Constraint::Edge((num(r1)?, num(c1)?), (num(r2)?, num(c2)?), used(verb)?)),
+            ["maxlen", n, "=", len] => Ok(Constraint::MaxLen(num(n)?, num(len)?)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+pub fn parse_constraints(text: &str) -> Result<Vec<Constraint>, String> {
+    text.lines()
+        .flat_map(|line| line.split('#').next().unwrap_or("").split(';'))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+// 英数字の並びと, それ以外の 1 文字ずつ (空白は捨てる)
+fn tokens(s: &str) -> Vec<&str> {
+    let mut res = vec![];
+    let mut start = None;
+
+    for (k, c) in s.char_indices() {
+        if c.is_ascii_alphanumeric() {
+            start.get_or_insert(k);
+            continue;
+        }
+
+        if let Some(start) = start.take() {
+            res.push(&s[start..k]);
+        }
+
+        if !c.is_whitespace() {
+            res.push(&s[k..k+c.len_utf8()]);
+        }
+    }
+
+    res.extend(start.map(|start| &s[start..]));
+
+    res
+}
+
+// maxlen を盤面の長さの上限に足したもの (maxlen がなければ None). 上限がすでにあれば小さい方
+// 解がセルと辺の制約を満たすか (maxlen は verify が盤面の limits で見る)
+pub(crate) fn holds(constraints: &[Constraint], sol: &Sol) -> bool {
+    constraints.iter().all(|&constraint| match constraint {
+        Constraint::Cell(p, used) => sol.iter().any(|&(u, v)| u == p || v == p) == used,
+        Constraint::Edge(p, q, used) => sol.iter().any(|&arc| arc == (p, q) || arc == (q, p)) == used,
+        Constraint::MaxLen(..) => true,
+    })
+}
+
+pub(crate) fn limited(puzzle: &Puzzle, constraints: &[Constraint], lengths: bool) -> Result<Option<Puzzle>, Error> {
+    let mut res: Option<Puzzle> = None;
+
+    for &constraint in constraints {
+        if let Constraint::MaxLen(num, len) = constraint {
+            if lengths || puzzle.dots {
+                return Err(Error::InvalidConstraint("maxlen needs numbered paths (not --lengths or dots)".to_string()));
+            }
+
+            if num == 0 || !puzzle.cells.contains(&num) {
+                return Err(Error::InvalidConstraint(format!("maxlen: no path numbered {}", num)));
+            }
+
+            let limits = &mut res.get_or_insert_with(|| puzzle.clone()).limits;
+            let limit = limits.get(&num).map_or(len, |&limit| limit.min(len));
+
+            limits.insert(num, limit);
+        }
+    }
+
+    Ok(res)
+}
+
+// セルと辺の制約の節 (maxlen は limited で盤面に入れてある)
+pub(crate) fn encode_constraints<F: ExtendFormula>(formula: &mut F, puzzle: &Puzzle, constraints: &[Constraint]) -> Result<(), Error> {
+    if constraints.is_empty() {
+        return Ok(());
+    }
+
+    let adjs = gen_adjs(puzzle);
+    let topology = puzzle.topology;
+    let cell = |(row, column): P| match row < puzzle.height && column < puzzle.width {
+        true => Ok(puzzle.index((row, column))),
+        false => Err(Error::InvalidConstraint(format!("cell ({}, {}) is not on the board", row, column))),
+    };
+    let mut encode = |lits: Vec<Lit>, used: bool| match used {
+        true => formula.add_clause(&lits),
+        false => lits.iter().for_each(|&lit| formula.add_clause(&[!lit])),
+    };
+
+    for &constraint in constraints {
+        match constraint {
+            Constraint::Cell(p, used) => {
+                let u = cell(p)?;
+
+                encode(adjs[u].iter().flat_map(|&(v, d)| vec![topology.arc_var(u, d).positive(), topology.arc_var(v, topology.opposite(d)).positive()]).collect(), used);
+            },
+            Constraint::Edge(p, q, used) => {
+                cell(p)?;
+                cell(q)?;
+
+                let lits: Vec<Lit> = [(p, q), (q, p)].iter().filter_map(|&arc| find_arc_var(puzzle, arc)).map(|x| x.positive()).collect();
+
+                if lits.is_empty() {
+                    return Err(Error::InvalidConstraint(format!("cells ({}, {}) and ({}, {}) are not neighbours", p.0, p.1, q.0, q.1)));
+                }
+
+                encode(lits, used);
+            },
+            Constraint::MaxLen(..) => {},
+        }
+    }
+
+    Ok(())
+}
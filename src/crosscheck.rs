@@ -0,0 +1,138 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::backend::Backend;
+use crate::compare::{agree, compare, render_with, Outcome};
+use crate::input::{arcs, parse_paths};
+use crate::monitor::{Control, Stop};
+use crate::rules::verify;
+use crate::{Error, Instant, Puzzle, SolverConfig, Stats};
+
+// 期限と中断を確かめる間隔
+const POLL: Duration = Duration::from_millis(10);
+
+// 答えを突き合わせる外部のソルバ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Checker {
+    // 盤面の行を標準入力に渡して起動するコマンド. 標準出力の答え (gen --from と同じ形) か `no solution` を読む
+    Numberlink(String),
+    // 同じ符号化の DIMACS を解く SAT ソルバのコマンド (--solver-cmd と同じ)
+    Sat(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Verdict {
+    // 先頭がこのソルバ, 続いて checkers の順
+    pub outcomes: Vec<Outcome>,
+    // 規則を満たさない答えを返したもの (名前, 理由)
+    pub invalid: Vec<(String, String)>,
+}
+
+impl Verdict {
+    pub fn agree(&self) -> bool {
+        self.invalid.is_empty() && agree(&self.outcomes)
+    }
+}
+
+// このソルバと checkers で同じ盤面を同時に解き, 出た答えを規則で確かめる. line は外部のコマンドに渡す盤面の行
+pub fn crosscheck(puzzle: &Puzzle, line: &str, config: &SolverConfig, checkers: &[Checker]) -> Verdict {
+    let outcomes: Vec<Outcome> = thread::scope(|scope| {
+        let own = scope.spawn(|| compare(puzzle, &[("solver".to_string(), config.clone())]));
+        let handles: Vec<_> = checkers.iter().map(|checker| scope.spawn(move || match checker {
+            Checker::Numberlink(command) => external(puzzle, line, command, config),
+            Checker::Sat(command) => compare(puzzle, &[(command.clone(), SolverConfig { backend: Backend::External(command.clone()), ..config.clone() })]).remove(0),
+        })).collect();
+
+        own.join().unwrap().into_iter().chain(handles.into_iter().map(|handle| handle.join().unwrap())).collect()
+    });
+
+    let invalid = outcomes.iter().filter_map(|outcome| match &outcome.result {
+        Ok(sol) => verify(puzzle, &config.rules, sol).err().map(|reason| (outcome.name.clone(), reason)),
+        Err(_) => None,
+    }).collect();
+
+    Verdict { outcomes, invalid }
+}
+
+pub fn render_verdict(label: &str, verdict: &Verdict) -> String {
+    let mut res = render_with(label, verdict.agree(), &verdict.outcomes);
+
+    for (name, reason) in &verdict.invalid {
+        res.push_str(&format!(" | {} invalid: {}", name, reason));
+    }
+
+    res
+}
+
+fn external(puzzle: &Puzzle, line: &str, command: &str, config: &SolverConfig) -> Outcome {
+    let start = Instant::now();
+    let control = Control {
+        deadline: Mutex::new(config.timeout.map(|timeout| start+timeout)),
+        interrupts: config.interrupt.iter().cloned().collect(),
+        ..Control::default()
+    };
+    let result = run(line, command, &control).and_then(|text| {
+        if text.to_lowercase().contains("no solution") {
+            return Err(Error::NoSolution);
+        }
+
+        let (width, height, paths) = parse_paths(&text).ok_or_else(|| Error::BackendFailed(format!("`{}` printed no solution that can be read", command)))?;
+
+        if (width, height) != (puzzle.width, puzzle.height) {
+            return Err(Error::BackendFailed(format!("`{}` answered on a {}x{} board, not {}x{}", command, width, height, puzzle.width, puzzle.height)));
+        }
+
+        Ok(arcs(puzzle, &paths))
+    });
+    let stats = Stats { elapsed: start.elapsed(), ..Stats::default() };
+    let result = match result {
+        Err(Error::Timeout(_)) => Err(Error::Timeout(stats)),
+        Err(Error::Interrupted(_)) => Err(Error::Interrupted(stats)),
+        result => result,
+    };
+
+    Outcome { name: command.to_string(), result, stats }
+}
+
+// 終わるか止めるべきになるまで待ち, 標準出力を返す (backend::dimacs と同じ)
+fn run(line: &str, command: &str, control: &Control) -> Result<String, Error> {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let (program, args) = words.split_first().ok_or_else(|| Error::BackendFailed("empty solver command".to_string()))?;
+    let mut child = Command::new(program).args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null())
+        .spawn().map_err(|e| Error::BackendFailed(format!("{}: {}", program, e)))?;
+
+    // 読まずに終わるコマンドもあるので書き込みの失敗は気にしない
+    let mut stdin = child.stdin.take().unwrap();
+    let _ = writeln!(stdin, "{}", line);
+    drop(stdin);
+
+    let mut stdout = child.stdout.take().unwrap();
+    let reader = thread::spawn(move || {
+        let mut text = String::new();
+
+        stdout.read_to_string(&mut text).map(|_| text)
+    });
+
+    loop {
+        if let Some(stop) = control.check() {
+            let _ = child.kill();
+            let _ = child.wait();
+
+            return Err(match stop {
+                Stop::Timeout => Error::Timeout(Stats::default()),
+                _ => Error::Interrupted(Stats::default()),
+            });
+        }
+
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) => thread::sleep(POLL),
+            Err(e) => return Err(Error::BackendFailed(e.to_string())),
+        }
+    }
+
+    reader.join().unwrap().map_err(|e| Error::BackendFailed(e.to_string()))
+}
@@ -0,0 +1,147 @@
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::render::{render_error_line, render_line, Format};
+use crate::{solve_numberlink, try_parse_puzzle, Error, SolverConfig};
+
+// 1 つの要求の本文の上限 (これより長い長さが来たら接続を切る)
+pub const MAX_FRAME: usize = 16 << 20;
+
+// 同時に開いておける接続の数 (接続ごとにスレッドを作るので). 超えた接続にはエラーを 1 つ返して閉じる
+pub const MAX_CONNECTIONS: usize = 64;
+
+// 既定のソケット: $XDG_RUNTIME_DIR/numberlink.sock (なければ一時ディレクトリ)
+pub fn default_socket() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR").map_or_else(env::temp_dir, PathBuf::from).join("numberlink.sock")
+}
+
+// Unix ドメインソケットで要求を待ち続ける常駐プロセス (daemon). プロセスを起動しないので, エディタやボットから小さい盤面を
+// 何度も解くときの手間がほぼなくなる. 待ち受けを始めたら stderr に書く
+//
+// 要求も応答も, 本文の長さ (4 byte のビッグエンディアン) に続けて UTF-8 の本文. 1 つの接続で何度でも送れる
+//     要求  盤面 (puzz.link の URL, テキストか JSON の盤面)
+//     応答  solve --format json の 1 行 (解けなければ {"error":...})
+//
+// 盤面のセルの数は MAX_CELLS まで, 同時の接続は MAX_CONNECTIONS まで
+// 前のプロセスが残したソケットのファイルは消して使う. 他のプロセスが待ち受けていれば AddrInUse
+pub fn serve_socket(path: &Path, config: SolverConfig) -> io::Result<()> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(io::ErrorKind::AddrInUse, format!("{} is already served", path.display())));
+        }
+
+        fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+
+    eprintln!("listening on {}", path.display());
+
+    let open = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("daemon: {}", e);
+                continue;
+            },
+        };
+
+        if open.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            open.fetch_sub(1, Ordering::SeqCst);
+
+            let _ = write_frame(&mut stream, &format!("{{\"error\":\"too many connections (at most {})\"}}", MAX_CONNECTIONS));
+            continue;
+        }
+
+        let (config, slot) = (config.clone(), Slot(open.clone()));
+
+        thread::spawn(move || {
+            let _slot = slot;
+
+            if let Err(e) = answer(stream, &config) {
+                eprintln!("daemon: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// 接続 1 つ分の枠. スレッドが終われば (panic しても) 返す
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// 相手が閉じるまで要求に答える
+fn answer(mut stream: UnixStream, config: &SolverConfig) -> io::Result<()> {
+    while let Some(body) = read_frame(&mut stream)? {
+        write_frame(&mut stream, &solve(&body, config))?;
+    }
+
+    Ok(())
+}
+
+fn solve(body: &[u8], config: &SolverConfig) -> String {
+    let puzzle = match std::str::from_utf8(body).map_err(|_| Error::InvalidUrl).and_then(|body| try_parse_puzzle(body.trim())) {
+        Ok(puzzle) => puzzle,
+        Err(e) => return render_error_line(&e, Format::Json),
+    };
+
+    match solve_numberlink(&puzzle, config) {
+        Ok(sol) => render_line(&puzzle, &sol, Format::Json),
+        Err(e) => render_error_line(&e, Format::Json),
+    }
+}
+
+// 長さと本文を 1 つ読む. 次の要求の前に閉じられたら None
+pub fn read_frame(stream: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+
+    match stream.read_exact(&mut len) {
+        Ok(()) => {},
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+
+    if len > MAX_FRAME {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("frame of {} bytes is too long", len)));
+    }
+
+    let mut body = vec![0; len];
+
+    stream.read_exact(&mut body)?;
+
+    Ok(Some(body))
+}
+
+pub fn write_frame(stream: &mut impl Write, body: &str) -> io::Result<()> {
+    let len = u32::try_from(body.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame is too long"))?;
+
+    stream.write_all(&len.to_be_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
+
+// 常駐プロセスに盤面を 1 つ送り, 応答を待つ (クライアント側)
+pub fn request(stream: &mut UnixStream, puzzle: &str) -> io::Result<String> {
+    write_frame(stream, puzzle)?;
+
+    let body = read_frame(stream)?.ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "daemon closed the connection"))?;
+
+    String::from_utf8(body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
@@ -0,0 +1,197 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection};
+
+use crate::canonical::canonical_hash;
+use crate::predict::predict;
+use crate::render::{render_line, Format};
+use crate::{Error, Puzzle, Sol};
+
+// db の既定のファイル (今のディレクトリ)
+pub const DEFAULT_PATH: &str = "numberlink.db";
+
+// 盤面 (同じ代表の盤面は 1 つ) と, 解いた記録 (解けなくても 1 回ごと) を置く SQLite のファイル.
+// 大きな盤面集を何度も解き直すときに, ばらばらのファイルではなく 1 か所で問い合わせられるようにする
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS puzzles (
+    id INTEGER PRIMARY KEY,
+    hash TEXT NOT NULL UNIQUE,
+    input TEXT NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    pairs INTEGER NOT NULL,
+    difficulty TEXT NOT NULL,
+    score REAL NOT NULL
+);
+CREATE TABLE IF NOT EXISTS solves (
+    id INTEGER PRIMARY KEY,
+    puzzle INTEGER NOT NULL REFERENCES puzzles(id),
+    at INTEGER NOT NULL,
+    seconds REAL NOT NULL,
+    outcome TEXT NOT NULL,
+    solution TEXT
+);
+CREATE INDEX IF NOT EXISTS solves_puzzle ON solves(puzzle);
+";
+
+// solves.outcome の解けたときの値 (ほかは Error の文)
+const SOLVED: &str = "solved";
+
+pub struct Store {
+    conn: Connection,
+}
+
+// 盤面 1 つと, その解いた記録をまとめたもの
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    // 足したときの入力 (URL など. parse_puzzle で読める)
+    pub input: String,
+    pub hash: String,
+    pub width: usize,
+    pub height: usize,
+    pub pairs: usize,
+    // 解く前の予想 (predict)
+    pub difficulty: String,
+    pub score: f64,
+    pub attempts: usize,
+    // 解けたうちで一番速かった秒数. 解けたことがなければ None
+    pub best: Option<f64>,
+}
+
+// db query の問い合わせ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+    All,
+    Solved,
+    Unsolved,
+    // 解けたことのない盤面を予想の難しい順に
+    HardestUnsolved,
+    // 解けた盤面を一番速い記録の遅い順に
+    Slowest,
+    Size(usize, usize),
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Query::All),
+            "solved" => Ok(Query::Solved),
+            "unsolved" => Ok(Query::Unsolved),
+            "hardest-unsolved" => Ok(Query::HardestUnsolved),
+            "slowest" => Ok(Query::Slowest),
+            _ => match s.split_once('x').map(|(w, h)| (w.parse(), h.parse())) {
+                Some((Ok(width), Ok(height))) => Ok(Query::Size(width, height)),
+                _ => Err(format!("unknown query `{}` (expected all, solved, unsolved, hardest-unsolved, slowest or WxH)", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Query::All => write!(f, "all"),
+            Query::Solved => write!(f, "solved"),
+            Query::Unsolved => write!(f, "unsolved"),
+            Query::HardestUnsolved => write!(f, "hardest-unsolved"),
+            Query::Slowest => write!(f, "slowest"),
+            Query::Size(width, height) => write!(f, "{}x{}", width, height),
+        }
+    }
+}
+
+impl Store {
+    // なければ作る
+    pub fn open(path: &Path) -> Result<Store, String> {
+        let conn = Connection::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        conn.execute_batch(SCHEMA).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        Ok(Store { conn })
+    }
+
+    // 盤面を足して番号を返す. 同じ代表の盤面がもうあればその番号 (入力は最初のまま)
+    pub fn add(&self, puzzle: &Puzzle, input: &str) -> Result<i64, String> {
+        let hash = canonical_hash(puzzle);
+        let prediction = predict(puzzle);
+        let difficulty = prediction.difficulty.to_string();
+        let difficulty = difficulty.split(' ').next().unwrap_or_default();
+
+        self.conn.execute(
+            "INSERT OR IGNORE INTO puzzles (hash, input, width, height, pairs, difficulty, score) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![hash, input, puzzle.width as i64, puzzle.height as i64, prediction.pairs as i64, difficulty, prediction.score],
+        ).map_err(|e| e.to_string())?;
+
+        self.conn.query_row("SELECT id FROM puzzles WHERE hash = ?1", params![hash], |row| row.get(0)).map_err(|e| e.to_string())
+    }
+
+    // 解いた結果を 1 回分書き留める. 解けたら答えを solve --format json の 1 行で
+    pub fn record(&self, id: i64, puzzle: &Puzzle, res: &Result<Sol, Error>, elapsed: Duration) -> Result<(), String> {
+        let at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()) as i64;
+        let (outcome, solution) = match res {
+            Ok(sol) => (SOLVED.to_string(), Some(render_line(puzzle, sol, Format::Json))),
+            Err(e) => (e.to_string(), None),
+        };
+
+        self.conn.execute(
+            "INSERT INTO solves (puzzle, at, seconds, outcome, solution) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![id, at, elapsed.as_secs_f64(), outcome, solution],
+        ).map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    // 問い合わせに合う盤面を limit 個まで
+    pub fn query(&self, query: Query, limit: usize) -> Result<Vec<Entry>, String> {
+        let (filter, order) = match query {
+            Query::All => ("1".to_string(), "p.id"),
+            Query::Solved => ("best IS NOT NULL".to_string(), "p.id"),
+            Query::Unsolved => ("best IS NULL".to_string(), "p.id"),
+            Query::HardestUnsolved => ("best IS NULL".to_string(), "p.score DESC, p.id"),
+            Query::Slowest => ("best IS NOT NULL".to_string(), "best DESC, p.id"),
+            Query::Size(width, height) => (format!("p.width = {} AND p.height = {}", width, height), "p.id"),
+        };
+        let sql = format!(
+            "SELECT p.input, p.hash, p.width, p.height, p.pairs, p.difficulty, p.score, COUNT(s.id),
+                    MIN(CASE WHEN s.outcome = '{}' THEN s.seconds END) AS best
+             FROM puzzles p LEFT JOIN solves s ON s.puzzle = p.id
+             GROUP BY p.id HAVING {} ORDER BY {} LIMIT ?1",
+            SOLVED, filter, order,
+        );
+        let mut stmt = self.conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let rows = stmt.query_map(params![limit], |row| {
+            Ok(Entry {
+                input: row.get(0)?,
+                hash: row.get(1)?,
+                width: row.get::<_, i64>(2)? as usize,
+                height: row.get::<_, i64>(3)? as usize,
+                pairs: row.get::<_, i64>(4)? as usize,
+                difficulty: row.get(5)?,
+                score: row.get(6)?,
+                attempts: row.get::<_, i64>(7)? as usize,
+                best: row.get(8)?,
+            })
+        });
+
+        rows.and_then(|rows| rows.collect()).map_err(|e| e.to_string())
+    }
+}
+
+// 1 行に 1 問, タブ区切り: 入力, 大きさ, 組の数, 予想の難しさ, 解いた回数と一番速い記録 (cut -f1 で入力だけ取れる)
+//
+//     numlin/5/5/...	5x5	4	trivial	2	0.003s
+//     numlin/17/17/...	17x17	11	medium	1	-
+pub fn render_entries(entries: &[Entry]) -> String {
+    entries.iter().map(|entry| {
+        let best = entry.best.map_or("-".to_string(), |best| format!("{:.3}s", best));
+
+        format!("{}\t{}x{}\t{}\t{}\t{}\t{}\n", entry.input, entry.width, entry.height, entry.pairs, entry.difficulty, entry.attempts, best)
+    }).collect()
+}
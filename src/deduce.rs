@@ -0,0 +1,580 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use varisat::ExtendFormula;
+
+use crate::{encode_clue, find_arc_var, parse_field, sat_only, zdd, Arc, Encoding, Error, Puzzle, Role, Sol, SolverConfig};
+
+// 人が使う推論だけで辺を決める. 符号化と同じく空白だけの閉路も解に含めるので, 閉路を作らない・解が一意, を仮定する手筋は使わない
+// 安い手筋で行き詰まったときだけ次の手筋を使い, 1 本決まるたびに安い手筋に戻る
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Technique {
+    // 必要な本数と残りの辺の本数が等しい, または既に足りている
+    Forced,
+    // 違う数字につながった 2 セルの間は線にならない
+    Labels,
+    // 同じ数字の 2 セルの間の道がその辺を通るしかない (壁沿いの一本道など)
+    Bridge,
+    // 市松模様に塗ると, 閉路は白黒同数, 経路は両端の色だけ偏る. 区切られた領域ごとに偏りが合わなければならない
+    Parity,
+}
+
+const TECHNIQUES: [Technique; 4] = [Technique::Forced, Technique::Labels, Technique::Bridge, Technique::Parity];
+
+impl fmt::Display for Technique {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Technique::Forced => write!(f, "forced moves"),
+            Technique::Labels => write!(f, "label conflicts"),
+            Technique::Bridge => write!(f, "bridges"),
+            Technique::Parity => write!(f, "region parity"),
+        }
+    }
+}
+
+// 推論で解くのに要る一番難しい技法 (雑誌の難易度に近い). 推論だけで解けなければ Search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Grade {
+    Technique(Technique),
+    Search,
+}
+
+impl fmt::Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Grade::Technique(technique) => technique.fmt(f),
+            Grade::Search => write!(f, "search"),
+        }
+    }
+}
+
+// 辺は向きのない (番号の小さいセルが先の) Arc で表す
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deduction {
+    pub lines: Vec<Arc>,
+    pub crosses: Vec<Arc>,
+    // 決まっていない辺の数
+    pub undecided: usize,
+    // 線でつながって決まったセルの数字 (手がかりのセルを含む, 0 は未定)
+    pub labels: Vec<usize>,
+    // 技法ごとに決めた辺の数 (Technique の順)
+    pub uses: [usize; 4],
+    // 推論だけで解がないと分かった
+    pub contradiction: bool,
+}
+
+impl Deduction {
+    pub fn solved(&self) -> bool {
+        !self.contradiction && self.undecided == 0
+    }
+
+    // 使ったうちで一番難しい技法
+    pub fn hardest(&self) -> Option<Technique> {
+        TECHNIQUES.iter().zip(&self.uses).rev().find(|&(_, &uses)| uses > 0).map(|(&technique, _)| technique)
+    }
+
+    // 何も決めずに解けている盤面 (辺のない盤面) は Forced とする
+    pub fn grade(&self) -> Grade {
+        match self.solved() {
+            true => Grade::Technique(self.hardest().unwrap_or(Technique::Forced)),
+            false => Grade::Search,
+        }
+    }
+
+    // 推論だけで解けたときの解
+    pub fn solution(&self, puzzle: &Puzzle) -> Option<Sol> {
+        if !self.solved() {
+            return None;
+        }
+
+        let roles = parse_field(puzzle)?;
+        let mut adj = vec![vec![]; puzzle.cells.len()];
+
+        for &(u, v) in &self.lines {
+            let (u, v) = (puzzle.index(u), puzzle.index(v));
+
+            adj[u].push(v);
+            adj[v].push(u);
+        }
+
+        Some(zdd::orient(puzzle, &roles, &adj))
+    }
+}
+
+const UNKNOWN: u8 = 0;
+const LINE: u8 = 1;
+const CROSS: u8 = 2;
+
+pub fn deduce(puzzle: &Puzzle) -> Result<Deduction, Error> {
+    deduce_from(puzzle, &[], &[]).map(|(deduction, _)| deduction)
+}
+
+// 推論が 1 本決めた記録 (cell はその根拠にしたセル)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Step {
+    pub arc: Arc,
+    pub line: bool,
+    pub technique: Technique,
+    pub cell: usize,
+}
+
+// 線 (lines) と × (crosses) を決めた盤面から推論を進める. 決めた辺を決めた順に返す (辺はどちら向きに書いてもよい)
+pub(crate) fn deduce_from(puzzle: &Puzzle, lines: &[Arc], crosses: &[Arc]) -> Result<(Deduction, Vec<Step>), Error> {
+    if puzzle.width == 0 || puzzle.height == 0 || puzzle.cells.len() != puzzle.width*puzzle.height {
+        return Err(Error::InvalidField);
+    }
+
+    // 推論は既定の規則 (手がかりは組の数字) で読む
+    if let Some(feature) = sat_only(puzzle, &SolverConfig::default()) {
+        return Err(Error::BackendFailed(format!("deduction does not support {}", feature)));
+    }
+
+    let roles = parse_field(puzzle).ok_or(Error::InvalidField)?;
+    let n = puzzle.cells.len();
+    let edges = puzzle.edges();
+    let mut cell_edges = vec![vec![]; n];
+
+    for (e, &(u, v)) in edges.iter().enumerate() {
+        cell_edges[u].push(e);
+        cell_edges[v].push(e);
+    }
+
+    let mut board = Board { puzzle, roles, state: vec![UNKNOWN; edges.len()], edges, cell_edges, uses: [0; 4], contradiction: false, log: vec![] };
+
+    for (arcs, state) in [(lines, LINE), (crosses, CROSS)] {
+        for &(p, q) in arcs {
+            let (u, v) = (puzzle.index(p), puzzle.index(q));
+            let e = board.edges.iter().position(|&edge| edge == (u.min(v), u.max(v))).ok_or(Error::InvalidPin)?;
+
+            if board.state[e] != UNKNOWN && board.state[e] != state {
+                board.contradiction = true;
+            }
+
+            board.state[e] = state;
+        }
+    }
+
+    while !board.contradiction && (board.forced() || board.label_conflicts() || board.bridge() || board.parity()) {}
+
+    let (comp, comp_label) = board.components();
+    let pick = |s: u8| board.edges.iter().zip(&board.state).filter(|&(_, &state)| state == s).map(|(&(u, v), _)| (puzzle.pos(u), puzzle.pos(v))).collect();
+
+    let deduction = Deduction {
+        lines: pick(LINE),
+        crosses: pick(CROSS),
+        undecided: board.state.iter().filter(|&&s| s == UNKNOWN).count(),
+        labels: (0..n).map(|u| comp_label[comp[u]]).collect(),
+        uses: board.uses,
+        contradiction: board.contradiction,
+    };
+    let steps = board.log.iter().map(|&(e, technique, cell)| {
+        let (u, v) = board.edges[e];
+
+        Step { arc: (puzzle.pos(u), puzzle.pos(v)), line: board.state[e] == LINE, technique, cell }
+    }).collect();
+
+    Ok((deduction, steps))
+}
+
+// 推論だけで解がないと分かれば NoSolution
+pub fn grade(puzzle: &Puzzle) -> Result<Grade, Error> {
+    let deduction = deduce(puzzle)?;
+
+    match deduction.contradiction {
+        true => Err(Error::NoSolution),
+        false => Ok(deduction.grade()),
+    }
+}
+
+struct Board<'a> {
+    puzzle: &'a Puzzle,
+    roles: Vec<Role>,
+    edges: Vec<(usize, usize)>,
+    cell_edges: Vec<Vec<usize>>,
+    state: Vec<u8>,
+    uses: [usize; 4],
+    contradiction: bool,
+    // 決めた辺, 技法, 根拠のセル
+    log: Vec<(usize, Technique, usize)>,
+}
+
+impl Board<'_> {
+    fn set(&mut self, e: usize, state: u8, technique: Technique, cell: usize) {
+        self.state[e] = state;
+        self.uses[technique as usize] += 1;
+        self.log.push((e, technique, cell));
+    }
+
+    fn need(&self, u: usize) -> usize {
+        let (out_degree, in_degree) = self.roles[u].degrees();
+
+        out_degree+in_degree
+    }
+
+    fn other(&self, e: usize, u: usize) -> usize {
+        let (a, b) = self.edges[e];
+
+        if a == u { b } else { a }
+    }
+
+    fn forced(&mut self) -> bool {
+        let mut changed = false;
+
+        for u in 0..self.puzzle.cells.len() {
+            let count = |s: u8| self.cell_edges[u].iter().filter(|&&e| self.state[e] == s).count();
+            let (lines, unknown) = (count(LINE), count(UNKNOWN));
+            let need = self.need(u);
+
+            if lines > need || lines+unknown < need {
+                self.contradiction = true;
+
+                return false;
+            }
+
+            if unknown == 0 {
+                continue;
+            }
+
+            let state = if lines == need { CROSS } else if lines+unknown == need { LINE } else { continue };
+
+            for k in 0..self.cell_edges[u].len() {
+                let e = self.cell_edges[u][k];
+
+                if self.state[e] == UNKNOWN {
+                    self.set(e, state, Technique::Forced, u);
+                }
+            }
+
+            changed = true;
+        }
+
+        changed
+    }
+
+    // 線でつながったセルの成分と, 成分ごとの数字 (違う数字がつながっていれば矛盾)
+    fn components(&mut self) -> (Vec<usize>, Vec<usize>) {
+        let n = self.puzzle.cells.len();
+        let mut comp = vec![usize::MAX; n];
+        let mut labels = vec![];
+
+        for s in 0..n {
+            if comp[s] != usize::MAX {
+                continue;
+            }
+
+            let c = labels.len();
+            let mut stack = vec![s];
+            let mut label = 0;
+
+            comp[s] = c;
+
+            while let Some(u) = stack.pop() {
+                let num = self.puzzle.cells[u];
+
+                if num != 0 && label != 0 && label != num {
+                    self.contradiction = true;
+                }
+
+                if num != 0 {
+                    label = num;
+                }
+
+                for &e in &self.cell_edges[u] {
+                    let v = self.other(e, u);
+
+                    if self.state[e] == LINE && comp[v] == usize::MAX {
+                        comp[v] = c;
+                        stack.push(v);
+                    }
+                }
+            }
+
+            labels.push(label);
+        }
+
+        (comp, labels)
+    }
+
+    fn label_conflicts(&mut self) -> bool {
+        let (comp, labels) = self.components();
+        let mut changed = false;
+
+        for e in 0..self.edges.len() {
+            let (u, v) = self.edges[e];
+            let (a, b) = (labels[comp[u]], labels[comp[v]]);
+
+            if self.state[e] == UNKNOWN && a != 0 && b != 0 && a != b {
+                self.set(e, CROSS, Technique::Labels, u);
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    // 数字 num の 2 セルの間を, 数字が num か未定のセルと × でない辺だけでたどり, skip の辺を使わずに行ける道 (辺の列)
+    fn route(&self, comp: &[usize], labels: &[usize], num: usize, from: usize, to: usize, skip: Option<usize>) -> Option<Vec<usize>> {
+        let mut prev = vec![usize::MAX; self.puzzle.cells.len()];
+        let mut queue = VecDeque::from([from]);
+
+        prev[from] = self.edges.len();
+
+        while let Some(u) = queue.pop_front() {
+            if u == to {
+                let mut path = vec![];
+                let mut x = to;
+
+                while x != from {
+                    let e = prev[x];
+
+                    path.push(e);
+                    x = self.other(e, x);
+                }
+
+                return Some(path);
+            }
+
+            for &e in &self.cell_edges[u] {
+                let v = self.other(e, u);
+                let label = labels[comp[v]];
+
+                if self.state[e] != CROSS && Some(e) != skip && prev[v] == usize::MAX && (label == 0 || label == num) {
+                    prev[v] = e;
+                    queue.push_back(v);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn bridge(&mut self) -> bool {
+        let (comp, labels) = self.components();
+        let ends = self.ends();
+
+        for (num, &(from, to)) in ends.iter().enumerate().skip(1) {
+            if from == to || comp[from] == comp[to] {
+                continue;
+            }
+
+            let path = match self.route(&comp, &labels, num, from, to, None) {
+                Some(path) => path,
+                None => {
+                    self.contradiction = true;
+
+                    return false;
+                },
+            };
+
+            for &e in path.iter().filter(|&&e| self.state[e] == UNKNOWN) {
+                if self.route(&comp, &labels, num, from, to, Some(e)).is_none() {
+                    self.set(e, LINE, Technique::Bridge, from);
+
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    // 数字ごとの 2 セル (使わない数字は (0, 0))
+    fn ends(&self) -> Vec<(usize, usize)> {
+        let nums = self.puzzle.cells.iter().cloned().max().unwrap_or(0);
+        let mut ends = vec![(0, 0); nums+1];
+
+        for (u, &role) in self.roles.iter().enumerate() {
+            match role {
+                Role::Start => ends[self.puzzle.cells[u]].0 = u,
+                Role::End => ends[self.puzzle.cells[u]].1 = u,
+                Role::Blank | Role::Hole | Role::Bridge | Role::Terminal | Role::Dot => {},
+            }
+        }
+
+        ends
+    }
+
+    // 穴は線が通らないので数えない
+    fn color(&self, u: usize) -> i64 {
+        let (i, j) = self.puzzle.pos(u);
+
+        if self.roles[u] == Role::Hole { 0 } else if (i+j)%2 == 0 { 1 } else { -1 }
+    }
+
+    // × でない辺 (skip を除く) で区切った領域ごとに, 同じ数字が同じ領域にあり色の偏りが合うか
+    fn parity_holds(&self, skip: Option<usize>) -> bool {
+        let n = self.puzzle.cells.len();
+        let mut region = vec![usize::MAX; n];
+        let mut balance = vec![];
+
+        for s in 0..n {
+            if region[s] != usize::MAX {
+                continue;
+            }
+
+            let r = balance.len();
+            let mut stack = vec![s];
+            let mut sum = 0;
+
+            region[s] = r;
+
+            while let Some(u) = stack.pop() {
+                sum += self.color(u);
+
+                for &e in &self.cell_edges[u] {
+                    let v = self.other(e, u);
+
+                    if self.state[e] != CROSS && Some(e) != skip && region[v] == usize::MAX {
+                        region[v] = r;
+                        stack.push(v);
+                    }
+                }
+            }
+
+            balance.push(sum);
+        }
+
+        for &(a, b) in self.ends().iter().skip(1).filter(|&&(a, b)| a != b) {
+            if region[a] != region[b] {
+                return false;
+            }
+
+            balance[region[a]] -= (self.color(a)+self.color(b))/2;
+        }
+
+        balance.iter().all(|&sum| sum == 0)
+    }
+
+    fn parity(&mut self) -> bool {
+        // 同じ色のセルをつなぐワープがあると色の偏りが保たれない
+        if self.edges.iter().any(|&(u, v)| self.color(u) == self.color(v)) {
+            return false;
+        }
+
+        if !self.parity_holds(None) {
+            self.contradiction = true;
+
+            return false;
+        }
+
+        // × にすると領域が分かれる辺だけ確かめればよい
+        for e in self.region_bridges() {
+            if self.state[e] == UNKNOWN && !self.parity_holds(Some(e)) {
+                self.set(e, LINE, Technique::Parity, self.edges[e].0);
+
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // × でない辺のグラフの橋 (大きな盤面でも溢れないよう再帰を使わない lowlink)
+    fn region_bridges(&self) -> Vec<usize> {
+        let n = self.puzzle.cells.len();
+        let mut order = vec![usize::MAX; n];
+        let mut low = vec![0; n];
+        let mut res = vec![];
+        let mut time = 0;
+
+        for s in 0..n {
+            if order[s] != usize::MAX {
+                continue;
+            }
+
+            // (セル, 入ってきた辺, 次に見る cell_edges の位置)
+            let mut stack = vec![(s, usize::MAX, 0)];
+
+            order[s] = time;
+            low[s] = time;
+            time += 1;
+
+            while let Some(&mut (u, parent, ref mut k)) = stack.last_mut() {
+                if let Some(&e) = self.cell_edges[u].get(*k) {
+                    *k += 1;
+
+                    if self.state[e] == CROSS || e == parent {
+                        continue;
+                    }
+
+                    let v = self.other(e, u);
+
+                    if order[v] == usize::MAX {
+                        order[v] = time;
+                        low[v] = time;
+                        time += 1;
+                        stack.push((v, e, 0));
+                    } else {
+                        low[u] = low[u].min(order[v]);
+                    }
+                } else {
+                    stack.pop();
+
+                    if let Some(&(p, _, _)) = stack.last() {
+                        low[p] = low[p].min(low[u]);
+
+                        if low[u] > order[p] {
+                            res.push(parent);
+                        }
+                    }
+                }
+            }
+        }
+
+        res
+    }
+}
+
+// 前処理として推論の結果を節にする (線は少なくとも一方の向き, × は両方の向きを否定, 決まった数字は手がかりと同じ単位節)
+pub(crate) fn encode_deduction<F: ExtendFormula>(formula: &mut F, puzzle: &Puzzle, encoding: Encoding, numbers: &[Vec<usize>]) -> Result<(), Error> {
+    let deduction = deduce(puzzle)?;
+
+    if deduction.contradiction {
+        formula.add_clause(&[]);
+
+        return Ok(());
+    }
+
+    for &(u, v) in &deduction.lines {
+        let (x, y) = arc_pair(puzzle, u, v);
+
+        formula.add_clause(&[x.positive(), y.positive()]);
+    }
+
+    for &(u, v) in &deduction.crosses {
+        let (x, y) = arc_pair(puzzle, u, v);
+
+        formula.add_clause(&[x.negative()]);
+        formula.add_clause(&[y.negative()]);
+    }
+
+    for (u, &label) in deduction.labels.iter().enumerate() {
+        if puzzle.cells[u] == 0 && label != 0 {
+            encode_clue(formula, encoding, &numbers[u], label);
+        }
+    }
+
+    Ok(())
+}
+
+// u から v, v から u の arc の変数
+fn arc_pair(puzzle: &Puzzle, u: (usize, usize), v: (usize, usize)) -> (varisat::Var, varisat::Var) {
+    (find_arc_var(puzzle, (u, v)).unwrap(), find_arc_var(puzzle, (v, u)).unwrap())
+}
+
+// encode_deduction が足す節の数とリテラル数 (bits は数字 1 つの単位節の数)
+pub(crate) fn presolve_size(puzzle: &Puzzle, bits: u64) -> (u64, u64) {
+    let deduction = match deduce(puzzle) {
+        Ok(deduction) => deduction,
+        Err(_) => return (0, 0),
+    };
+
+    if deduction.contradiction {
+        return (1, 0);
+    }
+
+    let labeled = deduction.labels.iter().zip(&puzzle.cells).filter(|&(&label, &num)| num == 0 && label != 0).count() as u64;
+    let (lines, crosses) = (deduction.lines.len() as u64, deduction.crosses.len() as u64);
+
+    (lines+2*crosses+labeled*bits, 2*lines+2*crosses+labeled*bits)
+}
@@ -0,0 +1,65 @@
+use std::collections::BTreeSet;
+
+use crate::render::{cell_text, label_cells};
+use crate::{Puzzle, Sol, Topology, P};
+
+// 同じ盤面の 2 つの答えの違い. 辺は向きを除いて (行優先で先のセルを前に) 書く
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SolutionDiff {
+    // 片方の答えだけが使う辺
+    pub only_first: Vec<(P, P)>,
+    pub only_second: Vec<(P, P)>,
+    // 通る線の数字か, 出入りする辺の違うセル (行優先)
+    pub cells: Vec<P>,
+}
+
+impl SolutionDiff {
+    // 向きだけが違う (空白の閉路を逆にたどった) 答えどうしも同じとみなす
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+}
+
+pub fn diff(puzzle: &Puzzle, first: &Sol, second: &Sol) -> SolutionDiff {
+    let undirected = |sol: &Sol| sol.iter().map(|&(p, q)| (p.min(q), p.max(q))).collect::<BTreeSet<_>>();
+    let (a, b) = (undirected(first), undirected(second));
+    let (x, y) = (label_cells(puzzle, first), label_cells(puzzle, second));
+    let cells: BTreeSet<P> = a.symmetric_difference(&b).flat_map(|&(p, q)| vec![p, q])
+        .chain((0..puzzle.cells.len()).filter(|&u| x.cells[u] != y.cells[u]).map(|u| puzzle.pos(u)))
+        .collect();
+
+    SolutionDiff {
+        only_first: a.difference(&b).cloned().collect(),
+        only_second: b.difference(&a).cloned().collect(),
+        cells: cells.into_iter().collect(),
+    }
+}
+
+// 2 つの答えを左右に並べ, 右端に違うセルを `*` で示す. グラフの盤面は片方だけが使う辺を並べる
+pub fn render_diff(puzzle: &Puzzle, first: &Sol, second: &Sol) -> String {
+    let d = diff(puzzle, first, second);
+    let mut res = format!("{} cells differ, {} edges only in the first, {} only in the second\n", d.cells.len(), d.only_first.len(), d.only_second.len());
+
+    if let Topology::Graph(_) = puzzle.topology {
+        for (sign, edges) in [('-', &d.only_first), ('+', &d.only_second)] {
+            for &(p, q) in edges {
+                res.push_str(&format!("{} {} {}\n", sign, puzzle.index(p), puzzle.index(q)));
+            }
+        }
+
+        return res;
+    }
+
+    let (a, b) = (label_cells(puzzle, first), label_cells(puzzle, second));
+    let w = a.cells.iter().chain(&b.cells).cloned().max().unwrap_or(0).to_string().len();
+    let cells: BTreeSet<P> = d.cells.into_iter().collect();
+
+    for i in 0..puzzle.height {
+        let row = |labels: &Puzzle| (0..puzzle.width).map(|j| cell_text(puzzle, labels, (i, j), w)).collect::<Vec<_>>().join(" ");
+        let marks: Vec<String> = (0..puzzle.width).map(|j| format!("{:>w$}", if cells.contains(&(i, j)) { "*" } else { "." }, w = w)).collect();
+
+        res.push_str(&format!("{} | {} | {}\n", row(&a), row(&b), marks.join(" ")));
+    }
+
+    res
+}
@@ -0,0 +1,118 @@
+use crate::deduce::presolve_size;
+use crate::monitor::formula_memory;
+use crate::{blocked_arcs, gen_adjs, grid_adjs, parse_field, Encoding, Puzzle, Role, SolverConfig};
+
+// 節の種類 (論文の式番号) ごとの節数とリテラル数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Family {
+    pub name: &'static str,
+    pub clauses: u64,
+    pub lits: u64,
+}
+
+// encode が作る formula の大きさの見積もり (formula は作らない)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncodingEstimate {
+    pub vars: u64,
+    pub families: Vec<Family>,
+}
+
+impl EncodingEstimate {
+    pub fn clauses(&self) -> u64 {
+        self.families.iter().map(|family| family.clauses).sum()
+    }
+
+    pub fn lits(&self) -> u64 {
+        self.families.iter().map(|family| family.lits).sum()
+    }
+
+    // Session が max_memory と比べるのと同じ見積もり (バイト, 学習節は含まない)
+    pub fn memory(&self) -> u64 {
+        formula_memory(self.clauses(), self.lits(), self.vars)
+    }
+}
+
+// 手がかりが不正な盤面では役割の節を 0 として数える
+pub fn estimate(puzzle: &Puzzle, config: &SolverConfig) -> EncodingEstimate {
+    if puzzle.width == 0 || puzzle.height == 0 || puzzle.cells.len() != puzzle.width*puzzle.height {
+        return EncodingEstimate::default();
+    }
+
+    let n = puzzle.cells.iter().cloned().max().unwrap_or(0) as u64;
+    let cells = puzzle.cells.len() as u64;
+    let bits = match config.encoding {
+        Encoding::Log => (64-n.leading_zeros()) as u64,
+        Encoding::Direct => n,
+    };
+
+    let adjs = grid_adjs(puzzle.width, puzzle.height, puzzle.topology, &puzzle.warps);
+    let roles = parse_field(puzzle).unwrap_or_default();
+    let arcs: u64 = adjs.iter().map(|vs| vs.len() as u64).sum();
+    let choose2 = |k: usize| (k*k.saturating_sub(1)/2) as u64;
+    // 橋は軸ごとに高々 1 本
+    let pairs: u64 = adjs.iter().enumerate().map(|(u, vs)| match puzzle.is_bridge(u) {
+        true => (0..2).map(|axis| choose2(vs.iter().filter(|&&(_, d)| d%2 == axis).count())).sum(),
+        false => choose2(vs.len()),
+    }).sum();
+    let lanes = cells+puzzle.bridges.len() as u64;
+
+    let mut families = vec![];
+
+    if config.encoding == Encoding::Direct {
+        families.push(Family { name: "number domain", clauses: lanes*(1+n*n.saturating_sub(1)/2), lits: lanes*(n+n*n.saturating_sub(1)) });
+    }
+
+    families.push(Family { name: "(12) arc implies equal numbers", clauses: arcs*2*bits, lits: arcs*6*bits });
+    families.push(Family { name: "(2) no arc in both directions", clauses: arcs, lits: arcs*2 });
+    families.push(Family { name: "at most one out/in", clauses: pairs*2, lits: pairs*4 });
+
+    // 手がかり 1 つにつき単位節が log なら bit 数, direct なら 1 つ
+    let clues = roles.iter().filter(|&&r| r == Role::Start || r == Role::End || r == Role::Terminal || r == Role::Dot).count() as u64;
+    let units = clues*if config.encoding == Encoding::Log { bits } else { 1 };
+
+    families.push(Family { name: "(11) clues", clauses: units, lits: units });
+
+    let mut role = Family { name: "(3)-(9) roles", clauses: 0, lits: 0 };
+
+    for (vs, &r) in gen_adjs(puzzle).iter().zip(&roles) {
+        let degree = vs.len() as u64;
+
+        match r {
+            // 点は向きと番号の節が出入りの組ごとにあるが, 始点・終点と同じに数える
+            Role::Start | Role::End | Role::Dot => {
+                role.clauses += 1+degree;
+                role.lits += 2*degree;
+            },
+            Role::Blank => {
+                role.clauses += 2;
+                role.lits += 2*degree;
+            },
+            // 線なら出入りのどちらか, 木なら入る arc の 1 節 (線のネットの端の変数は数えない)
+            Role::Terminal => {
+                role.clauses += 1;
+                role.lits += 2*degree;
+            },
+            Role::Hole => {},
+            Role::Bridge => {
+                role.clauses += 4;
+                role.lits += 2*degree;
+            },
+        }
+    }
+
+    families.push(role);
+
+    let blocked = blocked_arcs(puzzle).len() as u64;
+
+    if blocked > 0 {
+        families.push(Family { name: "walls and holes", clauses: blocked, lits: blocked });
+    }
+
+    if config.presolve {
+        let (clauses, lits) = presolve_size(puzzle, if config.encoding == Encoding::Log { bits } else { 1 });
+
+        families.push(Family { name: "presolve", clauses, lits });
+    }
+
+    EncodingEstimate { vars: puzzle.topology.arc_var_count(puzzle.width, puzzle.height) as u64+lanes*bits+1, families }
+}
@@ -0,0 +1,38 @@
+use crate::render::render_svg;
+use crate::{Puzzle, Sol};
+
+// evcxr (Jupyter の Rust カーネル) は最後の式の値に evcxr_display があればそれを呼ぶ. 盤面と解は render_svg の SVG で出す
+//
+//     :dep numberlink_solver = { version = "*", features = ["evcxr"] }
+//     let url = "numlin/3/3/1ggggggg1";
+//     numberlink_solver::parse_puzzle(url).unwrap()                                                   // 盤面だけ
+//     numberlink_solver::evcxr::Solution::from(numberlink_solver::solve(url.to_string()).unwrap())   // 解の線も
+impl Puzzle {
+    pub fn evcxr_display(&self) {
+        print!("{}", content(self, &vec![]))
+    }
+}
+
+// 盤面とその解. solve の (Puzzle, Sol) から作る
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    pub puzzle: Puzzle,
+    pub sol: Sol,
+}
+
+impl From<(Puzzle, Sol)> for Solution {
+    fn from((puzzle, sol): (Puzzle, Sol)) -> Self {
+        Solution { puzzle, sol }
+    }
+}
+
+impl Solution {
+    pub fn evcxr_display(&self) {
+        print!("{}", content(&self.puzzle, &self.sol))
+    }
+}
+
+// evcxr_display が書く 1 つの出力 (render_svg は改行で終わるので, 終わりの印は行の頭に来る)
+pub fn content(puzzle: &Puzzle, sol: &Sol) -> String {
+    format!("EVCXR_BEGIN_CONTENT image/svg+xml\n{}EVCXR_END_CONTENT\n", render_svg(puzzle, sol))
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::atomic::Ordering;
+use std::sync::Arc as Shared;
+
+use varisat::Var;
+
+use crate::monitor::{Control, Monitor};
+use crate::{var_arc, Arc, Puzzle, Sol};
+
+// 探索の途中で起きたこと. 可視化や分析のために, ソルバのループに手を入れずに外から受け取る
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    // 変数の値がこの先ずっと変わらないと決まった (学習した単位節とトップレベルの単位伝播). varisat だけ
+    // var は符号化の変数番号 (arc の変数なら arc_of で arc に直せる)
+    Fixed { var: usize, value: bool },
+    // 衝突 (学習節) が 1 つ増えた. conflicts は Session を作ってからの通算
+    // (varisat は証明の学習節の step で数えるので, 単位節で短くした節も 1 つに数える)
+    Conflict { conflicts: u64 },
+    // 再始動. restarts は Session を作ってからの通算. 再始動を知らせるのは minisat だけ
+    // (varisat は証明に書かず, cadical と IPASIR のライブラリ, 外部のソルバは外から分かる形で知らせない)
+    Restart { restarts: u64 },
+    // Session が解を返す
+    Model { sol: Sol },
+}
+
+// 受け取る側. ソルバのスレッドから呼ぶので, 重い処理は別のスレッドに渡す
+pub trait Subscriber: Send + Sync {
+    fn event(&self, event: &Event);
+}
+
+impl<F: Fn(&Event) + Send + Sync> Subscriber for F {
+    fn event(&self, event: &Event) {
+        self(event)
+    }
+}
+
+// SolverConfig::events に登録した受け取る側. 同じ config で解いた Session (portfolio の各スレッドなど) の出来事が混ざって届く
+#[derive(Clone, Default)]
+pub struct Events {
+    subscribers: Vec<Shared<dyn Subscriber>>,
+}
+
+impl Events {
+    pub fn subscribe(&mut self, subscriber: impl Subscriber + 'static) {
+        self.subscribers.push(Shared::new(subscriber));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscribers.is_empty()
+    }
+
+    pub(crate) fn publish(&self, event: &Event) {
+        for subscriber in &self.subscribers {
+            subscriber.event(event);
+        }
+    }
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Events({} subscribers)", self.subscribers.len())
+    }
+}
+
+// Fixed の変数が arc の変数ならその arc
+pub fn arc_of(puzzle: &Puzzle, var: usize) -> Option<Arc> {
+    var_arc(Var::from_index(var), puzzle)
+}
+
+// 書き出しておいた varisat 形式の証明 (Solver::write_proof) を読み直し, 解いていたときと同じ Fixed と Conflict を
+// events に流す (Model は証明から分からないので流さない). var はソルバに渡した変数番号のまま. 学習節の数を返す
+pub fn replay_proof(mut proof: impl Read, events: &Events) -> io::Result<u64> {
+    let control = Shared::new(Control { events: events.clone(), ..Control::default() });
+
+    io::copy(&mut proof, &mut Monitor::new(control.clone()))?;
+
+    Ok(control.conflicts.load(Ordering::Relaxed))
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use crate::phase::Phase;
+use crate::{Encoding, Error, Puzzle, Session, SolverConfig};
+
+// 設定の組み合わせごとにコーパス全体を解いた結果 (時間と conflict 数は探索部分だけ)
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub name: String,
+    pub solved: usize,
+    pub unsolved: usize,
+    pub failed: usize,
+    pub elapsed: Duration,
+    pub conflicts: u64,
+    // 最速だった問題の数
+    pub wins: usize,
+}
+
+// 符号化 x 初期 phase x seed の全組み合わせ (seed 0 は並べ替えなし)
+pub fn matrix(base: &SolverConfig, encodings: &[Encoding], phases: &[Phase], seeds: u64) -> Vec<(String, SolverConfig)> {
+    let mut res = vec![];
+
+    for &encoding in encodings {
+        for &phase in phases {
+            for seed in 0..seeds.max(1) {
+                let config = SolverConfig { encoding, phase, seed, ..base.clone() };
+
+                res.push((format!("{:?}/{:?}/seed={}", encoding, phase, seed).to_lowercase(), config));
+            }
+        }
+    }
+
+    res
+}
+
+// 計時が干渉しないよう 1 つずつ順に解く
+pub fn run(puzzles: &[Puzzle], trials: &[(String, SolverConfig)]) -> Vec<Summary> {
+    let mut summaries: Vec<Summary> = trials.iter().map(|(name, _)| Summary { name: name.clone(), ..Summary::default() }).collect();
+
+    for puzzle in puzzles {
+        let mut best: Option<(Duration, usize)> = None;
+
+        for (k, (_, config)) in trials.iter().enumerate() {
+            let (res, stats) = match Session::new(puzzle, config) {
+                Ok(mut session) => {
+                    let res = session.solve();
+
+                    (res, session.stats())
+                },
+                Err(e) => (Err(e), Default::default()),
+            };
+
+            let summary = &mut summaries[k];
+
+            summary.elapsed += stats.elapsed;
+            summary.conflicts += stats.conflicts;
+
+            match res {
+                Ok(_) | Err(Error::NoSolution) => {
+                    summary.solved += 1;
+
+                    if best.is_none_or(|(elapsed, _)| stats.elapsed < elapsed) {
+                        best = Some((stats.elapsed, k));
+                    }
+                },
+                Err(Error::Timeout(_)) | Err(Error::MemoryLimit(_)) => summary.unsolved += 1,
+                Err(Error::Interrupted(_)) => return summaries,
+                Err(_) => summary.failed += 1,
+            }
+        }
+
+        if let Some((_, k)) = best {
+            summaries[k].wins += 1;
+        }
+    }
+
+    summaries
+}
+
+pub fn render_table(summaries: &[Summary]) -> String {
+    let width = summaries.iter().map(|summary| summary.name.len()).max().unwrap_or(0).max("config".len());
+
+    let mut res = format!("{:<w$}  {:>6}  {:>8}  {:>6}  {:>10}  {:>12}  {:>5}\n", "config", "solved", "unsolved", "failed", "time", "conflicts", "wins", w = width);
+
+    for summary in summaries {
+        res.push_str(&format!(
+            "{:<w$}  {:>6}  {:>8}  {:>6}  {:>9.3}s  {:>12}  {:>5}\n",
+            summary.name, summary.solved, summary.unsolved, summary.failed, summary.elapsed.as_secs_f64(), summary.conflicts, summary.wins, w = width,
+        ));
+    }
+
+    res
+}
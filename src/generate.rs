@@ -0,0 +1,574 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::deduce::deduce;
+use crate::predict::{predict, score, Difficulty};
+use crate::render::{paths, render, render_svg, render_url, Format, Theme};
+use crate::rng::Rng;
+use crate::{Puzzle, Session, Sol, SolverConfig, SystemTime, P, UNIX_EPOCH};
+
+// 1 つの seed で試す盤面の数
+const ATTEMPTS: usize = 20;
+
+// 難しさを合わせるとき, 試す組の数の候補の数
+const TARGET_PAIRS: usize = 5;
+
+// 目標の score に合わせるときの幅 (score の 1 割)
+const SCORE_TOLERANCE: f64 = 0.1;
+
+// つなげる道がなくなったとき, 道の形を変えてやり直す回数とそのたびのつなぎ替えの数
+const STUCK: usize = 20;
+const REWIRES: usize = 20;
+
+// 作る盤面のセルの数の上限 (gen と serve の /generate). 生成は解き直しを繰り返すので, 読み込める盤面よりずっと小さく抑える
+pub const MAX_GENERATE_CELLS: usize = 40*40;
+
+// daily_seed の日ごとの間隔 (黄金比の 2^64 倍)
+const DAILY_STRIDE: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// 道 a の末尾 (rev_a なら先頭) と道 b の先頭 (rev_b なら末尾) をつなぐ
+type Merge = (usize, usize, bool, bool);
+
+// 作る盤面の大きさと数字の組の数, 乱数の seed
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenConfig {
+    pub width: usize,
+    pub height: usize,
+    // target があれば, 難しさが合う組の数のうちこれに近いもの
+    pub pairs: usize,
+    pub seed: u64,
+    pub target: Option<Target>,
+    // 数字 (と穴) のセルの配置の対称性
+    pub symmetry: Symmetry,
+    // 意図した解の出来がどれもこれ以上の盤面だけを返す
+    pub min_quality: Quality,
+    // どれかに当てはまるつまらない盤面は捨てる
+    pub reject: BTreeSet<Trivial>,
+}
+
+impl Default for GenConfig {
+    fn default() -> Self {
+        GenConfig { width: 10, height: 10, pairs: 8, seed: 0, target: None, symmetry: Symmetry::None, min_quality: Quality::default(), reject: BTreeSet::new() }
+    }
+}
+
+// 目標の難しさ: predict の区分か, Prediction::score の値 (1 割の幅で合わせる)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Tier(Difficulty),
+    Score(f64),
+}
+
+impl Target {
+    pub fn matches(&self, score: f64) -> bool {
+        match *self {
+            Target::Tier(difficulty) => Difficulty::of(score) == difficulty,
+            Target::Score(target) => (score-target).abs() <= target*SCORE_TOLERANCE,
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<f64>() {
+            Ok(score) if score > 0.0 => Ok(Target::Score(score)),
+            _ => s.parse().map(Target::Tier).map_err(|_| format!("unknown difficulty `{}` (expected trivial, easy, medium, hard or a score)", s)),
+        }
+    }
+}
+
+// 盤面を写して数字のセルの配置が変わらないようにする写し方 (数字そのものは揃えない)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    // 180 度回転
+    Rotational,
+    // 90 度回転 (正方形の盤面だけ)
+    Quarter,
+    // 左右の鏡映
+    Horizontal,
+    // 上下の鏡映
+    Vertical,
+}
+
+impl FromStr for Symmetry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Symmetry::None),
+            "rotational" => Ok(Symmetry::Rotational),
+            "quarter" => Ok(Symmetry::Quarter),
+            "horizontal" => Ok(Symmetry::Horizontal),
+            "vertical" => Ok(Symmetry::Vertical),
+            _ => Err(format!("unknown symmetry `{}` (expected none, rotational, quarter, horizontal or vertical)", s)),
+        }
+    }
+}
+
+impl Symmetry {
+    // width x height の盤面で p を 1 回写した先
+    fn map(self, width: usize, height: usize, (i, j): P) -> P {
+        match self {
+            Symmetry::None => (i, j),
+            Symmetry::Rotational => (height-1-i, width-1-j),
+            Symmetry::Quarter => (j, width-1-i),
+            Symmetry::Horizontal => (i, width-1-j),
+            Symmetry::Vertical => (height-1-i, j),
+        }
+    }
+
+    // 何回写すと元に戻るか
+    fn order(self) -> usize {
+        match self {
+            Symmetry::None => 1,
+            Symmetry::Quarter => 4,
+            _ => 2,
+        }
+    }
+}
+
+// 意図した解 (作ったときの道) の出来の目安. 大きいほど面白いとみなす
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Quality {
+    // 道が通るセルの割合 (残りは穴)
+    pub fill: f64,
+    // 道 1 本あたりのセルの数
+    pub length: f64,
+    // 道が曲がる回数の合計
+    pub bends: usize,
+    // 数字のセルから一番近い別の数字のセルまでの距離 (マンハッタン) の平均
+    pub spread: f64,
+}
+
+impl Quality {
+    pub fn meets(&self, min: &Quality) -> bool {
+        self.fill >= min.fill && self.length >= min.length && self.bends >= min.bends && self.spread >= min.spread
+    }
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "fill {:.2}, length {:.1}, {} bends, spread {:.2}", self.fill, self.length, self.bends, self.spread)
+    }
+}
+
+// 作った盤面のつまらなさ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Trivial {
+    // 意図した解に曲がらない道がある
+    Straight,
+    // 両端が隣り合う道がある
+    Adjacent,
+    // 推論 (deduce) だけで解ける
+    Deduced,
+}
+
+impl FromStr for Trivial {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "straight" => Ok(Trivial::Straight),
+            "adjacent" => Ok(Trivial::Adjacent),
+            "deduced" => Ok(Trivial::Deduced),
+            _ => Err(format!("unknown filter `{}` (expected straight, adjacent or deduced)", s)),
+        }
+    }
+}
+
+impl Trivial {
+    // paths を意図した解とする盤面 puzzle が当てはまるか
+    fn applies(self, puzzle: &Puzzle, paths: &[Vec<P>]) -> bool {
+        let paths = paths.iter().filter(|path| path.len() >= 2);
+
+        match self {
+            Trivial::Straight => paths.into_iter().any(|path| path.iter().all(|p| p.0 == path[0].0) || path.iter().all(|p| p.1 == path[0].1)),
+            Trivial::Adjacent => paths.into_iter().any(|path| adjacent(path[0], path[path.len()-1])),
+            Trivial::Deduced => deduce(puzzle).is_ok_and(|deduction| deduction.solved()),
+        }
+    }
+}
+
+// 作った盤面の書き出し方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    // puzz.link の URL
+    Url,
+    // parse_text で読める盤面
+    Text,
+    Svg,
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "url" => Ok(Output::Url),
+            "text" => Ok(Output::Text),
+            "svg" => Ok(Output::Svg),
+            _ => Err(format!("unknown output `{}` (expected url, text or svg)", s)),
+        }
+    }
+}
+
+// width x height の盤面に pairs 組の数字を置いた, 答えがただ 1 つの Numberlink
+pub fn generate(width: usize, height: usize, pairs: usize, seed: u64) -> Option<Puzzle> {
+    generate_with(&GenConfig { width, height, pairs, seed, ..GenConfig::default() })
+}
+
+// 1 セルずつの道から始めて, 隣り合う道の端を別解ができない (Session::is_unique) ようにつないでいく
+// target があれば, predict の難しさが合う組の数を config.pairs に近い順に試す
+// symmetry があれば, つなぐたびに写した先の道の端もつなぎ, 道の組 (したがって数字と穴の配置) を対称に保つ
+// config だけで決まる (同じ config なら同じ盤面を返す). 試した盤面がどれも pairs 組まで減らせなければ None
+pub fn generate_with(config: &GenConfig) -> Option<Puzzle> {
+    let (width, height) = (config.width, config.height);
+    let mut rng = Rng::new(config.seed);
+
+    if config.symmetry == Symmetry::Quarter && width != height {
+        return None;
+    }
+
+    let mut candidates: Vec<usize> = match config.target {
+        Some(target) => (1..=width*height/2).filter(|&pairs| target.matches(score(width*height, 2*pairs))).collect(),
+        None => vec![config.pairs],
+    };
+
+    candidates.sort_by_key(|&pairs| pairs.abs_diff(config.pairs));
+
+    // 作った盤面から測った難しさも確かめる
+    candidates.into_iter().filter(|&pairs| pairs > 0 && 2*pairs <= width*height).take(TARGET_PAIRS).find_map(|pairs| {
+        (0..ATTEMPTS)
+            .find_map(|_| {
+                let paths = attempt(width, height, pairs, config.symmetry, &mut rng)?;
+                let puzzle = puzzle(width, height, &paths);
+
+                (path_quality(width, height, &paths).meets(&config.min_quality) && !config.reject.iter().any(|trivial| trivial.applies(&puzzle, &paths))).then_some(puzzle)
+            })
+            .filter(|puzzle| config.target.is_none_or(|target| target.matches(predict(puzzle).score)))
+    })
+}
+
+// sol を意図した解とみなした puzzle の出来
+pub fn quality(puzzle: &Puzzle, sol: &Sol) -> Quality {
+    let paths: Vec<Vec<P>> = paths(puzzle, sol).into_iter().map(|(_, path)| path).collect();
+
+    path_quality(puzzle.width, puzzle.height, &paths)
+}
+
+// 道 (セル列) の両端に数字を置いた盤面 (どの道も通らないセルは穴にする)
+// 道が盤面の外に出るか, 重なるか, 隣り合わないセルをつなぐか, 1 セルしかなければ None
+pub fn from_paths(width: usize, height: usize, paths: &[Vec<P>]) -> Option<Puzzle> {
+    let mut covered = vec![false; width*height];
+
+    for path in paths {
+        if path.len() < 2 || path.windows(2).any(|w| !adjacent(w[0], w[1])) {
+            return None;
+        }
+
+        for &(i, j) in path {
+            if i >= height || j >= width || covered[i*width+j] {
+                return None;
+            }
+
+            covered[i*width+j] = true;
+        }
+    }
+
+    let holes = (0..width*height).filter(|&u| !covered[u]).map(|u| vec![(u/width, u%width)]);
+
+    Some(puzzle(width, height, &paths.iter().cloned().chain(holes).collect::<Vec<_>>()))
+}
+
+// YYYY-MM-DD の日付から決まる seed (日ごとに離れた値にして, --count で続く seed が次の日と重ならないようにする)
+pub fn daily_seed(date: &str) -> Option<u64> {
+    let mut parts = date.trim().splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    let days = days_from_civil(year, month, day)?;
+
+    Some((days as u64).wrapping_mul(DAILY_STRIDE))
+}
+
+// 今日 (UTC) の YYYY-MM-DD
+pub fn today() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs()/86400) as i64;
+    // 1970-03-01 からの日数で数えると閏日が年の最後に来る
+    let (era, day) = ((days+719468).div_euclid(146097), (days+719468).rem_euclid(146097));
+    let year_of_era = (day - day/1460 + day/36524 - day/146096)/365;
+    let day_of_year = day - (365*year_of_era + year_of_era/4 - year_of_era/100);
+    let month = (5*day_of_year+2)/153;
+    let (d, m) = (day_of_year - (153*month+2)/5 + 1, if month < 10 { month+3 } else { month-9 });
+    let y = year_of_era + era*400 + (m <= 2) as i64;
+
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+// 1970-01-01 からの日数 (ありえない日付は None)
+fn days_from_civil(year: i64, month: i64, day: i64) -> Option<i64> {
+    let leap = year%4 == 0 && (year%100 != 0 || year%400 == 0);
+    let lengths = [31, if leap { 29 } else { 28 }, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+    if !(1..=12).contains(&month) || day < 1 || day > lengths[month as usize-1] {
+        return None;
+    }
+
+    let y = if month <= 2 { year-1 } else { year };
+    let (era, year_of_era) = (y.div_euclid(400), y.rem_euclid(400));
+    let day_of_year = (153*((month+9)%12)+2)/5 + day-1;
+    let day_of_era = year_of_era*365 + year_of_era/4 - year_of_era/100 + day_of_year;
+
+    Some(era*146097 + day_of_era - 719468)
+}
+
+pub fn write_puzzle(puzzle: &Puzzle, output: Output) -> String {
+    match output {
+        Output::Url => render_url(puzzle)+"\n",
+        Output::Text => render(puzzle, &vec![], Format::Text, Theme::Plain),
+        Output::Svg => render_svg(puzzle, &vec![]),
+    }
+}
+
+// 答えがただ 1 つになった道の組 (1 セルの道は穴)
+fn attempt(width: usize, height: usize, pairs: usize, symmetry: Symmetry, rng: &mut Rng) -> Option<Vec<Vec<P>>> {
+    let mut paths: Vec<Vec<P>> = (0..height).flat_map(|i| (0..width).map(move |j| vec![(i, j)])).collect();
+    let mut stuck = 0;
+
+    while paths.len() > pairs {
+        let mut merges = merges(&paths);
+
+        // 1 セルの道をつなぐものと, つないだ道が自分自身と隣り合わないものを先に試す
+        rng.shuffle(&mut merges);
+        merges.sort_by_key(|&((a, b, _, _), touching)| (paths[a].len() > 1 && paths[b].len() > 1, touching));
+
+        let next = merges.into_iter()
+            .filter_map(|(merge, _)| symmetric_merged(&paths, merge, symmetry, width, height))
+            .find(|next| next.len() >= pairs && is_unique(width, height, next));
+
+        match next {
+            Some(next) => paths = next,
+            None if stuck < STUCK => {
+                // 別解ができない範囲で道の形を変えてからつなぎ直す
+                for _ in 0..REWIRES {
+                    if let Some(next) = rewired(&paths, rng, symmetry, width, height).filter(|next| is_unique(width, height, next)) {
+                        paths = next;
+                    }
+                }
+
+                stuck += 1;
+            },
+            None => return None,
+        }
+    }
+
+    paths.iter().all(|path| path.len() >= 2).then_some(paths)
+}
+
+// 1 セルの道 (穴) は数えない
+fn path_quality(width: usize, height: usize, paths: &[Vec<P>]) -> Quality {
+    let paths: Vec<&Vec<P>> = paths.iter().filter(|path| path.len() >= 2).collect();
+    let cells: usize = paths.iter().map(|path| path.len()).sum();
+    let bends = paths.iter().map(|path| path.windows(3).filter(|w| w[0].0 != w[2].0 && w[0].1 != w[2].1).count()).sum();
+    let ends: Vec<P> = paths.iter().flat_map(|path| vec![path[0], path[path.len()-1]]).collect();
+    let nearest = |&p: &P| ends.iter().filter(|&&q| q != p).map(|&q| p.0.abs_diff(q.0)+p.1.abs_diff(q.1)).min().unwrap_or(0);
+
+    Quality {
+        fill: cells as f64/(width*height).max(1) as f64,
+        length: cells as f64/paths.len().max(1) as f64,
+        bends,
+        spread: ends.iter().map(nearest).sum::<usize>() as f64/ends.len().max(1) as f64,
+    }
+}
+
+// 端が隣り合う 2 本の道のつなぎ方と, つないだ道が自分自身と (つないだところ以外で) 隣り合うか
+fn merges(paths: &[Vec<P>]) -> Vec<(Merge, bool)> {
+    let mut res = vec![];
+
+    for a in 0..paths.len() {
+        for b in a+1..paths.len() {
+            for (rev_a, rev_b) in [(false, false), (false, true), (true, false), (true, true)] {
+                let x = if rev_a { paths[a][0] } else { paths[a][paths[a].len()-1] };
+                let y = if rev_b { paths[b][paths[b].len()-1] } else { paths[b][0] };
+
+                // 1 セルの道は向きを変えても同じ
+                if !adjacent(x, y) || (rev_a && paths[a].len() == 1) || (rev_b && paths[b].len() == 1) {
+                    continue;
+                }
+
+                let touching = paths[a].iter().any(|&p| paths[b].iter().any(|&q| adjacent(p, q) && (p, q) != (x, y)));
+
+                res.push(((a, b, rev_a, rev_b), touching));
+            }
+        }
+    }
+
+    res
+}
+
+fn merged(paths: &[Vec<P>], (a, b, rev_a, rev_b): Merge) -> Vec<Vec<P>> {
+    let mut res = paths.to_vec();
+    let mut path = res[a].clone();
+    let mut tail = res[b].clone();
+
+    if rev_a {
+        path.reverse();
+    }
+
+    if rev_b {
+        tail.reverse();
+    }
+
+    path.extend(tail);
+    res[a] = path;
+    res.remove(b);
+
+    res
+}
+
+// merge でつないだ端の組を symmetry で写した先も, それぞれの道の端どうしならつなぐ
+// 写した先が同じ道の中にあり, つないだところでなければ (輪になるので) None
+fn symmetric_merged(paths: &[Vec<P>], (a, b, rev_a, rev_b): Merge, symmetry: Symmetry, width: usize, height: usize) -> Option<Vec<Vec<P>>> {
+    let mut x = if rev_a { paths[a][0] } else { paths[a][paths[a].len()-1] };
+    let mut y = if rev_b { paths[b][paths[b].len()-1] } else { paths[b][0] };
+    let mut res = merged(paths, (a, b, rev_a, rev_b));
+
+    for _ in 1..symmetry.order() {
+        x = symmetry.map(width, height, x);
+        y = symmetry.map(width, height, y);
+
+        let a = res.iter().position(|path| path.contains(&x))?;
+        let b = res.iter().position(|path| path.contains(&y))?;
+
+        if a == b {
+            let path = &res[a];
+            let (k, l) = (path.iter().position(|&p| p == x)?, path.iter().position(|&p| p == y)?);
+
+            if k.abs_diff(l) != 1 {
+                return None;
+            }
+
+            continue;
+        }
+
+        let (rev_a, rev_b) = (res[a][0] == x && res[a].len() > 1, res[b][res[b].len()-1] == y && res[b].len() > 1);
+        let ends = |path: &Vec<P>, p: P| path[0] == p || path[path.len()-1] == p;
+
+        if !ends(&res[a], x) || !ends(&res[b], y) {
+            return None;
+        }
+
+        res = merged(&res, (a, b, rev_a, rev_b));
+    }
+
+    is_symmetric(&res, symmetry, width, height).then_some(res)
+}
+
+// 道の端 e と隣のセル c をランダムに選んでつなぎ替え (rewire), symmetry で写した先でも同じようにつなぎ替える
+// 写した先どうしがぶつかって対称でなくなれば None
+fn rewired(paths: &[Vec<P>], rng: &mut Rng, symmetry: Symmetry, width: usize, height: usize) -> Option<Vec<Vec<P>>> {
+    let path = &paths[rng.below(paths.len())];
+    let e = if rng.below(2) == 0 { path[0] } else { path[path.len()-1] };
+    let cells: Vec<P> = paths.iter().flatten().cloned().filter(|&c| adjacent(e, c)).collect();
+    let c = cells[rng.below(cells.len())];
+    let b = paths.iter().find(|path| path.contains(&c))?;
+    let k = b.iter().position(|&p| p == c)?;
+    // c を切り離すときに b に残る側の隣 (c が道の端ならもう一方はない)
+    let sides: Vec<P> = [k.checked_sub(1), Some(k+1)].iter().filter_map(|&l| b.get(l?)).cloned().collect();
+    let stay = if sides.is_empty() { c } else { sides[rng.below(sides.len())] };
+    let mut res = rewire(paths, e, c, stay)?;
+    let (mut e, mut c, mut stay) = (e, c, stay);
+
+    for _ in 1..symmetry.order() {
+        e = symmetry.map(width, height, e);
+        c = symmetry.map(width, height, c);
+        stay = symmetry.map(width, height, stay);
+
+        // 写した先がすでにつなぎ替わっていれば何もしない
+        if let Some(next) = rewire(&res, e, c, stay) {
+            res = next;
+        }
+    }
+
+    is_symmetric(&res, symmetry, width, height).then_some(res)
+}
+
+// 道の端 e を隣のセル c へつなぎ替える. 道の数は変えない
+// c が同じ道なら c のあとを逆順にし, 別の道なら stay と c の間で切って c の側を e に付ける (stay の側は 2 セル以上残す)
+fn rewire(paths: &[Vec<P>], e: P, c: P, stay: P) -> Option<Vec<Vec<P>>> {
+    let mut res = paths.to_vec();
+    let a = res.iter().position(|path| path[0] == e || path[path.len()-1] == e)?;
+    let b = res.iter().position(|path| path.contains(&c))?;
+
+    if res[a][0] == e {
+        res[a].reverse();
+    }
+
+    if b == a {
+        let k = res[a].iter().position(|&p| p == c)?;
+
+        // すぐ前のセルなら何も変わらない
+        if k+2 >= res[a].len() {
+            return None;
+        }
+
+        res[a][k+1..].reverse();
+
+        return Some(res);
+    }
+
+    if res[b].iter().position(|&p| p == stay)? > res[b].iter().position(|&p| p == c)? {
+        res[b].reverse();
+    }
+
+    match res[b].iter().position(|&p| p == c)? {
+        k if k >= 2 && res[b][k-1] == stay => {
+            let tail = res[b].split_off(k);
+
+            res[a].extend(tail);
+        },
+        _ => return None,
+    }
+
+    Some(res)
+}
+
+// 道の組が symmetry で写しても変わらないか (道の向きは問わない)
+fn is_symmetric(paths: &[Vec<P>], symmetry: Symmetry, width: usize, height: usize) -> bool {
+    let normalized = |paths: &mut dyn Iterator<Item = Vec<P>>| -> BTreeSet<Vec<P>> {
+        paths.map(|path| path.iter().rev().cloned().collect::<Vec<P>>().min(path)).collect()
+    };
+
+    normalized(&mut paths.iter().cloned()) == normalized(&mut paths.iter().map(|path| path.iter().map(|&p| symmetry.map(width, height, p)).collect()))
+}
+
+fn adjacent(p: P, q: P) -> bool {
+    p.0.abs_diff(q.0)+p.1.abs_diff(q.1) == 1
+}
+
+// 道の両端に 1 から順に数字を置いた盤面 (途中の 1 セルの道は穴にする)
+fn puzzle(width: usize, height: usize, paths: &[Vec<P>]) -> Puzzle {
+    let mut puzzle = Puzzle::new(width, height);
+    let mut label = 0;
+
+    for path in paths {
+        if path.len() == 1 {
+            puzzle.add_hole(path[0]);
+            continue;
+        }
+
+        label += 1;
+        puzzle.set(path[0], label);
+        puzzle.set(path[path.len()-1], label);
+    }
+
+    puzzle
+}
+
+fn is_unique(width: usize, height: usize, paths: &[Vec<P>]) -> bool {
+    let puzzle = puzzle(width, height, paths);
+
+    Session::new(&puzzle, &SolverConfig::default()).and_then(|mut session| session.is_unique()).unwrap_or(false)
+}
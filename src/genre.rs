@@ -0,0 +1,101 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::hashi::{self, parse_hashi, render_hashi, solve_hashi};
+use crate::masyu::{self, parse_masyu, render_masyu, solve_masyu};
+use crate::render::{render, Format, Theme};
+use crate::slither::{self, parse_slither, render_slither, solve_slither};
+use crate::yajilin::{self, parse_yajilin, render_yajilin, solve_yajilin};
+use crate::{solve_numberlink, try_parse_puzzle, Error, SolverConfig};
+
+// 解けるパズルの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Genre {
+    Numberlink,
+    Slither,
+    Masyu,
+    Yajilin,
+    Hashi,
+}
+
+impl Genre {
+    pub const ALL: [Genre; 5] = [Genre::Numberlink, Genre::Slither, Genre::Masyu, Genre::Yajilin, Genre::Hashi];
+
+    // puzz.link の URL でのパズルの名前
+    pub fn names(self) -> &'static [&'static str] {
+        match self {
+            Genre::Numberlink => &["numlin", "numberlink"],
+            Genre::Slither => slither::NAMES,
+            Genre::Masyu => masyu::NAMES,
+            Genre::Yajilin => yajilin::NAMES,
+            Genre::Hashi => hashi::NAMES,
+        }
+    }
+}
+
+impl FromStr for Genre {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "numberlink" => Ok(Genre::Numberlink),
+            "slither" => Ok(Genre::Slither),
+            "masyu" => Ok(Genre::Masyu),
+            "yajilin" => Ok(Genre::Yajilin),
+            "hashi" => Ok(Genre::Hashi),
+            _ => Err(format!("unknown genre `{}` (expected numberlink, slither, masyu, yajilin or hashi)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Genre {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Genre::Numberlink => "numberlink",
+            Genre::Slither => "slither",
+            Genre::Masyu => "masyu",
+            Genre::Yajilin => "yajilin",
+            Genre::Hashi => "hashi",
+        })
+    }
+}
+
+// URL の `?` のあと (か `/` で区切った部分) にあるパズルの名前から. 名前のない入力 (テキスト, JSON) は None
+pub fn detect(input: &str) -> Option<Genre> {
+    input.trim().split('/').find_map(|param| {
+        let name = param.rsplit('?').next()?;
+
+        Genre::ALL.iter().cloned().find(|genre| genre.names().contains(&name))
+    })
+}
+
+// genre のパズルとして読んで解き, format で書いた答え
+pub fn solve_genre(genre: Genre, input: &str, config: &SolverConfig, format: Format) -> Result<String, Error> {
+    match genre {
+        Genre::Numberlink => {
+            let puzzle = try_parse_puzzle(input)?;
+
+            solve_numberlink(&puzzle, config).map(|sol| render(&puzzle, &sol, format, Theme::Plain))
+        },
+        Genre::Slither => {
+            let puzzle = parse_slither(input).ok_or(Error::InvalidUrl)?;
+
+            solve_slither(&puzzle, config).map(|cycle| render_slither(&puzzle, &cycle, format))
+        },
+        Genre::Masyu => {
+            let puzzle = parse_masyu(input).ok_or(Error::InvalidUrl)?;
+
+            solve_masyu(&puzzle, config).map(|cycle| render_masyu(&puzzle, &cycle, format))
+        },
+        Genre::Yajilin => {
+            let puzzle = parse_yajilin(input).ok_or(Error::InvalidUrl)?;
+
+            solve_yajilin(&puzzle, config).map(|cycle| render_yajilin(&puzzle, &cycle, format))
+        },
+        Genre::Hashi => {
+            let puzzle = parse_hashi(input).ok_or(Error::InvalidUrl)?;
+
+            solve_hashi(&puzzle, config).map(|bridges| render_hashi(&puzzle, &bridges, format))
+        },
+    }
+}
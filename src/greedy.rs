@@ -0,0 +1,202 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::rng::Rng;
+use crate::{Puzzle, Sol};
+
+// route_greedy の既定の繰り返しの数 (組ごと)
+pub const ROUNDS: usize = 64;
+
+// 貪欲につないだ結果. sol はつながった組の道だけ (規則を満たす解とは限らない)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Routing {
+    pub sol: Sol,
+    // つながった数字とつながらなかった数字 (どちらも小さい順). 2 回ちょうど現れない数字はつながらない方
+    pub connected: Vec<usize>,
+    pub unconnected: Vec<usize>,
+}
+
+// SAT が時間内に解けなかったときの代わり. 数字の組を 1 つずつ最短路でつなぎ, 他の道に塞がれたら塞いだ道を
+// 剥がしてつなぎ直す (rip-up and reroute). 剥がされたセルほど次から通りにくくして, rounds × 組の数だけ繰り返す.
+// 途中で一番多くの組がつながった (同じなら道の短い) 配線を返す. 同じ seed なら同じ結果
+pub fn route_greedy(puzzle: &Puzzle, seed: u64, rounds: usize) -> Routing {
+    let nets = pairs(puzzle);
+    let mut adjs = vec![vec![]; puzzle.cells.len()];
+
+    for (u, v) in puzzle.edges() {
+        adjs[u].push(v);
+        adjs[v].push(u);
+    }
+
+    // 近い組から. 同じ距離の組の順は seed で混ぜる
+    let mut rng = Rng::new(seed);
+    let mut order: Vec<usize> = (0..nets.len()).collect();
+
+    rng.shuffle(&mut order);
+    order.sort_by_key(|&k| distance(&adjs, nets[k].1, nets[k].2));
+
+    let mut owner: Vec<Option<usize>> = vec![None; puzzle.cells.len()];
+    let mut history = vec![0; puzzle.cells.len()];
+    let mut routes: Vec<Option<Vec<usize>>> = vec![None; nets.len()];
+    let mut queue: VecDeque<usize> = order.into_iter().collect();
+    let mut best = routes.clone();
+
+    for &(_, s, t) in &nets {
+        owner[s] = Some(usize::MAX);
+        owner[t] = Some(usize::MAX);
+    }
+
+    for _ in 0..rounds*nets.len() {
+        let k = match queue.pop_front() {
+            Some(k) => k,
+            None => break,
+        };
+        let (_, s, t) = nets[k];
+        let path = match cheapest(puzzle, &adjs, &owner, &history, s, t) {
+            Some(path) => path,
+            None => continue,
+        };
+
+        // 通り道にある他の組の道を剥がして, 後でつなぎ直す
+        for &u in &path[1..path.len()-1] {
+            if let Some(other) = owner[u] {
+                for &v in &routes[other].take().unwrap()[1..] {
+                    if owner[v] == Some(other) {
+                        owner[v] = None;
+                    }
+                }
+
+                history[u] += 1;
+                queue.push_back(other);
+            }
+        }
+
+        for &u in &path[1..path.len()-1] {
+            owner[u] = Some(k);
+        }
+
+        routes[k] = Some(path);
+
+        let score = |routes: &[Option<Vec<usize>>]| {
+            let connected = routes.iter().flatten().count();
+
+            (connected, Reverse(routes.iter().flatten().map(|path| path.len()).sum::<usize>()))
+        };
+
+        if score(&routes) > score(&best) {
+            best = routes.clone();
+        }
+    }
+
+    let mut sol: Sol = best.iter().flatten().flat_map(|path| path.windows(2).map(|w| (puzzle.pos(w[0]), puzzle.pos(w[1])))).collect();
+
+    // 符号化の変数の順 (セルの番号, 向き) に並べる
+    sol.sort_by_key(|&(p, q)| (puzzle.index(p), puzzle.direction(p, q)));
+
+    let mut connected: Vec<usize> = nets.iter().zip(&best).filter(|(_, path)| path.is_some()).map(|(&(num, _, _), _)| num).collect();
+    let mut unconnected: Vec<usize> = nums(puzzle).into_iter().filter(|num| !connected.contains(num)).collect();
+
+    connected.sort_unstable();
+    unconnected.sort_unstable();
+
+    Routing { sol, connected, unconnected }
+}
+
+fn nums(puzzle: &Puzzle) -> Vec<usize> {
+    let mut nums: Vec<usize> = puzzle.cells.iter().cloned().filter(|&num| num > 0).collect();
+
+    nums.sort_unstable();
+    nums.dedup();
+    nums
+}
+
+// ちょうど 2 回現れる数字の (数字, 始点, 終点). 始点は番号の小さい方 (符号化の Start と同じ)
+fn pairs(puzzle: &Puzzle) -> Vec<(usize, usize, usize)> {
+    if puzzle.dots {
+        return vec![];
+    }
+
+    nums(puzzle).into_iter().filter_map(|num| {
+        let cells: Vec<usize> = (0..puzzle.cells.len()).filter(|&u| puzzle.cells[u] == num).collect();
+
+        match cells[..] {
+            [s, t] => Some((num, s, t)),
+            _ => None,
+        }
+    }).collect()
+}
+
+fn distance(adjs: &[Vec<usize>], s: usize, t: usize) -> usize {
+    let mut dist = vec![usize::MAX; adjs.len()];
+    let mut queue = VecDeque::from(vec![s]);
+
+    dist[s] = 0;
+
+    while let Some(u) = queue.pop_front() {
+        for &v in &adjs[u] {
+            if dist[v] == usize::MAX {
+                dist[v] = dist[u]+1;
+                queue.push_back(v);
+            }
+        }
+    }
+
+    dist[t]
+}
+
+// s から t への一番安い道. 空いたセルは 1 + 剥がされた回数, 他の組の道のセルはさらに盤面の広さだけ高い.
+// 数字, 橋, 盤外のセルは通らない
+fn cheapest(puzzle: &Puzzle, adjs: &[Vec<usize>], owner: &[Option<usize>], history: &[usize], s: usize, t: usize) -> Option<Vec<usize>> {
+    let passable = |u: usize| u == t || (owner[u] != Some(usize::MAX) && !puzzle.is_bridge(u) && !puzzle.is_outside(u) && !puzzle.is_hole(u));
+    let cost = |u: usize| 1+history[u]+if owner[u].is_some() && u != t { adjs.len() } else { 0 };
+    let mut dist = vec![usize::MAX; adjs.len()];
+    let mut prev = vec![usize::MAX; adjs.len()];
+    let mut heap = BinaryHeap::from(vec![Reverse((0, s))]);
+
+    dist[s] = 0;
+
+    while let Some(Reverse((d, u))) = heap.pop() {
+        if d > dist[u] {
+            continue;
+        }
+
+        if u == t {
+            let mut path = vec![t];
+
+            while *path.last().unwrap() != s {
+                path.push(prev[*path.last().unwrap()]);
+            }
+
+            path.reverse();
+
+            return Some(path);
+        }
+
+        for &v in &adjs[u] {
+            if passable(v) && d+cost(v) < dist[v] {
+                dist[v] = d+cost(v);
+                prev[v] = u;
+                heap.push(Reverse((dist[v], v)));
+            }
+        }
+    }
+
+    None
+}
+
+// "connected 3 of 5 pairs (1, 2, 4); unconnected: 3, 5"
+pub fn render_routing(routing: &Routing) -> String {
+    let list = |nums: &[usize]| nums.iter().map(|num| num.to_string()).collect::<Vec<String>>().join(", ");
+    let total = routing.connected.len()+routing.unconnected.len();
+    let mut res = format!("connected {} of {} pairs", routing.connected.len(), total);
+
+    if !routing.connected.is_empty() {
+        res.push_str(&format!(" ({})", list(&routing.connected)));
+    }
+
+    if !routing.unconnected.is_empty() {
+        res.push_str(&format!("; unconnected: {}", list(&routing.unconnected)));
+    }
+
+    res
+}
@@ -0,0 +1,242 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status as Code};
+
+use crate::generate::generate;
+use crate::input::{arcs, parse_paths};
+use crate::optimize::Objective;
+use crate::render::{paths, render_puzzle_json, render_url};
+use crate::rules::verify;
+use crate::server::{cancel_job, enqueue, solve_request, take, unique, Answer as Solved, Shared, Status, PROGRESS_INTERVAL};
+use crate::{try_parse_puzzle, Error, Instant, Progress as Counters, Puzzle, SolverConfig};
+
+// proto/numberlink.proto から build.rs が作ったメッセージとサービス. Rust からは
+// proto::numberlink_client::NumberlinkClient::new(Endpoint::from_shared("http://ADDR")?.connect().await?) として呼ぶ
+pub mod proto {
+    tonic::include_proto!("numberlink");
+}
+
+use proto::numberlink_server::{Numberlink, NumberlinkServer};
+use proto::{progress_event, Answer, Cell, CheckReply, CheckRequest, GenerateReply, GenerateRequest, Job, JobId, Path, Progress, ProgressEvent, SolveRequest};
+
+struct Service {
+    shared: Arc<Shared>,
+}
+
+// serve --grpc ADDR. HTTP のサーバと仕事の列, 覚えた答えと API キーを共有する
+// キーは metadata の authorization: Bearer KEY か x-api-key: KEY で付ける (なければ UNAUTHENTICATED, 上限を超えたら RESOURCE_EXHAUSTED)
+pub(crate) async fn serve(addr: String, shared: Arc<Shared>) -> Result<(), String> {
+    let addr = tokio::net::lookup_host(&addr).await.map_err(|e| format!("{}: {}", addr, e))?
+        .next().ok_or_else(|| format!("{}: no address", addr))?;
+    let keys = shared.clone();
+    let service = NumberlinkServer::with_interceptor(Service { shared }, move |request: Request<()>| authorize(&keys, request));
+
+    eprintln!("listening on grpc://{}", addr);
+    tonic::transport::Server::builder().add_service(service).serve(addr).await.map_err(|e| e.to_string())
+}
+
+fn authorize(shared: &Shared, request: Request<()>) -> Result<Request<()>, Code> {
+    if shared.server.keys.is_empty() {
+        return Ok(request);
+    }
+
+    let metadata = |name: &str| request.metadata().get(name).and_then(|value| value.to_str().ok());
+    let key = metadata("authorization").and_then(|value| value.strip_prefix("Bearer ")).or_else(|| metadata("x-api-key"));
+
+    let (key, &rate) = match key.and_then(|key| shared.server.keys.get_key_value(key)) {
+        Some(found) => found,
+        None => return Err(Code::unauthenticated("missing or unknown API key")),
+    };
+
+    match take(shared, key, rate) {
+        Ok(()) => Ok(request),
+        Err(wait) => Err(Code::resource_exhausted(format!("rate limit exceeded (retry after {}s)", wait.as_secs_f64().ceil().max(1.0)))),
+    }
+}
+
+#[tonic::async_trait]
+impl Numberlink for Service {
+    async fn solve(&self, request: Request<SolveRequest>) -> Result<Response<Answer>, Code> {
+        let (puzzle, objective) = read(request.get_ref())?;
+        let shared = self.shared.clone();
+
+        let res = tokio::task::spawn_blocking(move || solve_request(puzzle, objective, &shared.config, &shared)).await
+            .map_err(|e| Code::internal(e.to_string()))?;
+
+        res.map(|solved| Response::new(answer(&solved))).map_err(|e| failure(&e))
+    }
+
+    async fn check(&self, request: Request<CheckRequest>) -> Result<Response<CheckReply>, Code> {
+        let request = request.into_inner();
+        let puzzle = try_parse_puzzle(request.puzzle.trim()).map_err(|e| failure(&e))?;
+        let shared = self.shared.clone();
+
+        if !request.solution.trim().is_empty() {
+            let (_, _, drawn) = parse_paths(&request.solution).ok_or_else(|| Code::invalid_argument("invalid solution (expected each path as a chain of cells with the same name, or JSON paths)"))?;
+            let reply = match verify(&puzzle, &shared.config.rules, &arcs(&puzzle, &drawn)) {
+                Ok(()) => CheckReply { valid: Some(true), ..CheckReply::default() },
+                Err(reason) => CheckReply { valid: Some(false), reason, ..CheckReply::default() },
+            };
+
+            return Ok(Response::new(reply));
+        }
+
+        let res = tokio::task::spawn_blocking(move || unique(&puzzle, &shared)).await.map_err(|e| Code::internal(e.to_string()))?;
+
+        match res {
+            Ok(unique) => Ok(Response::new(CheckReply { solvable: Some(true), unique: Some(unique), ..CheckReply::default() })),
+            Err(Error::NoSolution) => Ok(Response::new(CheckReply { solvable: Some(false), unique: Some(false), ..CheckReply::default() })),
+            Err(e) => Err(failure(&e)),
+        }
+    }
+
+    async fn generate(&self, request: Request<GenerateRequest>) -> Result<Response<GenerateReply>, Code> {
+        let GenerateRequest { width, height, pairs, seed } = request.into_inner();
+
+        let res = tokio::task::spawn_blocking(move || generate(width as usize, height as usize, pairs as usize, seed)).await
+            .map_err(|e| Code::internal(e.to_string()))?;
+
+        match res {
+            // 穴のある盤面は URL に書けない
+            Some(puzzle) => Ok(Response::new(GenerateReply {
+                url: if puzzle.holes.is_empty() { render_url(&puzzle) } else { String::new() },
+                puzzle: render_puzzle_json(&puzzle).trim_end().to_string(),
+            })),
+            None => Err(Code::failed_precondition(format!("no {}x{} puzzle with {} pairs found", width, height, pairs))),
+        }
+    }
+
+    type ProgressStream = ReceiverStream<Result<ProgressEvent, Code>>;
+
+    async fn progress(&self, request: Request<SolveRequest>) -> Result<Response<Self::ProgressStream>, Code> {
+        let (puzzle, objective) = read(request.get_ref())?;
+        let shared = self.shared.clone();
+        let (tx, rx) = mpsc::channel(4);
+
+        // 要求ごとの進み具合と, 接続が切れたときに止める印
+        let counters = Arc::new(Counters::default());
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let config = SolverConfig { progress: Some(counters.clone()), interrupt: Some(interrupt.clone()), ..shared.config.clone() };
+        let start = Instant::now();
+
+        tokio::spawn(async move {
+            let mut task = tokio::task::spawn_blocking(move || solve_request(puzzle, objective, &config, &shared));
+
+            let last = loop {
+                match tokio::time::timeout(PROGRESS_INTERVAL, &mut task).await {
+                    Ok(res) => break match res {
+                        Ok(Ok(solved)) => Ok(ProgressEvent { event: Some(progress_event::Event::Result(answer(&solved))) }),
+                        Ok(Err(e)) => Err(failure(&e)),
+                        Err(e) => Err(Code::internal(e.to_string())),
+                    },
+                    Err(_) => {
+                        let event = ProgressEvent { event: Some(progress_event::Event::Progress(progress(&counters, start.elapsed().as_secs_f64()))) };
+
+                        if tx.send(Ok(event)).await.is_err() {
+                            interrupt.store(true, Ordering::SeqCst);
+                            return;
+                        }
+                    },
+                }
+            };
+
+            let _ = tx.send(last).await;
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn submit_job(&self, request: Request<SolveRequest>) -> Result<Response<JobId>, Code> {
+        let request = request.into_inner();
+
+        // 仕事の列は /jobs の本文と同じ形で積む
+        read(&request)?;
+        let body = serde_json::json!({ "puzzle": request.puzzle, "objective": objective_name(&request.objective) }).to_string();
+
+        match enqueue(&self.shared, body) {
+            Some(id) => Ok(Response::new(JobId { id: id.to_string() })),
+            None => Err(Code::resource_exhausted("too many jobs waiting")),
+        }
+    }
+
+    async fn get_job(&self, request: Request<JobId>) -> Result<Response<Job>, Code> {
+        job(&self.shared, &request.get_ref().id).map(Response::new)
+    }
+
+    async fn cancel_job(&self, request: Request<JobId>) -> Result<Response<Job>, Code> {
+        let id = &request.get_ref().id;
+
+        match id.parse().map(|id| cancel_job(&self.shared, id)) {
+            Ok(true) => job(&self.shared, id).map(Response::new),
+            _ => Err(Code::not_found("no such job")),
+        }
+    }
+}
+
+fn read(request: &SolveRequest) -> Result<(Puzzle, Objective), Code> {
+    let puzzle = try_parse_puzzle(request.puzzle.trim()).map_err(|e| failure(&e))?;
+    let objective = objective_name(&request.objective).parse::<Objective>().map_err(Code::invalid_argument)?;
+
+    Ok((puzzle, objective))
+}
+
+fn objective_name(objective: &str) -> &str {
+    match objective {
+        "" => "none",
+        _ => objective,
+    }
+}
+
+fn job(shared: &Shared, id: &str) -> Result<Job, Code> {
+    let jobs = shared.jobs.lock().unwrap();
+    let job = match id.parse().ok().and_then(|id| jobs.all.get(&id).map(|job| (id, job))) {
+        Some((id, job)) => match &job.status {
+            Status::Queued => Job { status: "queued".to_string(), position: jobs.waiting.iter().position(|&other| other == id).unwrap_or(0) as u32, ..Job::default() },
+            // 仕事は始めた時刻を持っていないので elapsed は 0
+            Status::Running => Job { status: "running".to_string(), progress: Some(progress(&job.progress, 0.0)), ..Job::default() },
+            Status::Finished(Ok(solved)) => Job { status: "done".to_string(), answer: Some(answer(solved)), ..Job::default() },
+            Status::Finished(Err(msg)) => Job { status: "failed".to_string(), error: msg.clone(), ..Job::default() },
+            Status::Cancelled => Job { status: "cancelled".to_string(), ..Job::default() },
+        },
+        None => return Err(Code::not_found("no such job")),
+    };
+
+    Ok(Job { id: id.to_string(), ..job })
+}
+
+fn progress(counters: &Counters, elapsed: f64) -> Progress {
+    Progress { conflicts: counters.conflicts(), bound: counters.bound(), elapsed }
+}
+
+fn answer(solved: &Solved) -> Answer {
+    // 点の盤面は結んだ 2 点に同じ数字を書いたものを返す (--format json と同じ)
+    let puzzle = match solved.puzzle.dots {
+        true => solved.puzzle.paired(&solved.sol),
+        false => solved.puzzle.clone(),
+    };
+    let cell = |(row, column): (usize, usize)| Cell { row: row as u32, column: column as u32 };
+
+    Answer {
+        width: puzzle.width as u32,
+        height: puzzle.height as u32,
+        field: puzzle.cells.iter().map(|&num| num as u32).collect(),
+        paths: paths(&puzzle, &solved.sol).into_iter().map(|(number, cells)| Path { number: number as u32, cells: cells.into_iter().map(cell).collect() }).collect(),
+        turns: solved.score.map(|(turns, _, _)| turns as u32),
+        cost: solved.score.map(|(_, cost, _)| cost as u32),
+        optimal: solved.score.map(|(_, _, optimal)| optimal),
+    }
+}
+
+// HTTP の 400, 422, 503 に当たるもの
+fn failure(e: &Error) -> Code {
+    match e {
+        Error::InvalidUrl | Error::InvalidField | Error::InvalidPin => Code::invalid_argument(e.to_string()),
+        Error::TooLarge(..) => Code::resource_exhausted(e.to_string()),
+        Error::NoSolution => Code::failed_precondition(e.to_string()),
+        Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_) => Code::unavailable(e.to_string()),
+        _ => Code::internal(e.to_string()),
+    }
+}
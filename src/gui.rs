@@ -0,0 +1,371 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc as Shared};
+use std::thread;
+use std::time::Duration;
+
+use eframe::egui::{self, Align2, Color32, FontId, Rect, Sense, Shape, Stroke, Vec2};
+use eframe::egui::ecolor::HsvaGamma;
+
+use crate::render::{paths, render_puzzle_json, render_url};
+use crate::{parse_puzzle, solve_numberlink, Error, Instant, Puzzle, Sol, SolverConfig, P};
+
+// セルの大きさ (px, render_svg と同じ)
+const CELL: f32 = 40.0;
+
+// 解の線を端から引き終えるまでの時間
+const ANIMATION: Duration = Duration::from_millis(1200);
+
+// エディタで置ける盤面の大きさの上限
+const MAX_SIZE: usize = 40;
+
+// クリックでセルに置くもの. 壁はクリックに近い辺に置く (もう一度で外す)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pen {
+    Number,
+    Hole,
+    Wall,
+    Erase,
+}
+
+struct Solving {
+    rx: mpsc::Receiver<Result<Sol, Error>>,
+    interrupt: Shared<AtomicBool>,
+    start: Instant,
+}
+
+struct App {
+    puzzle: Puzzle,
+    config: SolverConfig,
+    pen: Pen,
+    number: usize,
+    size: (usize, usize),
+    // 読み込みと書き出しの URL (または盤面のテキストか JSON)
+    url: String,
+    message: String,
+    solving: Option<Solving>,
+    // 解と, 線を引き始めた時刻
+    sol: Option<(Sol, Instant)>,
+}
+
+// numberlink_solver gui [URL]. puzzle がなければ 5x5 の空の盤面から. 解くときは config を使う (Stop で中断できる)
+pub fn run(puzzle: Option<Puzzle>, config: SolverConfig) -> Result<(), String> {
+    let puzzle = puzzle.unwrap_or_else(|| Puzzle::new(5, 5));
+    let app = App {
+        size: (puzzle.width, puzzle.height),
+        url: String::new(),
+        puzzle,
+        config,
+        pen: Pen::Number,
+        number: 1,
+        message: String::new(),
+        solving: None,
+        sol: None,
+    };
+    let options = eframe::NativeOptions { viewport: egui::ViewportBuilder::default().with_inner_size([720.0, 640.0]), ..eframe::NativeOptions::default() };
+
+    eframe::run_native("numberlink_solver", options, Box::new(|_| Ok(Box::new(app)))).map_err(|e| e.to_string())
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
+        self.poll();
+
+        egui::TopBottomPanel::top("controls").show(ctx, |ui| {
+            ui.horizontal(|ui| self.file_bar(ui, ctx));
+            ui.horizontal(|ui| self.edit_bar(ui));
+        });
+
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            ui.label(&self.message);
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::both().show(ui, |ui| self.board(ui));
+        });
+
+        // 解いている間と線を引いている間は描き直し続ける
+        let drawing = self.sol.as_ref().is_some_and(|(_, start)| start.elapsed() < ANIMATION);
+
+        if self.solving.is_some() || drawing {
+            ctx.request_repaint_after(Duration::from_millis(16));
+        }
+    }
+}
+
+impl App {
+    fn file_bar(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        ui.add(egui::TextEdit::singleline(&mut self.url).hint_text("puzz.link URL or board").desired_width(320.0));
+
+        if ui.button("Import").clicked() {
+            match parse_puzzle(self.url.trim()) {
+                Some(puzzle) => {
+                    self.size = (puzzle.width, puzzle.height);
+                    self.puzzle = puzzle;
+                    self.edited();
+                    self.message = "imported".to_string();
+                },
+                None => self.message = Error::InvalidUrl.to_string(),
+            }
+        }
+
+        if ui.button("Export").clicked() {
+            self.url = export(&self.puzzle);
+            ctx.copy_text(self.url.clone());
+            self.message = "copied to the clipboard".to_string();
+        }
+
+        ui.separator();
+
+        match self.solving {
+            Some(_) => {
+                if ui.button("Stop").clicked() {
+                    self.stop();
+                }
+
+                ui.spinner();
+            },
+            None => {
+                if ui.button("Solve").clicked() {
+                    self.solve();
+                }
+            },
+        }
+    }
+
+    fn edit_bar(&mut self, ui: &mut egui::Ui) {
+        ui.label("Size");
+
+        let width = ui.add(egui::DragValue::new(&mut self.size.0).range(1..=MAX_SIZE));
+        ui.label("x");
+        let height = ui.add(egui::DragValue::new(&mut self.size.1).range(1..=MAX_SIZE));
+
+        if width.changed() || height.changed() {
+            self.resize();
+        }
+
+        if ui.button("Clear").clicked() {
+            self.puzzle = Puzzle::new(self.puzzle.width, self.puzzle.height);
+            self.edited();
+        }
+
+        ui.separator();
+
+        for (pen, name) in [(Pen::Number, "Number"), (Pen::Hole, "Hole"), (Pen::Wall, "Wall"), (Pen::Erase, "Erase")] {
+            ui.selectable_value(&mut self.pen, pen, name);
+        }
+
+        ui.add_enabled(self.pen == Pen::Number, egui::DragValue::new(&mut self.number).range(1..=255));
+    }
+
+    fn board(&mut self, ui: &mut egui::Ui) {
+        let puzzle = &self.puzzle;
+        let size = Vec2::new(puzzle.width as f32*CELL+2.0, puzzle.height as f32*CELL+2.0);
+        let (response, painter) = ui.allocate_painter(size, Sense::click());
+        let origin = response.rect.min+Vec2::splat(1.0);
+        let corner = |i: usize, j: usize| origin+Vec2::new(j as f32*CELL, i as f32*CELL);
+        let centre = |(i, j): P| corner(i, j)+Vec2::splat(CELL/2.0);
+        let thin = Stroke::new(0.5, Color32::BLACK);
+        let thick = Stroke::new(2.0, Color32::BLACK);
+
+        painter.rect_filled(Rect::from_min_size(origin, size-Vec2::splat(2.0)), 0.0, Color32::WHITE);
+
+        for u in (0..puzzle.cells.len()).filter(|&u| puzzle.is_hole(u)) {
+            let (i, j) = puzzle.pos(u);
+
+            painter.rect_filled(Rect::from_min_size(corner(i, j), Vec2::splat(CELL)), 0.0, Color32::from_gray(0xcc));
+        }
+
+        for i in 1..puzzle.height {
+            painter.line_segment([corner(i, 0), corner(i, puzzle.width)], thin);
+        }
+
+        for j in 1..puzzle.width {
+            painter.line_segment([corner(0, j), corner(puzzle.height, j)], thin);
+        }
+
+        painter.rect_stroke(Rect::from_min_max(corner(0, 0), corner(puzzle.height, puzzle.width)), 0.0, thick, egui::StrokeKind::Middle);
+
+        // 壁は v (右か下のセル) の左か上の辺
+        for &(u, v) in &puzzle.walls {
+            let ((i, _), (k, l)) = (puzzle.pos(u), puzzle.pos(v));
+
+            match i == k {
+                true => painter.line_segment([corner(k, l), corner(k+1, l)], thick),
+                false => painter.line_segment([corner(k, l), corner(k, l+1)], thick),
+            };
+        }
+
+        // 線は始点から伸ばしていき, どの経路も ANIMATION で引き終える
+        if let Some((sol, start)) = &self.sol {
+            let t = (start.elapsed().as_secs_f32()/ANIMATION.as_secs_f32()).min(1.0);
+
+            for (num, path) in paths(puzzle, sol) {
+                let drawn = drawn(&path, t).into_iter().map(|(x, y)| origin+Vec2::new(x, y)*CELL).collect();
+
+                painter.add(Shape::line(drawn, Stroke::new(6.0, colour(num))));
+            }
+        }
+
+        for (u, &num) in puzzle.cells.iter().enumerate().filter(|&(_, &num)| num > 0) {
+            painter.text(centre(puzzle.pos(u)), Align2::CENTER_CENTER, num.to_string(), FontId::proportional(24.0), Color32::BLACK);
+        }
+
+        if let Some(pos) = response.interact_pointer_pos().filter(|_| response.clicked()) {
+            let (x, y) = ((pos-origin).x/CELL, (pos-origin).y/CELL);
+
+            if x >= 0.0 && y >= 0.0 && (x as usize) < puzzle.width && (y as usize) < puzzle.height {
+                self.click((y as usize, x as usize), (x.fract(), y.fract()));
+            }
+        }
+    }
+
+    fn click(&mut self, p: P, at: (f32, f32)) {
+        if self.solving.is_some() {
+            return;
+        }
+
+        if edit(&mut self.puzzle, self.pen, self.number, p, at) {
+            self.edited();
+        }
+    }
+
+    fn resize(&mut self) {
+        self.puzzle = resized(&self.puzzle, self.size.0, self.size.1);
+        self.edited();
+    }
+
+    fn edited(&mut self) {
+        self.stop();
+        self.sol = None;
+        self.message.clear();
+    }
+
+    fn solve(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        let interrupt = Shared::new(AtomicBool::new(false));
+        let puzzle = self.puzzle.clone();
+        let config = SolverConfig { interrupt: Some(interrupt.clone()), ..self.config.clone() };
+
+        thread::spawn(move || {
+            let _ = tx.send(solve_numberlink(&puzzle, &config));
+        });
+
+        self.sol = None;
+        self.message = "solving...".to_string();
+        self.solving = Some(Solving { rx, interrupt, start: Instant::now() });
+    }
+
+    fn stop(&mut self) {
+        if let Some(solving) = self.solving.take() {
+            solving.interrupt.store(true, Ordering::SeqCst);
+            self.message = "stopped".to_string();
+        }
+    }
+
+    fn poll(&mut self) {
+        let res = match self.solving.as_ref().map(|solving| solving.rx.try_recv()) {
+            Some(Ok(res)) => res,
+            Some(Err(mpsc::TryRecvError::Disconnected)) => Err(Error::BackendFailed("the solver stopped".to_string())),
+            _ => return,
+        };
+        let elapsed = self.solving.take().map_or(Duration::ZERO, |solving| solving.start.elapsed());
+
+        match res {
+            Ok(sol) => {
+                self.message = format!("solved in {:.2}s", elapsed.as_secs_f64());
+                self.sol = Some((sol, Instant::now()));
+            },
+            Err(e) => self.message = e.to_string(),
+        }
+    }
+}
+
+// p に pen で置く. (fx, fy) はセルの中のクリックの位置 (0..1). 盤面の端の辺に壁を置こうとしたときだけ何もせずに false
+pub fn edit(puzzle: &mut Puzzle, pen: Pen, number: usize, p: P, (fx, fy): (f32, f32)) -> bool {
+    let u = puzzle.index(p);
+
+    match pen {
+        Pen::Number => {
+            puzzle.holes.remove(&u);
+            puzzle.set(p, number);
+        },
+        Pen::Hole => {
+            puzzle.set(p, 0);
+            puzzle.holes.insert(u);
+        },
+        Pen::Erase => {
+            puzzle.set(p, 0);
+            puzzle.holes.remove(&u);
+        },
+        Pen::Wall => {
+            // 一番近い辺の向こうのセル (盤面の端なら何もしない)
+            let (i, j) = p;
+            let q = [(fy, (i.wrapping_sub(1), j)), (1.0-fy, (i+1, j)), (fx, (i, j.wrapping_sub(1))), (1.0-fx, (i, j+1))]
+                .iter().min_by(|a, b| a.0.total_cmp(&b.0)).map(|&(_, q)| q).unwrap();
+
+            if q.0 >= puzzle.height || q.1 >= puzzle.width {
+                return false;
+            }
+
+            let (u, v) = (u.min(puzzle.index(q)), u.max(puzzle.index(q)));
+
+            if !puzzle.walls.remove(&(u, v)) {
+                puzzle.walls.insert((u, v));
+            }
+        },
+    }
+
+    true
+}
+
+// 大きさを変えても残る位置の数字, 穴と壁はそのまま
+pub fn resized(old: &Puzzle, width: usize, height: usize) -> Puzzle {
+    let mut puzzle = Puzzle::new(width, height);
+    let kept = |u: usize| {
+        let (i, j) = old.pos(u);
+
+        (i < height && j < width).then(|| i*width+j)
+    };
+
+    for (u, &num) in old.cells.iter().enumerate() {
+        if let Some(v) = kept(u) {
+            puzzle.cells[v] = num;
+        }
+    }
+
+    puzzle.holes = old.holes.iter().filter_map(|&u| kept(u)).collect();
+    puzzle.walls = old.walls.iter().filter_map(|&(u, v)| Some((kept(u)?, kept(v)?))).collect();
+
+    puzzle
+}
+
+// Export で書き出す盤面. 穴は URL に書けないので JSON の盤面にする
+pub fn export(puzzle: &Puzzle) -> String {
+    match puzzle.holes.is_empty() {
+        true => render_url(puzzle),
+        false => render_puzzle_json(puzzle).trim_end().to_string(),
+    }
+}
+
+// 経路を t (0..1) の割合だけ始点から伸ばした折れ線. 点はセルを 1 とした盤面の座標 (x, y) で, セルの中心を通る
+pub fn drawn(path: &[P], t: f32) -> Vec<(f32, f32)> {
+    let points: Vec<(f32, f32)> = path.iter().map(|&(i, j)| (j as f32+0.5, i as f32+0.5)).collect();
+    let shown = t.clamp(0.0, 1.0)*points.len().saturating_sub(1) as f32;
+    let whole = shown as usize;
+    let mut drawn: Vec<(f32, f32)> = points.iter().take(whole+1).copied().collect();
+
+    let f = shown-whole as f32;
+
+    if let Some(&(x, y)) = points.get(whole+1).filter(|_| f > 0.0) {
+        let (a, b) = points[whole];
+
+        drawn.push((a+(x-a)*f, b+(y-b)*f));
+    }
+
+    drawn
+}
+
+// wasm の canvas と同じく hsl(num*137, 70%, 45%)
+fn colour(num: usize) -> Color32 {
+    Color32::from(HsvaGamma { h: (num*137%360) as f32/360.0, s: 0.82, v: 0.77, a: 1.0 })
+}
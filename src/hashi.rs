@@ -0,0 +1,151 @@
+use varisat::{CnfFormula, ExtendFormula, Lit, Var};
+
+use crate::input::puzzlink;
+use crate::loops::{add_count, disconnected, solve_lazy, Grid};
+use crate::render::Format;
+use crate::{decode_field, is_valid_code, Error, SolverConfig, P};
+
+// Hashiwokakero: 島 (数字のセル) の間に縦か横にまっすぐ橋を架ける. 2 島の間の橋は 2 本まで, 橋は交差しない
+// 島ごとの橋の数はその数字で, 全部の島が 1 つにつながる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hashi {
+    pub width: usize,
+    pub height: usize,
+    // 行優先. 0 は海
+    pub islands: Vec<usize>,
+}
+
+// puzz.link でのパズルの名前
+pub(crate) const NAMES: &[&str] = &["hashikake", "hashi"];
+
+// 島 p から右か下の島 q へ架けた橋の本数
+pub type Bridges = Vec<(P, P, usize)>;
+
+impl Hashi {
+    pub fn new(width: usize, height: usize) -> Hashi {
+        Hashi { width, height, islands: vec![0; width*height] }
+    }
+
+    fn is_island(&self, (i, j): P) -> bool {
+        self.islands[i*self.width+j] > 0
+    }
+
+    // 橋を架けられる島の組 (右か下の一番近い島)
+    fn links(&self) -> Vec<(P, P)> {
+        let mut res = vec![];
+
+        for i in 0..self.height {
+            for j in (0..self.width).filter(|&j| self.is_island((i, j))) {
+                res.extend((j+1..self.width).find(|&l| self.is_island((i, l))).map(|l| ((i, j), (i, l))));
+                res.extend((i+1..self.height).find(|&k| self.is_island((k, j))).map(|k| ((i, j), (k, j))));
+            }
+        }
+
+        res
+    }
+}
+
+// puzz.link の hashikake/W/H/CODE (numlin と同じ数字の形式) か, janko.at の [problem] の盤面,
+// または行を `/` か改行, セルを `,` か空白で区切ったテキスト (`.` と `-` は海)
+pub fn parse_hashi(input: &str) -> Option<Hashi> {
+    if let Some((width, height, code)) = puzzlink(input, NAMES) {
+        let field = decode_field(width, height, code.to_string()).ok().filter(|_| is_valid_code(code))?;
+
+        return Some(Hashi { width, height, islands: field.cells });
+    }
+
+    // janko.at の形式は [problem] から次の [...] までが盤面
+    let board = match input.find("[problem]") {
+        Some(k) => input[k+9..].split('[').next()?,
+        None => input,
+    };
+    let rows: Vec<Vec<usize>> = board.trim().split(['/', '\n']).map(|row| {
+        row.split([',', ' ', '\t']).filter(|cell| !cell.is_empty()).map(|cell| match cell {
+            "." | "-" => Some(0),
+            cell => cell.parse().ok().filter(|&num| (1..=8).contains(&num)),
+        }).collect::<Option<Vec<_>>>()
+    }).collect::<Option<Vec<_>>>()?;
+    let width = rows.first()?.len();
+
+    if width == 0 || rows.iter().any(|row| row.len() != width) {
+        return None;
+    }
+
+    Some(Hashi { width, height: rows.len(), islands: rows.concat() })
+}
+
+pub fn solve_hashi(puzzle: &Hashi, config: &SolverConfig) -> Result<Bridges, Error> {
+    if puzzle.width == 0 || puzzle.height == 0 || puzzle.islands.len() != puzzle.width*puzzle.height {
+        return Err(Error::InvalidField);
+    }
+
+    let links = puzzle.links();
+    let islands: Vec<P> = Grid { rows: puzzle.height, cols: puzzle.width }.cells().filter(|&p| puzzle.is_island(p)).collect();
+    let mut formula = CnfFormula::new();
+
+    if islands.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // 組ごとに 1 本以上 (変数 2k) と 2 本 (変数 2k+1)
+    formula.set_var_count(2*links.len());
+
+    let one = |k: usize| Var::from_index(2*k);
+    let two = |k: usize| Var::from_index(2*k+1);
+
+    for k in 0..links.len() {
+        formula.add_clause(&[two(k).negative(), one(k).positive()]);
+    }
+
+    // 横の橋と縦の橋は交差しない
+    for (k, &((i, j), (_, l))) in links.iter().enumerate().filter(|&(_, &(p, q))| p.0 == q.0) {
+        for (m, &((a, b), (c, _))) in links.iter().enumerate().filter(|&(_, &(p, q))| p.1 == q.1) {
+            if a < i && i < c && j < b && b < l {
+                formula.add_clause(&[one(k).negative(), one(m).negative()]);
+            }
+        }
+    }
+
+    for &p in &islands {
+        let bridges: Vec<Var> = links.iter().enumerate().filter(|&(_, &(q, r))| q == p || r == p).flat_map(|(k, _)| [one(k), two(k)]).collect();
+
+        add_count(&mut formula, &bridges, puzzle.islands[p.0*puzzle.width+p.1]);
+    }
+
+    // 島がつながっていなければ, 最初の島とつながった島の組から外へ架かる橋のどれかを使う
+    let nodes: Vec<(usize, usize)> = links.iter().map(|(p, q)| (islands.binary_search(p).unwrap(), islands.binary_search(q).unwrap())).collect();
+    let values = solve_lazy(&formula, config, |values, _| match disconnected(islands.len(), &nodes, |k| values[one(k).index()]) {
+        Some(cut) => vec![cut.iter().map(|&k| one(k).positive()).collect::<Vec<Lit>>()],
+        None => vec![],
+    })?;
+
+    Ok(links.iter().enumerate().filter(|&(k, _)| values[one(k).index()]).map(|(k, &(p, q))| (p, q, 1+values[two(k).index()] as usize)).collect())
+}
+
+// 島は数字, 橋は横 1 本 `-`, 2 本 `=`, 縦 1 本 `|`, 2 本 `"`
+pub fn render_hashi(puzzle: &Hashi, bridges: &Bridges, format: Format) -> String {
+    match format {
+        Format::Debug | Format::Plugin(_) => format!("{:?}\n", bridges),
+        Format::Text => {
+            let mut rows: Vec<Vec<char>> = puzzle.islands.chunks(puzzle.width).map(|row| row.iter().map(|&num| if num > 0 { (b'0'+num as u8) as char } else { '.' }).collect()).collect();
+
+            for &((i, j), (k, l), count) in bridges {
+                for (a, b) in (i..=k).flat_map(|a| (j..=l).map(move |b| (a, b))).filter(|&p| p != (i, j) && p != (k, l)) {
+                    rows[a][b] = match (i == k, count) {
+                        (true, 1) => '-',
+                        (true, _) => '=',
+                        (false, 1) => '|',
+                        (false, _) => '"',
+                    };
+                }
+            }
+
+            rows.iter().map(|row| format!("{}\n", row.iter().collect::<String>())).collect()
+        },
+        Format::Json => {
+            let bridges: Vec<String> = bridges.iter().map(|&((i, j), (k, l), count)| format!("[[{},{}],[{},{}],{}]", i, j, k, l, count)).collect();
+
+            format!("{{\"width\":{},\"height\":{},\"bridges\":[{}]}}\n", puzzle.width, puzzle.height, bridges.join(","))
+        },
+    }
+}
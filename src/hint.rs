@@ -0,0 +1,226 @@
+use std::fmt;
+
+use crate::deduce::{deduce_from, Grade, Step, Technique};
+use crate::{find_arc_var, sat_only, Arc, Error, Puzzle, Session, SolverConfig, P};
+
+// 途中まで解いた盤面: 線を引いた辺と, 線を引かないと決めた (×) 辺. 辺はどちら向きに書いてもよい
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartialSolution {
+    pub lines: Vec<Arc>,
+    pub crosses: Vec<Arc>,
+}
+
+impl PartialSolution {
+    // 描きかけの道 (セル列) の隣り合うセルの間が線
+    pub fn from_paths(paths: &[Vec<P>]) -> PartialSolution {
+        let lines = paths.iter().flat_map(|path| path.windows(2).map(|w| (w[0], w[1]))).collect();
+
+        PartialSolution { lines, crosses: vec![] }
+    }
+
+    pub fn add(&mut self, arc: Arc, line: bool) {
+        match line {
+            true => self.lines.push(arc),
+            false => self.crosses.push(arc),
+        }
+    }
+
+    fn decided(&self) -> usize {
+        self.lines.len()+self.crosses.len()
+    }
+}
+
+// 次に決まる 1 本と, その理由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hint {
+    pub arc: Arc,
+    // 線を引く (true) か × (false) か
+    pub line: bool,
+    // 決めるのに使った技法 (手筋で決まらず探索で確かめたものは Search)
+    pub grade: Grade,
+    pub reason: String,
+}
+
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.reason, self.grade)
+    }
+}
+
+pub fn hint(puzzle: &Puzzle, partial: &PartialSolution) -> Result<Option<Hint>, Error> {
+    hint_with(puzzle, partial, &SolverConfig::default())
+}
+
+// partial から一番安い手筋で決まる 1 本. 手筋で決まらない (か手筋の読めない盤面の) ときは,
+// partial に合うどの答えでも同じになる辺を探索で探す. partial に合う答えがなければ NoSolution,
+// もう決められる辺がない (全部決まっているか, 残りは答えによって違う) なら None
+pub fn hint_with(puzzle: &Puzzle, partial: &PartialSolution, config: &SolverConfig) -> Result<Option<Hint>, Error> {
+    if sat_only(puzzle, config).is_none() {
+        let (deduction, steps) = deduce_from(puzzle, &partial.lines, &partial.crosses)?;
+
+        if deduction.contradiction {
+            return Err(Error::NoSolution);
+        }
+
+        if let Some(step) = steps.first() {
+            return Ok(Some(explain_step(puzzle, step)));
+        }
+    }
+
+    search(puzzle, partial, config, false)
+}
+
+pub fn explain(puzzle: &Puzzle) -> Result<Vec<Hint>, Error> {
+    explain_with(puzzle, &SolverConfig::default())
+}
+
+// 何もない盤面から答えまでの手順. 手筋で決まるところは手筋で進め, 行き詰まったら探索で確かめた 1 本を足す (grade が Search).
+// 答えが 2 つ以上あって決められなくなったら, 答えの 1 つから 1 本選んで続ける
+pub fn explain_with(puzzle: &Puzzle, config: &SolverConfig) -> Result<Vec<Hint>, Error> {
+    let edges = puzzle.edges().len();
+    let mut partial = PartialSolution::default();
+    let mut res = vec![];
+
+    loop {
+        if sat_only(puzzle, config).is_none() {
+            let (deduction, steps) = deduce_from(puzzle, &partial.lines, &partial.crosses)?;
+
+            if deduction.contradiction {
+                return Err(Error::NoSolution);
+            }
+
+            for step in steps {
+                let hint = explain_step(puzzle, &step);
+
+                partial.add(hint.arc, hint.line);
+                res.push(hint);
+            }
+        }
+
+        if partial.decided() == edges {
+            return Ok(res);
+        }
+
+        // guess を許すので None にはならない
+        let hint = search(puzzle, &partial, config, true)?.ok_or(Error::NoSolution)?;
+
+        partial.add(hint.arc, hint.line);
+        res.push(hint);
+    }
+}
+
+// guess なら, どの答えでも同じ辺がないとき最初に見つけた答えの線を 1 本返す
+fn search(puzzle: &Puzzle, partial: &PartialSolution, config: &SolverConfig, guess: bool) -> Result<Option<Hint>, Error> {
+    let mut session = Session::new(puzzle, config)?;
+    let arc_vars = |(p, q): Arc| find_arc_var(puzzle, (p, q)).zip(find_arc_var(puzzle, (q, p))).ok_or(Error::InvalidPin);
+
+    for &arc in &partial.lines {
+        let (x, y) = arc_vars(arc)?;
+
+        session.add_clause(&[x.positive(), y.positive()]);
+    }
+
+    for &arc in &partial.crosses {
+        let (x, y) = arc_vars(arc)?;
+
+        session.add_clause(&[x.negative()]);
+        session.add_clause(&[y.negative()]);
+    }
+
+    session.solve()?;
+
+    let decided = |(p, q): Arc| partial.lines.iter().chain(&partial.crosses).any(|&arc| arc == (p, q) || arc == (q, p));
+    let mut first = None;
+
+    for (u, v) in puzzle.edges() {
+        let arc = (puzzle.pos(u), puzzle.pos(v));
+
+        if decided(arc) {
+            continue;
+        }
+
+        // 直前の答え (どれも partial に合う) と逆を仮定して解けなければ, どの答えでも同じ
+        let (x, y) = arc_vars(arc)?;
+        let line = session.value(x) || session.value(y);
+
+        first = first.or(Some((arc, line)));
+
+        let res = if line {
+            session.solve_assuming(&[(arc, false), ((arc.1, arc.0), false)])
+        } else {
+            let act = session.fresh_var();
+
+            session.add_clause(&[act.negative(), x.positive(), y.positive()]);
+
+            let res = session.run(&[act.positive()]);
+
+            session.add_clause(&[act.negative()]);
+            res
+        };
+
+        match res {
+            Ok(_) => {},
+            Err(Error::NoSolution) => return Ok(Some(Hint { arc, line, grade: Grade::Search, reason: searched(arc, line) })),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(first.filter(|_| guess).map(|((p, q), line)| {
+        let reason = match line {
+            true => format!("the puzzle has several solutions; one of them draws a line between {:?} and {:?}", p, q),
+            false => format!("the puzzle has several solutions; one of them draws no line between {:?} and {:?}", p, q),
+        };
+
+        Hint { arc: (p, q), line, grade: Grade::Search, reason }
+    }))
+}
+
+fn searched((p, q): Arc, line: bool) -> String {
+    match line {
+        true => format!("every solution draws a line between {:?} and {:?}", p, q),
+        false => format!("no solution draws a line between {:?} and {:?}", p, q),
+    }
+}
+
+// 手筋で決めた 1 本を言葉にする
+pub(crate) fn explain_step(puzzle: &Puzzle, step: &Step) -> Hint {
+    let (p, q) = step.arc;
+    let from = puzzle.pos(step.cell);
+    let to = if from == p { q } else { p };
+    let num = puzzle.cells[step.cell];
+    let what = match num {
+        0 => format!("the line at {:?}", from),
+        num => format!("the {} at {:?}", num, from),
+    };
+    let reason = match (step.technique, step.line) {
+        (Technique::Forced, true) => format!("{} has no other way left, so it must go {}", what, direction(from, to)),
+        (Technique::Forced, false) => format!("{} is already complete, so it cannot go {}", what, direction(from, to)),
+        (Technique::Labels, _) => format!("the lines at {:?} and {:?} belong to different numbers, so they cannot join", p, q),
+        (Technique::Bridge, _) => format!("every route joining the two {}s passes between {:?} and {:?}", num, p, q),
+        (Technique::Parity, _) => format!("without a line between {:?} and {:?} the checkerboard colors of a region cannot balance", p, q),
+    };
+
+    Hint { arc: step.arc, line: step.line, grade: Grade::Technique(step.technique), reason }
+}
+
+// 隣 (斜めを含む) なら向き, 離れていれば (ワープなど) 行き先
+fn direction(from: P, to: P) -> String {
+    let vertical = match to.0 as i64-from.0 as i64 {
+        -1 => "up",
+        0 => "",
+        1 => "down",
+        _ => return format!("to {:?}", to),
+    };
+    let horizontal = match to.1 as i64-from.1 as i64 {
+        -1 => "left",
+        0 => "",
+        1 => "right",
+        _ => return format!("to {:?}", to),
+    };
+
+    match (vertical, horizontal) {
+        ("", h) => h.to_string(),
+        (v, "") => v.to_string(),
+        (v, h) => format!("{}-{}", v, h),
+    }
+}
@@ -0,0 +1,129 @@
+#[cfg(feature = "cbc")]
+use good_lp::coin_cbc as lp_solver;
+#[cfg(not(feature = "cbc"))]
+use good_lp::microlp as lp_solver;
+use good_lp::{constraint, variable, Expression, ProblemVariables, ResolutionError, Solution, SolutionStatus, SolverModel, Variable, WithTimeLimit};
+
+use crate::optimize::{optimized, Optimized};
+use crate::{gen_adjs, parse_field, sat_only, Error, Instant, Puzzle, Sol, SolverConfig, Stats};
+
+// 整数計画としての定式化: arc の 0-1 変数で各セルの入出次数 (流量保存) を決め, 空白には数字の 0-1 変数を 1 つだけ立てる
+// 数字は arc に沿って等しい (SAT の直接符号化と同じ条件を線形にしたもの). 曲がる回数の最小化は目的関数を足すだけで済む
+// 外部のソルバは途中で止められないので, interrupt は解き始める前にしか見ない
+pub fn solve_ilp(puzzle: &Puzzle, config: &SolverConfig) -> Result<Sol, Error> {
+    run(puzzle, config, false).map(|(sol, _)| sol)
+}
+
+pub fn minimize_turns_ilp(puzzle: &Puzzle, config: &SolverConfig) -> Result<Optimized, Error> {
+    let (sol, optimal) = run(puzzle, config, true)?;
+
+    Ok(Optimized { optimal, ..optimized(puzzle, sol) })
+}
+
+fn run(puzzle: &Puzzle, config: &SolverConfig, turns: bool) -> Result<(Sol, bool), Error> {
+    if puzzle.width == 0 || puzzle.height == 0 || puzzle.cells.len() != puzzle.width*puzzle.height {
+        return Err(Error::InvalidField);
+    }
+
+    let start = Instant::now();
+    let stats = || Stats { elapsed: start.elapsed(), ..Stats::default() };
+
+    if config.interrupt.as_ref().is_some_and(|interrupt| interrupt.load(std::sync::atomic::Ordering::Relaxed)) {
+        return Err(Error::Interrupted(stats()));
+    }
+
+    if let Some(feature) = sat_only(puzzle, config) {
+        return Err(Error::BackendFailed(format!("the ilp backend does not support {}", feature)));
+    }
+
+    let roles = parse_field(puzzle).ok_or(Error::InvalidField)?;
+    let adjs = gen_adjs(puzzle);
+    let n = puzzle.cells.len();
+    let nums = puzzle.cells.iter().cloned().max().unwrap_or(0);
+    let mut vars = ProblemVariables::new();
+
+    // arcs[u][d] は u から dirs[d] へ出る arc
+    let arcs: Vec<Vec<Option<Variable>>> = (0..n).map(|u| {
+        let mut res = vec![None; puzzle.topology.slots()];
+
+        for &(_, d) in &adjs[u] {
+            res[d] = Some(vars.add(variable().binary()));
+        }
+
+        res
+    }).collect();
+
+    // 手がかりのセルの数字は定数
+    let labels: Vec<Vec<Expression>> = (0..n).map(|u| (1..=nums).map(|k| match puzzle.cells[u] {
+        0 => vars.add(variable().binary()).into(),
+        num => Expression::from((num == k) as i32),
+    }).collect()).collect();
+
+    let turn_vars: Vec<Variable> = if turns { (0..n).map(|_| vars.add(variable().min(0).max(1))).collect() } else { vec![] };
+    let objective: Expression = turn_vars.iter().sum();
+    let mut model = vars.minimise(objective).using(lp_solver);
+
+    if let Some(timeout) = config.timeout {
+        model = model.with_time_limit(timeout.as_secs_f64());
+    }
+
+    for u in 0..n {
+        let outs: Expression = adjs[u].iter().map(|&(_, d)| arcs[u][d].unwrap()).sum();
+        let ins: Expression = adjs[u].iter().map(|&(v, d)| arcs[v][puzzle.topology.opposite(d)].unwrap()).sum();
+
+        let (out_degree, in_degree) = roles[u].degrees();
+
+        model.add_constraint(constraint!(outs == out_degree as i32));
+        model.add_constraint(constraint!(ins == in_degree as i32));
+
+        if puzzle.cells[u] == 0 {
+            let one: Expression = labels[u].iter().cloned().sum();
+
+            model.add_constraint(constraint!(one == 1));
+        }
+
+        // 辺 u-v をどちら向きに使っても両端の数字は等しい (向きごとに書くより LP 緩和が強い)
+        for &(v, d) in adjs[u].iter().filter(|&&(v, _)| u < v) {
+            let (x, y) = (arcs[u][d].unwrap(), arcs[v][puzzle.topology.opposite(d)].unwrap());
+
+            // (2)
+            model.add_constraint(constraint!(x + y <= 1));
+
+            for (lu, lv) in labels[u].iter().zip(&labels[v]) {
+                model.add_constraint(constraint!(x + y + lu.clone() - lv.clone() <= 1));
+                model.add_constraint(constraint!(x + y + lv.clone() - lu.clone() <= 1));
+            }
+        }
+
+        if turns {
+            for &(v, d_out) in &adjs[u] {
+                for &(w, d) in &adjs[u] {
+                    let d_in = puzzle.topology.opposite(d);
+
+                    if w != v && d_in != d_out {
+                        model.add_constraint(constraint!(turn_vars[u] >= arcs[w][d_in].unwrap() + arcs[u][d_out].unwrap() - 1));
+                    }
+                }
+            }
+        }
+    }
+
+    let solution = match model.solve() {
+        Ok(solution) => solution,
+        Err(ResolutionError::Infeasible) => return Err(Error::NoSolution),
+        Err(_) if config.timeout.is_some_and(|timeout| start.elapsed() >= timeout) => return Err(Error::Timeout(stats())),
+        Err(e) => return Err(Error::BackendFailed(e.to_string())),
+    };
+
+    let mut sol = vec![];
+
+    for u in 0..n {
+        for &(v, d) in &adjs[u] {
+            if solution.value(arcs[u][d].unwrap()) > 0.5 {
+                sol.push((puzzle.pos(u), puzzle.pos(v)));
+            }
+        }
+    }
+
+    Ok((sol, matches!(solution.status(), SolutionStatus::Optimal)))
+}
@@ -0,0 +1,434 @@
+use serde_json::Value;
+
+use crate::{Puzzle, Sol, Topology, MAX_CELLS, P};
+
+// render の JSON 出力と同じ形: {"field":[[1,0,2],...],"holes":[[i,j],...],"bridges":[[i,j],...],"walls":[[[i,j],[k,l]],...],
+// "warps":[[[i,j],[k,l],"right"],...],"topology":"hex"}. ワープの向き (p から盤外へ出る向き) は省略すると盤外に出られる最初の向き
+// width と height は省略してよい (書くなら field と合っていること). topology を省略すると四角い盤面
+// "wrap":"rows" などは warps の後で残りの端をつなぐ (Puzzle::wrap)
+// "mask" は field と同じ形の 0 と 1 で, 0 のセルは盤面の形の外 (Puzzle::remove_cell)
+// 重ねた盤面は field の代わりに "layers":[field, ...] と, すぐ上の層へ通じるセルの "vias":[[k,i,j],...] で書き, セルはどれも [層, 行, 列]
+// つながった複数の盤面は "boards":[field, ...] (同じ大きさ, vias なし) と, 端のセルから別の盤面の端のセルへ抜ける "links":[[[k,i,j],[l,m,n],"right"],...] で書く
+// 中身は層の間を行き来しない重ねた盤面とワープで, 出力も boards と links になる
+// "dots":[[i,j],...] は組の決まっていない点 (field は全部 0 にする)
+// "waypoints":[[i,j],[[k,l],3],...] は線が必ず通る空白で, 数字を添えるとその数字の線が通る
+// "one_way":[[[i,j],[k,l]],...] は隣り合う [i,j] から [k,l] へしか通れない一方通行
+// "limits":[[3,12],...] は数字 3 の線が通るセルの数の上限
+// "costs":[[[i,j],5],...] は線が通るセルのコスト, "budget":20 はその合計の上限
+// グラフの盤面は {"nodes":6,"edges":[[0,1],...],"pairs":[[0,5],...]} で, pairs の k 番目 (0 から) の 2 頂点が数字 k+1
+pub fn parse_json(input: &str) -> Option<Puzzle> {
+    let value: Value = serde_json::from_str(input).ok()?;
+
+    if value.get("nodes").is_some() {
+        return parse_graph(&value);
+    }
+    let boards = value.get("boards").is_some();
+
+    if boards && (value.get("layers").is_some() || value.get("vias").is_some()) {
+        return None;
+    }
+
+    let layered = boards || value.get("layers").is_some();
+    let fields = match value.get("layers").or_else(|| value.get("boards")) {
+        Some(layers) => layers.as_array()?.iter().map(parse_rows).collect::<Option<Vec<_>>>()?,
+        None => vec![parse_rows(value.get("field")?)?],
+    };
+
+    let layer_rows = fields.first()?.len();
+
+    if fields.iter().any(|rows| rows.len() != layer_rows) {
+        return None;
+    }
+
+    let mut puzzle = from_rows(&fields.concat())?;
+
+    for (key, size) in [("width", puzzle.width), ("height", layer_rows)] {
+        if value.get(key).is_some_and(|v| v.as_u64() != Some(size as u64)) {
+            return None;
+        }
+    }
+
+    if layered {
+        puzzle.topology = Topology::Layers(layer_rows);
+    } else if let Some(topology) = value.get("topology") {
+        puzzle.topology = topology.as_str()?.parse().ok()?;
+    }
+
+    let cell = |v: &Value| -> Option<(usize, usize)> {
+        match v.as_array()?.as_slice() {
+            [i, j] if !layered => Some((i.as_u64()? as usize, j.as_u64()? as usize)),
+            [k, i, j] if layered => Some((k.as_u64()? as usize*layer_rows + i.as_u64()? as usize, j.as_u64()? as usize)).filter(|_| i.as_u64() < Some(layer_rows as u64)),
+            _ => None,
+        }
+    };
+
+    if let Some(mask) = value.get("mask") {
+        let masks = match layered {
+            true => mask.as_array()?.iter().map(parse_rows).collect::<Option<Vec<_>>>()?,
+            false => vec![parse_rows(mask)?],
+        };
+        let mask = masks.concat();
+
+        if mask.len() != puzzle.height || mask.iter().any(|row| row.len() != puzzle.width) {
+            return None;
+        }
+
+        for (i, row) in mask.iter().enumerate() {
+            for (j, &bit) in row.iter().enumerate() {
+                match bit {
+                    0 => puzzle.remove_cell((i, j)),
+                    1 => {},
+                    _ => return None,
+                }
+            }
+        }
+    }
+
+    for p in value.get("vias").map_or(Some(&vec![]), Value::as_array)? {
+        let p = cell(p).filter(|&p| inside(&puzzle, p) && p.0+layer_rows < puzzle.height)?;
+
+        puzzle.add_via(p);
+    }
+
+    if let Some(dots) = value.get("dots") {
+        if puzzle.cells.iter().any(|&num| num > 0) {
+            return None;
+        }
+
+        for p in dots.as_array()? {
+            let p = cell(p).filter(|&p| inside(&puzzle, p))?;
+
+            puzzle.add_dot(p);
+        }
+    }
+
+    for v in value.get("waypoints").map_or(Some(&vec![]), Value::as_array)? {
+        let (p, num) = match cell(v) {
+            Some(p) => (p, 0),
+            None => match v.as_array()?.as_slice() {
+                [p, num] => (cell(p)?, num.as_u64()? as usize),
+                _ => return None,
+            },
+        };
+
+        if !inside(&puzzle, p) {
+            return None;
+        }
+
+        puzzle.add_waypoint(p, num);
+    }
+
+    for limit in value.get("limits").map_or(Some(&vec![]), Value::as_array)? {
+        match limit.as_array()?.as_slice() {
+            [num, cells] => puzzle.limits.insert(num.as_u64()? as usize, cells.as_u64()? as usize),
+            _ => return None,
+        };
+    }
+
+    for cost in value.get("costs").map_or(Some(&vec![]), Value::as_array)? {
+        match cost.as_array()?.as_slice() {
+            [p, cost] => {
+                let p = cell(p).filter(|&p| inside(&puzzle, p))?;
+
+                puzzle.costs.insert(puzzle.index(p), cost.as_u64()? as usize);
+            },
+            _ => return None,
+        }
+    }
+
+    if let Some(budget) = value.get("budget") {
+        puzzle.budget = Some(budget.as_u64()? as usize);
+    }
+
+    for key in ["holes", "bridges"] {
+        for p in value.get(key).map_or(Some(&vec![]), Value::as_array)? {
+            let p = cell(p).filter(|&p| inside(&puzzle, p))?;
+
+            if key == "holes" {
+                puzzle.add_hole(p);
+            } else {
+                puzzle.add_bridge(p);
+            }
+        }
+    }
+
+    if let Some(walls) = value.get("walls") {
+        for wall in walls.as_array()? {
+            let (p, q) = match wall.as_array()?.as_slice() {
+                [p, q] => (cell(p)?, cell(q)?),
+                _ => return None,
+            };
+
+            if !inside(&puzzle, p) || !inside(&puzzle, q) || puzzle.direction(p, q).is_none() {
+                return None;
+            }
+
+            puzzle.add_wall(p, q);
+        }
+    }
+
+    // 層の間の向きには出られない
+    let no_warps = vec![];
+    let warps = value.get("warps").map_or(Some(&no_warps), Value::as_array)?;
+    let links = value.get("links").map_or(Some(&no_warps), Value::as_array)?;
+
+    for warp in warps.iter().chain(links) {
+        let (p, q, dirs) = match warp.as_array()?.as_slice() {
+            [p, q] => (cell(p)?, cell(q)?, (0..puzzle.topology.slots()).filter(|&d| !puzzle.topology.is_layer_dir(d)).collect()),
+            [p, q, d] => (cell(p)?, cell(q)?, vec![puzzle.topology.dir_names().iter().position(|&name| Some(name) == d.as_str()).filter(|&d| !puzzle.topology.is_layer_dir(d))?]),
+            _ => return None,
+        };
+
+        if !inside(&puzzle, p) || !inside(&puzzle, q) || !dirs.into_iter().any(|d| puzzle.add_warp(p, d, q)) {
+            return None;
+        }
+    }
+
+    if let Some(wrap) = value.get("wrap") {
+        puzzle.wrap(wrap.as_str()?.parse().ok()?);
+    }
+
+    // ワープを通る一方通行もあるので, 端をつないでから
+    for arrow in value.get("one_way").map_or(Some(&vec![]), Value::as_array)? {
+        let (p, q) = match arrow.as_array()?.as_slice() {
+            [p, q] => (cell(p)?, cell(q)?),
+            _ => return None,
+        };
+
+        if !inside(&puzzle, p) || !inside(&puzzle, q) || puzzle.direction(p, q).is_none() || !puzzle.add_one_way(p, q) {
+            return None;
+        }
+    }
+
+    Some(puzzle)
+}
+
+fn parse_graph(value: &Value) -> Option<Puzzle> {
+    let nodes = value.get("nodes")?.as_u64().filter(|&nodes| nodes <= MAX_CELLS as u64)? as usize;
+    let pair = |v: &Value| -> Option<(usize, usize)> {
+        match v.as_array()?.as_slice() {
+            [u, v] => Some((u.as_u64()? as usize, v.as_u64()? as usize)),
+            _ => None,
+        }
+    };
+
+    let edges: Vec<(usize, usize)> = value.get("edges")?.as_array()?.iter().map(pair).collect::<Option<_>>()?;
+    let mut puzzle = Puzzle::from_graph(nodes, &edges)?;
+
+    // 3 つ以上の頂点を並べるとネット
+    for (k, p) in value.get("pairs")?.as_array()?.iter().enumerate() {
+        let ends: Vec<usize> = p.as_array()?.iter().map(|x| x.as_u64().map(|x| x as usize)).collect::<Option<_>>()?;
+
+        if ends.len() < 2 {
+            return None;
+        }
+
+        for w in ends {
+            if w >= nodes || puzzle.cells[w] != 0 {
+                return None;
+            }
+
+            puzzle.cells[w] = k+1;
+        }
+    }
+
+    Some(puzzle)
+}
+
+// render_line のテキスト出力と同じ形: 行を `/` か改行で, セルを `,` か空白で区切る. `.` と 0 は空白, `#` は穴, `+` は橋, `_` は盤面の外, `*` は点, `@` は線が必ず通る空白
+pub fn parse_text(input: &str) -> Option<Puzzle> {
+    let mut rows = vec![];
+    let mut holes = vec![];
+    let mut bridges = vec![];
+    let mut outside = vec![];
+    let mut dots = vec![];
+    let mut waypoints = vec![];
+
+    for (i, line) in input.split(['/', '\n']).map(str::trim).filter(|line| !line.is_empty()).enumerate() {
+        let mut row = vec![];
+
+        for (j, token) in line.split(|ch: char| ch == ',' || ch.is_whitespace()).filter(|token| !token.is_empty()).enumerate() {
+            match token {
+                "." => row.push(0),
+                "#" => {
+                    row.push(0);
+                    holes.push((i, j));
+                },
+                "+" => {
+                    row.push(0);
+                    bridges.push((i, j));
+                },
+                "_" => {
+                    row.push(0);
+                    outside.push((i, j));
+                },
+                "*" => {
+                    row.push(0);
+                    dots.push((i, j));
+                },
+                "@" => {
+                    row.push(0);
+                    waypoints.push((i, j));
+                },
+                _ => row.push(token.parse().ok()?),
+            }
+        }
+
+        rows.push(row);
+    }
+
+    let mut puzzle = from_rows(&rows)?;
+
+    for p in holes {
+        puzzle.add_hole(p);
+    }
+
+    for p in bridges {
+        puzzle.add_bridge(p);
+    }
+
+    for p in outside {
+        puzzle.remove_cell(p);
+    }
+
+    // 点と数字は混ぜられない
+    if !dots.is_empty() && puzzle.cells.iter().any(|&num| num > 0) {
+        return None;
+    }
+
+    for p in dots {
+        puzzle.add_dot(p);
+    }
+
+    for p in waypoints {
+        puzzle.add_waypoint(p, 0);
+    }
+
+    Some(puzzle)
+}
+
+// 盤面を道に分けた答えの幅, 高さと道 (セル列). solve の --format text か json の出力と同じ形:
+// 行を改行か `/`, セルを空白か `,` で区切ってセルごとに道の名前を書いたもの (`.` と `#` はどの道も通らないセル),
+// または {"width":W,"height":H,"paths":[{"cells":[[i,j],...]},...]} (道は {"cells":...} の代わりにセルの列だけでもよい)
+// テキストでは同じ名前のセルを隣どうしでつないで道の順にするので, 自分自身と隣り合う道は JSON で書く
+pub fn parse_paths(input: &str) -> Option<(usize, usize, Vec<Vec<P>>)> {
+    if input.trim_start().starts_with('{') {
+        let value: Value = serde_json::from_str(input).ok()?;
+        let cell = |v: &Value| -> Option<P> {
+            match v.as_array()?.as_slice() {
+                [i, j] => Some((i.as_u64()? as usize, j.as_u64()? as usize)),
+                _ => None,
+            }
+        };
+        let paths = value.get("paths")?.as_array()?.iter().map(|path| {
+            path.get("cells").unwrap_or(path).as_array()?.iter().map(cell).collect()
+        }).collect::<Option<Vec<Vec<_>>>>()?;
+        let size = |key: &str, max: usize| value.get(key).map_or(Some(max), |v| v.as_u64().map(|v| v as usize));
+        let width = size("width", paths.iter().flatten().map(|p| p.1+1).max().unwrap_or(0))?;
+        let height = size("height", paths.iter().flatten().map(|p| p.0+1).max().unwrap_or(0))?;
+
+        return Some((width, height, paths));
+    }
+
+    let rows: Vec<Vec<&str>> = input.trim().split(['/', '\n']).map(|row| row.split([',', ' ']).filter(|cell| !cell.is_empty()).collect()).collect();
+    let width = rows.first()?.len();
+
+    if rows.iter().any(|row| row.len() != width) {
+        return None;
+    }
+
+    let name = |(i, j): P| Some(rows.get(i)?.get(j)?).filter(|&&name| name != "." && name != "#");
+    let mut names: Vec<&str> = vec![];
+    let mut paths = vec![];
+
+    // 行優先で先に出てくる道から
+    for &label in rows.iter().flatten().filter(|&&name| name != "." && name != "#") {
+        if !names.contains(&label) {
+            names.push(label);
+        }
+    }
+
+    for label in names {
+        let cells: Vec<P> = (0..rows.len()).flat_map(|i| (0..width).map(move |j| (i, j))).filter(|&p| name(p) == Some(&label)).collect();
+        let next = |(i, j): P| -> Vec<P> {
+            [(i+1, j), (i, j+1), (i.wrapping_sub(1), j), (i, j.wrapping_sub(1))].iter().cloned().filter(|&q| name(q) == Some(&label)).collect()
+        };
+
+        // 端 (隣が 1 つ) から順にたどる. 分かれ道か輪があれば読めない
+        if cells.iter().any(|&p| next(p).len() > 2) {
+            return None;
+        }
+
+        let mut path = vec![*cells.iter().find(|&&p| next(p).len() == 1)?];
+
+        while let Some(&q) = next(path[path.len()-1]).iter().find(|q| path.len() < 2 || path[path.len()-2] != **q) {
+            path.push(q);
+        }
+
+        if path.len() != cells.len() {
+            return None;
+        }
+
+        paths.push(path);
+    }
+
+    Some((width, rows.len(), paths))
+}
+
+// puzz.link の URL .../p?NAME/W/H/CODE の幅, 高さと盤面の部分 (NAME が names のどれかのとき)
+pub(crate) fn puzzlink<'a>(url: &'a str, names: &[&str]) -> Option<(usize, usize, &'a str)> {
+    let params: Vec<&str> = url.trim().split('/').collect();
+    let k = params.iter().position(|param| names.iter().any(|name| param.rsplit('?').next() == Some(*name)))?;
+
+    match params[k+1..] {
+        [width, height, code, ..] => {
+            let (width, height): (usize, usize) = (width.parse().ok().filter(|&width| width > 0)?, height.parse().ok().filter(|&height| height > 0)?);
+
+            // 大きすぎる盤面は作る前に断る
+            width.checked_mul(height).filter(|&cells| cells <= MAX_CELLS)?;
+
+            Some((width, height, code))
+        },
+        _ => None,
+    }
+}
+
+fn parse_rows(field: &Value) -> Option<Vec<Vec<usize>>> {
+    field.as_array()?.iter().map(|row| row.as_array()?.iter().map(|num| num.as_u64().map(|num| num as usize)).collect()).collect()
+}
+
+fn from_rows(rows: &[Vec<usize>]) -> Option<Puzzle> {
+    let width = rows.first()?.len();
+
+    if width == 0 || rows.iter().any(|row| row.len() != width) {
+        return None;
+    }
+
+    let mut puzzle = Puzzle::new(width, rows.len());
+
+    puzzle.cells = rows.concat();
+
+    Some(puzzle)
+}
+
+fn inside(puzzle: &Puzzle, (i, j): (usize, usize)) -> bool {
+    i < puzzle.height && j < puzzle.width
+}
+
+// 数字の道は行優先で先にある数字から向ける (verify はその向きで確かめる)
+pub(crate) fn arcs(puzzle: &Puzzle, paths: &[Vec<P>]) -> Sol {
+    let inside = |p: P| p.0 < puzzle.height && p.1 < puzzle.width;
+
+    paths.iter().flat_map(|path| {
+        let reversed = match (path.first(), path.last()) {
+            (Some(&p), Some(&q)) if inside(p) && inside(q) => puzzle.cells[puzzle.index(p)] > 0 && puzzle.index(q) < puzzle.index(p),
+            _ => false,
+        };
+        let path: Vec<P> = match reversed {
+            true => path.iter().rev().cloned().collect(),
+            false => path.clone(),
+        };
+
+        path.windows(2).map(|w| (w[0], w[1])).collect::<Vec<_>>()
+    }).collect()
+}
@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+
+use varisat::{ExtendFormula, Lit, Var};
+
+use crate::render::paths;
+use crate::template::template;
+use crate::tags::{Family, Tagger};
+use crate::{encode_with, gen_adjs, number_lits, Arc, Error, Puzzle, Sol, SolverConfig};
+
+// 手がかりの数字が経路のセル数 (両端を含む) を表す盤面. 同じ数字の手がかりのどれとどれを結ぶかも解で決める
+// 手がかりを点として (Puzzle::dots と同じく) 番号の小さい方から線を引き, 数字の一致と長さを足す
+// 空白の閉路の分だけ長さを数え違えた解は, Session が cuts で見つけて節を足して解き直す
+pub(crate) fn encode_lengths<F: ExtendFormula>(puzzle: &Puzzle, config: &SolverConfig, formula: &mut Tagger<F>) -> Result<usize, Error> {
+    let clues: Vec<usize> = (0..puzzle.cells.len()).filter(|&u| puzzle.cells[u] > 0).collect();
+
+    // 長さ 1 の線はなく, 同じ長さの手がかりは 2 つずつ組になる
+    if puzzle.dots || clues.iter().any(|&u| puzzle.cells[u] < 2 || clues.iter().filter(|&&v| puzzle.cells[v] == puzzle.cells[u]).count()%2 != 0) {
+        return Err(Error::InvalidField);
+    }
+
+    let ranked = ranked(puzzle);
+    let mut next_var = encode_with(&ranked, config, formula)?;
+
+    formula.tag(Family::Length, None);
+
+    let template = template(&ranked, config);
+    let numbers = &template.numbers;
+    let adjs = gen_adjs(puzzle);
+    let topology = puzzle.topology;
+    let ins = |u: usize| -> Vec<Lit> { adjs[u].iter().map(|&(v, d)| topology.arc_var(v, topology.opposite(d)).positive()).collect() };
+
+    // 終点にする線の始点は同じ長さの手がかり
+    for (k, &e) in clues.iter().enumerate() {
+        for (r, _) in clues[..k].iter().enumerate().filter(|&(_, &s)| puzzle.cells[s] != puzzle.cells[e]) {
+            let other = number_lits(config.encoding, &numbers[e], r+1);
+
+            for y in ins(e) {
+                formula.add_clause(&std::iter::once(!y).chain(other.iter().map(|&lit| !lit)).collect::<Vec<Lit>>());
+            }
+        }
+    }
+
+    // 始点なら, その番号の数字のセルがちょうど n 個 (n-1 歩より遠いセルは経路に入らないので数えない)
+    for (k, &s) in clues.iter().enumerate() {
+        let n = puzzle.cells[s];
+        let start: Vec<Lit> = adjs[s].iter().map(|&(_, d)| topology.arc_var(s, d).positive()).collect();
+        let mut counted = vec![];
+
+        for u in near(&adjs, s, n-1) {
+            let z = Var::from_index(next_var);
+            let lits = number_lits(config.encoding, &numbers[u], k+1);
+
+            next_var += 1;
+
+            for &lit in &lits {
+                formula.add_clause(&[z.negative(), lit]);
+            }
+
+            formula.add_clause(&lits.iter().map(|&lit| !lit).chain(std::iter::once(z.positive())).collect::<Vec<Lit>>());
+
+            counted.push(z);
+        }
+
+        next_var = encode_exactly(formula, &start, &counted, n, next_var);
+    }
+
+    Ok(next_var)
+}
+
+// 手がかりのセルに行優先に 1, 2, ... と番号を振った点の盤面
+fn ranked(puzzle: &Puzzle) -> Puzzle {
+    let mut res = Puzzle { cells: vec![0; puzzle.cells.len()], ..puzzle.clone() };
+
+    for u in (0..puzzle.cells.len()).filter(|&u| puzzle.cells[u] > 0) {
+        res.add_dot(puzzle.pos(u));
+    }
+
+    res
+}
+
+// s から steps 歩以内で行けるセル
+fn near(adjs: &[Vec<(usize, usize)>], s: usize, steps: usize) -> Vec<usize> {
+    let mut dist = vec![usize::MAX; adjs.len()];
+    let mut queue = VecDeque::from([s]);
+
+    dist[s] = 0;
+
+    while let Some(u) = queue.pop_front() {
+        for &(v, _) in &adjs[u] {
+            if dist[v] == usize::MAX && dist[u] < steps {
+                dist[v] = dist[u]+1;
+                queue.push_back(v);
+            }
+        }
+    }
+
+    (0..adjs.len()).filter(|&u| dist[u] != usize::MAX).collect()
+}
+
+// 数字ごとの線のセル数の上限 (Puzzle::limits). その数字のセルを数えて上限以下にする
+// 始点から上限-1 歩より遠いセルはその数字にならない. 空白の閉路は template の余分な数字にできるので数えなくてよい
+pub(crate) fn encode_limits<F: ExtendFormula>(puzzle: &Puzzle, config: &SolverConfig, formula: &mut F, mut next_var: usize) -> usize {
+    let template = template(puzzle, config);
+    let adjs = gen_adjs(puzzle);
+
+    for (&num, &limit) in &puzzle.limits {
+        let s = match (0..puzzle.cells.len()).find(|&u| puzzle.cells[u] == num) {
+            Some(s) => s,
+            None => continue,
+        };
+        let near = near(&adjs, s, limit.saturating_sub(1));
+        let mut counted = vec![];
+
+        for u in (0..puzzle.cells.len()).filter(|&u| !puzzle.is_hole(u)) {
+            // 橋は横と縦の線を別々に数える
+            for vars in [&template.numbers[u], &template.lanes[u]].iter().filter(|vars| !vars.is_empty()) {
+                let lits = number_lits(config.encoding, vars, num);
+
+                if !near.contains(&u) {
+                    formula.add_clause(&lits.iter().map(|&lit| !lit).collect::<Vec<Lit>>());
+                    continue;
+                }
+
+                let z = Var::from_index(next_var);
+
+                next_var += 1;
+                formula.add_clause(&lits.iter().map(|&lit| !lit).chain(std::iter::once(z.positive())).collect::<Vec<Lit>>());
+                counted.push(z);
+            }
+        }
+
+        let (count, used) = encode_counter(formula, &counted, limit, next_var);
+
+        next_var = used;
+
+        if let Some(c) = count[limit] {
+            formula.add_clause(&[c.negative()]);
+        }
+    }
+
+    next_var
+}
+
+// 線が通るセルのコストの合計が Puzzle::budget 以下. コスト c のセルを, 線が通れば真になる変数 c 個として数える
+pub(crate) fn encode_budget<F: ExtendFormula>(puzzle: &Puzzle, formula: &mut F, mut next_var: usize) -> usize {
+    let budget = match puzzle.budget {
+        Some(budget) => budget,
+        None => return next_var,
+    };
+
+    let mut counted = vec![];
+
+    for (x, cost) in covered_vars(puzzle, formula, &mut next_var) {
+        counted.extend(std::iter::repeat_n(x, cost));
+    }
+
+    let (count, used) = encode_counter(formula, &counted, budget, next_var);
+
+    if let Some(c) = count[budget] {
+        formula.add_clause(&[c.negative()]);
+    }
+
+    used
+}
+
+// コストのあるセルごとに, そこに出入りする arc があれば真になる変数とそのコスト
+fn covered_vars<F: ExtendFormula>(puzzle: &Puzzle, formula: &mut F, next_var: &mut usize) -> Vec<(Var, usize)> {
+    let adjs = gen_adjs(puzzle);
+    let topology = puzzle.topology;
+
+    puzzle.costs.iter().filter(|&(&u, &cost)| cost > 0 && u < puzzle.cells.len() && !puzzle.is_hole(u)).map(|(&u, &cost)| {
+        let x = Var::from_index(*next_var);
+
+        *next_var += 1;
+
+        for &(v, d) in &adjs[u] {
+            formula.add_clause(&[topology.arc_var(u, d).negative(), x.positive()]);
+            formula.add_clause(&[topology.arc_var(v, topology.opposite(d)).negative(), x.positive()]);
+        }
+
+        (x, cost)
+    }).collect()
+}
+
+// when のどれかが真なら vars のちょうど n 個が真
+fn encode_exactly<F: ExtendFormula>(formula: &mut F, when: &[Lit], vars: &[Var], n: usize, next_var: usize) -> usize {
+    let (prev, next_var) = encode_counter(formula, vars, n, next_var);
+
+    // n 個以上で n+1 個以上ではない
+    for &w in when {
+        match prev[n-1] {
+            Some(c) => formula.add_clause(&[!w, c.positive()]),
+            None => formula.add_clause(&[!w]),
+        }
+
+        if let Some(c) = prev[n] {
+            formula.add_clause(&[!w, c.negative()]);
+        }
+    }
+
+    next_var
+}
+
+// vars のちょうど n 個が真
+pub(crate) fn encode_count<F: ExtendFormula>(formula: &mut F, vars: &[Var], n: usize, next_var: usize) -> usize {
+    let (count, next_var) = encode_counter(formula, vars, n, next_var);
+
+    if n > 0 {
+        match count[n-1] {
+            Some(c) => formula.add_clause(&[c.positive()]),
+            None => formula.add_clause(&[]),
+        }
+    }
+
+    if let Some(c) = count[n] {
+        formula.add_clause(&[c.negative()]);
+    }
+
+    next_var
+}
+
+// vars を順に数える counter. 返す c[j] は vars のうち j+1 個以上が真 (j = 0..=n, vars がなければ None) と同値
+pub(crate) fn encode_counter<F: ExtendFormula>(formula: &mut F, vars: &[Var], n: usize, mut next_var: usize) -> (Vec<Option<Var>>, usize) {
+    let mut prev: Vec<Option<Var>> = vec![None; n+1];
+
+    for &x in vars {
+        let cur: Vec<Option<Var>> = (0..=n).map(|_| {
+            next_var += 1;
+
+            Some(Var::from_index(next_var-1))
+        }).collect();
+
+        for j in 0..=n {
+            let c = cur[j].unwrap();
+            // 1 つ前までに j 個以上 (j == 0 なら常に真)
+            let below = if j == 0 { None } else { Some(prev[j-1]) };
+
+            // c <-> prev[j] or (below and x)
+            if let Some(p) = prev[j] {
+                formula.add_clause(&[p.negative(), c.positive()]);
+            }
+
+            match below {
+                None => formula.add_clause(&[x.negative(), c.positive()]),
+                Some(Some(b)) => formula.add_clause(&[b.negative(), x.negative(), c.positive()]),
+                Some(None) => {},
+            }
+
+            let mut clause = vec![c.negative()];
+
+            clause.extend(prev[j].map(|p| p.positive()));
+
+            formula.add_clause(&clause.iter().cloned().chain(std::iter::once(x.positive())).collect::<Vec<Lit>>());
+
+            if let Some(Some(b)) = below {
+                formula.add_clause(&clause.iter().cloned().chain(std::iter::once(b.positive())).collect::<Vec<Lit>>());
+            } else if let Some(None) = below {
+                formula.add_clause(&clause);
+            }
+        }
+
+        prev = cur;
+    }
+
+    (prev, next_var)
+}
+
+// 長さが手がかりと違う経路 (空白の閉路がその番号の数字を持っていた) の arc
+pub(crate) fn cuts(puzzle: &Puzzle, sol: &Sol) -> Vec<Vec<Arc>> {
+    paths(puzzle, sol).into_iter().filter(|(num, path)| path.len() != *num).map(|(_, path)| path.windows(2).map(|w| (w[0], w[1])).collect()).collect()
+}
@@ -0,0 +1,1288 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use varisat::{CnfFormula, ExtendFormula};
+use varisat::{Var, Lit};
+
+use crate::tags::{Family, Tagger};
+
+pub mod backend;
+pub mod backtrack;
+#[cfg(feature = "bevy")]
+pub mod bevy_plugin;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod cache;
+pub mod canonical;
+pub mod checkpoint;
+pub mod compare;
+pub mod constraints;
+pub mod crosscheck;
+#[cfg(unix)]
+pub mod daemon;
+#[cfg(feature = "db")]
+pub mod db;
+pub mod deduce;
+pub mod diff;
+pub mod estimate;
+pub mod events;
+#[cfg(feature = "evcxr")]
+pub mod evcxr;
+pub mod experiment;
+pub mod generate;
+pub mod genre;
+pub mod greedy;
+pub mod hint;
+pub mod live;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "gui")]
+pub mod gui;
+#[cfg(feature = "ilp")]
+pub mod ilp;
+pub mod hashi;
+pub mod input;
+mod lengths;
+pub mod loops;
+pub mod masyu;
+#[cfg(feature = "server")]
+pub mod metrics;
+pub mod minimize;
+mod monitor;
+pub mod nets;
+#[cfg(feature = "server")]
+pub mod openapi;
+#[cfg(feature = "node")]
+pub mod node;
+pub mod optimize;
+pub mod phase;
+pub mod portfolio;
+pub mod predict;
+pub mod profile;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod rate;
+pub mod region;
+pub mod repair;
+pub mod render;
+pub mod report;
+mod rng;
+pub mod route;
+pub mod rules;
+pub mod sample;
+pub mod screen;
+#[cfg(feature = "script")]
+pub mod script;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod session;
+pub mod slither;
+pub mod source;
+pub mod tags;
+pub mod template;
+pub mod tidy;
+pub mod topology;
+pub mod transform;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod whatif;
+pub mod yajilin;
+pub mod zdd;
+
+pub use rules::RuleSet;
+pub use session::Session;
+pub use topology::{Topology, Wrap};
+
+// wasm32-unknown-unknown では std の時計が使えないので, 同じ形の web-time のものを使う
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::{Instant, SystemTime, UNIX_EPOCH};
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::{Instant, SystemTime, UNIX_EPOCH};
+
+pub type P = (usize, usize);
+pub type Arc = (P, P);
+pub type Sol = Vec<Arc>;
+// true にすると実行中の探索を打ち切る (Ctrl-C など)
+pub type Interrupt = std::sync::Arc<AtomicBool>;
+
+// 読み込む盤面のセルの数の上限. URL の W/H は数文字で書けるので, 確かめずに盤面を作るとメモリを使い切る
+pub const MAX_CELLS: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Puzzle {
+    pub width: usize,
+    pub height: usize,
+    // 行優先 (row*width+col), 0 は空白
+    pub cells: Vec<usize>,
+    // 線が通れない, 隣り合うセルの間の壁 (セルの番号の小さい方が先)
+    pub walls: BTreeSet<(usize, usize)>,
+    // 盤面の穴 (線が通らず, 埋めなくてよいセル)
+    pub holes: BTreeSet<usize>,
+    // 橋 (横に 1 本, 縦に 1 本の線がそれぞれまっすぐ通るセル)
+    pub bridges: BTreeSet<usize>,
+    // ワープ: セル u から盤外へ dir(d) の向きに出ると warps[&(u, d)] に入る (逆向きも入れておく)
+    pub warps: BTreeMap<(usize, usize), usize>,
+    pub topology: Topology,
+    // 重ねた盤面で, すぐ上の層の同じ位置のセルと線が通じているセル (層をまたぐ arc はここにしかない)
+    pub vias: BTreeSet<usize>,
+    // 盤面の形の外のセル (holes にも入れて穴と同じに解き, 表示では何も書かない)
+    pub outside: BTreeSet<usize>,
+    // 数字の代わりに点だけがある盤面 (どの 2 点を結ぶかも解で決める). 点のセルには行優先に 1, 2, ... と別々の番号が入る
+    pub dots: bool,
+    // 線が必ず通る空白 (値はそこを通る線の数字で, 0 ならどの線でもよい)
+    pub waypoints: BTreeMap<usize, usize>,
+    // 一方通行 (u, v): 隣り合う u と v の間は u から v へしか通れない. 線の向きは始点 (行優先で先に現れるセル) から
+    pub one_way: BTreeSet<(usize, usize)>,
+    // 数字ごとの, その線が通るセルの数 (両端を含む) の上限
+    pub limits: BTreeMap<usize, usize>,
+    // 線が通るセルのコスト (書いていないセルは 0) と, 線が通るセルのコストの合計の上限
+    pub costs: BTreeMap<usize, usize>,
+    pub budget: Option<usize>,
+}
+
+impl Puzzle {
+    pub fn new(width: usize, height: usize) -> Self {
+        Puzzle {
+            width,
+            height,
+            cells: vec![0; width.checked_mul(height).expect("board size overflows usize")],
+            walls: BTreeSet::new(),
+            holes: BTreeSet::new(),
+            bridges: BTreeSet::new(),
+            warps: BTreeMap::new(),
+            topology: Topology::Square,
+            vias: BTreeSet::new(),
+            outside: BTreeSet::new(),
+            dots: false,
+            waypoints: BTreeMap::new(),
+            one_way: BTreeSet::new(),
+            limits: BTreeMap::new(),
+            costs: BTreeMap::new(),
+            budget: None,
+        }
+    }
+
+    // 外から受け取った大きさの盤面. セルの数が MAX_CELLS を超えれば (掛け算があふれても) TooLarge
+    pub fn try_new(width: usize, height: usize) -> Result<Self, Error> {
+        match width.checked_mul(height) {
+            Some(cells) if cells <= MAX_CELLS => Ok(Puzzle::new(width, height)),
+            _ => Err(Error::TooLarge(width, height)),
+        }
+    }
+
+    pub fn index(&self, p: P) -> usize {
+        p.0*self.width+p.1
+    }
+
+    pub fn pos(&self, index: usize) -> P {
+        (index/self.width, index%self.width)
+    }
+
+    pub fn get(&self, p: P) -> usize {
+        self.cells[self.index(p)]
+    }
+
+    pub fn set(&mut self, p: P, num: usize) {
+        let index = self.index(p);
+
+        self.cells[index] = num;
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[usize]> {
+        self.cells.chunks(self.width)
+    }
+
+    pub fn add_wall(&mut self, p: P, q: P) {
+        let (u, v) = (self.index(p), self.index(q));
+
+        self.walls.insert((u.min(v), u.max(v)));
+    }
+
+    // p と q の間を p から q への一方通行にする (逆向きがすでにあれば false)
+    pub fn add_one_way(&mut self, p: P, q: P) -> bool {
+        let (u, v) = (self.index(p), self.index(q));
+
+        !self.one_way.contains(&(v, u)) && self.one_way.insert((u, v))
+    }
+
+    pub fn is_wall(&self, u: usize, v: usize) -> bool {
+        self.walls.contains(&(u.min(v), u.max(v)))
+    }
+
+    pub fn add_hole(&mut self, p: P) {
+        let index = self.index(p);
+
+        self.holes.insert(index);
+    }
+
+    pub fn is_hole(&self, index: usize) -> bool {
+        self.holes.contains(&index)
+    }
+
+    // p を盤面の形から外す (長方形でない盤面)
+    pub fn remove_cell(&mut self, p: P) {
+        let index = self.index(p);
+
+        self.holes.insert(index);
+        self.outside.insert(index);
+    }
+
+    pub fn is_outside(&self, index: usize) -> bool {
+        self.outside.contains(&index)
+    }
+
+    pub fn add_bridge(&mut self, p: P) {
+        let index = self.index(p);
+
+        self.bridges.insert(index);
+    }
+
+    pub fn is_bridge(&self, index: usize) -> bool {
+        self.bridges.contains(&index)
+    }
+
+    // p を組の決まっていない点にする (数字のある盤面には置けない)
+    pub fn add_dot(&mut self, p: P) {
+        let index = self.index(p);
+
+        self.dots = true;
+        self.cells[index] = 1;
+
+        for (k, num) in self.cells.iter_mut().filter(|num| **num > 0).enumerate() {
+            *num = k+1;
+        }
+    }
+
+    // p を線が必ず通る空白にする (num > 0 なら数字 num の線が通る)
+    pub fn add_waypoint(&mut self, p: P, num: usize) {
+        let index = self.index(p);
+
+        self.waypoints.insert(index, num);
+    }
+
+    // 点の盤面の解から, 結んだ 2 点に同じ数字 (始点の順に 1, 2, ...) を書いた普通の盤面を作る
+    pub fn paired(&self, sol: &Sol) -> Puzzle {
+        let mut res = Puzzle { dots: false, cells: vec![0; self.cells.len()], ..self.clone() };
+
+        for (k, (_, path)) in render::paths(self, sol).into_iter().enumerate() {
+            for p in [path[0], path[path.len()-1]] {
+                res.set(p, k+1);
+            }
+        }
+
+        res
+    }
+
+    // p とすぐ上の層のセルの間を通れるようにする
+    pub fn add_via(&mut self, p: P) {
+        let index = self.index(p);
+
+        self.vias.insert(index);
+    }
+
+    // p から盤外へ dir(d) の向きに出ると q の反対側に入るワープを作る
+    // 盤外に出る向きが空いていて, 隣り合わない 2 セルの間でなければ false
+    pub fn add_warp(&mut self, p: P, d: usize, q: P) -> bool {
+        let back = self.topology.opposite(d);
+
+        if p == q || self.step(p, d).is_some() || self.step(q, back).is_some() || self.direction(p, q).is_some() {
+            return false;
+        }
+
+        let (u, v) = (self.index(p), self.index(q));
+
+        if self.warps.contains_key(&(u, d)) || self.warps.contains_key(&(v, back)) {
+            return false;
+        }
+
+        self.warps.insert((u, d), v);
+        self.warps.insert((v, back), u);
+
+        true
+    }
+
+    // 頂点 0..nodes と辺からなるグラフの盤面 (頂点 k はセル (0, k)). ループや重複した辺があれば None
+    // 辺ごとに, 両端でまだ空いている向きの組 (d, d+m) を選ぶ. 次数の最大値を D とすると m = 2D-1 なら必ず選べる
+    pub fn from_graph(nodes: usize, edges: &[(usize, usize)]) -> Option<Puzzle> {
+        let mut degree = vec![0; nodes];
+
+        for (k, &(u, v)) in edges.iter().enumerate() {
+            if u == v || u >= nodes || v >= nodes || edges[..k].iter().any(|&(x, y)| (x, y) == (u, v) || (x, y) == (v, u)) {
+                return None;
+            }
+
+            degree[u] += 1;
+            degree[v] += 1;
+        }
+
+        let max = degree.iter().cloned().max().unwrap_or(0).max(1);
+
+        (max..2*max).find_map(|m| {
+            let mut puzzle = Puzzle::new(nodes, 1);
+
+            puzzle.topology = Topology::Graph(m);
+
+            edges.iter().all(|&(u, v)| (0..2*m).any(|d| puzzle.add_warp((0, u), d, (0, v)))).then_some(puzzle)
+        })
+    }
+
+    // 四角い盤面を斜めにも進める盤面にする (ワープの向きは 8 近傍の番号に付け直す)
+    pub fn allow_diagonals(&mut self) {
+        if self.topology != Topology::Square {
+            return;
+        }
+
+        self.warps = self.warps.iter().map(|(&(u, d), &v)| ((u, 2*d), v)).collect();
+        self.topology = Topology::Diagonal;
+    }
+
+    // 盤外に出る arc を反対側の端へのワープにする (隣り合うセルや, すでにワープのある向きはそのまま)
+    // 重ねた盤面では層ごとに端をつなぐ
+    pub fn wrap(&mut self, wrap: Wrap) {
+        let rows = wrap == Wrap::Rows || wrap == Wrap::Both;
+        let columns = wrap == Wrap::Columns || wrap == Wrap::Both;
+        let topology = self.topology;
+        let layer_rows = topology.layer_rows(self.height);
+        let (height, width) = (layer_rows as i32, self.width as i32);
+
+        for u in 0..self.cells.len() {
+            let p = self.pos(u);
+            let top = p.0/layer_rows*layer_rows;
+
+            for d in (0..topology.slots()).filter(|&d| !topology.is_layer_dir(d)) {
+                let (di, dj) = topology.dir(d);
+                let (i, j) = ((p.0-top) as i32 + di, p.1 as i32 + dj);
+
+                if (!(0..height).contains(&i) && !columns) || (!(0..width).contains(&j) && !rows) {
+                    continue;
+                }
+
+                self.add_warp(p, d, (top+i.rem_euclid(height) as usize, j.rem_euclid(width) as usize));
+            }
+        }
+    }
+
+    // p から dir(d) へ 1 歩進んだセル (盤内なら)
+    fn step(&self, p: P, d: usize) -> Option<P> {
+        self.topology.step(self.width, self.height, p, d)
+    }
+
+    // p から q への arc の向き (隣り合うかワープでつながっていれば)
+    pub fn direction(&self, p: P, q: P) -> Option<usize> {
+        if p.0 >= self.height || p.1 >= self.width || q.0 >= self.height || q.1 >= self.width {
+            return None;
+        }
+
+        (0..self.topology.slots()).find(|&d| self.step(p, d) == Some(q) || self.warps.get(&(self.index(p), d)) == Some(&self.index(q)))
+    }
+
+    // 隣り合う u と v の間を線が通れるか
+    pub fn is_open(&self, u: usize, v: usize) -> bool {
+        !self.is_wall(u, v) && !self.is_hole(u) && !self.is_hole(v) && (!self.is_layer_pair(u, v) || self.vias.contains(&u.min(v)))
+    }
+
+    // 上下の層の同じ位置のセルの組
+    fn is_layer_pair(&self, u: usize, v: usize) -> bool {
+        match self.topology {
+            Topology::Layers(rows) => u.abs_diff(v) == rows*self.width,
+            _ => false,
+        }
+    }
+
+    // 線が通れる隣り合うセルの組 (セルごとに向きの順で番号の大きい方へのものを行優先に並べ, ワープは最後)
+    pub fn edges(&self) -> Vec<(usize, usize)> {
+        let grid = (0..self.cells.len()).flat_map(|u| {
+            (0..self.topology.slots()).filter_map(move |d| self.step(self.pos(u), d)).map(move |q| (u, self.index(q))).filter(|&(u, v)| u < v)
+        });
+
+        let warps = self.warps.iter().map(|(&(u, _), &v)| (u, v)).filter(|&(u, v)| u < v);
+
+        grid.chain(warps).filter(|&(u, v)| self.is_open(u, v)).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    // 数字を bit ごとに分解 (論文の (11), (12))
+    Log,
+    // 数字ごとに 1 変数
+    Direct,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "log" => Ok(Encoding::Log),
+            "direct" => Ok(Encoding::Direct),
+            _ => Err(format!("unknown encoding `{}` (expected log or direct)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SolverConfig {
+    pub encoding: Encoding,
+    pub timeout: Option<Duration>,
+    pub interrupt: Option<Interrupt>,
+    // 0 以外ならセルを符号化する順番をこの seed で並べ替える (portfolio で探索をばらけさせる)
+    pub seed: u64,
+    // 節と学習節の見積もりがこれ (バイト) を超えたら MemoryLimit で打ち切る
+    pub max_memory: Option<u64>,
+    pub phase: phase::Phase,
+    pub tuning: Tuning,
+    // 直前に解いた似た問題の解 (編集中の盤面の解き直しなど). 初期 phase として使う
+    pub warm_start: Option<Sol>,
+    // 探索を止めずに途中経過を外から読むため (同じ config で解いた全 Session の合計)
+    pub progress: Option<std::sync::Arc<Progress>>,
+    pub backend: backend::Backend,
+    // 人の手筋で決まる辺と数字を節として先に足す
+    pub presolve: bool,
+    // 解が満たすべき規則 (被覆, 2x2, 接触, ネットのつなぎ方, 長さの手がかり)
+    pub rules: RuleSet,
+    // 盤面ごとに足す制約 (--constraints). セルの位置で書くので回した盤面には使えない
+    pub constraints: Vec<constraints::Constraint>,
+    // 探索の途中の出来事 (変数の確定, 衝突, 再始動, 解) を受け取る側
+    pub events: events::Events,
+}
+
+// varisat の探索パラメータ (None なら varisat の既定値)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Tuning {
+    // VSIDS の減衰率 (0.5..1.0, 既定 0.95)
+    pub vsids_decay: Option<f32>,
+    // 学習節の activity の減衰率 (0.5..1.0, 既定 0.999)
+    pub clause_activity_decay: Option<f32>,
+    // local / mid の学習節を整理する間隔 (conflict 数, 既定 15000 / 10000)
+    pub reduce_locals_interval: Option<u64>,
+    pub reduce_mids_interval: Option<u64>,
+    // Luby 列によるリスタートの間隔の単位 (conflict 数, 既定 128)
+    pub luby_restart_interval_scale: Option<u64>,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        SolverConfig {
+            encoding: Encoding::Log,
+            timeout: None,
+            interrupt: None,
+            seed: 0,
+            max_memory: None,
+            phase: phase::Phase::Default,
+            tuning: Tuning::default(),
+            warm_start: None,
+            progress: None,
+            backend: backend::Backend::Varisat,
+            presolve: false,
+            rules: RuleSet::default(),
+            constraints: vec![],
+            events: events::Events::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    pub elapsed: Duration,
+    // timeout か interrupt を指定したときだけ数える
+    pub conflicts: u64,
+    // 節データベースの大きさの見積もり (バイト, 数えていなければ 0)
+    pub memory: u64,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.3}s, {} conflicts", self.elapsed.as_secs_f64(), self.conflicts)?;
+
+        if self.memory > 0 {
+            write!(f, ", ~{:.1} MiB", self.memory as f64/(1 << 20) as f64)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Progress {
+    conflicts: AtomicU64,
+    // 最適化中の今の最良値 (u64::MAX ならまだない)
+    bound: AtomicU64,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress { conflicts: AtomicU64::new(0), bound: AtomicU64::new(u64::MAX) }
+    }
+}
+
+impl Progress {
+    pub fn conflicts(&self) -> u64 {
+        self.conflicts.load(Ordering::Relaxed)
+    }
+
+    pub fn bound(&self) -> Option<u64> {
+        Some(self.bound.load(Ordering::Relaxed)).filter(|&bound| bound != u64::MAX)
+    }
+
+    pub(crate) fn add_conflicts(&self, conflicts: u64) {
+        self.conflicts.fetch_add(conflicts, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_bound(&self, bound: u64) {
+        self.bound.store(bound, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    InvalidUrl,
+    InvalidField,
+    NoSolution,
+    InvalidPin,
+    Timeout(Stats),
+    Interrupted(Stats),
+    MemoryLimit(Stats),
+    InvalidTuning(String),
+    // この build では使えない backend
+    UnsupportedBackend(String),
+    BackendLoad(String),
+    BackendFailed(String),
+    // script や制約の記述が読めないか盤面に合わない
+    InvalidConstraint(String),
+    // 盤面の幅と高さ. セルの数が MAX_CELLS を超える
+    TooLarge(usize, usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidUrl => write!(f, "invalid puzzle url"),
+            Error::InvalidField => write!(f, "invalid field"),
+            Error::NoSolution => write!(f, "no solution"),
+            Error::InvalidPin => write!(f, "pinned arc is not on the board"),
+            Error::Timeout(stats) => write!(f, "timed out ({})", stats),
+            Error::Interrupted(stats) => write!(f, "interrupted ({})", stats),
+            Error::MemoryLimit(stats) => write!(f, "memory limit exceeded ({})", stats),
+            Error::InvalidTuning(msg) => write!(f, "invalid solver tuning: {}", msg),
+            Error::UnsupportedBackend(name) => write!(f, "backend `{}` is not available in this build (rebuild with --features {})", name, name),
+            Error::BackendLoad(msg) => write!(f, "cannot load solver library: {}", msg),
+            Error::BackendFailed(msg) => write!(f, "solver failed: {}", msg),
+            Error::InvalidConstraint(msg) => write!(f, "invalid constraint: {}", msg),
+            Error::TooLarge(width, height) => write!(f, "board too large ({}x{}, at most {} cells)", width, height, MAX_CELLS),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub fn solve(url: String) -> Option<(Puzzle, Sol)> {
+    solve_with_config(url, &SolverConfig::default()).ok()
+}
+
+pub fn solve_with_config(url: String, config: &SolverConfig) -> Result<(Puzzle, Sol), Error> {
+    let puzzle = try_parse_puzzle(&url)?;
+
+    let sol = solve_numberlink(&puzzle, config)?;
+
+    Ok((puzzle, sol))
+}
+
+// 節を生成しながら formula (Solver に直接渡せば CnfFormula を作らずに済む) に追加し, 次に使える変数番号を返す
+// 盤面の大きさだけで決まる節は template::template で使い回し, ここでは手がかりに依存する節だけを作る
+pub fn encode<F: ExtendFormula>(puzzle: &Puzzle, config: &SolverConfig, formula: &mut F) -> Result<usize, Error> {
+    encode_with(puzzle, config, &mut Tagger::new(formula, None))
+}
+
+// encode の本体. 節ごとの出どころ (tags::Family) を formula に伝えながら作る
+pub(crate) fn encode_with<F: ExtendFormula>(puzzle: &Puzzle, config: &SolverConfig, formula: &mut Tagger<F>) -> Result<usize, Error> {
+    if puzzle.width == 0 || puzzle.height == 0 || puzzle.cells.len() != puzzle.width*puzzle.height {
+        return Err(Error::InvalidField);
+    }
+
+    // maxlen は盤面の長さの上限として符号化する
+    let limited = constraints::limited(puzzle, &config.constraints, config.rules.lengths)?;
+    let puzzle = limited.as_ref().unwrap_or(puzzle);
+
+    formula.tag(Family::Constraint, None);
+    constraints::encode_constraints(formula, puzzle, &config.constraints)?;
+
+    if config.rules.lengths && !puzzle.dots {
+        return lengths::encode_lengths(puzzle, config, formula);
+    }
+
+    let roles = parse_field(puzzle).ok_or(Error::InvalidField)?;
+    let adjs = gen_adjs(puzzle);
+
+    /* "Solving Nubmerlink by a SAT-based Constraint Solver" (https://ipsj.ixsq.nii.ac.jp/ej/index.php?action=pages_view_main&active_action=repository_action_common_download&item_id=102780&item_no=1&attribute_id=1&file_no=1&page_id=13&block_id=8) */
+    let template = template::add_template(puzzle, config, formula);
+
+    // セルごとの作業用 (確保し直さない)
+    let topology = puzzle.topology;
+    let mut outs: Vec<Lit> = Vec::with_capacity(topology.slots());
+    let mut ins: Vec<Lit> = Vec::with_capacity(topology.slots());
+    // 線のネットの端を決めるのに使う, セルごとの入る arc
+    let mut net_ins: Vec<Vec<Lit>> = vec![vec![]; puzzle.cells.len()];
+    let nets = nets::net_numbers(puzzle);
+    // 線のネットは最初のセルも端とは限らない
+    let tree = config.rules.nets == nets::Nets::Tree;
+
+    for &u in &template.order {
+        let vs = &adjs[u];
+
+        outs.clear();
+        outs.extend(vs.iter().map(|&(_, d)| topology.arc_var(u, d).positive()));
+        ins.clear();
+        ins.extend(vs.iter().map(|&(v, d)| topology.arc_var(v, topology.opposite(d)).positive()));
+
+        if roles[u] == Role::Dot {
+            formula.tag(Family::Dot, Some(u));
+            encode_dot(formula, config.encoding, &template.numbers[u], puzzle.cells[u], &outs, &ins);
+            continue;
+        }
+
+        formula.tag(Family::Clue, Some(u));
+        encode_clue(formula, config.encoding, &template.numbers[u], puzzle.cells[u]);
+
+        if roles[u] == Role::Terminal || (roles[u] == Role::Start && !tree && nets.contains(&puzzle.cells[u])) {
+            formula.tag(Family::Net, Some(u));
+            nets::encode_terminal(formula, config.rules.nets, &outs, &ins);
+            net_ins[u] = ins.clone();
+            continue;
+        }
+
+        // 通る線の決まった空白はその数字
+        if let Some(&num) = puzzle.waypoints.get(&u) {
+            encode_clue(formula, config.encoding, &template.numbers[u], num);
+        }
+
+        if roles[u] == Role::Blank && !config.rules.coverage && !puzzle.waypoints.contains_key(&u) {
+            formula.tag(Family::Optional, Some(u));
+            rules::encode_optional(formula, &outs, &ins);
+            continue;
+        }
+
+        formula.tag(match roles[u] {
+            Role::Start => Family::Start,
+            Role::End => Family::End,
+            _ => Family::Blank,
+        }, Some(u));
+
+        if roles[u] != Role::Bridge {
+            encode_role(formula, roles[u], &outs, &ins);
+            continue;
+        }
+
+        // 橋は横と縦のそれぞれで空白と同じく 1 本ずつ出入りする (まっすぐ通ることは (2) と軸ごとの高々 1 本から従う)
+        for axis in 0..2 {
+            outs.clear();
+            outs.extend(vs.iter().filter(|&&(_, d)| d%2 == axis).map(|&(_, d)| topology.arc_var(u, d).positive()));
+            ins.clear();
+            ins.extend(vs.iter().filter(|&&(_, d)| d%2 == axis).map(|&(v, d)| topology.arc_var(v, topology.opposite(d)).positive()));
+
+            encode_role(formula, Role::Blank, &outs, &ins);
+        }
+    }
+
+    encode_blocked(formula, puzzle);
+
+    // 推論は橋もネットも点も知らない
+    if config.presolve && sat_only(puzzle, config).is_none() {
+        formula.tag(Family::Deduction, None);
+        deduce::encode_deduction(formula, puzzle, config.encoding, &template.numbers)?;
+    }
+
+    let mut next_var = template.next_var;
+
+    if config.rules.nets == nets::Nets::Path {
+        formula.tag(Family::Net, None);
+        next_var = nets::encode_ends(formula, puzzle, &net_ins, next_var);
+    }
+
+    formula.tag(Family::Length, None);
+    next_var = lengths::encode_limits(puzzle, config, formula, next_var);
+    next_var = lengths::encode_budget(puzzle, formula, next_var);
+
+    formula.tag(Family::Rule, None);
+
+    Ok(rules::encode_rules(formula, puzzle, &config.rules, config.encoding, &template.numbers, next_var))
+}
+
+// template は盤面の大きさだけで作るので, 壁をまたぐ arc, 穴に出入りする arc と一方通行を逆に進む arc をここで使えなくする
+fn encode_blocked<F: ExtendFormula>(formula: &mut Tagger<F>, puzzle: &Puzzle) {
+    for x in blocked_arcs(puzzle) {
+        formula.tag(Family::Blocked, Some((x.index()-1)/puzzle.topology.slots()));
+        formula.add_clause(&[x.negative()]);
+    }
+}
+
+fn blocked_arcs(puzzle: &Puzzle) -> Vec<Var> {
+    if puzzle.walls.is_empty() && puzzle.holes.is_empty() && puzzle.one_way.is_empty() && !matches!(puzzle.topology, Topology::Layers(_)) {
+        return vec![];
+    }
+
+    let adjs = grid_adjs(puzzle.width, puzzle.height, puzzle.topology, &puzzle.warps);
+
+    adjs.iter().enumerate().flat_map(|(u, vs)| vs.iter().filter(move |&&(v, _)| !puzzle.is_open(u, v) || puzzle.one_way.contains(&(v, u))).map(move |&(_, d)| puzzle.topology.arc_var(u, d))).collect()
+}
+
+// (11)
+fn encode_clue<F: ExtendFormula>(formula: &mut F, encoding: Encoding, vars: &[usize], num: usize) {
+    if num == 0 {
+        return;
+    }
+
+    for lit in number_lits(encoding, vars, num) {
+        formula.add_clause(&[lit]);
+    }
+}
+
+// 数字が num のとき真になるリテラル (どれも真)
+fn number_lits(encoding: Encoding, vars: &[usize], num: usize) -> Vec<Lit> {
+    match encoding {
+        Encoding::Log => vars.iter().enumerate().map(|(b, &x)| Lit::from_var(Var::from_index(x), ((num-1)>>b&1) != 0)).collect(),
+        Encoding::Direct => vec![Var::from_index(vars[num-1]).positive()],
+    }
+}
+
+// 点は始点にも終点にもなる. 線の数字を始点の点の番号にして, 終点の点の番号はそれより大きいとする
+// (1 本の線を逆向きにたどった解を数えない)
+fn encode_dot<F: ExtendFormula>(formula: &mut F, encoding: Encoding, vars: &[usize], num: usize, outs: &[Lit], ins: &[Lit]) {
+    let own = number_lits(encoding, vars, num);
+    let values = match encoding {
+        Encoding::Log => 1 << vars.len(),
+        Encoding::Direct => vars.len(),
+    };
+
+    formula.add_clause(&outs.iter().chain(ins).cloned().collect::<Vec<Lit>>());
+
+    for &x in outs {
+        for &y in ins {
+            formula.add_clause(&[!x, !y]);
+        }
+
+        for &lit in &own {
+            formula.add_clause(&[!x, lit]);
+        }
+    }
+
+    for &y in ins {
+        formula.add_clause(&std::iter::once(!y).chain(own.iter().map(|&lit| !lit)).collect::<Vec<Lit>>());
+    }
+
+    for v in num+1..=values {
+        formula.add_clause(&number_lits(encoding, vars, v).into_iter().map(|lit| !lit).collect::<Vec<Lit>>());
+    }
+}
+
+// SAT の符号化でしか扱えない盤面の要素や規則 (なければ None)
+pub fn sat_only(puzzle: &Puzzle, config: &SolverConfig) -> Option<&'static str> {
+    if let Some(rule) = config.rules.sat_only() {
+        Some(rule)
+    } else if !config.constraints.is_empty() {
+        Some("custom constraints")
+    } else if !puzzle.bridges.is_empty() {
+        Some("bridges")
+    } else if puzzle.topology == Topology::Diagonal {
+        Some("diagonal moves")
+    } else if !puzzle.waypoints.is_empty() {
+        Some("waypoints")
+    } else if !puzzle.one_way.is_empty() {
+        Some("one-way passages")
+    } else if !puzzle.limits.is_empty() {
+        Some("path length limits")
+    } else if puzzle.budget.is_some() {
+        Some("a cost budget")
+    } else if !nets::net_numbers(puzzle).is_empty() {
+        Some("numbers with more than two cells")
+    } else if puzzle.dots {
+        Some("unlabeled dots")
+    } else {
+        None
+    }
+}
+
+// 高々 1 本は template 側にあるので, 少なくとも 1 本か 0 本かだけを足す
+fn encode_role<F: ExtendFormula>(formula: &mut F, role: Role, outs: &[Lit], ins: &[Lit]) {
+    match role {
+        Role::Start => {
+            // (3)
+            formula.add_clause(outs);
+
+            // (4)
+            for &x in ins {
+                formula.add_clause(&[!x]);
+            }
+        },
+        Role::End => {
+            // (5)
+            for &x in outs {
+                formula.add_clause(&[!x]);
+            }
+
+            // (6)
+            formula.add_clause(ins);
+        },
+        Role::Blank => {
+            // (8) (9)
+            formula.add_clause(outs);
+            formula.add_clause(ins);
+        },
+        // 出入りする arc は encode_blocked で偽にしてある
+        Role::Hole => {},
+        Role::Bridge | Role::Terminal | Role::Dot => unreachable!(),
+    }
+}
+
+pub fn encode_formula(puzzle: &Puzzle, config: &SolverConfig) -> Result<CnfFormula, Error> {
+    let mut formula = CnfFormula::new();
+
+    encode(puzzle, config, &mut formula)?;
+
+    Ok(formula)
+}
+
+pub fn solve_numberlink(puzzle: &Puzzle, config: &SolverConfig) -> Result<Sol, Error> {
+    match config.backend {
+        backend::Backend::Backtrack => return backtrack::solve_backtrack(puzzle, config),
+        backend::Backend::Zdd => return zdd::solve_zdd(puzzle, config),
+        #[cfg(feature = "ilp")]
+        backend::Backend::Ilp => return ilp::solve_ilp(puzzle, config),
+        #[cfg(not(feature = "ilp"))]
+        backend::Backend::Ilp => return Err(Error::UnsupportedBackend("ilp".to_string())),
+        backend::Backend::Auto => {
+            let config = SolverConfig { backend: backend::select(puzzle, config, backend::Query::Solve), ..config.clone() };
+
+            return solve_numberlink(puzzle, &config);
+        },
+        _ => {},
+    }
+
+    let sol = Session::new(puzzle, config)?.solve()?;
+
+    Ok(tidy::shorten(puzzle, config, &sol))
+}
+
+// 解の数 (arc の集合として数える). zdd は ZDD の上で数え, SAT ソルバは見つけた解を除きながら 1 つずつ数え上げる
+pub fn count_numberlink(puzzle: &Puzzle, config: &SolverConfig) -> Result<u128, Error> {
+    match config.backend {
+        backend::Backend::Zdd => return zdd::count_solutions(puzzle, config),
+        backend::Backend::Auto => {
+            let config = SolverConfig { backend: backend::select(puzzle, config, backend::Query::Count), ..config.clone() };
+
+            return count_numberlink(puzzle, &config);
+        },
+        _ => {},
+    }
+
+    let mut session = Session::new(puzzle, config)?;
+    let mut count = 0;
+
+    loop {
+        match session.solve() {
+            Ok(sol) => session.exclude(&sol)?,
+            Err(Error::NoSolution) => return Ok(count),
+            Err(e) => return Err(e),
+        }
+
+        count += 1;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Start,
+    End,
+    Blank,
+    Hole,
+    Bridge,
+    // 3 回以上現れる数字の, 最初以外のセル
+    Terminal,
+    // 組の決まっていない点 (線が 1 本だけ出入りする)
+    Dot,
+}
+
+impl Role {
+    // 出ていく arc と入ってくる arc の本数
+    fn degrees(self) -> (usize, usize) {
+        match self {
+            Role::Start => (1, 0),
+            Role::End => (0, 1),
+            Role::Blank => (1, 1),
+            Role::Hole => (0, 0),
+            Role::Bridge => (2, 2),
+            Role::Terminal | Role::Dot => unreachable!("only the SAT encoding supports nets and dots"),
+        }
+    }
+}
+
+// 各数字が 2 回以上現れるときだけ, 先に現れた方を始点とする (3 回以上なら残りは Terminal)
+fn parse_field(puzzle: &Puzzle) -> Option<Vec<Role>> {
+    let n = puzzle.cells.iter().cloned().max().unwrap_or(0);
+    let mut cnt = vec![0; n+1];
+    let mut roles = vec![Role::Blank; puzzle.cells.len()];
+
+    // 橋は四角い盤面の縦と横の線が交わるところにしか置けない
+    if puzzle.topology != Topology::Square && !puzzle.bridges.is_empty() {
+        return None;
+    }
+
+    for (index, &p) in puzzle.cells.iter().enumerate() {
+        if puzzle.is_hole(index) || puzzle.is_bridge(index) {
+            if p > 0 || (puzzle.is_hole(index) && puzzle.is_bridge(index)) {
+                return None;
+            }
+
+            roles[index] = if puzzle.is_hole(index) { Role::Hole } else { Role::Bridge };
+        } else if p > 0 && puzzle.dots {
+            roles[index] = Role::Dot;
+            cnt[p] += 1;
+        } else if p > 0 {
+            roles[index] = if cnt[p] == 0 { Role::Start } else { Role::End };
+            cnt[p] += 1;
+        }
+    }
+
+    // 通る点は空白に置き, 数字はある数字 (点の盤面では数字を決められない)
+    for (&index, &num) in &puzzle.waypoints {
+        if index >= roles.len() || roles[index] != Role::Blank || puzzle.cells[index] > 0 || (num > 0 && (puzzle.dots || num > n || cnt[num] == 0)) {
+            return None;
+        }
+    }
+
+    // 点の線はどちらの端から引くかを解で決めないので, 一方通行は置けない. 長さの上限は数字ごとなので点にはない
+    if puzzle.dots && (!puzzle.one_way.is_empty() || !puzzle.limits.is_empty()) {
+        return None;
+    }
+
+    if puzzle.limits.keys().any(|&num| num > n || cnt[num] == 0) {
+        return None;
+    }
+
+    // 点はどれも別の番号で, 偶数個
+    if puzzle.dots {
+        return (n > 0 && n%2 == 0 && cnt.iter().skip(1).all(|&c| c == 1)).then_some(roles);
+    }
+
+    if n == 0 || cnt.iter().skip(1).any(|&c| c == 1) {
+        return None;
+    }
+
+    for (index, &p) in puzzle.cells.iter().enumerate() {
+        if roles[index] == Role::End && cnt[p] > 2 {
+            roles[index] = Role::Terminal;
+        }
+    }
+
+    Some(roles)
+}
+
+// 節ごとに Vec を作らず, 固定長の配列のまま formula に渡す
+fn mk_clause_impl<F: ExtendFormula>(formula: &mut F, x: &Var, fu: &[usize], fv: &[usize]) {
+    for (&fuidx, &fvidx) in fu.iter().zip(fv) {
+        let fui = Var::from_index(fuidx);
+        let fvi = Var::from_index(fvidx);
+
+        formula.add_clause(&[x.negative(), fui.negative(), fvi.positive()]);
+        formula.add_clause(&[x.negative(), fui.positive(), fvi.negative()]);
+    }
+}
+
+// adj と同じ順 (右, 下, 左, 上)
+const DIRS: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+// 盤上で隣り合うかワープでつながった 2 セル間の arc なら対応する変数
+fn find_arc_var(puzzle: &Puzzle, (u, v): Arc) -> Option<Var> {
+    let d = puzzle.direction(u, v)?;
+
+    Some(puzzle.topology.arc_var(puzzle.index(u), d))
+}
+
+fn var_arc(x: Var, puzzle: &Puzzle) -> Option<Arc> {
+    let index = x.index();
+
+    if index == 0 || index > puzzle.topology.arc_var_count(puzzle.width, puzzle.height) {
+        return None;
+    }
+
+    let slots = puzzle.topology.slots();
+    let (u, d) = ((index-1)/slots, (index-1)%slots);
+    let p = puzzle.pos(u);
+
+    match puzzle.step(p, d) {
+        Some(q) => Some((p, q)),
+        None => puzzle.warps.get(&(u, d)).map(|&v| (p, puzzle.pos(v))),
+    }
+}
+
+// 壁と穴を除いたセル u の隣接セル (v, u から見た向き)
+fn gen_adjs(puzzle: &Puzzle) -> Vec<Vec<(usize, usize)>> {
+    let mut res = grid_adjs(puzzle.width, puzzle.height, puzzle.topology, &puzzle.warps);
+
+    for (u, vs) in res.iter_mut().enumerate() {
+        vs.retain(|&(v, _)| puzzle.is_open(u, v));
+    }
+
+    res
+}
+
+// 壁を考えないセル u の隣接セル (v, u から見た向き), 盤面ごとに 1 回だけ作る
+// ワープは盤外に出る向きの隣として入る
+fn grid_adjs(width: usize, height: usize, topology: Topology, warps: &BTreeMap<(usize, usize), usize>) -> Vec<Vec<(usize, usize)>> {
+    let mut res = Vec::with_capacity(width*height);
+
+    for i in 0..height {
+        for j in 0..width {
+            let mut adjs = Vec::with_capacity(topology.slots());
+
+            for d in 0..topology.slots() {
+                if let Some((ni, nj)) = topology.step(width, height, (i, j), d) {
+                    adjs.push((ni*width+nj, d));
+                } else if let Some(&v) = warps.get(&(i*width+j, d)) {
+                    adjs.push((v, d));
+                }
+            }
+
+            res.push(adjs);
+        }
+    }
+
+    res
+}
+
+// URL か, input の JSON かテキストの形式
+pub fn parse_puzzle(input: &str) -> Option<Puzzle> {
+    try_parse_puzzle(input).ok()
+}
+
+// parse_puzzle と同じだが, 読めなければ理由を返す (URL の大きさが大きすぎれば TooLarge, ほかは InvalidUrl)
+pub fn try_parse_puzzle(input: &str) -> Result<Puzzle, Error> {
+    match try_parse_url(input) {
+        Err(Error::InvalidUrl) => input::parse_json(input).or_else(|| input::parse_text(input)).ok_or(Error::InvalidUrl),
+        res => res,
+    }
+}
+
+pub fn parse_url(url: String) -> Option<Puzzle> {
+    try_parse_url(&url).ok()
+}
+
+pub fn try_parse_url(url: &str) -> Result<Puzzle, Error> {
+    let splitter = '/';
+    let params: Vec<String> = url.split(splitter).map(|s| s.to_string()).collect();
+    let length = params.len();
+
+    if length < 3 {
+        return parse_code(url);
+    }
+
+    let width = params[length-3].parse().unwrap_or(0);
+    let height = params[length-2].parse().unwrap_or(0);
+
+    if width == 0 || height == 0 {
+        return parse_code(url);
+    }
+
+    let field_code = params[length-1].clone();
+
+    if !is_valid_code(&field_code) {
+        return Err(Error::InvalidUrl);
+    }
+
+    // numlin/b/W/H/... は数字の前に壁がある
+    if length >= 4 && params[length-4] == "b" {
+        Puzzle::try_new(width, height)?;
+
+        let (walls, rest) = decode_border(width, height, &field_code).ok_or(Error::InvalidUrl)?;
+        let mut puzzle = decode_field(width, height, rest.to_string())?;
+
+        for (p, q) in walls {
+            puzzle.add_wall(p, q);
+        }
+
+        return Ok(puzzle);
+    }
+
+    decode_field(width, height, field_code)
+}
+
+// URL から盤面の部分だけを写したもの: `WxH CODE` (`CODE WxH`, `WxH/CODE` でもよい) か CODE だけ
+// 大きさがなければ, CODE の表すセルの数が平方数のときだけ正方形の盤面とみなす
+fn parse_code(input: &str) -> Result<Puzzle, Error> {
+    let words: Vec<&str> = input.split(|ch: char| ch.is_whitespace() || ch == '/').filter(|word| !word.is_empty()).collect();
+    let size = |word: &str| -> Option<(usize, usize)> {
+        let (width, height) = word.split_once('x')?;
+
+        Some((width.parse().ok()?, height.parse().ok()?))
+    };
+
+    let (width, height, code) = match words[..] {
+        [a, b] => match (size(a), size(b)) {
+            (Some((width, height)), None) => (width, height, b),
+            (None, Some((width, height))) => (width, height, a),
+            _ => return Err(Error::InvalidUrl),
+        },
+        [code] => {
+            let cells = code_cells(code).ok_or(Error::InvalidUrl)?;
+            let side = (cells as f64).sqrt().round() as usize;
+
+            if side*side != cells {
+                return Err(Error::InvalidUrl);
+            }
+
+            (side, side, code)
+        },
+        _ => return Err(Error::InvalidUrl),
+    };
+
+    if width == 0 || height == 0 || !is_valid_code(code) {
+        return Err(Error::InvalidUrl);
+    }
+
+    decode_field(width, height, code.to_string())
+}
+
+// CODE が数字と空白の続きとして表すセルの数
+fn code_cells(code: &str) -> Option<usize> {
+    let list: Vec<char> = code.chars().collect();
+    let (mut index, mut rows, mut cells) = (0, 0, 0);
+
+    while index < list.len() {
+        let start = index;
+
+        while index < list.len() && get_num(&mut index, &list).is_some() {
+            cells += 1;
+        }
+
+        consume(&mut index, &mut rows, &mut cells, usize::MAX, &list);
+
+        if index == start {
+            return None;
+        }
+    }
+
+    Some(cells)
+}
+
+// puzz.link の境界線の形式: 縦の境界 (W-1)*H 本, 横の境界 W*(H-1) 本を 5 本ずつ 32 進の 1 文字にしたもの
+fn decode_border(width: usize, height: usize, code: &str) -> Option<(Vec<Arc>, &str)> {
+    let vertical = (width-1)*height;
+    let horizontal = width*(height-1);
+    let len = vertical.div_ceil(5)+horizontal.div_ceil(5);
+
+    let mut bits = vec![];
+
+    for (c, ch) in code.get(..len)?.chars().enumerate() {
+        let digit = ch.to_digit(32)?;
+
+        // 縦の境界の最後の文字の余りのビットは捨てる
+        let count = if c < vertical.div_ceil(5) { 5.min(vertical-c*5) } else { 5.min(horizontal-(c-vertical.div_ceil(5))*5) };
+
+        bits.extend((0..count).map(|b| digit >> (4-b)&1 != 0));
+    }
+
+    let walls = bits.iter().enumerate().filter(|&(_, &wall)| wall).map(|(id, _)| if id < vertical {
+        let (i, j) = (id/(width-1), id%(width-1));
+
+        ((i, j), (i, j+1))
+    } else {
+        let (i, j) = ((id-vertical)/width, (id-vertical)%width);
+
+        ((i, j), (i+1, j))
+    }).collect();
+
+    Some((walls, &code[len..]))
+}
+
+pub(crate) fn is_valid_code(code: &str) -> bool {
+    code.chars().all(|ch| char::is_alphanumeric(ch) || ch == '-')
+}
+
+pub(crate) fn decode_field(width: usize, height: usize, code: String) -> Result<Puzzle, Error> {
+    let list: &Vec<char> = &code.chars().collect();
+    let mut index: usize = 0;
+    let mut i: usize = 0;
+    let mut j: usize = 0;
+
+    let mut res = Puzzle::try_new(width, height)?;
+
+    while index < list.len() {
+        let start = index;
+
+        while let Some(num) = get_num(&mut index, list) {
+            if i >= height {
+                return Err(Error::InvalidUrl);
+            }
+
+            res.set((i, j), num);
+
+            j += 1;
+
+            if j >= width {
+                j = 0;
+                i += 1;
+            }
+
+            if index >= list.len() {
+                break;
+            }
+        }
+
+        consume(&mut index, &mut i, &mut j, width, list);
+
+        if index == start {
+            return Err(Error::InvalidUrl);
+        }
+    }
+
+    Ok(res)
+}
+
+fn get_num(index: &mut usize, list: &[char]) -> Option<usize> {
+    let ch = list[*index];
+
+    if ch == '-' {
+        *index += 1;
+        let mut res = 0;
+
+        while res*16 < 100 && *index < list.len() && list[*index].is_ascii_hexdigit() {
+            res *= 16;
+            res += list[*index].to_digit(16).unwrap();
+            *index += 1;
+        }
+
+        Some(res as usize)
+    } else if ch.is_ascii_hexdigit() {
+        *index += 1;
+
+        ch.to_digit(16).map(|num| num as usize)
+    } else {
+        None
+    }
+}
+
+fn consume(index: &mut usize, i: &mut usize, j: &mut usize, width: usize, list: &[char]) {
+    let length = list.len();
+
+    while *index < length && !list[*index].is_ascii_hexdigit() {
+        let ch = list[*index];
+        let value = (ch as i32) - ('f' as i32);
+
+        if value <= 0 {
+            return;
+        }
+
+        let value: usize = value as usize;
+
+        *j += value;
+
+        if *j >= width {
+            *i += *j/width;
+            *j %= width;
+        }
+
+        *index += 1;
+    }
+}
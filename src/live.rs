@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc as Shared, Mutex};
+
+use crate::events::{arc_of, Event, Events};
+use crate::{Arc, Puzzle, Sol, Topology, P};
+
+// 探索の途中で分かったこと. 決まった arc は解でもそのまま (Event::Fixed)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LiveState {
+    pub fixed: BTreeMap<Arc, bool>,
+    pub conflicts: u64,
+    pub sol: Option<Sol>,
+}
+
+// events に受け取る側を足し, 出来事を書き込む先を返す. 描く側は好きな間隔でこれを読んで render_live に渡す
+pub fn subscribe_live(puzzle: &Puzzle, events: &mut Events) -> Shared<Mutex<LiveState>> {
+    let state = Shared::new(Mutex::new(LiveState::default()));
+    let (puzzle, shared) = (puzzle.clone(), state.clone());
+
+    events.subscribe(move |event: &Event| {
+        let mut state = shared.lock().unwrap();
+
+        match *event {
+            Event::Fixed { var, value } => {
+                if let Some(arc) = arc_of(&puzzle, var) {
+                    state.fixed.insert(arc, value);
+                }
+            },
+            Event::Conflict { conflicts } => state.conflicts = state.conflicts.max(conflicts),
+            // --live は varisat だけなので再始動は届かない
+            Event::Restart { .. } => {},
+            Event::Model { ref sol } => state.sol = Some(sol.clone()),
+        }
+    });
+
+    state
+}
+
+// 今の盤面を 1 コマ. 数字はそのまま, 線の通ると決まったセルは決まった arc の向きの罫線 (四角い盤面のほかは `*`),
+// 出入りするどの arc も通らないと決まったセルは空白, まだ分からないセルは `.`. 解が届いたら解の線を描く
+//
+//     1 ─ ┐ .
+//     . . │ .
+//     . . 1 .
+//     fixed 12/48 arcs, 57 conflicts
+pub fn render_live(puzzle: &Puzzle, state: &LiveState) -> String {
+    let fixed: BTreeMap<Arc, bool> = match &state.sol {
+        Some(sol) => sol.iter().map(|&arc| (arc, true)).collect(),
+        None => state.fixed.clone(),
+    };
+    let arcs: Vec<Arc> = puzzle.edges().into_iter().flat_map(|(u, v)| {
+        let (p, q) = (puzzle.pos(u), puzzle.pos(v));
+
+        [(p, q), (q, p)]
+    }).collect();
+    let mut sides: BTreeMap<P, Vec<usize>> = BTreeMap::new();
+    let mut open: BTreeMap<P, bool> = BTreeMap::new();
+
+    for &(p, q) in &arcs {
+        for (a, b) in [(p, q), (q, p)] {
+            match fixed.get(&(p, q)) {
+                Some(true) => sides.entry(a).or_default().extend(puzzle.direction(a, b)),
+                Some(false) => {},
+                None => {
+                    open.insert(a, true);
+                },
+            }
+        }
+    }
+
+    let w = puzzle.cells.iter().max().map_or(1, |max| max.to_string().len());
+    let glyph = |p: P| -> String {
+        let u = puzzle.index(p);
+        let s = match sides.get(&p) {
+            _ if puzzle.is_outside(u) => " ".to_string(),
+            _ if puzzle.is_hole(u) => "#".to_string(),
+            _ if puzzle.get(p) > 0 => puzzle.get(p).to_string(),
+            Some(sides) => line(puzzle, sides).to_string(),
+            None if open.contains_key(&p) => ".".to_string(),
+            None => " ".to_string(),
+        };
+
+        format!("{:>w$}", s, w = w)
+    };
+    let mut res = String::new();
+
+    for i in 0..puzzle.height {
+        let row: Vec<String> = (0..puzzle.width).map(|j| glyph((i, j))).collect();
+
+        res.push_str(row.join(" ").trim_end());
+        res.push('\n');
+    }
+
+    let known = arcs.iter().filter(|arc| fixed.contains_key(arc)).count();
+
+    res.push_str(&match state.sol {
+        Some(_) => format!("solved, {} conflicts\n", state.conflicts),
+        None => format!("fixed {}/{} arcs, {} conflicts\n", known, arcs.len(), state.conflicts),
+    });
+
+    res
+}
+
+// 向き (右, 下, 左, 上) の組の罫線
+fn line(puzzle: &Puzzle, sides: &[usize]) -> char {
+    let mut sides = sides.to_vec();
+
+    sides.sort_unstable();
+    sides.dedup();
+
+    if puzzle.topology != Topology::Square {
+        return '*';
+    }
+
+    match sides[..] {
+        [0, 2] => '─',
+        [1, 3] => '│',
+        [0, 1] => '┌',
+        [1, 2] => '┐',
+        [0, 3] => '└',
+        [2, 3] => '┘',
+        [0] => '╶',
+        [1] => '╷',
+        [2] => '╴',
+        [3] => '╵',
+        _ => '*',
+    }
+}
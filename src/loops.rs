@@ -0,0 +1,324 @@
+use std::collections::BTreeSet;
+use std::sync::atomic::Ordering;
+use std::sync::Arc as Shared;
+
+use varisat::{CnfFormula, ExtendFormula, Lit, Var};
+
+use crate::backend::{self, Backend, Stop};
+use crate::lengths::encode_count;
+use crate::monitor::Control;
+use crate::render::Format;
+use crate::{Error, Instant, SolverConfig, Stats, P};
+
+// Numberlink 以外の格子のパズルの共通部分: 格子と辺の変数, 個数の制約, つながりの遅延制約, 解の値の取り出し
+// 1 本の輪を引くパズル (Slitherlink は点の格子, Masyu はセルの格子) は solve_loop で,
+// 辺の変数を先に作って次数 (0 か 2) を節で書き, 輪が 2 つ以上に分かれた解は節を足して解き直す
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grid {
+    pub rows: usize,
+    pub cols: usize,
+}
+
+impl Grid {
+    // 横の辺 rows*(cols-1) 本のあとに縦の辺 (rows-1)*cols 本
+    pub fn edge_count(&self) -> usize {
+        self.rows*(self.cols-1) + (self.rows-1)*self.cols
+    }
+
+    // 隣り合う格子点 p と q の間の辺の番号 (変数の番号と同じ)
+    pub fn edge(&self, p: P, q: P) -> Option<usize> {
+        let ((i, j), (k, l)) = (p.min(q), p.max(q));
+
+        match () {
+            _ if k >= self.rows || l >= self.cols => None,
+            _ if i == k && j+1 == l => Some(i*(self.cols-1)+j),
+            _ if i+1 == k && j == l => Some(self.rows*(self.cols-1) + i*self.cols+j),
+            _ => None,
+        }
+    }
+
+    pub fn ends(&self, e: usize) -> (P, P) {
+        let horizontal = self.rows*(self.cols-1);
+
+        if e < horizontal {
+            let (i, j) = (e/(self.cols-1), e%(self.cols-1));
+
+            ((i, j), (i, j+1))
+        } else {
+            let (i, j) = ((e-horizontal)/self.cols, (e-horizontal)%self.cols);
+
+            ((i, j), (i+1, j))
+        }
+    }
+
+    // 行優先の全格子点
+    pub fn cells(&self) -> impl Iterator<Item = P> {
+        let cols = self.cols;
+
+        (0..self.rows).flat_map(move |i| (0..cols).map(move |j| (i, j)))
+    }
+
+    // p から右, 下, 左, 上 (d = 0..4) へ 1 歩 (格子の中なら)
+    pub fn step(&self, (i, j): P, d: usize) -> Option<P> {
+        let (di, dj) = [(0, 1), (1, 0), (0, -1), (-1, 0)][d];
+        let (k, l) = (i as i32 + di, j as i32 + dj);
+
+        ((0..self.rows as i32).contains(&k) && (0..self.cols as i32).contains(&l)).then_some((k as usize, l as usize))
+    }
+
+    // p の右, 下, 左, 上の隣 (格子の中にあるものだけ)
+    pub fn neighbours(&self, p: P) -> Vec<P> {
+        (0..4).filter_map(|d| self.step(p, d)).collect()
+    }
+
+    pub fn edge_var(&self, p: P, q: P) -> Option<Var> {
+        self.edge(p, q).map(Var::from_index)
+    }
+
+    // p に出入りする辺の変数
+    pub fn incident(&self, p: P) -> Vec<Var> {
+        self.neighbours(p).into_iter().filter_map(|q| self.edge_var(p, q)).collect()
+    }
+}
+
+// grid の上の 1 本の輪を探して, 通る格子点を順に返す (最初の点には戻らない)
+// encoder は辺の変数 (番号 0..edge_count) のあとに変数を作って, パズルごとの節を足す
+pub(crate) fn solve_loop(grid: &Grid, config: &SolverConfig, encoder: impl FnOnce(&mut CnfFormula)) -> Result<Vec<P>, Error> {
+    let mut formula = CnfFormula::new();
+    let edges = grid.edge_count();
+
+    formula.set_var_count(edges);
+
+    // 次数は 0 か 2 (1 本だけ, 3 本以上はない)
+    for p in grid.cells() {
+        let incident = grid.incident(p);
+
+        for (k, &e) in incident.iter().enumerate() {
+            formula.add_clause(&std::iter::once(e.negative()).chain(incident.iter().filter(|&&f| f != e).map(|f| f.positive())).collect::<Vec<Lit>>());
+
+            for (l, &f) in incident.iter().enumerate().skip(k+1) {
+                for &g in &incident[l+1..] {
+                    formula.add_clause(&[e.negative(), f.negative(), g.negative()]);
+                }
+            }
+        }
+    }
+
+    // 空の輪は認めない
+    formula.add_clause(&(0..edges).map(|e| Var::from_index(e).positive()).collect::<Vec<Lit>>());
+
+    encoder(&mut formula);
+
+    let mut found = vec![];
+
+    solve_lazy(&formula, config, |model, next_var| {
+        let used: Vec<usize> = (0..edges).filter(|&e| model[e]).collect();
+        let loops = components(grid, &used);
+
+        if loops.len() == 1 {
+            found = cycle(grid, &loops[0]);
+
+            return vec![];
+        }
+
+        // 輪 C を全部使うなら, C の外の辺は使わない (C だけが答えの輪)
+        let mut cuts = vec![];
+
+        for component in &loops {
+            let all = Var::from_index(*next_var);
+
+            *next_var += 1;
+            cuts.push(component.iter().map(|&e| Var::from_index(e).negative()).chain(std::iter::once(all.positive())).collect());
+
+            for e in (0..edges).filter(|e| !component.contains(e)) {
+                cuts.push(vec![all.negative(), Var::from_index(e).negative()]);
+            }
+        }
+
+        cuts
+    })?;
+
+    Ok(found)
+}
+
+// formula を解き, cuts が節を返すあいだはそれを足して解き直す. 返すのは変数ごとの値
+// cuts は解の値と次に使える変数の番号を受け取る (節に新しい変数を使うなら進める)
+pub(crate) fn solve_lazy(formula: &CnfFormula, config: &SolverConfig, mut cuts: impl FnMut(&[bool], &mut usize) -> Vec<Vec<Lit>>) -> Result<Vec<bool>, Error> {
+    let control = Shared::new(Control { interrupts: config.interrupt.iter().cloned().collect(), progress: config.progress.clone(), ..Control::default() });
+    let backend = match &config.backend {
+        Backend::Auto => Backend::Varisat,
+        backend => backend.clone(),
+    };
+    let mut solver = backend::connect(&backend, config, &control)?;
+    let mut next_var = formula.var_count();
+    let start = Instant::now();
+
+    for clause in formula.iter() {
+        solver.add_clause(clause);
+    }
+
+    *control.deadline.lock().unwrap() = config.timeout.map(|timeout| start+timeout);
+
+    loop {
+        let result = solver.solve();
+        let stats = Stats { elapsed: start.elapsed(), conflicts: control.conflicts.load(Ordering::Relaxed), memory: 0 };
+
+        match result {
+            Ok(true) => {},
+            Ok(false) => return Err(Error::NoSolution),
+            Err(Stop::Timeout) => return Err(Error::Timeout(stats)),
+            Err(Stop::Interrupted) => return Err(Error::Interrupted(stats)),
+            Err(Stop::MemoryLimit) => return Err(Error::MemoryLimit(stats)),
+            Err(Stop::Failed) => return Err(Error::BackendFailed(solver.failure().unwrap_or_default())),
+        }
+
+        let model = solver.model().ok_or(Error::NoSolution)?;
+        let values: Vec<bool> = (0..formula.var_count()).map(|x| model.get(x).is_some_and(|lit| lit.is_positive())).collect();
+        let clauses = cuts(&values, &mut next_var);
+
+        if clauses.is_empty() {
+            return Ok(values);
+        }
+
+        for clause in clauses {
+            solver.add_clause(&clause);
+        }
+    }
+}
+
+// vars のちょうど n 個が真 (補助の変数は formula の変数のあとに作る)
+pub(crate) fn add_count(formula: &mut CnfFormula, vars: &[Var], n: usize) {
+    let next_var = formula.var_count();
+    let next_var = encode_count(formula, vars, n, next_var);
+
+    formula.set_var_count(next_var);
+}
+
+// 頂点 0..nodes を used の辺 links[k] でたどって, 頂点 0 から届かない頂点があれば
+// 届く頂点の組とそれ以外を結ぶ辺の番号 (そのどれかを使えばつながりうる). 全部に届けば None
+pub(crate) fn disconnected(nodes: usize, links: &[(usize, usize)], used: impl Fn(usize) -> bool) -> Option<Vec<usize>> {
+    let mut reached = vec![false; nodes];
+    let mut stack = vec![0];
+
+    if nodes == 0 {
+        return None;
+    }
+
+    reached[0] = true;
+
+    while let Some(u) = stack.pop() {
+        for &(a, b) in links.iter().enumerate().filter(|&(k, _)| used(k)).map(|(_, link)| link) {
+            for (a, b) in [(a, b), (b, a)] {
+                if a == u && !reached[b] {
+                    reached[b] = true;
+                    stack.push(b);
+                }
+            }
+        }
+    }
+
+    if reached.iter().all(|&r| r) {
+        return None;
+    }
+
+    Some((0..links.len()).filter(|&k| reached[links[k].0] != reached[links[k].1]).collect())
+}
+
+// セルの格子の輪を, 格子点を label の文字列 (一番長いものに揃える), 使った辺を `-` と `|` にして描く. JSON は輪の格子点を順に
+pub(crate) fn render_loop(grid: &Grid, cycle: &[P], format: Format, label: impl Fn(P) -> String) -> String {
+    let edges: BTreeSet<usize> = (0..cycle.len()).filter_map(|k| grid.edge(cycle[k], cycle[(k+1)%cycle.len()])).collect();
+    let used = |p: P, d: usize| grid.step(p, d).and_then(|q| grid.edge(p, q)).is_some_and(|e| edges.contains(&e));
+
+    match format {
+        Format::Debug | Format::Plugin(_) => format!("{:?}\n", cycle),
+        Format::Text => {
+            let mut res = String::new();
+            let w = (0..grid.rows).flat_map(|i| (0..grid.cols).map(move |j| (i, j))).map(|p| label(p).chars().count()).max().unwrap_or(1);
+
+            for i in 0..grid.rows {
+                let line: String = (0..grid.cols).map(|j| {
+                    let name = label((i, j));
+                    let fill = if used((i, j), 0) { "-" } else { " " };
+
+                    format!("{}{}", name, fill.repeat(w+1-name.chars().count()))
+                }).collect();
+                let below: String = (0..grid.cols).map(|j| format!("{:w$}", if used((i, j), 1) { "|" } else { "" }, w = w+1)).collect();
+
+                res.push_str(line.trim_end());
+                res.push('\n');
+
+                if i+1 < grid.rows {
+                    res.push_str(below.trim_end());
+                    res.push('\n');
+                }
+            }
+
+            res
+        },
+        Format::Json => loop_json(grid.cols, grid.rows, cycle, ""),
+    }
+}
+
+// extra は loop のあとに足すキー (`,"key":...` の形)
+pub(crate) fn loop_json(width: usize, height: usize, cycle: &[P], extra: &str) -> String {
+    let points: Vec<String> = cycle.iter().map(|&(i, j)| format!("[{},{}]", i, j)).collect();
+
+    format!("{{\"width\":{},\"height\":{},\"loop\":[{}]{}}}\n", width, height, points.join(","), extra)
+}
+
+// つながった辺の組ごとの辺の番号
+fn components(grid: &Grid, used: &[usize]) -> Vec<Vec<usize>> {
+    let mut seen = vec![false; used.len()];
+    let mut res = vec![];
+
+    for k in 0..used.len() {
+        if seen[k] {
+            continue;
+        }
+
+        let mut component = vec![used[k]];
+        let mut n = 0;
+
+        seen[k] = true;
+
+        while n < component.len() {
+            let (p, q) = grid.ends(component[n]);
+
+            for (l, &e) in used.iter().enumerate() {
+                let (r, s) = grid.ends(e);
+
+                if !seen[l] && (r == p || r == q || s == p || s == q) {
+                    seen[l] = true;
+                    component.push(e);
+                }
+            }
+
+            n += 1;
+        }
+
+        res.push(component);
+    }
+
+    res
+}
+
+// 1 本の輪の辺を, 最初の辺の端から順にたどった格子点
+fn cycle(grid: &Grid, edges: &[usize]) -> Vec<P> {
+    let (start, mut cur) = grid.ends(edges[0]);
+    let mut res = vec![start];
+    let mut prev = start;
+
+    while cur != start {
+        res.push(cur);
+
+        let next = grid.neighbours(cur).into_iter().find(|&q| q != prev && grid.edge(cur, q).is_some_and(|e| edges.contains(&e)));
+
+        prev = cur;
+        cur = match next {
+            Some(q) => q,
+            None => break,
+        };
+    }
+
+    res
+}
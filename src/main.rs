@@ -1,607 +1,2369 @@
-use std::collections::{HashMap, HashSet};
-use varisat::{CnfFormula, ExtendFormula};
-use varisat::solver::Solver;
-use varisat::{Var, Lit};
+use std::collections::BTreeMap;
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use varisat::CnfFormula;
+
+use numberlink_solver::backend::{select, Backend, Query};
+use numberlink_solver::backtrack::SMALL_CELLS;
+use numberlink_solver::cache::ResultCache;
+use numberlink_solver::canonical::dedup;
+use numberlink_solver::checkpoint;
+use numberlink_solver::compare::{agree, compare, render_outcomes};
+use numberlink_solver::crosscheck::{crosscheck, render_verdict, Checker};
+use numberlink_solver::experiment::{matrix, render_table, run};
+use numberlink_solver::optimize::{all_optimal, minimize_cost, minimize_turns, minimize_turns_from, Objective, Optimized};
+use numberlink_solver::phase::Phase;
+use numberlink_solver::portfolio::{portfolio_configs, solve_portfolio, solve_portfolio_ordered};
+use numberlink_solver::estimate::estimate;
+use numberlink_solver::minimize::minimize;
+use numberlink_solver::repair::{suggest_solvable, suggest_unique};
+use numberlink_solver::predict::{predict, Difficulty};
+use numberlink_solver::profile::profile;
+use numberlink_solver::rate::rate_with;
+use numberlink_solver::screen::screen;
+use numberlink_solver::source::{blocking, open_source};
+use numberlink_solver::tags::{encode_tagged, family_stats, render_families, unsat_core};
+use numberlink_solver::report::{entries, render_report, ReportFormat};
+use numberlink_solver::region::solve_regions;
+use numberlink_solver::transform::{crop, renumber, tile, transform, Transform};
+use numberlink_solver::zdd::Zdd;
+use numberlink_solver::render::{render, render_error_line, render_flow, render_line, render_puzzle_json, Format};
+use numberlink_solver::generate::{daily_seed, from_paths, generate_with, quality, today, write_puzzle, GenConfig, Output, Symmetry, Target, MAX_GENERATE_CELLS};
+use numberlink_solver::genre::{detect, solve_genre, Genre};
+use numberlink_solver::deduce::Grade;
+use numberlink_solver::diff::{diff, render_diff};
+use numberlink_solver::live::{render_live, subscribe_live};
+use numberlink_solver::greedy::{render_routing, route_greedy, ROUNDS};
+use numberlink_solver::hint::{explain_with, hint_with, PartialSolution};
+use numberlink_solver::input::parse_paths;
+use numberlink_solver::{count_numberlink, parse_puzzle, sat_only, solve_numberlink, try_parse_puzzle, Encoding, Error, Progress, Puzzle, Session, Sol, SolverConfig, Topology};
+
+mod config;
+
+use config::{Config, KEYS};
+
+const USAGE: &str = "usage: numberlink_solver [OPTIONS] URL
+       numberlink_solver [OPTIONS] URL URL...
+       numberlink_solver [OPTIONS] -
+       numberlink_solver [OPTIONS] --source SCHEME:ARG
+       numberlink_solver experiment [--seeds N] [OPTIONS] URL...|-
+       numberlink_solver compare [OPTIONS] URL...|-
+       numberlink_solver crosscheck [--cmd CMD]... [--sat-cmd CMD]... [OPTIONS]
+                                    URL...|-
+       numberlink_solver predict URL...|-
+       numberlink_solver rate [OPTIONS] URL...|-
+       numberlink_solver profile [OPTIONS] URL...|-
+       numberlink_solver report [--out FORMAT] [OPTIONS] DIR|FILE|URL...|-
+       numberlink_solver minimize [OPTIONS] URL...|-
+       numberlink_solver repair [OPTIONS] URL...|-
+       numberlink_solver hint [--drawn PATHS] [OPTIONS] URL...|-
+       numberlink_solver explain [OPTIONS] URL...|-
+       numberlink_solver families [OPTIONS] URL...|-
+       numberlink_solver diff [--with BACKEND] [OPTIONS] URL...|-
+       numberlink_solver screen URL...|-
+       numberlink_solver count [OPTIONS] URL...|-
+       numberlink_solver dedup [OPTIONS] URL...|-
+       numberlink_solver transform STEP,... [OPTIONS] URL...|-
+       numberlink_solver tile [--columns N] [--shared] [OPTIONS] URL...|-
+       numberlink_solver pack [--solutions] [OPTIONS] URL...|-
+       numberlink_solver script FILE [OPTIONS] URL...|-
+       numberlink_solver slither|masyu|yajilin|hashi [OPTIONS] URL...|-
+       numberlink_solver gen [--size WxH] [--pairs N] [--difficulty D]
+                             [--symmetry S] [--min-fill F] [--min-length L]
+                             [--min-bends N] [--min-spread S]
+                             [--reject FILTER,...] [--seed N] [--count N]
+                             [--out FORMAT] [--report] [--daily [DATE]]
+       numberlink_solver gen --from SOLUTION|- [--out FORMAT]
+       numberlink_solver serve [--addr ADDR] [--workers N] [--queue N]
+                               [--cache N] [--keys FILE] [--rate N]
+                               [--grpc ADDR] [OPTIONS]
+       numberlink_solver serve --openapi
+       numberlink_solver daemon [--socket PATH] [OPTIONS]
+       numberlink_solver gui [OPTIONS] [URL]
+       numberlink_solver db add|solve [--db PATH] [OPTIONS] URL...|-
+       numberlink_solver db query QUERY [--db PATH] [--limit N]
+
+with several URLs, or with `-` to read URLs from stdin one per line, one
+result line is written per puzzle in input order (blank lines are skipped),
+and the exit status is the largest that any of the puzzles would give alone.
+--source SCHEME:ARG takes the puzzles from a source instead of URLs:
+file:PATH reads a file with one puzzle per line, cmd:COMMAND runs COMMAND
+and reads its output the same way (a scraper or a database query), and
+programs built on the library can add their own schemes with
+source::register_source. Puzzles a source cannot fetch are skipped with a
+warning.
+URLs of the form numlin/b/W/H/... carry walls between cells (puzz.link
+border data) before the numbers. The host may be left out, and so may
+everything but the board code after W/H/ when the size is given as `WxH
+CODE` (or `CODE WxH`); a code alone is read as a square board if the number
+of cells it covers is a square. A puzzle can also be given in the same
+forms as the output: as a text board with rows separated by `/` and cells
+by `,` (`.` or 0 for a blank, `#` for a hole that no path enters and that
+need not be filled, `+` for a bridge that one path crosses horizontally and
+another vertically, `_` for a cell outside a board that is not a
+rectangle, `*` for a dot, `@` for a waypoint, a blank cell that some path
+must pass through even with --coverage false), or as JSON with a `field`
+array of rows and optional `mask` (rows of 1 for cells of the board and 0
+for cells outside it), `holes`, `bridges` and `dots` ([row, col] pairs),
+`waypoints` ([row, col], or [[row, col], number] for a waypoint on the
+path of that number), `walls` (pairs of adjacent cells), `warps` (pairs of
+border cells, optionally with the direction in which a path leaves the
+first one, e.g. [[0,4],[0,0],\"right\"]), `one_way` (pairs of adjacent
+cells that a path may only pass from the first to the second; a path runs
+from the first cell of its number in row-major order, and boards of dots
+take no one-way passages), `limits` ([number, cells] pairs: the path of
+that number may pass through at most that many cells, its ends included),
+`costs` ([[row, col], cost] pairs; other cells cost 0) and `budget` (the
+most that the cells on paths may cost in total, which matters with
+--coverage false) and `wrap` (as --wrap, for this board only). JSON with
+`\"topology\":\"hex\"` is a hex board in axial coordinates: each cell
+[i, j] also touches [i+1, j-1] and [i-1, j+1], directions are right,
+down-right, down-left, left, up-left and up-right, and other board shapes
+are cut out with a mask. With
+`\"topology\":\"diagonal\"` (or --diagonal) a path may also move to the
+four diagonal neighbours (directions right, down-right, down, down-left,
+left, up-left, up and up-right); two diagonal moves across the same 2x2
+block cross each other, which --crossing forbids by default. A 3D board
+gives `layers`, a list of equally sized fields from the bottom layer up,
+instead of `field`, and `vias`, the cells whose path may continue into the
+same cell of the layer above, as [layer, row, col]; its other cells are
+written as [layer, row, col] too (and `mask` is one per layer), and the
+text output prints one layer after another. Linked boards, where a path
+leaves one board through a marked edge cell and goes on in another, give
+`boards`, a list of equally sized fields, instead of `field`, and `links`,
+written like `warps` with cells as [board, row, col]; they are read as
+layers without vias. A puzzle on any graph is JSON
+with `nodes` (the number of vertices), `edges` (pairs of vertices) and
+`pairs` (the vertices of each number, from 1); every vertex must be
+covered as on a grid, and the text output lists each path as
+`number: vertex ...`. A number may appear more than twice: then one path
+passes through all of its cells (--nets path) or a tree from its first cell
+reaches all the others (--nets tree). A board of dots has no numbers: the
+solver also chooses which two dots each path joins and prints them as
+numbered pairs. Only the SAT backends solve puzzles with bridges (square
+boards only), with such numbers, dots, waypoints, one-way passages, limits
+or a budget, or with any rule other than the default ones (--coverage,
+--loops, --no-2x2, --no-touch, --no-adjacent), and --regions supports
+neither bridges, warps nor boards other than the square grid.
+
+`experiment` solves every puzzle with each encoding, phase heuristic and N
+shuffling seeds (default 1) and prints a table of solved counts, time,
+conflicts and wins.
+
+`compare` solves every puzzle with each encoding at the same time, prints
+one line per puzzle with each configuration's time and answer, and exits
+with status 1 if any two configurations disagree on whether it is solvable.
+Puzzles of at most 36 cells are also solved by the backtracking search.
+
+`crosscheck` solves every puzzle at the same time with this solver and
+with external ones, prints one line per puzzle as `compare` does, and
+exits with status 1 if they disagree on whether it is solvable or if any
+of them gives a solution that breaks the rules (which the line names).
+--cmd runs CMD with the puzzle's input line on stdin; it must print a
+solution in the forms accepted by `gen --from` (the JSON output of this
+solver will do, with --loops false as closed loops are not written) or a
+line containing `no solution`. --sat-cmd solves
+the same encoding with CMD as for --solver-cmd. Both may be given several
+times, and --timeout also stops the external commands.
+
+`predict` prints a rough difficulty class for each puzzle without solving
+it, from the board size, the number of pairs and the clue density.
+
+`rate` solves each puzzle with varisat, checks that it has no other
+solution, and prints a difficulty tier (easy, medium, hard or expert) with
+the score it comes from, the conflicts spent, the number of cell edges
+that the deductions of a human solver leave open, the board's `predict`
+score, and its grade: the hardest deduction a human solver needs (forced
+moves, label conflicts, bridges or region parity, in increasing order), or
+`search` if these deductions alone do not solve it. Scores grow with
+hardness, so a corpus can be ordered by them.
+
+`profile` solves each puzzle and prints its structure: the board size,
+the cells that paths may use, the holes, walls and bridges, the number of
+pairs and the share of cells with numbers, the shortest, mean and longest
+distance (in steps, ignoring walls) between the two numbers of a pair, and
+for the solution found the share of cells on paths, the mean path length
+in cells, the number of bends, the mean distance from each number to the
+nearest other one, and the detour: the mean ratio of a path's length to
+the distance between its ends. Unsolvable puzzles print `no solution` in
+place of the solution's figures.
+
+`report` rates every puzzle like `rate` and writes one table of them all,
+easiest first (puzzles that could not be rated last), with a thumbnail of
+each board (a link to puzz.link when its URL carries the whole board), its
+size, number of pairs, tier, score and grade, the conflicts spent and the
+time taken: as Markdown (--out markdown, default) or as an HTML page
+(--out html). An input that is a directory stands for the files in it, in
+name order; a file holds one puzzle, or one URL or one-line JSON puzzle
+per line, and each puzzle is named after its file (and line).
+
+`minimize` trims each uniquely solvable puzzle while it stays uniquely
+solvable: it drops outer rows and columns without numbers, then removes
+whole pairs of numbers, until neither is possible, and prints the result
+with its numbers renumbered from 1 (as a puzz.link URL, as a text board if
+it has holes or bridges, or as JSON if it has features that text boards
+cannot carry), or `not unique` if the puzzle did not
+have exactly one solution to begin with.
+
+`repair` looks for small edits that make each unsolvable puzzle solvable:
+removing one wall, or moving one number to a blank cell at most two steps
+away. For a puzzle with several solutions, it compares two of them and
+looks only where they differ, for edits that leave exactly one solution:
+adding a wall on an edge that one of them uses, making the line of one of
+them pass a blank cell, or moving a number to such a cell. It prints up to
+five edits, the closest to the original first (walls, then passed cells,
+then moves by distance), separated by `;`, with cells given as (row,
+column) from 0; `unique` if the puzzle already has exactly one solution,
+or `no repair found`.
+
+`hint` prints the next step a human solver can take on each puzzle, with
+the reason: the first line or cross (an edge that no line uses) that the
+deductions of `rate` find, cheapest technique first, or else an edge that
+is the same in every solution, marked `search`. --drawn gives the lines
+drawn so far, in the forms accepted by `gen --from`. If every edge is
+decided or the rest differ between solutions, it prints `nothing more
+follows`.
+
+`explain` prints the steps that solve each puzzle from an empty board, one
+per line after a line with the number of steps: the deductions of `hint`
+in order, and wherever they get stuck one edge that is the same in every
+solution, marked `search`. For a puzzle with several solutions, an edge of
+one of them is picked where nothing else follows.
+
+`families` prints, for each puzzle, the clauses of its encoding grouped by
+the constraint family they come from (the equations (2) to (12) of the
+paper the encoding follows, and the extensions such as walls, nets or
+--constraints): the number of clauses, literals and cells of each. For an
+unsolvable puzzle it adds an UNSAT core: a set of families and cells whose
+clauses alone have no solution while any smaller part of it has one (small
+puzzles only).
+
+`diff` solves each puzzle twice and prints how the two solutions differ:
+a line with the number of differing cells (cells whose path or edges
+change) and edges, then the two solutions side by side with the differing
+cells marked `*`. The second solution is another solution of the puzzle
+(`unique` if there is none), or with --with the solution found by
+BACKEND. It prints `same` if both draw the same lines (as when only a
+closed loop of blank cells is reversed).
+
+`screen` flags the parts of each puzzle that make it likely to have more
+than one solution, without solving it: regions with no numbers that
+paths cannot reach (only closed loops fill them, in either direction, so
+such a puzzle never has a unique solution), and open areas of 2x2 blank
+cells away from every number, whose turns can often be swapped. It prints
+the flags separated by `;`, or `clear`.
+
+`count` prints the exact number of solutions of each puzzle, counted on a
+ZDD of all solutions (frontier method) instead of enumerating them; a
+closed loop of blank cells counts once per direction, as when solving.
+Puzzles or rules the ZDD does not support (bridges, --coverage false, ...)
+and --backend with a SAT solver are counted by enumerating the solutions.
+
+`dedup` prints the inputs that are not the same puzzle as an earlier one,
+one per line and in input order: two puzzles are the same if one of
+them turns into the other by rotating, mirroring and renumbering its
+numbers (boards with warps, layers, hexagonal cells or one-way edges are
+only renumbered).
+
+`transform` applies the comma-separated STEPs in order to each puzzle and
+prints the result (as for `minimize`). A step is `rotate90`,
+`rotate180` or `rotate270` (clockwise), `mirror` (left to right), `flip`
+(top to bottom), `transpose`, `antitranspose`, `identity`, or `renumber`
+to number the pairs 1, 2, ... in reading order. Boards with warps, layers,
+hexagonal cells or one-way edges can only be renumbered. `crop=WxH@ROW:COL`
+cuts out the W columns and H rows from ROW and COL (counted from 0) and
+keeps only the pairs with both numbers inside; the number left behind by a
+pair that leaves the region becomes a blank, or with `seal=WxH@ROW:COL`
+a hole that no path enters. Boards with warps, layers or hexagonal cells
+cannot be cropped.
+
+`tile` lays all the puzzles out on one board, --columns of them per row
+(default: as many as make the board about square), each row and column as
+wide as its largest puzzle and the rest filled with holes, and prints it
+(as for `minimize`).
+The numbers of each puzzle go on from those of the one before it. Walls
+between neighbouring puzzles keep every path inside its own puzzle, unless
+--shared lets paths cross the borders. Boards with warps, layers,
+hexagonal cells or a budget cannot be tiled.
+
+`pack` writes the puzzles as a Flow Free level pack, one level per line:
+`SIZE,0,LEVEL,FLOWS;flow;flow;...`, where SIZE is the width of a square
+board or `W:H`, levels are numbered from 1 and each flow lists the cells
+(numbered row by row from 0) of one pair, in the order of the numbers.
+A flow holds only the two cells of its pair, or with --solutions every
+cell of the solved path from one end to the other. Only square grids whose
+numbers each appear exactly twice fit in a pack (no walls, holes, bridges,
+warps, dots, waypoints or one-way edges); other puzzles, and with
+--solutions unsolvable ones, are reported on stderr and left out.
+
+`script` (in builds with --features script) solves each puzzle with the
+extra rules of the Rhai script FILE, which defines `fn constrain(puzzle,
+b)`: `puzzle` has `width`, `height` and `cells` (rows of numbers, 0 for a
+blank), and `b` gives literals, numbered from 1 and negated with `-` as in
+DIMACS: `b.arc(r1, c1, r2, c2)` for a path going from cell (r1, c1) to the
+neighbouring cell (r2, c2), `b.used(r, c)` for a path passing through (r,
+c), `b.number(r, c, n)` for the number of that path being n (not with
+--lengths) and `b.fresh()` for a new variable. `b.clause([...])` requires
+one of the literals to be true, and `b.minimize([...])` asks for a solution
+with as few of them true as possible (several calls add up); the number
+left is written to stderr, with a warning if time ran out first. Rows and
+columns count from 0; `print` writes to stderr.
+
+`slither` solves Slitherlink puzzles instead: puzz.link URLs of the form
+slither/W/H/... or text boards with rows separated by `/` and cells by `,`
+(`.` for a cell without a number). It draws one loop along the cell edges
+so that each number counts the edges of its cell on the loop, and prints it
+as a board of `+` corners with `-` and `|` for the loop (--format text), as
+JSON with the corners of the loop in order, or as that list (debug); it
+takes --backend (SAT solvers only) and --timeout.
+
+`masyu` likewise solves Masyu puzzles (masyu/W/H/... URLs, or text boards
+with `o` for a white pearl and `x` for a black one): one loop through the
+centres of cells passes every pearl, straight through a white pearl and
+turning in a cell next to it, and turning at a black pearl and going
+straight through both cells next to it. The text output marks the other
+cells on the loop with `+`.
+
+`yajilin` solves Yajilin puzzles (yajilin/W/H/... URLs, or text boards
+where a clue is its number, or `?`, followed by `>`, `v`, `<` or `^`): every
+cell that is not a clue is either shaded or on one loop through the
+centres of cells, no two shaded cells are next to each other, and each
+clue counts the shaded cells in the direction of its arrow. The text output
+marks shaded cells with `#`, and JSON also lists them as `shaded`.
+
+`hashi` solves Hashiwokakero puzzles (hashikake/W/H/... URLs, the
+[problem] section of a janko.at file, or text boards with `.` or `-` for
+water and cells separated by `,` or spaces): bridges run straight between
+islands, at most two between the same two islands and none crossing
+another, each island has as many bridges as its number, and all islands
+are connected. The text output draws bridges with `-` and `=`, or `|` and
+`\"`; JSON lists them as [[row, col], [row, col], count].
+
+`gen` creates a Numberlink puzzle with a unique solution instead of
+solving one: a board of --size (default 10x10, at most 1600 cells) with
+--pairs pairs of numbers (default 8), written as a puzz.link URL (--out
+url, default), as a text board (text) or as an SVG image (svg). With
+--difficulty (trivial, easy, medium, hard, or a score as printed by
+`predict`, matched within 10%) it picks the number of pairs closest to
+--pairs for which `predict` gives that difficulty. --symmetry places the
+numbers (and the holes left by cells no path needs) symmetrically:
+rotational (half turn), quarter (quarter turn, square boards only),
+horizontal (mirrored left to right), vertical (mirrored top to bottom) or
+none (default); the numbers themselves need not match. --min-fill (the
+share of cells on paths rather than holes, 0 to 1), --min-length (the
+average number of cells per path), --min-bends (the number of turns of all
+paths together) and --min-spread (the average distance from a number to
+the nearest other number) keep only puzzles whose intended solution
+reaches these values, and --report writes them for each puzzle to stderr.
+--reject discards puzzles that are trivial in any of the listed ways:
+straight (a path of the intended solution runs in a straight line),
+adjacent (the two ends of a path are next to each other) or deduced (the
+deductions of `rate` solve it without search). --count N writes N puzzles,
+from seeds --seed (default 0) onwards, wrapping around to 0 after the
+largest seed 18446744073709551615; the same seed always gives the same
+puzzle. --daily DATE (YYYY-MM-DD, default today in UTC) takes the seed
+from the date instead, so that with the same other options everyone gets
+the same puzzle of the day. Exits with status 2 if no puzzle was found.
+
+`gen --from` instead makes the puzzle of a solution drawn by hand, with the
+numbers at the ends of its paths and holes in the cells no path passes
+through. The solution (or `-` to read it from stdin) is written like the
+text or JSON output of a solve: one name per cell, the cells of a path
+sharing a name and `.` or `#` for cells off all paths, or JSON `paths`,
+each a list of cells in order (needed when a path runs next to itself).
+A puzzle with holes is written as a text board even with --out url. It
+writes `unique` to stderr if this is the only solution, or exits with
+status 1 after `not unique`.
+
+`serve` (in builds with --features server) answers HTTP requests on ADDR
+(default 127.0.0.1:8080) until it is killed, solving with the other
+options. Each takes a JSON body and returns JSON: POST /solve takes a
+puzzle (a URL, a text or JSON board, or {\"puzzle\": ...}) and returns the
+line of --format json; POST /check takes {\"puzzle\": ..., \"solution\":
+...} with a solution written as for `gen --from` and returns whether it is
+valid, or without a solution whether the puzzle is solvable and unique;
+POST /generate takes {\"width\": W, \"height\": H, \"pairs\": N, \"seed\":
+S} and returns the puzzle as a URL and as JSON. Unreadable requests get
+status 400, unsolvable puzzles 422 and timeouts 503. A WebSocket on
+/progress takes the body of /solve as its first message (with
+\"objective\": \"turns\" or \"cost\" it also optimizes), sends
+{\"event\": \"progress\", ...} with the conflicts, the best bound so far and
+the elapsed time every 0.5s, then {\"event\": \"result\", \"solution\": ...}
+or {\"event\": \"error\", ...}; closing it stops the solve. POST /jobs
+queues the same body as a job and returns {\"id\": ...} at once (status
+503 when --queue jobs, default 100, are already waiting); --workers
+threads (default one per CPU) solve the queued jobs in order. GET
+/jobs/ID returns the job's status (queued, running, done, failed or
+cancelled), GET /jobs/ID/result its result once it has finished (status
+202 until then), and DELETE /jobs/ID cancels it. Solutions (and the lack
+of one) of the last --cache puzzles (default 1000, 0 = none) are kept and
+reused for the same puzzle rotated, reflected or with its numbers
+relabelled, by /solve and by jobs and /progress without an objective.
+With --keys, every request must carry one of the API keys listed in FILE
+(one per line, optionally followed by its own limit) as `Authorization:
+Bearer KEY`, `X-API-Key: KEY` or `?key=KEY`, or gets status 401; each key
+may make --rate requests a minute (default 60, 0 = no limit), after which
+requests get status 429 with Retry-After. With --grpc (in builds with
+--features grpc), the same service is also offered over gRPC on ADDR, as
+described in proto/numberlink.proto, with keys sent as `authorization` or
+`x-api-key` metadata. GET /metrics returns
+request and solve counts and durations (sat, unsat, timeouts, ...) in the
+Prometheus text format. GET /openapi.json
+describes all of these as an OpenAPI 3.0 document, which `serve --openapi`
+prints without starting the server (e.g. to generate a client).
+
+`daemon` (on Unix) answers solve requests on the Unix domain socket PATH
+(default $XDG_RUNTIME_DIR/numberlink.sock, or in the temporary directory)
+until it is killed, solving with the other options, so that editors and
+bots need not start a process for every puzzle. A request is the length of
+its body in bytes as 4 bytes, most significant first, followed by the body:
+a puzzle in any of the forms above. The answer comes back the same way and
+is the line of --format json. A connection may send any number of requests.
+
+`gui` (in builds with --features gui) opens a window with the puzzle URL
+(or an empty 5x5 board): set the size, click cells to place the chosen
+number, a hole or (near a border) a wall, or erase them, and Solve draws
+the solution with the other options; Stop gives up. Import reads the URL
+or board typed above the board, and Export writes the board there as a
+puzz.link URL (JSON with holes) and copies it to the clipboard.
+
+`db` (in builds with --features db) keeps puzzles and their solve history
+in the SQLite database PATH (default numberlink.db, created if missing),
+one row per puzzle up to rotation, reflection and renumbering. `db add`
+stores the puzzles with their size, number of pairs and predicted
+difficulty; `db solve` also solves each with the other options, prints its
+line as in batch mode and records the time, the outcome and the solution.
+`db query` prints the stored puzzles that match QUERY, one per line, as
+the input they were added with, size, pairs, predicted difficulty, number
+of solves and fastest solve, separated by tabs (at most N lines with
+--limit): all, solved, unsolved, hardest-unsolved (unsolved, by predicted
+difficulty), slowest (solved, by fastest solve) or WxH (every puzzle of
+that size).
+
+Without a subcommand, a puzz.link URL names its puzzle (slither/...,
+masyu/..., yajilin/..., hashikake/... and their other puzz.link names), and
+it is solved as that puzzle; URLs without one of these names and text or
+JSON boards are Numberlink. With several URLs each is solved by its own
+name, one line per puzzle.
+
+options:
+    --all-optimal N     with --objective, print every solution that reaches
+                        the optimum, up to N of them, and their number to
+                        stderr; exits with status 0 however many there are,
+                        or 3 if time ran out before the optimum was proven
+                        or all of them were found (single puzzle only)
+    --backend NAME      auto (default) picks per puzzle and command: zdd
+                        for count, backtrack to solve small boards, cadical
+                        (when built in) for puzzles predicted medium or
+                        harder and varisat otherwise; or name the
+                        SAT solver: varisat, cadical or minisat
+                        (the last two need a build with --features NAME), or
+                        backtrack for a plain search without SAT (small
+                        puzzles only), zdd to build a ZDD of all solutions
+                        (narrow boards) or ilp for integer programming
+                        (--features ilp for small boards, or cbc to use
+                        CBC); none of these
+                        supports --regions, and only ilp supports
+                        --objective
+    --solver-lib PATH   use the IPASIR solver library at PATH instead (e.g.
+                        libkissat.so), loaded at run time
+    --solver-cmd CMD    or run CMD (e.g. `kissat -q`) on a DIMACS file for
+                        every query and read its `s` and `v` lines
+    --checkpoint PATH   with --objective turns, save the best solution so far
+                        to PATH after each improvement and resume from it
+                        when PATH already exists (single puzzle only)
+    --config PATH       read settings from PATH instead of numberlink.toml
+    --constraints SPEC  extra rules, separated by `;` or newlines (`#` starts
+                        a comment): `forbid cell (R,C)` or `require cell
+                        (R,C)` keeps paths out of or through a cell,
+                        `forbid edge (R,C)-(R,C)` or `require edge
+                        (R,C)-(R,C)` out of or across the border between
+                        two neighbouring cells, and `maxlen N = L` lets
+                        the path of number N pass at most L cells; rows
+                        and columns count from 0 (SAT backends only)
+    --coverage BOOL     every blank cell must be on a path (default true);
+                        false leaves unused cells empty, and detours and
+                        loops of blank cells are removed from the answer
+    --crossing CROSSING on diagonal boards, forbid (default) two paths or one
+                        path from using both diagonals of a 2x2 block, or
+                        allow them to cross
+    --deterministic BOOL
+                        with -j and a single URL, always print the answer of
+                        the first configuration (in race order) that
+                        finishes, so runs are reproducible (default false)
+    --diagonal BOOL     let paths on square boards also move diagonally
+                        (SAT backends only, default false)
+    --encoding ENC      number encoding: log (default) or direct
+    --fallback BOOL     when a single puzzle times out, print what a greedy
+                        router (shortest paths, ripping up and rerouting the
+                        ones in the way) could connect instead, and list the
+                        connected and unconnected pairs on stderr (default
+                        false)
+    --force             with --result-cache, solve every puzzle again and
+                        replace its stored answer
+    --format FMT        output format: debug (default), text, json, svg or a
+                        format registered with render::register_renderer
+    -j, --jobs N        solve up to N puzzles in parallel (default 1); with a
+                        single URL, race N solver configurations instead
+                        (without -j, auto races one per core when the puzzle
+                        is predicted medium or harder)
+    --lengths BOOL      read each number as the length of its path in cells
+                        instead of a label; two cells with the same number
+                        may be joined if the path between them has that
+                        many cells (SAT backends only, default false)
+    --live BOOL         redraw the board on stderr while solving a single
+                        puzzle: path cells the solver has fixed so far,
+                        blanks it has ruled out and the number of conflicts
+                        (varisat backend only, default false)
+    --loops BOOL        allow closed loops of blank cells that join no
+                        numbers (default true; never with --no-2x2,
+                        --no-touch or --no-adjacent)
+    --max-memory SIZE   give up when the clause database is estimated to
+                        exceed SIZE (e.g. 512M, 2G; 0 = no limit)
+    --nets NETS         how to connect a number that appears more than twice:
+                        path (default), one path through all of its cells,
+                        or tree, a tree that may branch
+    --no-2x2 BOOL       no 2x2 block of cells may belong to a single path
+                        (square and layered boards, default false)
+    --no-adjacent BOOL  like --no-touch, but also for cells on either side
+                        of a wall (or of a closed via), which therefore never
+                        belong to the same path (default false)
+    --no-touch BOOL     two neighbouring cells of the same path must be
+                        joined by it, so no path runs alongside itself
+                        (default false)
+    --objective OBJ     none (default), turns: minimize the number of bends,
+                        or cost: minimize the total cost of the cells that
+                        paths pass through (SAT backends only); on timeout
+                        the best solution so far is printed
+    --phase PHASE       initial phases: default, or corridors to first try
+                        the shortest, straightest path between each pair
+    --presolve BOOL     first decide what human techniques (forced moves,
+                        label conflicts, bridges, region parity) can, and
+                        add it to the SAT encoding (default false)
+    --regions COLS      solve the board in vertical strips of COLS columns,
+                        left to right, backtracking over the strip borders
+                        (0 = whole board at once, the default)
+    --result-cache DIR  with several puzzles, keep each answer (or that there
+                        is none) in DIR and answer a puzzle that is a
+                        rotation, reflection or renumbering of one solved
+                        before under the same rules without solving it (not
+                        with --objective or --constraints)
+    --status-interval SECS
+                        print elapsed time, conflicts and the best bound so
+                        far to stderr every SECS seconds (0 = never, the
+                        default); SIGUSR1 prints the same line at any time
+    --timeout SECS      give up after SECS seconds (0 = no limit)
+    --theme THEME       text output theme: plain (default) or color
+    --wrap WRAP         join the ends of every row (rows), column (columns)
+                        or both (a torus) with warps, as far as the board
+                        has no warp there already (default none); the text
+                        output marks paths that wrap with `~` at the border
+    -h, --help          print this message
+
+varisat tuning (defaults in brackets; auto then always picks varisat):
+    --vsids-decay X                  VSIDS activity decay [0.95], 0.5..1.0
+    --clause-activity-decay X        learnt clause activity decay [0.999]
+    --reduce-locals-interval N       conflicts between local clause
+                                     reductions [15000]
+    --reduce-mids-interval N         conflicts between mid clause
+                                     reductions [10000]
+    --luby-restart-interval-scale N  conflicts per Luby restart unit [128]
+
+settings can also be given in numberlink.toml (current directory or
+~/.config/numberlink/) and as environment variables named after the option
+(NUMBERLINK_ENCODING, NUMBERLINK_MAX_MEMORY, ...); flags take precedence
+over the environment, which takes precedence over the file.";
+
+const EXIT_ERROR: i32 = 1;
+const EXIT_NO_SOLUTION: i32 = 2;
+const EXIT_TIMEOUT: i32 = 3;
+const EXIT_INTERRUPTED: i32 = 130;
+
+// 1 問だけ解いて e で終わったときの終了コード
+fn exit_code(e: &Error) -> i32 {
+    match e {
+        Error::NoSolution => EXIT_NO_SOLUTION,
+        Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_) => EXIT_TIMEOUT,
+        _ => EXIT_ERROR,
+    }
+}
+
+// repair が示す手直しの数
+const REPAIR_LIMIT: usize = 5;
+
+const STATUS_POLL: Duration = Duration::from_millis(100);
 
-type Field = Vec<Vec<usize>>;
-type P = (usize, usize);
-type Arc = (P, P);
-type Sol = Vec<Arc>;
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let command = match args.first().map(String::as_str) {
+        Some("gen") => {
+            run_gen(&args[1..]);
+            return;
+        },
+        Some("experiment") | Some("compare") | Some("crosscheck") | Some("predict") | Some("rate") | Some("profile") | Some("report") | Some("minimize") | Some("repair") | Some("hint") | Some("explain") | Some("families") | Some("diff") | Some("screen") | Some("count") | Some("dedup") | Some("transform") | Some("tile") | Some("pack") | Some("script") | Some("slither") | Some("masyu") | Some("yajilin") | Some("hashi") | Some("serve") | Some("daemon") | Some("gui") | Some("db") => Some(args.remove(0)),
+        _ => None,
+    };
+    let experiment = command.as_deref() == Some("experiment");
+
+    // transform の最初の引数は変換の並び
+    let steps = match command.as_deref() {
+        Some("transform") if !args.is_empty() => match args.remove(0).split(',').map(str::parse).collect::<Result<Vec<Step>, String>>() {
+            Ok(steps) => steps,
+            Err(msg) => fail(&format!("{}\n\n{}", msg, USAGE)),
+        },
+        Some("transform") => fail(&format!("transform needs a list of steps\n\n{}", USAGE)),
+        _ => vec![],
+    };
+
+    // script の最初の引数はスクリプトのファイル
+    let script = match command.as_deref() {
+        Some("script") if !args.is_empty() => Some(args.remove(0)),
+        Some("script") => fail(&format!("script needs a script file\n\n{}", USAGE)),
+        _ => None,
+    };
+
+    let (columns, shared) = match command.as_deref() {
+        Some("tile") => match take_tile(&mut args) {
+            Ok(tile) => tile,
+            Err(msg) => fail(&format!("{}\n\n{}", msg, USAGE)),
+        },
+        _ => (None, false),
+    };
+
+    // pack --solutions は解いた経路を書く
+    let solutions = match command.as_deref() {
+        Some("pack") => match args.iter().position(|arg| arg == "--solutions") {
+            Some(pos) => {
+                args.remove(pos);
+                true
+            },
+            None => false,
+        },
+        _ => false,
+    };
+
+    // crosscheck の --cmd と --sat-cmd (何度でも)
+    let checkers = match command.as_deref() {
+        Some("crosscheck") => match take_checkers(&mut args) {
+            Ok(checkers) if !checkers.is_empty() => checkers,
+            Ok(_) => fail(&format!("crosscheck needs --cmd or --sat-cmd\n\n{}", USAGE)),
+            Err(msg) => fail(&format!("{}\n\n{}", msg, USAGE)),
+        },
+        _ => vec![],
+    };
 
-pub fn solve(url: String) -> Option<(Field, Sol)> {
-    let field = parse_url(url);
+    let report_format = match command.as_deref() {
+        Some("report") => match take_value(&mut args, "--out").and_then(|out| out.map_or(Ok(ReportFormat::Markdown), |out| out.parse().map_err(|msg| format!("--out: {}", msg)))) {
+            Ok(format) => format,
+            Err(msg) => fail(&format!("{}\n\n{}", msg, USAGE)),
+        },
+        _ => ReportFormat::Markdown,
+    };
+
+    let partial = match command.as_deref() {
+        Some("hint") => match take_value(&mut args, "--drawn") {
+            Ok(None) => PartialSolution::default(),
+            Ok(Some(drawn)) => match parse_paths(&drawn) {
+                Some((_, _, paths)) => PartialSolution::from_paths(&paths),
+                None => fail("--drawn: invalid paths (expected each path as a chain of cells with the same name, or JSON paths)"),
+            },
+            Err(msg) => fail(&format!("{}\n\n{}", msg, USAGE)),
+        },
+        _ => PartialSolution::default(),
+    };
 
-    if let None = field {
-        return None;
+    let against = match command.as_deref() {
+        Some("diff") => match take_value(&mut args, "--with").and_then(|with| with.map(|with| with.parse::<Backend>().map_err(|msg| format!("--with: {}", msg))).transpose()) {
+            Ok(against) => against,
+            Err(msg) => fail(&format!("{}\n\n{}", msg, USAGE)),
+        },
+        _ => None,
+    };
+
+    // serve --openapi はサーバの OpenAPI の文書を書いて終わる
+    if command.as_deref() == Some("serve") {
+        if let Some(pos) = args.iter().position(|arg| arg == "--openapi") {
+            args.remove(pos);
+            run_openapi();
+            return;
+        }
     }
 
-    let field = field.unwrap();
+    // serve の --addr, --workers, --queue, --cache, --keys, --rate と --grpc (サーバのない build では run_serve が断る)
+    let server = match command.as_deref() {
+        Some("serve") => match ["--addr", "--workers", "--queue", "--cache", "--keys", "--rate", "--grpc"].iter().map(|name| take_value(&mut args, name)).collect::<Result<Vec<_>, String>>() {
+            Ok(values) => values,
+            Err(msg) => fail(&format!("{}\n\n{}", msg, USAGE)),
+        },
+        _ => vec![],
+    };
+
+    // daemon の --socket
+    let socket = match command.as_deref() {
+        Some("daemon") => match take_value(&mut args, "--socket") {
+            Ok(socket) => socket,
+            Err(msg) => fail(&format!("{}\n\n{}", msg, USAGE)),
+        },
+        _ => None,
+    };
+
+    // db の最初の引数は add, solve か query (query は続けて問い合わせ). --db と --limit
+    let (action, query) = match command.as_deref() {
+        Some("db") if args.first().is_some_and(|action| action == "add" || action == "solve") => (Some(args.remove(0)), None),
+        Some("db") if args.first().is_some_and(|action| action == "query") && args.get(1).is_some_and(|query| !query.starts_with("--")) => (Some(args.remove(0)), Some(args.remove(0))),
+        Some("db") => fail(&format!("db needs add, solve or query QUERY\n\n{}", USAGE)),
+        _ => (None, None),
+    };
+    let (db, limit) = match command.as_deref() {
+        Some("db") => match (take_value(&mut args, "--db"), take_value(&mut args, "--limit")) {
+            (Ok(db), Ok(None)) => (db, usize::MAX),
+            (Ok(db), Ok(Some(limit))) => (db, limit.parse().unwrap_or_else(|_| fail(&format!("--limit: invalid number `{}`", limit)))),
+            (Err(msg), _) | (_, Err(msg)) => fail(&format!("{}\n\n{}", msg, USAGE)),
+        },
+        _ => (None, usize::MAX),
+    };
+
+    // --force は --result-cache にある答えを使わずに解き直す
+    let force = match args.iter().position(|arg| arg == "--force") {
+        Some(pos) if command.is_none() => {
+            args.remove(pos);
+            true
+        },
+        _ => false,
+    };
+
+    // --source SCHEME:ARG は URL の代わりに盤面を 1 問ずつ取ってくる
+    let source = match command.as_deref() {
+        Some("serve") | Some("daemon") | Some("gui") => None,
+        Some("db") if query.is_some() => None,
+        _ => match take_value(&mut args, "--source") {
+            Ok(source) => source,
+            Err(msg) => fail(&format!("{}\n\n{}", msg, USAGE)),
+        },
+    };
+
+    let seeds = match take_seeds(&mut args) {
+        Ok(seeds) if experiment || seeds.is_none() => seeds.unwrap_or(1),
+        Ok(_) => fail(&format!("--seeds is only for `experiment`\n\n{}", USAGE)),
+        Err(msg) => fail(&format!("{}\n\n{}", msg, USAGE)),
+    };
+
+    let (config_path, settings, urls) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(msg) => fail(&format!("{}\n\n{}", msg, USAGE)),
+    };
+
+    // serve は URL を取らず, gui は開く盤面を 1 つまで取る
+    match command.as_deref() {
+        Some("serve") if !urls.is_empty() => fail(&format!("serve takes no puzzle URL\n\n{}", USAGE)),
+        Some("daemon") if !urls.is_empty() => fail(&format!("daemon takes no puzzle URL\n\n{}", USAGE)),
+        Some("gui") if urls.len() > 1 => fail(&format!("gui takes at most one puzzle URL\n\n{}", USAGE)),
+        Some("db") if query.is_some() && !urls.is_empty() => fail(&format!("db query takes no puzzle URL\n\n{}", USAGE)),
+        Some("serve") | Some("daemon") | Some("gui") => {},
+        Some("db") if query.is_some() => {},
+        _ if source.is_some() && !urls.is_empty() => fail(&format!("--source cannot be combined with URLs\n\n{}", USAGE)),
+        _ if urls.is_empty() && source.is_none() => fail(&format!("missing puzzle URL\n\n{}", USAGE)),
+        _ => {},
+    }
 
-    let sol = solve_numberlink(&field);
+    let mut config = match Config::load(config_path) {
+        Ok(config) => config,
+        Err(msg) => fail(&msg),
+    };
 
-    if let None = sol {
-        return None;
+    for (key, value) in settings {
+        if let Err(msg) = config.set(&key, &value) {
+            fail(&format!("--{}: {}", key, msg));
+        }
     }
 
-    
-    Some((field.clone(), sol.unwrap()))
-}
+    // サーバは Ctrl-C でそのまま終わる (割り込みの印を立てると以後の要求がすべて interrupted になる)
+    if command.as_deref() == Some("serve") {
+        run_serve(&server, &config);
+        return;
+    }
 
-fn solve_numberlink(field: &Field) -> Option<Sol> {
-    if field.len() == 0 || field[0].len() == 0 {
-        return None;
+    if command.as_deref() == Some("daemon") {
+        run_daemon(socket.as_deref(), &config);
+        return;
     }
 
-    let width = field[0].len();
-    let height = field.len();
+    if let Some(query) = &query {
+        run_db_query(db.as_deref(), query, limit);
+        return;
+    }
 
-    let (s, t, b) = parse_field(&field).unwrap_or((vec![], vec![], vec![]));
+    // 窓の Stop で止めるので, Ctrl-C の印は立てない
+    if command.as_deref() == Some("gui") {
+        run_gui(urls.first(), &config);
+        return;
+    }
+
+    config.solver.interrupt = Some(install_interrupt_handler());
+
+    let progress = Arc::new(Progress::default());
+
+    config.solver.progress = Some(progress.clone());
+    spawn_status_reporter(progress, config.status_interval);
+
+    // 取れなかった盤面は警告して飛ばす
+    let inputs: Box<dyn Iterator<Item = String>> = if let Some(spec) = &source {
+        let source = open_source(spec).unwrap_or_else(|msg| fail(&format!("--source: {}", msg)));
+
+        Box::new(blocking(source).filter_map(|res| res.map_err(|msg| eprintln!("warning: --source: {}", msg)).ok()))
+    } else if urls == ["-"] {
+        Box::new(io::stdin().lock().lines().map(|line| match line {
+            Ok(line) => line,
+            Err(e) => fail(&format!("stdin: {}", e)),
+        }))
+    } else {
+        Box::new(urls.clone().into_iter())
+    };
 
-    if s.len() == 0 || s.len() != t.len() || s.len()+t.len()+b.len() != width*height {
-        return None;
+    if experiment {
+        run_experiment(inputs, &config, seeds);
+        return;
     }
 
-    let mut vs: Vec<P> = vec![];
+    if let Some(action) = &action {
+        run_db(inputs, db.as_deref(), action == "solve", &config);
+        return;
+    }
 
-    for i in 0..height {
-        for j in 0..width {
-            vs.push((i, j));
-        }
+    if command.as_deref() == Some("predict") {
+        run_predict(inputs);
+        return;
     }
 
-    let arcs: Vec<Arc> = gen_arcs(width, height);
+    if command.as_deref() == Some("rate") {
+        run_rate(inputs, &config);
+        return;
+    }
 
-    let mut formula = CnfFormula::new();
+    if command.as_deref() == Some("profile") {
+        run_profile(inputs, &config);
+        return;
+    }
 
-    let mut mp: HashMap<Arc, usize> = HashMap::new();
+    if command.as_deref() == Some("report") {
+        run_report(inputs, &config, report_format);
+        return;
+    }
 
-    /*for (i, arc) in arcs.clone().into_iter().enumerate() {
-        println!("arc {}: {:?}", i, arc);
-    }*/
+    if command.as_deref() == Some("minimize") {
+        run_minimize(inputs, &config);
+        return;
+    }
 
-    /* "Solving Nubmerlink by a SAT-based Constraint Solver" (https://ipsj.ixsq.nii.ac.jp/ej/index.php?action=pages_view_main&active_action=repository_action_common_download&item_id=102780&item_no=1&attribute_id=1&file_no=1&page_id=13&block_id=8) */
-    for (i, (u, v)) in arcs.clone().into_iter().enumerate() {
-        mp.insert((u, v), i);
+    if command.as_deref() == Some("screen") {
+        run_screen(inputs, &config);
+        return;
     }
 
-    let length = arcs.len();
-    let mut m = s.len();
-    let mut mb = 0;
+    if command.as_deref() == Some("diff") {
+        run_diff(inputs, &config, against.as_ref());
+        return;
+    }
 
-    while m > 0 {
-        m >>= 1;
-        mb += 1;
+    if command.as_deref() == Some("explain") {
+        run_explain(inputs, &config);
+        return;
     }
 
-    let mut bmp: HashMap<P, Vec<usize>> = HashMap::new();
+    if command.as_deref() == Some("families") {
+        run_families(inputs, &config);
+        return;
+    }
 
-    // vs*log(n)
-    // 自然数変数を bit ごとに分解
-    for (index, (i,j)) in vs.clone().into_iter().enumerate() {
-        for b in 0..mb {
-            let x = Var::from_index(length+index*mb+b+1);
+    if command.as_deref() == Some("hint") {
+        run_hint(inputs, &config, &partial);
+        return;
+    }
 
-            if !bmp.contains_key(&(i,j)) {
-                bmp.insert((i,j), vec![length+index*mb+b+1]);
-            } else {
-                bmp.get_mut(&(i,j)).unwrap().push(length+index*mb+b+1);
-            }
+    if command.as_deref() == Some("repair") {
+        run_repair(inputs, &config);
+        return;
+    }
 
-            let mut num: usize = field[i][j];
+    if command.as_deref() == Some("dedup") {
+        run_dedup(inputs, &config);
+        return;
+    }
 
-            if num == 0 {
-                continue;
-            }
+    if command.as_deref() == Some("transform") {
+        run_transform(inputs, &config, &steps);
+        return;
+    }
+
+    if command.as_deref() == Some("tile") {
+        run_tile(inputs, &config, columns, shared);
+        return;
+    }
 
-            num -= 1;
+    if command.as_deref() == Some("pack") {
+        run_pack(inputs, &config, solutions);
+        return;
+    }
 
-            // (11)
-            formula.add_clause(&[Lit::from_var(x, (num>>b&1) != 0)]);
+    if let Some(path) = &script {
+        run_script(inputs, &config, path);
+        return;
+    }
+
+    if command.as_deref() == Some("compare") {
+        run_compare(inputs, &config);
+        return;
+    }
+
+    if command.as_deref() == Some("crosscheck") {
+        run_crosscheck(inputs, &config, &checkers);
+        return;
+    }
+
+    if command.as_deref() == Some("count") {
+        run_count(inputs, &config);
+        return;
+    }
+
+    if let Some(genre) = command.as_deref().and_then(|command| command.parse::<Genre>().ok()) {
+        run_genre(inputs, |url| solve_genre(genre, url, &config.solver, config.format));
+        return;
+    }
+
+    if urls == ["-"] || urls.len() > 1 || source.is_some() {
+        if config.checkpoint.is_some() {
+            fail("--checkpoint can only be used with a single puzzle");
+        }
+
+        if config.all_optimal > 0 {
+            fail("--all-optimal can only be used with a single puzzle");
         }
+
+        batch(inputs, &config, force);
+        return;
+    }
+
+    // puzz.link の URL の名前が Numberlink 以外ならそのパズルとして解く
+    if let Some(genre) = detect(&urls[0]).filter(|&genre| genre != Genre::Numberlink) {
+        run_genre(inputs, |url| solve_genre(genre, url, &config.solver, config.format));
+        return;
+    }
+
+    if config.all_optimal > 0 {
+        run_all_optimal(&urls[0], &config);
+        return;
+    }
+
+    // 読めない盤面は solve_single が同じ誤りを出す
+    let live = match read_puzzle(&urls[0], &config) {
+        Ok(puzzle) if config.live => Some(spawn_live(&puzzle, &mut config.solver)),
+        _ => None,
+    };
+    let res = solve_single(urls[0].clone(), &config);
+
+    if let Some((stop, drawer)) = live {
+        stop.store(true, Ordering::SeqCst);
+        let _ = drawer.join();
+    }
+
+    match res {
+        Ok((puzzle, sol)) => print!("{}", render(&puzzle, &sol, config.format, config.theme)),
+        Err(Error::Timeout(stats)) if config.fallback => {
+            let puzzle = read_puzzle(&urls[0], &config).unwrap_or_else(|e| fail(&format!("{}: {}", urls[0], e)));
+            let routing = route_greedy(&puzzle, 0, ROUNDS);
+
+            print!("{}", render(&puzzle, &routing.sol, config.format, config.theme));
+            eprintln!("warning: timed out ({}); greedy routing {}", stats, render_routing(&routing));
+
+            process::exit(EXIT_TIMEOUT);
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+
+            process::exit(exit_code(&e));
+        }
+    }
+}
+
+// 最良の値の解をすべて出し, 数を stderr に書く. 最良の解がいくつでも (N 個で打ち切っても) 終了コードは 0.
+// 最良と示せないうちや, 最良の解を数え切らないうちに時間切れになったら EXIT_TIMEOUT
+fn run_all_optimal(url: &str, config: &Config) {
+    if config.objective == Objective::None {
+        fail("--all-optimal needs --objective turns or cost");
     }
 
-    for (u, v) in arcs.clone().into_iter() {
-        let x = Var::from_index(mp[&(u, v)]+1);
+    let puzzle = read_puzzle(url, config).unwrap_or_else(|e| fail(&format!("{}: {}", url, e)));
+    let res = match all_optimal(&puzzle, &config.solver, config.objective, config.all_optimal) {
+        Ok(res) => res,
+        Err(e) => {
+            eprintln!("{}", e);
+
+            process::exit(exit_code(&e));
+        },
+    };
 
-        // (12)
-        // !(x and num_u != num_v)
-        // !x or f_u == f_v
-        for lits in mk_clause_impl(&x, &bmp[&u], &bmp[&v]) {
-            formula.add_clause(lits.as_slice());
+    // 盤面を描く形式では解の間を 1 行空ける
+    for (k, sol) in res.sols.iter().enumerate() {
+        if k > 0 && config.format == Format::Text {
+            println!();
         }
 
-        let y = Var::from_index(mp[&(v, u)]+1);
+        print!("{}", render(&puzzle, sol, config.format, config.theme));
+    }
+
+    let value = match config.objective {
+        Objective::Cost => format!("cost {}", res.best.cost),
+        _ => format!("{} turns", res.best.turns),
+    };
+
+    match (res.best.optimal, res.complete) {
+        (false, _) => eprintln!("warning: time is up before the optimum was proven; best solution so far has {}", value),
+        (true, true) => eprintln!("{} optimal solution(s) with {}", res.sols.len(), value),
+        (true, false) => eprintln!("at least {} optimal solutions with {} (stopped at --all-optimal or on timeout)", res.sols.len(), value),
+    }
 
-        // (2)
-        formula.add_clause(&[x.negative(), y.negative()]);
+    // 打ち切ったのが N 個に届いたからでなければ時間切れ
+    if !res.best.optimal || (!res.complete && res.sols.len() < config.all_optimal) {
+        process::exit(EXIT_TIMEOUT);
     }
+}
 
-    for u in vs {
-        let adjs: &Vec<P> = &adj(u, width, height);
+// 1 問だけのときは -j の数だけ設定を変えて競争させる
+fn solve_single(url: String, config: &Config) -> Result<(Puzzle, Sol), Error> {
+    if config.jobs == Some(1) || config.objective != Objective::None || config.regions > 0 || !config.solver.backend.is_sat() {
+        return solve_url(url, config);
+    }
 
-        if s.contains(&u) {
-            // (3)
-            {
-                let mut vars: Vec<Var> = vec![];    
-                for v in adjs {
-                    vars.push(Var::from_index(mp[&(u, *v)]+1));
-                }
+    let puzzle = read_puzzle(&url, config)?;
+    let jobs = match config.jobs {
+        Some(jobs) => jobs,
+        None if config.solver.backend == Backend::Auto => auto_jobs(&puzzle, &config.solver),
+        None => 1,
+    };
 
-                for lits in mk_clause_eq1(vars) {
-                    formula.add_clause(lits.as_slice());
-                }
-            }    
-            
-            // (4)
-            {
-                for v in adjs {
-                    formula.add_clause(&[Var::from_index(mp[&(*v, u)]+1).negative()]);
-                }
+    if jobs == 1 {
+        return solve_url(url, config);
+    }
+
+    let configs = portfolio_configs(&config.solver, jobs);
+    let sol = if config.deterministic {
+        solve_portfolio_ordered(&puzzle, &configs)?
+    } else {
+        solve_portfolio(&puzzle, &configs)?
+    };
+
+    Ok((puzzle, sol))
+}
+
+// 難しそうな盤面だけ, コアの数まで設定を競争させる (どちらの符号化でも見積もりが --max-memory に収まる数まで)
+fn auto_jobs(puzzle: &Puzzle, config: &SolverConfig) -> usize {
+    if predict(puzzle).difficulty < Difficulty::Medium {
+        return 1;
+    }
+
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let memory = [Encoding::Log, Encoding::Direct].iter().map(|&encoding| estimate(puzzle, &SolverConfig { encoding, ..config.clone() }).memory()).max().unwrap_or(0);
+
+    match config.max_memory {
+        Some(limit) => cores.min((limit/memory.max(1)) as usize).max(1),
+        None => cores,
+    }
+}
+
+fn solve_url(url: String, config: &Config) -> Result<(Puzzle, Sol), Error> {
+    let puzzle = read_puzzle(&url, config)?;
+
+    match config.objective {
+        Objective::None if config.regions > 0 => {
+            let sol = solve_regions(&puzzle, &config.solver, config.regions)?;
+
+            Ok((puzzle, sol))
+        },
+        Objective::None => {
+            let sol = solve_numberlink(&puzzle, &config.solver)?;
+
+            Ok((puzzle, sol))
+        },
+        Objective::Turns => {
+            let res = match &config.checkpoint {
+                Some(path) => minimize_with_checkpoint(&puzzle, config, path)?,
+                None => minimize_turns(&puzzle, &config.solver)?,
+            };
+
+            if !res.optimal {
+                eprintln!("warning: time is up, best solution so far has {} turns (not proven minimal)", res.turns);
             }
-        }
 
-        if t.contains(&u) {
-            // (5)
-            {
-                for v in adjs {
-                    formula.add_clause(&[Var::from_index(mp[&(u, *v)]+1).negative()]);
-                }
+            Ok((puzzle, res.sol))
+        },
+        Objective::Cost => {
+            let res = minimize_cost(&puzzle, &config.solver)?;
+
+            if !res.optimal {
+                eprintln!("warning: time is up, best solution so far costs {} (not proven minimal)", res.cost);
             }
-            
-            // (6)
-            {
-                let mut vars: Vec<Var> = vec![];    
-                for v in adjs {
-                    vars.push(Var::from_index(mp[&(*v, u)]+1));
-                }
 
-                for lits in mk_clause_eq1(vars) {
-                    formula.add_clause(lits.as_slice());
-                }
+            Ok((puzzle, res.sol))
+        },
+    }
+}
+
+// 書き出しに失敗しても探索は続ける (警告は 1 回だけ)
+fn minimize_with_checkpoint(puzzle: &Puzzle, config: &Config, path: &Path) -> Result<Optimized, Error> {
+    let start = match checkpoint::load(path, puzzle) {
+        Ok(start) => start,
+        Err(e) => fail(&format!("--checkpoint: {}", e)),
+    };
+
+    let mut warned = false;
+
+    minimize_turns_from(puzzle, &config.solver, start.as_ref(), |best| {
+        if let Err(e) = checkpoint::save(path, puzzle, best) {
+            if !warned {
+                eprintln!("warning: cannot write checkpoint {}: {}", path.display(), e);
+                warned = true;
             }
         }
+    })
+}
 
-        if b.contains(&u) {
-            // (8) (9)
-            {
-                let mut varss: Vec<Vec<Var>> = vec![];
-                let mut vars1: Vec<Var> = vec![];
-                let mut vars2: Vec<Var> = vec![];    
-                for v in adjs {
-                    vars1.push(Var::from_index(mp[&(u, *v)]+1));
-                    vars2.push(Var::from_index(mp[&(*v, u)]+1));
+// 入力を 1 行ずつ読んで jobs 本のスレッドに配り, 入力順に 1 行ずつ出す
+// 解いている途中と出力待ちを合わせて jobs*4 件までしか抱えないので, 入力がいくら長くてもメモリは増えない.
+// 終了コードは各行を 1 問だけ解いたときの終了コードの最大 (全部解ければ 0). 中断されたらそこで EXIT_TIMEOUT
+fn batch(inputs: impl Iterator<Item = String>, config: &Config, force: bool) {
+    // 答えの座標を含む設定では, 向きを変えた盤面に同じ答えを使えない
+    let cache = match &config.result_cache {
+        Some(_) if config.objective != Objective::None || !config.solver.constraints.is_empty() => {
+            eprintln!("warning: --result-cache is not used with --objective or --constraints");
+            None
+        },
+        Some(dir) => Some(ResultCache::open(dir, &config.solver.rules).unwrap_or_else(|msg| fail(&format!("--result-cache: {}", msg)))),
+        None => None,
+    };
+    let hits = AtomicUsize::new(0);
+    let jobs = config.jobs.unwrap_or(1);
+    let window = jobs*4;
+    let mut inputs = inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty());
+
+    let (job_tx, job_rx) = mpsc::sync_channel::<(usize, String)>(window);
+    let (res_tx, res_rx) = mpsc::channel::<(usize, String, Option<Error>)>();
+    let job_rx = Mutex::new(job_rx);
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut worst = 0;
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let (job_rx, res_tx, cache, hits) = (&job_rx, res_tx.clone(), &cache, &hits);
+
+            scope.spawn(move || {
+                while let Ok((k, url)) = job_rx.lock().unwrap().recv() {
+                    let res = match detect(&url).filter(|&genre| genre != Genre::Numberlink) {
+                        Some(genre) => solve_genre(genre, &url, &config.solver, config.format).map(|out| out.trim_end().to_string()),
+                        None => match &cache {
+                            Some(cache) => solve_cached(url, config, cache, force, hits),
+                            None => solve_url(url, config),
+                        }.map(|(puzzle, sol)| render_line(&puzzle, &sol, config.format)),
+                    };
+                    let (line, err) = match res {
+                        Ok(line) => (line, None),
+                        Err(e) => (render_error_line(&e, config.format), Some(e)),
+                    };
+
+                    if res_tx.send((k, line, err)).is_err() {
+                        return;
+                    }
                 }
+            });
+        }
+
+        drop(res_tx);
 
-                varss.push(vars1);
-                varss.push(vars2);
+        let mut job_tx = Some(job_tx);
+        let mut pending = BTreeMap::new();
+        let (mut read, mut written) = (0, 0);
 
-                /*for i in 0..2 {
-                    println!("----------");
+        loop {
+            if read-written < window {
+                if let Some(tx) = &job_tx {
+                    match inputs.next() {
+                        Some(url) => {
+                            let _ = tx.send((read, url));
+                            read += 1;
 
-                    for var in &varss[i] {
-                        println!("{}", var.index());
+                            continue;
+                        },
+                        None => job_tx = None,
                     }
+                }
+            }
+
+            if written == read {
+                return;
+            }
+
+            let (k, line, err) = match res_rx.recv() {
+                Ok(res) => res,
+                Err(_) => return,
+            };
+
+            pending.insert(k, (line, err));
+
+            while let Some((line, err)) = pending.remove(&written) {
+                written += 1;
 
-                    println!("----------");
-                }*/
+                // 出力先が閉じられたら (head など) 黙って終わる
+                if writeln!(out, "{}", line).and_then(|_| out.flush()).is_err() {
+                    process::exit(0);
+                }
 
-                for lits in mk_clause_d(varss) {
-                    formula.add_clause(lits.as_slice());
+                if matches!(err, Some(Error::Interrupted(_))) {
+                    process::exit(EXIT_TIMEOUT);
                 }
+
+                worst = worst.max(err.as_ref().map_or(0, exit_code));
             }
         }
+    });
+
+    if hits.load(Ordering::SeqCst) > 0 {
+        eprintln!("{} puzzles answered from --result-cache (--force solves them again)", hits.load(Ordering::SeqCst));
     }
 
-    /*for lits in formula.iter() {
-        let mut flag = false;
+    if worst > 0 {
+        process::exit(worst);
+    }
+}
 
-        for lit in lits {
-            flag = flag || lit.var().index() == 8;
-        }
+// 同じ代表の盤面の答えがあればそれを, なければ解いて覚える
+fn solve_cached(url: String, config: &Config, cache: &ResultCache, force: bool, hits: &AtomicUsize) -> Result<(Puzzle, Sol), Error> {
+    let puzzle = read_puzzle(&url, config)?;
 
-        flag = true;
+    if let Some(res) = cache.get(&puzzle).filter(|_| !force) {
+        hits.fetch_add(1, Ordering::SeqCst);
 
-        if flag {
-            println!("---------");
+        return res.map(|sol| (puzzle, sol));
+    }
 
-            for lit in lits {
-                println!("{} [{}]", lit.var().index(), if lit.is_positive() { "True" } else { "False" });
-            }
+    let res = solve_url(url, config);
+    let sol = res.as_ref().map(|(_, sol)| sol.clone()).map_err(Error::clone);
 
-            println!("---------");
-        }
-    }*/
+    if let Err(msg) = cache.put(&puzzle, &sol) {
+        eprintln!("warning: --result-cache: {}", msg);
+    }
 
-    let mut solver = Solver::new();
+    res
+}
 
-    solver.add_formula(&formula);
+// --diagonal と --wrap があれば読んだ盤面を斜めにも進めるようにし, 端をつなぐ
+fn read_puzzle(url: &str, config: &Config) -> Result<Puzzle, Error> {
+    let mut puzzle = try_parse_puzzle(url)?;
 
-    solver.solve().unwrap();
+    if config.diagonal {
+        puzzle.allow_diagonals();
+    }
 
-    let model = solver.model();
+    puzzle.wrap(config.wrap);
 
-    /*let mut index = 1;
+    Ok(puzzle)
+}
 
-    let mut prev_formula = CnfFormula::new();
+fn run_experiment(inputs: impl Iterator<Item = String>, config: &Config, seeds: u64) {
+    let puzzles: Vec<Puzzle> = inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()).map(|url| {
+        match read_puzzle(&url, config) {
+            Ok(puzzle) => puzzle,
+            Err(e) => fail(&format!("{}: {}", url, e)),
+        }
+    }).collect();
 
-    loop {
-        solver = Solver::new();
+    let trials = matrix(&config.solver, &[Encoding::Log, Encoding::Direct], &[Phase::Default, Phase::Corridors], seeds);
 
-        solver.add_formula(&formula);
+    print!("{}", render_table(&run(&puzzles, &trials)));
+}
 
-        solution = solver.solve().unwrap();
-        model = solver.model();
+fn run_dedup(inputs: impl Iterator<Item = String>, config: &Config) {
+    let urls: Vec<String> = inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()).collect();
+    let puzzles: Vec<Puzzle> = urls.iter().map(|url| {
+        match read_puzzle(url, config) {
+            Ok(puzzle) => puzzle,
+            Err(e) => fail(&format!("{}: {}", url, e)),
+        }
+    }).collect();
 
-        match &model {
-            Some(lits) => {
-                let mut cnt = 0;
-                let mut flag = false;
+    for k in dedup(&puzzles) {
+        println!("{}", urls[k]);
+    }
+}
 
-                for (idx, lit) in lits.iter().enumerate() {
-                    if lit.var().index() >= arcs.len()+1 {
-                        continue;
-                    }
+// transform の 1 段
+enum Step {
+    Turn(Transform),
+    Renumber,
+    // top, left, rows, cols と, 外へ出る組の端を穴にするか
+    Crop(usize, usize, usize, usize, bool),
+}
 
-                    if lit.is_positive() {
-                        cnt += 1;
-                    }
+impl FromStr for Step {
+    type Err = String;
 
-                    if !flag && idx >= index && lit.is_negative() {
-                        flag = true;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // crop=WxH@ROW:COL, seal=WxH@ROW:COL
+        if let Some((name, rect)) = s.split_once('=').filter(|&(name, _)| name == "crop" || name == "seal") {
+            let parse = || {
+                let (size, at) = rect.split_once('@')?;
+                let (cols, rows) = size.split_once('x')?;
+                let (top, left) = at.split_once(':')?;
 
-                        index = idx;
-                    }
-                }
+                Some(Step::Crop(top.parse().ok()?, left.parse().ok()?, rows.parse().ok()?, cols.parse().ok()?, name == "seal"))
+            };
 
-                if cnt == width*height-s.len() {
-                    break;
-                }
+            return parse().ok_or_else(|| format!("invalid rectangle `{}` (expected WxH@ROW:COL)", rect));
+        }
+
+        match s {
+            "renumber" => Ok(Step::Renumber),
+            _ => s.parse().map(Step::Turn).map_err(|_| format!("unknown step `{}` (expected rotate90, rotate180, rotate270, mirror, flip, transpose, antitranspose, identity, renumber, crop=WxH@ROW:COL or seal=WxH@ROW:COL)", s)),
+        }
+    }
+}
 
-                prev_formula = CnfFormula::new();
+fn run_transform(inputs: impl Iterator<Item = String>, config: &Config, steps: &[Step]) {
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        let puzzle = match read_puzzle(&url, config) {
+            Ok(puzzle) => puzzle,
+            Err(e) => {
+                println!("{}: {}", url, e);
+                continue;
+            },
+        };
 
-                for lits in formula.iter() {
-                    prev_formula.add_clause(lits.clone());
-                }
+        let res = steps.iter().try_fold(puzzle, |puzzle, step| match *step {
+            Step::Turn(t) => transform(&puzzle, t).ok_or_else(|| format!("cannot {} this board", t)),
+            Step::Renumber => Ok(renumber(&puzzle)),
+            Step::Crop(top, left, rows, cols, seal) => crop(&puzzle, top, left, rows, cols, seal).ok_or_else(|| format!("cannot crop {}x{} at {}:{} from this board", cols, rows, top, left)),
+        });
 
-                println!("index: {}", index);
+        match res {
+            Ok(puzzle) => print!("{}", write_board(&puzzle)),
+            Err(msg) => println!("{}: {}", url, msg),
+        }
+    }
+}
 
-                formula.add_clause(&[Lit::from_var(Var::from_index(index), true)]);
+fn run_tile(inputs: impl Iterator<Item = String>, config: &Config, columns: Option<usize>, shared: bool) {
+    let puzzles: Vec<Puzzle> = inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()).map(|url| {
+        match read_puzzle(&url, config) {
+            Ok(puzzle) => puzzle,
+            Err(e) => fail(&format!("{}: {}", url, e)),
+        }
+    }).collect();
 
-                index += 1;
+    // 既定ではおおよそ正方形に並べる
+    let columns = columns.unwrap_or_else(|| (1..).find(|&c| c*c >= puzzles.len()).unwrap());
 
-                if index >= lits.len() {
-                    return None;
-                }
-            },
-            None => {
-                formula = CnfFormula::new();
+    match tile(&puzzles, columns, !shared) {
+        Some(puzzle) => print!("{}", write_board(&puzzle)),
+        None => fail("cannot tile these boards"),
+    }
+}
 
-                for lits in prev_formula.iter() {
-                    formula.add_clause(lits.clone());
-                }
-            }
+// 書けない盤面と解けない盤面は stderr に書いて飛ばし, 書いたものだけに 1 から番号を振る
+fn run_pack(inputs: impl Iterator<Item = String>, config: &Config, solutions: bool) {
+    let mut level = 1;
+
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        let res = read_puzzle(&url, config).map_err(|e| e.to_string()).and_then(|puzzle| {
+            let sol = match solutions {
+                true => Some(solve_numberlink(&puzzle, &config.solver).map_err(|e| e.to_string())?),
+                false => None,
+            };
+
+            render_flow(&puzzle, sol.as_ref(), level)
+        });
+
+        match res {
+            Ok(line) => {
+                println!("{}", line);
+                level += 1;
+            },
+            Err(msg) => eprintln!("{}: {}", url, msg),
         }
-    }*/
-    
-    //println!("Solution: {}", solution);
+    }
+}
 
-    let mut sol: Vec<Arc> = vec![];
+// URL に書けるのは数字と壁だけ. テキストの盤面に書けないもの (壁, ワープ, 数字の付いた通る点など) があれば JSON
+fn write_board(puzzle: &Puzzle) -> String {
+    let plain = Puzzle { cells: puzzle.cells.clone(), walls: puzzle.walls.clone(), ..Puzzle::new(puzzle.width, puzzle.height) };
+    let text = puzzle.walls.is_empty() && puzzle.topology == Topology::Square && puzzle.warps.is_empty() && puzzle.vias.is_empty() && puzzle.one_way.is_empty()
+        && puzzle.limits.is_empty() && puzzle.costs.is_empty() && puzzle.budget.is_none() && puzzle.waypoints.values().all(|&num| num == 0);
 
-    match model {
-        Some(lits) => {
-            //println!("{:?}", lits);
+    if *puzzle == plain {
+        write_puzzle(puzzle, Output::Url)
+    } else if text {
+        write_puzzle(puzzle, Output::Text)
+    } else {
+        render_puzzle_json(puzzle)
+    }
+}
 
-            for lit in &lits {
-                //println!("{} [{}]", lit.var().index(), if lit.is_positive() { "True" } else { "False" });
+fn run_compare(inputs: impl Iterator<Item = String>, config: &Config) {
+    let trials = matrix(&config.solver, &[Encoding::Log, Encoding::Direct], &[config.solver.phase], 1);
+    let mut agreed = true;
 
-                if lit.is_positive() {
-                    let index = lit.var().index();
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        let puzzle = match read_puzzle(&url, config) {
+            Ok(puzzle) => puzzle,
+            Err(e) => fail(&format!("{}: {}", url, e)),
+        };
 
-                    if index >= arcs.len() {
-                        continue;
-                    }
+        let mut trials = trials.clone();
 
-                    //let arc = arcs[index-1];
-                    //println!("{:?}", arc);
+        // 小さい盤面では SAT を使わない探索の答えも並べて検算する
+        if puzzle.cells.len() <= SMALL_CELLS && sat_only(&puzzle, &config.solver).is_none() && config.solver.backend.is_sat() {
+            trials.push(("backtrack".to_string(), SolverConfig { backend: Backend::Backtrack, ..config.solver.clone() }));
+        }
 
-                    sol.push(arcs[index-1]);
-                }
-            }
+        let outcomes = compare(&puzzle, &trials);
 
-            /*if cnt != width*height-s.len() {
-                println!("Kansei Solution!!!");
-            }*/
-        },
-        None => {
-            //println!("No Solution");
+        println!("{}", render_outcomes(&url, &outcomes));
 
-            return None;
+        if outcomes.iter().any(|outcome| matches!(outcome.result, Err(Error::Interrupted(_)))) {
+            process::exit(EXIT_TIMEOUT);
         }
+
+        agreed &= agree(&outcomes);
     }
 
-    Some(sol)
+    if !agreed {
+        fail("configurations disagree");
+    }
 }
 
-fn parse_field(field :&Field) -> Option<(Vec<P>, Vec<P>, Vec<P>)> {
-    let mut cnt = vec![0; 100];
-    let mut ends = vec![vec![]; 2];
-    let mut b = vec![];
-    
-    for (i, line) in field.clone().into_iter().enumerate() {
-        for (j, p) in line.clone().into_iter().enumerate() {
-            if p > 0 {
-                if cnt[p] >= 2 {
-                    return None;
-                }
+// 打ち切られたら残りは数えない
+fn run_crosscheck(inputs: impl Iterator<Item = String>, config: &Config, checkers: &[Checker]) {
+    let mut agreed = true;
 
-                ends[cnt[p]].push((i, j));
-                cnt[p] += 1;
-            } else {
-                b.push((i, j));
-            }
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        let puzzle = match read_puzzle(&url, config) {
+            Ok(puzzle) => puzzle,
+            Err(e) => fail(&format!("{}: {}", url, e)),
+        };
+
+        let verdict = crosscheck(&puzzle, &url, &config.solver, checkers);
+
+        println!("{}", render_verdict(&url, &verdict));
+
+        if verdict.outcomes.iter().any(|outcome| matches!(outcome.result, Err(Error::Interrupted(_)))) {
+            process::exit(EXIT_TIMEOUT);
         }
+
+        agreed &= verdict.agree();
     }
 
-    Some((ends[0].clone(), ends[1].clone(), b))
+    if !agreed {
+        fail("solvers disagree");
+    }
 }
 
-fn mk_clause_impl(x: &Var, fu: &Vec<usize>, fv: &Vec<usize>) -> Vec<Vec<Lit>> {
-    let mut res: Vec<Vec<Lit>> = vec![];
-    
-    for (i, fuidx) in fu.clone().into_iter().enumerate() {
-        let fvidx = fv[i].clone();
-        let fui = Var::from_index(fuidx);
-        let fvi = Var::from_index(fvidx);
+fn run_count(inputs: impl Iterator<Item = String>, config: &Config) {
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        // ZDD で数えたときだけ節点の数も書く
+        let res = read_puzzle(&url, config).and_then(|puzzle| {
+            let backend = match &config.solver.backend {
+                Backend::Auto => select(&puzzle, &config.solver, Query::Count),
+                backend => backend.clone(),
+            };
+
+            match backend {
+                Backend::Zdd => Zdd::build(&puzzle, &config.solver).map(|zdd| format!("{} ({} ZDD nodes)", solutions(zdd.count()), zdd.size())),
+                backend => count_numberlink(&puzzle, &SolverConfig { backend, ..config.solver.clone() }).map(solutions),
+            }
+        });
+
+        match res {
+            Ok(out) => println!("{}: {}", url, out),
+            Err(e) => {
+                println!("{}: {}", url, e);
 
-        res.push(vec![x.negative(), fui.negative(), fvi.positive()]);
-        res.push(vec![x.negative(), fui.positive(), fvi.negative()]);
+                if matches!(e, Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_)) {
+                    process::exit(EXIT_TIMEOUT);
+                }
+            },
+        }
     }
+}
 
-    res
+// "1 solution", "0 solutions", "2 solutions"
+fn solutions(count: u128) -> String {
+    match count {
+        1 => "1 solution".to_string(),
+        count => format!("{} solutions", count),
+    }
 }
 
-fn popcount(bit: usize) -> usize {
-    let mut ret = 0;
-    let mut b = bit;
+#[cfg(feature = "script")]
+fn run_script(inputs: impl Iterator<Item = String>, config: &Config, path: &str) {
+    use numberlink_solver::script::{solve_script, Script};
 
-    while b > 0 {
-        if b&1 != 0 {
-            ret += 1;
-        }
+    let script = match std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|source| Script::new(&source).map_err(|e| e.to_string())) {
+        Ok(script) => script,
+        Err(msg) => fail(&format!("{}: {}", path, msg)),
+    };
 
-        b >>= 1;
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        let res = read_puzzle(&url, config).and_then(|puzzle| solve_script(&puzzle, &config.solver, &script).map(|res| (puzzle, res)));
+
+        match res {
+            Ok((puzzle, res)) => {
+                match res.value {
+                    Some(value) if !res.optimal => eprintln!("warning: time is up, best solution so far has {} true (not proven minimal)", value),
+                    Some(value) => eprintln!("{}: {} true", url, value),
+                    None => {},
+                }
+
+                print!("{}", render(&puzzle, &res.sol, config.format, config.theme));
+            },
+            Err(e) => {
+                println!("{}: {}", url, e);
+
+                if matches!(e, Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_)) {
+                    process::exit(EXIT_TIMEOUT);
+                }
+            },
+        }
     }
+}
 
-    ret
+#[cfg(not(feature = "script"))]
+fn run_script(_: impl Iterator<Item = String>, _: &Config, _: &str) {
+    fail("script is not available in this build (rebuild with --features script)");
 }
 
-fn mk_clause_eq1(vars: Vec<Var>) -> Vec<Vec<Lit>> {
-    let mut res: Vec<Vec<Lit>> = vec![];
-    let n = vars.len();
+// Numberlink 以外のパズル: solve は読んで解いて出力を返す
+fn run_genre(inputs: impl Iterator<Item = String>, solve: impl Fn(&str) -> Result<String, Error>) {
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        match solve(&url) {
+            Ok(out) => print!("{}", out),
+            Err(e) => {
+                println!("{}: {}", url, e);
 
-    for bit in 0..(1<<n) {
-        if 1+popcount(bit) == n {
-            continue;
+                if matches!(e, Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_)) {
+                    process::exit(EXIT_TIMEOUT);
+                }
+            },
         }
+    }
+}
 
-        let mut lits: Vec<Lit> = vec![];
+fn run_predict(inputs: impl Iterator<Item = String>) {
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        match parse_puzzle(&url) {
+            Some(puzzle) => {
+                let p = predict(&puzzle);
 
-        for i in 0..n {
-            lits.push(Lit::from_var(vars[i], (bit>>i&1) != 0));
+                println!("{}: {}, {} cells, {} pairs, density {:.3}, score {:.0}", url, p.difficulty, p.cells, p.pairs, p.density, p.score);
+            },
+            None => println!("{}: {}", url, Error::InvalidUrl),
         }
+    }
+}
+
+fn run_rate(inputs: impl Iterator<Item = String>, config: &Config) {
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        match read_puzzle(&url, config).and_then(|puzzle| rate_with(&puzzle, &config.solver)) {
+            Ok(r) => println!("{}: {}, score {:.0}, {} conflicts, {} open edges, structure {:.0}, solved by {}", url, r.tier, r.score, r.conflicts, r.decisions, r.structure, r.grade),
+            Err(e) => {
+                println!("{}: {}", url, e);
 
-        res.push(lits);
+                if matches!(e, Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_)) {
+                    process::exit(EXIT_TIMEOUT);
+                }
+            },
+        }
     }
+}
 
-    res
+fn run_profile(inputs: impl Iterator<Item = String>, config: &Config) {
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        let res = read_puzzle(&url, config).and_then(|puzzle| match solve_numberlink(&puzzle, &config.solver) {
+            Ok(sol) => Ok(profile(&puzzle, Some(&sol))),
+            Err(Error::NoSolution) => Ok(profile(&puzzle, None)),
+            Err(e) => Err(e),
+        });
+
+        match res {
+            Ok(p) => println!("{}: {}", url, p),
+            Err(e) => {
+                println!("{}: {}", url, e);
+
+                if matches!(e, Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_)) {
+                    process::exit(EXIT_TIMEOUT);
+                }
+            },
+        }
+    }
 }
 
-fn mk_clause_d(varss: Vec<Vec<Var>>) -> Vec<Vec<Lit>> {
-    let mut res: Vec<Vec<Lit>> = vec![];
-    let n = varss[0].len();
+fn run_report(inputs: impl Iterator<Item = String>, config: &Config, format: ReportFormat) {
+    let mut puzzles = vec![];
 
-    /*for ri in 0..2 {
-        for rj in 0..2 {
-            let mut lits: Vec<Lit> = vec![];
+    for input in inputs.map(|line| line.trim().to_string()).filter(|input| !input.is_empty()) {
+        let path = Path::new(&input);
 
-            for i in 0..n {
-                lits.push(Lit::from_var(varss[ri][i], false));
+        if !path.exists() {
+            match read_puzzle(&input, config) {
+                Ok(puzzle) => puzzles.push((input, puzzle)),
+                Err(e) => fail(&format!("{}: {}", input, e)),
             }
 
-            res.push(lits);
+            continue;
         }
-    }*/
 
-    for r in 0..2 {
-        for i in 0..(1<<n) {
-            if 1+popcount(i) == n {
+        let mut files: Vec<PathBuf> = match path.read_dir() {
+            Ok(dir) => dir.filter_map(|entry| entry.ok().map(|entry| entry.path())).filter(|path| path.is_file()).collect(),
+            Err(_) => vec![path.to_path_buf()],
+        };
+
+        files.sort();
+
+        for file in files {
+            let name = file.display().to_string();
+            let content = match std::fs::read_to_string(&file) {
+                Ok(content) => content,
+                Err(e) => fail(&format!("{}: {}", name, e)),
+            };
+
+            // URL か 1 行の JSON が 1 行に 1 問ずつ並んでいなければ, ファイル全体で 1 問 (テキストの盤面など)
+            let lines: Vec<&str> = content.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+
+            if lines.len() <= 1 || !lines.iter().all(|line| line.contains("://") || line.starts_with('{')) {
+                match read_puzzle(content.trim(), config) {
+                    Ok(puzzle) => puzzles.push((name, puzzle)),
+                    Err(e) => fail(&format!("{}: {}", name, e)),
+                }
+
                 continue;
             }
 
-            let mut lits = vec![];
-
-            for j in 0..n {
-                lits.push(Lit::from_var(varss[r][j], (i>>j&1) != 0));
+            for (k, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+                match read_puzzle(line.trim(), config) {
+                    Ok(puzzle) => puzzles.push((format!("{}:{}", name, k+1), puzzle)),
+                    Err(e) => fail(&format!("{}:{}: {}", name, k+1, e)),
+                }
             }
+        }
+    }
+
+    print!("{}", render_report(&entries(puzzles, &config.solver), format));
+}
 
-            res.push(lits);
+fn run_minimize(inputs: impl Iterator<Item = String>, config: &Config) {
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        match read_puzzle(&url, config).and_then(|puzzle| minimize(&puzzle, &config.solver)) {
+            Ok(Some(puzzle)) => {
+                print!("{}", write_board(&puzzle));
+            },
+            Ok(None) => println!("{}: not unique", url),
+            Err(e) => {
+                println!("{}: {}", url, e);
+
+                if matches!(e, Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_)) {
+                    process::exit(EXIT_TIMEOUT);
+                }
+            },
         }
     }
+}
 
-    res
+fn run_repair(inputs: impl Iterator<Item = String>, config: &Config) {
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        // 解けるなら, 答えをただ 1 つにする手直しを探す
+        let res = read_puzzle(&url, config).and_then(|puzzle| match suggest_solvable(&puzzle, &config.solver, REPAIR_LIMIT)? {
+            Some(edits) => Ok(Some(edits)),
+            None => suggest_unique(&puzzle, &config.solver, REPAIR_LIMIT),
+        });
+
+        match res {
+            Ok(None) => println!("{}: unique", url),
+            Ok(Some(edits)) if edits.is_empty() => println!("{}: no repair found", url),
+            Ok(Some(edits)) => println!("{}: {}", url, edits.iter().map(|edit| edit.to_string()).collect::<Vec<_>>().join("; ")),
+            Err(e) => {
+                println!("{}: {}", url, e);
+
+                if matches!(e, Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_)) {
+                    process::exit(EXIT_TIMEOUT);
+                }
+            },
+        }
+    }
+}
+
+fn run_hint(inputs: impl Iterator<Item = String>, config: &Config, partial: &PartialSolution) {
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        match read_puzzle(&url, config).and_then(|puzzle| hint_with(&puzzle, partial, &config.solver)) {
+            Ok(Some(hint)) => println!("{}: {}", url, hint),
+            Ok(None) => println!("{}: nothing more follows", url),
+            Err(e) => {
+                println!("{}: {}", url, e);
+
+                if matches!(e, Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_)) {
+                    process::exit(EXIT_TIMEOUT);
+                }
+            },
+        }
+    }
+}
+
+fn run_screen(inputs: impl Iterator<Item = String>, config: &Config) {
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        match read_puzzle(&url, config).map(|puzzle| screen(&puzzle)) {
+            Ok(suspects) if suspects.is_empty() => println!("{}: clear", url),
+            Ok(suspects) => println!("{}: {}", url, suspects.iter().map(|suspect| suspect.to_string()).collect::<Vec<_>>().join("; ")),
+            Err(e) => println!("{}: {}", url, e),
+        }
+    }
+}
+
+// against があればその backend の答えと, なければ別解と比べる
+fn run_diff(inputs: impl Iterator<Item = String>, config: &Config, against: Option<&Backend>) {
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        let res = read_puzzle(&url, config).and_then(|puzzle| {
+            let (first, second) = match against {
+                Some(backend) => {
+                    let first = solve_numberlink(&puzzle, &config.solver)?;
+                    let second = solve_numberlink(&puzzle, &SolverConfig { backend: backend.clone(), ..config.solver.clone() })?;
+
+                    (first, Some(second))
+                },
+                None => {
+                    let mut session = Session::new(&puzzle, &config.solver)?;
+                    let first = session.solve()?;
+                    let second = session.find_other(&first, &[])?;
+
+                    (first, second)
+                },
+            };
+
+            Ok(match second {
+                Some(second) if diff(&puzzle, &first, &second).is_empty() => "same\n".to_string(),
+                Some(second) => render_diff(&puzzle, &first, &second),
+                None => "unique\n".to_string(),
+            })
+        });
+
+        match res {
+            Ok(text) => print!("{}: {}", url, text),
+            Err(e) => {
+                println!("{}: {}", url, e);
+
+                if matches!(e, Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_)) {
+                    process::exit(EXIT_TIMEOUT);
+                }
+            },
+        }
+    }
 }
 
-fn adj(p: P, width: usize, height: usize) -> Vec<P> {
-    let dx: Vec<i32> = vec![1, 0, -1, 0];
-    let dy: Vec<i32> = vec![0, 1, 0, -1];
+fn run_families(inputs: impl Iterator<Item = String>, config: &Config) {
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        let res = read_puzzle(&url, config).and_then(|puzzle| {
+            let mut formula = CnfFormula::new();
+            let mut tags = vec![];
 
-    let mut st = HashSet::new();
-    let mut res = vec![];
+            encode_tagged(&puzzle, &config.solver, &mut formula, &mut tags)?;
 
-    for d in 0..4 {
-        let ni = (p.0 as i32 + dy[d]) as usize;
-        let nj = (p.1 as i32 + dx[d]) as usize;
+            let core = unsat_core(&puzzle, &config.solver)?;
 
-        if ni < height && nj < width && !st.contains(&(ni, nj)) {
-            res.push((ni, nj));
-            st.insert((ni, nj));
+            Ok(render_families(&puzzle, &family_stats(&formula, &tags), core.as_deref()))
+        });
+
+        match res {
+            Ok(view) => print!("{}:\n{}", url, view),
+            Err(e) => {
+                println!("{}: {}", url, e);
+
+                if matches!(e, Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_)) {
+                    process::exit(EXIT_TIMEOUT);
+                }
+            },
         }
     }
+}
 
-    res
+fn run_explain(inputs: impl Iterator<Item = String>, config: &Config) {
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        match read_puzzle(&url, config).and_then(|puzzle| explain_with(&puzzle, &config.solver)) {
+            Ok(steps) => {
+                let searched = steps.iter().filter(|step| step.grade == Grade::Search).count();
+
+                println!("{}: {} steps, {} by search", url, steps.len(), searched);
+
+                for (k, step) in steps.iter().enumerate() {
+                    println!("  {}. {}", k+1, step);
+                }
+            },
+            Err(e) => {
+                println!("{}: {}", url, e);
+
+                if matches!(e, Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_)) {
+                    process::exit(EXIT_TIMEOUT);
+                }
+            },
+        }
+    }
 }
 
-fn gen_arcs(width: usize, height: usize) -> Vec<Arc> {
-    let mut res: Vec<Arc> = vec![];
+// gen の --size WxH, --pairs N, --difficulty D, --symmetry S, --min-fill F などの下限, --reject F,..., --seed N, --count N, --out FORMAT
+// (それぞれ --key=value とも書ける) と, 値のない --report, 値を省ける --daily
+#[cfg(feature = "server")]
+fn run_serve(values: &[Option<String>], config: &Config) {
+    let mut server = numberlink_solver::server::ServerConfig::default();
+    let number = |what: &str, value: &str| match value.parse::<usize>() {
+        Ok(n) if n > 0 => n,
+        _ => fail(&format!("invalid number of {} `{}`", what, value)),
+    };
+
+    if let [addr, workers, queue, cache, keys, rate, grpc] = values {
+        server.addr = addr.clone().unwrap_or(server.addr);
+        server.workers = workers.as_deref().map_or(server.workers, |value| number("workers", value));
+        server.queue = queue.as_deref().map_or(server.queue, |value| number("queued jobs", value));
+        server.cache = match cache.as_deref().map(str::parse) {
+            Some(Ok(cache)) => cache,
+            Some(Err(_)) => fail(&format!("invalid cache size `{}`", cache.as_deref().unwrap_or(""))),
+            None => server.cache,
+        };
 
-    for i in 0..height {
-        for j in 0..width {
-            let u = (i, j);
-            let adjs = adj(u, width, height);
+        let rate = match rate.as_deref().map(str::parse) {
+            Some(Ok(rate)) => rate,
+            Some(Err(_)) => fail(&format!("invalid rate `{}`", rate.as_deref().unwrap_or(""))),
+            None => 60,
+        };
 
-            for v in adjs {
-                res.push((u, v));
+        if let Some(path) = keys {
+            let text = std::fs::read_to_string(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+
+            server.keys = numberlink_solver::server::parse_keys(&text, rate).unwrap_or_else(|msg| fail(&format!("{}: {}", path, msg)));
+
+            if server.keys.is_empty() {
+                fail(&format!("{}: no API keys", path));
             }
         }
+
+        if grpc.is_some() && cfg!(not(feature = "grpc")) {
+            fail("--grpc is not available in this build (rebuild with --features grpc)");
+        }
+
+        server.grpc = grpc.clone();
     }
 
-    res
+    if let Err(e) = numberlink_solver::server::serve(&server, config.solver.clone()) {
+        fail(&format!("serve: {}", e));
+    }
 }
 
-pub fn parse_url(url: String) -> Option<Field> {
-    let splitter = '/';
-    let params: Vec<String> = url.split(splitter).map(|s| s.to_string()).collect();
-    let length = params.len();
+#[cfg(feature = "server")]
+fn run_openapi() {
+    println!("{}", serde_json::to_string_pretty(&numberlink_solver::openapi::openapi()).unwrap());
+}
 
-    if length < 3 {
-        return None;
-    }
+#[cfg(not(feature = "server"))]
+fn run_openapi() {
+    fail("serve is not available in this build (rebuild with --features server)");
+}
 
-    let width = params[length-3].parse().unwrap_or(0);
-    let height = params[length-2].parse().unwrap_or(0);
+#[cfg(feature = "gui")]
+fn run_gui(url: Option<&String>, config: &Config) {
+    let puzzle = url.map(|url| match read_puzzle(url, config) {
+        Ok(puzzle) => puzzle,
+        Err(e) => fail(&format!("{}: {}", url, e)),
+    });
 
-    if width <= 0 || height <= 0 {
-        return None;
+    if let Err(msg) = numberlink_solver::gui::run(puzzle, config.solver.clone()) {
+        fail(&msg);
     }
+}
+
+#[cfg(not(feature = "gui"))]
+fn run_gui(_: Option<&String>, _: &Config) {
+    fail("gui is not available in this build (rebuild with --features gui)");
+}
 
-    let field_code = params[length-1].clone();
+#[cfg(not(feature = "server"))]
+fn run_serve(_: &[Option<String>], _: &Config) {
+    fail("serve is not available in this build (rebuild with --features server)");
+}
 
-    if !is_valid_code(&field_code) {
-        return None;
+#[cfg(unix)]
+fn run_daemon(socket: Option<&str>, config: &Config) {
+    let path = socket.map_or_else(numberlink_solver::daemon::default_socket, PathBuf::from);
+
+    if let Err(e) = numberlink_solver::daemon::serve_socket(&path, config.solver.clone()) {
+        fail(&format!("daemon: {}", e));
     }
+}
 
-    decode_field(width, height, field_code)
+#[cfg(not(unix))]
+fn run_daemon(_: Option<&str>, _: &Config) {
+    fail("daemon needs Unix domain sockets, which this platform does not have");
 }
 
-fn is_valid_code(code: &String) -> bool {
-    return code.chars().all(|ch| char::is_alphanumeric(ch) || ch == '-');
+#[cfg(feature = "db")]
+fn open_db(path: Option<&str>) -> numberlink_solver::db::Store {
+    let path = PathBuf::from(path.unwrap_or(numberlink_solver::db::DEFAULT_PATH));
+
+    numberlink_solver::db::Store::open(&path).unwrap_or_else(|msg| fail(&format!("db: {}", msg)))
 }
 
-fn decode_field(width: usize, height: usize, code: String) -> Option<Field> {
-    let list: &Vec<char> = &code.chars().collect();
-    let mut index: usize = 0;
-    let mut i: usize = 0;
-    let mut j: usize = 0;
+// 盤面を足し, solve なら解いて 1 行ずつ書き, 記録する. 読めない盤面は警告して飛ばす
+#[cfg(feature = "db")]
+fn run_db(inputs: impl Iterator<Item = String>, path: Option<&str>, solve: bool, config: &Config) {
+    let store = open_db(path);
+    let mut added = 0;
+
+    for url in inputs.map(|line| line.trim().to_string()).filter(|url| !url.is_empty()) {
+        let puzzle = match read_puzzle(&url, config) {
+            Ok(puzzle) => puzzle,
+            Err(e) => {
+                eprintln!("warning: {}: {}", url, e);
+                continue;
+            },
+        };
+        let id = store.add(&puzzle, &url).unwrap_or_else(|msg| fail(&format!("db: {}", msg)));
 
-    let mut res: Field = vec![vec![0; width as usize]; height as usize];
+        added += 1;
 
-    while index < list.len() {
-        while let Some(num) = get_num(&mut index, list) {
-            res[i as usize][j as usize] = num;
+        if !solve {
+            continue;
+        }
 
-            j += 1;
+        let start = Instant::now();
+        let res = solve_numberlink(&puzzle, &config.solver);
 
-            if j >= width {
-                j = 0;
-                i += 1;
-            }
+        store.record(id, &puzzle, &res, start.elapsed()).unwrap_or_else(|msg| fail(&format!("db: {}", msg)));
 
-            if index >= list.len() {
-                break;
-            }
+        match &res {
+            Ok(sol) => println!("{}", render_line(&puzzle, sol, config.format)),
+            Err(e) => println!("{}", render_error_line(e, config.format)),
         }
+    }
 
-        consume(&mut index, &mut i, &mut j, width, list);
+    if !solve {
+        eprintln!("{} puzzles stored", added);
     }
+}
+
+#[cfg(feature = "db")]
+fn run_db_query(path: Option<&str>, query: &str, limit: usize) {
+    let query = query.parse().unwrap_or_else(|msg: String| fail(&format!("db query: {}", msg)));
+    let entries = open_db(path).query(query, limit).unwrap_or_else(|msg| fail(&format!("db: {}", msg)));
+
+    print!("{}", numberlink_solver::db::render_entries(&entries));
+}
 
-    Some(res)
+#[cfg(not(feature = "db"))]
+fn run_db(_: impl Iterator<Item = String>, _: Option<&str>, _: bool, _: &Config) {
+    fail("db is not available in this build (rebuild with --features db)");
 }
 
-fn get_num(index: &mut usize, list: &Vec<char>) -> Option<usize> {
-    let ch = list[*index as usize];
+#[cfg(not(feature = "db"))]
+fn run_db_query(_: Option<&str>, _: &str, _: usize) {
+    fail("db is not available in this build (rebuild with --features db)");
+}
+
+fn run_gen(args: &[String]) {
+    let mut config = GenConfig::default();
+    let mut output = Output::Url;
+    let mut count = 1;
+    let mut report = false;
+    let mut from = None;
+    let mut index = 0;
 
-    if ch == '-' {
-        *index += 1;
-        let mut res = 0;
+    while index < args.len() {
+        let arg = &args[index];
 
-        while res*16 < 100 && *index < list.len() && list[*index].is_digit(16) {
-            res *= 16;
-            res += list[*index].to_digit(16).unwrap();
-            *index += 1;
+        if arg == "--report" {
+            report = true;
+            index += 1;
+            continue;
         }
 
-        return Some(res as usize);
-    } else if ch.is_digit(16) {
-        *index += 1;
+        // 日付は省略できる (今日の日付, UTC)
+        if arg == "--daily" || arg.starts_with("--daily=") {
+            let date = match arg.strip_prefix("--daily=") {
+                Some(date) => date.to_string(),
+                None => match args.get(index+1).filter(|next| !next.starts_with("--")) {
+                    Some(date) => {
+                        index += 1;
+                        date.clone()
+                    },
+                    None => today(),
+                },
+            };
+
+            config.seed = daily_seed(&date).unwrap_or_else(|| fail(&format!("--daily: invalid date `{}` (expected YYYY-MM-DD)", date)));
+            index += 1;
+            continue;
+        }
 
-        return match ch.to_digit(16) {
-            Some(num) => Some(num as usize),
-            None => None,
+        let (key, value) = match arg.split_once('=') {
+            Some((key, value)) => (key, value.to_string()),
+            None => {
+                index += 1;
+
+                match args.get(index) {
+                    Some(value) => (arg.as_str(), value.clone()),
+                    None => fail(&format!("{} needs a value\n\n{}", arg, USAGE)),
+                }
+            },
         };
-    } else {
-        return None;
+        let number = |value: &str| value.parse().ok().filter(|&n: &usize| n > 0);
+        let ratio = |value: &str| value.parse().ok().filter(|&x: &f64| x >= 0.0);
+        // 盤面の中での割合なので 1 を超えるとどの盤面も届かない
+        let share = |value: &str| value.parse().ok().filter(|x: &f64| (0.0..=1.0).contains(x));
+
+        index += 1;
+
+        let valid = match key {
+            "--size" => value.split_once('x').and_then(|(width, height)| Some((number(width)?, number(height)?))).map(|(width, height)| {
+                if width.checked_mul(height).is_none_or(|cells| cells > MAX_GENERATE_CELLS) {
+                    fail(&format!("--size: board too large ({}x{}, at most {} cells)", width, height, MAX_GENERATE_CELLS));
+                }
+
+                config.width = width;
+                config.height = height;
+            }),
+            "--pairs" => number(&value).map(|pairs| config.pairs = pairs),
+            "--seed" => value.parse().ok().map(|seed| config.seed = seed),
+            "--count" => number(&value).map(|n| count = n),
+            "--min-fill" => share(&value).map(|fill| config.min_quality.fill = fill),
+            "--min-length" => ratio(&value).map(|length| config.min_quality.length = length),
+            "--min-bends" => value.parse().ok().map(|bends| config.min_quality.bends = bends),
+            "--min-spread" => ratio(&value).map(|spread| config.min_quality.spread = spread),
+            "--from" => {
+                from = Some(value.clone());
+                Some(())
+            },
+            "--reject" => match value.split(',').map(str::parse).collect() {
+                Ok(reject) => {
+                    config.reject = reject;
+                    Some(())
+                },
+                Err(msg) => fail(&format!("--reject: {}", msg)),
+            },
+            "--difficulty" => match value.parse() {
+                Ok(target) => {
+                    config.target = Some(target);
+                    Some(())
+                },
+                Err(msg) => fail(&format!("--difficulty: {}", msg)),
+            },
+            "--symmetry" => match value.parse() {
+                Ok(symmetry) => {
+                    config.symmetry = symmetry;
+                    Some(())
+                },
+                Err(msg) => fail(&format!("--symmetry: {}", msg)),
+            },
+            "--out" => match value.parse() {
+                Ok(out) => {
+                    output = out;
+                    Some(())
+                },
+                Err(msg) => fail(&format!("--out: {}", msg)),
+            },
+            _ => fail(&format!("unknown option `{}`\n\n{}", key, USAGE)),
+        };
+
+        if valid.is_none() {
+            fail(&format!("{}: invalid value `{}`", key, value));
+        }
+    }
+
+    if let Some(solution) = from {
+        run_from_paths(&solution, output);
+        return;
+    }
+
+    // --daily の seed は u64 の端の近くにもなるので, 続く seed は端で 0 に戻る
+    for seed in (0..count as u64).map(|k| config.seed.wrapping_add(k)) {
+        match generate_with(&GenConfig { seed, ..config.clone() }) {
+            Some(puzzle) => {
+                print!("{}", write_puzzle(&puzzle, output));
+
+                // 答えはただ 1 つなので, 解き直せば意図した解になる
+                if report {
+                    match solve_numberlink(&puzzle, &SolverConfig::default()) {
+                        Ok(sol) => eprintln!("seed {}: {}", seed, quality(&puzzle, &sol)),
+                        Err(e) => eprintln!("seed {}: {}", seed, e),
+                    }
+                }
+            },
+            None => {
+                let with = match config.target {
+                    Some(Target::Tier(difficulty)) => format!("at difficulty {}", difficulty),
+                    Some(Target::Score(score)) => format!("at score {}", score),
+                    None => format!("with {} pairs", config.pairs),
+                };
+
+                let symmetric = match config.symmetry {
+                    Symmetry::None => String::new(),
+                    symmetry => format!(" and {:?} symmetry", symmetry).to_lowercase(),
+                };
+
+                let quality = if config.min_quality == Default::default() && config.reject.is_empty() { "" } else { " and the filters" };
+
+                eprintln!("no puzzle with a unique solution found for {}x{} {}{}{} (seed {})", config.width, config.height, with, symmetric, quality, seed);
+                process::exit(EXIT_NO_SOLUTION);
+            },
+        }
     }
 }
 
-fn consume(index: &mut usize, i: &mut usize, j: &mut usize, width: usize, list: &Vec<char>) {
-    let length = list.len();
+// gen --from: 道に分けた答え (`-` なら stdin) から盤面を作り, 答えがただ 1 つかを stderr に書く
+fn run_from_paths(solution: &str, output: Output) {
+    let input = match solution {
+        "-" => io::read_to_string(io::stdin()).unwrap_or_else(|e| fail(&format!("stdin: {}", e))),
+        solution => solution.to_string(),
+    };
+    let puzzle = match parse_paths(&input).and_then(|(width, height, paths)| from_paths(width, height, &paths)) {
+        Some(puzzle) => puzzle,
+        None => fail("--from: invalid solution (expected each path as a chain of cells with the same name, or JSON paths)"),
+    };
+    // URL には穴を書けない
+    let output = if output == Output::Url && !puzzle.holes.is_empty() { Output::Text } else { output };
+
+    print!("{}", write_puzzle(&puzzle, output));
+
+    match Session::new(&puzzle, &SolverConfig::default()).and_then(|mut session| session.is_unique()) {
+        Ok(true) => eprintln!("unique"),
+        Ok(false) => {
+            eprintln!("not unique: the puzzle has another solution");
+            process::exit(EXIT_ERROR);
+        },
+        Err(e) => fail(&e.to_string()),
+    }
+}
 
-    while *index < length && !(list[*index as usize]).is_digit(16) {
-        let ch = list[*index as usize];
-        let value = (ch as i32) - ('f' as i32);
+// --seeds N / --seeds=N を取り除いて返す
+fn take_seeds(args: &mut Vec<String>) -> Result<Option<u64>, String> {
+    match take_value(args, "--seeds")? {
+        None => Ok(None),
+        Some(value) => match value.parse() {
+            Ok(seeds) if seeds > 0 => Ok(Some(seeds)),
+            _ => Err(format!("invalid number of seeds `{}`", value)),
+        },
+    }
+}
 
-        if value <= 0 {
-            return;
+// tile の --columns N と --shared
+fn take_tile(args: &mut Vec<String>) -> Result<(Option<usize>, bool), String> {
+    let shared = match args.iter().position(|arg| arg == "--shared") {
+        Some(pos) => {
+            args.remove(pos);
+            true
+        },
+        None => false,
+    };
+
+    match take_value(args, "--columns")? {
+        None => Ok((None, shared)),
+        Some(value) => match value.parse() {
+            Ok(columns) if columns > 0 => Ok((Some(columns), shared)),
+            _ => Err(format!("invalid number of columns `{}`", value)),
+        },
+    }
+}
+
+// crosscheck の --cmd CMD と --sat-cmd CMD を並んだ順に
+fn take_checkers(args: &mut Vec<String>) -> Result<Vec<Checker>, String> {
+    let mut checkers = vec![];
+
+    while let Some(pos) = args.iter().position(|arg| ["--cmd", "--sat-cmd"].iter().any(|name| arg == name || arg.starts_with(&format!("{}=", name)))) {
+        let name = if args[pos].starts_with("--cmd") { "--cmd" } else { "--sat-cmd" };
+        let command = take_value(args, name)?.unwrap();
+
+        checkers.push(match name {
+            "--cmd" => Checker::Numberlink(command),
+            _ => Checker::Sat(command),
+        });
+    }
+
+    Ok(checkers)
+}
+
+// --name VALUE か --name=VALUE を args から取り除いて VALUE を返す
+fn take_value(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let prefix = format!("{}=", name);
+    let pos = match args.iter().position(|arg| arg == name || arg.starts_with(&prefix)) {
+        Some(pos) => pos,
+        None => return Ok(None),
+    };
+
+    let arg = args.remove(pos);
+
+    match arg.strip_prefix(&prefix) {
+        Some(value) => Ok(Some(value.to_string())),
+        None if pos < args.len() => Ok(Some(args.remove(pos))),
+        None => Err(format!("{} needs a value", name)),
+    }
+}
+
+// 1 回目の Ctrl-C は探索を止めて統計を出す, 2 回目は即終了
+fn install_interrupt_handler() -> Arc<AtomicBool> {
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let flag = interrupt.clone();
+
+    let result = ctrlc::set_handler(move || {
+        if flag.swap(true, Ordering::SeqCst) {
+            process::exit(EXIT_INTERRUPTED);
+        }
+    });
+
+    if let Err(e) = result {
+        eprintln!("warning: cannot install Ctrl-C handler: {}", e);
+    }
+
+    interrupt
+}
+
+// SIGUSR1 を受けたときと interval ごとに, 探索を止めずに途中経過を 1 行出す
+fn spawn_status_reporter(progress: Arc<Progress>, interval: Option<Duration>) {
+    let request = Arc::new(AtomicBool::new(false));
+
+    #[cfg(unix)]
+    if let Err(e) = signal_hook::flag::register(signal_hook::consts::SIGUSR1, request.clone()) {
+        eprintln!("warning: cannot install SIGUSR1 handler: {}", e);
+    }
+
+    if cfg!(not(unix)) && interval.is_none() {
+        return;
+    }
+
+    let start = Instant::now();
+
+    thread::spawn(move || {
+        let mut next = interval.map(|interval| start+interval);
+
+        loop {
+            thread::sleep(STATUS_POLL);
+
+            let due = next.is_some_and(|next| Instant::now() >= next);
+
+            if !request.swap(false, Ordering::SeqCst) && !due {
+                continue;
+            }
+
+            if let (true, Some(interval)) = (due, interval) {
+                next = next.map(|next| next+interval);
+            }
+
+            let bound = progress.bound().map_or(String::new(), |bound| format!(", best {} turns", bound));
+
+            eprintln!("status: {:.1}s, {} conflicts{}", start.elapsed().as_secs_f64(), progress.conflicts(), bound);
         }
+    });
+}
+
+// 探索の途中の盤面を stderr に描き直し続ける. stop が立ったら最後のコマを描いて終わる
+fn spawn_live(puzzle: &Puzzle, config: &mut SolverConfig) -> (Arc<AtomicBool>, thread::JoinHandle<()>) {
+    let state = subscribe_live(puzzle, &mut config.events);
+    let stop = Arc::new(AtomicBool::new(false));
+    let (puzzle, done) = (puzzle.clone(), stop.clone());
+    let drawer = thread::spawn(move || {
+        let mut lines = 0;
+
+        loop {
+            let finished = done.load(Ordering::SeqCst);
+            let frame = render_live(&puzzle, &state.lock().unwrap());
+            let mut stderr = io::stderr().lock();
+
+            // 前のコマの先頭に戻って消してから描く
+            if lines > 0 {
+                let _ = write!(stderr, "\x1b[{}A\x1b[J", lines);
+            }
 
-        let value: usize = value as usize;
+            let _ = write!(stderr, "{}", frame);
+            let _ = stderr.flush();
 
-        *j += value;
+            lines = frame.lines().count();
 
-        if *j >= width {
-            *i += *j/width;
-            *j %= width;
+            if finished {
+                break;
+            }
+
+            thread::sleep(STATUS_POLL);
         }
+    });
 
-        *index += 1;
-    }    
+    (stop, drawer)
 }
 
-fn main() {
-    //let url = "http://pzv.jp/p.html?numlin/10/10/8t12g8l34j21zt76j45l3g67t5".to_string();
-    //let url = "http://pzv.jp/p.html?numlin/17/17/h5n3jajanfj3s-11j7k7m1kcj1m6k8jdsbx-10zkbxds8j5kcm4j6kfmek-10j4s9j9nej2j2n-11h".to_string();
-    let url = "https://puzz.link/p?numlin/42/25/zzi1zx5j3ve-1cv-13n6zp-2br2zl-2cvep8-1dp-29z-10x7zj-14t-1dzn-16j-1abj-20zr-19l-21zv-1bh-1fzg2h6hbh-11hch-17l-22h-24h-27h-2ah-24h-1cj3h7hch-12h-16h-1al-14h-21h-23h-29h-26h-2cj4h8hdh9h-17h-12l-1fh-25h-28h-2bh-27h-10zgah-20zv-1bl-23zr-18j-18fj-22zn-15t-26zj-13x-25zfp-15-19p-11vazl4r9zp-2an5vd-1ev-1ej1zx-28zzi".to_string();
-    //let opt_field = parse_url(url);
-    //let opt_field = parse_url("http://pzv.jp/p.html?numlin/12/12/1p3h9g3j4i2j5t5l87g6l6j2g7jbgbjal8g1czg9uahcp4".to_string());
-    
-    //println!("{:?}", opt_field);
+type Args = (Option<PathBuf>, Vec<(String, String)>, Vec<String>);
 
-    /*if let Some(field) = opt_field {
-        let sol = solve_numberlink(&field);
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut config_path = None;
+    let mut settings = vec![];
+    let mut urls = vec![];
+    let mut index = 0;
 
-        println!("{:?}", sol);
+    while index < args.len() {
+        let arg = &args[index];
+        index += 1;
 
-        //solve_numberlink(&vec![vec![1,0,0,1]]);
-        //solve_numberlink(&vec![vec![1,2,3], vec![0,2,0], vec![0,1,3]]);
-        //solve_numberlink(&vec![vec![1,0,1]]);
-        //solve_numberlink(&vec![vec![1,2], vec![0,0], vec![2,1]]);
-    }*/
+        if arg == "-h" || arg == "--help" {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
 
-    //let field = parse_url(url);
+        if let Some(jobs) = arg.strip_prefix("-j") {
+            let value = if jobs.is_empty() {
+                index += 1;
+                args.get(index-1).ok_or("-j needs a value")?.clone()
+            } else {
+                jobs.to_string()
+            };
+
+            settings.push(("jobs".to_string(), value));
+        } else if let Some(flag) = arg.strip_prefix("--") {
+            let (key, value) = match flag.find('=') {
+                Some(pos) => (flag[..pos].to_string(), flag[pos+1..].to_string()),
+                None => {
+                    let value = args.get(index).ok_or(format!("--{} needs a value", flag))?;
+                    index += 1;
+
+                    (flag.to_string(), value.clone())
+                }
+            };
 
-    //println!("{:?}", field);
-    
-    let sol = solve(url);
+            if key == "config" {
+                config_path = Some(PathBuf::from(value));
+            } else if KEYS.contains(&key.as_str()) {
+                settings.push((key, value));
+            } else {
+                return Err(format!("unknown option `--{}`", key));
+            }
+        } else {
+            urls.push(arg.clone());
+        }
+    }
+
+    if urls.len() > 1 && urls.iter().any(|url| url == "-") {
+        return Err("`-` cannot be combined with other URLs".to_string());
+    }
 
-    println!("{:?}", sol);
-}
\ No newline at end of file
+    Ok((config_path, settings, urls))
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(EXIT_ERROR);
+}
@@ -0,0 +1,150 @@
+use varisat::{ExtendFormula, Lit, Var};
+
+use crate::input::puzzlink;
+use crate::loops::{render_loop, solve_loop, Grid};
+use crate::render::Format;
+use crate::{Error, SolverConfig, P};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pearl {
+    // 白: まっすぐ通り抜け, 前後のセルの少なくとも一方で曲がる
+    White,
+    // 黒: そこで曲がり, 前後のセルはどちらもまっすぐ通り抜ける
+    Black,
+}
+
+// Masyu: セルの中心を結んで 1 本の輪を引き, 真珠のセルは全部通る (ほかのセルは通らなくてもよい)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Masyu {
+    pub width: usize,
+    pub height: usize,
+    // 行優先
+    pub pearls: Vec<Option<Pearl>>,
+}
+
+// puzz.link でのパズルの名前
+pub(crate) const NAMES: &[&str] = &["masyu", "mashu"];
+
+impl Masyu {
+    pub fn new(width: usize, height: usize) -> Masyu {
+        Masyu { width, height, pearls: vec![None; width*height] }
+    }
+
+    pub fn grid(&self) -> Grid {
+        Grid { rows: self.height, cols: self.width }
+    }
+}
+
+// puzz.link の masyu/W/H/CODE か, 行を `/`, セルを `,` で区切ったテキスト (`.` は空き, `o` は白, `x` は黒)
+pub fn parse_masyu(input: &str) -> Option<Masyu> {
+    match puzzlink(input, NAMES) {
+        Some((width, height, code)) => decode_masyu(width, height, code),
+        None => parse_masyu_text(input),
+    }
+}
+
+// 27 進の 1 文字に 3 セルずつ, 上の桁から 0 (空き), 1 (白), 2 (黒)
+fn decode_masyu(width: usize, height: usize, code: &str) -> Option<Masyu> {
+    let mut puzzle = Masyu::new(width, height);
+
+    if code.len() > (width*height).div_ceil(3) {
+        return None;
+    }
+
+    for (k, ch) in code.chars().enumerate() {
+        let digit = ch.to_digit(27)?;
+
+        for (w, tri) in [9, 3, 1].iter().enumerate() {
+            match (puzzle.pearls.get_mut(3*k+w), digit/tri%3) {
+                (Some(pearl), 1) => *pearl = Some(Pearl::White),
+                (Some(pearl), 2) => *pearl = Some(Pearl::Black),
+                _ => {},
+            }
+        }
+    }
+
+    Some(puzzle)
+}
+
+fn parse_masyu_text(input: &str) -> Option<Masyu> {
+    let rows: Vec<Vec<Option<Pearl>>> = input.trim().split(['/', '\n']).map(|row| {
+        row.trim().split(',').map(|cell| match cell.trim() {
+            "." => Some(None),
+            "o" => Some(Some(Pearl::White)),
+            "x" => Some(Some(Pearl::Black)),
+            _ => None,
+        }).collect::<Option<Vec<_>>>()
+    }).collect::<Option<Vec<_>>>()?;
+    let width = rows.first()?.len();
+
+    if rows.iter().any(|row| row.len() != width) {
+        return None;
+    }
+
+    Some(Masyu { width, height: rows.len(), pearls: rows.concat() })
+}
+
+// 答えの輪が通るセル (行, 列) を順に
+pub fn solve_masyu(puzzle: &Masyu, config: &SolverConfig) -> Result<Vec<P>, Error> {
+    if puzzle.width == 0 || puzzle.height == 0 || puzzle.pearls.len() != puzzle.width*puzzle.height {
+        return Err(Error::InvalidField);
+    }
+
+    let grid = puzzle.grid();
+
+    solve_loop(&grid, config, |formula| {
+        // p から d の向きへ出る辺と, その先の (同じ向きに続く) 辺. 盤外なら None (偽)
+        let out = |p: P, d: usize| grid.step(p, d).and_then(|q| grid.edge_var(p, q));
+        let next = |p: P, d: usize| grid.step(p, d).and_then(|q| out(q, d));
+
+        for (u, pearl) in puzzle.pearls.iter().enumerate() {
+            let p = (u/puzzle.width, u%puzzle.width);
+
+            match pearl {
+                Some(Pearl::White) => {
+                    // 横か縦にまっすぐ通り抜ける
+                    for d in 0..4 {
+                        add(formula, &[out(p, (d+2)%4)], &[out(p, d)]);
+                    }
+
+                    add(formula, &[out(p, 0), out(p, 1)], &[]);
+
+                    // 前後のどちらかで曲がる
+                    for d in 0..2 {
+                        add(formula, &[], &[out(p, d), next(p, d), next(p, d+2)]);
+                    }
+                },
+                Some(Pearl::Black) => {
+                    // 横と縦に 1 本ずつ出て, その先もまっすぐ
+                    for d in 0..2 {
+                        add(formula, &[out(p, d), out(p, d+2)], &[]);
+                        add(formula, &[], &[out(p, d), out(p, d+2)]);
+                    }
+
+                    for d in 0..4 {
+                        add(formula, &[next(p, d)], &[out(p, d)]);
+                    }
+                },
+                None => {},
+            }
+        }
+    })
+}
+
+// pos のどれかか, neg のどれかの否定. pos の None は偽なので除き, neg に None があれば節は常に真
+fn add<F: ExtendFormula>(formula: &mut F, pos: &[Option<Var>], neg: &[Option<Var>]) {
+    if neg.iter().any(Option::is_none) {
+        return;
+    }
+
+    formula.add_clause(&pos.iter().flatten().map(|x| x.positive()).chain(neg.iter().flatten().map(|x| x.negative())).collect::<Vec<Lit>>());
+}
+
+pub fn render_masyu(puzzle: &Masyu, cycle: &[P], format: Format) -> String {
+    render_loop(&puzzle.grid(), cycle, format, |(i, j)| match puzzle.pearls[i*puzzle.width+j] {
+        Some(Pearl::White) => "o".to_string(),
+        Some(Pearl::Black) => "x".to_string(),
+        None if cycle.contains(&(i, j)) => "+".to_string(),
+        None => ".".to_string(),
+    })
+}
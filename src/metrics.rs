@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::Error;
+
+// 所要時間のヒストグラムの上限 (秒). 最後の +Inf は書き出すときに足す
+const BUCKETS: [f64; 10] = [0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 60.0, 300.0];
+
+// serve の GET /metrics (Prometheus のテキスト形式) に出す数
+//
+//     numberlink_http_requests_total{path,status}        要求の数 (path は /jobs/{id} のような経路の形)
+//     numberlink_http_request_duration_seconds{path}     要求に答えるまでの時間
+//     numberlink_solves_total{result}                    解いた数. result は sat, unsat, timeout, interrupted か error
+//     numberlink_solve_duration_seconds                  解くのにかかった時間
+//     numberlink_cache_requests_total{result}            覚えた答えを探した数. result は hit か miss
+//     numberlink_jobs_waiting                            列で待っている仕事の数
+#[derive(Debug, Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    requests: BTreeMap<(String, u16), u64>,
+    request_durations: BTreeMap<String, Histogram>,
+    solves: BTreeMap<&'static str, u64>,
+    solve_durations: Histogram,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    // BUCKETS のそれぞれ以下だった数 (累積ではない)
+    counts: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+
+        if let Some(k) = BUCKETS.iter().position(|&le| secs <= le) {
+            self.counts[k] += 1;
+        }
+
+        self.count += 1;
+        self.sum += secs;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        let mut total = 0;
+
+        for (le, count) in BUCKETS.iter().zip(&self.counts) {
+            total += count;
+            let _ = writeln!(out, "{}_bucket{{{}{}le=\"{}\"}} {}", name, labels, sep, le, total);
+        }
+
+        let _ = writeln!(out, "{}_bucket{{{}{}le=\"+Inf\"}} {}", name, labels, sep, self.count);
+        let braces = if labels.is_empty() { String::new() } else { format!("{{{}}}", labels) };
+        let _ = writeln!(out, "{}_sum{} {}", name, braces, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, braces, self.count);
+    }
+}
+
+impl Metrics {
+    pub fn request(&self, path: &str, status: u16, elapsed: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+
+        *inner.requests.entry((path.to_string(), status)).or_default() += 1;
+        inner.request_durations.entry(path.to_string()).or_default().observe(elapsed);
+    }
+
+    // 解いた結果 (最適化なら最後の結果) の種類を数える
+    pub fn solved<T>(&self, res: &Result<T, Error>, elapsed: Duration) {
+        let result = match res {
+            Ok(_) => "sat",
+            Err(Error::NoSolution) => "unsat",
+            Err(Error::Timeout(_)) | Err(Error::MemoryLimit(_)) => "timeout",
+            Err(Error::Interrupted(_)) => "interrupted",
+            Err(_) => "error",
+        };
+        let mut inner = self.inner.lock().unwrap();
+
+        *inner.solves.entry(result).or_default() += 1;
+        inner.solve_durations.observe(elapsed);
+    }
+
+    pub fn cached(&self, hit: bool) {
+        let mut inner = self.inner.lock().unwrap();
+
+        match hit {
+            true => inner.hits += 1,
+            false => inner.misses += 1,
+        }
+    }
+
+    pub fn render(&self, waiting: usize) -> String {
+        let inner = self.inner.lock().unwrap();
+        let mut out = String::new();
+
+        out += "# HELP numberlink_http_requests_total HTTP requests by route and status.\n";
+        out += "# TYPE numberlink_http_requests_total counter\n";
+        for ((path, status), count) in &inner.requests {
+            let _ = writeln!(out, "numberlink_http_requests_total{{path=\"{}\",status=\"{}\"}} {}", path, status, count);
+        }
+
+        out += "# HELP numberlink_http_request_duration_seconds Time to answer an HTTP request.\n";
+        out += "# TYPE numberlink_http_request_duration_seconds histogram\n";
+        for (path, histogram) in &inner.request_durations {
+            histogram.render(&mut out, "numberlink_http_request_duration_seconds", &format!("path=\"{}\"", path));
+        }
+
+        out += "# HELP numberlink_solves_total Puzzles solved by result.\n";
+        out += "# TYPE numberlink_solves_total counter\n";
+        for result in ["sat", "unsat", "timeout", "interrupted", "error"] {
+            let _ = writeln!(out, "numberlink_solves_total{{result=\"{}\"}} {}", result, inner.solves.get(result).unwrap_or(&0));
+        }
+
+        out += "# HELP numberlink_solve_duration_seconds Time spent solving a puzzle.\n";
+        out += "# TYPE numberlink_solve_duration_seconds histogram\n";
+        inner.solve_durations.render(&mut out, "numberlink_solve_duration_seconds", "");
+
+        out += "# HELP numberlink_cache_requests_total Lookups of cached solutions by result.\n";
+        out += "# TYPE numberlink_cache_requests_total counter\n";
+        let _ = writeln!(out, "numberlink_cache_requests_total{{result=\"hit\"}} {}", inner.hits);
+        let _ = writeln!(out, "numberlink_cache_requests_total{{result=\"miss\"}} {}", inner.misses);
+
+        out += "# HELP numberlink_jobs_waiting Jobs queued and not yet started.\n";
+        out += "# TYPE numberlink_jobs_waiting gauge\n";
+        let _ = writeln!(out, "numberlink_jobs_waiting {}", waiting);
+
+        out
+    }
+}
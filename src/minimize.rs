@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+
+use crate::{Error, Puzzle, Session, SolverConfig, Topology};
+
+// 答えがただ 1 つのまま, 手がかりのない端の行と列を削り, 数字の組を 1 組ずつ消していく
+// どちらもできなくなったら, 残った数字を小さい順に 1, 2, ... と振り直して返す
+// 消す順は決まっている (上, 下, 左, 右の端, 数字の小さい組) ので, 同じ盤面からは同じ結果になる
+// 元の盤面の答えがただ 1 つでなければ None
+pub fn minimize(puzzle: &Puzzle, config: &SolverConfig) -> Result<Option<Puzzle>, Error> {
+    if !is_unique(puzzle, config)? {
+        return Ok(None);
+    }
+
+    let mut current = puzzle.clone();
+
+    'trim: loop {
+        for next in candidates(&current) {
+            if is_unique(&next, config)? {
+                current = next;
+                continue 'trim;
+            }
+        }
+
+        return Ok(Some(relabeled(&current)));
+    }
+}
+
+// 1 手で小さくした盤面: 削れる端の行と列, そのあと消せる数字の組
+fn candidates(puzzle: &Puzzle) -> Vec<Puzzle> {
+    let (width, height) = (puzzle.width, puzzle.height);
+    let mut res: Vec<Puzzle> = [(1, 0, 0, 0), (0, 1, 0, 0), (0, 0, 1, 0), (0, 0, 0, 1)].iter()
+        .filter_map(|&(top, bottom, left, right)| cropped(puzzle, top, height.checked_sub(top+bottom)?, left, width.checked_sub(left+right)?))
+        .collect();
+
+    // 点の盤面の点と, 通る点に数字が指定された組は消さない
+    if !puzzle.dots {
+        let mut labels: Vec<usize> = puzzle.cells.iter().cloned().filter(|&num| num > 0).collect();
+
+        labels.sort_unstable();
+        labels.dedup();
+
+        for num in labels.into_iter().filter(|num| !puzzle.waypoints.values().any(|w| w == num)) {
+            let mut next = puzzle.clone();
+
+            for cell in next.cells.iter_mut().filter(|cell| **cell == num) {
+                *cell = 0;
+            }
+
+            next.limits.remove(&num);
+            res.push(next);
+        }
+    }
+
+    res
+}
+
+// rows 行 cols 列を top 行 left 列から切り出す. 削るところに数字や橋があるか,
+// 切り出すと意味が変わるもの (ワープ, 層, 形の外, 通る点, 一方通行, コスト) がある盤面は None
+fn cropped(puzzle: &Puzzle, top: usize, rows: usize, left: usize, cols: usize) -> Option<Puzzle> {
+    let plain = puzzle.topology == Topology::Square && puzzle.warps.is_empty() && puzzle.vias.is_empty() && puzzle.outside.is_empty()
+        && puzzle.waypoints.is_empty() && puzzle.one_way.is_empty() && puzzle.costs.is_empty() && puzzle.budget.is_none();
+
+    if !plain || rows == 0 || cols == 0 {
+        return None;
+    }
+
+    let inside = |u: usize| {
+        let (i, j) = puzzle.pos(u);
+
+        (top..top+rows).contains(&i) && (left..left+cols).contains(&j)
+    };
+    let index = |u: usize| {
+        let (i, j) = puzzle.pos(u);
+
+        (i-top)*cols + j-left
+    };
+
+    if (0..puzzle.cells.len()).any(|u| !inside(u) && (puzzle.cells[u] > 0 || puzzle.is_bridge(u))) {
+        return None;
+    }
+
+    let mut res = Puzzle { limits: puzzle.limits.clone(), dots: puzzle.dots, ..Puzzle::new(cols, rows) };
+
+    for u in (0..puzzle.cells.len()).filter(|&u| inside(u)) {
+        res.cells[index(u)] = puzzle.cells[u];
+    }
+
+    res.walls = puzzle.walls.iter().filter(|&&(u, v)| inside(u) && inside(v)).map(|&(u, v)| (index(u), index(v))).collect();
+    res.holes = puzzle.holes.iter().cloned().filter(|&u| inside(u)).map(index).collect();
+    res.bridges = puzzle.bridges.iter().cloned().map(index).collect();
+
+    Some(res)
+}
+
+fn relabeled(puzzle: &Puzzle) -> Puzzle {
+    if puzzle.dots {
+        return puzzle.clone();
+    }
+
+    let mut labels: Vec<usize> = puzzle.cells.iter().cloned().filter(|&num| num > 0).collect();
+
+    labels.sort_unstable();
+    labels.dedup();
+
+    let label: BTreeMap<usize, usize> = labels.into_iter().enumerate().map(|(k, num)| (num, k+1)).collect();
+    let mut res = puzzle.clone();
+
+    for cell in res.cells.iter_mut().filter(|cell| **cell > 0) {
+        *cell = label[cell];
+    }
+
+    res.limits = puzzle.limits.iter().map(|(num, &limit)| (label[num], limit)).collect();
+    res.waypoints = puzzle.waypoints.iter().map(|(&u, &num)| (u, if num > 0 { label[&num] } else { 0 })).collect();
+
+    res
+}
+
+// 解けない盤面と, 数字の組がなくなって読めない盤面もただ 1 つではないとする
+fn is_unique(puzzle: &Puzzle, config: &SolverConfig) -> Result<bool, Error> {
+    match Session::new(puzzle, config).and_then(|mut session| session.is_unique()) {
+        Err(Error::NoSolution) | Err(Error::InvalidField) => Ok(false),
+        res => res,
+    }
+}
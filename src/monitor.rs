@@ -0,0 +1,284 @@
+use std::error;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc as Shared;
+use std::sync::{Mutex, OnceLock};
+
+use crate::events::{Event, Events};
+use crate::{Instant, Interrupt, Progress};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Timeout,
+    Interrupted,
+    MemoryLimit,
+    // backend 自体が失敗した (外部のソルバが落ちたなど)
+    Failed,
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Timeout => write!(f, "deadline exceeded"),
+            Stop::Interrupted => write!(f, "interrupted"),
+            Stop::MemoryLimit => write!(f, "memory limit exceeded"),
+            Stop::Failed => write!(f, "solver failed"),
+        }
+    }
+}
+
+impl error::Error for Stop {}
+
+impl Stop {
+    // ErrorKind::Interrupted は BufWriter が再試行してしまうので使わない
+    pub fn of(err: &io::Error) -> Option<Stop> {
+        err.get_ref().and_then(|e| e.downcast_ref::<Stop>()).cloned()
+    }
+}
+
+// 探索の外から読み書きする状態 (Session が持ち, 問い合わせごとに期限を設定し直す)
+#[derive(Debug, Default)]
+pub struct Control {
+    pub deadline: Mutex<Option<Instant>>,
+    pub interrupts: Vec<Interrupt>,
+    pub conflicts: AtomicU64,
+    // 節データベースの見積もり (バイト) と上限
+    pub memory: AtomicU64,
+    pub memory_limit: Option<u64>,
+    pub progress: Option<Shared<Progress>>,
+    pub events: Events,
+    // Session がソルバに渡すとき極性を反転した変数 (Fixed を符号化の値に戻す)
+    pub flips: OnceLock<Vec<bool>>,
+}
+
+impl Control {
+    // 探索を止めるべきならその理由
+    pub fn check(&self) -> Option<Stop> {
+        if self.interrupts.iter().any(|interrupt| interrupt.load(Ordering::Relaxed)) {
+            return Some(Stop::Interrupted);
+        }
+
+        if let Some(deadline) = *self.deadline.lock().unwrap() {
+            if Instant::now() >= deadline {
+                return Some(Stop::Timeout);
+            }
+        }
+
+        match self.memory_limit {
+            Some(limit) if self.memory.load(Ordering::Relaxed) > limit => Some(Stop::MemoryLimit),
+            _ => None,
+        }
+    }
+
+    pub fn add_conflicts(&self, conflicts: u64) {
+        let before = self.conflicts.fetch_add(conflicts, Ordering::Relaxed);
+
+        if let Some(progress) = &self.progress {
+            progress.add_conflicts(conflicts);
+        }
+
+        if !self.events.is_empty() {
+            for k in 1..=conflicts {
+                self.events.publish(&Event::Conflict { conflicts: before+k });
+            }
+        }
+    }
+
+    // ソルバの変数 var が value に決まった
+    pub fn fix(&self, var: usize, value: bool) {
+        let flipped = self.flips.get().is_some_and(|flips| flips.get(var).cloned().unwrap_or(false));
+
+        self.events.publish(&Event::Fixed { var, value: value != flipped });
+    }
+}
+
+// varisat の節 1 つあたりのヘッダと watch, リテラル 1 つ, 変数 1 つあたりのおおよそのバイト数
+const CLAUSE_BYTES: u64 = 32;
+const LIT_BYTES: u64 = 4;
+const VAR_BYTES: u64 = 64;
+
+pub fn formula_memory(clauses: u64, lits: u64, vars: u64) -> u64 {
+    clauses*CLAUSE_BYTES + lits*LIT_BYTES + vars*VAR_BYTES
+}
+
+// 証明の書き出し先として渡し, 期限切れか中断要求があれば書き込みを失敗させて探索を止める
+// ついでに varisat 形式の証明から学習節 (AtClause) の step を数えて conflict 数とする
+// (DRAT 形式は assumption と解いた後の節追加に対応していないので使えない)
+// 出来事の受け取る側がいれば, 単位節から決まった変数も知らせる (varisat は再始動を証明に書かないので, 再始動は知らせない)
+pub struct Monitor {
+    control: Shared<Control>,
+    num: [u8; 8],
+    num_len: usize,
+    num_read: usize,
+    expect: Vec<Expect>,
+    // 証明の変数 (global) からソルバに渡した変数 (user) への対応
+    users: Vec<Option<usize>>,
+}
+
+// step の残りのフィールド (スタックなので逆順に積む)
+#[derive(Debug, Clone, Copy)]
+enum Expect {
+    // 数を n 個読み飛ばす
+    Skip(u64),
+    // 長さ k を読み, 続く k*n 個を読み飛ばす
+    List(u64),
+    // 節のリテラル列 (true なら追加, false なら削除としてメモリの見積もりに反映する)
+    Clause(bool),
+    // 変数の名前の変更 (global, user) の global と, 読んだ global に続く user
+    UserVarName,
+    UserVar(u64),
+    // 値の決まったリテラル (長さ 1 の学習節)
+    Unit,
+    // 単位節の列の長さと, 残り k 組の (リテラル, ハッシュ)
+    Units,
+    UnitPairs(u64),
+}
+
+const USER_VAR_NAME_UPDATE: u64 = 2;
+const AT_CLAUSE_RED: u64 = 7;
+const AT_CLAUSE_IRRED: u64 = 8;
+const UNIT_CLAUSES: u64 = 9;
+
+impl Monitor {
+    pub fn new(control: Shared<Control>) -> Self {
+        Monitor { control, num: [0; 8], num_len: 0, num_read: 0, expect: vec![], users: vec![] }
+    }
+
+    // 証明のリテラルの値が決まった
+    fn fix(&mut self, code: u64) {
+        let global = (code >> 1) as usize;
+
+        if let Some(&Some(user)) = self.users.get(global) {
+            self.control.fix(user, code&1 == 0);
+        }
+    }
+
+    // varisat の可変長整数: 先頭バイトの最下位の 1 の位置で長さが決まる (0 なら 9 バイト以上)
+    fn feed(&mut self, byte: u8) {
+        if self.num_read < 8 {
+            self.num[self.num_read] = byte;
+        }
+
+        self.num_read += 1;
+
+        if self.num_read == 1 {
+            self.num_len = if byte == 0 { 0 } else { byte.trailing_zeros() as usize+1 };
+        } else if self.num_read == 2 && self.num_len == 0 {
+            self.num_len = if byte&1 == 1 { 9 } else { 10 };
+        }
+
+        if self.num_read < self.num_len || self.num_len == 0 {
+            return;
+        }
+
+        self.num_read = 0;
+
+        // 9 バイト以上の値は終端コードかハッシュなので値は要らない
+        let value = if self.num_len <= 8 {
+            let mut bytes = [0; 8];
+            bytes[..self.num_len].copy_from_slice(&self.num[..self.num_len]);
+
+            u64::from_le_bytes(bytes) >> self.num_len
+        } else {
+            u64::MAX
+        };
+
+        self.step(value)
+    }
+
+    fn step(&mut self, value: u64) {
+        match self.expect.pop() {
+            Some(Expect::Skip(n)) => {
+                if n > 1 {
+                    self.expect.push(Expect::Skip(n-1));
+                }
+            },
+            Some(Expect::List(n)) => {
+                if value*n > 0 {
+                    self.expect.push(Expect::Skip(value*n));
+                }
+            },
+            Some(Expect::Clause(added)) => {
+                let bytes = formula_memory(1, value, 0);
+
+                if added {
+                    self.control.memory.fetch_add(bytes, Ordering::Relaxed);
+                } else {
+                    let _ = self.control.memory.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |memory| Some(memory.saturating_sub(bytes)));
+                }
+
+                if added && value == 1 && !self.control.events.is_empty() {
+                    self.expect.push(Expect::Unit);
+                } else if value > 0 {
+                    self.expect.push(Expect::Skip(value));
+                }
+            },
+            Some(Expect::UserVarName) => self.expect.push(Expect::UserVar(value)),
+            Some(Expect::UserVar(global)) => {
+                let global = global as usize;
+
+                if self.users.len() <= global {
+                    self.users.resize(global+1, None);
+                }
+
+                self.users[global] = Some(value as usize);
+            },
+            Some(Expect::Unit) => self.fix(value),
+            Some(Expect::Units) => {
+                if value > 0 {
+                    self.expect.push(Expect::UnitPairs(value));
+                }
+            },
+            Some(Expect::UnitPairs(k)) => {
+                self.fix(value);
+
+                if k > 1 {
+                    self.expect.push(Expect::UnitPairs(k-1));
+                }
+
+                // リテラルに続くハッシュ
+                self.expect.push(Expect::Skip(1));
+            },
+            None => {
+                let events = !self.control.events.is_empty();
+                let fields: &[Expect] = match value {
+                    USER_VAR_NAME_UPDATE if events => &[Expect::UserVarName],
+                    0 | 2 => &[Expect::Skip(2)],
+                    1 | 3..=6 | 13 => &[Expect::Skip(1)],
+                    AT_CLAUSE_RED | AT_CLAUSE_IRRED => &[Expect::Clause(true), Expect::List(1)],
+                    17 => &[Expect::List(1), Expect::List(1)],
+                    UNIT_CLAUSES if events => &[Expect::Units],
+                    UNIT_CLAUSES => &[Expect::List(2)],
+                    10..=12 => &[Expect::Clause(false)],
+                    14..=16 => &[Expect::List(1)],
+                    _ => &[],
+                };
+
+                self.expect.extend(fields.iter().rev());
+
+                if value == AT_CLAUSE_RED || value == AT_CLAUSE_IRRED {
+                    self.control.add_conflicts(1);
+                }
+            },
+        }
+    }
+}
+
+impl Write for Monitor {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(stop) = self.control.check() {
+            return Err(io::Error::other(stop));
+        }
+
+        for &byte in buf {
+            self.feed(byte);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use varisat::{ExtendFormula, Lit, Var};
+
+use crate::{Arc, Puzzle, Sol, P};
+
+// 3 回以上現れる数字 (ネット) のつなぎ方
+// 向きと次数は節で書き, 全部のセルがつながっていることは解いたあとに確かめて, 切れていれば節を足して解き直す
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Nets {
+    // 全部のセルを 1 本の線で通る (端はどのセルでもよい)
+    #[default]
+    Path,
+    // 最初に現れたセルを根とする木で全部のセルをつなぐ (枝分かれしてよい)
+    Tree,
+}
+
+impl FromStr for Nets {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(Nets::Path),
+            "tree" => Ok(Nets::Tree),
+            _ => Err(format!("unknown nets `{}` (expected path or tree)", s)),
+        }
+    }
+}
+
+// 3 回以上現れる数字
+pub fn net_numbers(puzzle: &Puzzle) -> Vec<usize> {
+    let mut cnt: HashMap<usize, usize> = HashMap::new();
+
+    for (u, &num) in puzzle.cells.iter().enumerate() {
+        if num > 0 && !puzzle.is_hole(u) {
+            *cnt.entry(num).or_default() += 1;
+        }
+    }
+
+    let mut nums: Vec<usize> = cnt.into_iter().filter(|&(_, c)| c >= 3).map(|(num, _)| num).collect();
+
+    nums.sort_unstable();
+    nums
+}
+
+// ネットのセル (木の根を除く). 木なら 1 本入って何本出てもよく, 線なら入るか出るかのどちらかがある
+pub(crate) fn encode_terminal<F: ExtendFormula>(formula: &mut F, nets: Nets, outs: &[Lit], ins: &[Lit]) {
+    match nets {
+        Nets::Path => formula.add_clause(&outs.iter().chain(ins).cloned().collect::<Vec<Lit>>()),
+        Nets::Tree => formula.add_clause(ins),
+    }
+}
+
+// 線のネットでは, 入る arc のないセル (線の端) がネットごとにちょうど 1 つ
+// ins[u] はセル u に入る arc. 使った変数の次の番号を返す
+pub(crate) fn encode_ends<F: ExtendFormula>(formula: &mut F, puzzle: &Puzzle, ins: &[Vec<Lit>], mut next_var: usize) -> usize {
+    for num in net_numbers(puzzle) {
+        let mut ends = vec![];
+
+        for u in (0..puzzle.cells.len()).filter(|&u| puzzle.cells[u] == num) {
+            let s = Var::from_index(next_var);
+
+            next_var += 1;
+
+            // s <-> 入る arc がない
+            for &x in &ins[u] {
+                formula.add_clause(&[s.negative(), !x]);
+            }
+
+            formula.add_clause(&ins[u].iter().cloned().chain([s.positive()]).collect::<Vec<Lit>>());
+
+            ends.push(s);
+        }
+
+        formula.add_clause(&ends.iter().map(|s| s.positive()).collect::<Vec<Lit>>());
+
+        for (k, s) in ends.iter().enumerate() {
+            for t in &ends[k+1..] {
+                formula.add_clause(&[s.negative(), t.negative()]);
+            }
+        }
+    }
+
+    next_var
+}
+
+// ネットのセルから入ってくる arc を逆にたどって, 端に着かずに同じ arc に戻るもの (端とつながっていない輪) を返す
+// どのセルも入る arc は高々 1 本 (橋は軸ごとに 1 本) なので, 輪に入ったまま端に着かないことはこれで全部見つかる
+// 返した arc を全部使う解はないので, 呼ぶ側はそれぞれを禁止する節を足して解き直す
+pub(crate) fn cuts(puzzle: &Puzzle, sol: &Sol) -> Vec<Vec<Arc>> {
+    let nums = net_numbers(puzzle);
+
+    if nums.is_empty() {
+        return vec![];
+    }
+
+    let mut ins: HashMap<P, Vec<P>> = HashMap::new();
+
+    for &(u, v) in sol {
+        ins.entry(v).or_default().push(u);
+    }
+
+    let mut res: Vec<Vec<Arc>> = vec![];
+
+    for t in (0..puzzle.cells.len()).filter(|&u| nums.contains(&puzzle.cells[u])).map(|u| puzzle.pos(u)) {
+        if let Some(chain) = back_chain(puzzle, &ins, t) {
+            if !res.contains(&chain) {
+                res.push(chain);
+            }
+        }
+    }
+
+    res
+}
+
+// t に入ってくる arc を逆にたどり, 同じ arc に戻ったらそこまでの arc (端に着いたら None)
+pub(crate) fn back_chain(puzzle: &Puzzle, ins: &HashMap<P, Vec<P>>, t: P) -> Option<Vec<Arc>> {
+    let mut chain: Vec<Arc> = vec![];
+    let mut seen: HashSet<Arc> = HashSet::new();
+    let mut v = t;
+    let mut next: Option<P> = None;
+
+    while let Some(us) = ins.get(&v) {
+        // 橋は出ていく向きとまっすぐ入ってくる arc
+        let parent = match next {
+            Some(w) if us.len() > 1 => *us.iter().find(|&&u| puzzle.direction(u, v) == puzzle.direction(v, w))?,
+            _ => us[0],
+        };
+
+        if !seen.insert((parent, v)) {
+            return Some(chain);
+        }
+
+        chain.push((parent, v));
+        next = Some(v);
+        v = parent;
+    }
+
+    None
+}
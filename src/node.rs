@@ -0,0 +1,101 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+use crate::generate::{generate as generate_puzzle, write_puzzle, Output};
+use crate::render::{paths, render, render_puzzle_json, Format, Theme};
+use crate::{parse_puzzle, solve_numberlink, Error as SolveError, Puzzle, Sol, SolverConfig};
+
+// Node からは require("numberlink-solver") として使う (node/ で npm run build すると numberlink_solver.node と型定義 index.d.ts ができる)
+// 関数名は JS の流儀の camelCase になる (solve_async は solveAsync). 解けない, 読めないときは Error を投げる
+
+// 数字 number の道. cells は端から端への [行, 列] の列
+#[napi(object)]
+pub struct Path {
+    pub number: u32,
+    pub cells: Vec<Vec<u32>>,
+}
+
+#[napi(object)]
+pub struct Solution {
+    pub width: u32,
+    pub height: u32,
+    pub paths: Vec<Path>,
+    // solve --format text と同じ盤面
+    pub text: String,
+}
+
+// input は CLI と同じ puzz.link の URL, テキストか JSON の盤面
+#[napi]
+pub fn solve(input: String) -> Result<Solution> {
+    let puzzle = read(&input)?;
+    let sol = solve_numberlink(&puzzle, &SolverConfig::default()).map_err(failure)?;
+
+    Ok(solution(&puzzle, &sol))
+}
+
+// solve を libuv のスレッドで解き, Promise<Solution> を返す (サーバのイベントループを止めない)
+#[napi]
+pub fn solve_async(input: String) -> Result<AsyncTask<SolveTask>> {
+    Ok(AsyncTask::new(SolveTask { puzzle: read(&input)? }))
+}
+
+pub struct SolveTask {
+    puzzle: Puzzle,
+}
+
+#[napi]
+impl Task for SolveTask {
+    type Output = Sol;
+    type JsValue = Solution;
+
+    fn compute(&mut self) -> Result<Sol> {
+        solve_numberlink(&self.puzzle, &SolverConfig::default()).map_err(failure)
+    }
+
+    fn resolve(&mut self, _env: Env, sol: Sol) -> Result<Solution> {
+        Ok(solution(&self.puzzle, &sol))
+    }
+}
+
+// 盤面を書き直す. format は url, text, json か svg
+#[napi]
+pub fn convert(input: String, format: String) -> Result<String> {
+    write(&read(&input)?, &format)
+}
+
+// 答えがただ 1 つの盤面を作り, format (既定は url. 穴のある盤面は URL に書けないので text) で返す
+#[napi]
+pub fn generate(width: u32, height: u32, pairs: u32, seed: Option<u32>, format: Option<String>) -> Result<String> {
+    let puzzle = generate_puzzle(width as usize, height as usize, pairs as usize, seed.unwrap_or(0) as u64)
+        .ok_or_else(|| Error::from_reason(format!("no {}x{} puzzle with {} pairs found", width, height, pairs)))?;
+    let format = format.unwrap_or_else(|| if puzzle.holes.is_empty() { "url" } else { "text" }.to_string());
+
+    write(&puzzle, &format)
+}
+
+fn read(input: &str) -> Result<Puzzle> {
+    parse_puzzle(input.trim()).ok_or_else(|| Error::new(Status::InvalidArg, SolveError::InvalidUrl.to_string()))
+}
+
+fn write(puzzle: &Puzzle, format: &str) -> Result<String> {
+    match format {
+        "url" => Ok(write_puzzle(puzzle, Output::Url)),
+        "text" => Ok(write_puzzle(puzzle, Output::Text)),
+        "json" => Ok(render_puzzle_json(puzzle)),
+        "svg" => Ok(write_puzzle(puzzle, Output::Svg)),
+        _ => Err(Error::new(Status::InvalidArg, format!("unknown format `{}` (expected url, text, json or svg)", format))),
+    }
+}
+
+fn failure(e: SolveError) -> Error {
+    Error::from_reason(e.to_string())
+}
+
+fn solution(puzzle: &Puzzle, sol: &Sol) -> Solution {
+    let paths = paths(puzzle, sol).into_iter().map(|(num, path)| Path {
+        number: num as u32,
+        cells: path.into_iter().map(|(i, j)| vec![i as u32, j as u32]).collect(),
+    }).collect();
+
+    Solution { width: puzzle.width as u32, height: puzzle.height as u32, paths, text: render(puzzle, sol, Format::Text, Theme::Plain) }
+}
@@ -0,0 +1,245 @@
+use serde_json::{json, Value};
+
+// serve の OpenAPI 3.0 の文書 (GET /openapi.json と serve --openapi). クライアントの SDK を作るため
+// 盤面と道は形がいくつもある (URL, テキスト, JSON) ので, 受け取る側は文字列かオブジェクトとだけ書く
+pub fn openapi() -> Value {
+    let json_body = |schema: Value| json!({ "required": true, "content": { "application/json": { "schema": schema } } });
+    let response = |description: &str, schema: &str| json!({
+        "description": description,
+        "content": { "application/json": { "schema": { "$ref": format!("#/components/schemas/{}", schema) } } },
+    });
+    let job_id = json!([{ "name": "id", "in": "path", "required": true, "schema": { "type": "string" } }]);
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "numberlink_solver",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Solve, check and generate Numberlink puzzles. The solver options are those given to `numberlink_solver serve`.",
+        },
+        // キーは serve --keys のときだけ要る
+        "security": [{}, { "bearer": [] }, { "header": [] }, { "query": [] }],
+        "paths": {
+            "/solve": {
+                "post": {
+                    "operationId": "solve",
+                    "summary": "Solve a puzzle",
+                    "requestBody": json_body(json!({ "$ref": "#/components/schemas/PuzzleRequest" })),
+                    "responses": {
+                        "200": response("The solution, as a line of --format json", "Solution"),
+                        "400": response("Unreadable puzzle", "Error"),
+                        "413": response("Board too large", "Error"),
+                        "422": response("No solution", "Error"),
+                        "503": response("Timeout or memory limit", "Error"),
+                    },
+                },
+            },
+            "/check": {
+                "post": {
+                    "operationId": "check",
+                    "summary": "Check a solution, or whether a puzzle is solvable and unique",
+                    "requestBody": json_body(json!({ "$ref": "#/components/schemas/CheckRequest" })),
+                    "responses": {
+                        "200": response("{\"valid\": ...} with a solution, {\"solvable\": ..., \"unique\": ...} without", "CheckResult"),
+                        "400": response("Unreadable puzzle or solution", "Error"),
+                        "413": response("Board too large", "Error"),
+                        "503": response("Timeout or memory limit", "Error"),
+                    },
+                },
+            },
+            "/generate": {
+                "post": {
+                    "operationId": "generate",
+                    "summary": "Generate a uniquely solvable puzzle",
+                    "requestBody": json_body(json!({ "$ref": "#/components/schemas/GenerateRequest" })),
+                    "responses": {
+                        "200": response("The puzzle as a URL (null for boards with holes) and as JSON", "Generated"),
+                        "400": response("Unreadable request or too many pairs", "Error"),
+                        "413": response("Board too large", "Error"),
+                        "422": response("No such puzzle found", "Error"),
+                    },
+                },
+            },
+            "/progress": {
+                "get": {
+                    "operationId": "progress",
+                    "summary": "WebSocket: send a JobRequest, receive ProgressEvent messages and a last result or error event",
+                    "responses": { "101": { "description": "Switching to the WebSocket protocol" } },
+                },
+            },
+            "/jobs": {
+                "post": {
+                    "operationId": "submitJob",
+                    "summary": "Queue a puzzle to solve",
+                    "requestBody": json_body(json!({ "$ref": "#/components/schemas/JobRequest" })),
+                    "responses": {
+                        "202": response("The job was queued", "JobId"),
+                        "503": response("Too many jobs waiting", "Error"),
+                    },
+                },
+            },
+            "/jobs/{id}": {
+                "get": {
+                    "operationId": "jobStatus",
+                    "summary": "The status of a job",
+                    "parameters": job_id,
+                    "responses": {
+                        "200": response("The job's status", "JobStatus"),
+                        "404": response("No such job", "Error"),
+                    },
+                },
+                "delete": {
+                    "operationId": "cancelJob",
+                    "summary": "Cancel a job",
+                    "parameters": job_id,
+                    "responses": {
+                        "200": response("The job's status after cancelling it", "JobStatus"),
+                        "404": response("No such job", "Error"),
+                    },
+                },
+            },
+            "/jobs/{id}/result": {
+                "get": {
+                    "operationId": "jobResult",
+                    "summary": "The result of a finished job",
+                    "parameters": job_id,
+                    "responses": {
+                        "200": response("The job has finished (done with a solution, failed with an error, or cancelled)", "JobStatus"),
+                        "202": response("The job is still queued or running", "JobStatus"),
+                        "404": response("No such job", "Error"),
+                    },
+                },
+            },
+            "/metrics": {
+                "get": {
+                    "operationId": "metrics",
+                    "summary": "Request and solve counters and durations for Prometheus",
+                    "responses": { "200": { "description": "The Prometheus text format", "content": { "text/plain": { "schema": { "type": "string" } } } } },
+                },
+            },
+        },
+        "components": {
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer", "description": "An API key of serve --keys" },
+                "header": { "type": "apiKey", "in": "header", "name": "X-API-Key" },
+                "query": { "type": "apiKey", "in": "query", "name": "key" },
+            },
+            "schemas": {
+                "Puzzle": {
+                    "description": "A puzz.link URL, a text board, or a JSON board",
+                    "oneOf": [{ "type": "string" }, { "type": "object" }],
+                },
+                "Paths": {
+                    "description": "Paths as text (one name per cell) or JSON paths (lists of [row, column] cells)",
+                    "oneOf": [{ "type": "string" }, { "type": "object" }],
+                },
+                "PuzzleRequest": {
+                    "oneOf": [
+                        { "$ref": "#/components/schemas/Puzzle" },
+                        { "type": "object", "required": ["puzzle"], "properties": { "puzzle": { "$ref": "#/components/schemas/Puzzle" } } },
+                    ],
+                },
+                "CheckRequest": {
+                    "type": "object",
+                    "required": ["puzzle"],
+                    "properties": {
+                        "puzzle": { "$ref": "#/components/schemas/Puzzle" },
+                        "solution": { "$ref": "#/components/schemas/Paths" },
+                    },
+                },
+                "GenerateRequest": {
+                    "type": "object",
+                    "required": ["width", "height", "pairs"],
+                    "properties": {
+                        "width": { "type": "integer", "minimum": 1 },
+                        "height": { "type": "integer", "minimum": 1 },
+                        "pairs": { "type": "integer", "minimum": 1 },
+                        "seed": { "type": "integer", "minimum": 0 },
+                    },
+                },
+                "JobRequest": {
+                    "oneOf": [
+                        { "$ref": "#/components/schemas/Puzzle" },
+                        {
+                            "type": "object",
+                            "required": ["puzzle"],
+                            "properties": {
+                                "puzzle": { "$ref": "#/components/schemas/Puzzle" },
+                                "objective": { "type": "string", "enum": ["none", "turns", "cost"] },
+                            },
+                        },
+                    ],
+                },
+                "Solution": {
+                    "type": "object",
+                    "properties": {
+                        "width": { "type": "integer" },
+                        "height": { "type": "integer" },
+                        "field": { "type": "array", "items": { "type": "array", "items": { "type": "integer" } } },
+                        "paths": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "number": { "type": "integer" },
+                                    "cells": { "type": "array", "items": { "type": "array", "items": { "type": "integer" } } },
+                                },
+                            },
+                        },
+                    },
+                },
+                "CheckResult": {
+                    "type": "object",
+                    "properties": {
+                        "valid": { "type": "boolean" },
+                        "reason": { "type": "string" },
+                        "solvable": { "type": "boolean" },
+                        "unique": { "type": "boolean" },
+                    },
+                },
+                "Generated": {
+                    "type": "object",
+                    "properties": {
+                        "url": { "type": "string", "nullable": true },
+                        "puzzle": { "type": "object" },
+                    },
+                },
+                "ProgressEvent": {
+                    "type": "object",
+                    "properties": {
+                        "event": { "type": "string", "enum": ["progress", "result", "error"] },
+                        "conflicts": { "type": "integer" },
+                        "bound": { "type": "integer", "nullable": true },
+                        "elapsed": { "type": "number" },
+                        "solution": { "$ref": "#/components/schemas/Solution" },
+                        "error": { "type": "string" },
+                    },
+                },
+                "JobId": {
+                    "type": "object",
+                    "properties": { "id": { "type": "string" } },
+                },
+                "JobStatus": {
+                    "type": "object",
+                    "required": ["id", "status"],
+                    "properties": {
+                        "id": { "type": "string" },
+                        "status": { "type": "string", "enum": ["queued", "running", "done", "failed", "cancelled"] },
+                        "position": { "type": "integer" },
+                        "conflicts": { "type": "integer" },
+                        "bound": { "type": "integer", "nullable": true },
+                        "solution": { "$ref": "#/components/schemas/Solution" },
+                        "turns": { "type": "integer" },
+                        "cost": { "type": "integer" },
+                        "optimal": { "type": "boolean" },
+                        "error": { "type": "string" },
+                    },
+                },
+                "Error": {
+                    "type": "object",
+                    "properties": { "error": { "type": "string" } },
+                },
+            },
+        },
+    })
+}
@@ -0,0 +1,304 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use varisat::{Lit, Var};
+
+use crate::backend::Backend;
+use crate::{gen_adjs, Arc, Error, Instant, Puzzle, Session, Sol, SolverConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    // 最初に見つかった解をそのまま返す
+    None,
+    // 曲がる回数を最小化する
+    Turns,
+    // 線が通るセルのコスト (Puzzle::costs) の合計を最小化する
+    Cost,
+}
+
+impl FromStr for Objective {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Objective::None),
+            "turns" => Ok(Objective::Turns),
+            "cost" => Ok(Objective::Cost),
+            _ => Err(format!("unknown objective `{}` (expected none, turns or cost)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Optimized {
+    pub sol: Sol,
+    pub turns: usize,
+    pub cost: usize,
+    // false なら時間切れで打ち切った途中の最良解
+    pub optimal: bool,
+}
+
+// 最良の値をとる解 (limit 個まで)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllOptimal {
+    pub best: Optimized,
+    pub sols: Vec<Sol>,
+    // false なら limit か時間切れで打ち切った (best.optimal も false なら最良かどうかも分からない)
+    pub complete: bool,
+}
+
+// 曲がる回数が最小の解を探す (anytime: config.timeout までに見つかった最良解を返す)
+// 解が 1 つも見つからないうちに時間切れになったときだけ Timeout を返す
+pub fn minimize_turns(puzzle: &Puzzle, config: &SolverConfig) -> Result<Optimized, Error> {
+    minimize_turns_from(puzzle, config, None, |_| {})
+}
+
+// start (checkpoint の解など) が解として正しければそこから上界を下げていき, 良い解が見つかるたびに on_improve を呼ぶ
+pub fn minimize_turns_from<F: FnMut(&Optimized)>(puzzle: &Puzzle, config: &SolverConfig, start: Option<&Sol>, mut on_improve: F) -> Result<Optimized, Error> {
+    // 整数計画では目的関数に入れて 1 回で解く (start は使わない)
+    if config.backend == Backend::Ilp {
+        let best = minimize_ilp(puzzle, config)?;
+
+        report(config, best.turns);
+        on_improve(&best);
+
+        return Ok(best);
+    }
+
+    minimize(puzzle, config, Objective::Turns, start, on_improve)
+}
+
+// 線が通るセルのコストの合計が最小の解を探す (minimize_turns と同じく anytime)
+pub fn minimize_cost(puzzle: &Puzzle, config: &SolverConfig) -> Result<Optimized, Error> {
+    if config.backend == Backend::Ilp {
+        return Err(Error::BackendFailed("the ilp backend does not minimize cost".to_string()));
+    }
+
+    minimize(puzzle, config, Objective::Cost, None, |_| {})
+}
+
+// objective の値を数える変数 (重みの分だけ並べる) の個数が今の最良より少ない解を探し直す
+fn minimize<F: FnMut(&Optimized)>(puzzle: &Puzzle, config: &SolverConfig, objective: Objective, start: Option<&Sol>, mut on_improve: F) -> Result<Optimized, Error> {
+    let deadline = config.timeout.map(|timeout| Instant::now()+timeout);
+    let mut session = Session::new(puzzle, config)?;
+    let counted = match objective {
+        Objective::Cost => cost_vars(puzzle, &mut session),
+        _ => turn_vars(puzzle, &mut session),
+    };
+    let value = |best: &Optimized| if objective == Objective::Cost { best.cost } else { best.turns };
+
+    let sol = match start {
+        Some(start) => {
+            let pins: Vec<(Arc, bool)> = start.iter().map(|&arc| (arc, true)).collect();
+
+            match session.solve_assuming(&pins) {
+                Ok(sol) => sol,
+                Err(Error::NoSolution) | Err(Error::InvalidPin) => session.solve()?,
+                Err(e) => return Err(e),
+            }
+        },
+        None => session.solve()?,
+    };
+
+    let mut best = optimized(puzzle, sol);
+
+    report(config, value(&best));
+    on_improve(&best);
+
+    if value(&best) == 0 {
+        best.optimal = true;
+
+        return Ok(best);
+    }
+
+    // 「k 個以上」を表す出力 (k <= 今の最良) だけ作り, ¬(最良以上) を仮定して解き直す
+    let at_least = sequential_counter(&mut session, &counted, value(&best));
+
+    loop {
+        session.set_timeout(deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1))));
+
+        match session.run(&[at_least[value(&best)-1].negative()]) {
+            Ok(sol) => {
+                best = optimized(puzzle, sol);
+
+                report(config, value(&best));
+                on_improve(&best);
+
+                if value(&best) == 0 {
+                    best.optimal = true;
+
+                    return Ok(best);
+                }
+            },
+            Err(Error::NoSolution) => {
+                best.optimal = true;
+
+                return Ok(best);
+            },
+            Err(Error::Timeout(_)) | Err(Error::Interrupted(_)) | Err(Error::MemoryLimit(_)) => return Ok(best),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// 最良の値を求めてから, その値の解を節で除きながらすべて探す. 作り手が「いちばんきれいな答え」が 1 つだけかを確かめるのに使う
+// config.timeout は全体の期限. 最良だと示せないうちに時間切れになったら, それまでの最良解だけを返す
+pub fn all_optimal(puzzle: &Puzzle, config: &SolverConfig, objective: Objective, limit: usize) -> Result<AllOptimal, Error> {
+    let deadline = config.timeout.map(|timeout| Instant::now()+timeout);
+    let best = match objective {
+        Objective::Turns => minimize_turns(puzzle, config)?,
+        Objective::Cost => minimize_cost(puzzle, config)?,
+        Objective::None => return Err(Error::InvalidConstraint("enumerating optimal solutions needs an objective".to_string())),
+    };
+
+    if !best.optimal {
+        return Ok(AllOptimal { sols: vec![best.sol.clone()], best, complete: false });
+    }
+
+    // 整数計画で最良を求めたときも, 数え上げは SAT で
+    let config = match config.backend {
+        Backend::Ilp => SolverConfig { backend: Backend::Auto, ..config.clone() },
+        _ => config.clone(),
+    };
+    let mut session = Session::new(puzzle, &config)?;
+    let (counted, value) = match objective {
+        Objective::Cost => (cost_vars(puzzle, &mut session), best.cost),
+        _ => (turn_vars(puzzle, &mut session), best.turns),
+    };
+
+    // 「value+1 個以上」でない解だけ
+    let at_most: Vec<Lit> = sequential_counter(&mut session, &counted, value+1).get(value).map(|x| x.negative()).into_iter().collect();
+    let mut sols = vec![];
+
+    loop {
+        session.set_timeout(deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1))));
+
+        match session.run(&at_most) {
+            Ok(_) if sols.len() == limit => return Ok(AllOptimal { best, sols, complete: false }),
+            Ok(sol) => {
+                session.exclude(&sol)?;
+                sols.push(sol);
+            },
+            Err(Error::NoSolution) => return Ok(AllOptimal { best, sols, complete: true }),
+            Err(Error::Timeout(_)) | Err(Error::Interrupted(_)) | Err(Error::MemoryLimit(_)) => return Ok(AllOptimal { best, sols, complete: false }),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(feature = "ilp")]
+fn minimize_ilp(puzzle: &Puzzle, config: &SolverConfig) -> Result<Optimized, Error> {
+    crate::ilp::minimize_turns_ilp(puzzle, config)
+}
+
+#[cfg(not(feature = "ilp"))]
+fn minimize_ilp(_: &Puzzle, _: &SolverConfig) -> Result<Optimized, Error> {
+    Err(Error::UnsupportedBackend("ilp".to_string()))
+}
+
+pub(crate) fn optimized(puzzle: &Puzzle, sol: Sol) -> Optimized {
+    Optimized { turns: count_turns(puzzle, &sol), cost: total_cost(puzzle, &sol), sol, optimal: false }
+}
+
+pub(crate) fn report(config: &SolverConfig, bound: usize) {
+    if let Some(progress) = &config.progress {
+        progress.set_bound(bound as u64);
+    }
+}
+
+// 線が出入りするセルのコストの合計
+pub fn total_cost(puzzle: &Puzzle, sol: &Sol) -> usize {
+    let cells: std::collections::BTreeSet<usize> = sol.iter().flat_map(|&(p, q)| [puzzle.index(p), puzzle.index(q)]).collect();
+
+    cells.iter().filter_map(|u| puzzle.costs.get(u)).sum()
+}
+
+// 橋からは 2 本出るが, どちらかは入ってきた向きのまま出ていく
+pub fn count_turns(puzzle: &Puzzle, sol: &Sol) -> usize {
+    let dir = |&(u, v): &Arc| puzzle.direction(u, v);
+    let mut outs: std::collections::HashMap<_, Vec<_>> = std::collections::HashMap::new();
+
+    for arc in sol {
+        outs.entry(arc.0).or_default().push(arc);
+    }
+
+    sol.iter().filter(|arc| outs.get(&arc.1).is_some_and(|outs| outs.iter().all(|out| dir(out) != dir(arc)))).count()
+}
+
+// セル u に入ってきた向きと出ていく向きが違えば t_u を真にする
+fn turn_vars(puzzle: &Puzzle, session: &mut Session) -> Vec<Var> {
+    let adjs = gen_adjs(puzzle);
+    let mut res = vec![];
+
+    for (u, vs) in adjs.iter().enumerate() {
+        let t = session.fresh_var();
+
+        // 橋はまっすぐ通るだけ
+        if puzzle.is_bridge(u) {
+            res.push(t);
+            continue;
+        }
+
+        for &(v, d_out) in vs {
+            for &(w, d) in vs {
+                // w から u へ入る arc の向きは d の逆
+                let d_in = puzzle.topology.opposite(d);
+
+                if w != v && d_in != d_out {
+                    session.add_clause(&[puzzle.topology.arc_var(w, d_in).negative(), puzzle.topology.arc_var(u, d_out).negative(), t.positive()]);
+                }
+            }
+        }
+
+        res.push(t);
+    }
+
+    res
+}
+
+// コスト c のセルに出入りする arc があれば x_u を真にして, x_u を c 個並べる
+fn cost_vars(puzzle: &Puzzle, session: &mut Session) -> Vec<Var> {
+    let adjs = gen_adjs(puzzle);
+    let topology = puzzle.topology;
+    let mut res = vec![];
+
+    for (&u, &cost) in puzzle.costs.iter().filter(|&(&u, &cost)| cost > 0 && u < puzzle.cells.len() && !puzzle.is_hole(u)) {
+        let x = session.fresh_var();
+
+        for &(v, d) in &adjs[u] {
+            session.add_clause(&[topology.arc_var(u, d).negative(), x.positive()]);
+            session.add_clause(&[topology.arc_var(v, topology.opposite(d)).negative(), x.positive()]);
+        }
+
+        res.extend(std::iter::repeat_n(x, cost));
+    }
+
+    res
+}
+
+// Sinz の sequential counter の上向きの含意だけ: r[j] は「xs のうち j+1 個以上が真」を下から押さえる
+pub(crate) fn sequential_counter(session: &mut Session, xs: &[Var], width: usize) -> Vec<Var> {
+    let mut prev: Vec<Var> = vec![];
+
+    for &x in xs {
+        let r: Vec<Var> = (0..width).map(|_| session.fresh_var()).collect();
+
+        session.add_clause(&[x.negative(), r[0].positive()]);
+
+        for (j, &rj) in r.iter().enumerate() {
+            if let Some(&p) = prev.get(j) {
+                session.add_clause(&[p.negative(), rj.positive()]);
+            }
+
+            if j > 0 {
+                if let Some(&p) = prev.get(j-1) {
+                    session.add_clause(&[x.negative(), p.negative(), rj.positive()]);
+                }
+            }
+        }
+
+        prev = r;
+    }
+
+    prev
+}
@@ -0,0 +1,150 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::str::FromStr;
+
+use crate::template::template;
+use crate::render::paths;
+use crate::{find_arc_var, gen_adjs, Encoding, Puzzle, Sol, SolverConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    // varisat のまま (最初はすべて偽から試す)
+    Default,
+    // 各数字の端点間の最短経路 (同じ長さなら曲がりの少ないもの) の arc と数字を真から試す
+    Corridors,
+}
+
+impl FromStr for Phase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Phase::Default),
+            "corridors" => Ok(Phase::Corridors),
+            _ => Err(format!("unknown phase heuristic `{}` (expected default or corridors)", s)),
+        }
+    }
+}
+
+// varisat には初期 phase を与える口がなく, 未割り当ての変数は前回の値 (最初は偽) から試すので,
+// 真から試したい変数は極性を反転して渡す. 変数番号ごとに反転するかどうかを返す
+// warm_start があればその解を (手がかりが変わっていても使える部分だけ) 優先する
+pub fn flips(puzzle: &Puzzle, config: &SolverConfig) -> Vec<bool> {
+    // 長さの手がかりは組の数字ではないので, 経路も数字も選べない
+    if config.rules.lengths || (config.phase == Phase::Default && config.warm_start.is_none()) || puzzle.width*puzzle.height == 0 || puzzle.cells.len() != puzzle.width*puzzle.height {
+        return vec![];
+    }
+
+    let n = puzzle.cells.iter().cloned().max().unwrap_or(0);
+    let template = template(puzzle, config);
+    let mut res = vec![false; template.next_var];
+
+    if let Some(sol) = &config.warm_start {
+        // 大きさを変える前の盤面の解なら, 今の盤面にある arc だけ
+        let sol: Sol = sol.iter().cloned().filter(|&arc| find_arc_var(puzzle, arc).is_some()).collect();
+
+        for &arc in &sol {
+            if let Some(x) = find_arc_var(puzzle, arc) {
+                res[x.index()] = true;
+            }
+        }
+
+        // 今の手がかりから始まる経路だけ数字が分かる
+        for (num, path) in paths(puzzle, &sol) {
+            for p in path {
+                prefer_number(&mut res, config.encoding, &template.numbers[puzzle.index(p)], num);
+            }
+        }
+
+        return res;
+    }
+
+    for num in 1..=n {
+        let ends: Vec<usize> = (0..puzzle.cells.len()).filter(|&u| puzzle.cells[u] == num).collect();
+
+        if ends.len() != 2 {
+            continue;
+        }
+
+        for (u, d, v) in corridor(puzzle, ends[0], ends[1]) {
+            res[puzzle.topology.arc_var(u, d).index()] = true;
+
+            for cell in [u, v] {
+                prefer_number(&mut res, config.encoding, &template.numbers[cell], num);
+            }
+        }
+    }
+
+    res
+}
+
+fn prefer_number(res: &mut [bool], encoding: Encoding, vars: &[usize], num: usize) {
+    match encoding {
+        Encoding::Log => {
+            for (b, &x) in vars.iter().enumerate() {
+                res[x] = (num-1)>>b&1 != 0;
+            }
+        },
+        Encoding::Direct => {
+            if let Some(&x) = vars.get(num-1) {
+                res[x] = true;
+            }
+        },
+    }
+}
+
+// 他の数字のセルを避けた s から t への経路の arc (セル, 向き, 行き先). (長さ, 曲がった回数) が最小のもの
+fn corridor(puzzle: &Puzzle, s: usize, t: usize) -> Vec<(usize, usize, usize)> {
+    let adjs = gen_adjs(puzzle);
+    let cells = puzzle.cells.len();
+
+    // 状態は (セル, 入ってきた向き), 始点だけ向き none (なし)
+    let none = puzzle.topology.slots();
+    let k = none+1;
+    let state = |u: usize, d: usize| u*k+d;
+    let mut dist = vec![(usize::MAX, usize::MAX); cells*k];
+    let mut prev = vec![usize::MAX; cells*k];
+    let mut heap = BinaryHeap::new();
+
+    dist[state(s, none)] = (0, 0);
+    heap.push(Reverse(((0, 0), state(s, none))));
+
+    while let Some(Reverse((cost, x))) = heap.pop() {
+        let (u, din) = (x/k, x%k);
+
+        if cost > dist[x] {
+            continue;
+        }
+
+        if u == t {
+            let mut res = vec![];
+            let mut x = x;
+
+            while prev[x] != usize::MAX {
+                res.push((prev[x]/k, x%k, x/k));
+                x = prev[x];
+            }
+
+            res.reverse();
+
+            return res;
+        }
+
+        for &(v, d) in &adjs[u] {
+            if v != t && puzzle.cells[v] != 0 {
+                continue;
+            }
+
+            let next = (cost.0+1, cost.1 + (din != none && din != d) as usize);
+            let y = state(v, d);
+
+            if next < dist[y] {
+                dist[y] = next;
+                prev[y] = x;
+                heap.push(Reverse((next, y)));
+            }
+        }
+    }
+
+    vec![]
+}
@@ -0,0 +1,123 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::tidy;
+use crate::{Encoding, Error, Interrupt, Puzzle, Session, Sol, SolverConfig};
+
+// base を元に符号化と seed を変えた n 通りの設定 (1 つ目は base そのもの)
+pub fn portfolio_configs(base: &SolverConfig, n: usize) -> Vec<SolverConfig> {
+    let encodings = [base.encoding, other_encoding(base.encoding)];
+
+    (0..n).map(|k| {
+        let mut config = base.clone();
+
+        config.encoding = encodings[k%2];
+
+        if k >= 2 {
+            config.seed = base.seed.wrapping_add(k as u64/2);
+        }
+
+        config
+    }).collect()
+}
+
+fn other_encoding(encoding: Encoding) -> Encoding {
+    match encoding {
+        Encoding::Log => Encoding::Direct,
+        Encoding::Direct => Encoding::Log,
+    }
+}
+
+// 全設定を並列に走らせ, 最初に SAT/UNSAT を確定させたものの結果を返して残りは打ち切る.
+// 勝った解は solve_numberlink と同じく回り道を縮める (規則はどの設定も同じ)
+pub fn solve_portfolio(puzzle: &Puzzle, configs: &[SolverConfig]) -> Result<Sol, Error> {
+    if configs.is_empty() {
+        return Err(Error::InvalidField);
+    }
+
+    let cancel: Interrupt = std::sync::Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for config in configs {
+            let tx = tx.clone();
+            let cancel = &cancel;
+
+            scope.spawn(move || {
+                let res = Session::with_cancel(puzzle, config, Some(cancel)).and_then(|mut session| session.solve());
+
+                if is_final(&res) {
+                    cancel.store(true, Ordering::SeqCst);
+                }
+
+                let _ = tx.send(res);
+            });
+        }
+
+        drop(tx);
+
+        let mut first = None;
+
+        for res in rx {
+            if is_final(&res) {
+                return res;
+            }
+
+            first.get_or_insert(res);
+        }
+
+        first.unwrap()
+    }).map(|sol| tidy::shorten(puzzle, &configs[0], &sol))
+}
+
+// 実行ごとに同じ結果を返す版: 確定した設定より後ろの設定だけを打ち切り, 前の設定がすべて終わるのを待つ
+// 結果は確定した中で最初の設定のもの (timeout で打ち切られたものがあると変わりうる)
+pub fn solve_portfolio_ordered(puzzle: &Puzzle, configs: &[SolverConfig]) -> Result<Sol, Error> {
+    if configs.is_empty() {
+        return Err(Error::InvalidField);
+    }
+
+    let cancels: Vec<Interrupt> = configs.iter().map(|_| std::sync::Arc::new(AtomicBool::new(false))).collect();
+    let (tx, rx) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for (k, (config, cancel)) in configs.iter().zip(&cancels).enumerate() {
+            let tx = tx.clone();
+
+            scope.spawn(move || {
+                let _ = tx.send((k, Session::with_cancel(puzzle, config, Some(cancel)).and_then(|mut session| session.solve())));
+            });
+        }
+
+        drop(tx);
+
+        let mut results: Vec<Option<Result<Sol, Error>>> = configs.iter().map(|_| None).collect();
+
+        for (k, res) in rx {
+            if is_final(&res) {
+                cancels[k+1..].iter().for_each(|cancel| cancel.store(true, Ordering::SeqCst));
+            }
+
+            results[k] = Some(res);
+
+            for res in &results {
+                match res {
+                    Some(res) if is_final(res) => {
+                        cancels.iter().for_each(|cancel| cancel.store(true, Ordering::SeqCst));
+
+                        return res.clone();
+                    },
+                    Some(_) => {},
+                    None => break,
+                }
+            }
+        }
+
+        results.swap_remove(0).unwrap()
+    }).map(|sol| tidy::shorten(puzzle, &configs[0], &sol))
+}
+
+fn is_final(res: &Result<Sol, Error>) -> bool {
+    matches!(res, Ok(_) | Err(Error::NoSolution) | Err(Error::InvalidField))
+}
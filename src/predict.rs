@@ -0,0 +1,87 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Puzzle;
+
+// 解く前に分かる特徴量から予想する難しさ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Difficulty {
+    // 0.1 秒未満
+    Trivial,
+    // 10 秒未満
+    Easy,
+    // 10 分未満
+    Medium,
+    Hard,
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difficulty::Trivial => write!(f, "trivial (under 0.1s)"),
+            Difficulty::Easy => write!(f, "easy (under 10s)"),
+            Difficulty::Medium => write!(f, "medium (under 10min)"),
+            Difficulty::Hard => write!(f, "hard (10min or more)"),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trivial" => Ok(Difficulty::Trivial),
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!("unknown difficulty `{}` (expected trivial, easy, medium or hard)", s)),
+        }
+    }
+}
+
+impl Difficulty {
+    pub fn of(score: f64) -> Difficulty {
+        THRESHOLDS.iter().find(|&&(limit, _)| score < limit).map_or(Difficulty::Hard, |&(_, difficulty)| difficulty)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Prediction {
+    pub difficulty: Difficulty,
+    pub cells: usize,
+    pub pairs: usize,
+    // 手がかりのあるセルの割合
+    pub density: f64,
+    // 大きいほど難しい (difficulty はこれを区切ったもの)
+    pub score: f64,
+}
+
+// 空きセルの数を, 1 組あたりの面積が広いほど (経路の選び方が多いほど) 重くして数える
+// 区切りは手元の問題 (10x10 と 12x12 は一瞬, 17x17 の難問は 1 分弱) に合わせた大まかなもの
+const BASE_CELLS_PER_PAIR: f64 = 12.0;
+const THRESHOLDS: [(f64, Difficulty); 3] = [(120.0, Difficulty::Trivial), (280.0, Difficulty::Easy), (700.0, Difficulty::Medium)];
+
+pub fn predict(puzzle: &Puzzle) -> Prediction {
+    let cells = puzzle.cells.len();
+    let clues = puzzle.cells.iter().filter(|&&num| num > 0).count();
+    let pairs = clues/2;
+
+    let score = score(cells, clues);
+
+    Prediction {
+        difficulty: Difficulty::of(score),
+        cells,
+        pairs,
+        density: if cells == 0 { 0.0 } else { clues as f64/cells as f64 },
+        score,
+    }
+}
+
+// cells セルの盤面の clues セルに数字があるときの Prediction::score
+pub fn score(cells: usize, clues: usize) -> f64 {
+    let open = cells.saturating_sub(clues) as f64;
+    let per_pair = cells as f64/(clues/2).max(1) as f64;
+
+    open*(per_pair/BASE_CELLS_PER_PAIR).sqrt()
+}
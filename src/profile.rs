@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::generate::{quality, Quality};
+use crate::render::paths;
+use crate::{Puzzle, Sol, P};
+
+// 盤面の形と答えの数値的な特徴 (問題集の傾向を見る用)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Profile {
+    pub width: usize,
+    pub height: usize,
+    // 線が通れるセル (穴と形の外を除く)
+    pub cells: usize,
+    pub holes: usize,
+    pub walls: usize,
+    pub bridges: usize,
+    // ちょうど 2 つある数字の組 (点の盤面では点の数の半分)
+    pub pairs: usize,
+    // 数字のセルの cells に対する割合
+    pub density: f64,
+    // 組の両端の距離 (マンハッタン) の最小, 平均, 最大
+    pub min_distance: usize,
+    pub mean_distance: f64,
+    pub max_distance: usize,
+    // 答えがあるときの出来 (Quality) と, 組ごとの (道のセルの数 - 1) / 両端の距離 の平均
+    pub solution: Option<Quality>,
+    pub detour: Option<f64>,
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}, {} cells, {} holes, {} walls, {} bridges, {} pairs, density {:.3}, distance {}/{:.1}/{}",
+            self.width, self.height, self.cells, self.holes, self.walls, self.bridges, self.pairs, self.density, self.min_distance, self.mean_distance, self.max_distance)?;
+
+        match (self.solution, self.detour) {
+            (Some(quality), Some(detour)) => write!(f, ", {}, detour {:.2}", quality, detour),
+            _ => write!(f, ", no solution"),
+        }
+    }
+}
+
+// sol は puzzle の答え (なければ答えの特徴は None)
+pub fn profile(puzzle: &Puzzle, sol: Option<&Sol>) -> Profile {
+    let cells = (0..puzzle.cells.len()).filter(|&u| !puzzle.is_hole(u)).count();
+    let clues = puzzle.cells.iter().filter(|&&num| num > 0).count();
+    let ends = pair_ends(puzzle);
+    let distances: Vec<usize> = ends.values().map(|&(p, q)| distance(p, q)).collect();
+    let detour = sol.map(|sol| {
+        let ratios: Vec<f64> = paths(puzzle, sol).into_iter().filter(|(_, path)| path.len() >= 2).map(|(_, path)| {
+            (path.len()-1) as f64/distance(path[0], path[path.len()-1]).max(1) as f64
+        }).collect();
+
+        ratios.iter().sum::<f64>()/ratios.len().max(1) as f64
+    });
+
+    Profile {
+        width: puzzle.width,
+        height: puzzle.height,
+        cells,
+        holes: puzzle.holes.difference(&puzzle.outside).count(),
+        walls: puzzle.walls.len(),
+        bridges: puzzle.bridges.len(),
+        pairs: if puzzle.dots { clues/2 } else { ends.len() },
+        density: clues as f64/cells.max(1) as f64,
+        min_distance: distances.iter().cloned().min().unwrap_or(0),
+        mean_distance: distances.iter().sum::<usize>() as f64/distances.len().max(1) as f64,
+        max_distance: distances.iter().cloned().max().unwrap_or(0),
+        solution: sol.map(|sol| quality(puzzle, sol)),
+        detour,
+    }
+}
+
+// ちょうど 2 つある数字の両端 (点の盤面にはない)
+fn pair_ends(puzzle: &Puzzle) -> BTreeMap<usize, (P, P)> {
+    let mut cells: BTreeMap<usize, Vec<P>> = BTreeMap::new();
+
+    if puzzle.dots {
+        return BTreeMap::new();
+    }
+
+    for (u, &num) in puzzle.cells.iter().enumerate().filter(|&(_, &num)| num > 0) {
+        cells.entry(num).or_default().push(puzzle.pos(u));
+    }
+
+    cells.into_iter().filter(|(_, ends)| ends.len() == 2).map(|(num, ends)| (num, (ends[0], ends[1]))).collect()
+}
+
+fn distance(p: P, q: P) -> usize {
+    p.0.abs_diff(q.0)+p.1.abs_diff(q.1)
+}
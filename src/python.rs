@@ -0,0 +1,160 @@
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+
+use crate::generate::generate;
+use crate::render::{paths, render, render_svg, render_url, Format, Theme};
+use crate::{parse_puzzle, solve_numberlink, Error, Puzzle as Board, Sol, SolverConfig, P};
+
+// Python からは import numberlink_solver として使う (モジュール名は crate の名前と同じでないと読み込めない)
+//
+//     puzzle = numberlink_solver.Puzzle("https://puzz.link/p?numlin/...")
+//     solution = puzzle.solve()
+//     print(solution.render("text"))
+
+create_exception!(numberlink_solver, SolveError, PyException);
+
+// puzz.link の URL, テキストか JSON の盤面 (CLI と同じ)
+#[pyclass(name = "Puzzle", module = "numberlink_solver", frozen)]
+pub struct PyPuzzle {
+    puzzle: Board,
+}
+
+#[pyclass(name = "Solution", module = "numberlink_solver", frozen)]
+pub struct PySolution {
+    puzzle: Board,
+    sol: Sol,
+}
+
+// 関数の引数には Puzzle でも文字列でも渡せる
+#[derive(FromPyObject)]
+enum Input {
+    Puzzle(Py<PyPuzzle>),
+    Text(String),
+}
+
+impl Input {
+    fn puzzle(self) -> PyResult<Board> {
+        match self {
+            Input::Puzzle(p) => Ok(p.get().puzzle.clone()),
+            Input::Text(s) => parse_puzzle(s.trim()).ok_or_else(|| PyValueError::new_err(Error::InvalidUrl.to_string())),
+        }
+    }
+}
+
+#[pymethods]
+impl PyPuzzle {
+    #[new]
+    fn new(input: &str) -> PyResult<PyPuzzle> {
+        Ok(PyPuzzle { puzzle: Input::Text(input.to_string()).puzzle()? })
+    }
+
+    // 答えがただ 1 つの盤面を作る. 作れなければ SolveError
+    #[staticmethod]
+    #[pyo3(signature = (width, height, pairs, seed = 0))]
+    fn generate(py: Python, width: usize, height: usize, pairs: usize, seed: u64) -> PyResult<PyPuzzle> {
+        py.detach(|| generate(width, height, pairs, seed))
+            .map(|puzzle| PyPuzzle { puzzle })
+            .ok_or_else(|| SolveError::new_err(format!("no {}x{} puzzle with {} pairs found", width, height, pairs)))
+    }
+
+    #[getter]
+    fn width(&self) -> usize {
+        self.puzzle.width
+    }
+
+    #[getter]
+    fn height(&self) -> usize {
+        self.puzzle.height
+    }
+
+    // 行ごとのセルの数字 (空白は 0)
+    #[getter]
+    fn cells(&self) -> Vec<Vec<usize>> {
+        self.puzzle.cells.chunks(self.puzzle.width.max(1)).map(|row| row.to_vec()).collect()
+    }
+
+    fn solve(&self, py: Python) -> PyResult<PySolution> {
+        solve_puzzle(py, self.puzzle.clone())
+    }
+
+    // format は url, text, json か svg
+    #[pyo3(signature = (format = "text"))]
+    fn render(&self, format: &str) -> PyResult<String> {
+        match format {
+            "url" => Ok(render_url(&self.puzzle)),
+            format => render_as(&self.puzzle, &vec![], format, "url, text, json or svg"),
+        }
+    }
+
+    fn __str__(&self) -> String {
+        render(&self.puzzle, &vec![], Format::Text, Theme::Plain)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Puzzle({:?})", render_url(&self.puzzle))
+    }
+}
+
+#[pymethods]
+impl PySolution {
+    #[getter]
+    fn puzzle(&self) -> PyPuzzle {
+        PyPuzzle { puzzle: self.puzzle.clone() }
+    }
+
+    // 数字ごとの道 (端から端へのセル (行, 列) の列)
+    #[getter]
+    fn paths(&self) -> Vec<(usize, Vec<P>)> {
+        paths(&self.puzzle, &self.sol)
+    }
+
+    // format は text, json か svg
+    #[pyo3(signature = (format = "text"))]
+    fn render(&self, format: &str) -> PyResult<String> {
+        render_as(&self.puzzle, &self.sol, format, "text, json or svg")
+    }
+
+    fn __str__(&self) -> String {
+        render(&self.puzzle, &self.sol, Format::Text, Theme::Plain)
+    }
+}
+
+// 解けない, 時間切れなどは SolveError. 解いている間は GIL を放す
+#[pyfunction]
+fn solve(py: Python, input: Input) -> PyResult<PySolution> {
+    solve_puzzle(py, input.puzzle()?)
+}
+
+#[pyfunction]
+#[pyo3(name = "generate", signature = (width, height, pairs, seed = 0))]
+fn generate_puzzle(py: Python, width: usize, height: usize, pairs: usize, seed: u64) -> PyResult<PyPuzzle> {
+    PyPuzzle::generate(py, width, height, pairs, seed)
+}
+
+fn solve_puzzle(py: Python, puzzle: Board) -> PyResult<PySolution> {
+    match py.detach(|| solve_numberlink(&puzzle, &SolverConfig::default())) {
+        Ok(sol) => Ok(PySolution { puzzle, sol }),
+        Err(e) => Err(SolveError::new_err(e.to_string())),
+    }
+}
+
+fn render_as(puzzle: &Board, sol: &Sol, format: &str, expected: &str) -> PyResult<String> {
+    match format {
+        "text" => Ok(render(puzzle, sol, Format::Text, Theme::Plain)),
+        "json" => Ok(render(puzzle, sol, Format::Json, Theme::Plain)),
+        "svg" => Ok(render_svg(puzzle, sol)),
+        _ => Err(PyValueError::new_err(format!("unknown format `{}` (expected {})", format, expected))),
+    }
+}
+
+#[pymodule]
+fn numberlink_solver(m: &Bound<PyModule>) -> PyResult<()> {
+    m.add_class::<PyPuzzle>()?;
+    m.add_class::<PySolution>()?;
+    m.add_function(wrap_pyfunction!(solve, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_puzzle, m)?)?;
+    m.add("SolveError", m.py().get_type::<SolveError>())?;
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc as Shared;
+
+use crate::backend::Backend;
+use crate::deduce::{deduce, Grade};
+use crate::predict::predict;
+use crate::{Error, Progress, Puzzle, Session, SolverConfig};
+
+// 実際に解いた手間と盤面の形から付ける難しさの段階
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Tier {
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tier::Easy => write!(f, "easy"),
+            Tier::Medium => write!(f, "medium"),
+            Tier::Hard => write!(f, "hard"),
+            Tier::Expert => write!(f, "expert"),
+        }
+    }
+}
+
+impl FromStr for Tier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Tier::Easy),
+            "medium" => Ok(Tier::Medium),
+            "hard" => Ok(Tier::Hard),
+            "expert" => Ok(Tier::Expert),
+            _ => Err(format!("unknown tier `{}` (expected easy, medium, hard or expert)", s)),
+        }
+    }
+}
+
+impl Tier {
+    pub fn of(score: f64) -> Tier {
+        TIERS.iter().find(|&&(limit, _)| score < limit).map_or(Tier::Expert, |&(_, tier)| tier)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rating {
+    pub tier: Tier,
+    // 大きいほど難しい (tier はこれを区切ったもの). 盤面を難しい順に並べるのに使う
+    pub score: f64,
+    // 解と別解がないことを確かめるまでの varisat の conflict 数
+    pub conflicts: u64,
+    // 人の推論 (deduce) では決まらず, 探索で選ぶしかない辺の数
+    pub decisions: usize,
+    // 盤面の形だけから見た難しさ (Prediction::score)
+    pub structure: f64,
+    // 人が解くのに要る一番難しい技法
+    pub grade: Grade,
+}
+
+// score = structure*(1 + 決まらない辺の割合) + CONFLICT_WEIGHT*log2(1 + conflicts)
+// 区切りは gen で作った盤面 (5x5 から 12x12) と benches/corpus.txt に合わせた大まかなもの
+const CONFLICT_WEIGHT: f64 = 10.0;
+const TIERS: [(f64, Tier); 3] = [(100.0, Tier::Easy), (250.0, Tier::Medium), (500.0, Tier::Hard)];
+
+pub fn rate(puzzle: &Puzzle) -> Result<Rating, Error> {
+    rate_with(puzzle, &SolverConfig::default())
+}
+
+// config の encoding, 期限と中断は使うが, conflict 数を比べられるように backend は varisat に固定する
+pub fn rate_with(puzzle: &Puzzle, config: &SolverConfig) -> Result<Rating, Error> {
+    let progress = Shared::new(Progress::default());
+    let config = SolverConfig { backend: Backend::Varisat, progress: Some(progress.clone()), ..config.clone() };
+    let mut session = Session::new(puzzle, &config)?;
+
+    session.is_unique()?;
+
+    let edges = puzzle.edges().len();
+    // 推論が使えない盤面 (bridges など) は全部の辺を探索で選ぶとみなす
+    let (decisions, grade) = deduce(puzzle).map_or((edges, Grade::Search), |deduction| (deduction.undecided, deduction.grade()));
+    let structure = predict(puzzle).score;
+    let conflicts = progress.conflicts();
+    let score = structure*(1.0 + decisions as f64/edges.max(1) as f64) + CONFLICT_WEIGHT*(1.0 + conflicts as f64).log2();
+
+    Ok(Rating { tier: Tier::of(score), score, conflicts, decisions, structure, grade })
+}
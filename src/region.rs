@@ -0,0 +1,179 @@
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::time::Duration;
+
+use varisat::{ExtendFormula, Lit, Var};
+
+use crate::tags::Tagger;
+use crate::template::{mk_clause_le1, number_domain, template};
+use crate::{encode_clue, encode_role, encode_blocked, gen_adjs, mk_clause_impl, parse_field, sat_only, solve_numberlink, Encoding, Error, Instant, Puzzle, Role, Session, Sol, SolverConfig, Topology};
+
+// 禁止した interface がこれだけ溜まっても帯の解がつながらなければ, 分けるのをやめて盤面全体を解く
+const MAX_BLOCKS: usize = 256;
+
+// 盤面を width 列ずつの縦の帯に分けて左から順に解き, 解をつなぎ合わせる
+// 帯の境界をまたぐ arc と, arc が通る行の両側のセルの数字 (interface) を前の帯の解から仮定として次の帯に渡し,
+// 次の帯が解けなければ, 解けない理由になった仮定 (failed core) だけを前の帯で禁止して解き直す
+pub fn solve_regions(puzzle: &Puzzle, config: &SolverConfig, width: usize) -> Result<Sol, Error> {
+    if width == 0 || puzzle.width <= width {
+        return solve_numberlink(puzzle, config);
+    }
+
+    if puzzle.height == 0 || puzzle.cells.len() != puzzle.width*puzzle.height {
+        return Err(Error::InvalidField);
+    }
+
+    if !puzzle.bridges.is_empty() || !puzzle.warps.is_empty() || puzzle.topology != Topology::Square {
+        return Err(Error::BackendFailed("--regions does not support bridges, warps or boards other than the square grid".to_string()));
+    }
+
+    if let Some(feature) = sat_only(puzzle, config) {
+        return Err(Error::BackendFailed(format!("--regions does not support {}", feature)));
+    }
+
+    let roles = parse_field(puzzle).ok_or(Error::InvalidField)?;
+    let template = template(puzzle, config);
+    let numbers = &template.numbers;
+    let deadline = config.timeout.map(|timeout| Instant::now()+timeout);
+
+    let strips: Vec<Range<usize>> = (0..puzzle.width).step_by(width).map(|col| col..(col+width).min(puzzle.width)).collect();
+    let mut sessions = strips.iter().map(|cols| {
+        Session::with_encoder(puzzle, config, None, |formula| {
+            encode_strip(puzzle, config.encoding, &roles, numbers, cols.clone(), formula);
+
+            Ok(template.next_var)
+        })
+    }).collect::<Result<Vec<Session>, Error>>()?;
+
+    // 帯 k の禁止節は左の interface が変わると無効になるので, 活性化変数ごと取り替える
+    let mut acts: Vec<Var> = sessions.iter_mut().map(|session| session.fresh_var()).collect();
+    let mut lefts: Vec<Vec<Lit>> = vec![vec![]; strips.len()];
+    let mut sols: Vec<Sol> = vec![vec![]; strips.len()];
+    let mut blocks = 0;
+    let mut k = 0;
+
+    while k < strips.len() {
+        let mut assumptions = lefts[k].clone();
+
+        assumptions.push(acts[k].positive());
+        sessions[k].set_timeout(deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1))));
+
+        match sessions[k].run(&assumptions) {
+            Ok(sol) => {
+                sols[k] = sol;
+
+                if k+1 < strips.len() {
+                    lefts[k+1] = interface(puzzle, numbers, &sessions[k], strips[k].end);
+                    acts[k+1] = sessions[k+1].fresh_var();
+                }
+
+                k += 1;
+            },
+            Err(Error::NoSolution) if k > 0 => {
+                blocks += 1;
+
+                if blocks > MAX_BLOCKS {
+                    let timeout = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1)));
+
+                    return solve_numberlink(puzzle, &SolverConfig { timeout, ..config.clone() });
+                }
+
+                // interface の全体ではなく core に入った部分だけを禁止すれば, 同じ理由で解けない interface をまとめて除ける
+                let core = sessions[k].failed().unwrap_or(assumptions);
+                let mut clause = vec![acts[k-1].negative()];
+
+                clause.extend(lefts[k].iter().filter(|lit| core.contains(lit)).map(|&lit| !lit));
+                k -= 1;
+                sessions[k].add_clause(&clause);
+            },
+            Err(e) => return Err(e),
+        }
+    }
+
+    // 各帯の外の arc の変数は制約がないので, 帯の中のセルに接する arc だけを拾う
+    let mut res = BTreeSet::new();
+
+    for (cols, sol) in strips.iter().zip(sols) {
+        res.extend(sol.into_iter().filter(|(u, v)| cols.contains(&u.1) || cols.contains(&v.1)));
+    }
+
+    Ok(res.into_iter().collect())
+}
+
+// 列 col-1 と列 col の境界の interface を, 直前の解での値のリテラルで表す
+fn interface(puzzle: &Puzzle, numbers: &[Vec<usize>], session: &Session, col: usize) -> Vec<Lit> {
+    let mut res = vec![];
+
+    for i in 0..puzzle.height {
+        let u = puzzle.index((i, col-1));
+        let v = puzzle.index((i, col));
+        let arcs = [puzzle.topology.arc_var(u, 0), puzzle.topology.arc_var(v, 2)];
+
+        for &x in &arcs {
+            res.push(Lit::from_var(x, session.value(x)));
+        }
+
+        if arcs.iter().any(|&x| session.value(x)) {
+            for &x in numbers[u].iter().chain(&numbers[v]) {
+                let x = Var::from_index(x);
+
+                res.push(Lit::from_var(x, session.value(x)));
+            }
+        }
+    }
+
+    res
+}
+
+// 帯の中のセルにはすべての制約を, 帯の両隣の列 (ghost) には数字と端点の向きの制約だけをかける
+fn encode_strip<F: ExtendFormula>(puzzle: &Puzzle, encoding: Encoding, roles: &[Role], numbers: &[Vec<usize>], cols: Range<usize>, formula: &mut F) {
+    let adjs = gen_adjs(puzzle);
+    let inside = |u: usize| cols.contains(&(u%puzzle.width));
+    let ghost = |u: usize| u%puzzle.width+1 == cols.start || u%puzzle.width == cols.end;
+
+    let mut buf = vec![];
+
+    for u in (0..puzzle.cells.len()).filter(|&u| inside(u) || ghost(u)) {
+        if encoding == Encoding::Direct {
+            let vars: Vec<Var> = numbers[u].iter().map(|&x| Var::from_index(x)).collect();
+
+            number_domain(formula, &vars, &mut buf);
+        }
+
+        encode_clue(formula, encoding, &numbers[u], puzzle.cells[u]);
+
+        let vs: Vec<(usize, usize)> = adjs[u].iter().cloned().filter(|&(v, _)| inside(u) || inside(v)).collect();
+        let outs: Vec<Var> = vs.iter().map(|&(_, d)| puzzle.topology.arc_var(u, d)).collect();
+        let ins: Vec<Var> = vs.iter().map(|&(v, d)| puzzle.topology.arc_var(v, puzzle.topology.opposite(d))).collect();
+
+        for (&(v, _), &x) in vs.iter().zip(&outs) {
+            // (12)
+            mk_clause_impl(formula, &x, &numbers[u], &numbers[v]);
+        }
+
+        if ghost(u) {
+            match roles[u] {
+                Role::Start => ins.iter().for_each(|x| formula.add_clause(&[x.negative()])),
+                Role::End => outs.iter().for_each(|x| formula.add_clause(&[x.negative()])),
+                Role::Blank | Role::Hole | Role::Bridge | Role::Terminal | Role::Dot => {},
+            }
+
+            continue;
+        }
+
+        for (&x, &y) in outs.iter().zip(&ins) {
+            // (2)
+            formula.add_clause(&[x.negative(), y.negative()]);
+        }
+
+        mk_clause_le1(formula, &outs);
+        mk_clause_le1(formula, &ins);
+
+        let outs: Vec<Lit> = outs.iter().map(|x| x.positive()).collect();
+        let ins: Vec<Lit> = ins.iter().map(|x| x.positive()).collect();
+
+        encode_role(formula, roles[u], &outs, &ins);
+    }
+
+    encode_blocked(&mut Tagger::new(formula, None), puzzle);
+}
@@ -0,0 +1,635 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc as Shared, OnceLock, RwLock};
+
+use crate::{Error, Puzzle, Sol, Topology, P};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Debug,
+    Text,
+    Json,
+    // register_renderer で登録した出力 (svg は最初からある). 数字の盤面以外のパズルでは Debug と同じ
+    Plugin(&'static str),
+}
+
+// 答えの書き出し方. 外のクレートから register_renderer で登録すると --format NAME や Format::Plugin(NAME) で使える
+// (画像の描き方を変えたもの, ゲームエンジンのメッシュなど). 点の盤面には結んだ点に同じ数字を書いた盤面を渡す
+pub trait Renderer: Send + Sync {
+    fn render(&self, puzzle: &Puzzle, sol: &Sol) -> String;
+
+    // バッチの 1 行 (改行を空白に)
+    fn render_line(&self, puzzle: &Puzzle, sol: &Sol) -> String {
+        self.render(puzzle, sol).trim_end().replace('\n', " ")
+    }
+
+    // 解けなかったときのバッチの 1 行
+    fn render_error(&self, err: &Error) -> String {
+        format!("error: {}", err)
+    }
+}
+
+// 盤面と答えの SVG (render_svg)
+pub struct Svg;
+
+impl Renderer for Svg {
+    fn render(&self, puzzle: &Puzzle, sol: &Sol) -> String {
+        render_svg(puzzle, sol)
+    }
+}
+
+type Renderers = RwLock<Vec<(&'static str, Shared<dyn Renderer>)>>;
+
+static RENDERERS: OnceLock<Renderers> = OnceLock::new();
+
+fn renderers() -> &'static Renderers {
+    RENDERERS.get_or_init(|| RwLock::new(vec![("svg", Shared::new(Svg) as Shared<dyn Renderer>)]))
+}
+
+// 同じ名前で登録し直すと置き換わる. debug, text, json は置き換えられない
+pub fn register_renderer(name: &'static str, renderer: impl Renderer + 'static) -> Result<(), String> {
+    if ["debug", "text", "json"].contains(&name) {
+        return Err(format!("format `{}` is built in and cannot be replaced", name));
+    }
+
+    let mut renderers = renderers().write().unwrap();
+
+    renderers.retain(|&(other, _)| other != name);
+    renderers.push((name, Shared::new(renderer)));
+
+    Ok(())
+}
+
+// 登録した出力の名前 (登録順)
+pub fn renderer_names() -> Vec<&'static str> {
+    renderers().read().unwrap().iter().map(|&(name, _)| name).collect()
+}
+
+fn renderer(name: &str) -> Shared<dyn Renderer> {
+    renderers().read().unwrap().iter().find(|&&(other, _)| other == name).map(|(_, renderer)| renderer.clone()).unwrap()
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "debug" => Ok(Format::Debug),
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => {
+                let names = renderer_names();
+
+                names.iter().find(|&&name| name == s).map(|&name| Format::Plugin(name))
+                    .ok_or_else(|| format!("unknown format `{}` (expected debug, text, json or {})", s, names.join(", ")))
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Plain,
+    Color,
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Theme::Plain),
+            "color" => Ok(Theme::Color),
+            _ => Err(format!("unknown theme `{}` (expected plain or color)", s)),
+        }
+    }
+}
+
+// 点の盤面は, 結んだ点に同じ数字を書いた盤面として出す
+pub fn render(puzzle: &Puzzle, sol: &Sol, format: Format, theme: Theme) -> String {
+    if puzzle.dots {
+        return render(&puzzle.paired(sol), sol, format, theme);
+    }
+
+    match format {
+        Format::Debug => format!("{:?}\n", Some((puzzle, sol))),
+        Format::Text => render_text(puzzle, sol, theme),
+        Format::Json => render_json(puzzle, sol),
+        Format::Plugin(name) => renderer(name).render(puzzle, sol),
+    }
+}
+
+// パイプライン用に 1 行で出す
+pub fn render_line(puzzle: &Puzzle, sol: &Sol, format: Format) -> String {
+    if puzzle.dots {
+        return render_line(&puzzle.paired(sol), sol, format);
+    }
+
+    match format {
+        Format::Debug => format!("{:?}", Some((puzzle, sol))),
+        Format::Text if matches!(puzzle.topology, Topology::Graph(_)) => graph_paths(puzzle, sol).join("; "),
+        Format::Text => {
+            let labels = label_cells(puzzle, sol);
+            let rows: Vec<String> = (0..puzzle.height).map(|i| {
+                (0..puzzle.width).map(|j| match puzzle.index((i, j)) {
+                    u if puzzle.is_outside(u) => "_".to_string(),
+                    u if puzzle.is_hole(u) => "#".to_string(),
+                    u if puzzle.is_bridge(u) => "+".to_string(),
+                    _ => labels.get((i, j)).to_string(),
+                }).collect::<Vec<String>>().join(",")
+            }).collect();
+
+            rows.join("/")
+        },
+        Format::Json => render_json(puzzle, sol).trim_end().to_string(),
+        Format::Plugin(name) => renderer(name).render_line(puzzle, sol),
+    }
+}
+
+pub fn render_error_line(err: &Error, format: Format) -> String {
+    match format {
+        Format::Debug => format!("{:?}", err),
+        Format::Text => format!("error: {}", err),
+        Format::Json => format!("{{\"error\":\"{}\"}}", err),
+        Format::Plugin(name) => renderer(name).render_error(err),
+    }
+}
+
+// 各数字の始点から arc をたどった経路 (数字, セル列). 木のネットは根から葉までの経路ごとに 1 つ
+pub fn paths(puzzle: &Puzzle, sol: &Sol) -> Vec<(usize, Vec<P>)> {
+    let mut outs: HashMap<P, Vec<P>> = HashMap::new();
+
+    for &(u, v) in sol {
+        outs.entry(u).or_default().push(v);
+    }
+
+    // 橋からは 2 本出ているので, 入ってきたのと同じ向きに出る方へ進む
+    let next = |prev: P, u: P| -> Vec<P> {
+        let vs = outs.get(&u).map_or(&[][..], |vs| vs.as_slice());
+
+        if puzzle.is_bridge(puzzle.index(u)) {
+            vs.iter().filter(|&&v| puzzle.direction(u, v) == puzzle.direction(prev, u)).cloned().collect()
+        } else {
+            vs.to_vec()
+        }
+    };
+    let mut has_in: HashMap<P, bool> = HashMap::new();
+
+    for (_, v) in sol {
+        has_in.insert(*v, true);
+    }
+
+    let mut res = vec![];
+
+    for (index, &num) in puzzle.cells.iter().enumerate() {
+        let p = puzzle.pos(index);
+
+        if num == 0 || has_in.contains_key(&p) {
+            continue;
+        }
+
+        let mut stack = vec![(vec![p], p, p)];
+
+        while let Some((mut path, mut prev, mut u)) = stack.pop() {
+            loop {
+                let vs = next(prev, u);
+
+                if vs.is_empty() || path.len() > sol.len()+1 {
+                    break;
+                }
+
+                for &v in &vs[1..] {
+                    stack.push((path.iter().cloned().chain([v]).collect(), u, v));
+                }
+
+                path.push(vs[0]);
+                prev = u;
+                u = vs[0];
+            }
+
+            res.push((num, path));
+        }
+    }
+
+    res.sort();
+
+    res
+}
+
+pub(crate) fn label_cells(puzzle: &Puzzle, sol: &Sol) -> Puzzle {
+    let mut res = Puzzle::new(puzzle.width, puzzle.height);
+
+    for (num, path) in paths(puzzle, sol) {
+        for p in path {
+            res.set(p, num);
+        }
+    }
+
+    res
+}
+
+fn render_text(puzzle: &Puzzle, sol: &Sol, theme: Theme) -> String {
+    if let Topology::Graph(_) = puzzle.topology {
+        return graph_paths(puzzle, sol).into_iter().map(|line| line+"\n").collect();
+    }
+
+    let labels = label_cells(puzzle, sol);
+    let max = labels.cells.iter().cloned().max().unwrap_or(0);
+    let w = max.to_string().len();
+
+    // 六角形の盤面は下の行ほど半セルずつ右にずらす (右下と左下が隣)
+    let indent = |i: usize| if puzzle.topology == Topology::Hex { " ".repeat(i*(w+1)/2) } else { String::new() };
+
+    // ワープのある盤面は周りに 1 文字ずつ空けて, 経路が盤外へ出入りする辺に `~` を置く
+    let marks = wrap_marks(puzzle, sol);
+    let margin = !puzzle.warps.is_empty();
+    let mark = |p: P, side: usize| if marks.contains(&(p, side)) { "~" } else { " " };
+    let border = |i: usize, side: usize| {
+        let cells: Vec<String> = (0..puzzle.width).map(|j| format!("{:>w$}", mark((i, j), side), w = w)).collect();
+
+        format!("{}  {}", indent(i), cells.join(" ")).trim_end().to_string()
+    };
+
+    // 重ねた盤面は下の層から順に, 空行で区切って出す
+    let rows = puzzle.topology.layer_rows(puzzle.height);
+    let mut res = String::new();
+
+    for (i, line) in labels.rows().enumerate() {
+        if i%rows == 0 && i > 0 {
+            res.push('\n');
+        }
+
+        if margin && i%rows == 0 && !border(i, TOP).is_empty() {
+            res.push_str(&border(i, TOP));
+            res.push('\n');
+        }
+
+        let cells: Vec<String> = line.iter().enumerate().map(|(j, &num)| {
+            let s = cell_text(puzzle, &labels, (i, j), w);
+
+            match theme {
+                Theme::Plain => s,
+                Theme::Color if num == 0 => s,
+                // 端点は太字
+                Theme::Color => format!("\x1b[{}38;5;{}m{}\x1b[0m", if puzzle.get((i, j)) > 0 { "1;" } else { "" }, color(num), s),
+            }
+        }).collect();
+
+        let row = match margin {
+            true => format!("{}{} {} {}", indent(i), mark((i, 0), LEFT), cells.join(" "), mark((i, puzzle.width-1), RIGHT)),
+            false => format!("{}{}", indent(i), cells.join(" ")),
+        };
+
+        res.push_str(row.trim_end());
+        res.push('\n');
+
+        if margin && i%rows == rows-1 && !border(i, BOTTOM).is_empty() {
+            res.push_str(&border(i, BOTTOM));
+            res.push('\n');
+        }
+    }
+
+    res
+}
+
+const RIGHT: usize = 0;
+const BOTTOM: usize = 1;
+const LEFT: usize = 2;
+const TOP: usize = 3;
+
+// 解で使われたワープの arc の両端のセルと, そのセルから盤外へ出る辺
+fn wrap_marks(puzzle: &Puzzle, sol: &Sol) -> HashSet<(P, usize)> {
+    let side = |p: P, d: usize| {
+        let rows = puzzle.topology.layer_rows(puzzle.height);
+        let (di, dj) = puzzle.topology.dir(d);
+        let (i, j) = ((p.0%rows) as i32 + di, p.1 as i32 + dj);
+
+        if i < 0 {
+            TOP
+        } else if i >= rows as i32 {
+            BOTTOM
+        } else if j < 0 {
+            LEFT
+        } else {
+            RIGHT
+        }
+    };
+
+    let mut res = HashSet::new();
+
+    for &(p, q) in sol {
+        if let Some(d) = puzzle.direction(p, q).filter(|&d| puzzle.step(p, d).is_none()) {
+            res.insert((p, side(p, d)));
+            res.insert((q, side(q, puzzle.topology.opposite(d))));
+        }
+    }
+
+    res
+}
+
+// 穴は `#`, 橋は `+`, どの経路も通らない空白は `.`, 盤面の外は空白
+pub(crate) fn cell_text(puzzle: &Puzzle, labels: &Puzzle, p: P, w: usize) -> String {
+    match labels.get(p) {
+        _ if puzzle.is_outside(puzzle.index(p)) => " ".repeat(w),
+        _ if puzzle.is_hole(puzzle.index(p)) => format!("{:>w$}", "#", w = w),
+        // 2 本の経路が通るので数字は出さない
+        _ if puzzle.is_bridge(puzzle.index(p)) => format!("{:>w$}", "+", w = w),
+        0 => format!("{:>w$}", ".", w = w),
+        num => format!("{:>w$}", num, w = w),
+    }
+}
+
+fn color(num: usize) -> usize {
+    let palette = [196, 46, 21, 226, 201, 51, 208, 129, 34, 214, 27, 160, 118, 93, 45, 166];
+
+    palette[(num-1) % palette.len()]
+}
+
+// グラフの盤面では経路を頂点の列で 1 本ずつ `数字: 頂点 ...` と書く
+fn graph_paths(puzzle: &Puzzle, sol: &Sol) -> Vec<String> {
+    paths(puzzle, sol).into_iter().map(|(num, path)| {
+        format!("{}: {}", num, path.iter().map(|p| p.1.to_string()).collect::<Vec<String>>().join(" "))
+    }).collect()
+}
+
+// input::parse_json の形
+fn render_graph_json(puzzle: &Puzzle, sol: &Sol) -> String {
+    let edges: Vec<String> = puzzle.edges().iter().map(|(u, v)| format!("[{},{}]", u, v)).collect();
+    let n = puzzle.cells.iter().cloned().max().unwrap_or(0);
+    let pairs: Vec<String> = (1..=n).map(|num| {
+        let ends: Vec<String> = (0..puzzle.cells.len()).filter(|&u| puzzle.cells[u] == num).map(|u| u.to_string()).collect();
+
+        format!("[{}]", ends.join(","))
+    }).collect();
+
+    let paths: Vec<String> = paths(puzzle, sol).into_iter().map(|(num, path)| {
+        let nodes: Vec<String> = path.iter().map(|p| p.1.to_string()).collect();
+
+        format!("{{\"number\":{},\"nodes\":[{}]}}", num, nodes.join(","))
+    }).collect();
+
+    format!("{{\"nodes\":{},\"edges\":[{}],\"pairs\":[{}],\"paths\":[{}]}}\n", puzzle.cells.len(), edges.join(","), pairs.join(","), paths.join(","))
+}
+
+fn render_json(puzzle: &Puzzle, sol: &Sol) -> String {
+    if let Topology::Graph(_) = puzzle.topology {
+        return render_graph_json(puzzle, sol);
+    }
+
+    let paths: Vec<String> = paths(puzzle, sol).into_iter().map(|(num, path)| {
+        let cells: Vec<String> = path.iter().map(|&p| cell_json(puzzle, p)).collect();
+
+        format!("{{\"number\":{},\"cells\":[{}]}}", num, cells.join(","))
+    }).collect();
+
+    format!("{{{},\"paths\":[{}]}}\n", board_json(puzzle), paths.join(","))
+}
+
+// 答えのない盤面だけの JSON (parse_json で読める)
+pub fn render_puzzle_json(puzzle: &Puzzle) -> String {
+    if let Topology::Graph(_) = puzzle.topology {
+        return render_graph_json(puzzle, &vec![]);
+    }
+
+    format!("{{{}}}\n", board_json(puzzle))
+}
+
+// render_json の paths より前
+fn board_json(puzzle: &Puzzle) -> String {
+    // 点は field に書かず dots に出す
+    let rows: Vec<String> = puzzle.rows().map(|line| {
+        format!("[{}]", line.iter().map(|&num| if puzzle.dots { 0 } else { num }.to_string()).collect::<Vec<String>>().join(","))
+    }).collect();
+
+    // 重ねた盤面は層ごとの field を並べる. 層の間を行き来しなければつながった複数の盤面
+    let boards = matches!(puzzle.topology, Topology::Layers(_)) && puzzle.vias.is_empty();
+    let layer_rows = puzzle.topology.layer_rows(puzzle.height);
+    let board = |rows: &[String]| match puzzle.topology {
+        Topology::Layers(_) => {
+            let layers: Vec<String> = rows.chunks(layer_rows).map(|layer| format!("[{}]", layer.join(","))).collect();
+
+            format!("[{}]", layers.join(","))
+        },
+        _ => format!("[{}]", rows.join(",")),
+    };
+    let field = format!("\"{}\":{}", match puzzle.topology {
+        _ if boards => "boards",
+        Topology::Layers(_) => "layers",
+        _ => "field",
+    }, board(&rows));
+
+    // 壁, 穴と橋は盤面にあるときだけ出す
+    let mut extra = String::new();
+
+    if !puzzle.outside.is_empty() {
+        let mask: Vec<String> = (0..puzzle.height).map(|i| {
+            format!("[{}]", (0..puzzle.width).map(|j| if puzzle.is_outside(puzzle.index((i, j))) { "0" } else { "1" }).collect::<Vec<&str>>().join(","))
+        }).collect();
+
+        extra.push_str(&format!(",\"mask\":{}", board(&mask)));
+    }
+
+    if matches!(puzzle.topology, Topology::Hex | Topology::Diagonal) {
+        extra.push_str(&format!(",\"topology\":\"{}\"", puzzle.topology));
+    }
+
+    // 盤面の外のセルは holes ではなく mask に出す
+    let holes = puzzle.holes.difference(&puzzle.outside).cloned().collect();
+
+    for (key, cells) in [("holes", &holes), ("bridges", &puzzle.bridges), ("vias", &puzzle.vias)] {
+        if !cells.is_empty() {
+            let cells: Vec<String> = cells.iter().map(|&u| cell_json(puzzle, puzzle.pos(u))).collect();
+
+            extra.push_str(&format!(",\"{}\":[{}]", key, cells.join(",")));
+        }
+    }
+
+    if puzzle.dots {
+        let dots: Vec<String> = (0..puzzle.cells.len()).filter(|&u| puzzle.cells[u] > 0).map(|u| cell_json(puzzle, puzzle.pos(u))).collect();
+
+        extra.push_str(&format!(",\"dots\":[{}]", dots.join(",")));
+    }
+
+    if !puzzle.waypoints.is_empty() {
+        let waypoints: Vec<String> = puzzle.waypoints.iter().map(|(&u, &num)| match num {
+            0 => cell_json(puzzle, puzzle.pos(u)),
+            _ => format!("[{},{}]", cell_json(puzzle, puzzle.pos(u)), num),
+        }).collect();
+
+        extra.push_str(&format!(",\"waypoints\":[{}]", waypoints.join(",")));
+    }
+
+    if !puzzle.limits.is_empty() {
+        let limits: Vec<String> = puzzle.limits.iter().map(|(num, cells)| format!("[{},{}]", num, cells)).collect();
+
+        extra.push_str(&format!(",\"limits\":[{}]", limits.join(",")));
+    }
+
+    if !puzzle.costs.is_empty() {
+        let costs: Vec<String> = puzzle.costs.iter().map(|(&u, cost)| format!("[{},{}]", cell_json(puzzle, puzzle.pos(u)), cost)).collect();
+
+        extra.push_str(&format!(",\"costs\":[{}]", costs.join(",")));
+    }
+
+    if let Some(budget) = puzzle.budget {
+        extra.push_str(&format!(",\"budget\":{}", budget));
+    }
+
+    if !puzzle.walls.is_empty() {
+        let walls: Vec<String> = puzzle.walls.iter().map(|&(u, v)| {
+            format!("[{},{}]", cell_json(puzzle, puzzle.pos(u)), cell_json(puzzle, puzzle.pos(v)))
+        }).collect();
+
+        extra.push_str(&format!(",\"walls\":[{}]", walls.join(",")));
+    }
+
+    // ワープは 1 組につき 1 回, 出ていく向きと一緒に
+    if !puzzle.warps.is_empty() {
+        let warps: Vec<String> = puzzle.warps.iter().filter(|&(&(u, _), &v)| u < v).map(|(&(u, d), &v)| {
+            format!("[{},{},\"{}\"]", cell_json(puzzle, puzzle.pos(u)), cell_json(puzzle, puzzle.pos(v)), puzzle.topology.dir_names()[d])
+        }).collect();
+
+        extra.push_str(&format!(",\"{}\":[{}]", if boards { "links" } else { "warps" }, warps.join(",")));
+    }
+
+    if !puzzle.one_way.is_empty() {
+        let arrows: Vec<String> = puzzle.one_way.iter().map(|&(u, v)| {
+            format!("[{},{}]", cell_json(puzzle, puzzle.pos(u)), cell_json(puzzle, puzzle.pos(v)))
+        }).collect();
+
+        extra.push_str(&format!(",\"one_way\":[{}]", arrows.join(",")));
+    }
+
+    format!("\"width\":{},\"height\":{},{}{}", puzzle.width, layer_rows, field, extra)
+}
+
+// [行, 列], 重ねた盤面では [層, 行, 列]
+fn cell_json(puzzle: &Puzzle, (i, j): P) -> String {
+    match puzzle.topology {
+        Topology::Layers(rows) => format!("[{},{},{}]", i/rows, i%rows, j),
+        _ => format!("[{},{}]", i, j),
+    }
+}
+
+// puzz.link の numlin/W/H/CODE (16 未満は 16 進 1 文字, 111 までは `-` と 2 文字, 空白は g-z で 1-20 個)
+// 壁があれば numlin/b/W/H/ で数字の前に境界線を書く. 数字と壁しか書けないので, 穴などはなくなる
+pub fn render_url(puzzle: &Puzzle) -> String {
+    let mut code = String::new();
+    let mut blanks = 0;
+
+    for &num in &puzzle.cells {
+        if num == 0 {
+            blanks += 1;
+
+            if blanks == 20 {
+                code.push('z');
+                blanks = 0;
+            }
+
+            continue;
+        }
+
+        if blanks > 0 {
+            code.push((b'f'+blanks as u8) as char);
+            blanks = 0;
+        }
+
+        match num {
+            num if num < 16 => code.push_str(&format!("{:x}", num)),
+            num => code.push_str(&format!("-{:02x}", num)),
+        }
+    }
+
+    if blanks > 0 {
+        code.push((b'f'+blanks as u8) as char);
+    }
+
+    if puzzle.walls.is_empty() || puzzle.topology != Topology::Square {
+        return format!("https://puzz.link/p?numlin/{}/{}/{}", puzzle.width, puzzle.height, code);
+    }
+
+    format!("https://puzz.link/p?numlin/b/{}/{}/{}{}", puzzle.width, puzzle.height, encode_border(puzzle), code)
+}
+
+// decode_border の逆: 縦の境界, 横の境界をそれぞれ 5 本ずつ 32 進の 1 文字に (余りのビットは 0)
+fn encode_border(puzzle: &Puzzle) -> String {
+    let (width, height) = (puzzle.width, puzzle.height);
+    let wall = |p: P, q: P| puzzle.walls.contains(&(puzzle.index(p), puzzle.index(q)));
+    let vertical: Vec<bool> = (0..height).flat_map(|i| (0..width-1).map(move |j| (i, j))).map(|(i, j)| wall((i, j), (i, j+1))).collect();
+    let horizontal: Vec<bool> = (0..height-1).flat_map(|i| (0..width).map(move |j| (i, j))).map(|(i, j)| wall((i, j), (i+1, j))).collect();
+
+    [vertical, horizontal].iter().flat_map(|bits| bits.chunks(5)).map(|chunk| {
+        let digit = chunk.iter().enumerate().fold(0, |digit, (b, &bit)| digit | (bit as u32) << (4-b));
+
+        std::char::from_digit(digit, 32).unwrap()
+    }).collect()
+}
+
+// Flow Free の level pack の 1 行 "大きさ,0,番号,線の数;線;線;...". 線はセルの番号 (行優先) を端から端まで並べたもの
+// 大きさは正方形なら幅, そうでなければ 幅:高さ. 解がなければ線は両端の 2 セルだけ (ゲームは両端しか見ない)
+// 書けるのは壁, 穴, 橋などのない四角い盤面で, 数字がどれもちょうど 2 回現れるものだけ
+pub fn render_flow(puzzle: &Puzzle, sol: Option<&Sol>, level: usize) -> Result<String, String> {
+    if puzzle.topology != Topology::Square || puzzle.dots || !puzzle.walls.is_empty() || !puzzle.holes.is_empty() || !puzzle.bridges.is_empty()
+        || !puzzle.warps.is_empty() || !puzzle.vias.is_empty() || !puzzle.outside.is_empty() || !puzzle.waypoints.is_empty() || !puzzle.one_way.is_empty() {
+        return Err("Flow Free packs only hold square-grid boards of numbers (no walls, holes, bridges, warps, vias, dots, waypoints or one-way edges)".to_string());
+    }
+
+    let mut ends: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+
+    for (u, &num) in puzzle.cells.iter().enumerate().filter(|&(_, &num)| num > 0) {
+        ends.entry(num).or_default().push(u);
+    }
+
+    if ends.is_empty() || ends.values().any(|cells| cells.len() != 2) {
+        return Err("Flow Free packs need every number exactly twice".to_string());
+    }
+
+    let flows: Vec<Vec<usize>> = match sol {
+        Some(sol) => paths(puzzle, sol).into_iter().map(|(_, path)| path.into_iter().map(|p| puzzle.index(p)).collect()).collect(),
+        None => ends.into_values().collect(),
+    };
+    let size = match puzzle.width == puzzle.height {
+        true => puzzle.width.to_string(),
+        false => format!("{}:{}", puzzle.width, puzzle.height),
+    };
+    let flows: Vec<String> = flows.iter().map(|flow| flow.iter().map(usize::to_string).collect::<Vec<String>>().join(",")).collect();
+
+    Ok(format!("{},0,{},{};{}", size, level, flows.len(), flows.join(";")))
+}
+
+// 1 セル 40 px の盤面に数字を書き, 解があれば経路をセルの中心を結ぶ線で描く
+pub fn render_svg(puzzle: &Puzzle, sol: &Sol) -> String {
+    const CELL: usize = 40;
+
+    let (width, height) = (puzzle.width*CELL, puzzle.height*CELL);
+    let centre = |(i, j): P| format!("{},{}", j*CELL+CELL/2, i*CELL+CELL/2);
+    let mut res = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n", width+2, height+2, width+2, height+2);
+
+    res.push_str(&format!("<g transform=\"translate(1,1)\" stroke=\"black\" fill=\"none\">\n<rect width=\"{}\" height=\"{}\" stroke-width=\"2\"/>\n", width, height));
+
+    for i in 1..puzzle.height {
+        res.push_str(&format!("<line x1=\"0\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke-width=\"0.5\"/>\n", i*CELL, width, i*CELL));
+    }
+
+    for j in 1..puzzle.width {
+        res.push_str(&format!("<line x1=\"{}\" y1=\"0\" x2=\"{}\" y2=\"{}\" stroke-width=\"0.5\"/>\n", j*CELL, j*CELL, height));
+    }
+
+    for (num, path) in paths(puzzle, sol) {
+        let points: Vec<String> = path.iter().map(|&p| centre(p)).collect();
+
+        res.push_str(&format!("<polyline points=\"{}\" stroke=\"hsl({},70%,45%)\" stroke-width=\"6\" stroke-linejoin=\"round\"/>\n", points.join(" "), num*137%360));
+    }
+
+    res.push_str("</g>\n<g font-family=\"sans-serif\" font-size=\"24\" text-anchor=\"middle\" dominant-baseline=\"central\">\n");
+
+    for (index, &num) in puzzle.cells.iter().enumerate().filter(|&(_, &num)| num > 0) {
+        let (i, j) = puzzle.pos(index);
+
+        res.push_str(&format!("<text x=\"{}\" y=\"{}\">{}</text>\n", j*CELL+CELL/2+1, i*CELL+CELL/2+1, num));
+    }
+
+    res.push_str("</g>\n</svg>\n");
+
+    res
+}
@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::render::paths;
+use crate::{solve_numberlink, Error, Puzzle, Session, Sol, SolverConfig, P};
+
+// 数字の端を動かせる距離 (マンハッタン) の上限
+const MAX_MOVE: usize = 2;
+
+// 盤面への 1 か所の手直し
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Edit {
+    // from の数字を空白の to へ動かす
+    Move { from: P, to: P },
+    RemoveWall(P, P),
+    AddWall(P, P),
+    // 空白を数字 num の線が必ず通るセルにする
+    AddWaypoint(P, usize),
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Edit::Move { from, to } => write!(f, "move the number at {:?} to {:?}", from, to),
+            Edit::RemoveWall(p, q) => write!(f, "remove the wall between {:?} and {:?}", p, q),
+            Edit::AddWall(p, q) => write!(f, "add a wall between {:?} and {:?}", p, q),
+            Edit::AddWaypoint(p, num) => write!(f, "make the line of {} pass {:?}", num, p),
+        }
+    }
+}
+
+impl Edit {
+    // 小さいほど元の盤面に近い (壁と通る点は 1, 動かすのは動かした距離)
+    pub fn cost(&self) -> usize {
+        match *self {
+            Edit::Move { from, to } => from.0.abs_diff(to.0)+from.1.abs_diff(to.1),
+            Edit::RemoveWall(..) | Edit::AddWall(..) | Edit::AddWaypoint(..) => 1,
+        }
+    }
+
+    // 示す順: cost が同じなら壁, 通る点, 数字を動かすものの順
+    fn rank(&self) -> (usize, usize) {
+        let kind = match self {
+            Edit::RemoveWall(..) | Edit::AddWall(..) => 0,
+            Edit::AddWaypoint(..) => 1,
+            Edit::Move { .. } => 2,
+        };
+
+        (self.cost(), kind)
+    }
+
+    // 手直しした盤面 (当てはまらない盤面なら None)
+    pub fn apply(&self, puzzle: &Puzzle) -> Option<Puzzle> {
+        let mut res = puzzle.clone();
+
+        match *self {
+            Edit::Move { from, to } => {
+                let (u, v) = (puzzle.index(from), puzzle.index(to));
+
+                if puzzle.cells[u] == 0 || !is_blank(puzzle, v) {
+                    return None;
+                }
+
+                res.cells.swap(u, v);
+            },
+            Edit::RemoveWall(p, q) => {
+                let (u, v) = (puzzle.index(p), puzzle.index(q));
+
+                if !res.walls.remove(&(u.min(v), u.max(v))) {
+                    return None;
+                }
+            },
+            Edit::AddWall(p, q) => {
+                let (u, v) = (puzzle.index(p), puzzle.index(q));
+
+                // 線が通れる辺だけ
+                if !puzzle.edges().contains(&(u.min(v), u.max(v))) {
+                    return None;
+                }
+
+                res.walls.insert((u.min(v), u.max(v)));
+            },
+            Edit::AddWaypoint(p, num) => {
+                if !is_blank(puzzle, puzzle.index(p)) {
+                    return None;
+                }
+
+                res.add_waypoint(p, num);
+            },
+        }
+
+        Some(res)
+    }
+}
+
+// 解けない puzzle を解けるようにする 1 か所の手直しを, 元に近い順 (Edit::cost, 同じなら壁が先) に limit 個まで
+// 元の盤面が解けるなら None. 手直しした盤面が時間切れになったものは解けないとみなす
+pub fn suggest_solvable(puzzle: &Puzzle, config: &SolverConfig, limit: usize) -> Result<Option<Vec<Edit>>, Error> {
+    match solve_numberlink(puzzle, config) {
+        Ok(_) => return Ok(None),
+        Err(Error::NoSolution) => {},
+        Err(e) => return Err(e),
+    }
+
+    let mut edits: Vec<Edit> = puzzle.walls.iter().map(|&(u, v)| Edit::RemoveWall(puzzle.pos(u), puzzle.pos(v))).collect();
+
+    for (u, &num) in puzzle.cells.iter().enumerate() {
+        if num == 0 {
+            continue;
+        }
+
+        let from = puzzle.pos(u);
+
+        edits.extend((0..puzzle.cells.len()).filter(|&v| is_blank(puzzle, v)).map(|v| Edit::Move { from, to: puzzle.pos(v) }).filter(|edit| edit.cost() <= MAX_MOVE));
+    }
+
+    edits.sort_by_key(Edit::rank);
+
+    let mut res = vec![];
+
+    for edit in edits {
+        if res.len() >= limit {
+            break;
+        }
+
+        let next = match edit.apply(puzzle) {
+            Some(next) => next,
+            None => continue,
+        };
+
+        match solve_numberlink(&next, config) {
+            Ok(_) => res.push(edit),
+            Err(Error::NoSolution) | Err(Error::InvalidField) | Err(Error::Timeout(_)) => {},
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(Some(res))
+}
+
+// 答えが 2 つ以上ある puzzle の答えをただ 1 つにする 1 か所の手直しを, 元に近い順に limit 個まで
+// 2 つの答えで線の違うセル (決め手の足りないところ) だけを狙う: 片方の答えだけが使う辺に壁を置く,
+// 違うセルをどちらかの答えの線が通るようにする, 数字の端を違うセルへ動かす
+// 元の答えがただ 1 つなら None, 解けなければ Error::NoSolution
+pub fn suggest_unique(puzzle: &Puzzle, config: &SolverConfig, limit: usize) -> Result<Option<Vec<Edit>>, Error> {
+    let mut session = Session::new(puzzle, config)?;
+    let sol = session.solve()?;
+    let other = match session.find_other(&sol, &[])? {
+        Some(other) => other,
+        None => return Ok(None),
+    };
+
+    let (edges, others) = (undirected(&sol), undirected(&other));
+    let (labels, other_labels) = (labeled(puzzle, &sol), labeled(puzzle, &other));
+    // 通る線か, 出入りする辺の違うセル
+    let region: BTreeSet<P> = edges.symmetric_difference(&others).flat_map(|&(p, q)| vec![p, q])
+        .chain(labels.iter().filter(|(p, num)| other_labels.get(p) != Some(num)).map(|(&p, _)| p))
+        .collect();
+
+    let mut edits: Vec<Edit> = edges.symmetric_difference(&others).map(|&(p, q)| Edit::AddWall(p, q)).collect();
+
+    if !puzzle.dots {
+        for &p in region.iter().filter(|&&p| is_blank(puzzle, puzzle.index(p))) {
+            edits.extend(labels.get(&p).into_iter().chain(other_labels.get(&p)).map(|&num| Edit::AddWaypoint(p, num)));
+        }
+    }
+
+    for (u, &num) in puzzle.cells.iter().enumerate() {
+        if num == 0 {
+            continue;
+        }
+
+        let from = puzzle.pos(u);
+
+        edits.extend(region.iter().filter(|&&to| is_blank(puzzle, puzzle.index(to))).map(|&to| Edit::Move { from, to }).filter(|edit| edit.cost() <= MAX_MOVE));
+    }
+
+    edits.sort_by_key(Edit::rank);
+    edits.dedup();
+
+    let mut res = vec![];
+
+    for edit in edits {
+        if res.len() >= limit {
+            break;
+        }
+
+        let next = match edit.apply(puzzle) {
+            Some(next) => next,
+            None => continue,
+        };
+
+        match Session::new(&next, config).and_then(|mut session| session.is_unique()) {
+            Ok(true) => res.push(edit),
+            Ok(false) | Err(Error::NoSolution) | Err(Error::InvalidField) | Err(Error::Timeout(_)) => {},
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(Some(res))
+}
+
+// 向きを除いた, 答えの使う辺 (行優先で先のセルが前)
+fn undirected(sol: &Sol) -> BTreeSet<(P, P)> {
+    sol.iter().map(|&(p, q)| (p.min(q), p.max(q))).collect()
+}
+
+// セルごとの, そこを通る線の数字 (橋は後から通る線)
+fn labeled(puzzle: &Puzzle, sol: &Sol) -> BTreeMap<P, usize> {
+    paths(puzzle, sol).into_iter().flat_map(|(num, path)| path.into_iter().map(move |p| (p, num))).collect()
+}
+
+// 数字を置ける空白 (穴, 橋, 通る点でない)
+fn is_blank(puzzle: &Puzzle, u: usize) -> bool {
+    puzzle.cells[u] == 0 && !puzzle.is_hole(u) && !puzzle.is_bridge(u) && !puzzle.waypoints.contains_key(&u)
+}
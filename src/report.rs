@@ -0,0 +1,129 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::rate::{rate_with, Rating};
+use crate::render::{render_svg, render_url};
+use crate::{Error, Instant, Puzzle, SolverConfig};
+
+// 縮小図の幅 (px)
+const THUMBNAIL_WIDTH: usize = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReportFormat::Markdown => write!(f, "markdown"),
+            ReportFormat::Html => write!(f, "html"),
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" => Ok(ReportFormat::Markdown),
+            "html" => Ok(ReportFormat::Html),
+            _ => Err(format!("unknown report format `{}` (expected markdown or html)", s)),
+        }
+    }
+}
+
+// 1 問ぶんの行
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub puzzle: Puzzle,
+    // 解けないか時間切れなら Err
+    pub rating: Result<Rating, Error>,
+    // 解いて別解がないことを確かめるまでの時間
+    pub time: Duration,
+}
+
+// 名前の付いた盤面を rate_with で測り, 易しい順 (score の小さい順, 測れなかったものは最後に名前の順) に並べる
+pub fn entries(puzzles: Vec<(String, Puzzle)>, config: &SolverConfig) -> Vec<Entry> {
+    let mut res: Vec<Entry> = puzzles.into_iter().map(|(name, puzzle)| {
+        let start = Instant::now();
+        let rating = rate_with(&puzzle, config);
+
+        Entry { name, puzzle, rating, time: start.elapsed() }
+    }).collect();
+
+    res.sort_by(|a, b| match (&a.rating, &b.rating) {
+        (Ok(x), Ok(y)) => x.score.total_cmp(&y.score).then_with(|| a.name.cmp(&b.name)),
+        (Ok(_), Err(_)) => std::cmp::Ordering::Less,
+        (Err(_), Ok(_)) => std::cmp::Ordering::Greater,
+        (Err(_), Err(_)) => a.name.cmp(&b.name),
+    });
+
+    res
+}
+
+// 縮小図と解いた手間を並べた表. Markdown の中の図は HTML の img で書く
+pub fn render_report(entries: &[Entry], format: ReportFormat) -> String {
+    const COLUMNS: [&str; 10] = ["#", "puzzle", "board", "size", "pairs", "tier", "score", "solved by", "conflicts", "time"];
+
+    let rows: Vec<Vec<String>> = entries.iter().enumerate().map(|(k, entry)| {
+        let puzzle = &entry.puzzle;
+        let pairs = puzzle.cells.iter().filter(|&&num| num > 0).count()/2;
+        let mut row = vec![(k+1).to_string(), escape(&entry.name), thumbnail(puzzle), format!("{}x{}", puzzle.width, puzzle.height), pairs.to_string()];
+
+        match &entry.rating {
+            Ok(r) => row.extend(vec![r.tier.to_string(), format!("{:.0}", r.score), r.grade.to_string(), r.conflicts.to_string()]),
+            Err(e) => row.extend(vec![escape(&e.to_string()), String::new(), String::new(), String::new()]),
+        }
+
+        row.push(format!("{:.3}s", entry.time.as_secs_f64()));
+        row
+    }).collect();
+
+    match format {
+        ReportFormat::Markdown => {
+            let mut res = format!("| {} |\n|{}\n", COLUMNS.join(" | "), "---|".repeat(COLUMNS.len()));
+
+            for row in rows {
+                res.push_str(&format!("| {} |\n", row.iter().map(|cell| cell.replace('|', "\\|")).collect::<Vec<_>>().join(" | ")));
+            }
+
+            res
+        },
+        ReportFormat::Html => {
+            let mut res = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Numberlink puzzles</title>\n</head>\n<body>\n<table>\n");
+
+            res.push_str(&format!("<tr>{}</tr>\n", COLUMNS.iter().map(|name| format!("<th>{}</th>", name)).collect::<String>()));
+
+            for row in rows {
+                res.push_str(&format!("<tr>{}</tr>\n", row.iter().map(|cell| format!("<td>{}</td>", cell)).collect::<String>()));
+            }
+
+            res.push_str("</table>\n</body>\n</html>\n");
+            res
+        },
+    }
+}
+
+// 答えを書かない盤面の SVG を data: URI にした img (puzz.link の URL に書ける盤面はそこへのリンクにする)
+fn thumbnail(puzzle: &Puzzle) -> String {
+    let svg: String = render_svg(puzzle, &vec![]).bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'=' | b'/' | b':' | b',' => (b as char).to_string(),
+        _ => format!("%{:02X}", b),
+    }).collect();
+    let img = format!("<img width=\"{}\" src=\"data:image/svg+xml,{}\">", THUMBNAIL_WIDTH, svg);
+
+    if *puzzle == (Puzzle { cells: puzzle.cells.clone(), walls: puzzle.walls.clone(), ..Puzzle::new(puzzle.width, puzzle.height) }) {
+        format!("<a href=\"{}\">{}</a>", render_url(puzzle), img)
+    } else {
+        img
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
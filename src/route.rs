@@ -0,0 +1,115 @@
+use std::collections::BTreeSet;
+
+use crate::nets::Nets;
+use crate::optimize::{count_turns, minimize_cost, minimize_turns};
+use crate::render::paths;
+use crate::{solve_numberlink, Error, Puzzle, RuleSet, SolverConfig, P};
+
+// パズルでない配線 (基板や FPGA の配線の実験など) の入口. 格子の上で, 障害物を避けて端子の組 (ネット) を
+// 互いに交わらない道で結ぶ. 中身は線の通らない空白を許した Numberlink で, 3 つ以上の端子のネットは木でつなぐ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Net {
+    pub name: String,
+    // 2 つ以上
+    pub terminals: Vec<P>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RouteObjective {
+    // 最初に見つかった配線 (回り道は縮める)
+    #[default]
+    None,
+    // 道が通るセルの数の合計を最小にする
+    Length,
+    // 曲がる回数の合計を最小にする
+    Bends,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RouteProblem {
+    pub width: usize,
+    pub height: usize,
+    pub obstacles: Vec<P>,
+    pub nets: Vec<Net>,
+    pub objective: RouteObjective,
+}
+
+// 1 つのネットの道. 端子が 2 つなら 1 本, 3 つ以上なら木の根 (行優先で最初の端子) から葉までの道ごとに 1 本
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutedNet {
+    pub name: String,
+    pub paths: Vec<Vec<P>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Routing {
+    // problem.nets の順
+    pub nets: Vec<RoutedNet>,
+    // 道が通るセルの数と曲がる回数 (どちらも全部のネットの合計)
+    pub length: usize,
+    pub bends: usize,
+    // false なら時間切れで, objective の最良かどうか分からない
+    pub optimal: bool,
+}
+
+// config の規則は線の通らない空白を許し, ネットを木でつなぐものに置き換える (期限や backend などはそのまま)
+// 端子が盤外か, 障害物や他の端子と重なるか, 端子が 1 つしかないネットは InvalidField. 結べなければ NoSolution
+pub fn route_nets(problem: &RouteProblem, config: &SolverConfig) -> Result<Routing, Error> {
+    let puzzle = problem_puzzle(problem)?;
+    let config = SolverConfig { rules: RuleSet { coverage: false, loops: false, nets: Nets::Tree, ..config.rules }, ..config.clone() };
+    let (sol, optimal) = match problem.objective {
+        RouteObjective::None => (solve_numberlink(&puzzle, &config)?, true),
+        RouteObjective::Length => {
+            let res = minimize_cost(&puzzle, &config)?;
+
+            (res.sol, res.optimal)
+        },
+        RouteObjective::Bends => {
+            let res = minimize_turns(&puzzle, &config)?;
+
+            (res.sol, res.optimal)
+        },
+    };
+    let found = paths(&puzzle, &sol);
+    let nets = problem.nets.iter().enumerate().map(|(k, net)| RoutedNet {
+        name: net.name.clone(),
+        paths: found.iter().filter(|&&(num, ref path)| num == k+1 && path.len() > 1).map(|(_, path)| path.clone()).collect(),
+    }).collect();
+    let cells: BTreeSet<P> = sol.iter().flat_map(|&(p, q)| [p, q]).collect();
+
+    Ok(Routing { nets, length: cells.len(), bends: count_turns(&puzzle, &sol), optimal })
+}
+
+// ネット k の端子に数字 k+1, 障害物に穴を置いた盤面. 長さを最小にするときは全部のセルのコストを 1 にする
+fn problem_puzzle(problem: &RouteProblem) -> Result<Puzzle, Error> {
+    let mut puzzle = Puzzle::new(problem.width, problem.height);
+    let inside = |(i, j): P| i < problem.height && j < problem.width;
+
+    for &p in &problem.obstacles {
+        if !inside(p) {
+            return Err(Error::InvalidField);
+        }
+
+        puzzle.add_hole(p);
+    }
+
+    for (k, net) in problem.nets.iter().enumerate() {
+        if net.terminals.len() < 2 {
+            return Err(Error::InvalidField);
+        }
+
+        for &p in &net.terminals {
+            if !inside(p) || puzzle.get(p) > 0 || puzzle.is_hole(puzzle.index(p)) {
+                return Err(Error::InvalidField);
+            }
+
+            puzzle.set(p, k+1);
+        }
+    }
+
+    if problem.objective == RouteObjective::Length {
+        puzzle.costs = (0..puzzle.cells.len()).map(|u| (u, 1)).collect();
+    }
+
+    Ok(puzzle)
+}
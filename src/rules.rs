@@ -0,0 +1,465 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::str::FromStr;
+
+use varisat::{ExtendFormula, Lit, Var};
+
+use crate::nets::{back_chain, net_numbers, Nets};
+use crate::optimize::total_cost;
+use crate::{lengths, nets, Arc, Encoding, Puzzle, Sol, Topology, P};
+
+// 解が満たすべき規則. 既定は普通の Numberlink (全部のセルを埋め, 空白の閉路はあってよい)
+// 符号化 (lib::encode), 解き直し (Session の cuts), 検算 (verify) はどれもこれを見る
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RuleSet {
+    // 全部の空白に線を通す (false なら線の通らない空白があってよい)
+    pub coverage: bool,
+    // 手がかりとつながらない空白の閉路を許す (no_square, no_touch, no_adjacent があれば許さない)
+    pub loops: bool,
+    // 2x2 のセルが全部同じ線のものにならない (四角い盤面と重ねた盤面の各層だけ)
+    pub no_square: bool,
+    // 同じ線のセルどうしは, 隣り合うならその間を線が通る (線が自分に接しない)
+    pub no_touch: bool,
+    // no_touch より強く, 壁や閉じた層の間を挟んで隣り合うセルも同じ線なら線が結ぶ (そこは結べないので同じ線にならない)
+    pub no_adjacent: bool,
+    // 3 回以上現れる数字のつなぎ方
+    pub nets: Nets,
+    // 手がかりの数字を経路の長さ (セル数) として読む. 同じ数字のどの 2 つを結ぶかも解で決める
+    pub lengths: bool,
+    // 斜めに進める盤面で, 2x2 の 2 本の対角線を両方とも使ってよいか
+    pub crossing: Crossing,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet { coverage: true, loops: true, no_square: false, no_touch: false, no_adjacent: false, nets: Nets::Path, lengths: false, crossing: Crossing::Forbid }
+    }
+}
+
+// 斜めの arc どうしの交差
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Crossing {
+    // 交わらない (線が互いの斜めの間をすり抜けない)
+    #[default]
+    Forbid,
+    // 交わってよい (配線のように平面でなくてよいとき)
+    Allow,
+}
+
+impl FromStr for Crossing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "forbid" => Ok(Crossing::Forbid),
+            "allow" => Ok(Crossing::Allow),
+            _ => Err(format!("unknown crossing `{}` (expected forbid or allow)", s)),
+        }
+    }
+}
+
+impl RuleSet {
+    // 2x2 と接する規則は同じ数字のセルを同じ線とみなすので, 閉路 (数字が線と重なりうる) は許さない
+    pub fn allows_loops(&self) -> bool {
+        self.loops && !self.no_square && !self.no_touch && !self.no_adjacent
+    }
+
+    // SAT の符号化でしか扱えない規則 (なければ None). 木のネットは盤面にネットがあるときだけ効くので盤面の側で見る
+    pub(crate) fn sat_only(&self) -> Option<&'static str> {
+        if self.lengths {
+            Some("path-length clues")
+        } else if !self.coverage {
+            Some("uncovered cells")
+        } else if self.no_square {
+            Some("the 2x2 rule")
+        } else if self.no_touch {
+            Some("the touching rule")
+        } else if self.no_adjacent {
+            Some("the no-adjacent rule")
+        } else if !self.loops {
+            Some("forbidding loops")
+        } else {
+            None
+        }
+    }
+}
+
+// 線が通らなくてよい空白: 入るなら出る, 出るなら入る
+pub(crate) fn encode_optional<F: ExtendFormula>(formula: &mut F, outs: &[Lit], ins: &[Lit]) {
+    for &x in outs {
+        formula.add_clause(&std::iter::once(!x).chain(ins.iter().cloned()).collect::<Vec<Lit>>());
+    }
+
+    for &y in ins {
+        formula.add_clause(&std::iter::once(!y).chain(outs.iter().cloned()).collect::<Vec<Lit>>());
+    }
+}
+
+// 斜めの交差, 2x2 と接する規則の節. 後の 2 つは隣り合うセルの数字が等しければ真になる変数を作って使う. 使った変数の次の番号を返す
+pub(crate) fn encode_rules<F: ExtendFormula>(formula: &mut F, puzzle: &Puzzle, rules: &RuleSet, encoding: Encoding, numbers: &[Vec<usize>], mut next_var: usize) -> usize {
+    if rules.crossing == Crossing::Forbid {
+        for (x, y) in crossings(puzzle) {
+            formula.add_clause(&[x.negative(), y.negative()]);
+        }
+    }
+
+    if !rules.no_square && !rules.no_touch && !rules.no_adjacent {
+        return next_var;
+    }
+
+    let mut fresh = || {
+        next_var += 1;
+
+        Var::from_index(next_var-1)
+    };
+
+    // 橋は縦と横で数字が違うので, どちらの規則でも見ない
+    let plain = |u: usize| !puzzle.is_hole(u) && !puzzle.is_bridge(u);
+
+    // 線が通らないかもしれない空白は, 通るときだけ規則に入れる (covered[u] は入る arc があれば真)
+    let covered: Vec<Option<Var>> = (0..puzzle.cells.len()).map(|u| {
+        if rules.coverage || puzzle.cells[u] != 0 || !plain(u) {
+            return None;
+        }
+
+        let c = fresh();
+
+        for (v, d) in in_arcs(puzzle, u) {
+            formula.add_clause(&[puzzle.topology.arc_var(v, d).negative(), c.positive()]);
+        }
+
+        Some(c)
+    }).collect();
+    let uncovered = |u: usize| covered[u].map(|c| c.negative());
+
+    // 同じ線なら間を線が通る組と, 数字が等しいかを見る組 (2x2 は壁があっても同じ線なら数える)
+    let touching = touching(puzzle, rules);
+    let pairs: BTreeSet<(usize, usize)> = touching.iter().cloned().chain(if rules.no_square { neighbours(puzzle) } else { vec![] }).filter(|&(u, v)| plain(u) && plain(v)).collect();
+    let mut same: BTreeMap<(usize, usize), Var> = BTreeMap::new();
+
+    for (u, v) in pairs {
+        let e = fresh();
+
+        encode_equal(formula, encoding, &numbers[u], &numbers[v], e, &mut fresh);
+        same.insert((u, v), e);
+    }
+
+    if rules.no_touch || rules.no_adjacent {
+        for (&(u, v), &e) in same.iter().filter(|&(pair, _)| touching.contains(pair)) {
+            let (p, q) = (puzzle.pos(u), puzzle.pos(v));
+            let mut clause = vec![e.negative()];
+
+            clause.extend([(p, q), (q, p)].iter().filter_map(|&arc| crate::find_arc_var(puzzle, arc)).map(|x| x.positive()));
+            clause.extend(uncovered(u));
+            clause.extend(uncovered(v));
+            formula.add_clause(&clause);
+        }
+    }
+
+    if rules.no_square {
+        for block in squares(puzzle) {
+            let [a, b, c, d] = block;
+
+            // a-b, a-c, b-d が等しければ 4 つとも等しい
+            let edges = [(a, b), (a, c), (b, d)];
+
+            if let Some(es) = edges.iter().map(|&(u, v)| same.get(&(u.min(v), u.max(v))).copied()).collect::<Option<Vec<Var>>>() {
+                let mut clause: Vec<Lit> = es.iter().map(|e| e.negative()).collect();
+
+                clause.extend(block.iter().filter_map(|&u| uncovered(u)));
+                formula.add_clause(&clause);
+            }
+        }
+    }
+
+    next_var
+}
+
+// 同じ線なら間を線が通らなければならないセルの組 (u < v). no_touch は線が通れる隣, no_adjacent は盤面の上の隣も
+fn touching(puzzle: &Puzzle, rules: &RuleSet) -> BTreeSet<(usize, usize)> {
+    let mut res: BTreeSet<(usize, usize)> = BTreeSet::new();
+
+    if rules.no_touch || rules.no_adjacent {
+        res.extend(puzzle.edges());
+    }
+
+    if rules.no_adjacent {
+        res.extend(neighbours(puzzle));
+    }
+
+    res
+}
+
+// 壁や層の間が閉じているかによらず, 盤面の上で隣り合うセルの組 (u < v, 穴は除く)
+fn neighbours(puzzle: &Puzzle) -> Vec<(usize, usize)> {
+    (0..puzzle.cells.len()).flat_map(|u| {
+        (0..puzzle.topology.slots()).filter_map(move |d| puzzle.topology.step(puzzle.width, puzzle.height, puzzle.pos(u), d)).map(move |q| (u, puzzle.index(q)))
+    }).filter(|&(u, v)| u < v && !puzzle.is_hole(u) && !puzzle.is_hole(v)).collect()
+}
+
+// u に入る arc (v, v から見た向き)
+fn in_arcs(puzzle: &Puzzle, u: usize) -> Vec<(usize, usize)> {
+    let p = puzzle.pos(u);
+
+    puzzle.edges().into_iter().filter_map(|(a, b)| match (a == u, b == u) {
+        (true, _) => Some(b),
+        (_, true) => Some(a),
+        _ => None,
+    }).filter_map(|v| puzzle.direction(puzzle.pos(v), p).map(|d| (v, d))).collect()
+}
+
+// 数字が等しければ e が真 (等しくないときの e は自由: e は規則の節に否定でしか現れない)
+fn encode_equal<F: ExtendFormula>(formula: &mut F, encoding: Encoding, fu: &[usize], fv: &[usize], e: Var, fresh: &mut impl FnMut() -> Var) {
+    match encoding {
+        // bit ごとに, 違うときだけ真になりうる d を作り, どれも偽なら e
+        Encoding::Log => {
+            let mut clause = vec![e.positive()];
+
+            for (&x, &y) in fu.iter().zip(fv) {
+                let (x, y, d) = (Var::from_index(x), Var::from_index(y), fresh());
+
+                formula.add_clause(&[d.negative(), x.positive(), y.positive()]);
+                formula.add_clause(&[d.negative(), x.negative(), y.negative()]);
+                clause.push(d.positive());
+            }
+
+            formula.add_clause(&clause);
+        },
+        Encoding::Direct => {
+            for (&x, &y) in fu.iter().zip(fv) {
+                formula.add_clause(&[Var::from_index(x).negative(), Var::from_index(y).negative(), e.positive()]);
+            }
+        },
+    }
+}
+
+// 交わる斜めの arc の組 (2x2 の左上と右下, 右上と左下を結ぶ arc の, それぞれの向き)
+fn crossings(puzzle: &Puzzle) -> Vec<(Var, Var)> {
+    if puzzle.topology != Topology::Diagonal {
+        return vec![];
+    }
+
+    let arcs = |p: P, q: P| -> Vec<Var> { [(p, q), (q, p)].iter().filter_map(|&arc| crate::find_arc_var(puzzle, arc)).collect() };
+
+    squares(puzzle).into_iter().flat_map(|[a, b, c, d]| {
+        let (down, up) = (arcs(puzzle.pos(a), puzzle.pos(d)), arcs(puzzle.pos(b), puzzle.pos(c)));
+
+        down.iter().flat_map(|&x| up.iter().map(move |&y| (x, y))).collect::<Vec<(Var, Var)>>()
+    }).collect()
+}
+
+// 2x2 のセル [左上, 右上, 左下, 右下] (重ねた盤面では層をまたがない)
+fn squares(puzzle: &Puzzle) -> Vec<[usize; 4]> {
+    let rows = match puzzle.topology {
+        Topology::Square | Topology::Diagonal => puzzle.height,
+        Topology::Layers(rows) => rows,
+        _ => return vec![],
+    };
+
+    (0..puzzle.height.saturating_sub(1)).filter(|&i| (i+1)%rows != 0).flat_map(|i| {
+        (0..puzzle.width.saturating_sub(1)).map(move |j| [(i, j), (i, j+1), (i+1, j), (i+1, j+1)])
+    }).map(|block| block.map(|p| puzzle.index(p))).collect()
+}
+
+// 解いたあとに見つかる, 規則を破る arc の組 (どれも, その arc を全部使う解はない). 空なら解は規則を満たす
+pub(crate) fn cuts(puzzle: &Puzzle, rules: &RuleSet, sol: &Sol) -> Vec<Vec<Arc>> {
+    let mut res = if rules.lengths { lengths::cuts(puzzle, sol) } else { nets::cuts(puzzle, sol) };
+
+    if !rules.allows_loops() {
+        res.extend(loops(puzzle, sol));
+    }
+
+    // 数字の決まった通る点は, 逆にたどって始点に着かなければ (空白の閉路にあれば) その数字の線上にない
+    let ins = in_map(sol);
+
+    for (&u, _) in puzzle.waypoints.iter().filter(|&(_, &num)| num > 0) {
+        if let Some(chain) = back_chain(puzzle, &ins, puzzle.pos(u)).filter(|chain| !res.contains(chain)) {
+            res.push(chain);
+        }
+    }
+
+    res
+}
+
+// 解の中の閉路 (入る arc を逆にたどるとちょうど 1 周して戻る)
+fn loops(puzzle: &Puzzle, sol: &Sol) -> Vec<Vec<Arc>> {
+    let ins = in_map(sol);
+    let mut res: Vec<Vec<Arc>> = vec![];
+
+    for &(_, v) in sol {
+        if let Some(chain) = back_chain(puzzle, &ins, v) {
+            let closes = chain.last().map(|arc| arc.0) == Some(v);
+
+            if closes && !res.iter().any(|cycle| cycle.contains(&chain[0])) {
+                res.push(chain);
+            }
+        }
+    }
+
+    res
+}
+
+fn in_map(sol: &Sol) -> HashMap<P, Vec<P>> {
+    let mut ins: HashMap<P, Vec<P>> = HashMap::new();
+
+    for &(u, v) in sol {
+        ins.entry(v).or_default().push(u);
+    }
+
+    ins
+}
+
+// 符号化とは別に, 解が盤面と規則を満たすか確かめる (満たさなければ理由)
+pub fn verify(puzzle: &Puzzle, rules: &RuleSet, sol: &Sol) -> Result<(), String> {
+    let n = puzzle.cells.len();
+    let mut outs: Vec<Vec<usize>> = vec![vec![]; n];
+    let mut ins: Vec<Vec<usize>> = vec![vec![]; n];
+
+    for &(p, q) in sol {
+        if p.0 >= puzzle.height || p.1 >= puzzle.width || puzzle.direction(p, q).is_none() {
+            return Err(format!("arc {:?} does not join two neighbouring cells", (p, q)));
+        }
+
+        let (u, v) = (puzzle.index(p), puzzle.index(q));
+
+        if !puzzle.is_open(u, v) || sol.contains(&(q, p)) || outs[u].contains(&v) {
+            return Err(format!("arc {:?} crosses a wall, enters a hole or is used twice", (p, q)));
+        }
+
+        if puzzle.one_way.contains(&(v, u)) {
+            return Err(format!("arc {:?} goes the wrong way along a one-way passage", (p, q)));
+        }
+
+        outs[u].push(v);
+        ins[v].push(u);
+    }
+
+    if rules.crossing == Crossing::Forbid {
+        let used = |x: Var| sol.iter().any(|&arc| crate::find_arc_var(puzzle, arc) == Some(x));
+
+        if let Some((x, _)) = crossings(puzzle).into_iter().find(|&(x, y)| used(x) && used(y)) {
+            return Err(format!("two diagonal lines cross at {:?}", crate::var_arc(x, puzzle)));
+        }
+    }
+
+    let nets = net_numbers(puzzle);
+    let cnt = |num: usize| puzzle.cells.iter().filter(|&&c| c == num).count();
+
+    for u in 0..n {
+        let (o, i, num) = (outs[u].len(), ins[u].len(), puzzle.cells[u]);
+        let first = num > 0 && puzzle.cells[..u].iter().all(|&c| c != num);
+        let ok = if puzzle.is_hole(u) {
+            o == 0 && i == 0
+        } else if puzzle.is_bridge(u) {
+            o == 2 && i == 2 && ins[u].iter().all(|&w| outs[u].iter().any(|&v| puzzle.direction(puzzle.pos(w), puzzle.pos(u)) == puzzle.direction(puzzle.pos(u), puzzle.pos(v))))
+        } else if num == 0 {
+            i == o.min(1) && (o == 1 || (o > 1 && rules.nets == Nets::Tree && !nets.is_empty()) || (o == 0 && !rules.coverage && !puzzle.waypoints.contains_key(&u)))
+        } else if puzzle.dots || rules.lengths {
+            o+i == 1
+        } else if nets.contains(&num) {
+            match (rules.nets, first) {
+                (Nets::Path, _) => o <= 1 && i <= 1 && o+i >= 1,
+                (Nets::Tree, true) => i == 0 && o >= 1,
+                (Nets::Tree, false) => i == 1,
+            }
+        } else {
+            (first && o == 1 && i == 0) || (!first && o == 0 && i == 1)
+        };
+
+        if !ok {
+            return Err(format!("wrong number of lines at {:?}", puzzle.pos(u)));
+        }
+    }
+
+    // 成分ごとに: 閉路がなく, 手がかりの組は端どうし, ネットは全部のセル, 長さの手がかりはセル数
+    let (comp, lanes) = components(puzzle, sol);
+    let mut nodes: HashMap<usize, (Vec<usize>, usize)> = HashMap::new();
+
+    for (node, &c) in comp.iter().enumerate().filter(|&(node, _)| lanes[node] > 0) {
+        let entry = nodes.entry(c).or_default();
+
+        entry.0.push(node%n);
+        entry.1 += lanes[node];
+    }
+
+    for (cells, arcs) in nodes.values() {
+        let mut clues: Vec<usize> = cells.iter().cloned().filter(|&u| puzzle.cells[u] > 0).collect();
+
+        clues.sort_unstable();
+
+        let ok = match clues[..] {
+            [] => rules.allows_loops(),
+            _ if *arcs != 2*(cells.len()-1) => false,
+            [a, b] if puzzle.dots => a != b,
+            [a, b] if rules.lengths => puzzle.cells[a] == puzzle.cells[b] && cells.len() == puzzle.cells[a],
+            _ => {
+                let num = puzzle.cells[clues[0]];
+
+                !puzzle.dots && !rules.lengths && clues.iter().all(|&u| puzzle.cells[u] == num) && clues.len() == cnt(num)
+            },
+        };
+
+        if !ok {
+            return Err(format!("the lines through {:?} do not join their clues or form a loop", puzzle.pos(cells[0])));
+        }
+
+        if let Some(&limit) = clues.first().and_then(|&u| puzzle.limits.get(&puzzle.cells[u])).filter(|&&limit| cells.len() > limit) {
+            return Err(format!("the line of {} passes through more than {} cells", puzzle.cells[clues[0]], limit));
+        }
+    }
+
+    for (&u, &num) in puzzle.waypoints.iter().filter(|&(_, &num)| num > 0) {
+        if !nodes.get(&comp[u]).is_some_and(|(cells, _)| cells.iter().any(|&v| puzzle.cells[v] == num)) {
+            return Err(format!("the line of {} does not pass through {:?}", num, puzzle.pos(u)));
+        }
+    }
+
+    let covered = |u: usize| !outs[u].is_empty() || !ins[u].is_empty();
+
+    if let Some(budget) = puzzle.budget.filter(|&budget| total_cost(puzzle, sol) > budget) {
+        return Err(format!("the lines cost {}, more than the budget {}", total_cost(puzzle, sol), budget));
+    }
+
+    if rules.no_square {
+        if let Some(block) = squares(puzzle).into_iter().find(|block| block.iter().all(|&u| covered(u) && !puzzle.is_bridge(u) && comp[u] == comp[block[0]])) {
+            return Err(format!("one line fills the 2x2 block at {:?}", puzzle.pos(block[0])));
+        }
+    }
+
+    if rules.no_touch || rules.no_adjacent {
+        let joined = |u: usize, v: usize| outs[u].contains(&v) || outs[v].contains(&u);
+
+        if let Some((u, _)) = touching(puzzle, rules).into_iter().find(|&(u, v)| covered(u) && covered(v) && !puzzle.is_bridge(u) && !puzzle.is_bridge(v) && comp[u] == comp[v] && !joined(u, v)) {
+            return Err(format!("a line touches itself at {:?}", puzzle.pos(u)));
+        }
+    }
+
+    Ok(())
+}
+
+// 解の arc でつながった成分の番号と, 各点に接する arc の本数. 点はセル u (橋の縦の線だけ n+u)
+fn components(puzzle: &Puzzle, sol: &Sol) -> (Vec<usize>, Vec<usize>) {
+    let n = puzzle.cells.len();
+    let mut root: Vec<usize> = (0..2*n).collect();
+    let mut lanes = vec![0; 2*n];
+
+    fn find(root: &mut [usize], u: usize) -> usize {
+        if root[u] != u {
+            root[u] = find(root, root[u]);
+        }
+
+        root[u]
+    }
+
+    for &(p, q) in sol {
+        let d = puzzle.direction(p, q).unwrap_or(0);
+        let node = |u: usize| if puzzle.is_bridge(u) && d%2 == 1 { n+u } else { u };
+        let (u, v) = (node(puzzle.index(p)), node(puzzle.index(q)));
+        let (ru, rv) = (find(&mut root, u), find(&mut root, v));
+
+        root[ru] = rv;
+        lanes[u] += 1;
+        lanes[v] += 1;
+    }
+
+    ((0..2*n).map(|u| find(&mut root, u)).collect(), lanes)
+}
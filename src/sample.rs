@@ -0,0 +1,40 @@
+use crate::rng::Rng;
+use crate::session::Session;
+use crate::{Arc, Error, Puzzle, Sol, SolverConfig};
+
+// 盤面のセルいくつにつき 1 本の arc を仮定するか
+const CELLS_PER_PIN: usize = 8;
+
+// 異なる解を k 個まで, なるべくばらけるように選ぶ (データセット向け). 別解を節で除きながら数え上げると
+// 前の解と少ししか違わない解が続くので, 毎回でたらめに選んだ arc を使うと仮定して解き, 解けなければ仮定を半分ずつ外していく
+// 符号化の順番も seed から決める. 解が k 個ない盤面ではあるだけ返す. 同じ seed なら同じ解を同じ順に返す
+pub fn sample_solutions(puzzle: &Puzzle, config: &SolverConfig, k: usize, seed: u64) -> Result<Vec<Sol>, Error> {
+    let mut rng = Rng::new(seed);
+    let config = SolverConfig { seed: rng.next_u64().max(1), ..config.clone() };
+    let mut session = Session::new(puzzle, &config)?;
+    let arcs: Vec<Arc> = puzzle.edges().into_iter()
+        .flat_map(|(u, v)| [(puzzle.pos(u), puzzle.pos(v)), (puzzle.pos(v), puzzle.pos(u))])
+        .filter(|&(p, q)| puzzle.direction(p, q).is_some())
+        .collect();
+    let count = if arcs.is_empty() { 0 } else { (puzzle.cells.len()/CELLS_PER_PIN).max(1) };
+    let mut samples = vec![];
+
+    while samples.len() < k {
+        let mut pins: Vec<(Arc, bool)> = (0..count).map(|_| (arcs[rng.below(arcs.len())], true)).collect();
+
+        let sol = loop {
+            match session.solve_assuming(&pins) {
+                Ok(sol) => break sol,
+                Err(Error::NoSolution) if !pins.is_empty() => pins.truncate(pins.len()/2),
+                // 仮定なしで解けなければ, もう除いていない解はない
+                Err(Error::NoSolution) => return Ok(samples),
+                Err(e) => return Err(e),
+            }
+        };
+
+        session.exclude(&sol)?;
+        samples.push(sol);
+    }
+
+    Ok(samples)
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::{Puzzle, Topology, P};
+
+// 解かずに見つける, 答えが 1 つに決まらなそうなところ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Reason {
+    // 数字のない, 線でほかとつながらない領域. 閉路で埋めるしかないので, 埋められても向きの違う答えが必ず 2 つある
+    Clueless,
+    // 数字から離れた 2x2 の空白の集まり. 線の曲がり方を入れ替えられることが多い (必ずではない)
+    Pocket,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Suspect {
+    pub reason: Reason,
+    // 行優先
+    pub cells: Vec<P>,
+}
+
+impl fmt::Display for Suspect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            Reason::Clueless => write!(f, "clueless region of {} cells from {:?}", self.cells.len(), self.cells[0]),
+            Reason::Pocket => write!(f, "open blank area of {} cells from {:?}", self.cells.len(), self.cells[0]),
+        }
+    }
+}
+
+// 答えがただ 1 つかを 2 度解いて確かめる前のふるい分け (盤面の形だけを見るので大きな問題集にも速い)
+// 既定の規則 (全部の空白を埋め, 閉路は向きごとに数える) では Clueless があれば答えは 1 つではない. Pocket は疑わしいだけ
+pub fn screen(puzzle: &Puzzle) -> Vec<Suspect> {
+    let n = puzzle.cells.len();
+    let edges = puzzle.edges();
+    let mut adj = vec![vec![]; n];
+
+    for &(u, v) in &edges {
+        adj[u].push(v);
+        adj[v].push(u);
+    }
+
+    let mut res = vec![];
+
+    // 線が通れるセルの連結成分のうち数字 (点) を含まないもの. 通る点があれば線が来るので除く
+    let mut seen = vec![false; n];
+    let mut clueless = vec![false; n];
+
+    for s in (0..n).filter(|&s| !puzzle.is_hole(s)) {
+        if seen[s] {
+            continue;
+        }
+
+        let component = flood(&adj, &mut seen, s, |_| true);
+
+        if component.iter().all(|&u| puzzle.cells[u] == 0 && !puzzle.waypoints.contains_key(&u)) && component.len() > 1 {
+            component.iter().for_each(|&u| clueless[u] = true);
+            res.push(Suspect { reason: Reason::Clueless, cells: positions(puzzle, &component) });
+        }
+    }
+
+    // 4 セルとも空白で, 間の 4 辺が開いていて, どのセルも数字と隣り合わない 2x2 (Clueless の領域の中は除く). 重なったり接したりするものはまとめる
+    if matches!(puzzle.topology, Topology::Square | Topology::Diagonal) {
+        let blank = |u: usize| puzzle.cells[u] == 0 && !puzzle.is_hole(u) && !puzzle.is_bridge(u) && !puzzle.waypoints.contains_key(&u);
+        let detached = |u: usize| blank(u) && !clueless[u] && adj[u].iter().all(|&v| puzzle.cells[v] == 0);
+        let open = |u: usize, v: usize| adj[u].contains(&v);
+        let mut pocket = vec![false; n];
+
+        for i in 0..puzzle.height.saturating_sub(1) {
+            for j in 0..puzzle.width.saturating_sub(1) {
+                let [a, b, c, d] = [(i, j), (i, j+1), (i+1, j), (i+1, j+1)].map(|p| puzzle.index(p));
+
+                if [a, b, c, d].iter().all(|&u| detached(u)) && open(a, b) && open(a, c) && open(b, d) && open(c, d) {
+                    for u in [a, b, c, d] {
+                        pocket[u] = true;
+                    }
+                }
+            }
+        }
+
+        let mut seen = vec![false; n];
+
+        for s in (0..n).filter(|&s| pocket[s]) {
+            if !seen[s] {
+                let area = flood(&adj, &mut seen, s, |u| pocket[u]);
+
+                res.push(Suspect { reason: Reason::Pocket, cells: positions(puzzle, &area) });
+            }
+        }
+    }
+
+    res.sort();
+    res
+}
+
+// s から keep のセルだけをたどった成分
+fn flood(adj: &[Vec<usize>], seen: &mut [bool], s: usize, keep: impl Fn(usize) -> bool) -> Vec<usize> {
+    let mut stack = vec![s];
+    let mut res = vec![];
+
+    seen[s] = true;
+
+    while let Some(u) = stack.pop() {
+        res.push(u);
+
+        for &v in &adj[u] {
+            if !seen[v] && keep(v) {
+                seen[v] = true;
+                stack.push(v);
+            }
+        }
+    }
+
+    res
+}
+
+fn positions(puzzle: &Puzzle, cells: &[usize]) -> Vec<P> {
+    cells.iter().map(|&u| puzzle.pos(u)).collect::<BTreeSet<_>>().into_iter().collect()
+}
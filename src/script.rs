@@ -0,0 +1,290 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use varisat::{Lit, Var};
+
+use crate::optimize::{report, sequential_counter};
+use crate::template::template;
+use crate::{find_arc_var, gen_adjs, number_lits, Encoding, Error, Instant, Puzzle, Session, Sol, SolverConfig};
+
+// script FILE の Rhai のスクリプト. 盤面ごとに fn constrain(puzzle, b) を呼び, b で節と目的関数を足す
+//
+//     puzzle.width, puzzle.height, puzzle.cells[row][column]   盤面 (0 は空白)
+//     b.arc(r1, c1, r2, c2)   (r1, c1) から隣の (r2, c2) へ線が進むリテラル
+//     b.used(r, c)            (r, c) を線が通るリテラル
+//     b.number(r, c, n)       (r, c) を通る線の数字が n のリテラル (--lengths では使えない)
+//     b.fresh()               新しい変数のリテラル
+//     b.clause([...])         どれかが真という節
+//     b.minimize([...])       真のリテラルの数を最小にする (何度も呼べば足し合わせる)
+//
+// リテラルは DIMACS と同じく変数の番号 (1 から) で, 負なら否定
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scripted {
+    pub sol: Sol,
+    // minimize したときの真のリテラルの数
+    pub value: Option<usize>,
+    // 時間切れなどで途中の解を返したら false
+    pub optimal: bool,
+}
+
+type Res<T> = Result<T, Box<EvalAltResult>>;
+
+// スクリプトに渡す b. 節は解く前にまとめて Session に足す
+#[derive(Clone)]
+struct Builder(Rc<RefCell<State>>);
+
+struct State {
+    puzzle: Puzzle,
+    adjs: Vec<Vec<(usize, usize)>>,
+    encoding: Encoding,
+    // セルごとの数字の変数 (--lengths では数字を符号化しないので None)
+    numbers: Option<Vec<Vec<usize>>>,
+    next_var: usize,
+    clauses: Vec<Vec<Lit>>,
+    objective: Vec<Lit>,
+}
+
+impl State {
+    fn cell(&self, row: i64, column: i64) -> Res<usize> {
+        if row < 0 || column < 0 || row as usize >= self.puzzle.height || column as usize >= self.puzzle.width {
+            return Err(format!("cell ({}, {}) is not on the board", row, column).into());
+        }
+
+        Ok(self.puzzle.index((row as usize, column as usize)))
+    }
+
+    fn fresh(&mut self) -> Lit {
+        let x = Var::from_index(self.next_var);
+
+        self.next_var += 1;
+
+        x.positive()
+    }
+
+    // lits のどれかが真のとき真
+    fn any(&mut self, lits: &[Lit]) -> Lit {
+        let y = self.fresh();
+
+        self.clauses.push(std::iter::once(!y).chain(lits.iter().cloned()).collect());
+
+        for &lit in lits {
+            self.clauses.push(vec![!lit, y]);
+        }
+
+        y
+    }
+
+    // lits のどれもが真のとき真
+    fn all(&mut self, lits: &[Lit]) -> Lit {
+        if let [lit] = lits {
+            return *lit;
+        }
+
+        let y = self.fresh();
+
+        self.clauses.push(std::iter::once(y).chain(lits.iter().map(|&lit| !lit)).collect());
+
+        for &lit in lits {
+            self.clauses.push(vec![!y, lit]);
+        }
+
+        y
+    }
+
+    fn lits(&self, lits: Array) -> Res<Vec<Lit>> {
+        lits.into_iter().map(|lit| {
+            let n = lit.as_int().map_err(|ty| format!("expected a literal, got {}", ty))?;
+
+            if n == 0 || n.unsigned_abs() > self.next_var as u64 {
+                return Err(format!("unknown literal {}", n).into());
+            }
+
+            Ok(Lit::from_dimacs(n as isize))
+        }).collect()
+    }
+
+    fn arc(&self, (r1, c1): (i64, i64), (r2, c2): (i64, i64)) -> Res<i64> {
+        let (u, v) = (self.cell(r1, c1)?, self.cell(r2, c2)?);
+
+        match find_arc_var(&self.puzzle, (self.puzzle.pos(u), self.puzzle.pos(v))) {
+            Some(x) => Ok(x.positive().to_dimacs() as i64),
+            None => Err(format!("no arc from ({}, {}) to ({}, {})", r1, c1, r2, c2).into()),
+        }
+    }
+
+    fn used(&mut self, row: i64, column: i64) -> Res<i64> {
+        let u = self.cell(row, column)?;
+        let topology = self.puzzle.topology;
+        let lits: Vec<Lit> = self.adjs[u].iter()
+            .flat_map(|&(v, d)| vec![topology.arc_var(u, d).positive(), topology.arc_var(v, topology.opposite(d)).positive()])
+            .collect();
+
+        Ok(self.any(&lits).to_dimacs() as i64)
+    }
+
+    fn number(&mut self, row: i64, column: i64, num: i64) -> Res<i64> {
+        let u = self.cell(row, column)?;
+        let vars = match &self.numbers {
+            Some(numbers) => numbers[u].clone(),
+            None => return Err("numbers are not encoded with --lengths".into()),
+        };
+        let values = match self.encoding {
+            Encoding::Log => 1 << vars.len(),
+            Encoding::Direct => vars.len(),
+        };
+
+        if num < 1 || num as usize > values {
+            return Err(format!("number {} is out of range (1..={})", num, values).into());
+        }
+
+        let lits = number_lits(self.encoding, &vars, num as usize);
+
+        Ok(self.all(&lits).to_dimacs() as i64)
+    }
+}
+
+impl Script {
+    pub fn new(source: &str) -> Result<Script, Error> {
+        let mut engine = Engine::new();
+
+        // print の出力が解と混ざらないように
+        engine.on_print(|msg| eprintln!("{}", msg));
+        engine.register_type_with_name::<Builder>("Builder")
+            .register_fn("arc", |b: &mut Builder, r1: i64, c1: i64, r2: i64, c2: i64| b.0.borrow().arc((r1, c1), (r2, c2)))
+            .register_fn("used", |b: &mut Builder, row: i64, column: i64| b.0.borrow_mut().used(row, column))
+            .register_fn("number", |b: &mut Builder, row: i64, column: i64, num: i64| b.0.borrow_mut().number(row, column, num))
+            .register_fn("fresh", |b: &mut Builder| b.0.borrow_mut().fresh().to_dimacs() as i64)
+            .register_fn("clause", |b: &mut Builder, lits: Array| -> Res<()> {
+                let lits = b.0.borrow().lits(lits)?;
+
+                b.0.borrow_mut().clauses.push(lits);
+
+                Ok(())
+            })
+            .register_fn("minimize", |b: &mut Builder, lits: Array| -> Res<()> {
+                let lits = b.0.borrow().lits(lits)?;
+
+                b.0.borrow_mut().objective.extend(lits);
+
+                Ok(())
+            });
+
+        let ast = engine.compile(source).map_err(|e| Error::InvalidConstraint(e.to_string()))?;
+
+        if !ast.iter_functions().any(|f| f.name == "constrain" && f.params.len() == 2) {
+            return Err(Error::InvalidConstraint("the script defines no fn constrain(puzzle, b)".to_string()));
+        }
+
+        Ok(Script { engine, ast })
+    }
+}
+
+// スクリプトの節を足して解く. minimize されていれば optimize::minimize と同じく上界を下げて解き直す
+pub fn solve_script(puzzle: &Puzzle, config: &SolverConfig, script: &Script) -> Result<Scripted, Error> {
+    let deadline = config.timeout.map(|timeout| Instant::now()+timeout);
+    let mut session = Session::new(puzzle, config)?;
+    let numbers = match config.rules.lengths && !puzzle.dots {
+        true => None,
+        false => Some(template(puzzle, config).numbers.clone()),
+    };
+    let builder = Builder(Rc::new(RefCell::new(State {
+        puzzle: puzzle.clone(),
+        adjs: gen_adjs(puzzle),
+        encoding: config.encoding,
+        numbers,
+        next_var: session.next_var(),
+        clauses: vec![],
+        objective: vec![],
+    })));
+
+    // 返り値は使わない
+    let _: Dynamic = script.engine.call_fn(&mut Scope::new(), &script.ast, "constrain", (board(puzzle), builder.clone()))
+        .map_err(|e| Error::InvalidConstraint(e.to_string()))?;
+
+    let objective = {
+        let state = builder.0.borrow();
+
+        while session.next_var() < state.next_var {
+            session.fresh_var();
+        }
+
+        for clause in &state.clauses {
+            session.add_clause(clause);
+        }
+
+        state.objective.clone()
+    };
+
+    let sol = session.solve()?;
+
+    if objective.is_empty() {
+        return Ok(Scripted { sol, value: None, optimal: true });
+    }
+
+    let count = |session: &Session| objective.iter().filter(|&&lit| session.value(lit.var()) == lit.is_positive()).count();
+    let mut best = Scripted { sol, value: Some(count(&session)), optimal: false };
+
+    report(config, count(&session));
+
+    // 最初の解で 0 個なら数える節は作らない (幅 0 の counter は作れない)
+    if best.value == Some(0) {
+        best.optimal = true;
+
+        return Ok(best);
+    }
+
+    // 真のリテラルを y (lit ならば y) で数え, 「k 個以上」の出力の否定を仮定する
+    let ys: Vec<Var> = objective.iter().map(|&lit| {
+        let y = session.fresh_var();
+
+        session.add_clause(&[!lit, y.positive()]);
+
+        y
+    }).collect();
+    let at_least = sequential_counter(&mut session, &ys, best.value.unwrap_or(0));
+
+    loop {
+        let value = best.value.unwrap_or(0);
+
+        session.set_timeout(deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1))));
+
+        match session.run(&[at_least[value-1].negative()]) {
+            Ok(sol) => {
+                best = Scripted { sol, value: Some(count(&session)), optimal: false };
+
+                report(config, count(&session));
+
+                if best.value == Some(0) {
+                    best.optimal = true;
+
+                    return Ok(best);
+                }
+            },
+            Err(Error::NoSolution) => {
+                best.optimal = true;
+
+                return Ok(best);
+            },
+            Err(Error::Timeout(_)) | Err(Error::Interrupted(_)) | Err(Error::MemoryLimit(_)) => return Ok(best),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn board(puzzle: &Puzzle) -> Map {
+    let cells: Array = puzzle.rows().map(|row| Dynamic::from_array(row.iter().map(|&num| Dynamic::from(num as i64)).collect())).collect();
+    let mut map = Map::new();
+
+    map.insert("width".into(), Dynamic::from(puzzle.width as i64));
+    map.insert("height".into(), Dynamic::from(puzzle.height as i64));
+    map.insert("cells".into(), Dynamic::from_array(cells));
+
+    map
+}
@@ -0,0 +1,629 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{MatchedPath, Path, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use serde_json::Value;
+
+use crate::canonical::canonical_with;
+use crate::generate::{generate, MAX_GENERATE_CELLS};
+use crate::input::{arcs, parse_paths};
+use crate::metrics::Metrics;
+use crate::openapi::openapi;
+use crate::optimize::{minimize_cost, minimize_turns, Objective};
+use crate::render::{render_error_line, render_line, render_puzzle_json, render_url, Format};
+use crate::rules::verify;
+use crate::transform::{transform_sol, Transform};
+use crate::{solve_numberlink, try_parse_puzzle, Error, Instant, Interrupt, Progress, Puzzle, Session, Sol, SolverConfig};
+
+// WebSocket (と gRPC の Progress) で途中経過を送る間隔
+pub(crate) const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+// 終わった仕事の結果を取っておく数 (古いものから捨てる)
+const RETAINED_JOBS: usize = 1000;
+
+// serve の待ち受けるアドレスと仕事の列の大きさ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerConfig {
+    pub addr: String,
+    // 仕事の列を解くスレッドの数 (同時に解く仕事の上限). /solve などはこれとは別に要求ごとに解く
+    pub workers: usize,
+    // 待たせておける仕事の数
+    pub queue: usize,
+    // 覚えておく答えの数 (0 なら覚えない)
+    pub cache: usize,
+    // API キーと, キーごとの 1 分あたりの要求の上限 (0 なら上限なし). 空ならキーを確かめない
+    pub keys: BTreeMap<String, u32>,
+    // gRPC のサービスも待ち受けるアドレス (--features grpc)
+    pub grpc: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        let workers = thread::available_parallelism().map_or(1, |n| n.get());
+
+        ServerConfig { addr: "127.0.0.1:8080".to_string(), workers, queue: 100, cache: 1000, keys: BTreeMap::new(), grpc: None }
+    }
+}
+
+// HTTP と gRPC (grpc.rs) のハンドラが共有する状態
+pub(crate) struct Shared {
+    pub config: SolverConfig,
+    pub server: ServerConfig,
+    pub jobs: Mutex<Jobs>,
+    ready: Condvar,
+    pub metrics: Metrics,
+    cache: Mutex<Cache>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+// キーごとの要求の上限 (token bucket). 1 分に上限の数だけ溜まり, 要求ごとに 1 つ使う
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+// ハンドラの引数
+type Ctx = State<Arc<Shared>>;
+
+// 解いた盤面の代表 (canonical) ごとの, 代表の向きの答え (None は解なし). 一杯になったら古いものから捨てる
+#[derive(Default)]
+struct Cache {
+    answers: HashMap<Puzzle, Option<Sol>>,
+    order: VecDeque<Puzzle>,
+}
+
+#[derive(Default)]
+pub(crate) struct Jobs {
+    next: u64,
+    pub waiting: VecDeque<u64>,
+    pub all: BTreeMap<u64, Job>,
+}
+
+pub(crate) struct Job {
+    body: String,
+    pub status: Status,
+    pub progress: Arc<Progress>,
+    interrupt: Interrupt,
+}
+
+pub(crate) enum Status {
+    Queued,
+    Running,
+    // optimize の結果
+    Finished(Result<Box<Answer>, String>),
+    Cancelled,
+}
+
+// 解いた盤面と答え. 最適化したら曲がる回数, コストと最適か (時間切れなら false)
+pub(crate) struct Answer {
+    pub puzzle: Puzzle,
+    pub sol: Sol,
+    pub score: Option<(usize, usize, bool)>,
+}
+
+// 1 つのプロセスで解き続ける HTTP サーバ (serve). 待ち受けを始めたら stderr に書く. 本文はどれも JSON を返す
+//
+//     POST /solve     盤面 (puzz.link の URL, テキストか JSON の盤面) か {"puzzle": 盤面}. 答えは solve --format json の 1 行
+//     POST /check     {"puzzle": 盤面, "solution": 道} なら答えを確かめて {"valid":...}, 道がなければ {"solvable":...,"unique":...}
+//     POST /generate  {"width":W,"height":H,"pairs":N,"seed":S} (seed は省略できる). {"url":...,"puzzle":盤面の JSON}
+//     GET /progress   WebSocket. 最初のメッセージは /solve と同じ (JSON なら "objective": "turns" か "cost" で最適化もする).
+//                     解いている間は {"event":"progress","conflicts":N,"bound":B,"elapsed":秒} を送り,
+//                     最後に {"event":"result","solution":...} か {"event":"error","error":...} を送って閉じる. 途中で切れたら解くのをやめる
+//     POST /jobs      /progress の最初のメッセージと同じものを仕事の列に積み, 202 と {"id":...} を返す (列が一杯なら 503)
+//     GET /jobs/ID    {"id":...,"status":...}. status は queued (position も), running (conflicts と bound も), done, failed か cancelled
+//     GET /jobs/ID/result  終わっていれば status と結果 (solution か error), まだなら 202 と状態
+//     DELETE /jobs/ID 待っている仕事は取り消し, 解いている仕事は止める
+//     GET /openapi.json  これらの OpenAPI の文書 (openapi.rs)
+//     GET /metrics    要求と解いた数, 覚えた答えを使った数, かかった時間の Prometheus のテキスト形式 (metrics.rs)
+//
+// キーがあれば, どの要求も Authorization: Bearer KEY, X-API-Key: KEY か ?key=KEY (WebSocket 向け) でキーを付ける.
+// ないか知らないキーは 401, 上限を超えたら 429 (Retry-After に次に使えるまでの秒)
+//
+// 道は hint --drawn と同じ形 (テキストか JSON). 読めない本文は 400, 解けない盤面は 422, 時間切れは 503
+pub fn serve(server: &ServerConfig, config: SolverConfig) -> io::Result<()> {
+    let shared = Arc::new(Shared { config, server: server.clone(), jobs: Mutex::new(Jobs::default()), ready: Condvar::new(), metrics: Metrics::default(), cache: Mutex::new(Cache::default()), buckets: Mutex::new(HashMap::new()) });
+
+    for _ in 0..server.workers.max(1) {
+        let shared = shared.clone();
+
+        thread::spawn(move || work(&shared));
+    }
+
+    let app = Router::new()
+        .route("/solve", post(|State(shared): Ctx, body: String| reply(shared, body, solve)))
+        .route("/check", post(|State(shared): Ctx, body: String| reply(shared, body, check)))
+        .route("/generate", post(|State(shared): Ctx, body: String| reply(shared, body, generate_puzzle)))
+        .route("/progress", get(|State(shared): Ctx, ws: WebSocketUpgrade| async move { ws.on_upgrade(move |socket| stream(socket, shared)) }))
+        .route("/jobs", post(|State(shared): Ctx, body: String| async move { json(submit(&shared, body)) }))
+        .route("/jobs/{id}", get(|State(shared): Ctx, Path(id): Path<u64>| async move { json(status(&shared, id, false)) })
+            .delete(|State(shared): Ctx, Path(id): Path<u64>| async move { json(cancel(&shared, id)) }))
+        .route("/jobs/{id}/result", get(|State(shared): Ctx, Path(id): Path<u64>| async move { json(status(&shared, id, true)) }))
+        .route("/openapi.json", get(|| async { json((200, openapi().to_string())) }))
+        .route("/metrics", get(|State(shared): Ctx| async move { metrics(&shared) }))
+        .route_layer(middleware::from_fn_with_state(shared.clone(), authorize))
+        .route_layer(middleware::from_fn_with_state(shared.clone(), count))
+        .with_state(shared.clone());
+
+    tokio::runtime::Builder::new_multi_thread().enable_all().build()?.block_on(async {
+        let listener = tokio::net::TcpListener::bind(&server.addr).await?;
+
+        eprintln!("listening on http://{}", listener.local_addr()?);
+
+        #[cfg(feature = "grpc")]
+        if let Some(addr) = server.grpc.clone() {
+            let shared = shared.clone();
+
+            tokio::spawn(async move {
+                if let Err(msg) = crate::grpc::serve(addr, shared).await {
+                    eprintln!("grpc: {}", msg);
+                }
+            });
+        }
+
+        axum::serve(listener, app).await
+    })
+}
+
+// 解くのは時間がかかるので, 非同期の処理を止めないように別のスレッドで
+async fn reply(shared: Arc<Shared>, body: String, handle: fn(&str, &Shared) -> (u16, String)) -> (StatusCode, [(header::HeaderName, &'static str); 1], String) {
+    let res = tokio::task::spawn_blocking(move || handle(&body, &shared)).await;
+
+    json(res.unwrap_or_else(|e| (500, error(&e.to_string()))))
+}
+
+// 経路 (/jobs/{id} のような形) ごとに要求を数える
+async fn count(State(shared): Ctx, path: MatchedPath, request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let response = next.run(request).await;
+
+    shared.metrics.request(path.as_str(), response.status().as_u16(), start.elapsed());
+    response
+}
+
+async fn authorize(State(shared): Ctx, request: Request, next: Next) -> Response {
+    if shared.server.keys.is_empty() {
+        return next.run(request).await;
+    }
+
+    // 要求を借りたまま await しない (本文は Sync でない)
+    let found = {
+        let header = |name: header::HeaderName| request.headers().get(name).and_then(|value| value.to_str().ok());
+        let key = header(header::AUTHORIZATION).and_then(|value| value.strip_prefix("Bearer "))
+            .or_else(|| header(header::HeaderName::from_static("x-api-key")))
+            .or_else(|| request.uri().query().and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("key="))));
+
+        key.and_then(|key| shared.server.keys.get_key_value(key))
+    };
+
+    let (key, rate) = match found {
+        Some((key, &rate)) => (key, rate),
+        None => return json((401, error("missing or unknown API key"))).into_response(),
+    };
+
+    if let Err(wait) = take(&shared, key, rate) {
+        let retry = wait.as_secs_f64().ceil().max(1.0).to_string();
+
+        let mut response = json((429, error("rate limit exceeded"))).into_response();
+
+        response.headers_mut().insert(header::RETRY_AFTER, retry.parse().unwrap());
+        return response;
+    }
+
+    next.run(request).await
+}
+
+// key の bucket から 1 つ使う. 空なら次に溜まるまでの時間
+pub(crate) fn take(shared: &Shared, key: &str, rate: u32) -> Result<(), Duration> {
+    if rate == 0 {
+        return Ok(());
+    }
+
+    let now = Instant::now();
+    let per_second = rate as f64/60.0;
+    let mut buckets = shared.buckets.lock().unwrap();
+    let bucket = buckets.entry(key.to_string()).or_insert(Bucket { tokens: rate as f64, last: now });
+
+    bucket.tokens = (bucket.tokens+now.duration_since(bucket.last).as_secs_f64()*per_second).min(rate as f64);
+    bucket.last = now;
+
+    if bucket.tokens < 1.0 {
+        return Err(Duration::from_secs_f64((1.0-bucket.tokens)/per_second));
+    }
+
+    bucket.tokens -= 1.0;
+    Ok(())
+}
+
+// serve --keys のファイル. 1 行に 1 つ `KEY` か `KEY N` (1 分あたりの上限, 0 なら上限なし). N がなければ rate
+// 空行と # から後は読み飛ばす
+pub fn parse_keys(text: &str, rate: u32) -> Result<BTreeMap<String, u32>, String> {
+    let mut keys = BTreeMap::new();
+
+    for (k, line) in text.lines().enumerate() {
+        let words: Vec<&str> = line.split('#').next().unwrap_or("").split_whitespace().collect();
+
+        let limit = match words[..] {
+            [] => continue,
+            [_] => rate,
+            [_, limit] => limit.parse().map_err(|_| format!("line {}: invalid rate `{}`", k+1, limit))?,
+            _ => return Err(format!("line {}: expected `KEY` or `KEY RATE`", k+1)),
+        };
+
+        keys.insert(words[0].to_string(), limit);
+    }
+
+    Ok(keys)
+}
+
+fn metrics(shared: &Shared) -> (StatusCode, [(header::HeaderName, &'static str); 1], String) {
+    let waiting = shared.jobs.lock().unwrap().waiting.len();
+
+    (StatusCode::OK, [(header::CONTENT_TYPE, "text/plain; version=0.0.4")], shared.metrics.render(waiting))
+}
+
+fn json((status, body): (u16, String)) -> (StatusCode, [(header::HeaderName, &'static str); 1], String) {
+    (StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR), [(header::CONTENT_TYPE, "application/json")], body+"\n")
+}
+
+async fn stream(mut socket: WebSocket, shared: Arc<Shared>) {
+    let body = match socket.recv().await {
+        Some(Ok(Message::Text(body))) => body.to_string(),
+        _ => return,
+    };
+
+    // 要求ごとの進み具合と, 接続が切れたときに止める印
+    let progress = Arc::new(Progress::default());
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let config = SolverConfig { progress: Some(progress.clone()), interrupt: Some(interrupt.clone()), ..shared.config.clone() };
+    let start = Instant::now();
+    let mut task = {
+        let shared = shared.clone();
+
+        tokio::task::spawn_blocking(move || optimize(&body, &config, &shared))
+    };
+
+    let last = loop {
+        match tokio::time::timeout(PROGRESS_INTERVAL, &mut task).await {
+            Ok(res) => break match res.unwrap_or_else(|e| Err(e.to_string())) {
+                Ok(answer) => format!("{{\"event\":\"result\",{}}}", fields(&answer)),
+                Err(msg) => format!("{{\"event\":\"error\",\"error\":{}}}", Value::String(msg)),
+            },
+            Err(_) => {
+                let event = format!("{{\"event\":\"progress\",{},\"elapsed\":{:.3}}}", progress_fields(&progress), start.elapsed().as_secs_f64());
+
+                if socket.send(Message::Text(event.into())).await.is_err() {
+                    interrupt.store(true, Ordering::SeqCst);
+                    return;
+                }
+            },
+        }
+    };
+
+    if socket.send(Message::Text(last.into())).await.is_ok() {
+        let _ = socket.send(Message::Close(None)).await;
+    }
+}
+
+fn submit(shared: &Shared, body: String) -> (u16, String) {
+    match enqueue(shared, body) {
+        Some(id) => (202, format!("{{\"id\":\"{}\"}}", id)),
+        None => (503, error("too many jobs waiting")),
+    }
+}
+
+// 仕事を列に積む. 列が一杯なら None
+pub(crate) fn enqueue(shared: &Shared, body: String) -> Option<u64> {
+    let mut jobs = shared.jobs.lock().unwrap();
+
+    if jobs.waiting.len() >= shared.server.queue {
+        return None;
+    }
+
+    let id = jobs.next;
+
+    jobs.next += 1;
+    jobs.waiting.push_back(id);
+    jobs.all.insert(id, Job { body, status: Status::Queued, progress: Arc::new(Progress::default()), interrupt: Arc::new(AtomicBool::new(false)) });
+    shared.ready.notify_one();
+
+    Some(id)
+}
+
+// result なら終わった仕事の結果も付け, 終わっていなければ 202
+fn status(shared: &Shared, id: u64, result: bool) -> (u16, String) {
+    let jobs = shared.jobs.lock().unwrap();
+    let job = match jobs.all.get(&id) {
+        Some(job) => job,
+        None => return (404, error("no such job")),
+    };
+
+    let status = match &job.status {
+        Status::Queued => format!("\"queued\",\"position\":{}", jobs.waiting.iter().position(|&other| other == id).unwrap_or(0)),
+        Status::Running => format!("\"running\",{}", progress_fields(&job.progress)),
+        Status::Finished(Ok(answer)) if result => format!("\"done\",{}", fields(answer)),
+        Status::Finished(Err(msg)) if result => format!("\"failed\",\"error\":{}", Value::String(msg.clone())),
+        Status::Finished(Ok(_)) => "\"done\"".to_string(),
+        Status::Finished(Err(_)) => "\"failed\"".to_string(),
+        Status::Cancelled => "\"cancelled\"".to_string(),
+    };
+    let code = match job.status {
+        Status::Queued | Status::Running if result => 202,
+        _ => 200,
+    };
+
+    (code, format!("{{\"id\":\"{}\",\"status\":{}}}", id, status))
+}
+
+fn cancel(shared: &Shared, id: u64) -> (u16, String) {
+    match cancel_job(shared, id) {
+        true => status(shared, id, false),
+        false => (404, error("no such job")),
+    }
+}
+
+// 待っている仕事は取り消し, 解いている仕事は止める. そんな仕事がなければ false
+pub(crate) fn cancel_job(shared: &Shared, id: u64) -> bool {
+    let mut jobs = shared.jobs.lock().unwrap();
+
+    jobs.waiting.retain(|&other| other != id);
+
+    let job = match jobs.all.get_mut(&id) {
+        Some(job) => job,
+        None => return false,
+    };
+
+    // 解いている仕事は Interrupted で終わり, work が取り消しにする
+    if let Status::Queued = job.status {
+        job.status = Status::Cancelled;
+    }
+
+    job.interrupt.store(true, Ordering::SeqCst);
+    true
+}
+
+// 仕事の列から 1 つずつ取って解く
+fn work(shared: &Shared) {
+    loop {
+        let (id, body, config) = {
+            let mut jobs = shared.ready.wait_while(shared.jobs.lock().unwrap(), |jobs| jobs.waiting.is_empty()).unwrap();
+            let id = jobs.waiting.pop_front().unwrap();
+            let job = jobs.all.get_mut(&id).unwrap();
+            let config = SolverConfig { progress: Some(job.progress.clone()), interrupt: Some(job.interrupt.clone()), ..shared.config.clone() };
+
+            job.status = Status::Running;
+            (id, job.body.clone(), config)
+        };
+
+        let res = optimize(&body, &config, shared);
+        let mut jobs = shared.jobs.lock().unwrap();
+
+        if let Some(job) = jobs.all.get_mut(&id) {
+            job.status = match job.interrupt.load(Ordering::SeqCst) {
+                true => Status::Cancelled,
+                false => Status::Finished(res.map(Box::new)),
+            };
+        }
+
+        let done: Vec<u64> = jobs.all.iter().filter(|(_, job)| matches!(job.status, Status::Finished(_) | Status::Cancelled)).map(|(&id, _)| id).collect();
+
+        for id in done.iter().take(done.len().saturating_sub(RETAINED_JOBS)) {
+            jobs.all.remove(id);
+        }
+    }
+}
+
+fn progress_fields(progress: &Progress) -> String {
+    let bound = progress.bound().map_or("null".to_string(), |bound| bound.to_string());
+
+    format!("\"conflicts\":{},\"bound\":{}", progress.conflicts(), bound)
+}
+
+// /progress と /jobs の要求を解く
+fn optimize(body: &str, config: &SolverConfig, shared: &Shared) -> Result<Answer, String> {
+    let (puzzle, request) = read(body).map_err(|e| e.to_string())?;
+
+    let objective = match request.as_ref().and_then(|request| request.get("objective")) {
+        Some(objective) => objective.as_str().unwrap_or("").parse::<Objective>()?,
+        None => Objective::None,
+    };
+
+    solve_request(puzzle, objective, config, shared).map_err(|e| e.to_string())
+}
+
+// objective があれば最適化する. 最適化しないなら覚えた答えを使う
+pub(crate) fn solve_request(puzzle: Puzzle, objective: Objective, config: &SolverConfig, shared: &Shared) -> Result<Answer, Error> {
+    match objective {
+        Objective::None => solve_cached(&puzzle, config, shared).map(|sol| Answer { puzzle, sol, score: None }),
+        Objective::Turns | Objective::Cost => {
+            let start = Instant::now();
+            let best = match objective {
+                Objective::Turns => minimize_turns(&puzzle, config),
+                _ => minimize_cost(&puzzle, config),
+            };
+
+            shared.metrics.solved(&best, start.elapsed());
+            best.map(|best| Answer { puzzle, sol: best.sol, score: Some((best.turns, best.cost, best.optimal)) })
+        },
+    }
+}
+
+// /progress と /jobs の結果の欄 ("solution":...). 最適化したら turns, cost と optimal も付ける
+fn fields(answer: &Answer) -> String {
+    let score = match answer.score {
+        Some((turns, cost, optimal)) => format!(",\"turns\":{},\"cost\":{},\"optimal\":{}", turns, cost, optimal),
+        None => String::new(),
+    };
+
+    format!("\"solution\":{}{}", render_line(&answer.puzzle, &answer.sol, Format::Json), score)
+}
+
+fn solve(body: &str, shared: &Shared) -> (u16, String) {
+    let puzzle = match read(body) {
+        Ok((puzzle, _)) => puzzle,
+        Err(e) => return failure(&e),
+    };
+
+    match solve_cached(&puzzle, &shared.config, shared) {
+        Ok(sol) => (200, render_line(&puzzle, &sol, Format::Json)),
+        Err(e) => failure(&e),
+    }
+}
+
+// 回転, 裏返しと数字の振り直しで移り合う盤面を前に解いていれば, その答えを移して返す
+// 時間切れなどは覚えない. 向きで答えの変わる盤面は canonical が向きを変えないので, そのまま同じ盤面だけが当たる
+pub(crate) fn solve_cached(puzzle: &Puzzle, config: &SolverConfig, shared: &Shared) -> Result<Sol, Error> {
+    let solve = || {
+        let start = Instant::now();
+        let res = solve_numberlink(puzzle, config);
+
+        shared.metrics.solved(&res, start.elapsed());
+        res
+    };
+
+    // 制約はセルの位置で書くので, 回した盤面の答えは使えない
+    if shared.server.cache == 0 || !config.constraints.is_empty() {
+        return solve();
+    }
+
+    let (key, t) = canonical_with(puzzle);
+    let back = |sol: &Sol| match t {
+        Transform::Identity => sol.clone(),
+        _ => transform_sol(&key, sol, t.inverse()),
+    };
+
+    if let Some(answer) = shared.cache.lock().unwrap().answers.get(&key) {
+        shared.metrics.cached(true);
+        return answer.as_ref().map(back).ok_or(Error::NoSolution);
+    }
+
+    shared.metrics.cached(false);
+    let res = solve();
+
+    let answer = match &res {
+        Ok(sol) if t == Transform::Identity => Some(sol.clone()),
+        Ok(sol) => Some(transform_sol(puzzle, sol, t)),
+        Err(Error::NoSolution) => None,
+        Err(_) => return res,
+    };
+    let mut cache = shared.cache.lock().unwrap();
+
+    if cache.answers.insert(key.clone(), answer).is_none() {
+        cache.order.push_back(key);
+    }
+
+    while cache.order.len() > shared.server.cache {
+        let old = cache.order.pop_front().unwrap();
+
+        cache.answers.remove(&old);
+    }
+
+    res
+}
+
+fn check(body: &str, shared: &Shared) -> (u16, String) {
+    let (puzzle, request) = match read(body) {
+        Ok(read) => read,
+        Err(e) => return failure(&e),
+    };
+
+    let solution = match request.as_ref().and_then(|request| request.get("solution")) {
+        Some(Value::String(s)) => Some(parse_paths(s)),
+        Some(value) => Some(parse_paths(&value.to_string())),
+        None => None,
+    };
+
+    match solution {
+        Some(Some((_, _, paths))) => match verify(&puzzle, &shared.config.rules, &arcs(&puzzle, &paths)) {
+            Ok(()) => (200, "{\"valid\":true}".to_string()),
+            Err(reason) => (200, format!("{{\"valid\":false,\"reason\":{}}}", Value::String(reason))),
+        },
+        Some(None) => (400, error("invalid solution (expected each path as a chain of cells with the same name, or JSON paths)")),
+        None => match unique(&puzzle, shared) {
+            Ok(unique) => (200, format!("{{\"solvable\":true,\"unique\":{}}}", unique)),
+            Err(Error::NoSolution) => (200, "{\"solvable\":false,\"unique\":false}".to_string()),
+            Err(e) => failure(&e),
+        },
+    }
+}
+
+pub(crate) fn unique(puzzle: &Puzzle, shared: &Shared) -> Result<bool, Error> {
+    let start = Instant::now();
+    let res = Session::new(puzzle, &shared.config).and_then(|mut session| session.is_unique());
+
+    shared.metrics.solved(&res, start.elapsed());
+    res
+}
+
+fn generate_puzzle(body: &str, _: &Shared) -> (u16, String) {
+    let request: Value = match serde_json::from_str(body) {
+        Ok(request) => request,
+        Err(_) => return (400, error("invalid request (expected {\"width\":W,\"height\":H,\"pairs\":N})")),
+    };
+    let field = |key: &str| request.get(key).and_then(Value::as_u64);
+
+    let (width, height, pairs) = match (field("width"), field("height"), field("pairs")) {
+        (Some(width), Some(height), Some(pairs)) => (width as usize, height as usize, pairs as usize),
+        _ => return (400, error("invalid request (expected {\"width\":W,\"height\":H,\"pairs\":N})")),
+    };
+
+    match width.checked_mul(height) {
+        Some(cells) if cells <= MAX_GENERATE_CELLS => {
+            if pairs == 0 || pairs > cells/2 {
+                return (400, error(&format!("invalid pairs {} (expected 1 to {} on a {}x{} board)", pairs, cells/2, width, height)));
+            }
+        },
+        _ => return (413, error(&format!("board too large ({}x{}, at most {} cells)", width, height, MAX_GENERATE_CELLS))),
+    }
+
+    match generate(width, height, pairs, field("seed").unwrap_or(0)) {
+        // 穴のある盤面は URL に書けない
+        Some(puzzle) => {
+            let url = match puzzle.holes.is_empty() {
+                true => Value::String(render_url(&puzzle)).to_string(),
+                false => "null".to_string(),
+            };
+
+            (200, format!("{{\"url\":{},\"puzzle\":{}}}", url, render_puzzle_json(&puzzle).trim_end()))
+        },
+        None => (422, error(&format!("no {}x{} puzzle with {} pairs found", width, height, pairs))),
+    }
+}
+
+// 本文の盤面と, JSON の {"puzzle": ...} で送られたならその中身
+fn read(body: &str) -> Result<(Puzzle, Option<Value>), Error> {
+    let request = serde_json::from_str::<Value>(body).ok().filter(|request| request.get("puzzle").is_some());
+
+    let puzzle = match request.as_ref().and_then(|request| request.get("puzzle")) {
+        Some(Value::String(s)) => try_parse_puzzle(s.trim())?,
+        Some(value) => try_parse_puzzle(&value.to_string())?,
+        None => try_parse_puzzle(body.trim())?,
+    };
+
+    Ok((puzzle, request))
+}
+
+fn failure(e: &Error) -> (u16, String) {
+    let status = match e {
+        Error::InvalidUrl | Error::InvalidField | Error::InvalidPin => 400,
+        Error::TooLarge(..) => 413,
+        Error::NoSolution => 422,
+        Error::Timeout(_) | Error::Interrupted(_) | Error::MemoryLimit(_) => 503,
+        _ => 500,
+    };
+
+    (status, render_error_line(e, Format::Json))
+}
+
+fn error(msg: &str) -> String {
+    format!("{{\"error\":{}}}", Value::String(msg.to_string()))
+}
@@ -0,0 +1,326 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc as Shared;
+use std::time::Duration;
+
+use varisat::{ExtendFormula, Lit, Var};
+
+use crate::backend::{self, SatBackend, Stop};
+use crate::events::Event;
+use crate::monitor::{formula_memory, Control};
+use crate::{encode, phase, rules, find_arc_var, var_arc, Arc, Error, Instant, Interrupt, Puzzle, Sol, SolverConfig, Stats};
+
+// 1 つの盤面を符号化したソルバを持ち続け, 仮定 (assumption) を変えた問い合わせで学習節を使い回す
+// 時間切れ・中断のあとは varisat の状態が壊れるので, 以降の問い合わせは同じエラーを返す
+pub struct Session {
+    backend: Box<dyn SatBackend>,
+    puzzle: Puzzle,
+    config: SolverConfig,
+    control: Shared<Control>,
+    next_var: usize,
+    stats: Stats,
+    broken: Option<Error>,
+    // 直前に見つかった解の割り当て
+    model: Vec<Lit>,
+    // 初期 phase のために極性を反転して solver に渡している変数
+    flips: Vec<bool>,
+}
+
+impl Session {
+    pub fn new(puzzle: &Puzzle, config: &SolverConfig) -> Result<Session, Error> {
+        Session::with_cancel(puzzle, config, None)
+    }
+
+    // cancel は config.interrupt とは別に外から止めるためのフラグ (portfolio の負けた側など)
+    pub(crate) fn with_cancel(puzzle: &Puzzle, config: &SolverConfig, cancel: Option<&Interrupt>) -> Result<Session, Error> {
+        Session::with_encoder(puzzle, config, cancel, |formula| encode(puzzle, config, formula))
+    }
+
+    // 盤面の一部だけを符号化するとき用 (encoder は次に使える変数番号を返す)
+    pub(crate) fn with_encoder(
+        puzzle: &Puzzle,
+        config: &SolverConfig,
+        cancel: Option<&Interrupt>,
+        encoder: impl FnOnce(&mut Counted) -> Result<usize, Error>,
+    ) -> Result<Session, Error> {
+        let control = Shared::new(Control {
+            interrupts: config.interrupt.iter().chain(cancel).cloned().collect(),
+            memory_limit: config.max_memory,
+            progress: config.progress.clone(),
+            events: config.events.clone(),
+            ..Control::default()
+        });
+
+        let backend = backend::open(puzzle, config, &control)?;
+
+        Session::with_backend(puzzle, config, control, backend, encoder)
+    }
+
+    // crate の外で実装した backend で解くとき用 (期限と中断の確認は backend に任せる)
+    pub fn with_custom_backend(puzzle: &Puzzle, config: &SolverConfig, backend: Box<dyn SatBackend>) -> Result<Session, Error> {
+        let control = Shared::new(Control { memory_limit: config.max_memory, progress: config.progress.clone(), events: config.events.clone(), ..Control::default() });
+
+        Session::with_backend(puzzle, config, control, backend, |formula| encode(puzzle, config, formula))
+    }
+
+    fn with_backend(
+        puzzle: &Puzzle,
+        config: &SolverConfig,
+        control: Shared<Control>,
+        mut backend: Box<dyn SatBackend>,
+        encoder: impl FnOnce(&mut Counted) -> Result<usize, Error>,
+    ) -> Result<Session, Error> {
+        let flips = phase::flips(puzzle, config);
+        let _ = control.flips.set(flips.clone());
+        let mut counted = Counted { backend: backend.as_mut(), flips: &flips, clauses: 0, lits: 0, vars: 0, buf: vec![] };
+        let next_var = encoder(&mut counted)?;
+        let memory = formula_memory(counted.clauses, counted.lits, next_var as u64);
+
+        control.memory.store(memory, Ordering::Relaxed);
+
+        Ok(Session {
+            backend,
+            puzzle: puzzle.clone(),
+            config: config.clone(),
+            control,
+            next_var,
+            stats: Stats::default(),
+            broken: None,
+            model: vec![],
+            flips,
+        })
+    }
+
+    pub fn puzzle(&self) -> &Puzzle {
+        &self.puzzle
+    }
+
+    // 直前の問い合わせの統計
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn solve(&mut self) -> Result<Sol, Error> {
+        self.solve_assuming(&[])
+    }
+
+    // pins の各 arc を使う (true) / 使わない (false) と仮定して解く
+    pub fn solve_assuming(&mut self, pins: &[(Arc, bool)]) -> Result<Sol, Error> {
+        let lits = self.pin_lits(pins)?;
+
+        self.run(&lits)
+    }
+
+    // sol と異なる解を 1 つ探す (なければ None)
+    pub fn find_other(&mut self, sol: &Sol, pins: &[(Arc, bool)]) -> Result<Option<Sol>, Error> {
+        let mut lits = self.pin_lits(pins)?;
+        let act = self.fresh_var();
+
+        // 全セルが覆われるので, 別解は sol の arc のどれかを使わない
+        let mut clause = vec![act.negative()];
+
+        for &arc in sol {
+            clause.push(find_arc_var(&self.puzzle, arc).ok_or(Error::InvalidPin)?.negative());
+        }
+
+        self.add_clause(&clause);
+
+        lits.push(act.positive());
+
+        let res = self.run(&lits);
+
+        // 使い終わった節は act を偽に固定して無効化する
+        self.add_clause(&[act.negative()]);
+
+        match res {
+            Ok(other) => Ok(Some(other)),
+            Err(Error::NoSolution) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // sol を以後の問い合わせの解から除く. 全セルが覆われるなら別解は sol の arc のどれかを使わないが,
+    // 覆わなくてよいときは sol に線を足した解もあるので, sol にない arc を使うことも許す
+    pub(crate) fn exclude(&mut self, sol: &Sol) -> Result<(), Error> {
+        let mut clause = sol.iter().map(|&arc| find_arc_var(&self.puzzle, arc).map(|x| x.negative()).ok_or(Error::InvalidPin)).collect::<Result<Vec<Lit>, Error>>()?;
+
+        if !self.config.rules.coverage {
+            let puzzle = &self.puzzle;
+            let others = puzzle.edges().into_iter()
+                .flat_map(|(u, v)| [(puzzle.pos(u), puzzle.pos(v)), (puzzle.pos(v), puzzle.pos(u))])
+                .filter(|arc| !sol.contains(arc))
+                .filter_map(|arc| find_arc_var(puzzle, arc))
+                .map(|x| x.positive());
+
+            clause.extend(others);
+        }
+
+        self.add_clause(&clause);
+
+        Ok(())
+    }
+
+    pub fn is_unique(&mut self) -> Result<bool, Error> {
+        let sol = self.solve()?;
+
+        Ok(self.find_other(&sol, &[])?.is_none())
+    }
+
+    // 残り時間を配る用 (監視は作成時に timeout を指定したときだけ入る)
+    pub(crate) fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.config.timeout = timeout;
+    }
+
+    pub(crate) fn add_clause(&mut self, lits: &[Lit]) {
+        let lits: Vec<Lit> = lits.iter().map(|&lit| flip(&self.flips, lit)).collect();
+
+        self.backend.add_clause(&lits);
+    }
+
+    // 直前の解で x が真か
+    pub(crate) fn value(&self, x: Var) -> bool {
+        self.model.get(x.index()).is_some_and(|lit| lit.is_positive())
+    }
+
+    // 直前に解けなかったとき, 矛盾に使った仮定 (分からなければ None)
+    pub(crate) fn failed(&self) -> Option<Vec<Lit>> {
+        self.backend.failed().map(|lits| lits.into_iter().map(|lit| flip(&self.flips, lit)).collect())
+    }
+
+    // 次に fresh_var が返す変数の番号
+    #[cfg(feature = "script")]
+    pub(crate) fn next_var(&self) -> usize {
+        self.next_var
+    }
+
+    pub(crate) fn fresh_var(&mut self) -> Var {
+        let x = Var::from_index(self.next_var);
+
+        self.next_var += 1;
+
+        x
+    }
+
+    fn pin_lits(&self, pins: &[(Arc, bool)]) -> Result<Vec<Lit>, Error> {
+        pins.iter().map(|&(arc, used)| {
+            find_arc_var(&self.puzzle, arc).map(|x| Lit::from_var(x, used)).ok_or(Error::InvalidPin)
+        }).collect()
+    }
+
+    pub(crate) fn run(&mut self, assumptions: &[Lit]) -> Result<Sol, Error> {
+        if let Some(e) = &self.broken {
+            return Err(e.clone());
+        }
+
+        let start = Instant::now();
+        let conflicts = self.control.conflicts.load(Ordering::Relaxed);
+
+        // 探索を始める前から上限を超えているなら解かない
+        if let Some(limit) = self.control.memory_limit {
+            if self.control.memory.load(Ordering::Relaxed) > limit {
+                self.stats = Stats { memory: self.control.memory.load(Ordering::Relaxed), ..Stats::default() };
+
+                return Err(Error::MemoryLimit(self.stats));
+            }
+        }
+
+        *self.control.deadline.lock().unwrap() = self.config.timeout.map(|timeout| start+timeout);
+
+        let assumptions: Vec<Lit> = assumptions.iter().map(|&lit| flip(&self.flips, lit)).collect();
+
+        // ネットが端とつながっていないか, 長さの手がかりと経路の長さが違えば, その arc を全部は使わない節を足して解き直す
+        // (統計は最初から通して数える)
+        loop {
+            self.backend.assume(&assumptions);
+
+            let result = self.backend.solve();
+
+            self.stats = Stats {
+                elapsed: start.elapsed(),
+                conflicts: self.control.conflicts.load(Ordering::Relaxed)-conflicts,
+                memory: self.control.memory.load(Ordering::Relaxed),
+            };
+
+            match result {
+                Ok(true) => {},
+                Ok(false) => return Err(Error::NoSolution),
+                Err(stop) => {
+                    let e = match stop {
+                        Stop::Timeout => Error::Timeout(self.stats),
+                        Stop::Interrupted => Error::Interrupted(self.stats),
+                        Stop::MemoryLimit => Error::MemoryLimit(self.stats),
+                        Stop::Failed => Error::BackendFailed(self.backend.failure().unwrap_or_default()),
+                    };
+
+                    self.broken = Some(e.clone());
+
+                    return Err(e);
+                },
+            }
+
+            self.model = self.backend.model().ok_or(Error::NoSolution)?.into_iter().map(|lit| flip(&self.flips, lit)).collect();
+
+            let sol: Sol = self.model.iter().filter(|lit| lit.is_positive()).filter_map(|lit| var_arc(lit.var(), &self.puzzle)).collect();
+            let cuts = rules::cuts(&self.puzzle, &self.config.rules, &sol);
+
+            if cuts.is_empty() {
+                if !self.config.events.is_empty() {
+                    self.config.events.publish(&Event::Model { sol: sol.clone() });
+                }
+
+                return Ok(sol);
+            }
+
+            for cut in cuts {
+                let clause: Vec<Lit> = cut.iter().filter_map(|&arc| find_arc_var(&self.puzzle, arc)).map(|x| x.negative()).collect();
+
+                self.add_clause(&clause);
+            }
+        }
+    }
+}
+
+// 符号化しながら節とリテラルの数を数える
+pub(crate) struct Counted<'a> {
+    backend: &'a mut dyn SatBackend,
+    flips: &'a [bool],
+    clauses: u64,
+    lits: u64,
+    // 節に出てきた変数の番号の最大 +1
+    vars: usize,
+    // 極性を反転した節の作業用 (節ごとに確保し直さない)
+    buf: Vec<Lit>,
+}
+
+impl ExtendFormula for Counted<'_> {
+    fn add_clause(&mut self, literals: &[Lit]) {
+        self.clauses += 1;
+        self.lits += literals.len() as u64;
+        self.vars = literals.iter().map(|lit| lit.index()+1).fold(self.vars, usize::max);
+
+        if self.flips.is_empty() {
+            self.backend.add_clause(literals);
+
+            return;
+        }
+
+        let flips = self.flips;
+
+        self.buf.clear();
+        self.buf.extend(literals.iter().map(|&lit| flip(flips, lit)));
+        self.backend.add_clause(&self.buf);
+    }
+
+    fn new_var(&mut self) -> Var {
+        self.vars += 1;
+
+        Var::from_index(self.vars-1)
+    }
+}
+
+fn flip(flips: &[bool], lit: Lit) -> Lit {
+    if flips.get(lit.index()).is_some_and(|&flip| flip) {
+        !lit
+    } else {
+        lit
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeSet;
+
+use varisat::Var;
+
+use crate::input::puzzlink;
+use crate::loops::{add_count, loop_json, solve_loop, Grid};
+use crate::render::Format;
+use crate::{Error, SolverConfig, P};
+
+// Slitherlink: セルの角の点を結んで 1 本の輪を引き, 数字のセルはその数だけ 4 辺を輪が通る
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Slither {
+    pub width: usize,
+    pub height: usize,
+    // 行優先. None は数字のないセル (puzz.link の ? も数字なしとして読む)
+    pub clues: Vec<Option<usize>>,
+}
+
+// puzz.link でのパズルの名前
+pub(crate) const NAMES: &[&str] = &["slither", "slitherlink"];
+
+impl Slither {
+    pub fn new(width: usize, height: usize) -> Slither {
+        Slither { width, height, clues: vec![None; width*height] }
+    }
+
+    // 輪を引く点の格子 (height+1 行 width+1 列)
+    pub fn grid(&self) -> Grid {
+        Grid { rows: self.height+1, cols: self.width+1 }
+    }
+
+    // セル (i, j) の上, 下, 左, 右の辺
+    fn sides(&self, (i, j): P) -> [usize; 4] {
+        let grid = self.grid();
+        let side = |p: P, q: P| grid.edge(p, q).unwrap();
+
+        [side((i, j), (i, j+1)), side((i+1, j), (i+1, j+1)), side((i, j), (i+1, j)), side((i, j+1), (i+1, j+1))]
+    }
+}
+
+// puzz.link の slither/W/H/CODE か, 行を `/`, セルを `,` で区切ったテキスト (`.` は数字なし)
+pub fn parse_slither(input: &str) -> Option<Slither> {
+    match puzzlink(input, NAMES) {
+        Some((width, height, code)) => decode_slither(width, height, code),
+        None => parse_slither_text(input),
+    }
+}
+
+// 0-4 はその数字, 5-9 と a-e は数字のあとに 1 つか 2 つ空白, g-z は 1-20 個の空白, `.` は ?
+fn decode_slither(width: usize, height: usize, code: &str) -> Option<Slither> {
+    let mut puzzle = Slither::new(width, height);
+    let mut u = 0;
+
+    for ch in code.chars() {
+        if u >= puzzle.clues.len() {
+            return None;
+        }
+
+        let digit = ch.to_digit(36)? as usize;
+
+        match ch {
+            '0'..='4' => puzzle.clues[u] = Some(digit),
+            '5'..='9' => {
+                puzzle.clues[u] = Some(digit-5);
+                u += 1;
+            },
+            'a'..='e' => {
+                puzzle.clues[u] = Some(digit-10);
+                u += 2;
+            },
+            'g'..='z' => u += digit-16,
+            '.' => {},
+            _ => return None,
+        }
+
+        u += 1;
+    }
+
+    Some(puzzle)
+}
+
+fn parse_slither_text(input: &str) -> Option<Slither> {
+    let rows: Vec<Vec<Option<usize>>> = input.trim().split(['/', '\n']).map(|row| {
+        row.trim().split(',').map(|cell| match cell.trim() {
+            "." => Some(None),
+            cell => cell.parse().ok().filter(|&num| num <= 4).map(Some),
+        }).collect::<Option<Vec<_>>>()
+    }).collect::<Option<Vec<_>>>()?;
+    let width = rows.first()?.len();
+
+    if rows.iter().any(|row| row.len() != width) {
+        return None;
+    }
+
+    Some(Slither { width, height: rows.len(), clues: rows.concat() })
+}
+
+// 答えの輪が通る点 (行, 列) を順に
+pub fn solve_slither(puzzle: &Slither, config: &SolverConfig) -> Result<Vec<P>, Error> {
+    if puzzle.width == 0 || puzzle.height == 0 || puzzle.clues.len() != puzzle.width*puzzle.height {
+        return Err(Error::InvalidField);
+    }
+
+    solve_loop(&puzzle.grid(), config, |formula| {
+        for (u, clue) in puzzle.clues.iter().enumerate() {
+            let n = match *clue {
+                Some(n) => n,
+                None => continue,
+            };
+            let sides: Vec<_> = puzzle.sides((u/puzzle.width, u%puzzle.width)).iter().map(|&e| Var::from_index(e)).collect();
+
+            add_count(formula, &sides, n);
+        }
+    })
+}
+
+pub fn render_slither(puzzle: &Slither, cycle: &[P], format: Format) -> String {
+    let grid = puzzle.grid();
+    let edges: BTreeSet<usize> = (0..cycle.len()).filter_map(|k| grid.edge(cycle[k], cycle[(k+1)%cycle.len()])).collect();
+    let used = |p: P, q: P| grid.edge(p, q).is_some_and(|e| edges.contains(&e));
+
+    match format {
+        Format::Debug | Format::Plugin(_) => format!("{:?}\n", cycle),
+        Format::Text => {
+            let mut res = String::new();
+
+            for i in 0..=puzzle.height {
+                for j in 0..=puzzle.width {
+                    res.push('+');
+
+                    if j < puzzle.width {
+                        res.push(if used((i, j), (i, j+1)) { '-' } else { ' ' });
+                    }
+                }
+
+                res.push('\n');
+
+                if i == puzzle.height {
+                    break;
+                }
+
+                for j in 0..=puzzle.width {
+                    res.push(if used((i, j), (i+1, j)) { '|' } else { ' ' });
+
+                    if j < puzzle.width {
+                        res.push(puzzle.clues[i*puzzle.width+j].map_or(' ', |n| (b'0'+n as u8) as char));
+                    }
+                }
+
+                res.push('\n');
+            }
+
+            res
+        },
+        Format::Json => loop_json(puzzle.width, puzzle.height, cycle, ""),
+    }
+}
@@ -0,0 +1,143 @@
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufRead, BufReader, Lines};
+use std::pin::{pin, Pin};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::{Arc as Shared, OnceLock, RwLock};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+// 盤面を渡してくるもの (ファイル, URL の一覧, データベース, Web から集めるもの など). 外のクレートは実装して
+// register_source で登録すると, バッチの入力 (--source SCHEME:ARG) にそのまま使える
+pub trait PuzzleSource: Send {
+    // 次の盤面 (parse_puzzle が読める 1 行). 尽きたら None. 取れなかったときはその理由で, 続けて呼べばその次を取る
+    fn next(&mut self) -> Fetch<'_>;
+}
+
+pub type Fetch<'a> = Pin<Box<dyn Future<Output = Option<Result<String, String>>> + Send + 'a>>;
+
+// SCHEME: に続く引数から source を開く
+pub type Opener = dyn Fn(&str) -> Result<Box<dyn PuzzleSource>, String> + Send + Sync;
+
+type Openers = RwLock<Vec<(&'static str, Shared<Opener>)>>;
+
+static OPENERS: OnceLock<Openers> = OnceLock::new();
+
+fn openers() -> &'static Openers {
+    OPENERS.get_or_init(|| RwLock::new(vec![
+        ("file", Shared::new(|path: &str| Ok(Box::new(FileSource::open(path)?) as Box<dyn PuzzleSource>)) as Shared<Opener>),
+        ("cmd", Shared::new(|command: &str| Ok(Box::new(CommandSource::spawn(command)?) as Box<dyn PuzzleSource>)) as Shared<Opener>),
+    ]))
+}
+
+// 同じ scheme で登録し直すと置き換わる
+pub fn register_source(scheme: &'static str, open: impl Fn(&str) -> Result<Box<dyn PuzzleSource>, String> + Send + Sync + 'static) {
+    let mut openers = openers().write().unwrap();
+
+    openers.retain(|&(other, _)| other != scheme);
+    openers.push((scheme, Shared::new(open)));
+}
+
+// 登録した scheme (登録順. file と cmd は最初からある)
+pub fn source_schemes() -> Vec<&'static str> {
+    openers().read().unwrap().iter().map(|&(scheme, _)| scheme).collect()
+}
+
+// SCHEME:ARG の source を開く
+pub fn open_source(spec: &str) -> Result<Box<dyn PuzzleSource>, String> {
+    let (scheme, arg) = spec.split_once(':').ok_or_else(|| format!("invalid source `{}` (expected SCHEME:ARG)", spec))?;
+    let open = openers().read().unwrap().iter().find(|&&(other, _)| other == scheme).map(|(_, open)| open.clone())
+        .ok_or_else(|| format!("unknown source `{}` (expected {})", scheme, source_schemes().join(", ")))?;
+
+    open(arg)
+}
+
+// 1 行に 1 問のファイル (file:PATH)
+pub struct FileSource {
+    lines: Lines<BufReader<File>>,
+}
+
+impl FileSource {
+    pub fn open(path: &str) -> Result<FileSource, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+
+        Ok(FileSource { lines: BufReader::new(file).lines() })
+    }
+}
+
+impl PuzzleSource for FileSource {
+    fn next(&mut self) -> Fetch<'_> {
+        Box::pin(async move { self.lines.next().map(|line| line.map_err(|e| e.to_string())) })
+    }
+}
+
+// コマンドが標準出力に 1 行に 1 問ずつ書く盤面 (cmd:COMMAND). スクレイパーやデータベースへの問い合わせをつなぐ
+pub struct CommandSource {
+    child: Child,
+    lines: Option<Lines<BufReader<ChildStdout>>>,
+}
+
+impl CommandSource {
+    pub fn spawn(command: &str) -> Result<CommandSource, String> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let (program, args) = words.split_first().ok_or_else(|| "empty source command".to_string())?;
+        let mut child = Command::new(program).args(args).stdin(Stdio::null()).stdout(Stdio::piped())
+            .spawn().map_err(|e| format!("{}: {}", program, e))?;
+        let lines = Some(BufReader::new(child.stdout.take().unwrap()).lines());
+
+        Ok(CommandSource { child, lines })
+    }
+}
+
+impl PuzzleSource for CommandSource {
+    fn next(&mut self) -> Fetch<'_> {
+        Box::pin(async move {
+            if let Some(line) = self.lines.as_mut()?.next() {
+                return Some(line.map_err(|e| e.to_string()));
+            }
+
+            // 書き終えたら, 失敗して終わっていないかを 1 度だけ確かめる
+            self.lines = None;
+
+            match self.child.wait() {
+                Ok(status) if status.success() => None,
+                Ok(status) => Some(Err(format!("source command exited with {}", status))),
+                Err(e) => Some(Err(e.to_string())),
+            }
+        })
+    }
+}
+
+// 一覧をそのまま渡す
+impl PuzzleSource for std::vec::IntoIter<String> {
+    fn next(&mut self) -> Fetch<'_> {
+        Box::pin(async move { Iterator::next(self).map(Ok) })
+    }
+}
+
+// 非同期の実行環境のない呼び出し側 (CLI のバッチ) のために, 今のスレッドを止めて 1 つの future を待つ
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Shared<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Shared::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+// source を待ちながら 1 問ずつ取り出す
+pub fn blocking(mut source: Box<dyn PuzzleSource>) -> impl Iterator<Item = Result<String, String>> {
+    std::iter::from_fn(move || block_on(source.next()))
+}
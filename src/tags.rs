@@ -0,0 +1,243 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use varisat::{CnfFormula, ExtendFormula, Lit, Var};
+
+use crate::{encode_with, Error, Puzzle, Session, SolverConfig};
+
+// 節の出どころ. 括弧の中は論文の式の番号 (論文にない拡張は番号なし)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Family {
+    // (2) 1 つの辺を両向きに通らない
+    Opposite,
+    // (3)-(9) のうち, 出る arc と入る arc がそれぞれ高々 1 本という部分 (template)
+    AtMostOne,
+    // (3) (4) 始点から 1 本出て, 入らない
+    Start,
+    // (5) (6) 終点に 1 本入って, 出ない
+    End,
+    // (8) (9) 空白に 1 本ずつ出入りする (橋は軸ごと)
+    Blank,
+    // (11) 手がかりの数字
+    Clue,
+    // (12) arc の両端の数字が等しい
+    Label,
+    // direct 符号化の数字変数がちょうど 1 つ真
+    Domain,
+    // 壁, 穴と一方通行で使えない arc
+    Blocked,
+    // 線が通らなくてよい空白 (--coverage false)
+    Optional,
+    // 組の決まっていない点
+    Dot,
+    // 3 回以上現れる数字のネット
+    Net,
+    // --constraints
+    Constraint,
+    // 人の手筋で決まる辺と数字 (--presolve)
+    Deduction,
+    // 長さの手がかり, 長さの上限とコストの予算
+    Length,
+    // 斜めの交差, 2x2 と接する規則
+    Rule,
+}
+
+impl Family {
+    pub const ALL: [Family; 16] = [
+        Family::Opposite, Family::AtMostOne, Family::Start, Family::End, Family::Blank, Family::Clue, Family::Label, Family::Domain,
+        Family::Blocked, Family::Optional, Family::Dot, Family::Net, Family::Constraint, Family::Deduction, Family::Length, Family::Rule,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Family::Opposite => "opposite (2)",
+            Family::AtMostOne => "at-most-one (3)-(9)",
+            Family::Start => "start (3)(4)",
+            Family::End => "end (5)(6)",
+            Family::Blank => "blank (8)(9)",
+            Family::Clue => "clue (11)",
+            Family::Label => "label (12)",
+            Family::Domain => "domain",
+            Family::Blocked => "blocked",
+            Family::Optional => "optional",
+            Family::Dot => "dot",
+            Family::Net => "net",
+            Family::Constraint => "constraint",
+            Family::Deduction => "deduction",
+            Family::Length => "length",
+            Family::Rule => "rule",
+        }
+    }
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// 節 1 つの出どころ. cell は節を作ったセル (盤面全体に掛かる節は None)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tag {
+    pub family: Family,
+    pub cell: Option<usize>,
+}
+
+// 節を formula に流しながら, 今の出どころを節ごとに書き留める (tags が None なら流すだけ)
+pub(crate) struct Tagger<'a, F> {
+    formula: &'a mut F,
+    tags: Option<&'a mut Vec<Tag>>,
+    current: Tag,
+}
+
+impl<'a, F: ExtendFormula> Tagger<'a, F> {
+    pub(crate) fn new(formula: &'a mut F, tags: Option<&'a mut Vec<Tag>>) -> Self {
+        Tagger { formula, tags, current: Tag { family: Family::Rule, cell: None } }
+    }
+
+    pub(crate) fn tag(&mut self, family: Family, cell: Option<usize>) {
+        self.current = Tag { family, cell };
+    }
+
+    pub(crate) fn recording(&self) -> bool {
+        self.tags.is_some()
+    }
+}
+
+impl<F: ExtendFormula> ExtendFormula for Tagger<'_, F> {
+    fn add_clause(&mut self, literals: &[Lit]) {
+        if let Some(tags) = self.tags.as_mut() {
+            tags.push(self.current);
+        }
+
+        self.formula.add_clause(literals);
+    }
+
+    fn new_var(&mut self) -> Var {
+        self.formula.new_var()
+    }
+}
+
+// encode と同じ節を同じ順に作り, 節ごとの出どころを tags に足す. 次に使える変数番号を返す
+pub fn encode_tagged<F: ExtendFormula>(puzzle: &Puzzle, config: &SolverConfig, formula: &mut F, tags: &mut Vec<Tag>) -> Result<usize, Error> {
+    encode_with(puzzle, config, &mut Tagger::new(formula, Some(tags)))
+}
+
+// 出どころごとの節の数, リテラルの数と節を作ったセルの数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FamilyStats {
+    pub family: Family,
+    pub clauses: usize,
+    pub literals: usize,
+    pub cells: usize,
+}
+
+// 節のある出どころだけを Family の順に
+pub fn family_stats(formula: &CnfFormula, tags: &[Tag]) -> Vec<FamilyStats> {
+    let mut stats: BTreeMap<Family, (FamilyStats, BTreeSet<usize>)> = BTreeMap::new();
+
+    for (clause, tag) in formula.iter().zip(tags) {
+        let (stat, cells) = stats.entry(tag.family).or_insert_with(|| (FamilyStats { family: tag.family, clauses: 0, literals: 0, cells: 0 }, BTreeSet::new()));
+
+        stat.clauses += 1;
+        stat.literals += clause.len();
+        cells.extend(tag.cell);
+    }
+
+    stats.into_values().map(|(stat, cells)| FamilyStats { cells: cells.len(), ..stat }).collect()
+}
+
+// 解けない盤面で, それだけで解けなくなる (出どころ, セル) の組 (どれを外しても解ける). 解けるなら None
+// 組ごとに選択変数を足して仮定し, ソルバの返す矛盾から外しても解けないものを 1 つずつ外す (小さい盤面向け)
+pub fn unsat_core(puzzle: &Puzzle, config: &SolverConfig) -> Result<Option<Vec<Tag>>, Error> {
+    let mut groups: Vec<(Tag, Var)> = vec![];
+    let mut session = Session::with_encoder(puzzle, config, None, |formula| {
+        let mut tagged = CnfFormula::new();
+        let mut tags = vec![];
+        let mut next_var = encode_tagged(puzzle, config, &mut tagged, &mut tags)?;
+        let mut acts: BTreeMap<Tag, Var> = BTreeMap::new();
+
+        for tag in tags.iter().cloned().collect::<BTreeSet<Tag>>() {
+            acts.insert(tag, Var::from_index(next_var));
+            next_var += 1;
+        }
+
+        for (clause, tag) in tagged.iter().zip(&tags) {
+            formula.add_clause(&clause.iter().cloned().chain([acts[tag].negative()]).collect::<Vec<Lit>>());
+        }
+
+        groups = acts.into_iter().collect();
+
+        Ok(next_var)
+    })?;
+    let mut failed = |groups: &[(Tag, Var)]| -> Result<Option<Vec<(Tag, Var)>>, Error> {
+        let assumptions: Vec<Lit> = groups.iter().map(|&(_, act)| act.positive()).collect();
+
+        match session.run(&assumptions) {
+            Ok(_) => Ok(None),
+            Err(Error::NoSolution) => {
+                let core = session.failed().unwrap_or(assumptions);
+
+                Ok(Some(groups.iter().filter(|&&(_, act)| core.contains(&act.positive())).cloned().collect()))
+            },
+            Err(e) => Err(e),
+        }
+    };
+    let mut core = match failed(&groups)? {
+        Some(core) => core,
+        None => return Ok(None),
+    };
+    let mut k = 0;
+
+    while k < core.len() {
+        let mut rest = core.clone();
+
+        rest.remove(k);
+
+        match failed(&rest)? {
+            Some(smaller) => core = smaller,
+            None => k += 1,
+        }
+    }
+
+    Ok(Some(core.into_iter().map(|(tag, _)| tag).collect()))
+}
+
+// 出どころごとの表と, 解けない盤面なら矛盾の組を出どころごとにまとめたもの
+//
+//     family                clauses  literals  cells
+//     opposite (2)               24        48      9
+//     ...
+//     core: 3 groups
+//       clue (11): (0,0) (2,2)
+//       blank (8)(9): (1,1)
+pub fn render_families(puzzle: &Puzzle, stats: &[FamilyStats], core: Option<&[Tag]>) -> String {
+    let mut res = format!("{:<20} {:>9} {:>9} {:>6}\n", "family", "clauses", "literals", "cells");
+
+    for stat in stats {
+        res.push_str(&format!("{:<20} {:>9} {:>9} {:>6}\n", stat.family.name(), stat.clauses, stat.literals, stat.cells));
+    }
+
+    res.push_str(&format!("{:<20} {:>9} {:>9}\n", "total", stats.iter().map(|stat| stat.clauses).sum::<usize>(), stats.iter().map(|stat| stat.literals).sum::<usize>()));
+
+    let core = match core {
+        Some(core) => core,
+        None => return res,
+    };
+    let mut families: BTreeMap<Family, Vec<String>> = BTreeMap::new();
+
+    for tag in core {
+        families.entry(tag.family).or_default().push(match tag.cell {
+            Some(u) => format!("({},{})", puzzle.pos(u).0, puzzle.pos(u).1),
+            None => "board".to_string(),
+        });
+    }
+
+    res.push_str(&format!("core: {} groups\n", core.len()));
+
+    for (family, cells) in families {
+        res.push_str(&format!("  {}: {}\n", family, cells.join(" ")));
+    }
+
+    res
+}
@@ -0,0 +1,323 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc as Shared;
+use std::sync::{Mutex, OnceLock};
+
+use rayon::prelude::*;
+use varisat::{CnfFormula, ExtendFormula, Lit, Var};
+
+use crate::constraints::Constraint;
+use crate::nets::{net_numbers, Nets};
+use crate::tags::{Family, Tag, Tagger};
+use crate::{grid_adjs, mk_clause_impl, rng, Encoding, Puzzle, SolverConfig, Topology};
+
+// 手がかりの位置によらない, 盤面の形 (大きさ, セルのつながり方, 橋, ワープ) と数字の種類数だけで決まる部分の節
+// 同じ大きさの問題を続けて解くときは作り直さずに使い回す
+#[derive(Debug)]
+pub struct Template {
+    // 節をセルの塊ごとに (この順に足す). 使い回さない大きな盤面では持たない (add_template が作りながら流す)
+    pub parts: Vec<CnfFormula>,
+    // セルごとの数字変数の番号
+    pub numbers: Vec<Vec<usize>>,
+    // 橋のセルの縦の線の数字変数 (numbers は横の線のもの). 橋でないセルは空
+    pub lanes: Vec<Vec<usize>>,
+    // セルを処理する順 (seed で並べ替える)
+    pub order: Vec<usize>,
+    pub next_var: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    width: usize,
+    height: usize,
+    topology: Topology,
+    labels: usize,
+    // 橋は出入りの arc の本数が, ワープは arc の行き先が変わるので大きさと一緒に区別する
+    bridges: Vec<usize>,
+    warps: BTreeMap<(usize, usize), usize>,
+    encoding: Encoding,
+    seed: u64,
+    // 木のネットがあると枝分かれするので, 出る arc を高々 1 本にしない
+    branching: bool,
+}
+
+// 並列に節を作るときの塊の大きさ (セル数)
+const CHUNK_CELLS: usize = 256;
+
+// これより大きな盤面の節は使い回さない. 作るのに比べて解くのがずっと重く, 持ち続けるとサーバでは解放されない
+const CACHE_CELLS: usize = 30*30;
+
+// 使い回す節のリテラルの数の合計の上限. 超えそうになったら一度捨てる
+const CACHE_LITERALS: usize = 1 << 24;
+
+#[derive(Default)]
+struct Cache {
+    templates: HashMap<Key, Shared<Template>>,
+    literals: usize,
+}
+
+static CACHE: OnceLock<Mutex<Cache>> = OnceLock::new();
+
+impl Template {
+    // セル u を向き d の arc で通るときの数字変数
+    pub fn label_vars(&self, u: usize, d: usize) -> &[usize] {
+        lane(&self.numbers, &self.lanes, u, d)
+    }
+}
+
+// 変数の割り当て. 使い回さない大きな盤面では節を作らない (parts は空)
+pub fn template(puzzle: &Puzzle, config: &SolverConfig) -> Shared<Template> {
+    let key = key(puzzle, config);
+
+    match key.width*key.height <= CACHE_CELLS {
+        true => cached(key),
+        false => Shared::new(build(key, false, None)),
+    }
+}
+
+// template の節を formula に足し, 変数の割り当てを返す. 大きな盤面は塊を作ったそばから formula に流し, 節を溜めない
+pub(crate) fn add_template<F: ExtendFormula>(puzzle: &Puzzle, config: &SolverConfig, formula: &mut Tagger<F>) -> Shared<Template> {
+    let key = key(puzzle, config);
+    let recording = formula.recording();
+
+    if key.width*key.height > CACHE_CELLS {
+        return Shared::new(build(key, recording, Some(&mut |part, tags| add_part(formula, &part, &tags))));
+    }
+
+    // 使い回す節の出どころは持っていないので, 要るときだけ作り直して数える
+    let tags = match recording {
+        true => {
+            let mut tags = vec![];
+
+            build(key.clone(), true, Some(&mut |_, part_tags| tags.extend(part_tags)));
+            tags
+        },
+        false => vec![],
+    };
+    let template = cached(key);
+    let mut offset = 0;
+
+    for part in &template.parts {
+        add_part(formula, part, tags.get(offset..).unwrap_or_default());
+        offset += part.len();
+    }
+
+    template
+}
+
+fn add_part<F: ExtendFormula>(formula: &mut Tagger<F>, part: &CnfFormula, tags: &[Tag]) {
+    for (k, clause) in part.iter().enumerate() {
+        if let Some(tag) = tags.get(k) {
+            formula.tag(tag.family, tag.cell);
+        }
+
+        formula.add_clause(clause);
+    }
+}
+
+fn cached(key: Key) -> Shared<Template> {
+    let cache = CACHE.get_or_init(|| Mutex::new(Cache::default()));
+
+    if let Some(template) = cache.lock().unwrap().templates.get(&key) {
+        return template.clone();
+    }
+
+    // 作っている間はロックを外す (並列バッチで別の大きさを待たせない)
+    let mut parts = vec![];
+    let mut template = build(key.clone(), false, Some(&mut |part, _| parts.push(part)));
+
+    template.parts = parts;
+
+    let template = Shared::new(template);
+    let literals = template.parts.iter().flat_map(|part| part.iter()).map(|clause| clause.len()).sum::<usize>();
+    let mut cache = cache.lock().unwrap();
+
+    if cache.templates.contains_key(&key) {
+        return cache.templates[&key].clone();
+    }
+
+    if cache.literals+literals > CACHE_LITERALS {
+        *cache = Cache::default();
+    }
+
+    cache.literals += literals;
+    cache.templates.insert(key, template.clone());
+
+    template
+}
+
+fn key(puzzle: &Puzzle, config: &SolverConfig) -> Key {
+    Key {
+        width: puzzle.width,
+        height: puzzle.height,
+        topology: puzzle.topology,
+        // 長さの手がかりと長さの上限 (--constraints の maxlen も) では, 空白の閉路がどの線とも数えられないよう 1 つ余分に数字を用意する
+        labels: puzzle.cells.iter().cloned().max().unwrap_or(0)+(config.rules.lengths || !puzzle.limits.is_empty() || config.constraints.iter().any(|c| matches!(c, Constraint::MaxLen(..)))) as usize,
+        bridges: puzzle.bridges.iter().cloned().collect(),
+        warps: puzzle.warps.clone(),
+        encoding: config.encoding,
+        seed: config.seed,
+        branching: config.rules.nets == Nets::Tree && !net_numbers(puzzle).is_empty(),
+    }
+}
+
+// 節は塊ごとに (節ごとの出どころは recording のときだけ) 順に sink へ渡す. sink がなければ変数の割り当てだけ
+fn build(key: Key, recording: bool, mut sink: Option<&mut dyn FnMut(CnfFormula, Vec<Tag>)>) -> Template {
+    let Key { width, height, topology, labels, bridges, warps, encoding, seed, branching } = key;
+
+    let mut formula = CnfFormula::new();
+    let mut tags = vec![];
+    let adjs = grid_adjs(width, height, topology, &warps);
+    let length = topology.arc_var_count(width, height);
+
+    let cells: Vec<usize> = (0..width*height).collect();
+    let numbers = match encoding {
+        Encoding::Log => number_vars_log(width*height, labels, length),
+        Encoding::Direct => number_vars_direct(&cells, labels, length, &mut Tagger::new(&mut formula, recording.then_some(&mut tags))),
+    };
+
+    let offset = numbers.iter().flatten().max().map_or(length, |&x| x);
+    let mut lanes = vec![vec![]; width*height];
+    let vertical = match encoding {
+        Encoding::Log => number_vars_log(bridges.len(), labels, offset),
+        Encoding::Direct => number_vars_direct(&bridges, labels, offset, &mut Tagger::new(&mut formula, recording.then_some(&mut tags))),
+    };
+
+    for (&u, vars) in bridges.iter().zip(vertical) {
+        lanes[u] = vars;
+    }
+
+    let mut order: Vec<usize> = (0..width*height).collect();
+
+    if seed != 0 {
+        rng::Rng::new(seed).shuffle(&mut order);
+    }
+
+    // 40x40 程度になると節の生成自体に時間がかかるので, セルを固定の大きさの塊に分けて並列に作り, 順に渡す
+    // (塊の大きさはスレッド数によらないので, 節の順番はいつも同じ). 一度に作るのはスレッドの数の塊だけ
+    if let Some(sink) = sink.as_mut() {
+        sink(formula, tags);
+
+        for batch in order.chunks(CHUNK_CELLS*rayon::current_num_threads()) {
+            let parts: Vec<(CnfFormula, Vec<Tag>)> = batch.par_chunks(CHUNK_CELLS).map(|cells| {
+                let mut part = CnfFormula::new();
+                let mut part_tags = vec![];
+
+                encode_cells(cells, topology, &adjs, &numbers, &lanes, branching, &mut Tagger::new(&mut part, recording.then_some(&mut part_tags)));
+
+                (part, part_tags)
+            }).collect();
+
+            for (part, part_tags) in parts {
+                sink(part, part_tags);
+            }
+        }
+    }
+
+    let next_var = numbers.iter().chain(&lanes).flatten().max().map_or(length+1, |&x| x+1);
+
+    Template { parts: vec![], numbers, lanes, order, next_var }
+}
+
+fn encode_cells(cells: &[usize], topology: Topology, adjs: &[Vec<(usize, usize)>], numbers: &[Vec<usize>], lanes: &[Vec<usize>], branching: bool, formula: &mut Tagger<CnfFormula>) {
+    for &u in cells {
+        for &(v, d) in &adjs[u] {
+            let x = topology.arc_var(u, d);
+
+            formula.tag(Family::Label, Some(u));
+
+            // (12)
+            // !(x and num_u != num_v)
+            // !x or f_u == f_v
+            mk_clause_impl(formula, &x, lane(numbers, lanes, u, d), lane(numbers, lanes, v, d));
+
+            let y = topology.arc_var(v, topology.opposite(d));
+
+            // (2)
+            formula.tag(Family::Opposite, Some(u));
+            formula.add_clause(&[x.negative(), y.negative()]);
+        }
+    }
+
+    // (3)-(9) のうち, 出る arc と入る arc がそれぞれ高々 1 本という部分はどのセルでも共通
+    let mut outs: Vec<Var> = Vec::with_capacity(topology.slots());
+    let mut ins: Vec<Var> = Vec::with_capacity(topology.slots());
+    let all: Vec<usize> = (0..topology.slots()).collect();
+
+    for &u in cells {
+        formula.tag(Family::AtMostOne, Some(u));
+
+        // 橋は横と縦の線が 1 本ずつ通るので, 軸ごとに高々 1 本 (橋は四角い盤面にしかない)
+        let groups: &[&[usize]] = if lanes[u].is_empty() { &[&all] } else { &[&[0, 2], &[1, 3]] };
+
+        for dirs in groups {
+            outs.clear();
+            outs.extend(adjs[u].iter().filter(|&&(_, d)| dirs.contains(&d)).map(|&(_, d)| topology.arc_var(u, d)));
+            ins.clear();
+            ins.extend(adjs[u].iter().filter(|&&(_, d)| dirs.contains(&d)).map(|&(v, d)| topology.arc_var(v, topology.opposite(d))));
+
+            // 2 回だけ現れる数字が枝分かれしても, 葉になれるのは終点 1 つなので 1 本道のまま
+            if !branching || !lanes[u].is_empty() {
+                mk_clause_le1(formula, &outs);
+            }
+
+            mk_clause_le1(formula, &ins);
+        }
+    }
+}
+
+// 橋のセルを縦に通る arc (向きが奇数) だけ lanes の変数を使う
+fn lane<'a>(numbers: &'a [Vec<usize>], lanes: &'a [Vec<usize>], u: usize, d: usize) -> &'a [usize] {
+    if d%2 == 1 && !lanes[u].is_empty() { &lanes[u] } else { &numbers[u] }
+}
+
+// vs*log(n)
+// 自然数変数を bit ごとに分解
+fn number_vars_log(cells: usize, n: usize, offset: usize) -> Vec<Vec<usize>> {
+    let mut m = n;
+    let mut mb = 0;
+
+    while m > 0 {
+        m >>= 1;
+        mb += 1;
+    }
+
+    (0..cells).map(|index| (0..mb).map(|b| offset+index*mb+b+1).collect()).collect()
+}
+
+// vs*n
+// 数字ごとの変数のうちちょうど 1 つが真
+// cells は変数を作るセル (出どころの記録用)
+fn number_vars_direct(cells: &[usize], n: usize, offset: usize, formula: &mut Tagger<CnfFormula>) -> Vec<Vec<usize>> {
+    let mut bmp: Vec<Vec<usize>> = vec![];
+    let mut vars: Vec<Var> = Vec::with_capacity(n);
+    let mut buf = vec![];
+
+    for (index, &u) in cells.iter().enumerate() {
+        vars.clear();
+        vars.extend((0..n).map(|k| Var::from_index(offset+index*n+k+1)));
+
+        formula.tag(Family::Domain, Some(u));
+        number_domain(formula, &vars, &mut buf);
+
+        bmp.push(vars.iter().map(|x| x.index()).collect());
+    }
+
+    bmp
+}
+
+// direct 符号化の 1 セル分 (ちょうど 1 つ), buf は少なくとも 1 つの節の作業用
+pub(crate) fn number_domain<F: ExtendFormula>(formula: &mut F, vars: &[Var], buf: &mut Vec<Lit>) {
+    buf.clear();
+    buf.extend(vars.iter().map(|x| x.positive()));
+    formula.add_clause(buf);
+
+    mk_clause_le1(formula, vars);
+}
+
+pub(crate) fn mk_clause_le1<F: ExtendFormula>(formula: &mut F, vars: &[Var]) {
+    for (k, x) in vars.iter().enumerate() {
+        for y in &vars[k+1..] {
+            formula.add_clause(&[x.negative(), y.negative()]);
+        }
+    }
+}
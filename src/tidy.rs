@@ -0,0 +1,113 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use crate::constraints::holds;
+use crate::render::paths;
+use crate::rules::verify;
+use crate::{Arc, Puzzle, Sol, SolverConfig, P};
+
+// 線の通らない空白を許すと, SAT の解は手がかりと関係のない空白の閉路や無駄に曲がりくねった道を含みがち.
+// 最適化 (--objective) ほど重くない後処理として, 数字のつながらない線を消し, 道の一部をより短い迂回路に
+// 付け替えるのを縮まらなくなるまで繰り返す. どの書き換えも規則と --constraints を満たすときだけ採る
+pub fn shorten(puzzle: &Puzzle, config: &SolverConfig, sol: &Sol) -> Sol {
+    if config.rules.coverage || !valid(puzzle, config, sol) {
+        return sol.clone();
+    }
+
+    let mut sol = drop_loops(puzzle, config, sol);
+
+    while let Some(next) = reroute(puzzle, config, &sol) {
+        sol = next;
+    }
+
+    sol
+}
+
+fn valid(puzzle: &Puzzle, config: &SolverConfig, sol: &Sol) -> bool {
+    verify(puzzle, &config.rules, sol).is_ok() && holds(&config.constraints, sol)
+}
+
+// 数字から辿れない arc (空白の閉路) を除く
+fn drop_loops(puzzle: &Puzzle, config: &SolverConfig, sol: &Sol) -> Sol {
+    let on_path: BTreeSet<Arc> = paths(puzzle, sol).iter().flat_map(|(_, path)| path.windows(2).map(|w| (w[0], w[1]))).collect();
+    let res: Sol = sol.iter().cloned().filter(|arc| on_path.contains(arc)).collect();
+
+    match res.len() < sol.len() && valid(puzzle, config, &res) {
+        true => res,
+        false => sol.clone(),
+    }
+}
+
+// 道の 2 つのセルの間を, 今より短く結べる空いたセル (とその区間のセル) を通る道があれば 1 つ付け替える
+fn reroute(puzzle: &Puzzle, config: &SolverConfig, sol: &Sol) -> Option<Sol> {
+    let used: BTreeSet<P> = sol.iter().flat_map(|&(p, q)| [p, q]).collect();
+    let free = |u: usize| {
+        puzzle.cells[u] == 0 && !puzzle.is_hole(u) && !puzzle.is_outside(u) && !puzzle.is_bridge(u) && !puzzle.waypoints.contains_key(&u)
+    };
+    let mut adjs = vec![vec![]; puzzle.cells.len()];
+
+    for (u, v) in puzzle.edges() {
+        adjs[u].push(v);
+        adjs[v].push(u);
+    }
+
+    for (_, path) in paths(puzzle, sol) {
+        // 長く縮む区間から試す
+        for len in (2..path.len()).rev() {
+            for i in 0..path.len()-len {
+                let j = i+len;
+                let inner: BTreeSet<P> = path[i+1..j].iter().cloned().collect();
+                let passable = |p: P| (!used.contains(&p) || inner.contains(&p)) && free(puzzle.index(p));
+                let detour = match bfs(puzzle, &adjs, path[i], path[j], passable) {
+                    Some(detour) if detour.len()-1 < len => detour,
+                    _ => continue,
+                };
+
+                let old: BTreeSet<Arc> = path[i..=j].windows(2).map(|w| (w[0], w[1])).collect();
+                let mut next: Sol = sol.iter().cloned().filter(|arc| !old.contains(arc)).chain(detour.windows(2).map(|w| (w[0], w[1]))).collect();
+
+                // 符号化の変数の順 (セルの番号, 向き) に並べ直す
+                next.sort_by_key(|&(p, q)| (puzzle.index(p), puzzle.direction(p, q)));
+
+                if valid(puzzle, config, &next) {
+                    return Some(next);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// from から to への最短の道 (途中のセルは passable なものだけ)
+fn bfs(puzzle: &Puzzle, adjs: &[Vec<usize>], from: P, to: P, passable: impl Fn(P) -> bool) -> Option<Vec<P>> {
+    let (s, t) = (puzzle.index(from), puzzle.index(to));
+    let mut prev = vec![None; puzzle.cells.len()];
+    let mut queue = VecDeque::from(vec![s]);
+
+    prev[s] = Some(s);
+
+    while let Some(u) = queue.pop_front() {
+        if u == t {
+            let mut res = vec![to];
+            let mut u = t;
+
+            while u != s {
+                u = prev[u].unwrap();
+                res.push(puzzle.pos(u));
+            }
+
+            res.reverse();
+
+            return Some(res);
+        }
+
+        for &v in &adjs[u] {
+            if prev[v].is_none() && (v == t || passable(puzzle.pos(v))) {
+                prev[v] = Some(u);
+                queue.push_back(v);
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,153 @@
+use std::fmt;
+use std::str::FromStr;
+
+use varisat::Var;
+
+use crate::DIRS;
+
+// 六角形のセルを軸座標 (行, 列) で並べた平行四辺形の盤面. 向きは右から時計回りで, d と d+3 が逆向き
+const HEX_DIRS: [(i32, i32); 6] = [(0, 1), (1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1)];
+const HEX_NAMES: [&str; 6] = ["right", "down-right", "down-left", "left", "up-left", "up-right"];
+const SQUARE_NAMES: [&str; 4] = ["right", "down", "left", "up"];
+// 斜めにも進める四角い盤面. 向きは右から時計回りで, d と d+4 が逆向き (偶数の向きは四角い盤面の d/2)
+const DIAGONAL_DIRS: [(i32, i32); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+const DIAGONAL_NAMES: [&str; 8] = ["right", "down-right", "down", "down-left", "left", "up-left", "up", "up-right"];
+const LAYER_NAMES: [&str; 6] = ["right", "down", "left", "up", "above", "below"];
+
+// セルのつながり方. 盤面は行優先の width*height 個のセルで, arc の変数はセルごとに向きの数だけ並ぶ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Topology {
+    #[default]
+    Square,
+    Hex,
+    // 8 近傍の四角い盤面 (斜めの arc どうしが交わってよいかは RuleSet::crossing)
+    Diagonal,
+    // 1 層 rows 行の四角い盤面を縦に並べて重ねたもの. 層 k の i 行目は k*rows+i 行目で, 上の層へは rows 行下に進む
+    Layers(usize),
+    // 1 行に並べた頂点の任意のグラフ (Puzzle::from_graph). 盤上の隣はなく, 辺はどれも向き d と d+m の組のワープ
+    Graph(usize),
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(Topology::Square),
+            "hex" => Ok(Topology::Hex),
+            "diagonal" => Ok(Topology::Diagonal),
+            _ => Err(format!("unknown topology `{}` (expected square, hex or diagonal)", s)),
+        }
+    }
+}
+
+// 盤面の端をつなぐ向き. rows は各行の右端と左端, columns は各列の下端と上端, both ならトーラス
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Wrap {
+    #[default]
+    None,
+    Rows,
+    Columns,
+    Both,
+}
+
+impl FromStr for Wrap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Wrap::None),
+            "rows" => Ok(Wrap::Rows),
+            "columns" => Ok(Wrap::Columns),
+            "both" => Ok(Wrap::Both),
+            _ => Err(format!("unknown wrap `{}` (expected none, rows, columns or both)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Topology::Square => write!(f, "square"),
+            Topology::Hex => write!(f, "hex"),
+            Topology::Diagonal => write!(f, "diagonal"),
+            Topology::Layers(_) => write!(f, "layers"),
+            Topology::Graph(_) => write!(f, "graph"),
+        }
+    }
+}
+
+impl Topology {
+    // 向き d に進むときの (行, 列) の差
+    pub fn dir(self, d: usize) -> (i32, i32) {
+        match (self, d) {
+            (Topology::Hex, _) => HEX_DIRS[d],
+            (Topology::Diagonal, _) => DIAGONAL_DIRS[d],
+            (Topology::Layers(rows), 4) => (rows as i32, 0),
+            (Topology::Layers(rows), 5) => (-(rows as i32), 0),
+            (Topology::Graph(_), _) => (0, 0),
+            _ => DIRS[d],
+        }
+    }
+
+    pub fn dir_names(self) -> &'static [&'static str] {
+        match self {
+            Topology::Square => &SQUARE_NAMES,
+            Topology::Hex => &HEX_NAMES,
+            Topology::Diagonal => &DIAGONAL_NAMES,
+            Topology::Layers(_) => &LAYER_NAMES,
+            Topology::Graph(_) => &[],
+        }
+    }
+
+    pub fn slots(self) -> usize {
+        match self {
+            Topology::Graph(m) => 2*m,
+            _ => self.dir_names().len(),
+        }
+    }
+
+    pub fn opposite(self, d: usize) -> usize {
+        match self {
+            Topology::Graph(m) => (d+m)%(2*m),
+            Topology::Layers(_) if d >= 4 => 9-d,
+            Topology::Layers(_) => (d+2)%4,
+            _ => (d+self.slots()/2)%self.slots(),
+        }
+    }
+
+    // 1 層の行数 (重ねていなければ盤面全体で 1 層)
+    pub fn layer_rows(self, height: usize) -> usize {
+        match self {
+            Topology::Layers(rows) => rows,
+            _ => height,
+        }
+    }
+
+    // 層をまたぐ向き
+    pub fn is_layer_dir(self, d: usize) -> bool {
+        matches!(self, Topology::Layers(_)) && d >= 4
+    }
+
+    // width x height の盤面で p から向き d に 1 歩進んだセル (盤内で, 層の中の向きなら同じ層のとき)
+    pub fn step(self, width: usize, height: usize, p: (usize, usize), d: usize) -> Option<(usize, usize)> {
+        let (di, dj) = self.dir(d);
+        let (i, j) = ((p.0 as i32 + di) as usize, (p.1 as i32 + dj) as usize);
+        let rows = self.layer_rows(height);
+
+        if let Topology::Graph(_) = self {
+            return None;
+        }
+
+        Some((i, j)).filter(|_| i < height && j < width && (i/rows == p.0/rows || self.is_layer_dir(d)))
+    }
+
+    // arc (u, u+dir(d)) の変数は slots*u+d+1 (盤外に出る arc の番号はワープにだけ使う)
+    pub(crate) fn arc_var(self, u: usize, d: usize) -> Var {
+        Var::from_index(self.slots()*u+d+1)
+    }
+
+    pub(crate) fn arc_var_count(self, width: usize, height: usize) -> usize {
+        self.slots()*width*height
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::render::paths;
+use crate::{Puzzle, Sol, Topology, P};
+
+// 盤面の回転と裏返し (正方形の 8 通りの対称)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transform {
+    Identity,
+    // 右回り
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    // 左右を入れ替える
+    Mirror,
+    // 上下を入れ替える
+    Flip,
+    // 左上と右下を結ぶ対角線で折り返す
+    Transpose,
+    // 右上と左下を結ぶ対角線で折り返す
+    AntiTranspose,
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Transform::Identity => write!(f, "identity"),
+            Transform::Rotate90 => write!(f, "rotate90"),
+            Transform::Rotate180 => write!(f, "rotate180"),
+            Transform::Rotate270 => write!(f, "rotate270"),
+            Transform::Mirror => write!(f, "mirror"),
+            Transform::Flip => write!(f, "flip"),
+            Transform::Transpose => write!(f, "transpose"),
+            Transform::AntiTranspose => write!(f, "antitranspose"),
+        }
+    }
+}
+
+impl FromStr for Transform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "identity" => Ok(Transform::Identity),
+            "rotate90" => Ok(Transform::Rotate90),
+            "rotate180" => Ok(Transform::Rotate180),
+            "rotate270" => Ok(Transform::Rotate270),
+            "mirror" => Ok(Transform::Mirror),
+            "flip" => Ok(Transform::Flip),
+            "transpose" => Ok(Transform::Transpose),
+            "antitranspose" => Ok(Transform::AntiTranspose),
+            _ => Err(format!("unknown transform `{}` (expected identity, rotate90, rotate180, rotate270, mirror, flip, transpose or antitranspose)", s)),
+        }
+    }
+}
+
+impl Transform {
+    pub const ALL: [Transform; 8] = [
+        Transform::Identity, Transform::Rotate90, Transform::Rotate180, Transform::Rotate270,
+        Transform::Mirror, Transform::Flip, Transform::Transpose, Transform::AntiTranspose,
+    ];
+
+    // 幅と高さが入れ替わるか
+    pub fn swaps(self) -> bool {
+        matches!(self, Transform::Rotate90 | Transform::Rotate270 | Transform::Transpose | Transform::AntiTranspose)
+    }
+
+    // 元に戻す変換
+    pub fn inverse(self) -> Transform {
+        match self {
+            Transform::Rotate90 => Transform::Rotate270,
+            Transform::Rotate270 => Transform::Rotate90,
+            t => t,
+        }
+    }
+
+    // width x height の盤面のセル p の移り先
+    pub fn map(self, width: usize, height: usize, (i, j): P) -> P {
+        match self {
+            Transform::Identity => (i, j),
+            Transform::Rotate90 => (j, height-1-i),
+            Transform::Rotate180 => (height-1-i, width-1-j),
+            Transform::Rotate270 => (width-1-j, i),
+            Transform::Mirror => (i, width-1-j),
+            Transform::Flip => (height-1-i, j),
+            Transform::Transpose => (j, i),
+            Transform::AntiTranspose => (width-1-j, height-1-i),
+        }
+    }
+}
+
+// 向きを変えると意味の変わる盤面 (ワープ, 層, 六角, グラフ, 一方通行) は Identity 以外 None
+// 一方通行は線の向き (行優先で先に現れる端から) が回すと変わるので写せない
+pub fn transform(puzzle: &Puzzle, t: Transform) -> Option<Puzzle> {
+    if t == Transform::Identity {
+        return Some(puzzle.clone());
+    }
+
+    let rigid = !matches!(puzzle.topology, Topology::Square | Topology::Diagonal) || !puzzle.warps.is_empty() || !puzzle.vias.is_empty() || !puzzle.one_way.is_empty();
+
+    if rigid {
+        return None;
+    }
+
+    let (width, height) = if t.swaps() { (puzzle.height, puzzle.width) } else { (puzzle.width, puzzle.height) };
+    let index = |u: usize| {
+        let (i, j) = t.map(puzzle.width, puzzle.height, puzzle.pos(u));
+
+        i*width + j
+    };
+    let mut res = Puzzle { topology: puzzle.topology, dots: puzzle.dots, limits: puzzle.limits.clone(), budget: puzzle.budget, ..Puzzle::new(width, height) };
+
+    for (u, &num) in puzzle.cells.iter().enumerate() {
+        res.cells[index(u)] = num;
+    }
+
+    res.walls = puzzle.walls.iter().map(|&(u, v)| (index(u).min(index(v)), index(u).max(index(v)))).collect();
+    res.holes = puzzle.holes.iter().cloned().map(index).collect();
+    res.bridges = puzzle.bridges.iter().cloned().map(index).collect();
+    res.outside = puzzle.outside.iter().cloned().map(index).collect();
+    res.waypoints = puzzle.waypoints.iter().map(|(&u, &num)| (index(u), num)).collect();
+    res.costs = puzzle.costs.iter().map(|(&u, &cost)| (index(u), cost)).collect();
+
+    // 点の盤面の点は行優先に 1, 2, ... と振る
+    Some(if res.dots { renumber(&res) } else { res })
+}
+
+// puzzle を transform した盤面の答えへ, puzzle の答え sol を写す
+// 線は行優先で先に現れる端から向きを付けるので, 写すと端の順が逆になる線は向きを変える
+pub fn transform_sol(puzzle: &Puzzle, sol: &Sol, t: Transform) -> Sol {
+    let map = |p: P| t.map(puzzle.width, puzzle.height, p);
+    let mut res = vec![];
+    let mut on_paths = HashSet::new();
+
+    for (_, path) in paths(puzzle, sol) {
+        let mut cells: Vec<P> = path.iter().cloned().map(map).collect();
+
+        on_paths.extend(path.windows(2).map(|w| (w[0], w[1])));
+
+        if cells.last() < cells.first() {
+            cells.reverse();
+        }
+
+        res.extend(cells.windows(2).map(|w| (w[0], w[1])));
+    }
+
+    // 空白だけの輪はそのまま
+    res.extend(sol.iter().filter(|arc| !on_paths.contains(arc)).map(|&(p, q)| (map(p), map(q))));
+
+    res
+}
+
+// 数字を行優先に現れる順に 1, 2, ... と振り直す
+pub fn renumber(puzzle: &Puzzle) -> Puzzle {
+    let mut labels = BTreeMap::new();
+
+    for &num in puzzle.cells.iter().filter(|&&num| num > 0) {
+        let next = labels.len()+1;
+
+        labels.entry(num).or_insert(next);
+    }
+
+    relabeled(puzzle, &labels)
+}
+
+// 数字 num を labels[&num] に付け替える (labels にない数字はそのまま)
+// 別々の数字が同じ数字になるか, 点の盤面なら None
+pub fn renumber_with(puzzle: &Puzzle, labels: &BTreeMap<usize, usize>) -> Option<Puzzle> {
+    let label = |num: usize| labels.get(&num).cloned().unwrap_or(num);
+    let mut nums: Vec<usize> = puzzle.cells.iter().cloned().filter(|&num| num > 0).collect();
+
+    nums.sort_unstable();
+    nums.dedup();
+
+    let count = nums.len();
+    let mut renamed: Vec<usize> = nums.into_iter().map(label).collect();
+
+    renamed.sort_unstable();
+    renamed.dedup();
+
+    if renamed.len() != count || renamed.contains(&0) || (puzzle.dots && labels.iter().any(|(&num, &to)| num != to)) {
+        return None;
+    }
+
+    Some(relabeled(puzzle, labels))
+}
+
+fn relabeled(puzzle: &Puzzle, labels: &BTreeMap<usize, usize>) -> Puzzle {
+    let label = |num: usize| labels.get(&num).cloned().unwrap_or(num);
+    let mut res = puzzle.clone();
+
+    for cell in res.cells.iter_mut().filter(|cell| **cell > 0) {
+        *cell = label(*cell);
+    }
+
+    // 盤面にない数字の上限は意味がないので落とす
+    res.limits = puzzle.limits.iter().filter(|(num, _)| puzzle.cells.contains(num)).map(|(&num, &limit)| (label(num), limit)).collect();
+    res.waypoints = puzzle.waypoints.iter().map(|(&u, &num)| (u, if num > 0 { label(num) } else { 0 })).collect();
+
+    res
+}
+
+// top 行 left 列からの rows 行 cols 列を切り出す. 両端とも中にある数字の組だけを残し,
+// 片方の端が外へ出る組の中の端は seal なら穴 (線が入れない), そうでなければ空白にする
+// 切り出すと意味が変わる盤面 (ワープ, 層, 六角, グラフ) と, 盤面からはみ出す範囲は None
+pub fn crop(puzzle: &Puzzle, top: usize, left: usize, rows: usize, cols: usize, seal: bool) -> Option<Puzzle> {
+    let rigid = !matches!(puzzle.topology, Topology::Square | Topology::Diagonal) || !puzzle.warps.is_empty() || !puzzle.vias.is_empty();
+
+    if rigid || rows == 0 || cols == 0 || top+rows > puzzle.height || left+cols > puzzle.width {
+        return None;
+    }
+
+    let inside = |u: usize| {
+        let (i, j) = puzzle.pos(u);
+
+        (top..top+rows).contains(&i) && (left..left+cols).contains(&j)
+    };
+    let index = |u: usize| {
+        let (i, j) = puzzle.pos(u);
+
+        (i-top)*cols + j-left
+    };
+    // 外にも端がある数字
+    let cut: HashSet<usize> = (0..puzzle.cells.len()).filter(|&u| !inside(u) && puzzle.cells[u] > 0).map(|u| puzzle.cells[u]).collect();
+    let mut res = Puzzle { topology: puzzle.topology, dots: puzzle.dots, budget: puzzle.budget, ..Puzzle::new(cols, rows) };
+
+    for u in (0..puzzle.cells.len()).filter(|&u| inside(u)) {
+        let num = puzzle.cells[u];
+
+        if num > 0 && !puzzle.dots && cut.contains(&num) {
+            if seal {
+                res.holes.insert(index(u));
+            }
+
+            continue;
+        }
+
+        res.cells[index(u)] = num;
+    }
+
+    res.walls = puzzle.walls.iter().filter(|&&(u, v)| inside(u) && inside(v)).map(|&(u, v)| (index(u), index(v))).collect();
+    res.holes.extend(puzzle.holes.iter().cloned().filter(|&u| inside(u)).map(index));
+    res.bridges = puzzle.bridges.iter().cloned().filter(|&u| inside(u)).map(index).collect();
+    res.outside = puzzle.outside.iter().cloned().filter(|&u| inside(u)).map(index).collect();
+    res.one_way = puzzle.one_way.iter().filter(|&&(u, v)| inside(u) && inside(v)).map(|&(u, v)| (index(u), index(v))).collect();
+    res.costs = puzzle.costs.iter().filter(|(&u, _)| inside(u)).map(|(&u, &cost)| (index(u), cost)).collect();
+    // 消えた数字の通る点はどの線でもよくする
+    res.waypoints = puzzle.waypoints.iter().filter(|(&u, _)| inside(u)).map(|(&u, &num)| (index(u), if cut.contains(&num) { 0 } else { num })).collect();
+    res.limits = puzzle.limits.iter().filter(|(num, _)| res.cells.contains(num)).map(|(&num, &limit)| (num, limit)).collect();
+
+    // 点の盤面は中の点を行優先に振り直す
+    Some(if res.dots { renumber(&res) } else { res })
+}
+
+// 盤面を左上から columns 個ずつ並べて 1 つの盤面にする (行と列の幅は一番大きい盤面に合わせ, 余りは穴)
+// 数字は盤面ごとに前の盤面の数字の続きから振り直す. separate なら盤面の間に壁を置き (答えは盤面ごとの答えを並べたもの),
+// そうでなければ線が隣の盤面へ抜けてもよい
+// 並べられない盤面 (ワープ, 層, 六角, グラフ, コストの上限, 数字と点の混ざったもの, 別々のつながり方) があるか, 盤面がなければ None
+pub fn tile(puzzles: &[Puzzle], columns: usize, separate: bool) -> Option<Puzzle> {
+    let first = puzzles.first()?;
+    let plain = |puzzle: &Puzzle| {
+        matches!(puzzle.topology, Topology::Square | Topology::Diagonal) && puzzle.warps.is_empty() && puzzle.vias.is_empty() && puzzle.budget.is_none()
+            && puzzle.topology == first.topology && puzzle.dots == first.dots
+    };
+
+    if columns == 0 || !puzzles.iter().all(plain) {
+        return None;
+    }
+
+    let rows = puzzles.len().div_ceil(columns);
+    let heights: Vec<usize> = (0..rows).map(|r| puzzles.iter().skip(r*columns).take(columns).map(|puzzle| puzzle.height).max().unwrap()).collect();
+    let widths: Vec<usize> = (0..columns).map(|c| puzzles.iter().skip(c).step_by(columns).map(|puzzle| puzzle.width).max().unwrap_or(0)).collect();
+    let (width, height) = (widths.iter().sum(), heights.iter().sum());
+    let mut res = Puzzle { topology: first.topology, dots: first.dots, ..Puzzle::new(width, height) };
+    // セルごとの盤面の番号 (余りは None)
+    let mut owner = vec![None; width*height];
+    let mut offset = 0;
+
+    for (k, puzzle) in puzzles.iter().enumerate() {
+        let top: usize = heights[..k/columns].iter().sum();
+        let left: usize = widths[..k%columns].iter().sum();
+        let index = |u: usize| {
+            let (i, j) = puzzle.pos(u);
+
+            (top+i)*width + left+j
+        };
+        let label = |num: usize| if num > 0 { num+offset } else { 0 };
+
+        for (u, &num) in puzzle.cells.iter().enumerate() {
+            res.cells[index(u)] = label(num);
+            owner[index(u)] = Some(k);
+        }
+
+        res.walls.extend(puzzle.walls.iter().map(|&(u, v)| (index(u), index(v))));
+        res.holes.extend(puzzle.holes.iter().cloned().map(index));
+        res.bridges.extend(puzzle.bridges.iter().cloned().map(index));
+        res.outside.extend(puzzle.outside.iter().cloned().map(index));
+        res.one_way.extend(puzzle.one_way.iter().map(|&(u, v)| (index(u), index(v))));
+        res.costs.extend(puzzle.costs.iter().map(|(&u, &cost)| (index(u), cost)));
+        res.waypoints.extend(puzzle.waypoints.iter().map(|(&u, &num)| (index(u), label(num))));
+        res.limits.extend(puzzle.limits.iter().map(|(&num, &limit)| (label(num), limit)));
+
+        offset += puzzle.cells.iter().cloned().max().unwrap_or(0);
+    }
+
+    // 余りのセルは穴にする
+    res.holes.extend((0..width*height).filter(|&u| owner[u].is_none()));
+
+    if separate {
+        let edges = res.edges();
+
+        res.walls.extend(edges.into_iter().filter(|&(u, v)| owner[u].is_some() && owner[v].is_some() && owner[u] != owner[v]));
+    }
+
+    Some(if res.dots { renumber(&res) } else { res })
+}
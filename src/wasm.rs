@@ -0,0 +1,133 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+
+use crate::render::{paths, render_error_line, render_line, Format};
+use crate::{parse_puzzle, solve_numberlink, Error, Puzzle, Sol, SolverConfig, P};
+
+// JS からは solve(input) として呼ぶ. input は CLI と同じ puzz.link の URL, テキストか JSON の盤面
+// 答えは solve --format json の 1 行と同じ JSON, 解けなければ {"error":"..."}. 探索はすべて pure Rust (varisat か SAT を使わない探索) で, ブラウザの中で終わる
+#[wasm_bindgen]
+pub fn solve(input: &str) -> String {
+    let puzzle = match parse_puzzle(input.trim()) {
+        Some(puzzle) => puzzle,
+        None => return render_error_line(&Error::InvalidUrl, Format::Json),
+    };
+
+    match solve_numberlink(&puzzle, &SolverConfig::default()) {
+        Ok(sol) => render_line(&puzzle, &sol, Format::Json),
+        Err(e) => render_error_line(&e, Format::Json),
+    }
+}
+
+// canvas のセルの大きさ (px, render_svg と同じ)
+const CELL: f64 = 40.0;
+
+// canvas に描くもの 1 つ. 座標は canvas の px (盤面の外に太線の分の 1 px の余白)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    // 塗った長方形 (x, y, 幅, 高さ)
+    Fill { rect: (f64, f64, f64, f64), color: String },
+    // 長方形の枠
+    Frame { rect: (f64, f64, f64, f64), width: f64, color: String },
+    // 点を順に結ぶ線 (角は丸める)
+    Stroke { points: Vec<(f64, f64)>, width: f64, color: String },
+    // 中央揃えの黒い文字 (24px sans-serif)
+    Text { at: (f64, f64), text: String },
+}
+
+// canvas の大きさと, 描く順に並べた図形. 見た目は render_svg と同じで, 穴は灰色に塗り, 壁は太線で描く
+// draw はこれをそのまま canvas に描く (ブラウザがなくても中身を確かめられるように分けてある)
+pub fn scene(puzzle: &Puzzle, sol: Option<&Sol>) -> ((f64, f64), Vec<Shape>) {
+    let (width, height) = (puzzle.width as f64*CELL, puzzle.height as f64*CELL);
+    let centre = |(i, j): P| (j as f64*CELL+CELL/2.0+1.0, i as f64*CELL+CELL/2.0+1.0);
+    let line = |from, to, width| Shape::Stroke { points: vec![from, to], width, color: "black".to_string() };
+    let mut shapes = vec![Shape::Fill { rect: (0.0, 0.0, width+2.0, height+2.0), color: "white".to_string() }];
+
+    for u in (0..puzzle.cells.len()).filter(|&u| puzzle.is_hole(u)) {
+        let (i, j) = puzzle.pos(u);
+
+        shapes.push(Shape::Fill { rect: (j as f64*CELL+1.0, i as f64*CELL+1.0, CELL, CELL), color: "#ccc".to_string() });
+    }
+
+    for i in 1..puzzle.height {
+        shapes.push(line((1.0, i as f64*CELL+1.0), (width+1.0, i as f64*CELL+1.0), 0.5));
+    }
+
+    for j in 1..puzzle.width {
+        shapes.push(line((j as f64*CELL+1.0, 1.0), (j as f64*CELL+1.0, height+1.0), 0.5));
+    }
+
+    shapes.push(Shape::Frame { rect: (1.0, 1.0, width, height), width: 2.0, color: "black".to_string() });
+
+    // 壁は v (右か下のセル) の左か上の辺
+    for &(u, v) in &puzzle.walls {
+        let ((i, _), (k, l)) = (puzzle.pos(u), puzzle.pos(v));
+        let (x, y) = (l as f64*CELL+1.0, k as f64*CELL+1.0);
+
+        shapes.push(match i == k {
+            true => line((x, y), (x, y+CELL), 2.0),
+            false => line((x, y), (x+CELL, y), 2.0),
+        });
+    }
+
+    for (num, path) in sol.map(|sol| paths(puzzle, sol)).unwrap_or_default() {
+        shapes.push(Shape::Stroke { points: path.into_iter().map(centre).collect(), width: 6.0, color: format!("hsl({},70%,45%)", num*137%360) });
+    }
+
+    for (u, &num) in puzzle.cells.iter().enumerate().filter(|&(_, &num)| num > 0) {
+        shapes.push(Shape::Text { at: centre(puzzle.pos(u)), text: num.to_string() });
+    }
+
+    ((width+2.0, height+2.0), shapes)
+}
+
+// canvas を盤面の大きさにして, 盤面と (solution なら) 答えの線を描く. 解けなかったときも盤面は描いてから理由を Err で返す
+#[wasm_bindgen]
+pub fn draw(canvas: &HtmlCanvasElement, input: &str, solution: bool) -> Result<(), JsValue> {
+    let puzzle = parse_puzzle(input.trim()).ok_or_else(|| JsValue::from_str(&Error::InvalidUrl.to_string()))?;
+    let ctx: CanvasRenderingContext2d = canvas.get_context("2d")?.ok_or_else(|| JsValue::from_str("no 2d context"))?.dyn_into()?;
+    let res = match solution {
+        true => solve_numberlink(&puzzle, &SolverConfig::default()).map(Some),
+        false => Ok(None),
+    };
+    let ((width, height), shapes) = scene(&puzzle, res.as_ref().ok().and_then(Option::as_ref));
+
+    canvas.set_width(width as u32);
+    canvas.set_height(height as u32);
+    ctx.set_line_join("round");
+    ctx.set_font("24px sans-serif");
+    ctx.set_text_align("center");
+    ctx.set_text_baseline("middle");
+
+    for shape in shapes {
+        match shape {
+            Shape::Fill { rect: (x, y, w, h), color } => {
+                ctx.set_fill_style_str(&color);
+                ctx.fill_rect(x, y, w, h);
+            },
+            Shape::Frame { rect: (x, y, w, h), width, color } => {
+                ctx.set_stroke_style_str(&color);
+                ctx.set_line_width(width);
+                ctx.stroke_rect(x, y, w, h);
+            },
+            Shape::Stroke { points, width, color } => {
+                ctx.set_stroke_style_str(&color);
+                ctx.set_line_width(width);
+                ctx.begin_path();
+
+                for (k, &(x, y)) in points.iter().enumerate() {
+                    if k == 0 { ctx.move_to(x, y) } else { ctx.line_to(x, y) }
+                }
+
+                ctx.stroke();
+            },
+            Shape::Text { at: (x, y), text } => {
+                ctx.set_fill_style_str("black");
+                ctx.fill_text(&text, x, y)?;
+            },
+        }
+    }
+
+    res.map(|_| ()).map_err(|e| JsValue::from_str(&e.to_string()))
+}
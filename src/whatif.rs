@@ -0,0 +1,212 @@
+use varisat::{Lit, Var};
+
+use crate::template::template;
+use crate::{find_arc_var, number_lits, Encoding, Error, Puzzle, Session, Sol, SolverConfig, P};
+
+// 盤面に置いてみる仮定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Pin {
+    // 2 セルの間に線を引く (向きは問わない)
+    Line(P, P),
+    // 2 セルの間に線を引かない
+    NoLine(P, P),
+    // セルを数字 num の線が通る (橋のセルは横の線). 通る点と同じく, 空白の閉路を許す規則では数字 num と読まれた閉路でもよい
+    Cell(P, usize),
+}
+
+// 仮定の分かれ目
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Space {
+    // 仮定に合う答えがない
+    Empty,
+    Unique(Sol),
+    // 少なくとも count 個 (数えた上限に届いたら上限)
+    Several { count: usize, sol: Sol },
+}
+
+// 盤面を 1 度だけ符号化し, 仮定を付け外ししながら解き直す (エディタの「ここを通したらどうなる?」用)
+// 仮定はそれぞれ活性化変数で節にしてあり, 解くときに今の仮定の変数だけを真と仮定する
+pub struct Explorer {
+    puzzle: Puzzle,
+    session: Session,
+    encoding: Encoding,
+    // セルの数字を仮定できない盤面 (点, 長さの手がかり) は空
+    numbers: Vec<Vec<usize>>,
+    pins: Vec<(Pin, Var)>,
+}
+
+impl Explorer {
+    pub fn new(puzzle: &Puzzle, config: &SolverConfig) -> Result<Explorer, Error> {
+        let session = Session::new(puzzle, config)?;
+        let numbers = match puzzle.dots || config.rules.lengths {
+            true => vec![],
+            false => template(puzzle, config).numbers.clone(),
+        };
+
+        Ok(Explorer { puzzle: puzzle.clone(), session, encoding: config.encoding, numbers, pins: vec![] })
+    }
+
+    pub fn pins(&self) -> Vec<Pin> {
+        self.pins.iter().map(|&(pin, _)| pin).collect()
+    }
+
+    // 盤面にない辺や数字なら Error::InvalidPin. 同じ仮定を 2 度置いても 1 つ
+    pub fn pin(&mut self, pin: Pin) -> Result<(), Error> {
+        if self.pins.iter().any(|&(other, _)| other == pin) {
+            return Ok(());
+        }
+
+        let clauses: Vec<Vec<Lit>> = match pin {
+            Pin::Line(p, q) => {
+                let (x, y) = self.arc_pair(p, q)?;
+
+                vec![vec![x.positive(), y.positive()]]
+            },
+            Pin::NoLine(p, q) => {
+                let (x, y) = self.arc_pair(p, q)?;
+
+                vec![vec![x.negative()], vec![y.negative()]]
+            },
+            Pin::Cell(p, num) => {
+                if p.0 >= self.puzzle.height || p.1 >= self.puzzle.width {
+                    return Err(Error::InvalidPin);
+                }
+
+                let vars = self.numbers.get(self.puzzle.index(p)).ok_or(Error::InvalidPin)?;
+                let max = self.puzzle.cells.iter().cloned().max().unwrap_or(0);
+
+                if num == 0 || num > max || self.puzzle.is_hole(self.puzzle.index(p)) {
+                    return Err(Error::InvalidPin);
+                }
+
+                number_lits(self.encoding, vars, num).into_iter().map(|lit| vec![lit]).collect()
+            },
+        };
+
+        let act = self.session.fresh_var();
+
+        for mut clause in clauses {
+            clause.push(act.negative());
+            self.session.add_clause(&clause);
+        }
+
+        self.pins.push((pin, act));
+
+        Ok(())
+    }
+
+    // 外した仮定の節は活性化変数を偽に固定して無効にする. 置いていなければ false
+    pub fn unpin(&mut self, pin: Pin) -> bool {
+        let k = match self.pins.iter().position(|&(other, _)| other == pin) {
+            Some(k) => k,
+            None => return false,
+        };
+        let (_, act) = self.pins.remove(k);
+
+        self.session.add_clause(&[act.negative()]);
+
+        true
+    }
+
+    pub fn clear(&mut self) {
+        for pin in self.pins() {
+            self.unpin(pin);
+        }
+    }
+
+    // 今の仮定に合う答え (なければ None)
+    pub fn solve(&mut self) -> Result<Option<Sol>, Error> {
+        let assumptions = self.assumptions();
+
+        match self.session.run(&assumptions) {
+            Ok(sol) => Ok(Some(sol)),
+            Err(Error::NoSolution) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // 今の仮定に合う答えを limit 個 (2 以上) まで数える. 見つけた答えを禁じる節は数え終わったら無効にする
+    pub fn space(&mut self, limit: usize) -> Result<Space, Error> {
+        let mut assumptions = self.assumptions();
+        let act = self.session.fresh_var();
+        let mut first = None;
+        let mut count = 0;
+
+        assumptions.push(act.positive());
+
+        while count < limit.max(2) {
+            let sol = match self.session.run(&assumptions) {
+                Ok(sol) => sol,
+                Err(Error::NoSolution) => break,
+                Err(e) => {
+                    self.session.add_clause(&[act.negative()]);
+
+                    return Err(e);
+                },
+            };
+
+            // 全セルが覆われるので, 別解は sol の arc のどれかを使わない
+            let mut clause = vec![act.negative()];
+
+            clause.extend(sol.iter().filter_map(|&arc| find_arc_var(&self.puzzle, arc)).map(|x| x.negative()));
+            self.session.add_clause(&clause);
+
+            first = first.or(Some(sol));
+            count += 1;
+        }
+
+        self.session.add_clause(&[act.negative()]);
+
+        Ok(match (first, count) {
+            (None, _) => Space::Empty,
+            (Some(sol), 1) => Space::Unique(sol),
+            (Some(sol), count) => Space::Several { count, sol },
+        })
+    }
+
+    // 今の仮定で解けないとき, それだけで解けなくなる仮定の組 (どれを外しても解ける). 解けるなら None
+    // ソルバの返す矛盾に使った仮定から始めて, 外しても解けないものを 1 つずつ外す. 仮定なしで解けない盤面なら空
+    pub fn conflict(&mut self) -> Result<Option<Vec<Pin>>, Error> {
+        let mut core = match self.failed(&self.pins.clone())? {
+            Some(core) => core,
+            None => return Ok(None),
+        };
+        let mut k = 0;
+
+        while k < core.len() {
+            let mut rest = core.clone();
+
+            rest.remove(k);
+
+            match self.failed(&rest)? {
+                Some(smaller) => core = smaller,
+                None => k += 1,
+            }
+        }
+
+        Ok(Some(core.into_iter().map(|(pin, _)| pin).collect()))
+    }
+
+    // pins だけを仮定して解けなければ, そのうち矛盾に使ったもの (元の順)
+    fn failed(&mut self, pins: &[(Pin, Var)]) -> Result<Option<Vec<(Pin, Var)>>, Error> {
+        let assumptions: Vec<Lit> = pins.iter().map(|&(_, act)| act.positive()).collect();
+
+        match self.session.run(&assumptions) {
+            Ok(_) => Ok(None),
+            Err(Error::NoSolution) => {
+                let core = self.session.failed().unwrap_or(assumptions);
+
+                Ok(Some(pins.iter().filter(|&&(_, act)| core.contains(&act.positive())).cloned().collect()))
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    fn assumptions(&self) -> Vec<Lit> {
+        self.pins.iter().map(|&(_, act)| act.positive()).collect()
+    }
+
+    fn arc_pair(&self, p: P, q: P) -> Result<(Var, Var), Error> {
+        find_arc_var(&self.puzzle, (p, q)).zip(find_arc_var(&self.puzzle, (q, p))).ok_or(Error::InvalidPin)
+    }
+}
@@ -0,0 +1,202 @@
+use varisat::{ExtendFormula, Lit, Var};
+
+use crate::input::puzzlink;
+use crate::loops::{add_count, loop_json, render_loop, solve_loop, Grid};
+use crate::render::Format;
+use crate::{Error, SolverConfig, P};
+
+// 矢印の手がかり: dir (Grid::step の向き) の先にある黒マスの数. どちらかがないものもある (puzz.link の向きなしと ?)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clue {
+    pub dir: Option<usize>,
+    pub count: Option<usize>,
+}
+
+// Yajilin: 手がかりでないセルは黒マスにするか 1 本の輪が通る. 黒マスは隣り合わず, 輪は手がかりと黒マスを通らない
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Yajilin {
+    pub width: usize,
+    pub height: usize,
+    // 行優先
+    pub clues: Vec<Option<Clue>>,
+}
+
+const ARROWS: [char; 4] = ['>', 'v', '<', '^'];
+
+// puzz.link でのパズルの名前
+pub(crate) const NAMES: &[&str] = &["yajilin", "yajirin"];
+
+impl Yajilin {
+    pub fn new(width: usize, height: usize) -> Yajilin {
+        Yajilin { width, height, clues: vec![None; width*height] }
+    }
+
+    pub fn grid(&self) -> Grid {
+        Grid { rows: self.height, cols: self.width }
+    }
+
+    fn is_clue(&self, (i, j): P) -> bool {
+        self.clues[i*self.width+j].is_some()
+    }
+
+    // 答えの輪が通らない, 手がかりでないセル
+    pub fn shaded(&self, cycle: &[P]) -> Vec<P> {
+        self.grid().cells().filter(|&p| !self.is_clue(p) && !cycle.contains(&p)).collect()
+    }
+}
+
+// puzz.link の yajilin/W/H/CODE か, 行を `/`, セルを `,` で区切ったテキスト
+// (`.` は空き, 手がかりは数字のあとに向き `>` `v` `<` `^` で, 数字の代わりの `?` と向きの省略もできる)
+pub fn parse_yajilin(input: &str) -> Option<Yajilin> {
+    match puzzlink(input, NAMES) {
+        Some((width, height, code)) => decode_yajilin(width, height, code),
+        None => parse_yajilin_text(input),
+    }
+}
+
+// 0-4 は向き (なし, 上, 下, 左, 右) と 16 進 1 桁の数字 (`.` は ?), 5-9 は向き+5 と 2 桁, `-` は向きと 3 桁, a-z は 1-26 個の空き
+fn decode_yajilin(width: usize, height: usize, code: &str) -> Option<Yajilin> {
+    let mut puzzle = Yajilin::new(width, height);
+    let chars: Vec<char> = code.chars().collect();
+    let (mut k, mut u) = (0, 0);
+    let dir = |ch: char| -> Option<Option<usize>> { [None, Some(3), Some(1), Some(2), Some(0)].get(ch.to_digit(16)? as usize).cloned() };
+    let hex = |k: usize, len: usize| -> Option<usize> { usize::from_str_radix(&chars.get(k..k+len)?.iter().collect::<String>(), 16).ok() };
+
+    while k < chars.len() {
+        if u >= puzzle.clues.len() {
+            return None;
+        }
+
+        match chars[k] {
+            ch @ '0'..='4' => {
+                let count = if chars.get(k+1) == Some(&'.') { None } else { Some(hex(k+1, 1)?) };
+
+                puzzle.clues[u] = Some(Clue { dir: dir(ch)?, count });
+                k += 2;
+            },
+            ch @ '5'..='9' => {
+                puzzle.clues[u] = Some(Clue { dir: dir(char::from_digit(ch.to_digit(16)?-5, 16)?)?, count: Some(hex(k+1, 2)?) });
+                k += 3;
+            },
+            '-' => {
+                puzzle.clues[u] = Some(Clue { dir: dir(*chars.get(k+1)?)?, count: Some(hex(k+2, 3)?) });
+                k += 5;
+            },
+            ch @ 'a'..='z' => {
+                u += ch.to_digit(36)? as usize-10;
+                k += 1;
+            },
+            _ => return None,
+        }
+
+        u += 1;
+    }
+
+    Some(puzzle)
+}
+
+fn parse_yajilin_text(input: &str) -> Option<Yajilin> {
+    let rows: Vec<Vec<Option<Clue>>> = input.trim().split(['/', '\n']).map(|row| {
+        row.trim().split(',').map(|cell| match cell.trim() {
+            "." => Some(None),
+            cell => {
+                let (count, dir) = match cell.char_indices().last() {
+                    Some((k, ch)) if ARROWS.contains(&ch) => (&cell[..k], ARROWS.iter().position(|&arrow| arrow == ch)),
+                    _ => (cell, None),
+                };
+                let count = match count {
+                    "?" => None,
+                    count => Some(count.parse().ok()?),
+                };
+
+                Some(Some(Clue { dir, count }))
+            },
+        }).collect::<Option<Vec<_>>>()
+    }).collect::<Option<Vec<_>>>()?;
+    let width = rows.first()?.len();
+
+    if rows.iter().any(|row| row.len() != width) {
+        return None;
+    }
+
+    Some(Yajilin { width, height: rows.len(), clues: rows.concat() })
+}
+
+// 答えの輪が通るセル (行, 列) を順に. 黒マスは Yajilin::shaded
+pub fn solve_yajilin(puzzle: &Yajilin, config: &SolverConfig) -> Result<Vec<P>, Error> {
+    if puzzle.width == 0 || puzzle.height == 0 || puzzle.clues.len() != puzzle.width*puzzle.height {
+        return Err(Error::InvalidField);
+    }
+
+    let grid = puzzle.grid();
+
+    solve_loop(&grid, config, |formula| {
+        let cells: Vec<P> = grid.cells().collect();
+        let mut shaded: Vec<Option<Var>> = vec![None; cells.len()];
+
+        for &p in &cells {
+            let edges = grid.incident(p);
+
+            if puzzle.is_clue(p) {
+                for x in &edges {
+                    formula.add_clause(&[x.negative()]);
+                }
+
+                continue;
+            }
+
+            // 黒マスなら輪が通らず, そうでなければ通る
+            let s = formula.new_var();
+
+            for x in &edges {
+                formula.add_clause(&[s.negative(), x.negative()]);
+            }
+
+            formula.add_clause(&edges.iter().map(|x| x.positive()).chain(std::iter::once(s.positive())).collect::<Vec<Lit>>());
+
+            shaded[p.0*puzzle.width+p.1] = Some(s);
+        }
+
+        // 黒マスは隣り合わない (右と下だけ見れば足りる)
+        for &p in &cells {
+            for q in (0..2).filter_map(|d| grid.step(p, d)) {
+                if let (Some(s), Some(t)) = (shaded[p.0*puzzle.width+p.1], shaded[q.0*puzzle.width+q.1]) {
+                    formula.add_clause(&[s.negative(), t.negative()]);
+                }
+            }
+        }
+
+        for &p in &cells {
+            let (d, n) = match puzzle.clues[p.0*puzzle.width+p.1] {
+                Some(Clue { dir: Some(d), count: Some(n) }) => (d, n),
+                _ => continue,
+            };
+            let mut line = vec![];
+            let mut q = p;
+
+            while let Some(next) = grid.step(q, d) {
+                line.extend(shaded[next.0*puzzle.width+next.1]);
+                q = next;
+            }
+
+            add_count(formula, &line, n);
+        }
+    })
+}
+
+// 黒マスは `#`, 手がかりは数字と向き
+pub fn render_yajilin(puzzle: &Yajilin, cycle: &[P], format: Format) -> String {
+    let shaded = puzzle.shaded(cycle);
+
+    if format == Format::Json {
+        let cells: Vec<String> = shaded.iter().map(|&(i, j)| format!("[{},{}]", i, j)).collect();
+
+        return loop_json(puzzle.width, puzzle.height, cycle, &format!(",\"shaded\":[{}]", cells.join(",")));
+    }
+
+    render_loop(&puzzle.grid(), cycle, format, |(i, j)| match puzzle.clues[i*puzzle.width+j] {
+        Some(clue) => format!("{}{}", clue.count.map_or("?".to_string(), |n| n.to_string()), clue.dir.map_or(String::new(), |d| ARROWS[d].to_string())),
+        None if shaded.contains(&(i, j)) => "#".to_string(),
+        None => "+".to_string(),
+    })
+}
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::atomic::Ordering;
+
+use crate::{parse_field, sat_only, Error, Instant, Puzzle, Role, Sol, SolverConfig, Stats, Topology};
+
+// 期限, 中断, メモリを確かめる間隔 (展開した節点の数)
+const CHECK_NODES: usize = 1 << 12;
+
+// 次数 1 の端の相方が frontier から出たとき
+const OUT: u32 = u32::MAX;
+
+const FALSE: usize = 0;
+const TRUE: usize = 1;
+
+// frontier のセルの状態. 次数が 1 のときだけ断片のもう一方の端と断片の数字 (0 は未定) を持つ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Slot {
+    cell: u32,
+    deg: u8,
+    mate: u32,
+    label: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    lo: usize,
+    hi: usize,
+    // hi で空白だけの閉路が閉じる (向きが 2 通りあるので数え方が 2 倍になる)
+    cycle: bool,
+}
+
+// 辺を行優先に並べた frontier 法 (Simpath) で作る, 辺の部分集合としての全解の ZDD
+// 符号化と同じく空白だけの閉路も解に含め, 解の数は arc の集合として数える (閉路 1 つにつき 2 通り)
+pub struct Zdd {
+    puzzle: Puzzle,
+    edges: Vec<(usize, usize)>,
+    roles: Vec<Role>,
+    nodes: Vec<Node>,
+    counts: Vec<u128>,
+}
+
+impl Zdd {
+    pub fn build(puzzle: &Puzzle, config: &SolverConfig) -> Result<Zdd, Error> {
+        if puzzle.width == 0 || puzzle.height == 0 || puzzle.cells.len() != puzzle.width*puzzle.height {
+            return Err(Error::InvalidField);
+        }
+
+        if let Some(feature) = sat_only(puzzle, config) {
+            return Err(Error::BackendFailed(format!("the zdd backend does not support {}", feature)));
+        }
+
+        let roles = parse_field(puzzle).ok_or(Error::InvalidField)?;
+        let n = puzzle.cells.len();
+        let mut edges = puzzle.edges();
+
+        // ワープと層をまたぐ辺は後ろの端のセルの番で見る (盤面の最初の行や列が最後まで frontier に残らないように)
+        if !puzzle.warps.is_empty() || !puzzle.vias.is_empty() {
+            edges.sort_by_key(|&(u, v)| (u.max(v), u.min(v)));
+        }
+
+        let mut last = vec![None; n];
+
+        for (e, &(u, v)) in edges.iter().enumerate() {
+            last[u] = Some(e);
+            last[v] = Some(e);
+        }
+
+        // 穴でない辺のないセル (1x1 の盤面など) は次数を満たせないので, 終端だけの (数が 0 の) ZDD
+        let nodes = match last.iter().zip(&roles).any(|(last, &role)| last.is_none() && role != Role::Hole) {
+            true => vec![Node { lo: FALSE, hi: FALSE, cycle: false }; TRUE+1],
+            false => Builder { puzzle, config, roles: &roles, edges: &edges, last: &last, start: Instant::now() }.run()?,
+        };
+
+        // 子は親より後に作るので後ろから数える
+        let mut counts = vec![0u128; nodes.len()];
+
+        counts[TRUE] = 1;
+
+        for id in (TRUE+1..nodes.len()).rev() {
+            let node = nodes[id];
+            let hi = if node.cycle { counts[node.hi].saturating_mul(2) } else { counts[node.hi] };
+
+            counts[id] = counts[node.lo].saturating_add(hi);
+        }
+
+        Ok(Zdd { puzzle: puzzle.clone(), edges, roles, nodes, counts })
+    }
+
+    // 解の数 (u128 に収まらなければ u128::MAX)
+    pub fn count(&self) -> u128 {
+        self.counts.get(TRUE+1).copied().unwrap_or(0)
+    }
+
+    // 終端を除いた節点の数
+    pub fn size(&self) -> usize {
+        self.nodes.len()-2
+    }
+
+    // 解の 1 つ (使う辺を前から貪欲に選ぶ)
+    pub fn solution(&self) -> Option<Sol> {
+        if self.count() == 0 {
+            return None;
+        }
+
+        let n = self.puzzle.cells.len();
+        let mut adj = vec![vec![]; n];
+        let mut id = TRUE+1;
+
+        for &(u, v) in &self.edges {
+            let node = self.nodes[id];
+
+            if self.counts[node.hi] > 0 {
+                adj[u].push(v);
+                adj[v].push(u);
+                id = node.hi;
+            } else {
+                id = node.lo;
+            }
+        }
+
+        Some(orient(&self.puzzle, &self.roles, &adj))
+    }
+}
+
+pub fn count_solutions(puzzle: &Puzzle, config: &SolverConfig) -> Result<u128, Error> {
+    Ok(Zdd::build(puzzle, config)?.count())
+}
+
+pub fn solve_zdd(puzzle: &Puzzle, config: &SolverConfig) -> Result<Sol, Error> {
+    Zdd::build(puzzle, config)?.solution().ok_or(Error::NoSolution)
+}
+
+// 経路は始点から, 残った閉路は適当な向きにたどって arc にする
+pub(crate) fn orient(puzzle: &Puzzle, roles: &[Role], adj: &[Vec<usize>]) -> Sol {
+    let n = puzzle.cells.len();
+    let mut seen = vec![false; n];
+    let mut res = vec![];
+    let starts = (0..n).filter(|&u| roles[u] == Role::Start);
+    let blanks = (0..n).filter(|&u| roles[u] == Role::Blank);
+
+    for first in starts.chain(blanks) {
+        if seen[first] {
+            continue;
+        }
+
+        let (mut prev, mut u) = (usize::MAX, first);
+
+        seen[u] = true;
+
+        while let Some(&v) = adj[u].iter().find(|&&v| v != prev && (!seen[v] || v == first)) {
+            res.push((puzzle.pos(u), puzzle.pos(v)));
+
+            if v == first {
+                break;
+            }
+
+            seen[v] = true;
+            prev = u;
+            u = v;
+        }
+    }
+
+    res
+}
+
+struct Builder<'a> {
+    puzzle: &'a Puzzle,
+    config: &'a SolverConfig,
+    roles: &'a [Role],
+    edges: &'a [(usize, usize)],
+    last: &'a [Option<usize>],
+    start: Instant,
+}
+
+impl Builder<'_> {
+    fn run(&self) -> Result<Vec<Node>, Error> {
+        let placeholder = Node { lo: FALSE, hi: FALSE, cycle: false };
+        let mut nodes = vec![placeholder, placeholder, placeholder];
+        let mut level: Vec<(Vec<Slot>, usize)> = vec![(vec![], TRUE+1)];
+        let mut visited = 0;
+
+        for e in 0..self.edges.len() {
+            let mut next: HashMap<Vec<Slot>, usize> = HashMap::new();
+
+            for (state, id) in mem::take(&mut level) {
+                let lo = self.child(&state, e, false).map(|(state, _)| state);
+                let hi = self.child(&state, e, true);
+                let cycle = hi.as_ref().is_some_and(|&(_, cycle)| cycle);
+                let lo = self.intern(lo, e, &mut next, &mut nodes, &mut level);
+                let hi = self.intern(hi.map(|(state, _)| state), e, &mut next, &mut nodes, &mut level);
+
+                nodes[id] = Node { lo, hi, cycle };
+                visited += 1;
+
+                if visited%CHECK_NODES == 0 {
+                    self.check(&nodes, &next)?;
+                }
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    // 次の段の同じ状態は同じ節点にまとめる
+    fn intern(&self, state: Option<Vec<Slot>>, e: usize, next: &mut HashMap<Vec<Slot>, usize>, nodes: &mut Vec<Node>, level: &mut Vec<(Vec<Slot>, usize)>) -> usize {
+        let state = match state {
+            Some(state) => state,
+            None => return FALSE,
+        };
+
+        if e+1 == self.edges.len() {
+            return if state.is_empty() { TRUE } else { FALSE };
+        }
+
+        if let Some(&id) = next.get(&state) {
+            return id;
+        }
+
+        let id = nodes.len();
+
+        nodes.push(Node { lo: FALSE, hi: FALSE, cycle: false });
+        next.insert(state.clone(), id);
+        level.push((state, id));
+
+        id
+    }
+
+    // 辺 e を使う (use_edge) / 使わないときの次の状態 (矛盾すれば None) と, 閉路が閉じたか
+    fn child(&self, state: &[Slot], e: usize, use_edge: bool) -> Option<(Vec<Slot>, bool)> {
+        let (u, v) = self.edges[e];
+        let mut state = state.to_vec();
+
+        for x in [u, v] {
+            if let Err(pos) = state.binary_search_by_key(&(x as u32), |slot| slot.cell) {
+                state.insert(pos, Slot { cell: x as u32, deg: 0, mate: OUT, label: 0 });
+            }
+        }
+
+        let find = |state: &[Slot], x: u32| state.binary_search_by_key(&x, |slot| slot.cell).ok();
+        let (pu, pv) = (find(&state, u as u32)?, find(&state, v as u32)?);
+        let mut cycle = false;
+
+        if use_edge {
+            let (su, sv) = (state[pu], state[pv]);
+
+            if su.deg >= self.max_deg(u) || sv.deg >= self.max_deg(v) {
+                return None;
+            }
+
+            let (lu, lv) = (self.label(su), self.label(sv));
+
+            if lu != 0 && lv != 0 && lu != lv {
+                return None;
+            }
+
+            if su.deg == 1 && su.mate == v as u32 {
+                cycle = true;
+                state[pu] = Slot { deg: 2, mate: OUT, label: 0, ..su };
+                state[pv] = Slot { deg: 2, mate: OUT, label: 0, ..sv };
+            } else {
+                // 新しい断片の両端
+                let eu = if su.deg == 0 { u as u32 } else { su.mate };
+                let ev = if sv.deg == 0 { v as u32 } else { sv.mate };
+                let label = lu.max(lv);
+
+                for (p, s) in [(pu, su), (pv, sv)] {
+                    state[p] = Slot { deg: s.deg+1, mate: OUT, label: 0, ..s };
+                }
+
+                for (end, mate) in [(eu, ev), (ev, eu)] {
+                    if end != OUT {
+                        let p = find(&state, end)?;
+
+                        state[p].mate = mate;
+                        state[p].label = label;
+                    }
+                }
+            }
+        }
+
+        // 最後の辺を見終えたセルは次数が決まるので frontier から外す
+        for x in [v, u] {
+            if self.last[x] != Some(e) {
+                continue;
+            }
+
+            let p = find(&state, x as u32)?;
+            let slot = state[p];
+
+            if slot.deg != self.max_deg(x) {
+                return None;
+            }
+
+            // 相方も同時に外れるなら何もしない
+            if let Some(q) = find(&state, slot.mate).filter(|_| slot.deg == 1) {
+                state[q].mate = OUT;
+            }
+
+            state.remove(p);
+        }
+
+        // ワープがあると盤面が平面でなくなるので使えない (六角形の盤面は frontier の並びが外周の順にならない)
+        if self.puzzle.warps.is_empty() && self.puzzle.topology == Topology::Square && self.crossing(&state) {
+            return None;
+        }
+
+        Some((state, cycle))
+    }
+
+    // frontier は残りの盤面の外周に番号順に並ぶので, 残りの部分でつなぐしかない同じ数字の 2 点の組どうしは交差できない
+    // (数字が決まった断片の空白の端と, まだ辺のない数字のセル)
+    // 数字の決まっていない空白の断片があると, 道がその断片を通って処理済みの側へ回り込めるので何も言えない
+    fn crossing(&self, state: &[Slot]) -> bool {
+        if state.iter().any(|slot| slot.deg == 1 && self.roles[slot.cell as usize] == Role::Blank && slot.label == 0) {
+            return false;
+        }
+
+        let open: Vec<u32> = state.iter().filter_map(|slot| match slot.deg {
+            0 if self.roles[slot.cell as usize] != Role::Blank => Some(self.puzzle.cells[slot.cell as usize] as u32),
+            1 if self.roles[slot.cell as usize] == Role::Blank && slot.label != 0 => Some(slot.label),
+            _ => None,
+        }).collect();
+
+        // 組になっていない点は飛ばして, 括弧の対応として閉じられるかを見る
+        let mut stack: Vec<u32> = vec![];
+
+        for (k, &label) in open.iter().enumerate() {
+            if !open[k+1..].contains(&label) && !open[..k].contains(&label) {
+                continue;
+            }
+
+            if stack.last() == Some(&label) {
+                stack.pop();
+            } else if stack.contains(&label) {
+                return true;
+            } else {
+                stack.push(label);
+            }
+        }
+
+        false
+    }
+
+    // 数字のセルは端 (次数 1), 空白は経路か閉路の途中 (次数 2)
+    fn max_deg(&self, x: usize) -> u8 {
+        let (out_degree, in_degree) = self.roles[x].degrees();
+
+        (out_degree+in_degree) as u8
+    }
+
+    fn label(&self, slot: Slot) -> u32 {
+        if slot.deg == 0 { self.puzzle.cells[slot.cell as usize] as u32 } else { slot.label }
+    }
+
+    fn check(&self, nodes: &[Node], next: &HashMap<Vec<Slot>, usize>) -> Result<(), Error> {
+        // 次の段の状態は表と処理待ちの列に 1 つずつ持つ
+        let memory = (mem::size_of_val(nodes) + 2*next.keys().map(|state| state.len()*mem::size_of::<Slot>() + 64).sum::<usize>()) as u64;
+        let stats = || Stats { elapsed: self.start.elapsed(), conflicts: 0, memory };
+
+        if self.config.interrupt.as_ref().is_some_and(|interrupt| interrupt.load(Ordering::Relaxed)) {
+            return Err(Error::Interrupted(stats()));
+        }
+
+        if self.config.timeout.is_some_and(|timeout| self.start.elapsed() >= timeout) {
+            return Err(Error::Timeout(stats()));
+        }
+
+        if self.config.max_memory.is_some_and(|limit| memory > limit) {
+            return Err(Error::MemoryLimit(stats()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,5861 @@
+// 小さい盤面で SAT の答えと SAT を使わない探索の答えを突き合わせる
+
+use std::collections::BTreeSet;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc as Shared, Mutex};
+use std::time::Duration;
+
+use varisat::{CnfFormula, ProofFormat, Solver};
+
+use numberlink_solver::backend::{select, Backend, Query};
+use numberlink_solver::backtrack::solve_backtrack;
+use numberlink_solver::cache::ResultCache;
+use numberlink_solver::checkpoint;
+use numberlink_solver::canonical::{canonical, canonical_hash, canonical_with, dedup};
+#[cfg(unix)]
+use numberlink_solver::compare::{agree, compare, render_outcomes};
+use numberlink_solver::constraints::{parse_constraints, Constraint};
+use numberlink_solver::crosscheck::{crosscheck, render_verdict, Checker};
+#[cfg(unix)]
+use numberlink_solver::daemon::{read_frame, request, serve_socket, write_frame, MAX_CONNECTIONS};
+use numberlink_solver::deduce::{deduce, grade, Grade, Technique};
+use numberlink_solver::diff::{diff, render_diff};
+use numberlink_solver::estimate::estimate;
+use numberlink_solver::events::{arc_of, replay_proof, Event, Events};
+use numberlink_solver::experiment::{matrix, run};
+use numberlink_solver::generate::{daily_seed, from_paths, generate, generate_with, quality, write_puzzle, GenConfig, Output, Quality, Symmetry, Target, Trivial, today};
+use numberlink_solver::phase::{flips, Phase};
+use numberlink_solver::portfolio::{portfolio_configs, solve_portfolio, solve_portfolio_ordered};
+use numberlink_solver::predict::{predict, Difficulty};
+use numberlink_solver::profile::profile;
+use numberlink_solver::rate::{rate, Tier};
+use numberlink_solver::region::solve_regions;
+use numberlink_solver::report::{entries, render_report, ReportFormat};
+use numberlink_solver::genre::{detect, solve_genre, Genre};
+use numberlink_solver::greedy::{render_routing, route_greedy, ROUNDS};
+use numberlink_solver::hint::{explain, hint, hint_with, PartialSolution};
+use numberlink_solver::input::{parse_json, parse_paths};
+use numberlink_solver::hashi::{parse_hashi, render_hashi, solve_hashi, Bridges, Hashi};
+use numberlink_solver::live::{render_live, subscribe_live, LiveState};
+use numberlink_solver::loops::Grid;
+use numberlink_solver::masyu::{parse_masyu, render_masyu, solve_masyu, Masyu, Pearl};
+use numberlink_solver::minimize::minimize;
+use numberlink_solver::repair::{suggest_solvable, suggest_unique, Edit};
+use numberlink_solver::nets::Nets;
+use numberlink_solver::optimize::{all_optimal, count_turns, minimize_cost, minimize_turns, minimize_turns_from, total_cost, Objective};
+use numberlink_solver::render::{paths, register_renderer, render, render_error_line, render_flow, render_line, render_puzzle_json, render_url, renderer_names, Format, Renderer, Theme};
+use numberlink_solver::route::{route_nets, Net, RouteObjective, RouteProblem, RoutedNet};
+use numberlink_solver::rules::{verify, Crossing};
+use numberlink_solver::sample::sample_solutions;
+use numberlink_solver::screen::{screen, Reason, Suspect};
+use numberlink_solver::slither::{parse_slither, render_slither, solve_slither, Slither};
+use numberlink_solver::source::{blocking, open_source, register_source, source_schemes, Fetch, PuzzleSource};
+use numberlink_solver::yajilin::{parse_yajilin, render_yajilin, solve_yajilin, Clue, Yajilin};
+use numberlink_solver::tags::{encode_tagged, family_stats, render_families, unsat_core, Family};
+use numberlink_solver::template::template;
+use numberlink_solver::tidy::shorten;
+use numberlink_solver::transform::{crop, renumber, renumber_with, tile, transform, transform_sol, Transform};
+use numberlink_solver::whatif::{Explorer, Pin, Space};
+use numberlink_solver::zdd::Zdd;
+use numberlink_solver::{count_numberlink, encode_formula, parse_puzzle, parse_url, solve_numberlink, try_parse_puzzle, Encoding, Error, Interrupt, Progress, Puzzle, RuleSet, Session, Sol, SolverConfig, Stats, Topology, Tuning, Wrap, MAX_CELLS, P};
+
+// 再現できるよう固定の線形合同法で盤面を作る
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, n: usize) -> usize {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+
+        (self.0 >> 33) as usize%n
+    }
+}
+
+fn random_puzzle(rng: &mut Lcg) -> Puzzle {
+    let width = 2+rng.next(4);
+    let height = 2+rng.next(4);
+    let mut puzzle = Puzzle::new(width, height);
+    let pairs = 1+rng.next((width*height/2).min(4));
+
+    for num in 1..=pairs {
+        for _ in 0..2 {
+            loop {
+                let index = rng.next(width*height);
+
+                if puzzle.cells[index] == 0 {
+                    puzzle.cells[index] = num;
+                    break;
+                }
+            }
+        }
+    }
+
+    puzzle
+}
+
+// 解の条件を符号化とは別に確かめる: 始点は出るだけ, 終点は入るだけ, 空白は 1 本ずつ, 経路は同じ数字どうしを結ぶ
+fn check(puzzle: &Puzzle, sol: &Sol) {
+    let n = puzzle.cells.len();
+    let mut out = vec![None; n];
+    let mut indeg = vec![0; n];
+
+    for &(u, v) in sol {
+        let (u, v) = (puzzle.index(u), puzzle.index(v));
+
+        assert!(puzzle.is_open(u, v), "arc {:?} crosses a wall or a missing via", (puzzle.pos(u), puzzle.pos(v)));
+        assert!(out[u].is_none(), "two arcs leave {:?}", puzzle.pos(u));
+        out[u] = Some(v);
+        indeg[v] += 1;
+    }
+
+    for &(u, v) in sol {
+        assert!(!sol.contains(&(v, u)), "arc {:?} is used both ways", (u, v));
+    }
+
+    let mut seen = vec![false; puzzle.cells.iter().cloned().max().unwrap_or(0)+1];
+
+    for u in 0..n {
+        let num = puzzle.cells[u];
+        let start = num > 0 && !seen[num];
+
+        if num > 0 {
+            seen[num] = true;
+        }
+
+        if puzzle.is_hole(u) {
+            assert!(indeg[u] == 0 && out[u].is_none(), "path through hole {:?}", puzzle.pos(u));
+            continue;
+        }
+
+        match (num > 0, start) {
+            (true, true) => assert!(indeg[u] == 0 && out[u].is_some(), "bad start {:?}", puzzle.pos(u)),
+            (true, false) => assert!(indeg[u] == 1 && out[u].is_none(), "bad end {:?}", puzzle.pos(u)),
+            _ => assert!(indeg[u] == 1 && out[u].is_some(), "bad blank {:?}", puzzle.pos(u)),
+        }
+
+        if start {
+            let mut v = u;
+
+            while let Some(w) = out[v] {
+                v = w;
+            }
+
+            assert_eq!(puzzle.cells[v], num, "path from {:?} ends at another number", puzzle.pos(u));
+        }
+    }
+}
+
+fn answer(res: &Result<Sol, Error>) -> bool {
+    match res {
+        Ok(_) => true,
+        Err(Error::NoSolution) => false,
+        Err(e) => panic!("unexpected error: {}", e),
+    }
+}
+
+fn cross_check(puzzle: &Puzzle) {
+    let expected = solve_backtrack(puzzle, &SolverConfig::default());
+
+    if let Ok(sol) = &expected {
+        check(puzzle, sol);
+
+        // 探索の解は符号化の条件も満たす
+        let pins: Vec<_> = sol.iter().map(|&arc| (arc, true)).collect();
+
+        Session::new(puzzle, &SolverConfig::default()).unwrap().solve_assuming(&pins).unwrap();
+    }
+
+    // 線を引けないセルがあっても ZDD は作れて, 数が 0 になる
+    let zdd = Zdd::build(puzzle, &SolverConfig::default()).unwrap();
+
+    assert_eq!(zdd.count() > 0, answer(&expected), "zdd disagrees on {:?}", puzzle.cells);
+    assert_eq!(zdd.solution().is_some(), answer(&expected));
+    assert_eq!(count_numberlink(puzzle, &SolverConfig { backend: Backend::Zdd, ..SolverConfig::default() }), Ok(zdd.count()));
+
+    if let Some(sol) = zdd.solution() {
+        check(puzzle, &sol);
+    }
+
+    for encoding in [Encoding::Log, Encoding::Direct] {
+        let res = solve_numberlink(puzzle, &SolverConfig { encoding, ..SolverConfig::default() });
+
+        assert_eq!(answer(&res), answer(&expected), "{:?} disagrees on {:?}", encoding, puzzle.cells);
+
+        if let Ok(sol) = &res {
+            check(puzzle, sol);
+        }
+    }
+}
+
+// benches/corpus.txt の盤面に, 解けない盤面と小さい乱択の盤面を足したもの
+fn corpus(seed: u64) -> Vec<Puzzle> {
+    let mut rng = Lcg(seed);
+    let mut puzzles: Vec<Puzzle> = include_str!("../benches/corpus.txt").lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| parse_url(line.split_whitespace().nth(1)?.to_string()))
+        .collect();
+
+    puzzles.push(parse_url("numlin/2/2/1221".to_string()).unwrap());
+    puzzles.extend((0..20).map(|_| random_puzzle(&mut rng)));
+    puzzles
+}
+
+// 既定の設定 (varisat) と同じ盤面が解けて, 解は条件を満たす
+fn agrees(puzzles: &[Puzzle], config: &SolverConfig) {
+    for puzzle in puzzles {
+        let res = solve_numberlink(puzzle, config);
+
+        assert_eq!(answer(&res), answer(&solve_numberlink(puzzle, &SolverConfig::default())), "{:?} disagrees on {:?}", config.backend, puzzle.cells);
+
+        if let Ok(sol) = &res {
+            check(puzzle, sol);
+        }
+    }
+}
+
+// コマンドを引数つきで走らせた (終了コード, 標準出力)
+fn cli(args: &[&str]) -> (i32, String) {
+    cli_input(args, "")
+}
+
+// input を stdin に流す (書き込みは別のスレッドで, 出力を読むのと並べて進める)
+fn cli_input(args: &[&str], input: &str) -> (i32, String) {
+    let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_numberlink_solver"));
+
+    command.args(args);
+    run_input(command, input)
+}
+
+fn run_input(mut command: std::process::Command, input: &str) -> (i32, String) {
+    let mut child = command
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_string();
+    let writer = std::thread::spawn(move || std::io::Write::write_all(&mut stdin, input.as_bytes()));
+    let output = child.wait_with_output().unwrap();
+
+    writer.join().unwrap().unwrap();
+
+    (output.status.code().unwrap_or(-1), String::from_utf8(output.stdout).unwrap())
+}
+
+// このテストと同じ features で build した cdylib (テストの実行ファイルと同じ deps/ にある. target/debug のものは
+// 別の features のときのままのことがある) を name という名前で一時ディレクトリに写し, そのディレクトリを返す
+// Python や Node.js はファイル名でモジュールを探すので, テストごとに別のディレクトリにする
+#[cfg(any(feature = "python", feature = "node"))]
+fn cdylib(test: &str, name: &str) -> std::path::PathBuf {
+    let lib = env::current_exe().unwrap().with_file_name(format!("{}numberlink_solver{}", env::consts::DLL_PREFIX, env::consts::DLL_SUFFIX));
+    let dir = env::temp_dir().join(format!("numberlink_{}_{}", test, std::process::id()));
+
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy(lib, dir.join(name)).unwrap();
+
+    dir
+}
+
+// serve を空いているポートで立て, 待ち受けを始めるまで待つ. 落とすと止める (stderr は読み残しのまま持っておく)
+#[cfg(feature = "server")]
+struct Server {
+    child: std::process::Child,
+    addr: String,
+    _stderr: std::io::BufReader<std::process::ChildStderr>,
+}
+
+#[cfg(feature = "server")]
+impl Server {
+    fn start(args: &[&str]) -> Server {
+        use std::io::BufRead;
+
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_numberlink_solver"))
+            .args(["serve", "--addr", &addr])
+            .args(args)
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stderr = std::io::BufReader::new(child.stderr.take().unwrap());
+        let mut line = String::new();
+
+        stderr.read_line(&mut line).unwrap();
+        assert_eq!(line, format!("listening on http://{}\n", addr));
+
+        // --grpc ならそちらの待ち受けも待つ
+        if let Some(k) = args.iter().position(|&arg| arg == "--grpc") {
+            line.clear();
+            stderr.read_line(&mut line).unwrap();
+            assert_eq!(line, format!("listening on grpc://{}\n", args[k+1]));
+        }
+
+        Server { child, addr, _stderr: stderr }
+    }
+
+    // HTTP/1.1 の要求を 1 つ送り, (status, 小文字にしたヘッダ, 本文) を返す
+    fn request(&self, method: &str, path: &str, headers: &[(&str, &str)], body: &str) -> (u16, Vec<(String, String)>, String) {
+        use std::io::{Read, Write};
+
+        let mut stream = std::net::TcpStream::connect(&self.addr).unwrap();
+        let headers: String = headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, value)).collect();
+
+        write!(stream, "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n{}\r\n{}", method, path, self.addr, body.len(), headers, body).unwrap();
+
+        let mut response = String::new();
+
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let mut lines = head.split("\r\n");
+        let status = lines.next().unwrap().split(' ').nth(1).unwrap().parse().unwrap();
+        let headers = lines.map(|line| {
+            let (name, value) = line.split_once(':').unwrap();
+
+            (name.to_ascii_lowercase(), value.trim().to_string())
+        }).collect();
+
+        (status, headers, body.to_string())
+    }
+
+    // (status, 本文)
+    fn post(&self, path: &str, body: &str) -> (u16, String) {
+        let (status, _, body) = self.request("POST", path, &[], body);
+
+        (status, body)
+    }
+
+    fn get(&self, path: &str) -> (u16, String) {
+        let (status, _, body) = self.request("GET", path, &[], "");
+
+        (status, body)
+    }
+
+    // path で WebSocket を開く (RFC 6455 の例の鍵で, 答えの Sec-WebSocket-Accept も確かめる)
+    fn websocket(&self, path: &str) -> std::net::TcpStream {
+        use std::io::{Read, Write};
+
+        let mut stream = std::net::TcpStream::connect(&self.addr).unwrap();
+
+        write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n", path, self.addr).unwrap();
+
+        let mut head = vec![];
+
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+
+            stream.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+
+        let head = String::from_utf8(head).unwrap().to_ascii_lowercase();
+
+        assert!(head.starts_with("http/1.1 101"), "{}", head);
+        assert!(head.contains("sec-websocket-accept: s3pplmbitxaq9kygzzhzrbk+xoo="), "{}", head);
+
+        stream
+    }
+}
+
+// WebSocket の 1 つのフレーム (opcode, 中身). 送る方は仮面 (mask) を付ける
+#[cfg(feature = "server")]
+fn ws_send(stream: &mut std::net::TcpStream, text: &str) {
+    use std::io::Write;
+
+    let mask = [0x12, 0x34, 0x56, 0x78];
+    let mut frame = vec![0x81];
+
+    match text.len() {
+        n if n < 126 => frame.push(0x80 | n as u8),
+        n if n < 1 << 16 => {
+            frame.push(0x80 | 126);
+            frame.extend((n as u16).to_be_bytes());
+        },
+        n => {
+            frame.push(0x80 | 127);
+            frame.extend((n as u64).to_be_bytes());
+        },
+    }
+
+    frame.extend(mask);
+    frame.extend(text.bytes().enumerate().map(|(k, b)| b ^ mask[k%4]));
+    stream.write_all(&frame).unwrap();
+}
+
+#[cfg(feature = "server")]
+fn ws_recv(stream: &mut std::net::TcpStream) -> (u8, String) {
+    use std::io::Read;
+
+    let mut head = [0; 2];
+
+    stream.read_exact(&mut head).unwrap();
+
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut len = [0; 2];
+
+            stream.read_exact(&mut len).unwrap();
+            u16::from_be_bytes(len) as usize
+        },
+        127 => {
+            let mut len = [0; 8];
+
+            stream.read_exact(&mut len).unwrap();
+            u64::from_be_bytes(len) as usize
+        },
+        len => len as usize,
+    };
+    let mut body = vec![0; len];
+
+    stream.read_exact(&mut body).unwrap();
+
+    (head[0] & 0x0f, String::from_utf8(body).unwrap())
+}
+
+#[cfg(feature = "server")]
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// 一度に高々 n バイトずつ返す (書き込みの切れ目が数の途中に来ても読めるか見る)
+struct ChunkReader<'a>(&'a [u8], usize);
+
+impl std::io::Read for ChunkReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(self.1).min(self.0.len());
+
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+
+        Ok(n)
+    }
+}
+
+#[test]
+fn known_puzzles() {
+    for url in ["numlin/2/2/1221", "numlin/3/3/1ggggggg1", "numlin/4/1/1gg1", "numlin/3/3/1g2ggg2g1"] {
+        cross_check(&parse_url(url.to_string()).unwrap());
+    }
+}
+
+#[test]
+fn random_puzzles() {
+    let mut rng = Lcg(1);
+    let mut answers = [0; 2];
+
+    for _ in 0..300 {
+        let puzzle = random_puzzle(&mut rng);
+
+        cross_check(&puzzle);
+        answers[solve_backtrack(&puzzle, &SolverConfig::default()).is_ok() as usize] += 1;
+    }
+
+    // 片方の答えしか出ない生成では突き合わせにならない
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn random_interrupts() {
+    let mut rng = Lcg(369);
+
+    for _ in 0..200 {
+        let puzzle = random_puzzle(&mut rng);
+        let interrupt: Interrupt = Shared::new(AtomicBool::new(false));
+        let config = SolverConfig { interrupt: Some(interrupt.clone()), ..SolverConfig::default() };
+        let res = solve_numberlink(&puzzle, &config);
+
+        // 止められるようにしても, 止めなければ答えは同じ
+        assert_eq!(answer(&res), answer(&solve_numberlink(&puzzle, &SolverConfig::default())), "{:?}", puzzle);
+
+        if let Ok(sol) = res {
+            check(&puzzle, &sol);
+        }
+
+        interrupt.store(true, Ordering::SeqCst);
+
+        assert!(matches!(solve_numberlink(&puzzle, &config), Err(Error::Interrupted(_))), "{:?}", puzzle);
+    }
+}
+
+#[test]
+fn random_portfolio() {
+    let mut rng = Lcg(376);
+
+    for _ in 0..100 {
+        let puzzle = random_puzzle(&mut rng);
+        let res = solve_portfolio(&puzzle, &portfolio_configs(&SolverConfig::default(), 4));
+
+        // どの設定が勝っても答えは 1 つの設定で解いたときと同じ
+        assert_eq!(answer(&res), answer(&solve_numberlink(&puzzle, &SolverConfig::default())), "{:?}", puzzle);
+
+        if let Ok(sol) = res {
+            check(&puzzle, &sol);
+        }
+    }
+}
+
+#[test]
+fn random_assumptions() {
+    let mut rng = Lcg(377);
+
+    for _ in 0..100 {
+        let puzzle = random_puzzle(&mut rng);
+        let arcs: Vec<(P, P)> = puzzle.edges().into_iter().flat_map(|(u, v)| [(puzzle.pos(u), puzzle.pos(v)), (puzzle.pos(v), puzzle.pos(u))]).collect();
+        let mut session = Session::new(&puzzle, &SolverConfig::default()).unwrap();
+
+        // 学習節を使い回した問い合わせも, 毎回作り直したソルバと同じ答え
+        for _ in 0..5 {
+            let pins: Vec<((P, P), bool)> = (0..1+rng.next(3)).map(|_| (arcs[rng.next(arcs.len())], rng.next(2) == 0)).collect();
+            let res = session.solve_assuming(&pins);
+
+            assert_eq!(answer(&res), answer(&Session::new(&puzzle, &SolverConfig::default()).unwrap().solve_assuming(&pins)), "{:?} {:?}", puzzle, pins);
+
+            if let Ok(sol) = res {
+                check(&puzzle, &sol);
+                assert!(pins.iter().all(|&(arc, used)| sol.contains(&arc) == used), "{:?} {:?}", pins, sol);
+            }
+        }
+
+        let count = Zdd::build(&puzzle, &SolverConfig::default()).unwrap().count();
+
+        match session.is_unique() {
+            Ok(unique) => assert_eq!(unique, count == 1, "{:?}", puzzle),
+            Err(e) => assert_eq!((e, count), (Error::NoSolution, 0)),
+        }
+    }
+}
+
+#[test]
+fn random_deterministic() {
+    let mut rng = Lcg(391);
+
+    for _ in 0..100 {
+        let puzzle = random_puzzle(&mut rng);
+        let configs = portfolio_configs(&SolverConfig::default(), 4);
+        let first = solve_numberlink(&puzzle, &configs[0]);
+
+        // 期限がなければ最初の設定は必ず確定するので, 何度走らせてもその設定だけで解いた答えになる
+        for _ in 0..3 {
+            assert_eq!(solve_portfolio_ordered(&puzzle, &configs), first, "{:?}", puzzle);
+        }
+    }
+}
+
+#[test]
+fn random_streaming() {
+    let mut rng = Lcg(393);
+    let puzzles: Vec<Puzzle> = (0..200).map(|_| random_puzzle(&mut rng)).collect();
+    let input: String = puzzles.iter().map(|puzzle| format!("{}\n", render_url(puzzle))).collect();
+    let results: Vec<Result<Sol, Error>> = puzzles.iter().map(|puzzle| solve_numberlink(puzzle, &SolverConfig::default())).collect();
+    let expected: String = puzzles.iter().zip(&results).map(|(puzzle, res)| match res {
+        Ok(sol) => format!("{}\n", render_line(puzzle, sol, Format::Json)),
+        Err(e) => format!("{}\n", render_error_line(e, Format::Json)),
+    }).collect();
+
+    assert!(results.iter().any(|res| res == &Err(Error::NoSolution)));
+
+    // 抱える数 (-j の 4 倍) より長い入力を流しても, 1 問ずつ解いたのと同じ行が入力順に出る.
+    // 解けない盤面があるので 1 問だけのときと同じく 2 で終わる
+    for jobs in ["1", "4"] {
+        assert_eq!(cli_input(&["--backend", "varisat", "--format", "json", "-j", jobs, "-"], &input), (2, expected.clone()), "-j {}", jobs);
+    }
+
+    // 終了コードは行ごとの終了コードの最大. 全部解ければ 0
+    let unique = "numlin/5/5/h123h2h4g3i1l4g";
+    let hard = "numlin/12/12/1p3h9g3j4i2j5t5l87g6l6j2g7jbgbjal8g1czg9uahcp4";
+
+    assert_eq!(cli(&[unique, unique]).0, 0);
+    assert_eq!(cli(&[unique, "nonsense"]).0, 1);
+    assert_eq!(cli(&["nonsense", "numlin/2/2/1221", unique]).0, 2);
+    assert_eq!(cli(&["--timeout", "0.001", hard, "numlin/2/2/1221", "nonsense"]).0, 3);
+    assert_eq!(cli_input(&["-"], &format!("{}\n\n{}\n", unique, unique)).0, 0);
+}
+
+#[test]
+fn random_regions() {
+    let known = ["numlin/2/2/1221", "numlin/3/3/1ggggggg1", "numlin/4/1/1gg1", "numlin/3/3/1g2ggg2g1", "numlin/12/12/1p3h9g3j4i2j5t5l87g6l6j2g7jbgbjal8g1czg9uahcp4"];
+    let mut rng = Lcg(48);
+    let mut puzzles: Vec<Puzzle> = known.iter().map(|url| parse_url(url.to_string()).unwrap()).collect();
+
+    puzzles.extend((0..200).map(|_| random_puzzle(&mut rng)));
+
+    // 帯に分けても分けずに解いたときと答えが同じ (大きい盤面で interface の禁止が指数的に増えないことも見る)
+    for puzzle in &puzzles {
+        let expected = solve_numberlink(puzzle, &SolverConfig::default());
+
+        for width in 1..=3 {
+            let res = solve_regions(puzzle, &SolverConfig::default(), width);
+
+            assert_eq!(answer(&res), answer(&expected), "--regions {} disagrees on {:?}", width, puzzle.cells);
+
+            if let Ok(sol) = &res {
+                check(puzzle, sol);
+            }
+        }
+    }
+}
+
+#[test]
+fn random_walls() {
+    let mut rng = Lcg(2);
+
+    for _ in 0..200 {
+        let mut puzzle = random_puzzle(&mut rng);
+
+        for (u, v) in puzzle.edges() {
+            if rng.next(4) == 0 {
+                puzzle.add_wall(puzzle.pos(u), puzzle.pos(v));
+            }
+        }
+
+        cross_check(&puzzle);
+    }
+}
+
+#[test]
+fn random_holes() {
+    let mut rng = Lcg(3);
+
+    for _ in 0..200 {
+        let mut puzzle = random_puzzle(&mut rng);
+
+        for u in 0..puzzle.cells.len() {
+            if puzzle.cells[u] == 0 && rng.next(5) == 0 {
+                puzzle.add_hole(puzzle.pos(u));
+            }
+        }
+
+        cross_check(&puzzle);
+    }
+}
+
+#[test]
+fn random_warps() {
+    let mut rng = Lcg(4);
+
+    for _ in 0..200 {
+        let mut puzzle = random_puzzle(&mut rng);
+        let (width, height) = (puzzle.width, puzzle.height);
+
+        // 左右の端どうしと上下の端どうし (隣り合うときは作られない)
+        for i in 0..height {
+            if rng.next(2) == 0 {
+                puzzle.add_warp((i, width-1), 0, (i, 0));
+            }
+        }
+
+        for j in 0..width {
+            if rng.next(2) == 0 {
+                puzzle.add_warp((height-1, j), 1, (0, j));
+            }
+        }
+
+        cross_check(&puzzle);
+    }
+}
+
+#[test]
+fn random_hex() {
+    let mut rng = Lcg(5);
+    let mut answers = [0; 2];
+
+    for _ in 0..200 {
+        let mut puzzle = random_puzzle(&mut rng);
+
+        puzzle.topology = Topology::Hex;
+        cross_check(&puzzle);
+        answers[solve_backtrack(&puzzle, &SolverConfig::default()).is_ok() as usize] += 1;
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn hex_diagonal() {
+    // 対角の 1 を結んで 2x2 のセルをすべて通るには, 右上と左下が隣り合っていないといけない
+    let puzzle = parse_puzzle(r#"{"field":[[1,0],[0,1]],"topology":"hex"}"#).unwrap();
+
+    assert_eq!(Zdd::build(&puzzle, &SolverConfig::default()).unwrap().count(), 2);
+    assert!(matches!(solve_numberlink(&Puzzle { topology: Topology::Square, ..puzzle }, &SolverConfig::default()), Err(Error::NoSolution)));
+}
+
+#[test]
+fn random_layers() {
+    let mut rng = Lcg(6);
+
+    for _ in 0..200 {
+        let mut puzzle = random_puzzle(&mut rng);
+        let rows = loop {
+            let rows = 1+rng.next(puzzle.height);
+
+            if puzzle.height.is_multiple_of(rows) {
+                break rows;
+            }
+        };
+
+        puzzle.topology = Topology::Layers(rows);
+
+        for u in 0..puzzle.cells.len()-rows*puzzle.width {
+            if rng.next(3) == 0 {
+                puzzle.add_via(puzzle.pos(u));
+            }
+        }
+
+        cross_check(&puzzle);
+    }
+}
+
+#[test]
+fn layer_inputs() {
+    let puzzle = parse_puzzle(r#"{"layers":[[[1,2,0],[0,0,0]],[[0,0,0],[0,2,1]]],"vias":[[0,0,2],[0,1,2]]}"#).unwrap();
+
+    assert_eq!((puzzle.height, puzzle.topology), (4, Topology::Layers(2)));
+    assert_eq!(puzzle.vias.iter().cloned().collect::<Vec<_>>(), vec![2, 5]);
+
+    let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+
+    assert_eq!(render(&puzzle, &sol, Format::Text, Theme::Plain), "1 2 2\n1 1 1\n\n2 2 2\n2 2 1\n");
+}
+
+#[test]
+fn random_boards() {
+    let mut rng = Lcg(43);
+
+    for _ in 0..200 {
+        let mut puzzle = random_puzzle(&mut rng);
+
+        if !puzzle.height.is_multiple_of(2) {
+            continue;
+        }
+
+        // 同じ大きさの 2 枚の盤面を, 端から出る向きのワープでつなぐ
+        let rows = puzzle.height/2;
+
+        puzzle.topology = Topology::Layers(rows);
+
+        for _ in 0..1+rng.next(3) {
+            let p = (rng.next(rows), rng.next(puzzle.width));
+            let q = (rows+rng.next(rows), rng.next(puzzle.width));
+
+            puzzle.add_warp(p, rng.next(4), q);
+        }
+
+        cross_check(&puzzle);
+    }
+}
+
+#[test]
+fn board_inputs() {
+    // 1 は左の盤面の右端から右の盤面の左端へ抜ける
+    let input = r#"{"boards":[[[1,0],[2,0]],[[0,1],[0,2]]],"links":[[[0,0,1],[1,0,0],"right"],[[0,1,1],[1,1,0]]]}"#;
+    let puzzle = parse_puzzle(input).unwrap();
+
+    assert_eq!((puzzle.height, puzzle.topology, puzzle.warps.len()), (4, Topology::Layers(2), 4));
+
+    let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+    let json = render(&puzzle, &sol, Format::Json, Theme::Plain);
+
+    assert_eq!(render(&puzzle, &sol, Format::Text, Theme::Plain), "  1 1 ~\n  2 2 ~\n\n~ 1 1\n~ 2 2\n");
+    assert!(json.contains(r#""boards":[[[1,0],[2,0]],[[0,1],[0,2]]],"links":[[[0,0,1],[1,0,0],"right"],[[0,1,1],[1,1,0],"right"]]"#));
+    assert_eq!(parse_puzzle(&json).map(|other| other.warps), Some(puzzle.warps));
+
+    // 端でないセルからは出られず, 層の間の向きや vias とは一緒に使えない
+    assert!(parse_puzzle(r#"{"boards":[[[1,0],[0,1]],[[0,0],[0,0]]],"links":[[[0,0,0],[1,0,1],"right"]]}"#).is_none());
+    assert!(parse_puzzle(r#"{"boards":[[[1,0],[0,1]],[[0,0],[0,0]]],"links":[[[0,0,0],[1,0,0],"above"]]}"#).is_none());
+    assert!(parse_puzzle(r#"{"boards":[[[1,0],[0,1]],[[0,0],[0,0]]],"vias":[[0,0,0]]}"#).is_none());
+}
+
+#[test]
+fn random_graphs() {
+    let mut rng = Lcg(7);
+    let mut answers = [0; 2];
+
+    for _ in 0..200 {
+        let nodes = 2+rng.next(7);
+        let edges: Vec<(usize, usize)> = (0..nodes).flat_map(|u| (u+1..nodes).map(move |v| (u, v))).filter(|_| rng.next(5) < 2).collect();
+        let mut puzzle = Puzzle::from_graph(nodes, &edges).unwrap();
+
+        for num in 1..=1+rng.next(nodes/2) {
+            for _ in 0..2 {
+                let u = loop {
+                    let u = rng.next(nodes);
+
+                    if puzzle.cells[u] == 0 {
+                        break u;
+                    }
+                };
+
+                puzzle.cells[u] = num;
+            }
+        }
+
+        cross_check(&puzzle);
+        answers[solve_backtrack(&puzzle, &SolverConfig::default()).is_ok() as usize] += 1;
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn graph_inputs() {
+    // 完全グラフ K5 の 2 組 (残りの頂点 4 をどちらかの経路が通る)
+    let puzzle = parse_puzzle(r#"{"nodes":5,"edges":[[0,1],[0,2],[0,3],[0,4],[1,2],[1,3],[1,4],[2,3],[2,4],[3,4]],"pairs":[[0,1],[2,3]]}"#).unwrap();
+
+    assert_eq!(puzzle.edges().len(), 10);
+    assert_eq!(Zdd::build(&puzzle, &SolverConfig::default()).unwrap().count(), 2);
+
+    assert!(Puzzle::from_graph(3, &[(0, 1), (1, 0)]).is_none());
+    assert!(Puzzle::from_graph(3, &[(2, 2)]).is_none());
+}
+
+// 辺の集合だけで接続の条件を確かめる (向きは数字の最初のセルから決まる): 2 回の数字は 1 本道, ネットは全部のセルを
+// 含む 1 本道 (線) か葉が全部ネットのセルの木, 手がかりのない成分は空白の閉路
+fn nets_valid(puzzle: &Puzzle, edges: &[(usize, usize)], nets: Nets) -> bool {
+    let n = puzzle.cells.len();
+    let mut deg = vec![0; n];
+    let mut root: Vec<usize> = (0..n).collect();
+
+    fn find(root: &mut [usize], u: usize) -> usize {
+        if root[u] != u {
+            root[u] = find(root, root[u]);
+        }
+
+        root[u]
+    }
+
+    for &(u, v) in edges {
+        deg[u] += 1;
+        deg[v] += 1;
+
+        let (ru, rv) = (find(&mut root, u), find(&mut root, v));
+
+        root[ru] = rv;
+    }
+
+    let total = |num: usize| puzzle.cells.iter().enumerate().filter(|&(u, &c)| c == num && !puzzle.is_hole(u)).count();
+
+    for r in 0..n {
+        let cells: Vec<usize> = (0..n).filter(|&u| !puzzle.is_hole(u) && find(&mut root, u) == r).collect();
+
+        if cells.is_empty() {
+            continue;
+        }
+
+        let count = edges.iter().filter(|&&(u, _)| find(&mut root, u) == r).count();
+        let mut nums: Vec<usize> = cells.iter().map(|&u| puzzle.cells[u]).filter(|&c| c > 0).collect();
+
+        nums.sort_unstable();
+        nums.dedup();
+
+        let ok = match nums[..] {
+            [] => cells.iter().all(|&u| deg[u] == 2),
+            [num] => {
+                let clues = cells.iter().filter(|&&u| puzzle.cells[u] == num).count();
+                let leaves_ok = cells.iter().all(|&u| deg[u] >= 1 && (deg[u] != 1 || puzzle.cells[u] == num));
+                let path = cells.iter().all(|&u| deg[u] <= 2);
+
+                clues == total(num) && count+1 == cells.len() && leaves_ok && match (clues, nets) {
+                    (2, _) | (_, Nets::Path) => path,
+                    (_, Nets::Tree) => true,
+                }
+            },
+            _ => false,
+        };
+
+        if !ok {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn undirected(puzzle: &Puzzle, sol: &Sol) -> Vec<(usize, usize)> {
+    sol.iter().map(|&(p, q)| (puzzle.index(p), puzzle.index(q))).collect()
+}
+
+#[test]
+fn random_nets() {
+    let mut rng = Lcg(11);
+    let mut answers = [0; 2];
+
+    for _ in 0..120 {
+        let (width, height) = (2+rng.next(2), 2+rng.next(2));
+        let mut puzzle = Puzzle::new(width, height);
+        let mut free: Vec<usize> = (0..width*height).collect();
+
+        if rng.next(3) == 0 {
+            let u = free.swap_remove(rng.next(free.len()));
+
+            puzzle.holes.insert(u);
+        }
+
+        for _ in 0..(3+rng.next(2)).min(free.len()) {
+            let u = free.swap_remove(rng.next(free.len()));
+
+            puzzle.cells[u] = 1;
+        }
+
+        if rng.next(2) == 0 && free.len() >= 2 {
+            for _ in 0..2 {
+                let u = free.swap_remove(rng.next(free.len()));
+
+                puzzle.cells[u] = 2;
+            }
+        }
+
+        let edges = puzzle.edges();
+
+        for nets in [Nets::Path, Nets::Tree] {
+            let expected = (0..1u32 << edges.len()).any(|mask| {
+                let subset: Vec<(usize, usize)> = edges.iter().enumerate().filter(|&(k, _)| mask >> k & 1 == 1).map(|(_, &e)| e).collect();
+
+                nets_valid(&puzzle, &subset, nets)
+            });
+
+            for encoding in [Encoding::Log, Encoding::Direct] {
+                let config = SolverConfig { encoding, rules: RuleSet { nets, ..RuleSet::default() }, ..SolverConfig::default() };
+                let res = solve_numberlink(&puzzle, &config);
+
+                assert_eq!(answer(&res), expected, "{:?} {:?} disagrees on {:?}", nets, encoding, puzzle);
+
+                if let Ok(sol) = &res {
+                    assert!(nets_valid(&puzzle, &undirected(&puzzle, sol), nets), "bad solution {:?} for {:?}", sol, puzzle);
+                    assert_eq!(verify(&puzzle, &config.rules, sol), Ok(()));
+                }
+            }
+
+            answers[expected as usize] += 1;
+        }
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn net_inputs() {
+    // T 字の盤面は枝分かれしないと 3 つのセルを結べない
+    let puzzle = parse_puzzle("1,.,1/#,.,#/#,1,#").unwrap();
+
+    assert!(matches!(solve_numberlink(&puzzle, &SolverConfig::default()), Err(Error::NoSolution)));
+
+    let sol = solve_numberlink(&puzzle, &SolverConfig { rules: RuleSet { nets: Nets::Tree, ..RuleSet::default() }, ..SolverConfig::default() }).unwrap();
+
+    assert_eq!(paths(&puzzle, &sol), vec![(1, vec![(0, 0), (0, 1), (0, 2)]), (1, vec![(0, 0), (0, 1), (1, 1), (2, 1)])]);
+    assert!(matches!(solve_backtrack(&puzzle, &SolverConfig::default()), Err(Error::BackendFailed(_))));
+
+    // グラフでは pairs に 3 つ以上の頂点を並べる (星形は中心で枝分かれする)
+    let star = parse_puzzle(r#"{"nodes":4,"edges":[[0,1],[0,2],[0,3]],"pairs":[[1,2,3]]}"#).unwrap();
+
+    assert!(solve_numberlink(&star, &SolverConfig::default()).is_err());
+    assert_eq!(solve_numberlink(&star, &SolverConfig { rules: RuleSet { nets: Nets::Tree, ..RuleSet::default() }, ..SolverConfig::default() }).unwrap().len(), 3);
+
+    // 1 回しか現れない数字はどちらでも解けない
+    assert!(parse_puzzle("1,.,2/2,.,.").is_some_and(|puzzle| solve_numberlink(&puzzle, &SolverConfig::default()).is_err()));
+}
+
+// 点の組み方を全部試して, どれかが SAT を使わない探索で解けるか
+fn any_matching(dots: &[usize], num: usize, labeled: &mut Puzzle) -> bool {
+    let Some((&u, rest)) = dots.split_first() else {
+        return answer(&solve_backtrack(labeled, &SolverConfig::default()));
+    };
+
+    (0..rest.len()).any(|k| {
+        let others: Vec<usize> = rest.iter().enumerate().filter(|&(l, _)| l != k).map(|(_, &v)| v).collect();
+
+        labeled.cells[u] = num;
+        labeled.cells[rest[k]] = num;
+
+        let res = any_matching(&others, num+1, labeled);
+
+        labeled.cells[rest[k]] = 0;
+        res
+    })
+}
+
+#[test]
+fn random_dots() {
+    let mut rng = Lcg(13);
+    let mut answers = [0; 2];
+
+    for _ in 0..150 {
+        let (width, height) = (2+rng.next(3), 2+rng.next(3));
+        let mut puzzle = Puzzle::new(width, height);
+
+        for _ in 0..2*(1+rng.next(3)).min(width*height/2) {
+            let u = loop {
+                let u = rng.next(width*height);
+
+                if puzzle.cells[u] == 0 {
+                    break u;
+                }
+            };
+
+            puzzle.add_dot(puzzle.pos(u));
+        }
+
+        let dots: Vec<usize> = (0..width*height).filter(|&u| puzzle.cells[u] > 0).collect();
+        let expected = any_matching(&dots, 1, &mut Puzzle { dots: false, cells: vec![0; width*height], ..puzzle.clone() });
+
+        for encoding in [Encoding::Log, Encoding::Direct] {
+            let res = solve_numberlink(&puzzle, &SolverConfig { encoding, ..SolverConfig::default() });
+
+            assert_eq!(answer(&res), expected, "{:?} disagrees on {:?}", encoding, puzzle.cells);
+
+            if let Ok(sol) = &res {
+                check(&puzzle.paired(sol), sol);
+            }
+        }
+
+        answers[expected as usize] += 1;
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn dot_inputs() {
+    let puzzle = parse_puzzle("*,.,*/.,.,./*,.,*/.,.,.").unwrap();
+
+    assert_eq!(puzzle.cells.iter().filter(|&&num| num > 0).count(), 4);
+    assert!(parse_puzzle("*,1/.,1").is_none());
+
+    // 1 本の線を逆向きにたどった解は別解ではない
+    assert!(Session::new(&parse_puzzle("*,*").unwrap(), &SolverConfig::default()).unwrap().is_unique().unwrap());
+
+    // 解では結んだ点に同じ数字を書く
+    let puzzle = parse_puzzle("*,.,*/#,#,#/*,.,*").unwrap();
+    let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+
+    assert_eq!(render_line(&puzzle, &sol, Format::Text), "1,1,1/#,#,#/2,2,2");
+}
+
+// 長さの手がかり: 手がかりのある成分は同じ数字の 2 つを結ぶ, その数字のセル数の 1 本道. 手がかりのない成分は空白の閉路
+fn lengths_valid(puzzle: &Puzzle, edges: &[(usize, usize)]) -> bool {
+    let n = puzzle.cells.len();
+    let mut deg = vec![0; n];
+    let mut seen = vec![false; n];
+
+    for &(u, v) in edges {
+        deg[u] += 1;
+        deg[v] += 1;
+    }
+
+    for s in (0..n).filter(|&u| !puzzle.is_hole(u)) {
+        if seen[s] {
+            continue;
+        }
+
+        let mut cells = vec![s];
+
+        seen[s] = true;
+
+        let mut k = 0;
+
+        while k < cells.len() {
+            let u = cells[k];
+
+            for &(a, b) in edges {
+                let v = if a == u { b } else if b == u { a } else { continue };
+
+                if !seen[v] {
+                    seen[v] = true;
+                    cells.push(v);
+                }
+            }
+
+            k += 1;
+        }
+
+        let clues: Vec<usize> = cells.iter().cloned().filter(|&u| puzzle.cells[u] > 0).collect();
+        let ok = match clues[..] {
+            [] => cells.iter().all(|&u| deg[u] == 2),
+            [a, b] => puzzle.cells[a] == puzzle.cells[b] && cells.len() == puzzle.cells[a] && deg[a] == 1 && deg[b] == 1 && cells.iter().all(|&u| u == a || u == b || deg[u] == 2),
+            _ => false,
+        };
+
+        if !ok {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[test]
+fn random_lengths() {
+    let mut rng = Lcg(17);
+    let mut answers = [0; 2];
+
+    for _ in 0..150 {
+        let (width, height) = [(2, 2), (2, 3), (3, 2), (2, 4), (3, 3)][rng.next(5)];
+        let mut puzzle = Puzzle::new(width, height);
+        let mut free: Vec<usize> = (0..width*height).collect();
+
+        for _ in 0..1+rng.next(2) {
+            let len = 2+rng.next(width*height-1);
+
+            for _ in 0..2 {
+                let u = free.swap_remove(rng.next(free.len()));
+
+                puzzle.cells[u] = len;
+            }
+        }
+
+        let edges = puzzle.edges();
+        let expected = (0..1u32 << edges.len()).any(|mask| {
+            let subset: Vec<(usize, usize)> = edges.iter().enumerate().filter(|&(k, _)| mask >> k & 1 == 1).map(|(_, &e)| e).collect();
+
+            lengths_valid(&puzzle, &subset)
+        });
+
+        for encoding in [Encoding::Log, Encoding::Direct] {
+            let res = solve_numberlink(&puzzle, &SolverConfig { encoding, rules: RuleSet { lengths: true, ..RuleSet::default() }, ..SolverConfig::default() });
+
+            assert_eq!(answer(&res), expected, "{:?} disagrees on {:?}", encoding, puzzle.cells);
+
+            if let Ok(sol) = &res {
+                assert!(lengths_valid(&puzzle, &undirected(&puzzle, sol)), "bad solution {:?} for {:?}", sol, puzzle.cells);
+                assert_eq!(verify(&puzzle, &RuleSet { lengths: true, ..RuleSet::default() }, sol), Ok(()));
+            }
+        }
+
+        answers[expected as usize] += 1;
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+// 組の盤面で, 辺の集合が規則を満たすか (総当たり用)
+fn rules_valid(puzzle: &Puzzle, edges: &[(usize, usize)], rules: &RuleSet) -> bool {
+    let n = puzzle.cells.len();
+    let mut deg = vec![0; n];
+    let mut root: Vec<usize> = (0..n).collect();
+
+    fn find(root: &mut [usize], u: usize) -> usize {
+        if root[u] != u {
+            root[u] = find(root, root[u]);
+        }
+
+        root[u]
+    }
+
+    for &(u, v) in edges {
+        deg[u] += 1;
+        deg[v] += 1;
+
+        let (ru, rv) = (find(&mut root, u), find(&mut root, v));
+
+        root[ru] = rv;
+    }
+
+    let comp: Vec<usize> = (0..n).map(|u| find(&mut root, u)).collect();
+
+    for r in 0..n {
+        let cells: Vec<usize> = (0..n).filter(|&u| !puzzle.is_hole(u) && comp[u] == r).collect();
+        let clues: Vec<usize> = cells.iter().cloned().filter(|&u| puzzle.cells[u] > 0).collect();
+        let ok = match clues[..] {
+            _ if cells.is_empty() => true,
+            [] if cells.len() == 1 => !rules.coverage,
+            [] => rules.allows_loops() && cells.iter().all(|&u| deg[u] == 2),
+            [a, b] => puzzle.cells[a] == puzzle.cells[b] && deg[a] == 1 && deg[b] == 1 && cells.iter().all(|&u| u == a || u == b || deg[u] == 2) && edges.iter().filter(|&&(u, _)| comp[u] == r).count()+1 == cells.len(),
+            _ => false,
+        };
+
+        if !ok {
+            return false;
+        }
+    }
+
+    let same = |u: usize, v: usize| deg[u] > 0 && deg[v] > 0 && comp[u] == comp[v];
+
+    if rules.no_square {
+        for i in 0..puzzle.height-1 {
+            for j in 0..puzzle.width-1 {
+                let block = [(i, j), (i, j+1), (i+1, j), (i+1, j+1)].map(|p| puzzle.index(p));
+
+                if block.iter().all(|&u| same(u, block[0])) {
+                    return false;
+                }
+            }
+        }
+    }
+
+    // no_adjacent は壁のない同じ大きさの盤面の隣も見る
+    let joined = |(u, v): (usize, usize)| !same(u, v) || edges.contains(&(u, v)) || edges.contains(&(v, u));
+
+    (!rules.no_touch || puzzle.edges().into_iter().all(joined)) && (!rules.no_adjacent || Puzzle::new(puzzle.width, puzzle.height).edges().into_iter().all(joined))
+}
+
+#[test]
+fn random_rules() {
+    let mut rng = Lcg(19);
+    let mut answers = [0; 2];
+
+    for _ in 0..50 {
+        let (width, height) = [(2, 2), (2, 3), (3, 2), (3, 3)][rng.next(4)];
+        let mut puzzle = Puzzle::new(width, height);
+        let mut free: Vec<usize> = (0..width*height).collect();
+
+        for num in 1..=1+rng.next((width*height/2).min(3)) {
+            for _ in 0..2 {
+                let u = free.swap_remove(rng.next(free.len()));
+
+                puzzle.cells[u] = num;
+            }
+        }
+
+        let edges = puzzle.edges();
+
+        for bits in 0..16 {
+            let rules = RuleSet { coverage: bits & 1 == 0, loops: bits & 2 == 0, no_square: bits & 4 != 0, no_touch: bits & 8 != 0, ..RuleSet::default() };
+            let expected = (0..1u32 << edges.len()).any(|mask| {
+                let subset: Vec<(usize, usize)> = edges.iter().enumerate().filter(|&(k, _)| mask >> k & 1 == 1).map(|(_, &e)| e).collect();
+
+                rules_valid(&puzzle, &subset, &rules)
+            });
+
+            for encoding in [Encoding::Log, Encoding::Direct] {
+                let res = solve_numberlink(&puzzle, &SolverConfig { encoding, rules, ..SolverConfig::default() });
+
+                assert_eq!(answer(&res), expected, "{:?} {:?} disagrees on {:?}", rules, encoding, puzzle.cells);
+
+                if let Ok(sol) = &res {
+                    assert!(rules_valid(&puzzle, &undirected(&puzzle, sol), &rules), "bad solution {:?} for {:?}", sol, puzzle.cells);
+                    assert_eq!(verify(&puzzle, &rules, sol), Ok(()));
+                }
+            }
+
+            answers[expected as usize] += 1;
+        }
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn random_walled_rules() {
+    let mut rng = Lcg(41);
+    let mut answers = [0; 2];
+
+    for _ in 0..40 {
+        let (width, height) = [(2, 3), (3, 2), (3, 3)][rng.next(3)];
+        let mut puzzle = Puzzle::new(width, height);
+        let mut free: Vec<usize> = (0..width*height).collect();
+
+        for num in 1..=1+rng.next(3) {
+            for _ in 0..2 {
+                let u = free.swap_remove(rng.next(free.len()));
+
+                puzzle.cells[u] = num;
+            }
+        }
+
+        let grid = puzzle.edges();
+
+        for _ in 0..1+rng.next(2) {
+            let (u, v) = grid[rng.next(grid.len())];
+
+            puzzle.add_wall(puzzle.pos(u), puzzle.pos(v));
+        }
+
+        let edges = puzzle.edges();
+
+        for bits in 0..16 {
+            let rules = RuleSet { coverage: bits & 1 == 0, no_square: bits & 2 != 0, no_touch: bits & 4 != 0, no_adjacent: bits & 8 != 0, ..RuleSet::default() };
+            let expected = (0..1u32 << edges.len()).any(|mask| {
+                let subset: Vec<(usize, usize)> = edges.iter().enumerate().filter(|&(k, _)| mask >> k & 1 == 1).map(|(_, &e)| e).collect();
+
+                rules_valid(&puzzle, &subset, &rules)
+            });
+
+            for encoding in [Encoding::Log, Encoding::Direct] {
+                let res = solve_numberlink(&puzzle, &SolverConfig { encoding, rules, ..SolverConfig::default() });
+
+                assert_eq!(answer(&res), expected, "{:?} {:?} disagrees on {:?} {:?}", rules, encoding, puzzle.cells, puzzle.walls);
+
+                if let Ok(sol) = &res {
+                    assert!(rules_valid(&puzzle, &undirected(&puzzle, sol), &rules), "bad solution {:?} for {:?}", sol, puzzle.cells);
+                    assert_eq!(verify(&puzzle, &rules, sol), Ok(()));
+                }
+            }
+
+            answers[expected as usize] += 1;
+        }
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn rule_inputs() {
+    // 向かい合う角のペアは 2x2 の全部を通れないが, 被覆しなければ 1 つ曲がって結べる
+    let puzzle = parse_puzzle("1,./.,1").unwrap();
+    let rules = RuleSet { coverage: false, ..RuleSet::default() };
+
+    assert!(matches!(solve_numberlink(&puzzle, &SolverConfig::default()), Err(Error::NoSolution)));
+    assert_eq!(solve_numberlink(&puzzle, &SolverConfig { rules, ..SolverConfig::default() }).unwrap().len(), 2);
+    assert!(matches!(solve_backtrack(&puzzle, &SolverConfig { rules, ..SolverConfig::default() }), Err(Error::BackendFailed(_))));
+
+    // 検算は規則を破る解を理由つきで断る
+    let sol = vec![((0, 0), (0, 1)), ((0, 1), (1, 1))];
+
+    assert!(verify(&puzzle, &RuleSet::default(), &sol).is_err());
+    assert_eq!(verify(&puzzle, &rules, &sol), Ok(()));
+}
+
+// 2x2 の 2 本の対角線がどちらも使われている
+fn crosses(puzzle: &Puzzle, edges: &[(usize, usize)]) -> bool {
+    let joined = |p: P, q: P| edges.iter().any(|&(u, v)| (u, v) == (puzzle.index(p), puzzle.index(q)) || (v, u) == (puzzle.index(p), puzzle.index(q)));
+
+    (0..puzzle.height-1).any(|i| (0..puzzle.width-1).any(|j| joined((i, j), (i+1, j+1)) && joined((i, j+1), (i+1, j))))
+}
+
+#[test]
+fn random_diagonal() {
+    let mut rng = Lcg(23);
+    let mut answers = [0; 2];
+
+    for _ in 0..60 {
+        let (width, height) = [(2, 2), (2, 3), (3, 2), (2, 4)][rng.next(4)];
+        let mut puzzle = Puzzle::new(width, height);
+        let mut free: Vec<usize> = (0..width*height).collect();
+
+        for num in 1..=1+rng.next((width*height/2).min(3)) {
+            for _ in 0..2 {
+                let u = free.swap_remove(rng.next(free.len()));
+
+                puzzle.cells[u] = num;
+            }
+        }
+
+        puzzle.allow_diagonals();
+
+        let edges = puzzle.edges();
+
+        for crossing in [Crossing::Forbid, Crossing::Allow] {
+            let rules = RuleSet { crossing, ..RuleSet::default() };
+            let valid = |subset: &[(usize, usize)]| rules_valid(&puzzle, subset, &rules) && (crossing == Crossing::Allow || !crosses(&puzzle, subset));
+            let expected = (0..1u32 << edges.len()).any(|mask| {
+                let subset: Vec<(usize, usize)> = edges.iter().enumerate().filter(|&(k, _)| mask >> k & 1 == 1).map(|(_, &e)| e).collect();
+
+                valid(&subset)
+            });
+
+            for encoding in [Encoding::Log, Encoding::Direct] {
+                let res = solve_numberlink(&puzzle, &SolverConfig { encoding, rules, ..SolverConfig::default() });
+
+                assert_eq!(answer(&res), expected, "{:?} {:?} disagrees on {:?}", crossing, encoding, puzzle.cells);
+
+                if let Ok(sol) = &res {
+                    assert!(valid(&undirected(&puzzle, sol)), "bad solution {:?} for {:?}", sol, puzzle.cells);
+                    assert_eq!(verify(&puzzle, &rules, sol), Ok(()));
+                }
+            }
+
+            answers[expected as usize] += 1;
+        }
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn diagonal_inputs() {
+    // 対角に並んだ 2 組は, 斜めに進めても交差しなければ結べない
+    let mut puzzle = parse_puzzle("1,2/2,1").unwrap();
+
+    assert!(solve_numberlink(&puzzle, &SolverConfig::default()).is_err());
+
+    puzzle.allow_diagonals();
+
+    assert!(matches!(solve_numberlink(&puzzle, &SolverConfig::default()), Err(Error::NoSolution)));
+
+    let allow = SolverConfig { rules: RuleSet { crossing: Crossing::Allow, ..RuleSet::default() }, ..SolverConfig::default() };
+
+    assert_eq!(solve_numberlink(&puzzle, &allow).unwrap().len(), 2);
+    assert!(matches!(solve_backtrack(&puzzle, &allow), Err(Error::BackendFailed(_))));
+
+    // JSON の topology でも斜めの盤面になり, 出力にも書く
+    let json = parse_puzzle(r#"{"field":[[1,0],[0,1]],"topology":"diagonal"}"#).unwrap();
+    let sol = solve_numberlink(&json, &SolverConfig::default()).unwrap();
+
+    assert_eq!(verify(&json, &RuleSet::default(), &sol), Ok(()));
+    assert!(sol.iter().any(|&(p, q)| p.0 != q.0 && p.1 != q.1));
+    assert!(render(&json, &sol, Format::Json, Theme::Plain).contains(r#""topology":"diagonal""#));
+}
+
+// 通る点はどれも線が通り, 数字があればその数字の線とつながっている
+fn waypoints_valid(puzzle: &Puzzle, edges: &[(usize, usize)]) -> bool {
+    puzzle.waypoints.iter().all(|(&w, &num)| {
+        let mut reached = vec![w];
+        let mut k = 0;
+
+        while k < reached.len() {
+            for &(a, b) in edges {
+                let v = if a == reached[k] { b } else if b == reached[k] { a } else { continue };
+
+                if !reached.contains(&v) {
+                    reached.push(v);
+                }
+            }
+
+            k += 1;
+        }
+
+        edges.iter().filter(|&&(a, b)| a == w || b == w).count() == 2 && (num == 0 || reached.iter().any(|&u| puzzle.cells[u] == num))
+    })
+}
+
+#[test]
+fn random_waypoints() {
+    let mut rng = Lcg(29);
+    let mut answers = [0; 2];
+
+    for _ in 0..80 {
+        let (width, height) = [(2, 3), (3, 2), (3, 3), (2, 4)][rng.next(4)];
+        let mut puzzle = Puzzle::new(width, height);
+        let mut free: Vec<usize> = (0..width*height).collect();
+        let pairs = 1+rng.next(2);
+
+        for num in 1..=pairs {
+            for _ in 0..2 {
+                let u = free.swap_remove(rng.next(free.len()));
+
+                puzzle.cells[u] = num;
+            }
+        }
+
+        for _ in 0..1+rng.next(2) {
+            let u = free.swap_remove(rng.next(free.len()));
+
+            puzzle.add_waypoint(puzzle.pos(u), rng.next(pairs+1));
+        }
+
+        let edges = puzzle.edges();
+
+        for coverage in [false, true] {
+            let rules = RuleSet { coverage, ..RuleSet::default() };
+            let expected = (0..1u32 << edges.len()).any(|mask| {
+                let subset: Vec<(usize, usize)> = edges.iter().enumerate().filter(|&(k, _)| mask >> k & 1 == 1).map(|(_, &e)| e).collect();
+
+                rules_valid(&puzzle, &subset, &rules) && waypoints_valid(&puzzle, &subset)
+            });
+
+            for encoding in [Encoding::Log, Encoding::Direct] {
+                let res = solve_numberlink(&puzzle, &SolverConfig { encoding, rules, ..SolverConfig::default() });
+
+                assert_eq!(answer(&res), expected, "{:?} {:?} disagrees on {:?} {:?}", coverage, encoding, puzzle.cells, puzzle.waypoints);
+
+                if let Ok(sol) = &res {
+                    let edges = undirected(&puzzle, sol);
+
+                    assert!(rules_valid(&puzzle, &edges, &rules) && waypoints_valid(&puzzle, &edges), "bad solution {:?} for {:?}", sol, puzzle.cells);
+                    assert_eq!(verify(&puzzle, &rules, sol), Ok(()));
+                }
+            }
+
+            answers[expected as usize] += 1;
+        }
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn waypoint_inputs() {
+    // 被覆しなくても @ には線が通る
+    let puzzle = parse_puzzle("1,@/.,1").unwrap();
+    let sol = solve_numberlink(&puzzle, &SolverConfig { rules: RuleSet { coverage: false, ..RuleSet::default() }, ..SolverConfig::default() }).unwrap();
+
+    assert_eq!(sol, vec![((0, 0), (0, 1)), ((0, 1), (1, 1))]);
+
+    // JSON では数字を添えられ (2 はまっすぐ下りずに右の列を回る), 出力にも書く. 置けるのは空白だけ
+    let json = parse_puzzle(r#"{"field":[[1,2,0],[0,0,0],[1,2,0]],"waypoints":[[[1,2],2]]}"#).unwrap();
+    let rules = RuleSet { coverage: false, ..RuleSet::default() };
+    let sol = solve_numberlink(&json, &SolverConfig { rules, ..SolverConfig::default() }).unwrap();
+
+    assert_eq!(verify(&json, &rules, &sol), Ok(()));
+    assert!(sol.contains(&((0, 2), (1, 2))) && !sol.contains(&((0, 1), (1, 1))));
+    assert!(render(&json, &sol, Format::Json, Theme::Plain).contains(r#""waypoints":[[[1,2],2]]"#));
+    assert!(parse_puzzle(r#"{"field":[[1,1]],"waypoints":[[0,0]]}"#).is_some_and(|puzzle| solve_numberlink(&puzzle, &SolverConfig::default()).is_err()));
+    assert!(parse_puzzle(r#"{"field":[[1,0,1]],"waypoints":[[[0,1],2]]}"#).is_some_and(|puzzle| solve_numberlink(&puzzle, &SolverConfig::default()).is_err()));
+}
+
+// どの線も一方通行を逆に進まない (線は先に現れる数字のセルから, 空白の閉路はどちら向きでもよい)
+fn one_way_valid(puzzle: &Puzzle, edges: &[(usize, usize)]) -> bool {
+    let n = puzzle.cells.len();
+    let mut adj = vec![vec![]; n];
+    let mut seen = vec![false; n];
+
+    for &(u, v) in edges {
+        adj[u].push(v);
+        adj[v].push(u);
+    }
+
+    let walk = |s: usize, seen: &mut Vec<bool>| -> Vec<(usize, usize)> {
+        let (mut prev, mut cur) = (s, adj[s][0]);
+        let mut arcs = vec![(s, cur)];
+
+        seen[s] = true;
+
+        while !seen[cur] {
+            seen[cur] = true;
+
+            match adj[cur].iter().find(|&&v| v != prev) {
+                Some(&next) => {
+                    arcs.push((cur, next));
+                    prev = cur;
+                    cur = next;
+                },
+                None => break,
+            }
+        }
+
+        arcs
+    };
+    let ok = |arcs: &[(usize, usize)]| arcs.iter().all(|&(u, v)| !puzzle.one_way.contains(&(v, u)));
+
+    for s in (0..n).filter(|&u| puzzle.cells[u] > 0 && !puzzle.cells[..u].contains(&puzzle.cells[u]) && !adj[u].is_empty()) {
+        if !ok(&walk(s, &mut seen)) {
+            return false;
+        }
+    }
+
+    for u in 0..n {
+        if !seen[u] && !adj[u].is_empty() {
+            let arcs = walk(u, &mut seen);
+            let back: Vec<(usize, usize)> = arcs.iter().map(|&(a, b)| (b, a)).collect();
+
+            if !ok(&arcs) && !ok(&back) {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+#[test]
+fn random_one_way() {
+    let mut rng = Lcg(31);
+    let mut answers = [0; 2];
+
+    for _ in 0..120 {
+        let mut puzzle = random_puzzle(&mut rng);
+
+        if puzzle.cells.len() > 9 {
+            continue;
+        }
+
+        let edges = puzzle.edges();
+
+        for _ in 0..1+rng.next(3) {
+            let (u, v) = edges[rng.next(edges.len())];
+            let (p, q) = if rng.next(2) == 0 { (u, v) } else { (v, u) };
+
+            puzzle.add_one_way(puzzle.pos(p), puzzle.pos(q));
+        }
+
+        let rules = RuleSet::default();
+        let expected = (0..1u32 << edges.len()).any(|mask| {
+            let subset: Vec<(usize, usize)> = edges.iter().enumerate().filter(|&(k, _)| mask >> k & 1 == 1).map(|(_, &e)| e).collect();
+
+            rules_valid(&puzzle, &subset, &rules) && one_way_valid(&puzzle, &subset)
+        });
+
+        for encoding in [Encoding::Log, Encoding::Direct] {
+            let res = solve_numberlink(&puzzle, &SolverConfig { encoding, ..SolverConfig::default() });
+
+            assert_eq!(answer(&res), expected, "{:?} disagrees on {:?} {:?}", encoding, puzzle.cells, puzzle.one_way);
+
+            if let Ok(sol) = &res {
+                assert_eq!(verify(&puzzle, &rules, sol), Ok(()));
+            }
+        }
+
+        answers[expected as usize] += 1;
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn one_way_inputs() {
+    // 1 は左上から右下へ引くので, 右から左への一方通行があると下を回る
+    let puzzle = parse_puzzle(r#"{"field":[[1,0],[0,1]],"one_way":[[[0,1],[0,0]]]}"#).unwrap();
+    let config = SolverConfig { rules: RuleSet { coverage: false, ..RuleSet::default() }, ..SolverConfig::default() };
+    let sol = solve_numberlink(&puzzle, &config).unwrap();
+
+    assert_eq!(sol, vec![((0, 0), (1, 0)), ((1, 0), (1, 1))]);
+    assert!(render(&puzzle, &sol, Format::Json, Theme::Plain).contains(r#""one_way":[[[0,1],[0,0]]]"#));
+    assert!(matches!(solve_backtrack(&puzzle, &SolverConfig::default()), Err(Error::BackendFailed(_))));
+
+    // 隣り合わないセルや, 逆向きと重ねた一方通行は読まない
+    assert!(parse_puzzle(r#"{"field":[[1,0,1]],"one_way":[[[0,0],[0,2]]]}"#).is_none());
+    assert!(parse_puzzle(r#"{"field":[[1,1]],"one_way":[[[0,0],[0,1]],[[0,1],[0,0]]]}"#).is_none());
+}
+
+#[test]
+fn random_limits() {
+    let mut rng = Lcg(37);
+    let mut answers = [0; 2];
+
+    for _ in 0..120 {
+        let mut puzzle = random_puzzle(&mut rng);
+
+        if puzzle.cells.len() > 9 {
+            continue;
+        }
+
+        let pairs = puzzle.cells.iter().cloned().max().unwrap_or(0);
+
+        for _ in 0..1+rng.next(2) {
+            puzzle.limits.insert(1+rng.next(pairs), 2+rng.next(puzzle.cells.len()-1));
+        }
+
+        // 空白の閉路は数えないので, 閉路があってよいときとないときの両方で
+        let edges = puzzle.edges();
+        let length = |subset: &[(usize, usize)], num: usize| {
+            let s = puzzle.cells.iter().position(|&c| c == num).unwrap();
+            let mut cells = vec![s];
+            let mut k = 0;
+
+            while k < cells.len() {
+                for &(a, b) in subset {
+                    let v = if a == cells[k] { b } else if b == cells[k] { a } else { continue };
+
+                    if !cells.contains(&v) {
+                        cells.push(v);
+                    }
+                }
+
+                k += 1;
+            }
+
+            cells.len()
+        };
+
+        for loops in [true, false] {
+            let rules = RuleSet { loops, ..RuleSet::default() };
+            let expected = (0..1u32 << edges.len()).any(|mask| {
+                let subset: Vec<(usize, usize)> = edges.iter().enumerate().filter(|&(k, _)| mask >> k & 1 == 1).map(|(_, &e)| e).collect();
+
+                rules_valid(&puzzle, &subset, &rules) && puzzle.limits.iter().all(|(&num, &limit)| length(&subset, num) <= limit)
+            });
+
+            for encoding in [Encoding::Log, Encoding::Direct] {
+                let res = solve_numberlink(&puzzle, &SolverConfig { encoding, rules, ..SolverConfig::default() });
+
+                assert_eq!(answer(&res), expected, "{:?} disagrees on {:?} {:?}", encoding, puzzle.cells, puzzle.limits);
+
+                if let Ok(sol) = &res {
+                    assert_eq!(verify(&puzzle, &rules, sol), Ok(()));
+                }
+            }
+
+            answers[expected as usize] += 1;
+        }
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+
+    // 上限は JSON で読み書きし, ない数字には置けない
+    let puzzle = parse_puzzle(r#"{"field":[[1,0,0],[0,0,0],[0,0,1]],"limits":[[1,5]]}"#).unwrap();
+    let sol = solve_numberlink(&puzzle, &SolverConfig { rules: RuleSet { coverage: false, ..RuleSet::default() }, ..SolverConfig::default() }).unwrap();
+
+    assert_eq!(sol.len(), 4);
+    assert!(render(&puzzle, &sol, Format::Json, Theme::Plain).contains(r#""limits":[[1,5]]"#));
+    assert!(parse_puzzle(r#"{"field":[[1,1]],"limits":[[2,3]]}"#).is_some_and(|puzzle| solve_numberlink(&puzzle, &SolverConfig::default()).is_err()));
+}
+
+#[test]
+fn random_constraints() {
+    let mut rng = Lcg(41);
+    let mut answers = [0; 2];
+
+    for _ in 0..80 {
+        let puzzle = random_puzzle(&mut rng);
+
+        if puzzle.cells.len() > 9 {
+            continue;
+        }
+
+        let edges = puzzle.edges();
+        let mut constraints = vec![];
+
+        for _ in 0..1+rng.next(2) {
+            let used = rng.next(2) == 0;
+
+            constraints.push(match rng.next(2) {
+                0 => Constraint::Cell(puzzle.pos(rng.next(puzzle.cells.len())), used),
+                _ => {
+                    let (u, v) = edges[rng.next(edges.len())];
+
+                    Constraint::Edge(puzzle.pos(u), puzzle.pos(v), used)
+                },
+            });
+        }
+
+        let holds = |subset: &[(usize, usize)], constraint: &Constraint| match *constraint {
+            Constraint::Cell(p, used) => subset.iter().any(|&(a, b)| a == puzzle.index(p) || b == puzzle.index(p)) == used,
+            Constraint::Edge(p, q, used) => subset.iter().any(|&(a, b)| (a, b) == (puzzle.index(p), puzzle.index(q)) || (b, a) == (puzzle.index(p), puzzle.index(q))) == used,
+            Constraint::MaxLen(..) => true,
+        };
+
+        for coverage in [true, false] {
+            let rules = RuleSet { coverage, ..RuleSet::default() };
+            let expected = (0..1u32 << edges.len()).any(|mask| {
+                let subset: Vec<(usize, usize)> = edges.iter().enumerate().filter(|&(k, _)| mask >> k & 1 == 1).map(|(_, &e)| e).collect();
+
+                rules_valid(&puzzle, &subset, &rules) && constraints.iter().all(|constraint| holds(&subset, constraint))
+            });
+
+            for encoding in [Encoding::Log, Encoding::Direct] {
+                let res = solve_numberlink(&puzzle, &SolverConfig { encoding, rules, constraints: constraints.clone(), ..SolverConfig::default() });
+
+                assert_eq!(answer(&res), expected, "{:?} disagrees on {:?} {:?}", encoding, puzzle.cells, constraints);
+
+                if let Ok(sol) = &res {
+                    assert!(constraints.iter().all(|constraint| holds(&undirected(&puzzle, sol), constraint)), "bad solution {:?} for {:?}", sol, constraints);
+                }
+            }
+
+            answers[expected as usize] += 1;
+        }
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+
+    // maxlen は長さの上限と同じ. 盤面にない数字, 盤面の外のセルと離れたセルの辺は読めても解けない
+    let spec = "forbid cell (1,1); require edge (0,0)-(0,1)\n# comment\nmaxlen 1 = 5";
+    let constraints = parse_constraints(spec).unwrap();
+
+    assert_eq!(constraints, vec![Constraint::Cell((1, 1), false), Constraint::Edge((0, 0), (0, 1), true), Constraint::MaxLen(1, 5)]);
+    assert!(parse_constraints("forbid cell 1,1").is_err());
+
+    let puzzle = parse_url("numlin/3/3/1ggggggg1".to_string()).unwrap();
+    let config = |spec: &str| SolverConfig { rules: RuleSet { coverage: false, ..RuleSet::default() }, constraints: parse_constraints(spec).unwrap(), ..SolverConfig::default() };
+
+    assert_eq!(solve_numberlink(&puzzle, &config(spec)).map(|sol| sol.len()), Ok(4));
+    assert_eq!(solve_numberlink(&puzzle, &config("maxlen 1 = 4")), Err(Error::NoSolution));
+
+    for spec in ["maxlen 2 = 5", "forbid cell (3,0)", "require edge (0,0)-(1,1)"] {
+        assert!(matches!(solve_numberlink(&puzzle, &config(spec)), Err(Error::InvalidConstraint(_))), "{}", spec);
+    }
+}
+
+#[test]
+fn random_costs() {
+    let mut rng = Lcg(41);
+    let mut answers = [0; 2];
+
+    for _ in 0..80 {
+        let mut puzzle = random_puzzle(&mut rng);
+
+        if puzzle.cells.len() > 9 {
+            continue;
+        }
+
+        for u in 0..puzzle.cells.len() {
+            puzzle.costs.insert(u, rng.next(3));
+        }
+
+        // 全部のセルを通らなくてよいときだけ通る道で合計が変わる
+        let rules = RuleSet { coverage: false, ..RuleSet::default() };
+        let edges = puzzle.edges();
+        let cost = |subset: &[(usize, usize)]| (0..puzzle.cells.len()).filter(|&u| subset.iter().any(|&(a, b)| a == u || b == u)).map(|u| puzzle.costs[&u]).sum::<usize>();
+        let best = (0..1u32 << edges.len()).filter_map(|mask| {
+            let subset: Vec<(usize, usize)> = edges.iter().enumerate().filter(|&(k, _)| mask >> k & 1 == 1).map(|(_, &e)| e).collect();
+
+            Some(cost(&subset)).filter(|_| rules_valid(&puzzle, &subset, &rules))
+        }).min();
+
+        if let Some(best) = best {
+            let res = minimize_cost(&puzzle, &SolverConfig { rules, ..SolverConfig::default() }).unwrap();
+
+            assert_eq!((res.cost, res.optimal), (best, true), "{:?} {:?}", puzzle.cells, puzzle.costs);
+        }
+
+        puzzle.budget = Some(rng.next(2*puzzle.cells.len()));
+
+        let expected = best.is_some_and(|best| best <= puzzle.budget.unwrap());
+
+        for encoding in [Encoding::Log, Encoding::Direct] {
+            let res = solve_numberlink(&puzzle, &SolverConfig { encoding, rules, ..SolverConfig::default() });
+
+            assert_eq!(answer(&res), expected, "{:?} disagrees on {:?} {:?} {:?}", encoding, puzzle.cells, puzzle.costs, puzzle.budget);
+
+            if let Ok(sol) = &res {
+                assert_eq!(verify(&puzzle, &rules, sol), Ok(()));
+            }
+        }
+
+        answers[expected as usize] += 1;
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+
+    // コストと予算は JSON で読み書きし, 遠回りは予算を超える
+    let puzzle = parse_puzzle(r#"{"field":[[1,0],[0,1]],"costs":[[[0,1],2],[[1,0],3]],"budget":2}"#).unwrap();
+    let rules = RuleSet { coverage: false, ..RuleSet::default() };
+    let sol = solve_numberlink(&puzzle, &SolverConfig { rules, ..SolverConfig::default() }).unwrap();
+
+    assert!(sol.contains(&((0, 1), (1, 1))));
+    assert!(render(&puzzle, &sol, Format::Json, Theme::Plain).contains(r#""costs":[[[0,1],2],[[1,0],3]],"budget":2"#));
+    assert!(solve_numberlink(&Puzzle { budget: Some(1), ..puzzle }, &SolverConfig { rules, ..SolverConfig::default() }).is_err());
+}
+
+#[test]
+fn torus_wrap() {
+    // 1 は右端から左端へ抜けるしかない
+    let mut puzzle = parse_puzzle("1,2,2,1").unwrap();
+
+    assert!(matches!(solve_numberlink(&puzzle, &SolverConfig::default()), Err(Error::NoSolution)));
+
+    puzzle.wrap(Wrap::Rows);
+
+    assert_eq!(puzzle, parse_puzzle(r#"{"field":[[1,2,2,1]],"wrap":"rows"}"#).unwrap());
+    assert_eq!(Zdd::build(&puzzle, &SolverConfig::default()).unwrap().count(), 1);
+
+    let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+
+    assert_eq!(render(&puzzle, &sol, Format::Text, Theme::Plain), "~ 1 2 2 1 ~\n");
+}
+
+#[test]
+fn hole_inputs() {
+    let text = parse_puzzle("1,1,./.,#,./.,.,.").unwrap();
+    let json = parse_puzzle(r#"{"field":[[1,1,0],[0,0,0],[0,0,0]],"holes":[[1,1]]}"#).unwrap();
+
+    assert_eq!(text, json);
+    assert_eq!(text.holes.iter().cloned().collect::<Vec<_>>(), vec![4]);
+    assert_eq!(Zdd::build(&text, &SolverConfig::default()).unwrap().count(), 1);
+}
+
+#[test]
+fn mask_inputs() {
+    let text = parse_puzzle("1,_,1/.,.,.").unwrap();
+    let json = parse_puzzle(r#"{"field":[[1,0,1],[0,0,0]],"mask":[[1,0,1],[1,1,1]]}"#).unwrap();
+
+    assert_eq!(text, json);
+    assert!(text.is_hole(1) && text.is_outside(1));
+
+    let sol = solve_numberlink(&text, &SolverConfig::default()).unwrap();
+
+    assert_eq!(render(&text, &sol, Format::Text, Theme::Plain), "1   1\n1 1 1\n");
+    assert_eq!(render_line(&text, &sol, Format::Text), "1,_,1/1,1,1");
+}
+
+#[test]
+fn bridge_cells() {
+    let puzzle = parse_puzzle("#,2,#/1,+,1/#,2,#").unwrap();
+
+    for encoding in [Encoding::Log, Encoding::Direct] {
+        let sol = solve_numberlink(&puzzle, &SolverConfig { encoding, ..SolverConfig::default() }).unwrap();
+
+        assert_eq!(paths(&puzzle, &sol), vec![(1, vec![(1, 0), (1, 1), (1, 2)]), (2, vec![(0, 1), (1, 1), (2, 1)])]);
+    }
+
+    // 橋がなければ 2 本は交差できない
+    let plain = parse_puzzle("#,2,#/1,.,1/#,2,#").unwrap();
+
+    assert!(matches!(solve_numberlink(&plain, &SolverConfig::default()), Err(Error::NoSolution)));
+}
+
+#[test]
+fn wall_url() {
+    let puzzle = parse_url("numlin/b/3/3/g00g1ggggggg1".to_string()).unwrap();
+
+    assert_eq!(puzzle.walls.iter().cloned().collect::<Vec<_>>(), vec![(0, 1), (5, 8)]);
+    assert_eq!(Zdd::build(&puzzle, &SolverConfig::default()).unwrap().count(), 2);
+}
+
+#[test]
+fn backtrack_rejects_invalid_fields() {
+    let mut puzzle = Puzzle::new(3, 1);
+
+    puzzle.cells[0] = 1;
+
+    assert!(matches!(solve_backtrack(&puzzle, &SolverConfig::default()), Err(Error::InvalidField)));
+}
+
+// 辺の集合がちょうど 1 本の輪か (どの点も次数 0 か 2 で, 使った辺がつながっている)
+fn single_loop(grid: &Grid, used: &[usize]) -> bool {
+    if used.is_empty() {
+        return false;
+    }
+
+    let degree = |p: P| used.iter().filter(|&&e| { let (r, s) = grid.ends(e); r == p || s == p }).count();
+    let mut reached = vec![used[0]];
+    let mut k = 0;
+
+    while k < reached.len() {
+        let (p, q) = grid.ends(reached[k]);
+
+        for &e in used {
+            let (r, s) = grid.ends(e);
+
+            if !reached.contains(&e) && [r, s].iter().any(|&x| x == p || x == q) {
+                reached.push(e);
+            }
+        }
+
+        k += 1;
+    }
+
+    reached.len() == used.len() && (0..grid.rows).all(|i| (0..grid.cols).all(|j| degree((i, j)) == 0 || degree((i, j)) == 2))
+}
+
+// 答えの輪の辺の番号
+fn loop_edges(grid: &Grid, cycle: &[P]) -> Vec<usize> {
+    let mut res: Vec<usize> = (0..cycle.len()).map(|k| grid.edge(cycle[k], cycle[(k+1)%cycle.len()]).unwrap()).collect();
+
+    res.sort_unstable();
+    res
+}
+
+fn slither_valid(puzzle: &Slither, used: &[usize]) -> bool {
+    let grid = puzzle.grid();
+
+    single_loop(&grid, used) && puzzle.clues.iter().enumerate().all(|(u, clue)| {
+        let (i, j) = (u/puzzle.width, u%puzzle.width);
+        let sides = [((i, j), (i, j+1)), ((i+1, j), (i+1, j+1)), ((i, j), (i+1, j)), ((i, j+1), (i+1, j+1))];
+
+        clue.is_none_or(|n| sides.iter().filter(|&&(p, q)| used.contains(&grid.edge(p, q).unwrap())).count() == n)
+    })
+}
+
+#[test]
+fn random_slither() {
+    let mut rng = Lcg(47);
+    let mut answers = [0; 2];
+
+    for _ in 0..100 {
+        let (width, height) = (1+rng.next(3), 1+rng.next(2));
+
+        // 3x2 は辺が多すぎる
+        if width*height > 4 {
+            continue;
+        }
+
+        let mut puzzle = Slither::new(width, height);
+
+        for u in 0..width*height {
+            if rng.next(2) == 0 {
+                puzzle.clues[u] = Some(rng.next(4));
+            }
+        }
+
+        let grid = puzzle.grid();
+        let edges = grid.edge_count();
+        let expected = (0..1u32 << edges).any(|mask| slither_valid(&puzzle, &(0..edges).filter(|&e| mask >> e & 1 == 1).collect::<Vec<usize>>()));
+        let res = solve_slither(&puzzle, &SolverConfig::default());
+
+        assert_eq!(res.is_ok(), expected, "{:?}", puzzle);
+
+        if let Ok(cycle) = &res {
+            assert!(slither_valid(&puzzle, &loop_edges(&grid, cycle)), "{:?} {:?}", puzzle, cycle);
+        }
+
+        answers[expected as usize] += 1;
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn slither_inputs() {
+    // 2 つの 3 を囲む輪は 1 本につながらないといけない
+    let puzzle = parse_slither("3,.,./.,.,./.,.,3").unwrap();
+    let cycle = solve_slither(&puzzle, &SolverConfig::default()).unwrap();
+
+    assert!(slither_valid(&puzzle, &loop_edges(&puzzle.grid(), &cycle)));
+    assert_eq!(render_slither(&puzzle, &cycle, Format::Text).lines().count(), 7);
+
+    // g-z は空白を飛ばし, 5-9 と a-e は数字のあとを飛ばす
+    let puzzle = parse_slither("https://puzz.link/p?slither/4/2/h6b").unwrap();
+
+    assert_eq!(puzzle.clues, vec![None, None, Some(1), None, Some(1), None, None, None]);
+    assert!(parse_slither("slither/2/1/5").is_some_and(|puzzle| puzzle.clues == vec![Some(0), None]));
+    assert!(parse_slither("slither/2/1/000").is_none());
+}
+
+fn masyu_valid(puzzle: &Masyu, used: &[usize]) -> bool {
+    let grid = puzzle.grid();
+    let out = |p: P, d: usize| grid.step(p, d).is_some_and(|q| used.contains(&grid.edge(p, q).unwrap()));
+    let straight = |p: P| (out(p, 0) && out(p, 2)) || (out(p, 1) && out(p, 3));
+
+    single_loop(&grid, used) && puzzle.pearls.iter().enumerate().all(|(u, pearl)| {
+        let p = (u/puzzle.width, u%puzzle.width);
+        let on = (0..4).any(|d| out(p, d));
+
+        match pearl {
+            None => true,
+            Some(Pearl::White) => on && straight(p) && (0..4).filter(|&d| out(p, d)).any(|d| !straight(grid.step(p, d).unwrap())),
+            Some(Pearl::Black) => on && !straight(p) && (0..4).filter(|&d| out(p, d)).all(|d| straight(grid.step(p, d).unwrap())),
+        }
+    })
+}
+
+#[test]
+fn random_masyu() {
+    let mut rng = Lcg(53);
+    let mut answers = [0; 2];
+
+    for _ in 0..150 {
+        let (width, height) = (2+rng.next(3), 2+rng.next(2));
+
+        if width*height > 9 {
+            continue;
+        }
+
+        let mut puzzle = Masyu::new(width, height);
+
+        for _ in 0..1+rng.next(3) {
+            puzzle.pearls[rng.next(width*height)] = Some(if rng.next(2) == 0 { Pearl::White } else { Pearl::Black });
+        }
+
+        let grid = puzzle.grid();
+        let edges = grid.edge_count();
+        let expected = (0..1u32 << edges).any(|mask| masyu_valid(&puzzle, &(0..edges).filter(|&e| mask >> e & 1 == 1).collect::<Vec<usize>>()));
+        let res = solve_masyu(&puzzle, &SolverConfig::default());
+
+        assert_eq!(res.is_ok(), expected, "{:?}", puzzle);
+
+        if let Ok(cycle) = &res {
+            assert!(masyu_valid(&puzzle, &loop_edges(&grid, cycle)), "{:?} {:?}", puzzle, cycle);
+        }
+
+        answers[expected as usize] += 1;
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn masyu_inputs() {
+    let puzzle = parse_masyu("x,.,./.,.,./.,.,.").unwrap();
+    let cycle = solve_masyu(&puzzle, &SolverConfig::default()).unwrap();
+
+    assert_eq!(render_masyu(&puzzle, &cycle, Format::Text), "x-+-+\n|   |\n+ +-+\n| |\n+-+ .\n");
+
+    // 3 セルずつ 27 進の 1 文字 (9, 3, 1 の桁)
+    let puzzle = parse_masyu("https://puzz.link/p?masyu/2/2/50").unwrap();
+
+    assert_eq!(puzzle.pearls, vec![None, Some(Pearl::White), Some(Pearl::Black), None]);
+    assert!(parse_masyu("masyu/2/2/500").is_none());
+}
+
+fn yajilin_valid(puzzle: &Yajilin, used: &[usize]) -> bool {
+    let grid = puzzle.grid();
+    let on = |p: P| grid.neighbours(p).iter().any(|&q| used.contains(&grid.edge(p, q).unwrap()));
+    let clue = |(i, j): P| puzzle.clues[i*puzzle.width+j];
+    let shaded = |p: P| clue(p).is_none() && !on(p);
+    let cells: Vec<P> = (0..puzzle.height).flat_map(|i| (0..puzzle.width).map(move |j| (i, j))).collect();
+
+    single_loop(&grid, used) && cells.iter().all(|&p| {
+        let counted = match clue(p) {
+            Some(Clue { dir: Some(d), count: Some(n) }) => {
+                let mut q = p;
+                let mut count = 0;
+
+                while let Some(next) = grid.step(q, d) {
+                    count += shaded(next) as usize;
+                    q = next;
+                }
+
+                count == n
+            },
+            _ => true,
+        };
+
+        counted && (clue(p).is_none() || !on(p)) && !(shaded(p) && grid.neighbours(p).into_iter().any(shaded))
+    })
+}
+
+#[test]
+fn random_yajilin() {
+    let mut rng = Lcg(59);
+    let mut answers = [0; 2];
+
+    for _ in 0..150 {
+        let (width, height) = (2+rng.next(3), 2+rng.next(2));
+
+        if width*height > 9 {
+            continue;
+        }
+
+        let mut puzzle = Yajilin::new(width, height);
+
+        for _ in 0..rng.next(3) {
+            puzzle.clues[rng.next(width*height)] = Some(Clue { dir: Some(rng.next(4)), count: Some(rng.next(2)) });
+        }
+
+        let grid = puzzle.grid();
+        let edges = grid.edge_count();
+        let expected = (0..1u32 << edges).any(|mask| yajilin_valid(&puzzle, &(0..edges).filter(|&e| mask >> e & 1 == 1).collect::<Vec<usize>>()));
+        let res = solve_yajilin(&puzzle, &SolverConfig::default());
+
+        assert_eq!(res.is_ok(), expected, "{:?}", puzzle);
+
+        if let Ok(cycle) = &res {
+            assert!(yajilin_valid(&puzzle, &loop_edges(&grid, cycle)), "{:?} {:?}", puzzle, cycle);
+        }
+
+        answers[expected as usize] += 1;
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn yajilin_inputs() {
+    // 右に黒マスがないので, 輪は手がかりのまわりを 1 周する
+    let puzzle = parse_yajilin(".,.,./.,0>,./.,.,.").unwrap();
+    let cycle = solve_yajilin(&puzzle, &SolverConfig::default()).unwrap();
+
+    assert_eq!((cycle.len(), puzzle.shaded(&cycle)), (8, vec![]));
+    assert!(render_yajilin(&puzzle, &cycle, Format::Json).ends_with("\"shaded\":[]}\n"));
+    assert_eq!(render_yajilin(&puzzle, &cycle, Format::Text), "+--+--+\n|     |\n+  0> +\n|     |\n+--+--+\n");
+
+    // 4 は右向きの 1 桁, b は 2 つ空き, 0. は向きも数字もない手がかり
+    let puzzle = parse_yajilin("https://puzz.link/p?yajilin/3/2/41b0.").unwrap();
+
+    assert_eq!(puzzle.clues[0], Some(Clue { dir: Some(0), count: Some(1) }));
+    assert_eq!(puzzle.clues[3], Some(Clue { dir: None, count: None }));
+    assert!(puzzle.clues[1..3].iter().chain(&puzzle.clues[4..]).all(Option::is_none));
+    assert!(parse_yajilin("yajilin/2/1/414141").is_none());
+}
+
+// 橋を架けられる組 (右か下の一番近い島) ごとの本数が答えになっているか
+fn hashi_valid(puzzle: &Hashi, bridges: &Bridges) -> bool {
+    let island = |(i, j): P| puzzle.islands[i*puzzle.width+j];
+    let islands: Vec<P> = (0..puzzle.height).flat_map(|i| (0..puzzle.width).map(move |j| (i, j))).filter(|&p| island(p) > 0).collect();
+    let cells = |&(p, q, _): &(P, P, usize)| -> Vec<P> { (p.0..=q.0).flat_map(|i| (p.1..=q.1).map(move |j| (i, j))).filter(|&r| r != p && r != q).collect() };
+    let mut reached = islands.iter().take(1).cloned().collect::<Vec<P>>();
+    let mut n = 0;
+
+    while n < reached.len() {
+        for &(p, q, _) in bridges {
+            for (a, b) in [(p, q), (q, p)] {
+                if a == reached[n] && !reached.contains(&b) {
+                    reached.push(b);
+                }
+            }
+        }
+
+        n += 1;
+    }
+
+    reached.len() == islands.len()
+        && bridges.iter().all(|bridge| (1..=2).contains(&bridge.2) && (bridge.0.0 == bridge.1.0 || bridge.0.1 == bridge.1.1) && cells(bridge).iter().all(|&r| island(r) == 0))
+        && islands.iter().all(|&p| bridges.iter().filter(|&&(q, r, _)| q == p || r == p).map(|&(_, _, count)| count).sum::<usize>() == island(p))
+        && bridges.iter().enumerate().all(|(k, a)| bridges[k+1..].iter().all(|b| cells(a).iter().all(|r| !cells(b).contains(r))))
+}
+
+#[test]
+fn random_hashi() {
+    let mut rng = Lcg(61);
+    let mut answers = [0; 2];
+
+    for _ in 0..150 {
+        let (width, height) = (2+rng.next(3), 2+rng.next(3));
+        let mut puzzle = Hashi::new(width, height);
+
+        for _ in 0..2+rng.next(4) {
+            puzzle.islands[rng.next(width*height)] = 1+rng.next(4);
+        }
+
+        // 組は島ごとに右と下の一番近い島
+        let island = |(i, j): P| puzzle.islands[i*width+j] > 0;
+        let mut links = vec![];
+
+        for i in 0..height {
+            for j in (0..width).filter(|&j| island((i, j))) {
+                links.extend((j+1..width).find(|&l| island((i, l))).map(|l| ((i, j), (i, l))));
+                links.extend((i+1..height).find(|&k| island((k, j))).map(|k| ((i, j), (k, j))));
+            }
+        }
+
+        let expected = (0..3usize.pow(links.len() as u32)).any(|mut code| {
+            let mut bridges = vec![];
+
+            for &(p, q) in &links {
+                if code%3 > 0 {
+                    bridges.push((p, q, code%3));
+                }
+
+                code /= 3;
+            }
+
+            hashi_valid(&puzzle, &bridges)
+        });
+        let res = solve_hashi(&puzzle, &SolverConfig::default());
+
+        assert_eq!(res.is_ok(), expected, "{:?}", puzzle);
+
+        if let Ok(bridges) = &res {
+            assert!(hashi_valid(&puzzle, bridges), "{:?} {:?}", puzzle, bridges);
+        }
+
+        answers[expected as usize] += 1;
+    }
+
+    assert!(answers[0] > 0 && answers[1] > 0, "answers {:?}", answers);
+}
+
+#[test]
+fn hashi_inputs() {
+    // 2 つずつに分かれず, 4 つの島が 1 つにつながる
+    let janko = "[title]\nsample\n[problem]\n2 - 2\n- - -\n2 - 2\n[solution]\n";
+    let puzzle = parse_hashi(janko).unwrap();
+    let bridges = solve_hashi(&puzzle, &SolverConfig::default()).unwrap();
+
+    assert_eq!(render_hashi(&puzzle, &bridges, Format::Text), "2-2\n|.|\n2-2\n");
+    assert_eq!(parse_hashi("2,.,2/.,.,./2,.,2"), Some(puzzle));
+    assert!(parse_hashi("1,1").is_some_and(|puzzle| solve_hashi(&puzzle, &SolverConfig::default()).is_ok()));
+    assert!(parse_hashi("1,.,1/.,.,./1,.,1").is_some_and(|puzzle| matches!(solve_hashi(&puzzle, &SolverConfig::default()), Err(Error::NoSolution))));
+
+    // numlin と同じ形式の数字 (g-z は空き)
+    let puzzle = parse_hashi("https://puzz.link/p?hashikake/3/1/1g1").unwrap();
+
+    assert_eq!(puzzle.islands, vec![1, 0, 1]);
+    assert!(render_hashi(&puzzle, &solve_hashi(&puzzle, &SolverConfig::default()).unwrap(), Format::Json).contains(r#""bridges":[[[0,0],[0,2],1]]"#));
+}
+
+#[test]
+fn genre_inputs() {
+    assert_eq!(detect("https://puzz.link/p?slither/2/2/33"), Some(Genre::Slither));
+    assert_eq!(detect("http://pzv.jp/p.html?mashu/3/3/00"), Some(Genre::Masyu));
+    assert_eq!(detect("https://puzz.link/p?numlin/b/2/2/000g1g1"), Some(Genre::Numberlink));
+    assert_eq!(detect("1,.,1/.,.,."), None);
+    assert_eq!("yajilin".parse::<Genre>(), Ok(Genre::Yajilin));
+    assert!("numlin".parse::<Genre>().unwrap_err().contains("expected numberlink"));
+
+    for genre in Genre::ALL {
+        assert_eq!(genre.to_string().parse::<Genre>(), Ok(genre));
+    }
+
+    // 名前から選んだ解き方で, サブコマンドのときと同じ答え
+    let url = "https://puzz.link/p?hashikake/3/1/1g1";
+    let genre = detect(url).unwrap();
+    let puzzle = parse_hashi(url).unwrap();
+
+    assert_eq!(solve_genre(genre, url, &SolverConfig::default(), Format::Text).unwrap(), render_hashi(&puzzle, &solve_hashi(&puzzle, &SolverConfig::default()).unwrap(), Format::Text));
+    assert!(matches!(solve_genre(Genre::Masyu, url, &SolverConfig::default(), Format::Text), Err(Error::InvalidUrl)));
+}
+
+#[test]
+fn random_generate() {
+    for seed in 0..20 {
+        let (width, height) = (2+seed as usize%4, 2+seed as usize/4%3);
+        let pairs = 1+seed as usize%3;
+        let puzzle = generate(width, height, pairs, seed).unwrap();
+
+        // 同じ seed なら同じ盤面で, 数字はちょうど pairs 組, 答えはただ 1 つ
+        assert_eq!(generate(width, height, pairs, seed).as_ref(), Some(&puzzle));
+        assert_eq!((puzzle.width, puzzle.height, puzzle.holes.len()), (width, height, 0));
+        assert!((1..=pairs).all(|num| puzzle.cells.iter().filter(|&&cell| cell == num).count() == 2), "{:?}", puzzle);
+        assert_eq!(puzzle.cells.iter().filter(|&&cell| cell > 0).count(), 2*pairs);
+        assert_eq!(Zdd::build(&puzzle, &SolverConfig::default()).unwrap().count(), 1, "{:?}", puzzle);
+    }
+
+    assert_eq!(generate(3, 3, 5, 0), None);
+    assert_eq!(generate(3, 3, 0, 0), None);
+}
+
+#[test]
+fn generate_outputs() {
+    let config = GenConfig { width: 6, height: 5, pairs: 5, seed: 42, ..GenConfig::default() };
+    let puzzle = generate_with(&config).unwrap();
+
+    assert_eq!(generate(6, 5, 5, 42), Some(puzzle.clone()));
+    assert_eq!(parse_puzzle(write_puzzle(&puzzle, Output::Url).trim()), Some(puzzle.clone()));
+    assert_eq!(parse_puzzle(&write_puzzle(&puzzle, Output::Text)), Some(puzzle.clone()));
+    assert!(write_puzzle(&puzzle, Output::Svg).starts_with("<svg"));
+    assert!("pdf".parse::<Output>().unwrap_err().contains("expected url, text or svg"));
+
+    // 16 以上の数字と 20 個を超える空白
+    let mut puzzle = Puzzle::new(9, 5);
+
+    puzzle.set((0, 0), 17);
+    puzzle.set((3, 2), 17);
+    puzzle.set((4, 7), 100);
+    puzzle.set((4, 8), 100);
+
+    assert_eq!(render_url(&puzzle), "https://puzz.link/p?numlin/9/5/-11zn-11s-64-64");
+    assert_eq!(parse_url(render_url(&puzzle)), Some(puzzle));
+
+    // gen は serve の /generate と同じく MAX_GENERATE_CELLS を超える盤面を作り始める前に断る
+    for size in ["100000x100000", "41x40", "18446744073709551615x2", "0x5"] {
+        assert_eq!(cli(&["gen", "--size", size]), (1, String::new()), "{}", size);
+    }
+
+    assert_eq!(cli(&["gen", "--size", "6x5", "--pairs", "5", "--seed", "42"]), (0, write_puzzle(&generate(6, 5, 5, 42).unwrap(), Output::Url)));
+}
+
+#[test]
+fn generate_difficulty() {
+    assert_eq!("easy".parse::<Target>(), Ok(Target::Tier(Difficulty::Easy)));
+    assert_eq!("150".parse::<Target>(), Ok(Target::Score(150.0)));
+    assert!("-3".parse::<Target>().is_err() && "insane".parse::<Target>().is_err());
+    assert!(Target::Score(100.0).matches(109.0) && !Target::Score(100.0).matches(111.0));
+
+    // 5x5 で trivial になる組の数のうち 4 組に近いもの
+    let config = GenConfig { width: 5, height: 5, pairs: 4, seed: 3, target: Some(Target::Tier(Difficulty::Trivial)), ..GenConfig::default() };
+    let puzzle = generate_with(&config).unwrap();
+
+    assert_eq!(predict(&puzzle).difficulty, Difficulty::Trivial);
+    assert_eq!(Zdd::build(&puzzle, &SolverConfig::default()).unwrap().count(), 1);
+    assert_eq!(generate_with(&config), Some(puzzle));
+
+    let puzzle = generate_with(&GenConfig { target: Some(Target::Score(20.0)), ..config.clone() }).unwrap();
+
+    assert!((18.0..=22.0).contains(&predict(&puzzle).score), "{:?}", predict(&puzzle));
+
+    // 5x5 ではどう置いても hard にならない
+    assert_eq!(generate_with(&GenConfig { target: Some(Target::Tier(Difficulty::Hard)), ..config }), None);
+}
+
+#[test]
+fn generate_symmetry() {
+    for symmetry in [Symmetry::Rotational, Symmetry::Quarter, Symmetry::Horizontal, Symmetry::Vertical] {
+        let map = |(i, j): P| match symmetry {
+            Symmetry::Rotational => (5-i, 5-j),
+            Symmetry::Quarter => (j, 5-i),
+            Symmetry::Horizontal => (i, 5-j),
+            _ => (5-i, j),
+        };
+
+        for seed in 0..3 {
+            let config = GenConfig { width: 6, height: 6, pairs: 4, seed, symmetry, ..GenConfig::default() };
+            let puzzle = generate_with(&config).unwrap();
+            let clue = |p: P| puzzle.get(p) > 0 || puzzle.is_hole(puzzle.index(p));
+
+            // 数字と穴のセルの配置が写しても変わらない (数字は揃わなくてよい)
+            assert!((0..6).all(|i| (0..6).all(|j| clue((i, j)) == clue(map((i, j))))), "{:?} {:?}", symmetry, puzzle);
+            assert_eq!(puzzle.cells.iter().filter(|&&cell| cell > 0).count(), 8);
+            assert_eq!(Zdd::build(&puzzle, &SolverConfig::default()).unwrap().count(), 1, "{:?}", puzzle);
+        }
+    }
+
+    assert_eq!(generate_with(&GenConfig { width: 6, height: 5, symmetry: Symmetry::Quarter, ..GenConfig::default() }), None);
+    assert_eq!("vertical".parse::<Symmetry>(), Ok(Symmetry::Vertical));
+    assert!("diagonal".parse::<Symmetry>().unwrap_err().contains("expected none, rotational"));
+}
+
+#[test]
+fn generate_quality() {
+    let config = SolverConfig::default();
+    let puzzle = parse_puzzle("1,.,1/2,.,./#,#,2").unwrap();
+    let sol = solve_numberlink(&puzzle, &config).unwrap();
+
+    // 2 は 1 回曲がる. 一番近い別の数字までは (0, 0) と (1, 0) が 1, (0, 2) と (2, 2) が 2
+    assert_eq!(quality(&puzzle, &sol), Quality { fill: 7.0/9.0, length: 3.5, bends: 1, spread: 1.5 });
+
+    let min = Quality { bends: 10, spread: 1.5, ..Quality::default() };
+    let puzzle = generate_with(&GenConfig { width: 6, height: 6, pairs: 4, min_quality: min, ..GenConfig::default() }).unwrap();
+    let sol = solve_numberlink(&puzzle, &config).unwrap();
+
+    assert!(quality(&puzzle, &sol).meets(&min), "{:?}", quality(&puzzle, &sol));
+    assert_eq!(generate_with(&GenConfig { width: 4, height: 4, pairs: 2, min_quality: Quality { fill: 1.5, ..min }, ..GenConfig::default() }), None);
+
+    // --min-fill は割合なので 0 から 1 まで. ほかは断って探し始めない
+    for fill in ["2", "1.01", "-0.1", "NaN"] {
+        assert_eq!(cli(&["gen", "--size", "4x4", "--pairs", "2", "--min-fill", fill]), (1, String::new()), "{}", fill);
+    }
+
+    let puzzle = generate_with(&GenConfig { width: 4, height: 4, pairs: 2, min_quality: Quality { fill: 1.0, ..Quality::default() }, ..GenConfig::default() }).unwrap();
+
+    assert_eq!(cli(&["gen", "--size", "4x4", "--pairs", "2", "--min-fill", "1"]), (0, write_puzzle(&puzzle, Output::Url)));
+}
+
+#[test]
+fn generate_reject() {
+    let reject: BTreeSet<Trivial> = [Trivial::Straight, Trivial::Adjacent, Trivial::Deduced].iter().cloned().collect();
+
+    for seed in 0..3 {
+        let puzzle = generate_with(&GenConfig { width: 6, height: 6, pairs: 4, seed, reject: reject.clone(), ..GenConfig::default() }).unwrap();
+        let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+
+        for (_, path) in paths(&puzzle, &sol) {
+            let (start, end) = (path[0], path[path.len()-1]);
+
+            assert!(path.iter().any(|p| p.0 != start.0) && path.iter().any(|p| p.1 != start.1), "{:?}", puzzle);
+            assert!(start.0.abs_diff(end.0)+start.1.abs_diff(end.1) > 1, "{:?}", puzzle);
+        }
+
+        assert!(!deduce(&puzzle).unwrap().solved(), "{:?}", puzzle);
+    }
+
+    assert!("boring".parse::<Trivial>().unwrap_err().contains("expected straight, adjacent or deduced"));
+}
+
+#[test]
+fn generate_daily() {
+    assert_eq!(daily_seed("1970-01-01"), Some(0));
+    assert_eq!(daily_seed("2000-02-29"), daily_seed(" 2000-02-29\n"));
+    assert!(daily_seed("2000-02-29").is_some() && daily_seed("1900-02-29").is_none());
+    assert!(daily_seed("2024-13-01").is_none() && daily_seed("2024-04-31").is_none() && daily_seed("yesterday").is_none());
+
+    // 続く日の seed は --count で続けた seed と重ならない
+    let (day, next) = (daily_seed("2026-12-31").unwrap(), daily_seed("2027-01-01").unwrap());
+
+    assert!(day.abs_diff(next) > 1000);
+
+    let today = today();
+
+    assert_eq!(today.len(), 10);
+    assert!(daily_seed(&today).is_some(), "{}", today);
+
+    // 一番大きい seed から続けると 0 に戻る
+    let url = |seed: u64| write_puzzle(&generate_with(&GenConfig { width: 4, height: 4, pairs: 2, seed, ..GenConfig::default() }).unwrap(), Output::Url);
+
+    assert_eq!(cli(&["gen", "--size", "4x4", "--pairs", "2", "--seed", &u64::MAX.to_string(), "--count", "2"]), (0, url(u64::MAX)+&url(0)));
+}
+
+#[test]
+fn generate_from_paths() {
+    for seed in 0..5 {
+        let puzzle = generate(5, 4, 3, seed).unwrap();
+        let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+        let (width, height, drawn) = parse_paths(&render(&puzzle, &sol, Format::Json, Theme::Plain)).unwrap();
+        let made = from_paths(width, height, &drawn).unwrap();
+
+        // 数字の振り方は違ってよいが, 同じセルどうしが組になる
+        assert_eq!(made.cells.iter().map(|&num| num > 0).collect::<Vec<_>>(), puzzle.cells.iter().map(|&num| num > 0).collect::<Vec<_>>());
+        assert!((0..made.cells.len()).all(|u| (0..made.cells.len()).all(|v| made.cells[u] == 0 || (made.cells[u] == made.cells[v]) == (puzzle.cells[u] == puzzle.cells[v]))));
+        assert_eq!(Zdd::build(&made, &SolverConfig::default()).unwrap().count(), 1);
+    }
+
+    let (width, height, drawn) = parse_paths("a a b\nc . b\nc c b").unwrap();
+
+    assert_eq!((width, height), (3, 3));
+    assert_eq!(drawn, vec![vec![(0, 0), (0, 1)], vec![(0, 2), (1, 2), (2, 2)], vec![(1, 0), (2, 0), (2, 1)]]);
+    assert_eq!(render_url(&from_paths(width, height, &drawn).unwrap()), "https://puzz.link/p?numlin/3/3/1123i32");
+    assert!(from_paths(width, height, &drawn).unwrap().is_hole(4));
+
+    // 分かれ道, 1 セルの道, 重なる道, 離れたセルをつなぐ道
+    assert_eq!(parse_paths("a a a/. a ."), None);
+    assert_eq!(parse_paths("a b/b b"), None);
+    assert_eq!(from_paths(2, 1, &[vec![(0, 0), (0, 1)], vec![(0, 1), (0, 0)]]), None);
+    assert_eq!(from_paths(3, 1, &[vec![(0, 0), (0, 2)]]), None);
+    assert_eq!(from_paths(3, 1, &[vec![(0, 0), (0, 1), (0, 2), (0, 3)]]), None);
+}
+
+#[test]
+fn rate_inputs() {
+    assert_eq!("hard".parse::<Tier>(), Ok(Tier::Hard));
+    assert!("insane".parse::<Tier>().is_err());
+    assert_eq!(Tier::of(0.0), Tier::Easy);
+    assert_eq!(Tier::of(1e9), Tier::Expert);
+
+    // 推論だけで決まる盤面は探索しない
+    let easy = rate(&parse_puzzle("https://puzz.link/p?numlin/5/5/h123h2h4g3i1l4g").unwrap()).unwrap();
+
+    assert_eq!((easy.tier, easy.conflicts, easy.decisions), (Tier::Easy, 0, 0));
+    assert_eq!(easy.score, easy.structure);
+
+    let hard = rate(&parse_puzzle("http://pzv.jp/p.html?numlin/10/10/8t12g8l34j21zt76j45l3g67t5").unwrap()).unwrap();
+
+    assert!(hard.tier > easy.tier && hard.score > easy.score, "{:?}", hard);
+    assert!(hard.decisions > 0);
+    assert_eq!(rate(&Puzzle::new(0, 0)).map(|r| r.tier), Err(Error::InvalidField));
+}
+
+#[test]
+fn grade_inputs() {
+    let grade_of = |url: &str| grade(&parse_puzzle(url).unwrap());
+
+    assert_eq!(grade_of("numlin/4/1/1gg1"), Ok(Grade::Technique(Technique::Forced)));
+    assert_eq!(grade_of("https://puzz.link/p?numlin/5/5/h123h2h4g3i1l4g"), Ok(Grade::Technique(Technique::Labels)));
+    assert_eq!(grade_of("http://pzv.jp/p.html?numlin/10/10/8t12g8l34j21zt76j45l3g67t5"), Ok(Grade::Search));
+    assert_eq!(grade_of("numlin/4/1/1221"), Err(Error::NoSolution));
+    assert!(Grade::Technique(Technique::Parity) < Grade::Search);
+    assert_eq!(Grade::Search.to_string(), "search");
+}
+
+#[test]
+fn random_minimize() {
+    let config = SolverConfig::default();
+    let count = |puzzle: &Puzzle| Zdd::build(puzzle, &config).map_or(0, |zdd| zdd.count());
+    let pairs = |puzzle: &Puzzle| puzzle.cells.iter().filter(|&&cell| cell > 0).count()/2;
+    let mut removed = 0;
+
+    for seed in 0..10 {
+        let puzzle = generate(5, 4, 3, seed).unwrap();
+        let sol = solve_numberlink(&puzzle, &config).unwrap();
+
+        // 一番長い道を 2 組に分けた, 数字の多すぎる下書き
+        let (num, path) = paths(&puzzle, &sol).into_iter().max_by_key(|(_, path)| path.len()).unwrap();
+        let mut draft = puzzle.clone();
+
+        draft.set(path[path.len()/2-1], num);
+        draft.set(path[path.len()/2], 4);
+        draft.set(path[path.len()-1], 4);
+
+        if count(&draft) != 1 {
+            assert_eq!(minimize(&draft, &config), Ok(None));
+            continue;
+        }
+
+        let min = minimize(&draft, &config).unwrap().unwrap();
+
+        assert_eq!(count(&min), 1, "{:?}", min);
+        assert!((1..=pairs(&min)).all(|num| min.cells.iter().filter(|&&cell| cell == num).count() == 2), "{:?}", min);
+
+        // どの組を消してもただ 1 つではなくなる
+        for num in 1..=pairs(&min) {
+            let mut less = min.clone();
+
+            less.cells.iter_mut().filter(|cell| **cell == num).for_each(|cell| *cell = 0);
+
+            assert_ne!(count(&less), 1, "{:?}", less);
+        }
+
+        removed += pairs(&draft)-pairs(&min);
+    }
+
+    assert!(removed > 0);
+
+    // 数字のない端の行は削る
+    let min = minimize(&parse_puzzle("#,#,#/1,.,1").unwrap(), &config).unwrap().unwrap();
+
+    assert_eq!(render_url(&min), "https://puzz.link/p?numlin/3/1/1g1");
+}
+
+#[test]
+fn random_repair() {
+    let config = SolverConfig::default();
+    let count = |puzzle: &Puzzle| Zdd::build(puzzle, &config).map_or(0, |zdd| zdd.count());
+    let mut repaired = 0;
+
+    for seed in 0..10 {
+        let puzzle = generate(4, 4, 3, seed).unwrap();
+        let sol = solve_numberlink(&puzzle, &config).unwrap();
+
+        // 一番長い道の途中に壁を置いて, 解けなくなるか試す
+        let (_, path) = paths(&puzzle, &sol).into_iter().max_by_key(|(_, path)| path.len()).unwrap();
+        let (p, q) = (path[path.len()/2-1], path[path.len()/2]);
+        let mut broken = puzzle.clone();
+
+        broken.add_wall(p, q);
+
+        let edits = suggest_solvable(&broken, &config, usize::MAX).unwrap();
+
+        if count(&broken) > 0 {
+            assert_eq!(edits, None);
+            continue;
+        }
+
+        let edits = edits.unwrap();
+
+        // 試せる手直しを全部試した答えと同じものが, 近い順に並ぶ
+        let mut expected: BTreeSet<Edit> = broken.walls.iter().map(|&(u, v)| Edit::RemoveWall(broken.pos(u), broken.pos(v))).collect();
+
+        for u in (0..broken.cells.len()).filter(|&u| broken.cells[u] > 0) {
+            for v in (0..broken.cells.len()).filter(|&v| broken.cells[v] == 0) {
+                expected.insert(Edit::Move { from: broken.pos(u), to: broken.pos(v) });
+            }
+        }
+
+        expected.retain(|edit| edit.cost() <= 2 && count(&edit.apply(&broken).unwrap()) > 0);
+
+        assert_eq!(edits.iter().cloned().collect::<BTreeSet<_>>(), expected, "{:?}", broken);
+        assert!(edits.windows(2).all(|w| w[0].cost() <= w[1].cost()));
+        assert!(edits.contains(&Edit::RemoveWall(p.min(q), p.max(q))));
+
+        repaired += 1;
+    }
+
+    assert!(repaired > 0);
+
+    // 手直しの数は limit まで
+    let puzzle = parse_puzzle("1,2,./.,.,./2,.,1").unwrap();
+
+    assert_eq!(suggest_solvable(&puzzle, &config, 2).unwrap().unwrap().len(), 2);
+    assert_eq!(suggest_solvable(&parse_puzzle("1,2/2,1").unwrap(), &config, 5), Ok(Some(vec![])));
+}
+
+#[test]
+fn random_repair_unique() {
+    let config = SolverConfig::default();
+    let count = |puzzle: &Puzzle| Zdd::build(puzzle, &config).map_or(0, |zdd| zdd.count());
+    let mut repaired = 0;
+
+    for seed in 0..10 {
+        let puzzle = generate(5, 4, 3, seed).unwrap();
+
+        assert_eq!(suggest_unique(&puzzle, &config, 5), Ok(None));
+
+        // 数字の組を 1 つ消して答えを増やす
+        let mut loose = puzzle.clone();
+
+        loose.cells.iter_mut().filter(|cell| **cell == 1).for_each(|cell| *cell = 0);
+
+        if count(&loose) <= 1 {
+            continue;
+        }
+
+        let edits = suggest_unique(&loose, &config, usize::MAX).unwrap().unwrap();
+
+        for edit in &edits {
+            let next = edit.apply(&loose).unwrap();
+
+            // zdd は通る点を扱えないので SAT で確かめ, 答えの線がそこを通るかも見る
+            if let Edit::AddWaypoint(p, num) = *edit {
+                let mut session = Session::new(&next, &config).unwrap();
+                let sol = session.solve().unwrap();
+
+                assert!(paths(&next, &sol).iter().any(|(n, path)| *n == num && path.contains(&p)), "{:?} {}", loose, edit);
+                assert_eq!(session.find_other(&sol, &[]), Ok(None), "{:?} {}", loose, edit);
+            } else {
+                assert_eq!(count(&next), 1, "{:?} {}", loose, edit);
+            }
+        }
+
+        assert!(edits.windows(2).all(|w| w[0].cost() <= w[1].cost()));
+
+        repaired += !edits.is_empty() as usize;
+    }
+
+    assert!(repaired > 0);
+    assert_eq!(suggest_unique(&parse_puzzle("1,2/2,1").unwrap(), &config, 5), Err(Error::NoSolution));
+}
+
+#[test]
+fn canonical_inputs() {
+    let config = SolverConfig::default();
+    let count = |puzzle: &Puzzle| Zdd::build(puzzle, &config).map_or(0, |zdd| zdd.count());
+    // 右に 90 度回した盤面と左右を裏返した盤面を, 変換を使わずに作る
+    let turned = |puzzle: &Puzzle, mirror: bool| {
+        let map = |(i, j): P| if mirror { (i, puzzle.width-1-j) } else { (j, puzzle.height-1-i) };
+        let (width, height) = if mirror { (puzzle.width, puzzle.height) } else { (puzzle.height, puzzle.width) };
+        let mut res = Puzzle::new(width, height);
+
+        for u in 0..puzzle.cells.len() {
+            let p = puzzle.pos(u);
+
+            res.set(map(p), puzzle.cells[u]);
+
+            if puzzle.is_hole(u) {
+                res.add_hole(map(p));
+            }
+        }
+
+        for &(u, v) in &puzzle.walls {
+            res.add_wall(map(puzzle.pos(u)), map(puzzle.pos(v)));
+        }
+
+        res
+    };
+
+    for seed in 0..5 {
+        let mut puzzle = generate(5, 4, 3, seed).unwrap();
+
+        puzzle.add_wall((0, 0), (0, 1));
+
+        let expected = canonical(&puzzle);
+
+        assert_eq!(count(&expected), count(&puzzle));
+
+        let mut variants = vec![puzzle.clone()];
+
+        for _ in 0..3 {
+            let next = turned(variants.last().unwrap(), false);
+
+            variants.push(next);
+        }
+
+        for k in 0..4 {
+            let next = turned(&variants[k], true);
+
+            variants.push(next);
+        }
+
+        // 数字を逆順に振り直したもの
+        let mut renumbered = puzzle.clone();
+
+        renumbered.cells.iter_mut().filter(|cell| **cell > 0).for_each(|cell| *cell = 4-*cell);
+        variants.push(renumbered);
+
+        let sol = solve_numberlink(&expected, &config).ok();
+
+        for variant in &variants {
+            assert_eq!(canonical(variant), expected, "{:?}", variant);
+            assert_eq!(canonical_hash(variant), canonical_hash(&puzzle));
+
+            // 代表の答えを逆の変換で移すと元の盤面の答えになる
+            let (_, t) = canonical_with(variant);
+
+            assert_eq!(renumber(&transform(variant, t).unwrap()), expected);
+
+            if let Some(sol) = &sol {
+                assert_eq!(verify(variant, &config.rules, &transform_sol(&expected, sol, t.inverse())), Ok(()), "{}", t);
+            }
+        }
+
+        assert_eq!(dedup(&variants), vec![0]);
+
+        // 壁を外すと別の盤面
+        variants.push(generate(5, 4, 3, seed).unwrap());
+
+        assert_eq!(dedup(&variants), vec![0, variants.len()-1]);
+        assert_ne!(canonical_hash(variants.last().unwrap()), canonical_hash(&puzzle));
+    }
+
+    // 鍵は版や環境によらない
+    assert_eq!(canonical_hash(&parse_puzzle("1,.,1").unwrap()).len(), 16);
+    assert_eq!(canonical_hash(&parse_puzzle("1,.,1").unwrap()), canonical_hash(&parse_puzzle("2/./2").unwrap()));
+
+    // 向きを変えられない盤面は数字の振り直しだけ
+    let mut one_way = parse_puzzle("2,.,2/1,.,1").unwrap();
+
+    one_way.add_one_way((0, 0), (0, 1));
+
+    assert_eq!(canonical(&one_way).cells, vec![1, 0, 1, 2, 0, 2]);
+    let mut mirrored = parse_puzzle("2,.,2/1,.,1").unwrap();
+
+    mirrored.add_one_way((0, 2), (0, 1));
+
+    assert_eq!(dedup(&[one_way, mirrored]), vec![0, 1]);
+}
+
+#[test]
+fn transform_inputs() {
+    let config = SolverConfig::default();
+    let count = |puzzle: &Puzzle| Zdd::build(puzzle, &config).map_or(0, |zdd| zdd.count());
+    let mut checked = 0;
+
+    for seed in 0..5 {
+        let mut puzzle = generate(5, 4, 3, seed).unwrap();
+
+        puzzle.add_wall((0, 0), (1, 0));
+
+        let sol = match solve_numberlink(&puzzle, &config) {
+            Ok(sol) => sol,
+            Err(_) => continue,
+        };
+        let expected = count(&puzzle);
+
+        for &t in Transform::ALL.iter() {
+            let next = transform(&puzzle, t).unwrap();
+
+            // 答えの数は変わらず, 写した答えはそのまま答えになる
+            assert_eq!(count(&next), expected, "{}", t);
+            assert_eq!(verify(&next, &config.rules, &transform_sol(&puzzle, &sol, t)), Ok(()), "{}", t);
+            assert_eq!(parse_url(render_url(&next)), Some(next.clone()), "{}", t);
+            assert_eq!(t.to_string().parse(), Ok(t));
+            assert_eq!(transform(&next, t.inverse()), Some(puzzle.clone()), "{}", t);
+        }
+
+        // 4 回回すか, 2 回裏返すと元に戻る
+        let turned = (0..4).fold(puzzle.clone(), |p, _| transform(&p, Transform::Rotate90).unwrap());
+
+        assert_eq!(turned, puzzle);
+        assert_eq!(transform(&transform(&puzzle, Transform::Mirror).unwrap(), Transform::Mirror), Some(puzzle.clone()));
+        assert_eq!(transform(&transform(&puzzle, Transform::Mirror).unwrap(), Transform::Flip), transform(&puzzle, Transform::Rotate180));
+
+        checked += 1;
+    }
+
+    assert!(checked > 0);
+
+    let puzzle = parse_puzzle("2,.,1/.,#,./2,.,1").unwrap();
+    let swapped = renumber_with(&puzzle, &[(1, 2), (2, 1)].iter().cloned().collect()).unwrap();
+
+    assert_eq!(swapped.cells, vec![1, 0, 2, 0, 0, 0, 1, 0, 2]);
+    assert_eq!(renumber(&puzzle), swapped);
+    assert_eq!(renumber_with(&puzzle, &[(1, 2)].iter().cloned().collect()), None);
+    assert_eq!(transform(&puzzle, Transform::Transpose).unwrap().holes, puzzle.holes);
+
+    let mut one_way = puzzle.clone();
+
+    one_way.add_one_way((0, 0), (0, 1));
+
+    assert_eq!(transform(&one_way, Transform::Mirror), None);
+    assert_eq!(transform(&one_way, Transform::Identity), Some(one_way));
+}
+
+#[test]
+fn crop_inputs() {
+    for seed in 0..5 {
+        let puzzle = generate(6, 5, 5, seed).unwrap();
+
+        assert_eq!(crop(&puzzle, 0, 0, 5, 6, false), Some(puzzle.clone()));
+        assert_eq!(crop(&puzzle, 3, 2, 3, 2, false), None);
+
+        let (open, sealed) = (crop(&puzzle, 1, 2, 4, 3, false).unwrap(), crop(&puzzle, 1, 2, 4, 3, true).unwrap());
+        let inside = |(i, j): P| (1..5).contains(&i) && (2..5).contains(&j);
+        let mut cut = 0;
+
+        for u in 0..puzzle.cells.len() {
+            let (p, num) = (puzzle.pos(u), puzzle.cells[u]);
+
+            if num == 0 || !inside(p) {
+                continue;
+            }
+
+            let q = (p.0-1, p.1-2);
+            let whole = (0..puzzle.cells.len()).filter(|&v| puzzle.cells[v] == num).all(|v| inside(puzzle.pos(v)));
+
+            // 両端とも中にある組だけが残り, 外へ出る組の端は穴か空白
+            assert_eq!(open.get(q), if whole { num } else { 0 }, "{:?}", puzzle);
+            assert_eq!(sealed.get(q), open.get(q));
+            assert_eq!(sealed.is_hole(sealed.index(q)), !whole);
+
+            cut += !whole as usize;
+        }
+
+        assert_eq!(sealed.holes.len(), cut);
+        assert!(open.holes.is_empty());
+    }
+
+    let mut warped = parse_puzzle("1,.,1/2,.,2").unwrap();
+
+    warped.wrap(Wrap::Rows);
+
+    assert_eq!(crop(&warped, 0, 0, 1, 3, false), None);
+}
+
+#[test]
+fn tile_inputs() {
+    let config = SolverConfig::default();
+    let count = |puzzle: &Puzzle| Zdd::build(puzzle, &config).map_or(0, |zdd| zdd.count());
+    let puzzles = vec![generate(3, 3, 2, 1).unwrap(), generate(4, 3, 3, 2).unwrap(), generate(3, 2, 2, 5).unwrap(), parse_puzzle("1,.,2/1,.,2").unwrap()];
+    let expected: u128 = puzzles.iter().map(count).product();
+
+    for columns in 1..=4 {
+        let tiled = tile(&puzzles, columns, true).unwrap();
+        let shared = tile(&puzzles, columns, false).unwrap();
+
+        // 壁で仕切れば答えは盤面ごとの答えの組, 仕切らなければそれより多いこともある
+        assert_eq!(count(&tiled), expected, "{}", columns);
+        assert!(count(&shared) >= expected, "{}", columns);
+        assert_eq!(tiled.cells.iter().cloned().max(), Some(9));
+        assert_eq!(parse_json(&render_puzzle_json(&tiled)), Some(tiled.clone()));
+        assert_eq!(parse_json(&render_puzzle_json(&shared)), Some(shared.clone()));
+    }
+
+    // 同じ大きさなら余りの穴はない
+    let same = tile(&[puzzles[0].clone(), puzzles[0].clone()], 2, false).unwrap();
+
+    assert_eq!((same.width, same.height), (6, 3));
+    assert!(same.holes.is_empty());
+    assert_eq!(tile(&[], 2, true), None);
+    assert_eq!(tile(&puzzles, 0, true), None);
+
+    let mut warped = puzzles[0].clone();
+
+    warped.wrap(Wrap::Rows);
+
+    assert_eq!(tile(&[puzzles[0].clone(), warped], 2, true), None);
+}
+
+#[test]
+fn profile_inputs() {
+    let config = SolverConfig::default();
+    let puzzle = parse_puzzle("1,.,1/2,.,2").unwrap();
+    let sol = solve_numberlink(&puzzle, &config).unwrap();
+    let p = profile(&puzzle, Some(&sol));
+
+    assert_eq!((p.width, p.height, p.cells, p.holes, p.walls, p.bridges, p.pairs), (3, 2, 6, 0, 0, 0, 2));
+    assert_eq!((p.min_distance, p.max_distance), (2, 2));
+    assert!((p.density-4.0/6.0).abs() < 1e-9 && (p.mean_distance-2.0).abs() < 1e-9);
+    assert_eq!(p.solution, Some(Quality { fill: 1.0, length: 3.0, bends: 0, spread: 1.0 }));
+    assert_eq!(p.detour, Some(1.0));
+
+    // 穴は cells に入れず, 答えがなければ答えの特徴もない
+    let puzzle = parse_puzzle("1,#,2/.,.,./2,.,1").unwrap();
+    let p = profile(&puzzle, None);
+
+    assert_eq!((p.cells, p.holes, p.pairs, p.min_distance, p.max_distance), (8, 1, 2, 4, 4));
+    assert_eq!((p.solution, p.detour), (None, None));
+    assert!(p.to_string().ends_with("no solution"));
+
+    // 曲がる道は両端の距離より長い
+    let puzzle = generate(6, 6, 4, 3).unwrap();
+    let sol = solve_numberlink(&puzzle, &config).unwrap();
+    let p = profile(&puzzle, Some(&sol));
+
+    assert_eq!(p.pairs, 4);
+    assert_eq!(p.solution, Some(quality(&puzzle, &sol)));
+    assert!(p.detour.unwrap() >= 1.0);
+    assert!(p.min_distance as f64 <= p.mean_distance && p.mean_distance <= p.max_distance as f64);
+}
+
+#[test]
+fn report_inputs() {
+    let config = SolverConfig::default();
+    let puzzles: Vec<(String, Puzzle)> = vec![
+        ("<big>".to_string(), generate(6, 6, 5, 3).unwrap()),
+        ("a|b".to_string(), parse_puzzle("1,2/2,1").unwrap()),
+        ("small".to_string(), generate(3, 3, 2, 1).unwrap()),
+    ];
+    let entries = entries(puzzles, &config);
+    let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+
+    // 易しい順で, 解けないものは最後
+    assert_eq!(names, vec!["small", "<big>", "a|b"]);
+    assert!(entries[0].rating.as_ref().unwrap().score <= entries[1].rating.as_ref().unwrap().score);
+    assert_eq!(entries[2].rating.as_ref().err(), Some(&Error::NoSolution));
+
+    let markdown = render_report(&entries, ReportFormat::Markdown);
+    let lines: Vec<&str> = markdown.lines().collect();
+
+    assert_eq!(lines.len(), 2+3);
+    assert!(lines[2].starts_with("| 1 | small | <a href=\"https://puzz.link/p?numlin/3/3/"));
+    assert!(lines[3].contains("&lt;big&gt;") && lines[4].contains("a\\|b") && lines[4].contains("no solution"));
+    assert!(lines[2..].iter().all(|line| line.contains("src=\"data:image/svg+xml,%3Csvg")));
+
+    let html = render_report(&entries, ReportFormat::Html);
+
+    assert!(html.starts_with("<!DOCTYPE html>") && html.trim_end().ends_with("</html>"));
+    assert_eq!(html.matches("<tr>").count(), 1+3);
+    assert_eq!("html".parse(), Ok(ReportFormat::Html));
+    assert!("pdf".parse::<ReportFormat>().is_err());
+}
+
+#[test]
+fn random_hint() {
+    let config = SolverConfig::default();
+    let undirected = |sol: &Sol| sol.iter().map(|&(p, q)| (p.min(q), p.max(q))).collect::<BTreeSet<_>>();
+    let mut searched = 0;
+
+    for seed in 0..10 {
+        let puzzle = generate(5, 4, 3, seed).unwrap();
+        let lines = undirected(&solve_numberlink(&puzzle, &config).unwrap());
+        let mut partial = PartialSolution::default();
+
+        // ヒントどおりに線と × を足していくと, ただ 1 つの答えになる
+        while let Some(hint) = hint_with(&puzzle, &partial, &config).unwrap() {
+            let (p, q) = hint.arc;
+
+            assert_eq!(lines.contains(&(p.min(q), p.max(q))), hint.line, "{:?} {:?}", puzzle, hint);
+
+            match hint.line {
+                true => partial.lines.push(hint.arc),
+                false => partial.crosses.push(hint.arc),
+            }
+
+            searched += (hint.grade == Grade::Search) as usize;
+        }
+
+        assert_eq!(partial.lines.iter().map(|&(p, q)| (p.min(q), p.max(q))).collect::<BTreeSet<_>>(), lines);
+        assert_eq!(partial.lines.len()+partial.crosses.len(), puzzle.edges().len());
+
+        // 答えにない線を 1 本足すと答えがない
+        let (u, v) = puzzle.edges().into_iter().find(|&(u, v)| !lines.contains(&(puzzle.pos(u), puzzle.pos(v)))).unwrap();
+
+        partial.lines.push((puzzle.pos(u), puzzle.pos(v)));
+        assert_eq!(hint_with(&puzzle, &partial, &config), Err(Error::NoSolution));
+    }
+
+    assert!(searched > 0);
+
+    let puzzle = parse_puzzle("4,.,.,./.,.,.,./.,.,.,4").unwrap();
+    let first = hint(&puzzle, &PartialSolution::default()).unwrap().unwrap();
+
+    assert_eq!(first.grade, Grade::Technique(Technique::Forced));
+}
+
+#[test]
+fn random_explain() {
+    let config = SolverConfig::default();
+
+    for seed in 0..10 {
+        let puzzle = generate(5, 4, 3, seed).unwrap();
+        let lines: BTreeSet<_> = solve_numberlink(&puzzle, &config).unwrap().iter().map(|&(p, q)| (p.min(q), p.max(q))).collect();
+        let steps = explain(&puzzle).unwrap();
+
+        // 手順は答えと合い, 全部の辺をちょうど 1 回ずつ決める
+        assert!(steps.iter().all(|step| lines.contains(&(step.arc.0.min(step.arc.1), step.arc.0.max(step.arc.1))) == step.line), "{:?}", puzzle);
+        assert_eq!(steps.iter().map(|step| step.arc).collect::<BTreeSet<_>>().len(), puzzle.edges().len());
+
+        // 手筋だけで解けるなら探索の手はない
+        let searched = steps.iter().any(|step| step.grade == Grade::Search);
+
+        assert_eq!(searched, grade(&puzzle).unwrap() == Grade::Search, "{:?}", puzzle);
+    }
+
+    assert_eq!(explain(&parse_puzzle("1,2/2,1").unwrap()), Err(Error::NoSolution));
+}
+
+#[test]
+fn random_whatif() {
+    let config = SolverConfig::default();
+    let count = |puzzle: &Puzzle| Zdd::build(puzzle, &config).map_or(0, |zdd| zdd.count());
+    let space_count = |space: Space| match space {
+        Space::Empty => 0,
+        Space::Unique(_) => 1,
+        Space::Several { count, .. } => count,
+    };
+    let mut rng = Lcg(7);
+
+    for seed in 0..10 {
+        let mut puzzle = generate(4, 4, 3, seed).unwrap();
+
+        // 数字の組を 1 つ消して答えを増やす
+        puzzle.cells.iter_mut().filter(|cell| **cell == 1).for_each(|cell| *cell = 0);
+
+        let total = count(&puzzle) as usize;
+        let mut explorer = Explorer::new(&puzzle, &config).unwrap();
+
+        assert_eq!(space_count(explorer.space(usize::MAX).unwrap()), total);
+
+        // 線を引かない仮定は壁と同じ, 引く仮定は残り
+        let edges = puzzle.edges();
+        let (u, v) = edges[rng.next(edges.len())];
+        let (p, q) = (puzzle.pos(u), puzzle.pos(v));
+        let mut walled = puzzle.clone();
+
+        walled.add_wall(p, q);
+
+        let without = count(&walled) as usize;
+
+        explorer.pin(Pin::NoLine(p, q)).unwrap();
+        assert_eq!(space_count(explorer.space(usize::MAX).unwrap()), without);
+        assert!(explorer.unpin(Pin::NoLine(p, q)));
+        explorer.pin(Pin::Line(q, p)).unwrap();
+        assert_eq!(space_count(explorer.space(usize::MAX).unwrap()), total-without);
+
+        // 両方置けば答えはない. 外せば元どおり
+        explorer.pin(Pin::NoLine(p, q)).unwrap();
+        assert_eq!(explorer.solve(), Ok(None));
+        assert_eq!(explorer.space(2), Ok(Space::Empty));
+        explorer.clear();
+        assert!(explorer.pins().is_empty());
+        assert_eq!(space_count(explorer.space(usize::MAX).unwrap()), total);
+
+        // セルの数字の仮定: ほかの数字の線はそこを通らない (空白の閉路は数字 2 と読まれる)
+        let cell = (rng.next(4), rng.next(4));
+
+        if puzzle.get(cell) == 0 {
+            explorer.pin(Pin::Cell(cell, 2)).unwrap();
+
+            if let Some(sol) = explorer.solve().unwrap() {
+                assert!(!paths(&puzzle, &sol).iter().any(|(num, path)| *num != 2 && path.contains(&cell)));
+            }
+
+            explorer.clear();
+        }
+
+        assert_eq!(explorer.pin(Pin::Cell(cell, 9)), Err(Error::InvalidPin));
+        assert_eq!(explorer.pin(Pin::Line((0, 0), (2, 2))), Err(Error::InvalidPin));
+    }
+}
+
+#[test]
+fn random_conflict() {
+    let config = SolverConfig::default();
+    let solvable = |puzzle: &Puzzle, pins: &[Pin]| {
+        let mut explorer = Explorer::new(puzzle, &config).unwrap();
+
+        pins.iter().for_each(|&pin| explorer.pin(pin).unwrap());
+        explorer.solve().unwrap().is_some()
+    };
+    let mut rng = Lcg(11);
+    let mut conflicts = 0;
+
+    for seed in 0..10 {
+        let mut puzzle = generate(5, 4, 3, seed).unwrap();
+
+        // 数字の組を 1 つ消して, 仮定の組み合わせで解けなくなるようにする
+        puzzle.cells.iter_mut().filter(|cell| **cell == 1).for_each(|cell| *cell = 0);
+
+        let sol = match solve_numberlink(&puzzle, &config) {
+            Ok(sol) => sol,
+            Err(_) => continue,
+        };
+        let edges = puzzle.edges();
+        let mut explorer = Explorer::new(&puzzle, &config).unwrap();
+
+        // 答えに合う仮定だけなら解ける
+        for &(p, q) in sol.iter().step_by(3) {
+            explorer.pin(Pin::Line(p, q)).unwrap();
+        }
+
+        assert_eq!(explorer.conflict(), Ok(None));
+
+        for _ in 0..3 {
+            let (u, v) = edges[rng.next(edges.len())];
+            let (p, q) = (puzzle.pos(u), puzzle.pos(v));
+
+            explorer.pin(if rng.next(2) == 0 { Pin::Line(p, q) } else { Pin::NoLine(p, q) }).unwrap();
+        }
+
+        let pins = explorer.pins();
+
+        match explorer.conflict().unwrap() {
+            None => assert!(solvable(&puzzle, &pins)),
+            // 矛盾する組は解けず, どれを外しても解ける
+            Some(core) => {
+                conflicts += 1;
+                assert!(!core.is_empty() && core.iter().all(|pin| pins.contains(pin)));
+                assert!(!solvable(&puzzle, &core), "{:?} {:?}", puzzle, core);
+
+                for k in 0..core.len() {
+                    let mut rest = core.clone();
+
+                    rest.remove(k);
+                    assert!(solvable(&puzzle, &rest), "{:?} {:?}", puzzle, core);
+                }
+            },
+        }
+
+        // 外した仮定は矛盾に数えない
+        explorer.clear();
+        assert_eq!(explorer.conflict(), Ok(None));
+    }
+
+    assert!(conflicts > 0);
+}
+
+#[test]
+fn random_diff() {
+    let config = SolverConfig::default();
+    let mut differed = 0;
+
+    for seed in 0..10 {
+        let mut puzzle = generate(5, 4, 3, seed).unwrap();
+
+        puzzle.cells.iter_mut().filter(|cell| **cell == 1).for_each(|cell| *cell = 0);
+
+        let mut session = match Session::new(&puzzle, &config) {
+            Ok(session) => session,
+            Err(_) => continue,
+        };
+        let first = match session.solve() {
+            Ok(sol) => sol,
+            Err(_) => continue,
+        };
+
+        assert!(diff(&puzzle, &first, &first).is_empty());
+
+        let second = match session.find_other(&first, &[]).unwrap() {
+            Some(second) => second,
+            None => continue,
+        };
+        let d = diff(&puzzle, &first, &second);
+        let back = diff(&puzzle, &second, &first);
+
+        // 片方だけの辺は答えの辺の差で, 両端のセルは違うセルに入る
+        let undirected = |sol: &Sol| sol.iter().map(|&(p, q)| (p.min(q), p.max(q))).collect::<BTreeSet<_>>();
+
+        assert_eq!(d.only_first, undirected(&first).difference(&undirected(&second)).cloned().collect::<Vec<_>>());
+        assert_eq!((d.only_first.clone(), d.only_second.clone(), d.cells.clone()), (back.only_second, back.only_first, back.cells));
+        assert!(d.only_first.iter().chain(&d.only_second).all(|&(p, q)| d.cells.contains(&p) && d.cells.contains(&q)));
+
+        let text = render_diff(&puzzle, &first, &second);
+        let marked: usize = text.lines().skip(1).map(|line| line.rsplit('|').next().unwrap().matches('*').count()).sum();
+
+        assert_eq!(text.lines().count(), puzzle.height+1);
+        assert_eq!(marked, d.cells.len());
+
+        differed += !d.is_empty() as usize;
+    }
+
+    assert!(differed > 0);
+}
+
+#[test]
+fn random_screen() {
+    let config = SolverConfig::default();
+    let count = |puzzle: &Puzzle| Zdd::build(puzzle, &config).map_or(0, |zdd| zdd.count());
+    let mut rng = Lcg(5);
+    let mut flagged = 0;
+
+    for seed in 0..10 {
+        let puzzle = generate(5, 5, 4, seed).unwrap();
+
+        assert!(screen(&puzzle).iter().all(|suspect| suspect.reason != Reason::Clueless), "{:?}", puzzle);
+
+        // 穴の列で仕切り, 右側の数字を消して数字のない領域を作る (穴の列は場所を変える)
+        let mut walled = puzzle.clone();
+        let col = 1+rng.next(3);
+
+        for i in 0..5 {
+            walled.set((i, col), 0);
+            walled.add_hole((i, col));
+
+            for j in col+1..5 {
+                walled.set((i, j), 0);
+            }
+        }
+
+        for p in [&puzzle, &walled] {
+            let suspects = screen(p);
+
+            if suspects.iter().any(|suspect| suspect.reason == Reason::Clueless) {
+                assert_ne!(count(p), 1, "{:?}", p);
+                flagged += 1;
+            }
+
+            for suspect in &suspects {
+                assert!(suspect.cells.windows(2).all(|w| w[0] < w[1]));
+                assert!(suspect.cells.iter().all(|&q| p.get(q) == 0 && !p.is_hole(p.index(q))));
+                assert!(suspect.reason != Reason::Pocket || suspect.cells.len() >= 4);
+            }
+        }
+    }
+
+    assert!(flagged > 0);
+
+    let puzzle = parse_puzzle("1,#,.,./1,#,.,.").unwrap();
+
+    assert_eq!(screen(&puzzle), vec![Suspect { reason: Reason::Clueless, cells: vec![(0, 2), (0, 3), (1, 2), (1, 3)] }]);
+    assert_eq!(count(&puzzle), 2);
+}
+
+#[test]
+fn random_flow() {
+    let config = SolverConfig::default();
+
+    for seed in 0..10 {
+        let puzzle = generate(5, 4, 3, seed).unwrap();
+        let sol = solve_numberlink(&puzzle, &config).unwrap();
+        let line = render_flow(&puzzle, Some(&sol), seed as usize+1).unwrap();
+        let (head, flows) = line.split_once(';').unwrap();
+
+        assert_eq!(head, format!("5:4,0,{},{}", seed+1, paths(&puzzle, &sol).len()));
+
+        // 線は数字の順に, 端の数字から隣のセルをたどって同じ数字の端まで
+        let mut cells = BTreeSet::new();
+
+        for (k, flow) in flows.split(';').enumerate() {
+            let flow: Vec<usize> = flow.split(',').map(|u| u.parse().unwrap()).collect();
+            let (first, last) = (flow[0], flow[flow.len()-1]);
+
+            assert_eq!((puzzle.cells[first], puzzle.cells[last]), (k+1, k+1), "{}", line);
+            assert!(flow.windows(2).all(|w| (w[0]%5).abs_diff(w[1]%5)+(w[0]/5).abs_diff(w[1]/5) == 1), "{}", line);
+            assert!(flow.iter().all(|&u| cells.insert(u)), "{}", line);
+        }
+
+        // 解がなければ両端だけ
+        let ends = render_flow(&puzzle, None, 1).unwrap();
+
+        assert_eq!(ends.split(';').skip(1).map(|flow| flow.split(',').count()).collect::<Vec<_>>(), vec![2; flows.split(';').count()]);
+    }
+
+    assert_eq!(render_flow(&parse_puzzle("numlin/3/3/1ggggggg1").unwrap(), None, 1).unwrap(), "3,0,1,1;0,8");
+    assert!(render_flow(&parse_puzzle("1,.,#/.,.,./.,.,1").unwrap(), None, 1).is_err());
+    assert!(render_flow(&parse_puzzle("1,.,1/.,1,./.,.,.").unwrap(), None, 1).is_err());
+}
+
+#[test]
+fn random_crosscheck() {
+    let config = SolverConfig { rules: RuleSet { loops: false, ..RuleSet::default() }, ..SolverConfig::default() };
+    let own = [Checker::Numberlink(format!("{} --loops false --format json -", env!("CARGO_BIN_EXE_numberlink_solver")))];
+    let mut rng = Lcg(47);
+    let mut answers = [0; 2];
+
+    for _ in 0..20 {
+        let puzzle = random_puzzle(&mut rng);
+        let verdict = crosscheck(&puzzle, &render_url(&puzzle), &config, &own);
+
+        // 同じソルバを外から呼んだものなので, どちらも答えを出して一致する
+        assert!(verdict.agree(), "{}", render_verdict("", &verdict));
+        assert_eq!(verdict.outcomes.len(), 2);
+        assert_eq!(verdict.outcomes[0].answer(), verdict.outcomes[1].answer(), "{}", render_verdict("", &verdict));
+        assert!(verdict.outcomes[1].answer().is_some(), "{}", render_verdict("", &verdict));
+
+        answers[verdict.outcomes[0].answer().unwrap() as usize] += 1;
+    }
+
+    assert!(answers.iter().all(|&count| count > 0), "{:?}", answers);
+
+    // 規則を破る答えと読めない答え
+    let puzzle = parse_puzzle("numlin/3/3/1ggggggg1").unwrap();
+    let wrong = Checker::Numberlink(r#"echo {"width":3,"height":3,"paths":[[[0,0],[0,1],[0,2],[1,2],[2,2]]]}"#.to_string());
+    let verdict = crosscheck(&puzzle, "numlin/3/3/1ggggggg1", &config, &[wrong, Checker::Numberlink("cat".to_string())]);
+
+    assert!(!verdict.agree());
+    assert_eq!(verdict.invalid.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), vec!["echo {\"width\":3,\"height\":3,\"paths\":[[[0,0],[0,1],[0,2],[1,2],[2,2]]]}"]);
+    assert!(matches!(verdict.outcomes[2].result, Err(Error::BackendFailed(_))));
+}
+
+struct Counting;
+
+impl Renderer for Counting {
+    fn render(&self, puzzle: &Puzzle, sol: &Sol) -> String {
+        format!("{}x{} {} arcs\n", puzzle.width, puzzle.height, sol.len())
+    }
+}
+
+#[test]
+fn renderer_plugins() {
+    let puzzle = parse_url("numlin/3/3/1ggggggg1".to_string()).unwrap();
+    let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+
+    assert!(register_renderer("json", Counting).is_err());
+    assert_eq!(register_renderer("counting", Counting), Ok(()));
+    assert!(renderer_names().contains(&"svg") && renderer_names().contains(&"counting"));
+
+    let format: Format = "counting".parse().unwrap();
+
+    assert_eq!(format, Format::Plugin("counting"));
+    assert_eq!(render(&puzzle, &sol, format, Theme::Plain), format!("{}x{} {} arcs\n", puzzle.width, puzzle.height, sol.len()));
+    assert_eq!(render_line(&puzzle, &sol, format), format!("{}x{} {} arcs", puzzle.width, puzzle.height, sol.len()));
+    assert_eq!(render_error_line(&Error::NoSolution, format), "error: no solution");
+    assert!(render(&puzzle, &sol, "svg".parse().unwrap(), Theme::Plain).starts_with("<svg"));
+    assert!("nothing".parse::<Format>().is_err());
+}
+
+// 呼ばれるたびに 1 つ先の盤面を返し, 2 回目は取れなかったことにする
+struct Countdown(usize);
+
+impl PuzzleSource for Countdown {
+    fn next(&mut self) -> Fetch<'_> {
+        Box::pin(async move {
+            self.0 = self.0.checked_sub(1)?;
+
+            Some(match self.0 {
+                1 => Err("unreachable".to_string()),
+                n => Ok(format!("numlin/{}/1/1{}1", n+2, "g".repeat(n))),
+            })
+        })
+    }
+}
+
+#[test]
+fn puzzle_sources() {
+    let path = env::temp_dir().join(format!("numberlink_source_{}.txt", std::process::id()));
+
+    std::fs::write(&path, "numlin/3/3/1ggggggg1\nnumlin/2/1/11\n").unwrap();
+
+    let lines: Vec<_> = blocking(open_source(&format!("file:{}", path.display())).unwrap()).collect();
+
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(lines, [Ok("numlin/3/3/1ggggggg1".to_string()), Ok("numlin/2/1/11".to_string())]);
+
+    register_source("countdown", |n: &str| Ok(Box::new(Countdown(n.parse().map_err(|_| "not a number")?)) as Box<dyn PuzzleSource>));
+    assert!(source_schemes().contains(&"countdown"));
+
+    let lines: Vec<_> = blocking(open_source("countdown:3").unwrap()).collect();
+
+    assert_eq!(lines, [Ok("numlin/4/1/1gg1".to_string()), Err("unreachable".to_string()), Ok("numlin/2/1/11".to_string())]);
+    assert!(lines.iter().flatten().all(|url| solve_numberlink(&parse_url(url.clone()).unwrap(), &SolverConfig::default()).is_ok()));
+    assert!(open_source("countdown:x").is_err());
+    assert!(open_source("nowhere:x").is_err());
+    assert!(open_source("no scheme").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn daemon_requests() {
+    use std::os::unix::net::UnixStream;
+
+    let path = env::temp_dir().join(format!("numberlink_daemon_{}.sock", std::process::id()));
+    let served = path.clone();
+
+    std::thread::spawn(move || serve_socket(&served, SolverConfig::default()));
+
+    let mut stream = (0..100).find_map(|_| UnixStream::connect(&path).ok().or_else(|| {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        None
+    })).unwrap();
+
+    assert_eq!(request(&mut stream, "numlin/4/1/1gg1").unwrap(), r#"{"width":4,"height":1,"field":[[1,0,0,1]],"paths":[{"number":1,"cells":[[0,0],[0,1],[0,2],[0,3]]}]}"#);
+    assert_eq!(request(&mut stream, "numlin/2/2/1221").unwrap(), r#"{"error":"no solution"}"#);
+    assert_eq!(request(&mut stream, "nonsense").unwrap(), r#"{"error":"invalid puzzle url"}"#);
+    assert_eq!(serve_socket(&path, SolverConfig::default()).unwrap_err().kind(), std::io::ErrorKind::AddrInUse);
+
+    let mut frames = vec![];
+
+    write_frame(&mut frames, "abc").unwrap();
+    assert_eq!(frames, [0, 0, 0, 3, b'a', b'b', b'c']);
+    assert_eq!(read_frame(&mut &frames[..]).unwrap(), Some(b"abc".to_vec()));
+    assert_eq!(read_frame(&mut &[][..]).unwrap(), None);
+    assert!(read_frame(&mut &[0xff, 0, 0, 0][..]).is_err());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn daemon_limits() {
+    use std::os::unix::net::UnixStream;
+
+    let path = env::temp_dir().join(format!("numberlink_daemon_limits_{}.sock", std::process::id()));
+    let served = path.clone();
+
+    std::thread::spawn(move || serve_socket(&served, SolverConfig::default()));
+
+    let connect = || (0..100).find_map(|_| UnixStream::connect(&path).ok().or_else(|| {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        None
+    })).unwrap();
+    let mut first = connect();
+
+    // 短い URL でも大きな盤面は作らずに断る
+    assert_eq!(request(&mut first, "numlin/100000/100000/1").unwrap(), r#"{"error":"board too large (100000x100000, at most 1048576 cells)"}"#);
+
+    // 接続は順に受け付けるので, 枠を使い切った後の接続はエラーを 1 つ受け取って閉じられる
+    let mut open: Vec<UnixStream> = (1..MAX_CONNECTIONS).map(|_| connect()).collect();
+    let mut extra = connect();
+
+    assert_eq!(read_frame(&mut extra).unwrap(), Some(format!(r#"{{"error":"too many connections (at most {})"}}"#, MAX_CONNECTIONS).into_bytes()));
+    assert_eq!(read_frame(&mut extra).unwrap(), None);
+    assert!(request(&mut open[0], "numlin/4/1/1gg1").unwrap().starts_with(r#"{"width":4"#));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn daemon_process() {
+    use std::io::{BufRead, Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    // daemon を走らせ, ライブラリの read_frame や write_frame を使わずに生のバイトで話す
+    let path = env::temp_dir().join(format!("numberlink_daemon_process_{}.sock", std::process::id()));
+    let socket = path.to_str().unwrap();
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_numberlink_solver"))
+        .args(["daemon", "--socket", socket, "--backend", "varisat"])
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = std::io::BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+
+    stderr.read_line(&mut line).unwrap();
+    assert_eq!(line, format!("listening on {}\n", socket));
+
+    let frame = |body: &[u8]| [&(body.len() as u32).to_be_bytes()[..], body].concat();
+    let reply = |stream: &mut UnixStream| {
+        let mut len = [0; 4];
+
+        stream.read_exact(&mut len).unwrap();
+
+        let mut body = vec![0; u32::from_be_bytes(len) as usize];
+
+        stream.read_exact(&mut body).unwrap();
+        String::from_utf8(body).unwrap()
+    };
+
+    // 1 つの接続に要求を続けて書いてから読んでも, 順に 1 つずつ答える
+    let mut rng = Lcg(480);
+    let puzzles: Vec<Puzzle> = (0..30).map(|_| random_puzzle(&mut rng)).collect();
+    let mut stream = UnixStream::connect(&path).unwrap();
+
+    stream.write_all(&puzzles.iter().flat_map(|puzzle| frame(render_url(puzzle).as_bytes())).collect::<Vec<u8>>()).unwrap();
+
+    for puzzle in &puzzles {
+        let expected = match solve_numberlink(puzzle, &SolverConfig::default()) {
+            Ok(sol) => render_line(puzzle, &sol, Format::Json),
+            Err(e) => render_error_line(&e, Format::Json),
+        };
+
+        assert_eq!(reply(&mut stream), expected);
+    }
+
+    // 1 バイトずつ届いても, 長さの途中で切れていても読む
+    for byte in frame(b"numlin/4/1/1gg1") {
+        stream.write_all(&[byte]).unwrap();
+        stream.flush().unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    assert_eq!(reply(&mut stream), r#"{"width":4,"height":1,"field":[[1,0,0,1]],"paths":[{"number":1,"cells":[[0,0],[0,1],[0,2],[0,3]]}]}"#);
+
+    // 空の本文と UTF-8 でない本文は読めない盤面
+    stream.write_all(&[frame(b""), frame(&[0xff, 0xfe])].concat()).unwrap();
+    assert_eq!(reply(&mut stream), r#"{"error":"invalid puzzle url"}"#);
+    assert_eq!(reply(&mut stream), r#"{"error":"invalid puzzle url"}"#);
+
+    // 長すぎる長さが来たら答えずに切る
+    stream.write_all(&[0xff, 0xff, 0xff, 0xff]).unwrap();
+    assert_eq!(stream.read(&mut [0; 4]).unwrap(), 0);
+
+    // 待ち受けているソケットにもう 1 つ daemon を立てようとしたり, URL を渡したりすると 1 で終わる
+    assert_eq!(cli(&["daemon", "--socket", socket]).0, 1);
+    assert_eq!(cli(&["daemon", "--socket", socket, "numlin/4/1/1gg1"]).0, 1);
+    assert!(UnixStream::connect(&path).is_ok());
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+fn wasm_solve() {
+    use numberlink_solver::wasm::solve;
+
+    // JS から呼ぶ solve は solve --format json の 1 行 (解けない, 読めないときは {"error":...})
+    let mut rng = Lcg(456);
+
+    for _ in 0..50 {
+        let puzzle = random_puzzle(&mut rng);
+        let expected = match solve_numberlink(&puzzle, &SolverConfig::default()) {
+            Ok(sol) => render_line(&puzzle, &sol, Format::Json),
+            Err(e) => render_error_line(&e, Format::Json),
+        };
+
+        assert_eq!(solve(&render_url(&puzzle)), expected);
+    }
+
+    let unique = "https://puzz.link/p?numlin/5/5/h123h2h4g3i1l4g";
+
+    assert_eq!(format!("{}\n", solve(unique)), cli(&["--format", "json", unique]).1);
+    assert_eq!(solve("nonsense"), r#"{"error":"invalid puzzle url"}"#);
+    assert_eq!(solve("numlin/2/2/1221"), r#"{"error":"no solution"}"#);
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+fn canvas_scene() {
+    use numberlink_solver::render::render_svg;
+    use numberlink_solver::wasm::{scene, Shape};
+
+    // canvas に描く図形は render_svg と同じ位置と色 (svg は translate(1,1) なので 1 px ずらして比べる).
+    // それに穴ごとの灰色のセル, 壁ごとの太線, 格子の細線
+    let attr = |line: &str, name: &str| line.split(&format!(" {}=\"", name)).nth(1).and_then(|rest| rest.split('"').next()).map(str::to_string);
+    let number = |s: &str| s.parse::<f64>().unwrap();
+    let mut rng = Lcg(457);
+
+    for _ in 0..200 {
+        let mut puzzle = random_puzzle(&mut rng);
+
+        for u in 0..puzzle.cells.len() {
+            if puzzle.cells[u] == 0 && rng.next(6) == 0 {
+                puzzle.add_hole(puzzle.pos(u));
+            }
+        }
+
+        for (u, v) in puzzle.edges() {
+            if rng.next(6) == 0 {
+                puzzle.add_wall(puzzle.pos(u), puzzle.pos(v));
+            }
+        }
+
+        let sol = solve_numberlink(&puzzle, &SolverConfig::default()).ok();
+        let svg = render_svg(&puzzle, sol.as_ref().map_or(&vec![], |sol| sol));
+        let (size, shapes) = scene(&puzzle, sol.as_ref());
+        let head = svg.lines().next().unwrap();
+
+        assert_eq!(size, (number(&attr(head, "width").unwrap()), number(&attr(head, "height").unwrap())));
+        assert_eq!(shapes[0], Shape::Fill { rect: (0.0, 0.0, size.0, size.1), color: "white".to_string() });
+
+        let polylines: Vec<Shape> = svg.lines().filter(|line| line.starts_with("<polyline")).map(|line| Shape::Stroke {
+            points: attr(line, "points").unwrap().split(' ').map(|p| {
+                let (x, y) = p.split_once(',').unwrap();
+
+                (number(x)+1.0, number(y)+1.0)
+            }).collect(),
+            width: 6.0,
+            color: attr(line, "stroke").unwrap(),
+        }).collect();
+        let texts: Vec<Shape> = svg.lines().filter(|line| line.starts_with("<text")).map(|line| Shape::Text {
+            at: (number(&attr(line, "x").unwrap()), number(&attr(line, "y").unwrap())),
+            text: line.split('>').nth(1).unwrap().trim_end_matches("</text").to_string(),
+        }).collect();
+        let strokes = |width: f64| shapes.iter().filter(|shape| matches!(shape, Shape::Stroke { width: w, .. } if *w == width)).cloned().collect::<Vec<_>>();
+        let holes: Vec<(f64, f64, f64, f64)> = (0..puzzle.cells.len()).filter(|&u| puzzle.is_hole(u)).map(|u| {
+            let (i, j) = puzzle.pos(u);
+
+            (j as f64*40.0+1.0, i as f64*40.0+1.0, 40.0, 40.0)
+        }).collect();
+        let fills: Vec<(f64, f64, f64, f64)> = shapes.iter().filter_map(|shape| match shape {
+            Shape::Fill { rect, color } if color == "#ccc" => Some(*rect),
+            _ => None,
+        }).collect();
+
+        // 解けなければ線は描かない (render_svg は空の解でも端点だけの線を書く)
+        assert_eq!(strokes(6.0), if sol.is_some() { polylines } else { vec![] }, "{:?}", puzzle.cells);
+        assert_eq!(shapes.iter().filter(|shape| matches!(shape, Shape::Text { .. })).cloned().collect::<Vec<_>>(), texts);
+        assert_eq!(fills, holes);
+        assert_eq!(strokes(0.5).len(), puzzle.width+puzzle.height-2);
+        assert_eq!(strokes(2.0).len(), puzzle.walls.len());
+        assert!(shapes.contains(&Shape::Frame { rect: (1.0, 1.0, size.0-2.0, size.1-2.0), width: 2.0, color: "black".to_string() }));
+
+        // 壁の線は 2 つのセルの間の辺そのもの
+        for (&(u, v), shape) in puzzle.walls.iter().zip(strokes(2.0)) {
+            let ((i, j), (k, l)) = (puzzle.pos(u), puzzle.pos(v));
+            let mid = ((j+l) as f64*20.0+21.0, (i+k) as f64*20.0+21.0);
+
+            if let Shape::Stroke { points, .. } = shape {
+                assert_eq!(((points[0].0+points[1].0)/2.0, (points[0].1+points[1].1)/2.0), mid);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn gui_editor() {
+    use numberlink_solver::gui::{drawn, edit, export, resized, Pen};
+
+    // 数字と穴は置き換え合い, 壁はクリックに近い辺に置いて同じ辺をもう一度で外す. 盤面の端の辺には置かない
+    let mut puzzle = Puzzle::new(3, 2);
+
+    assert!(edit(&mut puzzle, Pen::Number, 7, (0, 1), (0.5, 0.5)));
+    assert_eq!(puzzle.get((0, 1)), 7);
+    assert!(edit(&mut puzzle, Pen::Hole, 1, (0, 1), (0.5, 0.5)));
+    assert_eq!((puzzle.get((0, 1)), puzzle.is_hole(1)), (0, true));
+    assert!(edit(&mut puzzle, Pen::Number, 2, (0, 1), (0.5, 0.5)));
+    assert_eq!((puzzle.get((0, 1)), puzzle.is_hole(1)), (2, false));
+    assert!(edit(&mut puzzle, Pen::Erase, 1, (0, 1), (0.5, 0.5)));
+    assert_eq!(puzzle, Puzzle::new(3, 2));
+    assert!(edit(&mut puzzle, Pen::Wall, 1, (0, 1), (0.5, 0.9)));
+    assert!(edit(&mut puzzle, Pen::Wall, 1, (0, 1), (0.95, 0.5)));
+    assert_eq!(puzzle.walls, vec![(1, 2), (1, 4)].into_iter().collect());
+    assert!(edit(&mut puzzle, Pen::Wall, 1, (1, 1), (0.5, 0.1)));
+    assert_eq!(puzzle.walls, vec![(1, 2)].into_iter().collect());
+    assert!(!edit(&mut puzzle, Pen::Wall, 1, (0, 0), (0.1, 0.5)));
+    assert!(!edit(&mut puzzle, Pen::Wall, 1, (1, 2), (0.5, 0.9)));
+    assert_eq!(puzzle.walls, vec![(1, 2)].into_iter().collect());
+
+    // 大きさを変えても残る位置のものはそのままで, 広げてから戻せば元の盤面. Export は Import で読み戻せる
+    let mut rng = Lcg(471);
+
+    for _ in 0..200 {
+        let mut puzzle = random_puzzle(&mut rng);
+
+        for u in 0..puzzle.cells.len() {
+            if puzzle.cells[u] == 0 && rng.next(6) == 0 {
+                puzzle.add_hole(puzzle.pos(u));
+            }
+        }
+
+        for (u, v) in puzzle.edges() {
+            if rng.next(6) == 0 {
+                puzzle.add_wall(puzzle.pos(u), puzzle.pos(v));
+            }
+        }
+
+        let (width, height) = (1+rng.next(puzzle.width+2), 1+rng.next(puzzle.height+2));
+        let small = resized(&puzzle, width, height);
+
+        assert_eq!((small.width, small.height), (width, height));
+        assert_eq!(resized(&resized(&puzzle, puzzle.width+2, puzzle.height+1), puzzle.width, puzzle.height), puzzle);
+
+        for i in 0..height.min(puzzle.height) {
+            for j in 0..width.min(puzzle.width) {
+                assert_eq!(small.get((i, j)), puzzle.get((i, j)));
+                assert_eq!(small.is_hole(small.index((i, j))), puzzle.is_hole(puzzle.index((i, j))));
+            }
+        }
+
+        let walls = puzzle.walls.iter().map(|&(u, v)| (puzzle.pos(u), puzzle.pos(v))).filter(|&((i, j), (k, l))| i.max(k) < height && j.max(l) < width);
+
+        assert_eq!(small.walls, walls.map(|(p, q)| (small.index(p), small.index(q))).collect());
+
+        let text = export(&puzzle);
+        let back = parse_puzzle(&text).unwrap();
+
+        assert_eq!(text.starts_with('{'), !puzzle.holes.is_empty());
+        assert_eq!((back.width, back.height, &back.cells, &back.holes), (puzzle.width, puzzle.height, &puzzle.cells, &puzzle.holes));
+    }
+
+    // 線は始点のセルの中心から伸び, t = 1 で経路の全部のセルの中心を通る
+    let path = [(0, 0), (0, 1), (1, 1)];
+
+    assert_eq!(drawn(&path, 0.0), vec![(0.5, 0.5)]);
+    assert_eq!(drawn(&path, 0.25), vec![(0.5, 0.5), (1.0, 0.5)]);
+    assert_eq!(drawn(&path, 0.75), vec![(0.5, 0.5), (1.5, 0.5), (1.5, 1.0)]);
+    assert_eq!(drawn(&path, 1.0), vec![(0.5, 0.5), (1.5, 0.5), (1.5, 1.5)]);
+    assert_eq!(drawn(&path, 2.0), drawn(&path, 1.0));
+    assert_eq!(drawn(&[], 0.5), vec![]);
+
+    // gui は盤面を 1 つまで取り, 読めない盤面と窓を開けないときは 1
+    let gui = |args: &[&str]| {
+        let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_numberlink_solver"));
+
+        command.arg("gui").args(args).env_remove("DISPLAY").env_remove("WAYLAND_DISPLAY").env_remove("WAYLAND_SOCKET");
+        run_input(command, "")
+    };
+
+    assert_eq!(gui(&["nonsense"]), (1, String::new()));
+    assert_eq!(gui(&["numlin/2/2/1221", "numlin/2/2/1221"]), (1, String::new()));
+    assert_eq!(gui(&["numlin/2/2/1221"]), (1, String::new()));
+    assert_eq!(gui(&[]), (1, String::new()));
+}
+
+#[cfg(feature = "evcxr")]
+#[test]
+fn evcxr_content() {
+    use numberlink_solver::evcxr::{content, Solution};
+    use numberlink_solver::render::render_svg;
+
+    // evcxr_display の出力は SVG を 1 つだけ挟んだ印の行. 盤面だけなら線はなく, 解なら経路ごとに 1 本
+    let url = "numlin/5/5/h123h2h4g3i1l4g";
+    let puzzle = parse_puzzle(url).unwrap();
+    let solution = Solution::from(numberlink_solver::solve(url.to_string()).unwrap());
+    let board = content(&puzzle, &vec![]);
+    let solved = content(&solution.puzzle, &solution.sol);
+    let lines = |text: &str, prefix: &str| text.lines().filter(|line| line.starts_with(prefix)).count();
+
+    assert_eq!(solution.puzzle, puzzle);
+    assert_eq!(board, format!("EVCXR_BEGIN_CONTENT image/svg+xml\n{}EVCXR_END_CONTENT\n", render_svg(&puzzle, &vec![])));
+    assert_eq!(solved, format!("EVCXR_BEGIN_CONTENT image/svg+xml\n{}EVCXR_END_CONTENT\n", render_svg(&puzzle, &solution.sol)));
+
+    for text in [&board, &solved].iter() {
+        assert_eq!(text.lines().next(), Some("EVCXR_BEGIN_CONTENT image/svg+xml"));
+        assert_eq!(text.lines().last(), Some("EVCXR_END_CONTENT"));
+        assert_eq!((lines(text, "<svg"), lines(text, "</svg>")), (1, 1));
+        assert_eq!(lines(text, "<text"), 8);
+    }
+
+    assert_eq!(lines(&solved, "<polyline"), 4);
+
+    puzzle.evcxr_display();
+    solution.evcxr_display();
+}
+
+#[cfg(feature = "bevy")]
+#[test]
+fn bevy_levels() {
+    use bevy::app::{App, Last, TaskPoolPlugin};
+    use bevy::asset::{AssetId, AssetPlugin, AssetServer, Assets};
+    use bevy::ecs::message::MessageReader;
+    use bevy::ecs::resource::Resource;
+    use bevy::ecs::system::ResMut;
+    use numberlink_solver::bevy_plugin::{HintLevel, Level, LevelHinted, LevelSolved, NumberlinkPlugin, SolveLevel};
+    use numberlink_solver::render::paths;
+
+    #[derive(Resource, Default)]
+    struct Answers {
+        solved: Vec<LevelSolved>,
+        hinted: Vec<LevelHinted>,
+    }
+
+    fn collect(mut answers: ResMut<Answers>, mut solved: MessageReader<LevelSolved>, mut hinted: MessageReader<LevelHinted>) {
+        answers.solved.extend(solved.read().cloned());
+        answers.hinted.extend(hinted.read().cloned());
+    }
+
+    // .numlin のファイルと足した Level を読み, 読めたものは解いて, 読めないものと知らない番号は InvalidUrl で返す.
+    // handle を落とすとアセットも消えるので最後まで持っておく
+    let dir = env::temp_dir().join(format!("numberlink_bevy_{}", std::process::id()));
+    let unique = "numlin/5/5/h123h2h4g3i1l4g";
+    let config = SolverConfig::default();
+
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("unique.numlin"), format!("{}\n", unique)).unwrap();
+    std::fs::write(dir.join("unsolvable.numlin"), "numlin/2/2/1221").unwrap();
+    std::fs::write(dir.join("broken.numlin"), "nonsense").unwrap();
+
+    let mut app = App::new();
+
+    app.add_plugins((TaskPoolPlugin::default(), AssetPlugin { file_path: dir.to_str().unwrap().to_string(), ..AssetPlugin::default() }, NumberlinkPlugin::default()))
+        .init_resource::<Answers>()
+        .add_systems(Last, collect);
+
+    let server = app.world().resource::<AssetServer>().clone();
+    let handles = ["unique.numlin", "unsolvable.numlin", "broken.numlin", "missing.numlin"].iter().map(|&path| server.load::<Level>(path)).collect::<Vec<_>>();
+    let added = app.world_mut().resource_mut::<Assets<Level>>().add(Level { puzzle: parse_puzzle("numlin/3/3/1ggggggg1").unwrap() });
+    let levels: Vec<AssetId<Level>> = handles.iter().chain(vec![&added]).map(|handle| handle.id()).chain(vec![AssetId::invalid()]).collect();
+
+    for &level in &levels {
+        app.world_mut().write_message(SolveLevel { level });
+    }
+
+    app.world_mut().write_message(HintLevel { level: levels[0], drawn: vec![] });
+
+    for _ in 0..1000 {
+        app.update();
+
+        let answers = app.world().resource::<Answers>();
+
+        if answers.solved.len() == levels.len() && answers.hinted.len() == 1 {
+            break;
+        }
+
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let answers = app.world().resource::<Answers>();
+    let solved = |level: AssetId<Level>| answers.solved.iter().find(|answer| answer.level == level).map(|answer| answer.paths.clone()).unwrap();
+    let puzzle = parse_puzzle(unique).unwrap();
+    let small = parse_puzzle("numlin/3/3/1ggggggg1").unwrap();
+
+    assert_eq!(answers.solved.len(), levels.len());
+    assert_eq!(solved(levels[0]), Ok(paths(&puzzle, &solve_numberlink(&puzzle, &config).unwrap())));
+    assert_eq!(solved(levels[1]), Err(Error::NoSolution));
+    assert_eq!(solved(levels[2]), Err(Error::InvalidUrl));
+    assert_eq!(solved(levels[3]), Err(Error::InvalidUrl));
+    assert_eq!(solved(levels[4]), Ok(paths(&small, &solve_numberlink(&small, &config).unwrap())));
+    assert_eq!(solved(levels[5]), Err(Error::InvalidUrl));
+    assert_eq!(answers.hinted.len(), 1);
+    assert_eq!(answers.hinted[0].level, levels[0]);
+    assert_eq!(answers.hinted[0].hint, hint_with(&puzzle, &PartialSolution::from_paths(&[]), &config));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "python")]
+#[test]
+fn python_module() {
+    // build した cdylib を numberlink_solver.so として python3 から import し, 答えを 1 行ずつ出させて library と比べる
+    const SCRIPT: &str = r#"
+import sys
+import numberlink_solver as n
+
+for line in sys.stdin.read().split():
+    try:
+        print(n.solve(line).render("json"), end="")
+    except n.SolveError as e:
+        print("error:", e)
+
+puzzle = n.Puzzle("https://puzz.link/p?numlin/5/5/h123h2h4g3i1l4g")
+solution = puzzle.solve()
+print(puzzle.width, puzzle.height, puzzle.cells)
+print(solution.paths)
+print(solution.render() == str(solution), puzzle.render("url"), repr(puzzle))
+print(n.generate(5, 5, 4, seed=7).render("url"), n.Puzzle.generate(5, 5, 4, 7).render("url"))
+for f in [lambda: n.Puzzle("nonsense"), lambda: puzzle.render("png"), lambda: solution.render("url")]:
+    try:
+        f()
+    except ValueError as e:
+        print("ValueError:", e)
+"#;
+
+    let mut rng = Lcg(458);
+    let puzzles: Vec<Puzzle> = (0..50).map(|_| random_puzzle(&mut rng)).collect();
+    let input: String = puzzles.iter().map(|puzzle| render_url(puzzle)+"\n").collect();
+    let mut command = std::process::Command::new("python3");
+
+    command.args(["-c", SCRIPT]).env("PYTHONPATH", cdylib("python", "numberlink_solver.so"));
+
+    let (code, output) = run_input(command, &input);
+    let mut expected: String = puzzles.iter().map(|puzzle| match solve_numberlink(puzzle, &SolverConfig::default()) {
+        Ok(sol) => render(puzzle, &sol, Format::Json, Theme::Plain),
+        Err(e) => format!("error: {}\n", e),
+    }).collect();
+    let url = "https://puzz.link/p?numlin/5/5/h123h2h4g3i1l4g";
+    let puzzle = parse_puzzle(url).unwrap();
+    let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+    let rows: Vec<String> = puzzle.cells.chunks(5).map(|row| format!("{:?}", row)).collect();
+    let paths: Vec<String> = paths(&puzzle, &sol).iter().map(|(num, path)| {
+        format!("({}, [{}])", num, path.iter().map(|p| format!("{:?}", p)).collect::<Vec<_>>().join(", "))
+    }).collect();
+    let generated = render_url(&generate(5, 5, 4, 7).unwrap());
+
+    expected.push_str(&format!("5 5 [{}]\n[{}]\n", rows.join(", "), paths.join(", ")));
+    expected.push_str(&format!("True {} Puzzle({:?})\n", render_url(&puzzle), render_url(&puzzle)));
+    expected.push_str(&format!("{} {}\n", generated, generated));
+    expected.push_str("ValueError: invalid puzzle url\n");
+    expected.push_str("ValueError: unknown format `png` (expected url, text, json or svg)\n");
+    expected.push_str("ValueError: unknown format `url` (expected text, json or svg)\n");
+
+    assert_eq!(code, 0);
+    assert_eq!(output, expected);
+}
+
+#[cfg(feature = "node")]
+#[test]
+fn node_addon() {
+    // build した cdylib を numberlink_solver.node として node から require し, 同期と非同期の答えを library と比べる
+    const SCRIPT: &str = r#"
+const n = require(process.env.ADDON);
+const show = (s) => JSON.stringify([s.width, s.height, s.paths.map((p) => [p.number, p.cells]), s.text]);
+const lines = require("fs").readFileSync(0, "utf8").split("\n").filter((line) => line);
+const sync = lines.map((line) => { try { return show(n.solve(line)); } catch (e) { return "error: " + e.message; } });
+
+Promise.all(lines.map((line) => n.solveAsync(line).then(show, (e) => "error: " + e.message))).then((async) => {
+  sync.forEach((line) => console.log(line));
+  console.log(async.every((line, k) => line === sync[k]));
+  const url = "https://puzz.link/p?numlin/5/5/h123h2h4g3i1l4g";
+  for (const format of ["url", "text", "json", "svg"]) {
+    console.log(JSON.stringify(n.convert(url, format)));
+  }
+  console.log(n.generate(5, 5, 4, 7), n.generate(5, 5, 4, 7, "text") === n.convert(n.generate(5, 5, 4, 7), "text"));
+  for (const f of [() => n.solve("nonsense"), () => n.convert(url, "png"), () => n.solveAsync("nonsense")]) {
+    try { f(); } catch (e) { console.log(e.code, e.message); }
+  }
+});
+"#;
+
+    let mut rng = Lcg(459);
+    let puzzles: Vec<Puzzle> = (0..50).map(|_| random_puzzle(&mut rng)).collect();
+    let input: String = puzzles.iter().map(|puzzle| render_url(puzzle)+"\n").collect();
+    let mut command = std::process::Command::new("node");
+
+    command.args(["-e", SCRIPT]).env("ADDON", cdylib("node", "numberlink_solver.node").join("numberlink_solver.node"));
+
+    let (code, output) = run_input(command, &input);
+    let mut expected: String = puzzles.iter().map(|puzzle| match solve_numberlink(puzzle, &SolverConfig::default()) {
+        Ok(sol) => {
+            let paths: Vec<(usize, Vec<[usize; 2]>)> = paths(puzzle, &sol).into_iter().map(|(num, path)| (num, path.into_iter().map(|(i, j)| [i, j]).collect())).collect();
+
+            serde_json::to_string(&(puzzle.width, puzzle.height, paths, render(puzzle, &sol, Format::Text, Theme::Plain))).unwrap()+"\n"
+        },
+        Err(e) => format!("error: {}\n", e),
+    }).collect();
+    let puzzle = parse_puzzle("https://puzz.link/p?numlin/5/5/h123h2h4g3i1l4g").unwrap();
+
+    expected.push_str("true\n");
+
+    for output in [Output::Url, Output::Text].iter().map(|&output| write_puzzle(&puzzle, output)).chain(Some(render_puzzle_json(&puzzle))).chain(Some(write_puzzle(&puzzle, Output::Svg))) {
+        expected.push_str(&format!("{}\n", serde_json::to_string(&output).unwrap()));
+    }
+
+    expected.push_str(&format!("{} true\n", write_puzzle(&generate(5, 5, 4, 7).unwrap(), Output::Url)));
+    expected.push_str("InvalidArg invalid puzzle url\n");
+    expected.push_str("InvalidArg unknown format `png` (expected url, text, json or svg)\n");
+    expected.push_str("InvalidArg invalid puzzle url\n");
+
+    assert_eq!(code, 0);
+    assert_eq!(output, expected);
+}
+
+#[cfg(feature = "capi")]
+#[test]
+fn c_api() {
+    use std::ffi::{CStr, CString};
+    use numberlink_solver::capi::{numberlink_solution_free, numberlink_solve, numberlink_solve_json, numberlink_string_free, NumberlinkStatus};
+
+    // 解けたら行優先のセルごとの数字, 解けなければ status と理由. JSON の方は solve --format json の 1 行
+    let solve = |input: &CStr, timeout_ms: u64| unsafe {
+        let solution = numberlink_solve(input.as_ptr(), timeout_ms);
+        let s = &*solution;
+        let res = match s.status {
+            NumberlinkStatus::Solved => Ok(std::slice::from_raw_parts(s.cells, s.width*s.height).to_vec()),
+            status => Err((status, CStr::from_ptr(s.message).to_str().unwrap().to_string())),
+        };
+        let json = numberlink_solve_json(input.as_ptr(), timeout_ms);
+        let line = CStr::from_ptr(json).to_str().unwrap().to_string();
+
+        numberlink_string_free(json);
+        numberlink_solution_free(solution);
+
+        (res, line)
+    };
+    let mut rng = Lcg(460);
+
+    for _ in 0..100 {
+        let puzzle = random_puzzle(&mut rng);
+        let (res, line) = solve(&CString::new(render_url(&puzzle)).unwrap(), 0);
+
+        match solve_numberlink(&puzzle, &SolverConfig::default()) {
+            Ok(sol) => {
+                let mut cells = vec![0; puzzle.cells.len()];
+
+                for (num, path) in paths(&puzzle, &sol) {
+                    for p in path {
+                        cells[puzzle.index(p)] = num;
+                    }
+                }
+
+                assert_eq!(res, Ok(cells));
+                assert_eq!(line, render_line(&puzzle, &sol, Format::Json));
+            },
+            Err(e) => {
+                assert_eq!(res, Err((NumberlinkStatus::NoSolution, e.to_string())));
+                assert_eq!(line, render_error_line(&e, Format::Json));
+            },
+        }
+    }
+
+    let sample = CString::new("numlin/12/12/1p3h9g3j4i2j5t5l87g6l6j2g7jbgbjal8g1czg9uahcp4").unwrap();
+
+    assert!(matches!(solve(&sample, 1), (Err((NumberlinkStatus::Timeout, _)), _)));
+    assert!(matches!(solve(&sample, 0), (Ok(_), _)));
+    assert_eq!(solve(&CString::new("nonsense").unwrap(), 0), (Err((NumberlinkStatus::InvalidInput, "invalid puzzle url".to_string())), r#"{"error":"invalid puzzle url"}"#.to_string()));
+    assert_eq!(solve(&CString::new(vec![0xff, 0xfe]).unwrap(), 0).0, Err((NumberlinkStatus::InvalidInput, "invalid puzzle url".to_string())));
+
+    unsafe {
+        let solution = numberlink_solve(std::ptr::null(), 0);
+
+        assert_eq!((*solution).status, NumberlinkStatus::InvalidInput);
+        numberlink_solution_free(solution);
+        numberlink_solution_free(std::ptr::null_mut());
+        numberlink_string_free(std::ptr::null_mut());
+    }
+}
+
+#[cfg(feature = "capi")]
+#[test]
+fn c_example() {
+    // examples/c/solve.c を include/ のヘッダと, このテストと同じ deps/ の cdylib で build して走らせる
+    let deps = env::current_exe().unwrap().with_file_name("");
+    let exe = env::temp_dir().join(format!("numberlink_c_example_{}", std::process::id()));
+    let root = env!("CARGO_MANIFEST_DIR");
+    let status = std::process::Command::new("cc")
+        .arg(format!("{}/examples/c/solve.c", root))
+        .arg(format!("-I{}/include", root))
+        .arg(format!("-L{}", deps.display()))
+        .args(["-lnumberlink_solver", "-o"])
+        .arg(&exe)
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+
+    let run = |input: &str| {
+        let output = std::process::Command::new(&exe).arg(input).env("LD_LIBRARY_PATH", &deps).output().unwrap();
+
+        (output.status.code().unwrap_or(-1), String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    };
+    let url = "https://puzz.link/p?numlin/5/5/h123h2h4g3i1l4g";
+    let puzzle = parse_puzzle(url).unwrap();
+    let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+    let mut cells = [0; 25];
+
+    for (num, path) in paths(&puzzle, &sol) {
+        for p in path {
+            cells[puzzle.index(p)] = num;
+        }
+    }
+
+    let rows: String = cells.chunks(5).map(|row| row.iter().map(usize::to_string).collect::<Vec<_>>().join(" ")+"\n").collect();
+
+    assert_eq!(run(url), (0, rows+&render_line(&puzzle, &sol, Format::Json)+"\n", String::new()));
+    assert_eq!(run("numlin/2/2/1221"), (1, String::new(), "no solution\n".to_string()));
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn server_routes() {
+    let server = Server::start(&["--backend", "varisat", "--cache", "0"]);
+    let url = "https://puzz.link/p?numlin/5/5/h123h2h4g3i1l4g";
+    let puzzle = parse_puzzle(url).unwrap();
+    let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+    let line = render_line(&puzzle, &sol, Format::Json)+"\n";
+
+    // /solve は solve --format json の 1 行. 解けなければ 422, 読めなければ 400, 大きすぎれば 413
+    let (status, headers, body) = server.request("POST", "/solve", &[], url);
+
+    assert_eq!((status, body), (200, line.clone()));
+    assert!(headers.contains(&("content-type".to_string(), "application/json".to_string())));
+    assert_eq!(server.post("/solve", &format!("{{\"puzzle\":{:?}}}", url)), (200, line.clone()));
+    assert_eq!(server.post("/solve", &format!("{{\"puzzle\":{}}}", render_puzzle_json(&puzzle).trim_end())), (200, line));
+    assert_eq!(server.post("/solve", "numlin/2/2/1221"), (422, "{\"error\":\"no solution\"}\n".to_string()));
+    assert_eq!(server.post("/solve", "nonsense"), (400, "{\"error\":\"invalid puzzle url\"}\n".to_string()));
+    assert_eq!(server.post("/solve", "numlin/10000/10000/1").0, 413);
+
+    let mut rng = Lcg(461);
+
+    for _ in 0..30 {
+        let puzzle = random_puzzle(&mut rng);
+        let expected = match solve_numberlink(&puzzle, &SolverConfig::default()) {
+            Ok(sol) => (200, render_line(&puzzle, &sol, Format::Json)+"\n"),
+            Err(e) => (422, render_error_line(&e, Format::Json)+"\n"),
+        };
+
+        assert_eq!(server.post("/solve", &render_url(&puzzle)), expected);
+    }
+
+    // /check は道があれば確かめ, なければ解けるかと答えが 1 つか
+    let drawn = render(&puzzle, &sol, Format::Json, Theme::Plain);
+    let partial: Vec<Vec<[usize; 2]>> = paths(&puzzle, &sol).into_iter().skip(1).map(|(_, path)| path.into_iter().map(|(i, j)| [i, j]).collect()).collect();
+    let partial = format!("{{\"width\":5,\"height\":5,\"paths\":{}}}", serde_json::to_string(&partial).unwrap());
+    let request = |solution: &str| format!("{{\"puzzle\":{:?},\"solution\":{:?}}}", url, solution);
+
+    assert_eq!(server.post("/check", &request(&drawn)), (200, "{\"valid\":true}\n".to_string()));
+    assert_eq!(server.post("/check", &format!("{{\"puzzle\":{:?},\"solution\":{}}}", url, partial)).0, 200);
+    assert!(server.post("/check", &request(&partial)).1.starts_with("{\"valid\":false,\"reason\":"));
+    assert_eq!(server.post("/check", &request("(")).0, 400);
+    assert_eq!(server.post("/check", url), (200, "{\"solvable\":true,\"unique\":true}\n".to_string()));
+    assert_eq!(server.post("/check", "numlin/3/3/1ggggggg1"), (200, "{\"solvable\":true,\"unique\":false}\n".to_string()));
+    assert_eq!(server.post("/check", "numlin/2/2/1221"), (200, "{\"solvable\":false,\"unique\":false}\n".to_string()));
+
+    // /generate は gen と同じ盤面
+    let generated = generate(5, 5, 4, 7).unwrap();
+    let expected = format!("{{\"url\":{:?},\"puzzle\":{}}}\n", render_url(&generated), render_puzzle_json(&generated).trim_end());
+
+    assert_eq!(server.post("/generate", r#"{"width":5,"height":5,"pairs":4,"seed":7}"#), (200, expected));
+    assert_eq!(server.post("/generate", r#"{"width":5,"height":5,"pairs":0}"#).0, 400);
+    assert_eq!(server.post("/generate", r#"{"width":5,"height":5}"#).0, 400);
+    assert_eq!(server.post("/generate", "5x5").0, 400);
+    assert_eq!(server.post("/generate", r#"{"width":1000,"height":1000,"pairs":2}"#).0, 413);
+
+    assert_eq!(server.get("/solve").0, 405);
+    assert_eq!(server.post("/nothing", "").0, 404);
+
+    // 時間切れは 503
+    let slow = Server::start(&["--backend", "varisat", "--timeout", "0.001"]);
+
+    assert_eq!(slow.post("/solve", "numlin/12/12/1p3h9g3j4i2j5t5l87g6l6j2g7jbgbjal8g1czg9uahcp4").0, 503);
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn server_progress() {
+    let server = Server::start(&["--backend", "varisat", "--cache", "0"]);
+    let solve = |body: &str| {
+        let mut socket = server.websocket("/progress");
+
+        ws_send(&mut socket, body);
+
+        let mut events = vec![];
+
+        loop {
+            match ws_recv(&mut socket) {
+                (1, text) => events.push(text),
+                (8, _) => return events,
+                (opcode, text) => panic!("unexpected frame {} {:?}", opcode, text),
+            }
+        }
+    };
+    let url = "https://puzz.link/p?numlin/5/5/h123h2h4g3i1l4g";
+    let puzzle = parse_puzzle(url).unwrap();
+    let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+    let best = minimize_turns(&puzzle, &SolverConfig::default()).unwrap();
+
+    // すぐ解ける盤面は結果だけを送って閉じる. objective があれば最適化する
+    assert_eq!(solve(url), [format!("{{\"event\":\"result\",\"solution\":{}}}", render_line(&puzzle, &sol, Format::Json))]);
+    assert_eq!(solve(&format!("{{\"puzzle\":{:?},\"objective\":\"turns\"}}", url)), [format!(
+        "{{\"event\":\"result\",\"solution\":{},\"turns\":{},\"cost\":{},\"optimal\":{}}}",
+        render_line(&puzzle, &best.sol, Format::Json), best.turns, best.cost, best.optimal,
+    )]);
+    assert_eq!(solve("numlin/2/2/1221"), [r#"{"event":"error","error":"no solution"}"#]);
+    assert_eq!(solve("nonsense"), [r#"{"event":"error","error":"invalid puzzle url"}"#]);
+    assert!(matches!(&solve(&format!("{{\"puzzle\":{:?},\"objective\":\"speed\"}}", url))[..], [event] if event.starts_with(r#"{"event":"error","error":"#)));
+
+    // 長くかかる最適化は 0.5 秒ごとに途中経過を送り, 接続を切ると解くのをやめる
+    let mut socket = server.websocket("/progress");
+    let mut last = (0, 0.0);
+
+    ws_send(&mut socket, r#"{"puzzle":"numlin/20/20/1zzzzzzzzzzzzzzzzzzzx1","objective":"turns"}"#);
+
+    for _ in 0..2 {
+        let (opcode, text) = ws_recv(&mut socket);
+        let event: serde_json::Value = serde_json::from_str(&text).unwrap();
+        let (conflicts, elapsed) = (event["conflicts"].as_u64().unwrap(), event["elapsed"].as_f64().unwrap());
+
+        assert_eq!((opcode, event["event"].as_str()), (1, Some("progress")), "{}", text);
+        assert!(event.get("bound").is_some());
+        assert!(conflicts >= last.0 && elapsed > last.1);
+        last = (conflicts, elapsed);
+    }
+
+    socket.shutdown(std::net::Shutdown::Both).unwrap();
+    drop(socket);
+
+    let stopped = (0..100).any(|_| {
+        std::thread::sleep(Duration::from_millis(100));
+        server.get("/metrics").1.contains("numberlink_solves_total{result=\"interrupted\"} 1\n")
+    });
+
+    assert!(stopped);
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn server_jobs() {
+    let server = Server::start(&["--backend", "varisat", "--cache", "0", "--workers", "1", "--queue", "1"]);
+    let slow = r#"{"puzzle":"numlin/20/20/1zzzzzzzzzzzzzzzzzzzx1","objective":"turns"}"#;
+    let url = "https://puzz.link/p?numlin/5/5/h123h2h4g3i1l4g";
+    let puzzle = parse_puzzle(url).unwrap();
+    let best = minimize_turns(&puzzle, &SolverConfig::default()).unwrap();
+    // 終わるまで (result が 200 になるまで) 待つ
+    let result = |id: &str| (0..600).find_map(|_| match server.get(&format!("/jobs/{}/result", id)) {
+        (200, body) => Some(body),
+        (202, _) => {
+            std::thread::sleep(Duration::from_millis(10));
+            None
+        },
+        other => panic!("{:?}", other),
+    }).unwrap();
+    let status = |id: &str| serde_json::from_str::<serde_json::Value>(&server.get(&format!("/jobs/{}", id)).1).unwrap()["status"].as_str().unwrap().to_string();
+
+    // 1 つのスレッドが長い仕事を解いている間, 次の仕事は列で待ち, 列が一杯なら 503
+    assert_eq!(server.post("/jobs", slow), (202, "{\"id\":\"0\"}\n".to_string()));
+    assert!((0..600).any(|_| {
+        std::thread::sleep(Duration::from_millis(10));
+        status("0") == "running"
+    }));
+
+    let (code, running) = server.get("/jobs/0/result");
+
+    assert_eq!(code, 202);
+    assert!(running.starts_with("{\"id\":\"0\",\"status\":\"running\",\"conflicts\":"), "{}", running);
+    assert_eq!(server.post("/jobs", url), (202, "{\"id\":\"1\"}\n".to_string()));
+    assert_eq!(server.get("/jobs/1"), (200, "{\"id\":\"1\",\"status\":\"queued\",\"position\":0}\n".to_string()));
+    assert_eq!(server.get("/jobs/1/result"), (202, "{\"id\":\"1\",\"status\":\"queued\",\"position\":0}\n".to_string()));
+    assert_eq!(server.post("/jobs", url), (503, "{\"error\":\"too many jobs waiting\"}\n".to_string()));
+
+    // 待っている仕事はすぐ取り消し, 解いている仕事は止まってから取り消しになる
+    assert_eq!(server.request("DELETE", "/jobs/1", &[], "").2, "{\"id\":\"1\",\"status\":\"cancelled\"}\n");
+    assert_eq!(server.request("DELETE", "/jobs/0", &[], "").0, 200);
+    assert_eq!(result("0"), "{\"id\":\"0\",\"status\":\"cancelled\"}\n");
+    assert_eq!(result("1"), "{\"id\":\"1\",\"status\":\"cancelled\"}\n");
+
+    // 終わった仕事の結果は /progress の結果と同じ欄
+    assert_eq!(server.post("/jobs", &format!("{{\"puzzle\":{:?},\"objective\":\"turns\"}}", url)), (202, "{\"id\":\"2\"}\n".to_string()));
+    assert_eq!(result("2"), format!(
+        "{{\"id\":\"2\",\"status\":\"done\",\"solution\":{},\"turns\":{},\"cost\":{},\"optimal\":{}}}\n",
+        render_line(&puzzle, &best.sol, Format::Json), best.turns, best.cost, best.optimal,
+    ));
+    assert_eq!(server.get("/jobs/2"), (200, "{\"id\":\"2\",\"status\":\"done\"}\n".to_string()));
+    assert_eq!(server.post("/jobs", "numlin/2/2/1221").0, 202);
+    assert_eq!(result("3"), "{\"id\":\"3\",\"status\":\"failed\",\"error\":\"no solution\"}\n");
+    assert_eq!(server.get("/jobs/3"), (200, "{\"id\":\"3\",\"status\":\"failed\"}\n".to_string()));
+
+    assert_eq!(server.get("/jobs/99"), (404, "{\"error\":\"no such job\"}\n".to_string()));
+    assert_eq!(server.get("/jobs/99/result").0, 404);
+    assert_eq!(server.request("DELETE", "/jobs/99", &[], "").0, 404);
+    assert_eq!(server.get("/jobs/first").0, 400);
+}
+
+// OpenAPI の schema (使っている分だけ: $ref, oneOf, type, nullable, enum, required, properties, items) に value が合うか
+#[cfg(feature = "server")]
+fn conforms(value: &serde_json::Value, schema: &serde_json::Value, doc: &serde_json::Value) -> bool {
+    use serde_json::Value;
+
+    if let Some(Value::String(path)) = schema.get("$ref") {
+        return conforms(value, doc.pointer(path.trim_start_matches('#')).unwrap(), doc);
+    }
+
+    if let Some(Value::Array(schemas)) = schema.get("oneOf") {
+        return schemas.iter().filter(|schema| conforms(value, schema, doc)).count() >= 1;
+    }
+
+    if value.is_null() {
+        return schema.get("nullable") == Some(&Value::Bool(true));
+    }
+
+    let typed = match schema.get("type").and_then(Value::as_str) {
+        Some("object") => value.is_object(),
+        Some("array") => value.as_array().is_some_and(|items| items.iter().all(|item| conforms(item, &schema["items"], doc))),
+        Some("string") => value.is_string(),
+        Some("integer") => value.is_u64() || value.is_i64(),
+        Some("number") => value.is_number(),
+        Some("boolean") => value.is_boolean(),
+        _ => true,
+    };
+    let listed = schema.get("enum").and_then(Value::as_array).is_none_or(|values| values.contains(value));
+    let required = schema.get("required").and_then(Value::as_array).is_none_or(|keys| keys.iter().all(|key| value.get(key.as_str().unwrap()).is_some()));
+    let properties = schema.get("properties").and_then(Value::as_object).is_none_or(|properties| {
+        properties.iter().all(|(key, schema)| value.get(key).is_none_or(|value| conforms(value, schema, doc)))
+    });
+
+    typed && listed && required && properties
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn server_openapi() {
+    use numberlink_solver::openapi::openapi;
+
+    let server = Server::start(&["--backend", "varisat"]);
+    let doc = openapi();
+    let (code, printed) = cli(&["serve", "--openapi"]);
+
+    // serve --openapi と GET /openapi.json は同じ文書
+    assert_eq!((code, serde_json::from_str::<serde_json::Value>(&printed).unwrap()), (0, doc.clone()));
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&server.get("/openapi.json").1).unwrap(), doc);
+    assert_eq!(doc["openapi"], "3.0.3");
+    assert!(!conforms(&serde_json::json!({"width": "5"}), &doc["components"]["schemas"]["Solution"], &doc));
+    assert!(!conforms(&serde_json::json!({"id": "0", "status": "lost"}), &doc["components"]["schemas"]["JobStatus"], &doc));
+
+    // 文書の経路ごとに要求を送り, 返った status が書いてあり, 本文がその schema に合うか
+    let url = "https://puzz.link/p?numlin/5/5/h123h2h4g3i1l4g";
+    let check = |method: &str, route: &str, path: &str, body: &str, status: u16| {
+        let (code, headers, body) = server.request(method, path, &[], body);
+        let response = &doc["paths"][route][method.to_ascii_lowercase()]["responses"][code.to_string()];
+        let content = headers.iter().find(|(name, _)| name == "content-type").map(|(_, value)| value.split(';').next().unwrap()).unwrap();
+
+        assert_eq!(code, status, "{} {}: {}", method, path, body);
+        assert!(response.is_object(), "{} {} {} is not documented", method, route, code);
+
+        match content {
+            "application/json" => assert!(conforms(&serde_json::from_str(&body).unwrap(), &response["content"][content]["schema"], &doc), "{} {}: {}", method, path, body),
+            _ => assert!(response["content"][content].is_object(), "{} {}: {}", method, route, content),
+        }
+    };
+
+    check("POST", "/solve", "/solve", url, 200);
+    check("POST", "/solve", "/solve", &format!("{{\"puzzle\":{:?}}}", url), 200);
+    check("POST", "/solve", "/solve", "nonsense", 400);
+    check("POST", "/solve", "/solve", "numlin/10000/10000/1", 413);
+    check("POST", "/solve", "/solve", "numlin/2/2/1221", 422);
+    check("POST", "/check", "/check", url, 200);
+    check("POST", "/check", "/check", &format!("{{\"puzzle\":{:?},\"solution\":\"(\"}}", url), 400);
+    check("POST", "/generate", "/generate", r#"{"width":5,"height":5,"pairs":4,"seed":7}"#, 200);
+    check("POST", "/generate", "/generate", r#"{"width":5,"height":5,"pairs":20}"#, 400);
+    check("POST", "/generate", "/generate", r#"{"width":1000,"height":1000,"pairs":2}"#, 413);
+    check("POST", "/jobs", "/jobs", &format!("{{\"puzzle\":{:?},\"objective\":\"turns\"}}", url), 202);
+
+    while server.get("/jobs/0/result").0 == 202 {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    check("GET", "/jobs/{id}", "/jobs/0", "", 200);
+    check("GET", "/jobs/{id}/result", "/jobs/0/result", "", 200);
+    check("DELETE", "/jobs/{id}", "/jobs/0", "", 200);
+    check("GET", "/jobs/{id}", "/jobs/9", "", 404);
+    check("GET", "/jobs/{id}/result", "/jobs/9/result", "", 404);
+    check("DELETE", "/jobs/{id}", "/jobs/9", "", 404);
+    check("GET", "/metrics", "/metrics", "", 200);
+
+    // /progress は WebSocket (101) で, 最後のメッセージは ProgressEvent
+    let mut socket = server.websocket("/progress");
+
+    ws_send(&mut socket, url);
+    assert!(conforms(&serde_json::from_str(&ws_recv(&mut socket).1).unwrap(), &doc["components"]["schemas"]["ProgressEvent"], &doc));
+    assert!(doc["paths"]["/progress"]["get"]["responses"]["101"].is_object());
+
+    // 書いてある経路はすべて試した
+    let routes: Vec<&String> = doc["paths"].as_object().unwrap().keys().collect();
+
+    assert_eq!(routes, ["/check", "/generate", "/jobs", "/jobs/{id}", "/jobs/{id}/result", "/metrics", "/progress", "/solve"]);
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn server_metrics() {
+    let server = Server::start(&["--backend", "varisat"]);
+    let url = "numlin/5/5/h123h2h4g3i1l4g";
+    // 同じ盤面を回したもの (覚えた答えが当たる)
+    let turned = render_url(&transform(&parse_puzzle(url).unwrap(), Transform::Rotate90).unwrap());
+
+    assert_eq!(server.post("/solve", url).0, 200);
+    assert_eq!(server.post("/solve", url).0, 200);
+    assert_eq!(server.post("/solve", &turned).0, 200);
+    assert_eq!(server.post("/solve", "numlin/2/2/1221").0, 422);
+    assert_eq!(server.post("/solve", "nonsense").0, 400);
+    assert_eq!(server.post("/check", "numlin/2/2/1221").0, 200);
+
+    let (status, headers, text) = server.request("GET", "/metrics", &[], "");
+    let metric = |series: &str| text.lines().find_map(|line| line.strip_prefix(series)?.strip_prefix(' ')).map(|value| value.parse::<f64>().unwrap());
+
+    assert_eq!(status, 200);
+    assert!(headers.contains(&("content-type".to_string(), "text/plain; version=0.0.4".to_string())));
+
+    // 読めない要求は解かず, 覚えた答えを使った要求も解かない. /check は覚えた答えを使わずに解く
+    assert_eq!(metric(r#"numberlink_http_requests_total{path="/solve",status="200"}"#), Some(3.0));
+    assert_eq!(metric(r#"numberlink_http_requests_total{path="/solve",status="422"}"#), Some(1.0));
+    assert_eq!(metric(r#"numberlink_http_requests_total{path="/solve",status="400"}"#), Some(1.0));
+    assert_eq!(metric(r#"numberlink_http_requests_total{path="/check",status="200"}"#), Some(1.0));
+    assert_eq!(metric(r#"numberlink_http_requests_total{path="/metrics",status="200"}"#), None);
+    assert_eq!(metric(r#"numberlink_solves_total{result="sat"}"#), Some(1.0));
+    assert_eq!(metric(r#"numberlink_solves_total{result="unsat"}"#), Some(2.0));
+    assert_eq!(metric(r#"numberlink_solves_total{result="timeout"}"#), Some(0.0));
+    assert_eq!(metric(r#"numberlink_cache_requests_total{result="hit"}"#), Some(2.0));
+    assert_eq!(metric(r#"numberlink_cache_requests_total{result="miss"}"#), Some(2.0));
+    assert_eq!(metric("numberlink_solve_duration_seconds_count"), Some(3.0));
+    assert_eq!(metric("numberlink_jobs_waiting"), Some(0.0));
+    assert_eq!(metric(r#"numberlink_http_request_duration_seconds_count{path="/solve"}"#), Some(5.0));
+    assert_eq!(metric(r#"numberlink_http_request_duration_seconds_bucket{path="/solve",le="+Inf"}"#), Some(5.0));
+
+    // ヒストグラムの bucket は累積で, sum は 0 以上
+    let buckets: Vec<f64> = text.lines().filter(|line| line.starts_with("numberlink_solve_duration_seconds_bucket")).map(|line| line.rsplit(' ').next().unwrap().parse().unwrap()).collect();
+
+    assert_eq!(buckets.len(), 11);
+    assert!(buckets.windows(2).all(|pair| pair[0] <= pair[1]) && buckets[10] == 3.0);
+    assert!(metric("numberlink_solve_duration_seconds_sum").unwrap() >= 0.0);
+
+    // 2 回目には 1 回目の /metrics も数える. 時間切れは timeout
+    assert_eq!(server.get("/metrics").1.lines().find(|line| line.starts_with(r#"numberlink_http_requests_total{path="/metrics""#)), Some(r#"numberlink_http_requests_total{path="/metrics",status="200"} 1"#));
+
+    let slow = Server::start(&["--backend", "varisat", "--timeout", "0.001"]);
+
+    assert_eq!(slow.post("/solve", "numlin/12/12/1p3h9g3j4i2j5t5l87g6l6j2g7jbgbjal8g1czg9uahcp4").0, 503);
+    assert!(slow.get("/metrics").1.contains("numberlink_solves_total{result=\"timeout\"} 1\n"));
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn server_keys() {
+    let path = env::temp_dir().join(format!("numberlink_keys_{}.txt", std::process::id()));
+
+    std::fs::write(&path, "# 1 分に 2 回, 上限なし, --rate の 3 回\nalpha 2\nbeta 0  # 社内\n\ngamma\n").unwrap();
+
+    let server = Server::start(&["--backend", "varisat", "--keys", path.to_str().unwrap(), "--rate", "3"]);
+    let url = "numlin/4/1/1gg1";
+    let send = |path: &str, headers: &[(&str, &str)]| {
+        let (status, headers, body) = server.request("POST", path, headers, url);
+        let retry = headers.iter().find(|(name, _)| name == "retry-after").map(|(_, value)| value.parse::<u64>().unwrap());
+
+        (status, retry, body)
+    };
+    let unauthorized = (401, None, "{\"error\":\"missing or unknown API key\"}\n".to_string());
+    let limited = |retry| (429, Some(retry), "{\"error\":\"rate limit exceeded\"}\n".to_string());
+
+    // キーがないか知らないキーは 401 (どの経路も)
+    assert_eq!(send("/solve", &[]), unauthorized);
+    assert_eq!(send("/solve", &[("Authorization", "Bearer delta")]), unauthorized);
+    assert_eq!(send("/solve", &[("Authorization", "Basic alpha")]), unauthorized);
+    assert_eq!(send("/solve?key=", &[]), unauthorized);
+    assert_eq!(server.get("/metrics").0, 401);
+    assert_eq!(server.get("/openapi.json").0, 401);
+    assert_eq!(server.get("/jobs/0").0, 401);
+
+    // 3 つの付け方はどれも同じキーの上限を使う. 空になったら 429 で, Retry-After は次の 1 回が溜まるまでの秒
+    assert_eq!(send("/solve", &[("Authorization", "Bearer alpha")]).0, 200);
+    assert_eq!(send("/solve", &[("X-API-Key", "alpha")]).0, 200);
+    assert_eq!(send("/solve?key=alpha", &[]), limited(30));
+    assert_eq!(send("/check", &[("X-API-Key", "alpha")]), limited(30));
+
+    // 上限はキーごと. 0 なら上限なし, 数のない行は --rate
+    for _ in 0..10 {
+        assert_eq!(send("/solve", &[("X-API-Key", "beta")]).0, 200);
+    }
+
+    for _ in 0..3 {
+        assert_eq!(send("/solve", &[("X-API-Key", "gamma")]).0, 200);
+    }
+
+    assert_eq!(send("/solve", &[("X-API-Key", "gamma")]), limited(20));
+
+    // WebSocket はヘッダを付けにくいので ?key= で
+    let mut socket = server.websocket("/progress?key=beta");
+
+    ws_send(&mut socket, url);
+    assert!(ws_recv(&mut socket).1.starts_with("{\"event\":\"result\""));
+    assert_eq!(server.request("GET", "/progress", &[("Upgrade", "websocket"), ("Connection", "Upgrade"), ("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ=="), ("Sec-WebSocket-Version", "13")], "").0, 401);
+
+    // 読めないキーのファイルは serve の前に断る
+    std::fs::write(&path, "# なし\n").unwrap();
+    assert_eq!(cli(&["serve", "--keys", path.to_str().unwrap()]).0, 1);
+    std::fs::write(&path, "alpha many\n").unwrap();
+    assert_eq!(cli(&["serve", "--keys", path.to_str().unwrap()]).0, 1);
+    std::fs::write(&path, "alpha 1 2\n").unwrap();
+    assert_eq!(cli(&["serve", "--keys", path.to_str().unwrap()]).0, 1);
+    assert_eq!(cli(&["serve", "--keys", path.to_str().unwrap(), "--rate", "-1"]).0, 1);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(cli(&["serve", "--keys", path.to_str().unwrap()]).0, 1);
+}
+
+#[cfg(feature = "grpc")]
+#[test]
+fn grpc_service() {
+    use numberlink_solver::grpc::proto::numberlink_client::NumberlinkClient;
+    use numberlink_solver::grpc::proto::{progress_event::Event as Streamed, Answer, Cell, CheckRequest, GenerateRequest, JobId, Path, SolveRequest};
+    use tonic::{Code, Request};
+
+    let free = || std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+    let (grpc, limited_grpc) = (free(), free());
+    let server = Server::start(&["--backend", "varisat", "--cache", "0", "--grpc", &grpc]);
+    let keys = env::temp_dir().join(format!("numberlink_grpc_keys_{}.txt", std::process::id()));
+
+    std::fs::write(&keys, "alpha 1\n").unwrap();
+
+    let _limited = Server::start(&["--backend", "varisat", "--keys", keys.to_str().unwrap(), "--grpc", &limited_grpc]);
+    let url = "https://puzz.link/p?numlin/5/5/h123h2h4g3i1l4g";
+    let puzzle = parse_puzzle(url).unwrap();
+    let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+    let best = minimize_turns(&puzzle, &SolverConfig::default()).unwrap();
+    // HTTP の /solve の JSON と同じ中身
+    let answer = |puzzle: &Puzzle, sol: &Sol| Answer {
+        width: puzzle.width as u32,
+        height: puzzle.height as u32,
+        field: puzzle.cells.iter().map(|&num| num as u32).collect(),
+        paths: paths(puzzle, sol).into_iter().map(|(num, path)| Path {
+            number: num as u32,
+            cells: path.into_iter().map(|(i, j)| Cell { row: i as u32, column: j as u32 }).collect(),
+        }).collect(),
+        turns: None,
+        cost: None,
+        optimal: None,
+    };
+    let solve = |puzzle: &str, objective: &str| SolveRequest { puzzle: puzzle.to_string(), objective: objective.to_string() };
+
+    std::fs::remove_file(&keys).unwrap();
+
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        let connect = |addr: String| async move {
+            NumberlinkClient::new(tonic::transport::Endpoint::from_shared(format!("http://{}", addr)).unwrap().connect().await.unwrap())
+        };
+        let mut client = connect(grpc).await;
+
+        // Solve は /solve と同じ答えで, 失敗は HTTP の status に当たる code
+        assert_eq!(client.solve(solve(url, "")).await.unwrap().into_inner(), answer(&puzzle, &sol));
+        assert_eq!(client.solve(solve(url, "turns")).await.unwrap().into_inner(), Answer {
+            turns: Some(best.turns as u32),
+            cost: Some(best.cost as u32),
+            optimal: Some(best.optimal),
+            ..answer(&puzzle, &best.sol)
+        });
+
+        let mut rng = Lcg(468);
+
+        for _ in 0..30 {
+            let puzzle = random_puzzle(&mut rng);
+            let res = client.solve(solve(&render_url(&puzzle), "")).await;
+
+            match solve_numberlink(&puzzle, &SolverConfig::default()) {
+                Ok(sol) => assert_eq!(res.unwrap().into_inner(), answer(&puzzle, &sol)),
+                Err(e) => assert_eq!(res.map_err(|status| (status.code(), status.message().to_string())).unwrap_err(), (Code::FailedPrecondition, e.to_string())),
+            }
+        }
+
+        let code = |res: Result<tonic::Response<Answer>, tonic::Status>| res.unwrap_err().code();
+
+        assert_eq!(code(client.solve(solve("nonsense", "")).await), Code::InvalidArgument);
+        assert_eq!(code(client.solve(solve(url, "speed")).await), Code::InvalidArgument);
+        assert_eq!(code(client.solve(solve("numlin/10000/10000/1", "")).await), Code::ResourceExhausted);
+
+        // Check と Generate
+        let check = |solution: &str| CheckRequest { puzzle: url.to_string(), solution: solution.to_string() };
+        let reply = client.check(check(&render(&puzzle, &sol, Format::Json, Theme::Plain))).await.unwrap().into_inner();
+
+        assert_eq!((reply.valid, reply.solvable), (Some(true), None));
+        let partial: Vec<Vec<[usize; 2]>> = paths(&puzzle, &sol).into_iter().skip(1).map(|(_, path)| path.into_iter().map(|(i, j)| [i, j]).collect()).collect();
+        let reply = client.check(check(&format!("{{\"width\":5,\"height\":5,\"paths\":{}}}", serde_json::to_string(&partial).unwrap()))).await.unwrap().into_inner();
+
+        assert!(reply.valid == Some(false) && !reply.reason.is_empty());
+        assert_eq!(client.check(check("(")).await.unwrap_err().code(), Code::InvalidArgument);
+
+        let reply = client.check(check("")).await.unwrap().into_inner();
+
+        assert_eq!((reply.valid, reply.solvable, reply.unique), (None, Some(true), Some(true)));
+
+        let reply = client.check(CheckRequest { puzzle: "numlin/2/2/1221".to_string(), solution: String::new() }).await.unwrap().into_inner();
+
+        assert_eq!((reply.solvable, reply.unique), (Some(false), Some(false)));
+
+        let generated = generate(5, 5, 4, 7).unwrap();
+        let reply = client.generate(GenerateRequest { width: 5, height: 5, pairs: 4, seed: 7 }).await.unwrap().into_inner();
+
+        assert_eq!((reply.url, reply.puzzle), (render_url(&generated), render_puzzle_json(&generated).trim_end().to_string()));
+        assert_eq!(client.generate(GenerateRequest { width: 2, height: 1, pairs: 3, seed: 0 }).await.unwrap_err().code(), Code::FailedPrecondition);
+
+        // Progress はすぐ解ければ結果だけ, 長ければ途中経過を流す. 受け取るのをやめると解くのをやめる
+        let mut stream = client.progress(solve(url, "")).await.unwrap().into_inner();
+
+        assert_eq!(stream.message().await.unwrap().unwrap().event, Some(Streamed::Result(answer(&puzzle, &sol))));
+        assert_eq!(stream.message().await.unwrap(), None);
+
+        let mut stream = client.progress(solve("numlin/2/2/1221", "")).await.unwrap().into_inner();
+
+        assert_eq!(stream.message().await.unwrap_err().code(), Code::FailedPrecondition);
+
+        let mut stream = client.progress(solve("numlin/20/20/1zzzzzzzzzzzzzzzzzzzx1", "turns")).await.unwrap().into_inner();
+
+        assert!(matches!(stream.message().await.unwrap().unwrap().event, Some(Streamed::Progress(_))));
+        drop(stream);
+
+        // 仕事の列は HTTP と同じもの
+        let id = client.submit_job(solve(url, "turns")).await.unwrap().into_inner().id;
+
+        assert_eq!(id, "0");
+
+        let done = loop {
+            let job = client.get_job(JobId { id: id.clone() }).await.unwrap().into_inner();
+
+            match job.status.as_str() {
+                "queued" | "running" => tokio::time::sleep(Duration::from_millis(10)).await,
+                _ => break job,
+            }
+        };
+
+        assert_eq!((done.status.as_str(), done.answer.and_then(|answer| answer.turns)), ("done", Some(best.turns as u32)));
+        assert!(server.get("/jobs/0/result").1.starts_with("{\"id\":\"0\",\"status\":\"done\""));
+        assert_eq!(client.get_job(JobId { id: "9".to_string() }).await.unwrap_err().code(), Code::NotFound);
+        assert_eq!(client.get_job(JobId { id: "first".to_string() }).await.unwrap_err().code(), Code::NotFound);
+        assert_eq!(client.cancel_job(JobId { id: "9".to_string() }).await.unwrap_err().code(), Code::NotFound);
+        assert_eq!(client.submit_job(solve("nonsense", "")).await.unwrap_err().code(), Code::InvalidArgument);
+
+        // キーは metadata で. なければ UNAUTHENTICATED, 上限を超えたら RESOURCE_EXHAUSTED
+        let mut limited = connect(limited_grpc).await;
+        let keyed = |name: &'static str, value: &str| {
+            let mut request = Request::new(solve(url, ""));
+
+            request.metadata_mut().insert(name, value.parse().unwrap());
+            request
+        };
+
+        assert_eq!(code(limited.solve(solve(url, "")).await), Code::Unauthenticated);
+        assert_eq!(code(limited.solve(keyed("authorization", "Bearer beta")).await), Code::Unauthenticated);
+        assert!(limited.solve(keyed("x-api-key", "alpha")).await.is_ok());
+        assert_eq!(code(limited.solve(keyed("authorization", "Bearer alpha")).await), Code::ResourceExhausted);
+    });
+
+    // 途中で受け取るのをやめた Progress は止まる
+    assert!((0..100).any(|_| {
+        std::thread::sleep(Duration::from_millis(100));
+        server.get("/metrics").1.contains("numberlink_solves_total{result=\"interrupted\"} 1\n")
+    }));
+}
+
+#[cfg(feature = "script")]
+#[test]
+fn script_constraints() {
+    use numberlink_solver::script::{solve_script, Script};
+
+    // 横に進む線の数を最小にするスクリプト (盤面によっては置けない向きの線もあるので, arc の失敗は飛ばす)
+    let horizontal = Script::new(r#"
+        fn constrain(puzzle, b) {
+            let lits = [];
+
+            for k in 0..puzzle.height * (puzzle.width - 1) {
+                lits += horizontal(b, k / (puzzle.width - 1), k % (puzzle.width - 1));
+            }
+
+            b.minimize(lits);
+        }
+
+        fn horizontal(b, r, c) {
+            let lits = [];
+
+            try { lits.push(b.arc(r, c, r, c + 1)); } catch {}
+            try { lits.push(b.arc(r, c + 1, r, c)); } catch {}
+            lits
+        }
+    "#).unwrap();
+    let count = |sol: &Sol| sol.iter().filter(|&&(p, q)| p.0 == q.0).count();
+    let config = SolverConfig::default();
+    let mut rng = Lcg(469);
+
+    for _ in 0..100 {
+        let puzzle = random_puzzle(&mut rng);
+
+        if puzzle.cells.len() > 16 {
+            continue;
+        }
+
+        // 最小は全部の解を数えたものと同じ
+        let res = solve_script(&puzzle, &config, &horizontal);
+
+        match sample_solutions(&puzzle, &config, usize::MAX, 0) {
+            Ok(all) if !all.is_empty() => {
+                let res = res.unwrap();
+
+                check(&puzzle, &res.sol);
+                assert_eq!((res.value, res.optimal), (Some(count(&res.sol)), true));
+                assert_eq!(res.value, all.iter().map(count).min());
+            },
+            _ => assert_eq!(res.unwrap_err(), Error::NoSolution),
+        }
+
+        // 最初の解を節で除くと, 答えが 1 つの盤面だけ解けなくなる
+        let sol = match solve_numberlink(&puzzle, &config) {
+            Ok(sol) => sol,
+            Err(_) => continue,
+        };
+        let arcs: Vec<String> = sol.iter().map(|&((i, j), (k, l))| format!("-b.arc({}, {}, {}, {})", i, j, k, l)).collect();
+        let other = Script::new(&format!("fn constrain(puzzle, b) {{ b.clause([{}]); }}", arcs.join(", "))).unwrap();
+
+        match solve_script(&puzzle, &config, &other) {
+            Ok(res) => {
+                check(&puzzle, &res.sol);
+                assert!(res.sol.iter().collect::<BTreeSet<_>>() != sol.iter().collect::<BTreeSet<_>>());
+                assert_eq!((res.value, res.optimal), (None, true));
+                assert_ne!(count_numberlink(&puzzle, &config).unwrap(), 1);
+            },
+            Err(e) => assert_eq!((e, count_numberlink(&puzzle, &config).unwrap()), (Error::NoSolution, 1)),
+        }
+    }
+
+    // 読めないスクリプト, constrain のないスクリプトと, 解く途中のスクリプトの誤り
+    let puzzle = parse_puzzle("numlin/4/1/1gg1").unwrap();
+    let invalid = |source: &str| match Script::new(source).and_then(|script| solve_script(&puzzle, &config, &script)) {
+        Err(Error::InvalidConstraint(msg)) => msg,
+        other => panic!("{:?}", other.map(|res| res.sol)),
+    };
+
+    assert!(!invalid("fn constrain(puzzle, b) {").is_empty());
+    assert_eq!(invalid("fn other(puzzle, b) {}"), "the script defines no fn constrain(puzzle, b)");
+    assert!(invalid("fn constrain(puzzle, b) { b.used(1, 0); }").contains("cell (1, 0) is not on the board"));
+    assert!(invalid("fn constrain(puzzle, b) { b.clause([100000]); }").contains("unknown literal 100000"));
+    assert!(invalid("fn constrain(puzzle, b) { b.number(0, 1, 9); }").contains("is out of range"));
+    assert!(invalid("fn constrain(puzzle, b) { b.arc(0, 0, 0, 2); }").contains("no arc from (0, 0) to (0, 2)"));
+
+    // script FILE: 解けない盤面はその行に理由を書いて続け (0), 読めないスクリプトは 1, 時間切れは 3
+    let path = env::temp_dir().join(format!("numberlink_script_{}.rhai", std::process::id()));
+    let file = path.to_str().unwrap();
+    let unique = "numlin/5/5/h123h2h4g3i1l4g";
+    let solved = parse_puzzle(unique).unwrap();
+
+    std::fs::write(&path, "fn constrain(puzzle, b) { b.clause([b.used(0, 0)]); }").unwrap();
+    assert_eq!(cli(&["script", file, "--format", "text", unique]), (0, render(&solved, &solve_numberlink(&solved, &config).unwrap(), Format::Text, Theme::Plain)));
+    assert_eq!(cli_input(&["script", file, "-"], "numlin/2/2/1221\n"), (0, "numlin/2/2/1221: no solution\n".to_string()));
+    assert_eq!(cli(&["script", file, "--timeout", "0.001", "numlin/12/12/1p3h9g3j4i2j5t5l87g6l6j2g7jbgbjal8g1czg9uahcp4"]).0, 3);
+    std::fs::write(&path, "fn other(puzzle, b) {}").unwrap();
+    assert_eq!(cli(&["script", file, unique]), (1, String::new()));
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(cli(&["script", file, unique]), (1, String::new()));
+    assert_eq!(cli(&["script"]).0, 1);
+}
+
+#[test]
+fn url_fragments() {
+    let puzzle = parse_url("https://puzz.link/p?numlin/4/3/1h2g1i2".to_string()).unwrap();
+
+    for fragment in ["numlin/4/3/1h2g1i2", "4/3/1h2g1i2", "4x3 1h2g1i2", "1h2g1i2 4x3", "4x3/1h2g1i2", " 4x3  1h2g1i2\n"] {
+        assert_eq!(parse_url(fragment.to_string()).as_ref(), Some(&puzzle), "{}", fragment);
+    }
+
+    // 大きさのない CODE は正方形のときだけ読める
+    assert_eq!(parse_url("1ggggggg1".to_string()), parse_url("numlin/3/3/1ggggggg1".to_string()));
+    assert_eq!(parse_puzzle("1h2g1h2").unwrap(), parse_url("numlin/3/3/1h2g1h2".to_string()).unwrap());
+    assert_eq!(parse_url("1h2g1i2".to_string()), None);
+    assert_eq!(parse_url("4x3 5x5".to_string()), None);
+    assert_eq!(parse_url("0x3 1h2g1i2".to_string()), None);
+    assert_eq!(parse_url("4x3 1h2g1i2 extra".to_string()), None);
+}
+
+#[test]
+fn oversized_boards() {
+    // 大きさは数文字で書けるので, 盤面を作る前に断る (掛け算があふれる大きさも)
+    for url in ["numlin/4294967296/4294967296/1", "numlin/100000/100000/1", "numlin/b/100000/100000/1", "100000x100000 1", "18446744073709551615x2 1"] {
+        assert!(matches!(try_parse_puzzle(url), Err(Error::TooLarge(..))), "{}", url);
+        assert_eq!(parse_puzzle(url), None, "{}", url);
+    }
+
+    assert!(matches!(Puzzle::try_new(usize::MAX, 2), Err(Error::TooLarge(..))));
+    assert_eq!(Puzzle::try_new(1024, MAX_CELLS/1024).map(|puzzle| puzzle.cells.len()), Ok(MAX_CELLS));
+    assert_eq!(Puzzle::try_new(1025, MAX_CELLS/1024), Err(Error::TooLarge(1025, MAX_CELLS/1024)));
+    assert_eq!(try_parse_puzzle("nonsense"), Err(Error::InvalidUrl));
+    assert_eq!(try_parse_puzzle("numlin/4/1/1gg1").unwrap(), parse_url("numlin/4/1/1gg1".to_string()).unwrap());
+    assert_eq!(parse_json(&format!("{{\"nodes\":{},\"edges\":[],\"pairs\":[]}}", MAX_CELLS+1)), None);
+    assert_eq!(cli(&["numlin/4294967296/4294967296/1"]).0, 1);
+}
+
+#[test]
+fn memory_limit() {
+    let puzzle = parse_url("http://pzv.jp/p.html?numlin/10/10/8t12g8l34j21zt76j45l3g67t5".to_string()).unwrap();
+    let limited = |max_memory| solve_numberlink(&puzzle, &SolverConfig { max_memory, ..SolverConfig::default() });
+
+    // 符号化しただけで上限を超えるなら探索を始めない
+    match limited(Some(1024)) {
+        Err(Error::MemoryLimit(stats)) => assert!(stats.memory > 1024 && stats.conflicts == 0, "{:?}", stats),
+        res => panic!("{:?}", res),
+    }
+
+    // 上限が十分なら普通に解ける
+    for max_memory in [None, Some(1 << 30)] {
+        check(&puzzle, &limited(max_memory).unwrap());
+    }
+
+    let url = "http://pzv.jp/p.html?numlin/10/10/8t12g8l34j21zt76j45l3g67t5";
+
+    assert_eq!(cli(&["--max-memory", "1K", url]).0, 3);
+    assert_eq!(cli(&["--max-memory", "1G", url]).0, 0);
+    assert_eq!(cli(&["--max-memory", "0", url]).0, 0);
+}
+
+#[test]
+fn sampled_solutions() {
+    let config = SolverConfig::default();
+
+    // 解が k 個ないときはすべて
+    let puzzle = parse_url("numlin/3/3/1ggggggg1".to_string()).unwrap();
+    let all = sample_solutions(&puzzle, &config, 100, 1).unwrap();
+
+    assert_eq!(all.len() as u128, Zdd::build(&puzzle, &config).unwrap().count());
+
+    // 空いた盤面では, 異なる seed から同じ解ばかりにはならない
+    let puzzle = parse_url("numlin/6/6/1j1zp".to_string()).unwrap();
+    let mut seen = BTreeSet::new();
+
+    for seed in 0..4 {
+        let samples = sample_solutions(&puzzle, &config, 5, seed).unwrap();
+
+        assert_eq!(samples.len(), 5);
+        assert_eq!(samples, sample_solutions(&puzzle, &config, 5, seed).unwrap());
+        assert_eq!(samples.iter().map(|sol| sol.iter().collect::<BTreeSet<_>>()).collect::<BTreeSet<_>>().len(), 5);
+
+        for sol in &samples {
+            assert_eq!(verify(&puzzle, &config.rules, sol), Ok(()));
+        }
+
+        seen.extend(samples.into_iter().map(|sol| sol.into_iter().collect::<BTreeSet<_>>()));
+    }
+
+    assert!(seen.len() > 10, "{}", seen.len());
+    assert_eq!(sample_solutions(&parse_url("numlin/2/2/1221".to_string()).unwrap(), &config, 3, 0), Ok(vec![]));
+}
+
+#[test]
+fn random_anytime() {
+    let mut rng = Lcg(383);
+
+    for _ in 0..150 {
+        let puzzle = random_puzzle(&mut rng);
+
+        if puzzle.cells.len() > 16 {
+            continue;
+        }
+
+        let best = sample_solutions(&puzzle, &SolverConfig::default(), usize::MAX, 0).unwrap().iter().map(|sol| count_turns(&puzzle, sol)).min();
+        let res = minimize_turns(&puzzle, &SolverConfig::default());
+
+        // 期限がなければ最良まで下げきる
+        match best {
+            Some(best) => {
+                let res = res.unwrap();
+
+                check(&puzzle, &res.sol);
+                assert_eq!((res.turns, count_turns(&puzzle, &res.sol), res.optimal), (best, best, true), "{:?}", puzzle);
+            },
+            None => assert_eq!(res, Err(Error::NoSolution)),
+        }
+
+        // 期限に届いたら途中の最良解 (最良と示せたときだけ optimal)
+        let config = SolverConfig { timeout: Some(Duration::from_micros(1+rng.next(2000) as u64)), ..SolverConfig::default() };
+
+        match minimize_turns(&puzzle, &config) {
+            Ok(res) => {
+                check(&puzzle, &res.sol);
+                assert!(res.turns >= best.unwrap() && (!res.optimal || res.turns == best.unwrap()), "{:?} {:?}", puzzle, res);
+            },
+            Err(Error::Timeout(_)) => {},
+            Err(e) => assert_eq!((e, best), (Error::NoSolution, None)),
+        }
+    }
+}
+
+#[test]
+fn random_checkpoint() {
+    let mut rng = Lcg(389);
+    let path = env::temp_dir().join(format!("numberlink_checkpoint_{}.txt", std::process::id()));
+
+    for _ in 0..100 {
+        let puzzle = random_puzzle(&mut rng);
+        let best = match minimize_turns(&puzzle, &SolverConfig::default()) {
+            Ok(best) => best,
+            Err(e) => {
+                assert_eq!(e, Error::NoSolution);
+                continue;
+            },
+        };
+
+        // 良い解が見つかるたびに書き出した checkpoint は読み戻すと同じ解
+        let mut saved = vec![];
+        let res = minimize_turns_from(&puzzle, &SolverConfig::default(), None, |improved| {
+            checkpoint::save(&path, &puzzle, improved).unwrap();
+            assert_eq!(checkpoint::load(&path, &puzzle).unwrap().as_ref(), Some(&improved.sol));
+            saved.push(improved.clone());
+        }).unwrap();
+
+        assert_eq!(res.turns, best.turns);
+        assert!(saved.windows(2).all(|w| w[0].turns > w[1].turns) && saved.last().map(|last| (&last.sol, last.turns)) == Some((&res.sol, res.turns)), "{:?}", saved);
+
+        // 最初 (一番悪い) の checkpoint から再開しても, 途中から始めない最適化と同じ最良に着く
+        checkpoint::save(&path, &puzzle, &saved[0]).unwrap();
+
+        let start = checkpoint::load(&path, &puzzle).unwrap().unwrap();
+        let resumed = minimize_turns_from(&puzzle, &SolverConfig::default(), Some(&start), |_| {}).unwrap();
+
+        check(&puzzle, &resumed.sol);
+        assert_eq!((resumed.turns, resumed.optimal), (best.turns, true), "{:?}", puzzle);
+
+        // 別の盤面の checkpoint は読まない
+        let other = Puzzle::new(puzzle.width+1, puzzle.height);
+
+        assert_eq!(checkpoint::load(&path, &other).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn all_optimal_solutions() {
+    let mut rng = Lcg(43);
+
+    for _ in 0..150 {
+        let mut puzzle = random_puzzle(&mut rng);
+
+        if puzzle.cells.len() > 12 {
+            continue;
+        }
+
+        for u in 0..puzzle.cells.len() {
+            puzzle.costs.insert(u, rng.next(2));
+        }
+
+        // 解をすべて並べて (sample_solutions は解が尽きるまで返す) 最良の値とその数を数える
+        for (objective, rules) in [(Objective::Turns, RuleSet::default()), (Objective::Cost, RuleSet { coverage: false, ..RuleSet::default() })] {
+            let config = SolverConfig { rules, ..SolverConfig::default() };
+            let value = |sol: &Sol| if objective == Objective::Cost { total_cost(&puzzle, sol) } else { count_turns(&puzzle, sol) };
+            let sols = sample_solutions(&puzzle, &config, usize::MAX, 0).unwrap();
+            let best = match sols.iter().map(value).min() {
+                Some(best) => best,
+                None => {
+                    assert_eq!(all_optimal(&puzzle, &config, objective, 100), Err(Error::NoSolution));
+                    continue;
+                },
+            };
+            let count = sols.iter().filter(|&sol| value(sol) == best).count();
+            let res = all_optimal(&puzzle, &config, objective, 100).unwrap();
+
+            assert!(res.best.optimal && res.complete);
+            assert_eq!((res.sols.len(), res.sols.iter().map(value).max()), (count, Some(best)), "{:?} {:?} {:?}", objective, puzzle.cells, puzzle.costs);
+            assert!(res.sols.iter().all(|sol| verify(&puzzle, &rules, sol).is_ok()));
+
+            if count > 1 {
+                let capped = all_optimal(&puzzle, &config, objective, 1).unwrap();
+
+                assert_eq!((capped.sols.len(), capped.complete), (1, false));
+            }
+        }
+    }
+}
+
+#[test]
+fn all_optimal_exit_codes() {
+    let sample = "numlin/12/12/1p3h9g3j4i2j5t5l87g6l6j2g7jbgbjal8g1czg9uahcp4";
+
+    // 最良の解が複数でも, N 個で打ち切っても成功
+    assert_eq!(cli(&["--objective", "turns", "--all-optimal", "10", "numlin/3/3/1ggggggg1"]).0, 0);
+    assert_eq!(cli(&["--objective", "turns", "--all-optimal", "1", "numlin/5/5/1zi1"]).0, 0);
+    assert_eq!(cli(&["--objective", "turns", "--all-optimal", "10", "numlin/2/2/1221"]).0, 2);
+    assert_eq!(cli(&["--objective", "turns", "--all-optimal", "10", "--timeout", "0.001", sample]).0, 3);
+}
+
+#[test]
+fn backend_selection() {
+    let small = parse_url("numlin/5/5/1zi1".to_string()).unwrap();
+    // cadical を選ぶのは medium 以上と予想される盤面から
+    let large = parse_url("numlin/20/20/1x12x23x34x45x56x67x78x89x9axabxbcxcdxdexefxf-10x-10-11x-11-12x-12-13x-13-14x-14".to_string()).unwrap();
+    let plain = SolverConfig::default();
+    let uncovered = SolverConfig { rules: RuleSet { coverage: false, ..RuleSet::default() }, ..SolverConfig::default() };
+    let tuned = SolverConfig { tuning: Tuning { vsids_decay: Some(0.8), ..Tuning::default() }, ..SolverConfig::default() };
+    let sat = if cfg!(feature = "cadical") { Backend::Cadical } else { Backend::Varisat };
+
+    // (盤面, 設定, 問い合わせ) ごとの選ばれる backend
+    for (puzzle, config, query, backend) in [
+        (&small, &plain, Query::Solve, Backend::Backtrack),
+        (&small, &plain, Query::Session, Backend::Varisat),
+        (&small, &plain, Query::Count, Backend::Zdd),
+        (&small, &uncovered, Query::Solve, Backend::Varisat),
+        (&small, &uncovered, Query::Count, Backend::Varisat),
+        (&large, &plain, Query::Solve, sat.clone()),
+        (&large, &plain, Query::Session, sat.clone()),
+        (&large, &plain, Query::Count, Backend::Zdd),
+        (&large, &uncovered, Query::Count, sat.clone()),
+        (&small, &tuned, Query::Solve, Backend::Varisat),
+        (&small, &tuned, Query::Count, Backend::Varisat),
+        (&large, &tuned, Query::Solve, Backend::Varisat),
+    ] {
+        assert_eq!(select(puzzle, config, query), backend, "{:?} {:?}", query, config.rules);
+    }
+
+    // 数え方によらず数は同じ
+    let mut rng = Lcg(83);
+
+    for _ in 0..100 {
+        let puzzle = random_puzzle(&mut rng);
+        let count = Zdd::build(&puzzle, &plain).unwrap().count();
+
+        assert_eq!(count_numberlink(&puzzle, &SolverConfig { backend: Backend::Auto, ..SolverConfig::default() }), Ok(count));
+        assert_eq!(count_numberlink(&puzzle, &plain), Ok(count), "{:?}", puzzle);
+    }
+
+    // count は 1 つだけなら "1 solution", ほかは "N solutions". ZDD で数えたときは節点の数も書く
+    let unique = "numlin/5/5/h123h2h4g3i1l4g";
+    let open = "numlin/3/3/1ggggggg1";
+    let many = count_numberlink(&parse_url(open.to_string()).unwrap(), &plain).unwrap();
+    let zdd = Zdd::build(&parse_url(unique.to_string()).unwrap(), &plain).unwrap();
+
+    assert!(many > 1);
+    assert_eq!(cli(&["count", "--backend", "varisat", unique, open, "numlin/2/2/1221"]), (0, format!("{}: 1 solution\n{}: {} solutions\nnumlin/2/2/1221: 0 solutions\n", unique, open, many)));
+    assert_eq!(cli(&["count", "--backend", "zdd", unique]), (0, format!("{}: 1 solution ({} ZDD nodes)\n", unique, zdd.size())));
+
+    // 線を引けないセルのある盤面も 0 と数え, どの backend でも同じ行になる
+    assert_eq!(count_numberlink(&parse_puzzle("1,#,1").unwrap(), &SolverConfig { backend: Backend::Zdd, ..SolverConfig::default() }), Ok(0));
+    assert_eq!(cli(&["count", "1,#,1"]), (0, "1,#,1: 0 solutions (0 ZDD nodes)\n".to_string()));
+    assert_eq!(cli(&["count", "--backend", "varisat", "1,#,1"]), (0, "1,#,1: 0 solutions\n".to_string()));
+    assert_eq!(cli(&["--backend", "zdd", "1,#,1"]).0, 2);
+}
+
+#[test]
+fn experiment_matrix() {
+    let puzzles = corpus(382);
+    let trials = matrix(&SolverConfig::default(), &[Encoding::Log, Encoding::Direct], &[Phase::Default, Phase::Corridors], 2);
+
+    assert_eq!(trials.len(), 8);
+    assert_eq!((trials[0].0.as_str(), trials[7].0.as_str()), ("log/default/seed=0", "direct/corridors/seed=1"));
+
+    // どの組み合わせでも既定の設定と同じ答えで, 解は条件を満たす
+    for (_, config) in &trials {
+        agrees(&puzzles, config);
+    }
+
+    // 全部の問題に答えが出て, 問題ごとに最速の組み合わせが 1 つ勝つ
+    let summaries = run(&puzzles, &trials);
+
+    assert!(summaries.iter().all(|summary| (summary.solved, summary.unsolved, summary.failed) == (puzzles.len(), 0, 0)), "{:?}", summaries);
+    assert_eq!(summaries.iter().map(|summary| summary.wins).sum::<usize>(), puzzles.len());
+
+    // 期限に掛かった問題は解けなかったと数え, 勝ちにはしない
+    let hard = parse_url("numlin/12/12/1p3h9g3j4i2j5t5l87g6l6j2g7jbgbjal8g1czg9uahcp4".to_string()).unwrap();
+    let limited = SolverConfig { timeout: Some(Duration::from_millis(1)), ..SolverConfig::default() };
+    let summaries = run(&[hard], &matrix(&limited, &[Encoding::Log], &[Phase::Default], 1));
+
+    assert_eq!((summaries[0].solved, summaries[0].unsolved, summaries[0].wins), (0, 1, 0));
+
+    // 見出しと組み合わせごとに 1 行
+    let (code, table) = cli(&["experiment", "--seeds", "2", "numlin/5/5/h123h2h4g3i1l4g", "numlin/2/2/1221"]);
+    let rows: Vec<Vec<&str>> = table.lines().map(|line| line.split_whitespace().collect()).collect();
+
+    assert_eq!((code, rows.len()), (0, 9), "{}", table);
+    assert_eq!(rows[0], ["config", "solved", "unsolved", "failed", "time", "conflicts", "wins"]);
+    assert!(rows[1..].iter().zip(&trials).all(|(row, (name, _))| row[..4] == [name.as_str(), "2", "0", "0"]), "{}", table);
+    assert_eq!(rows[1..].iter().map(|row| row[6].parse::<usize>().unwrap()).sum::<usize>(), 2);
+    assert_eq!(cli(&["--seeds", "2", "numlin/5/5/h123h2h4g3i1l4g"]).0, 1);
+}
+
+#[test]
+fn corridor_phases() {
+    let corridors = SolverConfig { phase: Phase::Corridors, ..SolverConfig::default() };
+
+    for encoding in [Encoding::Log, Encoding::Direct] {
+        agrees(&corpus(385), &SolverConfig { encoding, ..corridors.clone() });
+    }
+
+    // 1 本道の盤面では, 真から試す arc がちょうど解の arc になる
+    let puzzle = parse_url("numlin/4/1/1gg1".to_string()).unwrap();
+    let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+    let flipped = |config: &SolverConfig| flips(&puzzle, config).iter().enumerate().filter(|&(_, &flip)| flip).filter_map(|(x, _)| arc_of(&puzzle, x)).collect::<BTreeSet<_>>();
+
+    assert_eq!(flipped(&corridors), sol.iter().cloned().collect());
+    assert!(flips(&puzzle, &SolverConfig::default()).is_empty());
+    assert!(flips(&puzzle, &SolverConfig { rules: RuleSet { lengths: true, ..RuleSet::default() }, ..corridors.clone() }).is_empty());
+
+    let url = "numlin/5/5/h123h2h4g3i1l4g";
+
+    assert_eq!(cli(&["--phase", "corridors", "--format", "text", url]), cli(&["--format", "text", url]));
+    assert_eq!(cli(&["--phase", "corridors", "numlin/2/2/1221"]).0, 2);
+    assert_eq!(cli(&["--phase", "sideways", url]).0, 1);
+}
+
+#[test]
+fn varisat_tuning() {
+    let tuning = Tuning {
+        vsids_decay: Some(0.8),
+        clause_activity_decay: Some(0.99),
+        reduce_locals_interval: Some(2000),
+        reduce_mids_interval: Some(1000),
+        luby_restart_interval_scale: Some(16),
+    };
+    let tuned = SolverConfig { tuning, ..SolverConfig::default() };
+
+    for encoding in [Encoding::Log, Encoding::Direct] {
+        agrees(&corpus(386), &SolverConfig { encoding, ..tuned.clone() });
+    }
+
+    // 範囲外の値と, varisat 以外の backend への指定は断る
+    let puzzle = parse_url("numlin/5/5/h123h2h4g3i1l4g".to_string()).unwrap();
+    let invalid = SolverConfig { tuning: Tuning { vsids_decay: Some(2.0), ..Tuning::default() }, ..SolverConfig::default() };
+
+    assert!(matches!(solve_numberlink(&puzzle, &invalid), Err(Error::InvalidTuning(_))));
+    assert!(matches!(Session::new(&puzzle, &SolverConfig { backend: Backend::External("kissat -q".to_string()), ..tuned.clone() }), Err(Error::InvalidTuning(_))));
+
+    let url = "numlin/5/5/h123h2h4g3i1l4g";
+
+    assert_eq!(cli(&["--vsids-decay", "0.8", "--luby-restart-interval-scale", "16", "--format", "text", url]), cli(&["--format", "text", url]));
+    assert_eq!(cli(&["--reduce-locals-interval", "2000", "numlin/2/2/1221"]).0, 2);
+    assert_eq!(cli(&["--vsids-decay", "2", url]).0, 1);
+}
+
+#[test]
+fn warm_starts() {
+    let puzzles = corpus(387);
+    let solutions: Vec<Option<Sol>> = puzzles.iter().map(|puzzle| solve_numberlink(puzzle, &SolverConfig::default()).ok()).collect();
+
+    for (k, puzzle) in puzzles.iter().enumerate() {
+        // 数字が 2 組以上あれば, 最後の 1 組を外した盤面 (編集の途中) も
+        let mut boards = vec![puzzle.clone()];
+        let last = puzzle.cells.iter().cloned().max().unwrap_or(0);
+
+        if last > 1 {
+            let mut edited = puzzle.clone();
+
+            for num in edited.cells.iter_mut().filter(|num| **num == last) {
+                *num = 0;
+            }
+
+            boards.push(edited);
+        }
+
+        // 同じ盤面の解, 手を入れる前の解, 大きさも違う別の盤面の解のどれから始めても答えは変わらない
+        for start in vec![&solutions[k], &solutions[(k+1)%puzzles.len()]].into_iter().flatten() {
+            for encoding in [Encoding::Log, Encoding::Direct] {
+                let config = SolverConfig { encoding, warm_start: Some(start.clone()), ..SolverConfig::default() };
+
+                agrees(&boards, &config);
+            }
+        }
+    }
+
+    // 同じ盤面の解から始めればその解に戻り, 衝突は (コーパス全体で) 何もないところから解くより少ない
+    let conflicts = |puzzle: &Puzzle, warm_start: Option<Sol>| {
+        let config = SolverConfig { warm_start, timeout: Some(Duration::from_secs(600)), ..SolverConfig::default() };
+        let mut session = Session::new(puzzle, &config).unwrap();
+        let sol = session.solve().unwrap();
+
+        (sol, session.stats().conflicts)
+    };
+    let (mut cold, mut warm) = (0, 0);
+
+    for (puzzle, sol) in puzzles.iter().zip(&solutions) {
+        if let Some(sol) = sol {
+            let (again, n) = conflicts(puzzle, Some(sol.clone()));
+
+            assert_eq!(&again, sol, "{:?}", puzzle.cells);
+            warm += n;
+            cold += conflicts(puzzle, None).1;
+        }
+    }
+
+    assert!(warm < cold, "{} {}", warm, cold);
+}
+
+#[cfg(unix)]
+#[test]
+fn status_reports() {
+    let puzzle = parse_url("http://pzv.jp/p.html?numlin/10/10/8t12g8l34j21zt76j45l3g67t5".to_string()).unwrap();
+    let progress = Shared::new(Progress::default());
+    let config = SolverConfig { progress: Some(progress.clone()), ..SolverConfig::default() };
+
+    agrees(&corpus(390), &SolverConfig { progress: Some(Shared::new(Progress::default())), ..SolverConfig::default() });
+
+    // 探索を止めずに, 衝突の数と最良の上界を外から読める
+    let mut session = Session::new(&puzzle, &config).unwrap();
+
+    check(&puzzle, &session.solve().unwrap());
+    assert!(progress.conflicts() > 0);
+    assert_eq!((progress.conflicts(), progress.bound()), (session.stats().conflicts, None));
+
+    let best = minimize_turns(&puzzle, &config).unwrap();
+
+    assert_eq!(progress.bound(), Some(best.turns as u64));
+
+    // stdin を開けたまま待たせても, interval ごとに 1 行出す. SIGUSR1 を受ければその場で 1 行
+    let url = "numlin/5/5/h123h2h4g3i1l4g";
+    let status = |args: &[&str], signal: bool| {
+        let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_numberlink_solver"))
+            .args(args)
+            .arg("-")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        std::thread::sleep(Duration::from_millis(500));
+
+        if signal {
+            assert!(std::process::Command::new("kill").args(["-USR1", &child.id().to_string()]).status().unwrap().success());
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        std::io::Write::write_all(&mut child.stdin.take().unwrap(), format!("{}\n", url).as_bytes()).unwrap();
+
+        let output = child.wait_with_output().unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+
+        assert!(stderr.lines().all(|line| line.starts_with("status: ") && line.ends_with(" conflicts")), "{}", stderr);
+        (output.status.code().unwrap_or(-1), String::from_utf8(output.stdout).unwrap(), stderr.lines().count())
+    };
+    let plain = cli_input(&["--format", "text", "-"], &format!("{}\n", url));
+
+    let (code, stdout, lines) = status(&["--status-interval", "0.1", "--format", "text"], false);
+
+    assert_eq!((code, stdout), plain);
+    assert!(lines >= 3, "{}", lines);
+    assert_eq!(status(&["--format", "text"], true), (plain.0, plain.1.clone(), 1));
+    assert_eq!(status(&["--format", "text"], false), (plain.0, plain.1, 0));
+}
+
+#[cfg(unix)]
+#[test]
+fn compare_outcomes() {
+    let trials = matrix(&SolverConfig::default(), &[Encoding::Log, Encoding::Direct], &[Phase::Default], 1);
+
+    // 符号化どうしは一致し, どの答えも既定の設定と同じで, 解は条件を満たす
+    for puzzle in corpus(394) {
+        let outcomes = compare(&puzzle, &trials);
+        let expected = answer(&solve_numberlink(&puzzle, &SolverConfig::default()));
+
+        assert!(agree(&outcomes));
+        assert!(render_outcomes("p", &outcomes).starts_with("p: agree | log/default/seed=0 "));
+
+        for outcome in &outcomes {
+            assert_eq!(outcome.answer(), Some(expected), "{} {:?}", outcome.name, puzzle.cells);
+
+            if let Ok(sol) = &outcome.result {
+                check(&puzzle, sol);
+            }
+        }
+    }
+
+    // 解ける盤面を解けないと言う外部のソルバとは一致しない. 答えの出なかったものは一致の判定に入れない
+    let dir = env::temp_dir().join(format!("numberlink_compare_{}", std::process::id()));
+
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("liar.sh"), "echo 's UNSATISFIABLE'\nexit 20\n").unwrap();
+    std::fs::write(dir.join("crash.sh"), "exit 1\n").unwrap();
+
+    let liar = format!("sh {}", dir.join("liar.sh").display());
+    let crash = format!("sh {}", dir.join("crash.sh").display());
+    let puzzle = parse_url("numlin/5/5/h123h2h4g3i1l4g".to_string()).unwrap();
+    let with = |name: &str, command: &str| {
+        let mut trials = trials.clone();
+
+        trials.push((name.to_string(), SolverConfig { backend: Backend::External(command.to_string()), ..SolverConfig::default() }));
+        compare(&puzzle, &trials)
+    };
+
+    let outcomes = with("liar", &liar);
+
+    assert!(!agree(&outcomes));
+    assert!(render_outcomes("p", &outcomes).starts_with("p: DISAGREE |") && render_outcomes("p", &outcomes).ends_with(" unsat"));
+
+    let outcomes = with("crash", &crash);
+
+    assert_eq!(outcomes.last().unwrap().answer(), None);
+    assert!(agree(&outcomes));
+
+    // 小さい盤面では SAT を使わない探索も並べるので, 嘘をつくソルバだけで比べても食い違いが分かる
+    let (code, line) = cli(&["compare", "--solver-cmd", &liar, "numlin/5/5/h123h2h4g3i1l4g"]);
+
+    assert_eq!(code, 1);
+    assert!(line.starts_with("numlin/5/5/h123h2h4g3i1l4g: DISAGREE |") && line.contains(" | backtrack "), "{}", line);
+
+    let (code, lines) = cli(&["compare", "numlin/5/5/h123h2h4g3i1l4g", "numlin/2/2/1221"]);
+
+    assert_eq!(code, 0);
+    assert_eq!(lines.lines().map(|line| line.split(" | ").next().unwrap()).collect::<Vec<_>>(), ["numlin/5/5/h123h2h4g3i1l4g: agree", "numlin/2/2/1221: agree"]);
+    assert!(lines.lines().all(|line| line.split(" | ").count() == 4), "{}", lines);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "cadical")]
+#[test]
+fn ipasir_library() {
+    // cadical の crate が build した CaDiCaL の静的ライブラリ (IPASIR の関数を含む) を共有ライブラリにして読み込む
+    let build = env::current_exe().unwrap().parent().unwrap().with_file_name("build");
+    let archive = std::fs::read_dir(&build).unwrap().map(|entry| entry.unwrap().path().join("out/libccadical.a")).find(|path| path.exists()).unwrap();
+    let lib = env::temp_dir().join(format!("libnumberlink_ipasir_{}.so", std::process::id()));
+    let status = std::process::Command::new("c++")
+        .args(["-shared", "-o"])
+        .arg(&lib)
+        .arg("-Wl,--whole-archive")
+        .arg(&archive)
+        .arg("-Wl,--no-whole-archive")
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+
+    let ipasir = SolverConfig { backend: Backend::Ipasir(lib.clone()), ..SolverConfig::default() };
+
+    agrees(&corpus(398), &ipasir);
+
+    // 期限は terminate で伝わる
+    let hard = parse_url("numlin/12/12/1p3h9g3j4i2j5t5l87g6l6j2g7jbgbjal8g1czg9uahcp4".to_string()).unwrap();
+
+    assert!(matches!(solve_numberlink(&hard, &SolverConfig { timeout: Some(Duration::from_millis(1)), ..ipasir.clone() }), Err(Error::Timeout(_))));
+    assert!(matches!(solve_numberlink(&hard, &SolverConfig { backend: Backend::Ipasir(build.join("missing.so")), ..SolverConfig::default() }), Err(Error::BackendLoad(_))));
+
+    let url = "numlin/5/5/h123h2h4g3i1l4g";
+    let lib = lib.to_str().unwrap();
+
+    assert_eq!(cli(&["--solver-lib", lib, "--format", "text", url]), cli(&["--format", "text", url]));
+    assert_eq!(cli(&["--solver-lib", lib, "numlin/2/2/1221"]).0, 2);
+    assert_eq!(cli(&["--solver-lib", "/nonexistent/libipasir.so", url]).0, 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn external_solver() {
+    let dir = env::temp_dir().join(format!("numberlink_solver_cmd_{}", std::process::id()));
+
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let script = |name: &str, body: &str| {
+        let path = dir.join(name);
+
+        std::fs::write(&path, body).unwrap();
+        format!("sh {}", path.display())
+    };
+    let external = |command: String| SolverConfig { backend: Backend::External(command), ..SolverConfig::default() };
+
+    // varisat で解いたモデルを決まった出力として返すソルバ. 競技会のソルバと同じく 10 / 20 で終わり, v 行は何行かに分ける
+    for puzzle in corpus(399) {
+        let mut solver = Solver::new();
+
+        solver.add_formula(&encode_formula(&puzzle, &SolverConfig::default()).unwrap());
+
+        let canned = match solver.solve().unwrap() {
+            true => {
+                let lits: Vec<String> = solver.model().unwrap().iter().map(|lit| lit.to_dimacs().to_string()).collect();
+                let lines: String = lits.chunks(10).map(|chunk| format!("echo 'v {}'\n", chunk.join(" "))).collect();
+
+                format!("echo 'c canned model'\necho 's SATISFIABLE'\n{}echo 'v 0'\nexit 10\n", lines)
+            },
+            false => "echo 's UNSATISFIABLE'\nexit 20\n".to_string(),
+        };
+
+        agrees(&[puzzle], &external(script("canned.sh", &canned)));
+    }
+
+    // s 行がない, 答えを出せなかった, v 行が読めない, 起動できないときは backend の失敗
+    let puzzle = parse_url("numlin/5/5/h123h2h4g3i1l4g".to_string()).unwrap();
+    let failure = |command: String| match solve_numberlink(&puzzle, &external(command)) {
+        Err(Error::BackendFailed(msg)) => msg,
+        res => panic!("{:?}", res),
+    };
+
+    assert!(failure(script("crash.sh", "echo 'c out of memory'\nexit 1\n")).contains("printed no `s` line"));
+    assert_eq!(failure(script("unknown.sh", "echo 's UNKNOWN'\nexit 0\n")), "solver gave up: s UNKNOWN");
+    assert_eq!(failure(script("garbage.sh", "echo 's SATISFIABLE'\necho 'v 1 x 0'\nexit 10\n")), "invalid value line: v 1 x 0");
+    assert!(failure("/nonexistent/kissat -q".to_string()).starts_with("/nonexistent/kissat: "));
+
+    // 走っている間も期限を見て止める
+    let slow = external(script("slow.sh", "exec sleep 10\n"));
+
+    assert!(matches!(solve_numberlink(&puzzle, &SolverConfig { timeout: Some(Duration::from_millis(100)), ..slow }), Err(Error::Timeout(_))));
+
+    let unsat = script("unsat.sh", "echo 's UNSATISFIABLE'\nexit 20\n");
+    let crash = script("crash.sh", "exit 1\n");
+
+    assert_eq!(cli(&["--solver-cmd", &unsat, "numlin/5/5/h123h2h4g3i1l4g"]).0, 2);
+    assert_eq!(cli(&["--solver-cmd", &crash, "numlin/5/5/h123h2h4g3i1l4g"]).0, 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "minisat")]
+#[test]
+fn minisat_backend() {
+    let minisat = SolverConfig { backend: Backend::Minisat, ..SolverConfig::default() };
+
+    agrees(&corpus(400), &minisat);
+
+    for encoding in [Encoding::Log, Encoding::Direct] {
+        agrees(&corpus(401), &SolverConfig { encoding, ..minisat.clone() });
+    }
+
+    let hard = parse_url("numlin/12/12/1p3h9g3j4i2j5t5l87g6l6j2g7jbgbjal8g1czg9uahcp4".to_string()).unwrap();
+
+    assert!(matches!(solve_numberlink(&hard, &SolverConfig { timeout: Some(Duration::from_millis(1)), ..minisat }), Err(Error::Timeout(_))));
+    assert_eq!(cli(&["--backend", "minisat", "numlin/2/2/1221"]).0, 2);
+}
+
+#[cfg(feature = "ilp")]
+#[test]
+fn ilp_backend() {
+    // microlp では 10x10 の盤面でも終わらないので, 小さい盤面だけ
+    let puzzles: Vec<Puzzle> = corpus(403).into_iter().filter(|puzzle| puzzle.cells.len() <= 25).collect();
+    let ilp = SolverConfig { backend: Backend::Ilp, ..SolverConfig::default() };
+
+    agrees(&puzzles, &ilp);
+
+    // 曲がる回数を目的関数にしたときの最小値は, SAT で上界を下げていったときと同じ
+    for puzzle in &puzzles {
+        match minimize_turns(puzzle, &SolverConfig::default()) {
+            Ok(best) => {
+                let res = minimize_turns(puzzle, &ilp).unwrap();
+
+                assert!(best.optimal && res.optimal);
+                assert_eq!((res.turns, count_turns(puzzle, &res.sol)), (best.turns, best.turns), "{:?}", puzzle.cells);
+                check(puzzle, &res.sol);
+            },
+            Err(e) => assert_eq!(minimize_turns(puzzle, &ilp).err(), Some(e)),
+        }
+    }
+
+    assert_eq!(cli(&["--backend", "ilp", "numlin/2/2/1221"]).0, 2);
+}
+
+#[test]
+fn template_cache() {
+    let config = SolverConfig::default();
+    let small = parse_url("numlin/5/5/1zi1".to_string()).unwrap();
+    let mut large = Puzzle::new(31, 31);
+
+    // 小さい盤面の節は使い回し, 大きい盤面の節は持たずに符号化のたびに作って流す
+    assert!(Shared::ptr_eq(&template(&small, &config), &template(&small, &config)));
+    assert!(!template(&small, &config).parts.is_empty());
+    assert!(template(&large, &config).parts.is_empty());
+
+    large.cells[0] = 1;
+    large.cells[31*31-1] = 1;
+
+    for puzzle in [&small, &large] {
+        let formula = encode_formula(puzzle, &config).unwrap();
+        let mut tagged = CnfFormula::new();
+        let mut tags = vec![];
+
+        encode_tagged(puzzle, &config, &mut tagged, &mut tags).unwrap();
+        assert_eq!(tagged.iter().collect::<Vec<_>>(), formula.iter().collect::<Vec<_>>());
+        assert_eq!(tags.len(), formula.len());
+    }
+
+    assert!(solve_numberlink(&large, &SolverConfig { rules: RuleSet { coverage: false, ..RuleSet::default() }, ..config }).is_ok());
+}
+
+#[test]
+fn shortened_detours() {
+    let rules = RuleSet { coverage: false, ..RuleSet::default() };
+    let config = SolverConfig { rules, ..SolverConfig::default() };
+    let puzzle = parse_puzzle("1,.,.,1/.,.,.,./.,.,.,./.,.,.,.").unwrap();
+
+    // 盤面全体を回る道と, 離れた空白の閉路は, まっすぐな道だけになる
+    let snake = [(0, 0), (1, 0), (1, 1), (0, 1), (0, 2), (1, 2), (1, 3), (0, 3)];
+    let square = [(2, 1), (2, 2), (3, 2), (3, 1), (2, 1)];
+    let sol: Sol = snake.windows(2).chain(square.windows(2)).map(|w| (w[0], w[1])).collect();
+
+    assert_eq!(verify(&puzzle, &rules, &sol), Ok(()));
+    assert_eq!(shorten(&puzzle, &config, &sol), vec![((0, 0), (0, 1)), ((0, 1), (0, 2)), ((0, 2), (0, 3))]);
+
+    // 全部を覆う盤面ではそのまま. 通るセルを決めた制約があればそこは残す
+    assert_eq!(shorten(&puzzle, &SolverConfig::default(), &sol), sol);
+
+    let required = SolverConfig { constraints: parse_constraints("require cell (1,3)").unwrap(), ..config.clone() };
+
+    assert_eq!(shorten(&puzzle, &required, &sol), vec![((0, 0), (0, 1)), ((0, 1), (0, 2)), ((0, 2), (1, 2)), ((1, 2), (1, 3)), ((1, 3), (0, 3))]);
+
+    // 解いた答えには, 道の離れた 2 つのセルが隣り合うところ (近道) がない
+    let mut rng = Lcg(47);
+
+    for _ in 0..40 {
+        let puzzle = random_puzzle(&mut rng);
+        let sol = match solve_numberlink(&puzzle, &config) {
+            Ok(sol) => sol,
+            Err(_) => continue,
+        };
+
+        assert_eq!(verify(&puzzle, &rules, &sol), Ok(()));
+
+        for (_, path) in paths(&puzzle, &sol) {
+            for i in 0..path.len() {
+                for j in i+2..path.len() {
+                    assert!(puzzle.direction(path[i], path[j]).is_none(), "{:?} {:?}", puzzle.cells, path);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn portfolio_detours() {
+    // 並列に解いても, 勝った解の回り道は 1 つで解いたときと同じく縮める. 最短の道が 1 本だけの盤面で比べる
+    for url in ["numlin/6/6/1j1zp", "numlin/6/6/1zo1j"] {
+        let single = cli(&["--coverage", "false", "--format", "json", "-j", "1", url]);
+
+        for _ in 0..4 {
+            assert_eq!(cli(&["--coverage", "false", "--format", "json", "-j", "4", url]), single, "{}", url);
+            assert_eq!(cli(&["--coverage", "false", "--format", "json", "-j", "4", "--deterministic", "true", url]), single, "{}", url);
+        }
+    }
+}
+
+#[test]
+fn tagged_families() {
+    let mut rng = Lcg(53);
+
+    // 出どころを書き留めても節は encode と同じで, 節ごとに 1 つずつ出どころがある
+    for _ in 0..20 {
+        let puzzle = random_puzzle(&mut rng);
+
+        for encoding in [Encoding::Log, Encoding::Direct] {
+            let config = SolverConfig { encoding, rules: RuleSet { coverage: rng.next(2) == 0, ..RuleSet::default() }, ..SolverConfig::default() };
+            let mut formula = CnfFormula::new();
+            let mut tags = vec![];
+
+            encode_tagged(&puzzle, &config, &mut formula, &mut tags).unwrap();
+
+            let plain = encode_formula(&puzzle, &config).unwrap();
+            let stats = family_stats(&formula, &tags);
+
+            assert_eq!(formula.iter().collect::<Vec<_>>(), plain.iter().collect::<Vec<_>>());
+            assert_eq!((tags.len(), stats.iter().map(|stat| stat.clauses).sum::<usize>()), (formula.len(), formula.len()));
+            assert_eq!(stats.iter().find(|stat| stat.family == Family::Clue).map(|stat| stat.cells), Some(puzzle.cells.iter().filter(|&&num| num > 0).count()));
+            assert_eq!(stats.iter().any(|stat| stat.family == Family::Domain), encoding == Encoding::Direct);
+
+            // 解けない盤面だけに矛盾の組があり, その組の節だけでも解けない
+            let core = unsat_core(&puzzle, &config).unwrap();
+
+            assert_eq!(core.is_some(), solve_numberlink(&puzzle, &config).is_err(), "{:?}", puzzle.cells);
+
+            if let Some(core) = core {
+                let mut solver = Solver::new();
+
+                solver.add_formula(&CnfFormula::from(formula.iter().zip(&tags).filter(|(_, tag)| core.contains(tag)).map(|(clause, _)| clause.to_vec())));
+
+                assert!(!core.is_empty() && !solver.solve().unwrap());
+            }
+        }
+    }
+
+    let puzzle = parse_puzzle("1,2/2,1").unwrap();
+    let core = unsat_core(&puzzle, &SolverConfig::default()).unwrap().unwrap();
+    let mut formula = CnfFormula::new();
+    let mut tags = vec![];
+
+    encode_tagged(&puzzle, &SolverConfig::default(), &mut formula, &mut tags).unwrap();
+
+    let view = render_families(&puzzle, &family_stats(&formula, &tags), Some(&core));
+
+    assert!(view.starts_with("family") && view.contains("label (12)") && view.contains(&format!("core: {} groups", core.len())), "{}", view);
+}
+
+#[test]
+fn estimated_families() {
+    // 見積もりの種類ごとに, 実際に符号化した式でそれに当たる出どころ
+    let families = |name: &str| match name {
+        "number domain" => vec![Family::Domain],
+        "(12) arc implies equal numbers" => vec![Family::Label],
+        "(2) no arc in both directions" => vec![Family::Opposite],
+        "at most one out/in" => vec![Family::AtMostOne],
+        "(11) clues" => vec![Family::Clue],
+        "(3)-(9) roles" => vec![Family::Start, Family::End, Family::Blank],
+        "walls and holes" => vec![Family::Blocked],
+        _ => panic!("{}", name),
+    };
+    let mut rng = Lcg(388);
+    let mut puzzles: Vec<Puzzle> = ["numlin/5/5/h123h2h4g3i1l4g", "http://pzv.jp/p.html?numlin/10/10/8t12g8l34j21zt76j45l3g67t5", "numlin/3/3/1ggggggg1", "numlin/2/2/1221"].iter().map(|url| parse_url(url.to_string()).unwrap()).collect();
+
+    puzzles.extend((0..20).map(|_| random_puzzle(&mut rng)));
+
+    // 式を作らずに, 変数の数と出どころごとの節とリテラルの数を当てる
+    for puzzle in &puzzles {
+        for encoding in [Encoding::Log, Encoding::Direct] {
+            let config = SolverConfig { encoding, ..SolverConfig::default() };
+            let mut formula = CnfFormula::new();
+            let mut tags = vec![];
+
+            let vars = encode_tagged(puzzle, &config, &mut formula, &mut tags).unwrap();
+            let stats = family_stats(&formula, &tags);
+            let estimated = estimate(puzzle, &config);
+
+            assert_eq!((estimated.vars, estimated.clauses()), (vars as u64, formula.len() as u64), "{:?} {:?}", puzzle.cells, encoding);
+            assert_eq!(estimated.lits(), formula.iter().map(|clause| clause.len() as u64).sum::<u64>(), "{:?} {:?}", puzzle.cells, encoding);
+
+            for family in &estimated.families {
+                let actual = stats.iter().filter(|stat| families(family.name).contains(&stat.family));
+
+                assert_eq!(
+                    (family.clauses, family.lits),
+                    actual.fold((0, 0), |(clauses, lits), stat| (clauses+stat.clauses as u64, lits+stat.literals as u64)),
+                    "{} {:?} {:?}", family.name, puzzle.cells, encoding,
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn solver_events() {
+    let puzzle = parse_url("http://pzv.jp/p.html?numlin/10/10/8t12g8l34j21zt76j45l3g67t5".to_string()).unwrap();
+    let seen = Shared::new(Mutex::new(vec![]));
+    let mut config = SolverConfig::default();
+
+    config.events.subscribe({
+        let seen = seen.clone();
+
+        move |event: &Event| seen.lock().unwrap().push(event.clone())
+    });
+
+    let mut session = Session::new(&puzzle, &config).unwrap();
+    let sol = session.solve().unwrap();
+    let conflicts = session.stats().conflicts;
+    let seen = seen.lock().unwrap();
+
+    // 衝突は 1 つずつ通算で, 解はそのまま届く
+    let counted: Vec<u64> = seen.iter().filter_map(|event| match *event { Event::Conflict { conflicts } => Some(conflicts), _ => None }).collect();
+
+    assert!(conflicts > 256);
+    assert_eq!(counted, (1..=conflicts).collect::<Vec<u64>>());
+    assert_eq!(seen.last(), Some(&Event::Model { sol: sol.clone() }));
+
+    // 決まった arc は解でもそのまま
+    let fixed: Vec<(P, P, bool)> = seen.iter().filter_map(|event| match *event {
+        Event::Fixed { var, value } => arc_of(&puzzle, var).map(|(p, q)| (p, q, value)),
+        _ => None,
+    }).collect();
+
+    assert!(!fixed.is_empty());
+    assert!(fixed.iter().all(|&(p, q, value)| sol.contains(&(p, q)) == value), "{:?}", fixed);
+}
+
+#[cfg(feature = "minisat")]
+#[test]
+fn minisat_restarts() {
+    let puzzle = parse_url("http://pzv.jp/p.html?numlin/10/10/8t12g8l34j21zt76j45l3g67t5".to_string()).unwrap();
+    let seen = Shared::new(Mutex::new(vec![]));
+    let mut config = SolverConfig { backend: Backend::Minisat, ..SolverConfig::default() };
+
+    config.events.subscribe({
+        let seen = seen.clone();
+
+        move |event: &Event| seen.lock().unwrap().push(event.clone())
+    });
+
+    let mut session = Session::new(&puzzle, &config).unwrap();
+    let sol = session.solve().unwrap();
+    let seen = seen.lock().unwrap();
+
+    // minisat は再始動を 1 つずつ通算で知らせる. 再始動のあとには必ず衝突がある
+    let restarts: Vec<u64> = seen.iter().filter_map(|event| match *event { Event::Restart { restarts } => Some(restarts), _ => None }).collect();
+    let conflicts = seen.iter().filter(|event| matches!(event, Event::Conflict { .. })).count() as u64;
+
+    assert!(!restarts.is_empty(), "{} conflicts", conflicts);
+    assert_eq!(restarts, (1..=restarts.len() as u64).collect::<Vec<u64>>());
+    assert!(conflicts > restarts.len() as u64);
+    assert_eq!(seen.last(), Some(&Event::Model { sol: sol.clone() }));
+    check(&puzzle, &sol);
+}
+
+#[test]
+fn replayed_proofs() {
+    let path = env::temp_dir().join(format!("numberlink_proof_{}", std::process::id()));
+
+    for url in ["http://pzv.jp/p.html?numlin/10/10/8t12g8l34j21zt76j45l3g67t5", "numlin/12/12/1p3h9g3j4i2j5t5l87g6l6j2g7jbgbjal8g1czg9uahcp4", "numlin/3/3/1g2ggg2g1"] {
+        let puzzle = parse_url(url.to_string()).unwrap();
+        let formula = encode_formula(&puzzle, &SolverConfig::default()).unwrap();
+
+        // 同じ探索を varisat 形式と DRAT (節の追加が 1 行ずつ) で書き出す
+        let proofs: Vec<Vec<u8>> = [ProofFormat::Varisat, ProofFormat::Drat].iter().map(|&format| {
+            let mut solver = Solver::new();
+
+            solver.write_proof(std::fs::File::create(&path).unwrap(), format);
+            solver.add_formula(&formula);
+            solver.solve().unwrap();
+            solver.close_proof().unwrap();
+            drop(solver);
+
+            std::fs::read(&path).unwrap()
+        }).collect();
+
+        // DRAT の追加の行は学習節と単位節. 長さ 1 の行が値の決まった変数 (DRAT の変数は 1 から)
+        let added: Vec<Vec<i64>> = String::from_utf8(proofs[1].clone()).unwrap().lines()
+            .filter(|line| !line.starts_with('d'))
+            .map(|line| line.split_whitespace().map(|lit| lit.parse().unwrap()).filter(|&lit| lit != 0).collect())
+            .collect();
+        let learned = added.iter().filter(|clause| clause.len() > 1).count() as u64;
+        let fixed: BTreeSet<(usize, bool)> = added.iter().filter(|clause| clause.len() == 1).map(|clause| (clause[0].unsigned_abs() as usize-1, clause[0] > 0)).collect();
+
+        // 数の途中で切れた書き込みでも同じ出来事になる
+        let replays: Vec<(u64, Vec<Event>)> = [proofs[0].len(), 3, 1].iter().map(|&chunk| {
+            let seen = Shared::new(Mutex::new(vec![]));
+            let mut events = Events::default();
+
+            events.subscribe({
+                let seen = seen.clone();
+
+                move |event: &Event| seen.lock().unwrap().push(event.clone())
+            });
+
+            let conflicts = replay_proof(ChunkReader(&proofs[0], chunk), &events).unwrap();
+            let seen = seen.lock().unwrap().clone();
+
+            (conflicts, seen)
+        }).collect();
+
+        assert!(replays.iter().all(|replay| *replay == replays[0]));
+
+        let (conflicts, seen) = &replays[0];
+        let counted: Vec<u64> = seen.iter().filter_map(|event| match *event { Event::Conflict { conflicts } => Some(conflicts), _ => None }).collect();
+        let units: BTreeSet<(usize, bool)> = seen.iter().filter_map(|event| match *event { Event::Fixed { var, value } => Some((var, value)), _ => None }).collect();
+
+        // 学習した単位節も 1 つの衝突なので, 衝突の数は長さ 2 以上の学習節と全部の追加の間
+        assert!(learned <= *conflicts && *conflicts <= added.len() as u64, "{}: {} not in {}..={}", url, conflicts, learned, added.len());
+        assert_eq!(counted, (1..=*conflicts).collect::<Vec<u64>>());
+        assert_eq!(units, fixed, "{}", url);
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn live_frames() {
+    let puzzle = parse_puzzle("1,.,./.,.,./.,.,1").unwrap();
+    let mut state = LiveState::default();
+
+    // 決まった arc の向きの罫線, 通らないと決まったセルは空白
+    state.fixed.insert(((0, 0), (0, 1)), true);
+    state.fixed.insert(((0, 1), (0, 2)), true);
+    state.fixed.insert(((0, 2), (1, 2)), true);
+
+    for (p, q) in [((1, 0), (1, 1)), ((1, 0), (2, 0)), ((1, 0), (0, 0)), ((0, 0), (1, 0)), ((1, 1), (1, 0)), ((2, 0), (1, 0))] {
+        state.fixed.insert((p, q), false);
+    }
+
+    state.conflicts = 3;
+
+    assert_eq!(render_live(&puzzle, &state), "1 ─ ┐\n  . ╵\n. . 1\nfixed 9/24 arcs, 3 conflicts\n");
+
+    // 解が届いたら解の線を描く
+    let mut config = SolverConfig::default();
+    let live = subscribe_live(&puzzle, &mut config.events);
+    let sol = solve_numberlink(&puzzle, &config).unwrap();
+    let state = live.lock().unwrap();
+
+    assert_eq!(state.sol, Some(sol.clone()));
+    assert!(state.fixed.iter().all(|(arc, &value)| sol.contains(arc) == value));
+    assert!(render_live(&puzzle, &state).ends_with(&format!("solved, {} conflicts\n", state.conflicts)));
+    assert!(!render_live(&puzzle, &state).contains('.'));
+}
+
+#[test]
+fn greedy_routing() {
+    // 3 回現れる数字はつながらない方に数える
+    let puzzle = parse_puzzle("1,.,.,1/2,.,2,./3,3,.,3").unwrap();
+    let routing = route_greedy(&puzzle, 0, ROUNDS);
+
+    assert_eq!(routing.sol, vec![((0, 0), (0, 1)), ((0, 1), (0, 2)), ((0, 2), (0, 3)), ((1, 0), (1, 1)), ((1, 1), (1, 2))]);
+    assert_eq!((routing.connected.clone(), routing.unconnected.clone()), (vec![1, 2], vec![3]));
+    assert_eq!(render_routing(&routing), "connected 2 of 3 pairs (1, 2); unconnected: 3");
+
+    // つながった組の道は互いに重ならず同じ数字どうしを結び, 全部つながれば線の通らない空白を許した解になる
+    let rules = RuleSet { coverage: false, ..RuleSet::default() };
+    let mut rng = Lcg(48);
+
+    for _ in 0..200 {
+        let puzzle = random_puzzle(&mut rng);
+        let seed = rng.next(4) as u64;
+        let routing = route_greedy(&puzzle, seed, ROUNDS);
+        let found: Vec<(usize, Vec<P>)> = paths(&puzzle, &routing.sol).into_iter().filter(|(_, path)| path.len() > 1).collect();
+        let mut cells = BTreeSet::new();
+
+        assert_eq!(route_greedy(&puzzle, seed, ROUNDS), routing);
+        assert_eq!(found.iter().map(|&(num, _)| num).collect::<Vec<usize>>(), routing.connected);
+
+        for (num, path) in &found {
+            assert_eq!(puzzle.get(*path.last().unwrap()), *num, "{:?} {:?}", puzzle.cells, path);
+            assert!(path.iter().all(|&p| cells.insert(p)), "{:?} {:?}", puzzle.cells, routing.sol);
+        }
+
+        let mut nums = routing.connected.iter().chain(&routing.unconnected).cloned().collect::<Vec<usize>>();
+
+        nums.sort_unstable();
+        nums.dedup();
+
+        assert_eq!(nums.len(), routing.connected.len()+routing.unconnected.len());
+        assert!(nums.iter().all(|num| puzzle.cells.contains(num)));
+
+        if routing.unconnected.is_empty() {
+            assert_eq!(verify(&puzzle, &rules, &routing.sol), Ok(()), "{:?}", puzzle.cells);
+        }
+    }
+}
+
+#[test]
+fn result_cache() {
+    let dir = env::temp_dir().join(format!("numberlink_cache_{}", std::process::id()));
+    let rules = RuleSet::default();
+    let cache = ResultCache::open(&dir, &rules).unwrap();
+    let mut rng = Lcg(49);
+
+    // 覚えた答えは, 回して裏返して数字を振り直した盤面でもそのまま答えになる
+    for _ in 0..30 {
+        let puzzle = random_puzzle(&mut rng);
+        let res = solve_numberlink(&puzzle, &SolverConfig::default());
+
+        cache.put(&puzzle, &res).unwrap();
+
+        for &t in &Transform::ALL {
+            let variant = match transform(&puzzle, t) {
+                Some(variant) => renumber(&variant),
+                None => continue,
+            };
+
+            match cache.get(&variant) {
+                Some(Ok(sol)) => assert_eq!(verify(&variant, &rules, &sol), Ok(()), "{:?} {}", puzzle.cells, t),
+                Some(Err(e)) => assert_eq!((e, res.is_err()), (Error::NoSolution, true)),
+                None => panic!("{:?} {} is not cached", puzzle.cells, t),
+            }
+        }
+    }
+
+    // 向きを変えなければ答えも同じ. 時間切れは覚えず, 規則が違えば別の答え
+    let puzzle = parse_puzzle("1,.,2/1,.,2").unwrap();
+    let sol = solve_numberlink(&puzzle, &SolverConfig::default()).unwrap();
+
+    cache.put(&puzzle, &Ok(sol.clone())).unwrap();
+
+    assert_eq!(cache.get(&puzzle), Some(Ok(sol)));
+
+    let fresh = parse_puzzle("1,.,.,2/1,.,.,2").unwrap();
+
+    cache.put(&fresh, &Err(Error::Timeout(Stats::default()))).unwrap();
+
+    assert_eq!(cache.get(&fresh), None);
+    assert_eq!(ResultCache::open(&dir, &RuleSet { coverage: false, ..rules }).unwrap().get(&puzzle), None);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn net_routing() {
+    let config = SolverConfig::default();
+    let net = |name: &str, terminals: &[P]| Net { name: name.to_string(), terminals: terminals.to_vec() };
+    // a は障害物の切れ目 (3,2) を通って回り込むしかない. b はまっすぐ結べる
+    let mut problem = RouteProblem {
+        width: 5,
+        height: 4,
+        obstacles: vec![(0, 2), (1, 2), (2, 2)],
+        nets: vec![net("a", &[(0, 0), (0, 4)]), net("b", &[(1, 3), (2, 3)])],
+        objective: RouteObjective::Length,
+    };
+    let routing = route_nets(&problem, &config).unwrap();
+
+    assert_eq!(routing.nets[1], RoutedNet { name: "b".to_string(), paths: vec![vec![(1, 3), (2, 3)]] });
+    assert_eq!(routing.nets[0].paths.len(), 1);
+    assert!(routing.nets[0].paths[0].contains(&(3, 2)));
+    assert_eq!((routing.length, routing.optimal), (11+2, true));
+
+    // 曲がる回数を減らす: a は盤面の縁を回って 2 回だけ曲がる
+    problem.objective = RouteObjective::Bends;
+
+    let routing = route_nets(&problem, &config).unwrap();
+
+    assert_eq!((routing.bends, routing.optimal), (2, true));
+    assert!(routing.nets[0].paths[0].contains(&(3, 4)));
+
+    // 3 つの端子は木でつなぐ (根から葉への道ごと)
+    problem.nets = vec![net("c", &[(3, 0), (3, 4), (1, 3)])];
+    problem.objective = RouteObjective::Length;
+
+    let routing = route_nets(&problem, &config).unwrap();
+    let cells: BTreeSet<P> = routing.nets[0].paths.iter().flatten().cloned().collect();
+
+    assert_eq!(routing.nets[0].paths.len(), 2);
+    assert!([(3, 0), (3, 4), (1, 3)].iter().all(|p| cells.contains(p)));
+    assert_eq!((routing.length, cells.len()), (7, 7));
+
+    // 端子が障害物の上か 1 つだけなら盤面の誤り. 結べなければ解なし
+    problem.nets = vec![net("d", &[(0, 2), (0, 0)])];
+
+    assert_eq!(route_nets(&problem, &config), Err(Error::InvalidField));
+
+    problem.nets = vec![net("d", &[(0, 0)])];
+
+    assert_eq!(route_nets(&problem, &config), Err(Error::InvalidField));
+
+    problem.obstacles.push((3, 2));
+    problem.nets = vec![net("a", &[(0, 0), (0, 4)])];
+
+    assert_eq!(route_nets(&problem, &config), Err(Error::NoSolution));
+
+    // 長さの最小は, ネットごとに他を無視した最短路の長さの合計以上で, 道は互いに重ならない
+    let mut rng = Lcg(50);
+
+    for _ in 0..40 {
+        let puzzle = random_puzzle(&mut rng);
+        let nets: Vec<Net> = (1..=puzzle.cells.iter().cloned().max().unwrap()).map(|num| {
+            net(&num.to_string(), &(0..puzzle.cells.len()).filter(|&u| puzzle.cells[u] == num).map(|u| puzzle.pos(u)).collect::<Vec<P>>())
+        }).collect();
+        let problem = RouteProblem { width: puzzle.width, height: puzzle.height, obstacles: vec![], nets, objective: RouteObjective::Length };
+        let routing = match route_nets(&problem, &config) {
+            Ok(routing) => routing,
+            Err(e) => {
+                assert_eq!(e, Error::NoSolution);
+                continue;
+            },
+        };
+        let bound: usize = problem.nets.iter().map(|net| {
+            let ((a, b), (c, d)) = (net.terminals[0], net.terminals[1]);
+
+            a.max(c)-a.min(c)+b.max(d)-b.min(d)+1
+        }).sum();
+        let mut cells = BTreeSet::new();
+
+        assert!(routing.length >= bound, "{:?}", puzzle.cells);
+
+        for (net, routed) in problem.nets.iter().zip(&routing.nets) {
+            let path = &routed.paths[0];
+
+            assert_eq!(routed.paths.len(), 1);
+            assert_eq!((path[0], *path.last().unwrap()), (net.terminals[0], net.terminals[1]));
+            assert!(path.iter().all(|&p| cells.insert(p)), "{:?}", puzzle.cells);
+        }
+
+        assert_eq!(routing.length, cells.len());
+    }
+}
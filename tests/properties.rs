@@ -0,0 +1,191 @@
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+
+use numberlink_solver::generate::from_paths;
+use numberlink_solver::render::{render_puzzle_json, render_url};
+use numberlink_solver::rules::verify;
+use numberlink_solver::transform::{transform, transform_sol, Transform};
+use numberlink_solver::zdd::Zdd;
+use numberlink_solver::{parse_puzzle, parse_url, solve_numberlink, Encoding, Puzzle, Sol, SolverConfig, P};
+
+// 答えを埋め込んだ盤面を作り, URL や JSON に書いて読み直し, 解き, 回して裏返しても結果が変わらないことを確かめる
+// 失敗したら seed と盤面を書く. PROPERTY_SEED=N で その seed だけを, PROPERTY_CASES=N で N 個ずつ試す
+
+const CASES: u64 = 60;
+
+// 数え上げる (ZDD を作る) 盤面の大きさの上限
+const COUNT_CELLS: usize = 20;
+
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, n: usize) -> usize {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+
+        (self.0 >> 33) as usize%n
+    }
+}
+
+// 答えの分かっている盤面: 空いたセルから空いた隣へ進む道を伸ばしていき, 1 セルで止まったところは穴にする
+// 道の通らない辺の一部には壁を立てる
+struct Planted {
+    puzzle: Puzzle,
+    paths: Vec<Vec<P>>,
+}
+
+fn planted(rng: &mut Lcg) -> Planted {
+    let width = 2+rng.next(5);
+    let height = 2+rng.next(5);
+    let mut used = vec![false; width*height];
+    let mut paths = vec![];
+
+    loop {
+        let free: Vec<usize> = (0..width*height).filter(|&u| !used[u]).collect();
+
+        if free.is_empty() {
+            break;
+        }
+
+        let start = free[rng.next(free.len())];
+        let limit = 2+rng.next(width*height);
+        let mut path = vec![(start/width, start%width)];
+
+        used[start] = true;
+
+        while path.len() < limit {
+            let (i, j) = path[path.len()-1];
+            let next: Vec<P> = [(i+1, j), (i, j+1), (i.wrapping_sub(1), j), (i, j.wrapping_sub(1))].iter().cloned()
+                .filter(|&(i, j)| i < height && j < width && !used[i*width+j])
+                .collect();
+
+            if next.is_empty() {
+                break;
+            }
+
+            let (i, j) = next[rng.next(next.len())];
+
+            used[i*width+j] = true;
+            path.push((i, j));
+        }
+
+        if path.len() >= 2 {
+            paths.push(path);
+        }
+    }
+
+    let mut puzzle = from_paths(width, height, &paths).unwrap();
+    let on_path = |p: P, q: P| paths.iter().any(|path| path.windows(2).any(|w| (w[0], w[1]) == (p, q) || (w[1], w[0]) == (p, q)));
+
+    for _ in 0..rng.next(4) {
+        let (i, j) = (rng.next(height), rng.next(width));
+        let q = if rng.next(2) == 0 { (i+1, j) } else { (i, j+1) };
+
+        if q.0 < height && q.1 < width && !on_path((i, j), q) {
+            puzzle.add_wall((i, j), q);
+        }
+    }
+
+    Planted { puzzle, paths }
+}
+
+// 道を arc に. 数字の道は行優先で先にある端から向ける (verify の向き)
+fn arcs(puzzle: &Puzzle, paths: &[Vec<P>]) -> Sol {
+    paths.iter().flat_map(|path| {
+        let mut path = path.clone();
+
+        if puzzle.index(path[0]) > puzzle.index(path[path.len()-1]) {
+            path.reverse();
+        }
+
+        path.windows(2).map(|w| (w[0], w[1])).collect::<Vec<_>>()
+    }).collect()
+}
+
+// seed ごとに property を確かめ, 落ちたら seed と盤面を添えて落とす
+fn check(name: &str, property: impl Fn(&Planted)) {
+    let seeds: Vec<u64> = match env::var("PROPERTY_SEED").ok().and_then(|seed| seed.parse().ok()) {
+        Some(seed) => vec![seed],
+        None => (0..env::var("PROPERTY_CASES").ok().and_then(|cases| cases.parse().ok()).unwrap_or(CASES)).collect(),
+    };
+
+    for seed in seeds {
+        let case = planted(&mut Lcg(seed));
+
+        if let Err(e) = panic::catch_unwind(AssertUnwindSafe(|| property(&case))) {
+            eprintln!("{} failed for PROPERTY_SEED={} on {}", name, seed, render_puzzle_json(&case.puzzle).trim_end());
+            panic::resume_unwind(e);
+        }
+    }
+}
+
+#[test]
+fn planted_solutions_verify() {
+    check("planted_solutions_verify", |case| {
+        let config = SolverConfig::default();
+
+        assert_eq!(verify(&case.puzzle, &config.rules, &arcs(&case.puzzle, &case.paths)), Ok(()));
+    });
+}
+
+#[test]
+fn encode_decode_solve() {
+    check("encode_decode_solve", |case| {
+        let puzzle = &case.puzzle;
+
+        // 穴のない盤面は URL に, どの盤面も JSON に書ける
+        if puzzle.holes.is_empty() {
+            assert_eq!(parse_url(render_url(puzzle)).as_ref(), Some(puzzle));
+        }
+
+        let decoded = parse_puzzle(render_puzzle_json(puzzle).trim()).unwrap();
+
+        assert_eq!(&decoded, puzzle);
+
+        for &encoding in [Encoding::Log, Encoding::Direct].iter() {
+            let config = SolverConfig { encoding, ..SolverConfig::default() };
+            let sol = solve_numberlink(&decoded, &config).unwrap();
+
+            assert_eq!(verify(&decoded, &config.rules, &sol), Ok(()), "{:?}", encoding);
+        }
+    });
+}
+
+#[test]
+fn transforms_keep_results() {
+    check("transforms_keep_results", |case| {
+        let config = SolverConfig::default();
+        let puzzle = &case.puzzle;
+        let sol = solve_numberlink(puzzle, &config).unwrap();
+        let count = |puzzle: &Puzzle| Zdd::build(puzzle, &config).unwrap().count();
+        let expected = match puzzle.cells.len() <= COUNT_CELLS {
+            true => Some(count(puzzle)),
+            false => None,
+        };
+
+        for &t in Transform::ALL.iter() {
+            let next = transform(puzzle, t).unwrap();
+
+            // 写した答えはそのまま答えで, 解き直しても答えが見つかり, 答えの数も同じ
+            assert_eq!(verify(&next, &config.rules, &transform_sol(puzzle, &sol, t)), Ok(()), "{}", t);
+            assert_eq!(verify(&next, &config.rules, &solve_numberlink(&next, &config).unwrap()), Ok(()), "{}", t);
+            assert_eq!(transform(&next, t.inverse()).as_ref(), Some(puzzle), "{}", t);
+
+            if let Some(expected) = expected {
+                assert_eq!(count(&next), expected, "{}", t);
+            }
+        }
+    });
+}
+
+// PROPERTY_SEED=211 で見つかった盤面. 1 の道が数字の決まっていない空白の断片を通って 2 の道を回り込む答えを
+// ZDD の交差の枝刈りが落としていた
+#[test]
+fn rotated_counts() {
+    let config = SolverConfig::default();
+
+    for field in ["[[0,0,0,2,0,0],[0,0,0,1,0,0],[2,0,0,0,0,1]]", "[[1,0,0,0,0,2],[0,0,1,0,0,0],[0,0,2,0,0,0]]"].iter() {
+        let puzzle = parse_puzzle(&format!(r#"{{"width":6,"height":3,"field":{}}}"#, field)).unwrap();
+
+        assert_eq!(Zdd::build(&puzzle, &config).unwrap().count(), 24, "{}", field);
+    }
+}
@@ -0,0 +1,124 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use numberlink_solver::render::{render, Format, Theme};
+use numberlink_solver::{encode_formula, parse_url, Encoding, Session, SolverConfig};
+
+// benches/corpus.txt の盤面を符号化ごとに解き, 解と統計を tests/snapshots/corpus.txt と突き合わせる
+// 符号化を変えて結果が変わったときは差分を見て, 意図したものなら UPDATE_SNAPSHOTS=1 cargo test --test snapshot で書き直す
+const CORPUS: &str = include_str!("../benches/corpus.txt");
+const SNAPSHOT: &str = "tests/snapshots/corpus.txt";
+
+// 差分の前後に出す変わらない行の数
+const CONTEXT: usize = 2;
+
+fn corpus() -> Vec<(&'static str, &'static str)> {
+    CORPUS.lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+
+            Some((words.next()?, words.next()?))
+        })
+        .collect()
+}
+
+// 盤面と符号化ごとに見出し, 変数と節の数, 衝突の数, 解の盤面 (--format text と同じ)
+fn solve_corpus() -> String {
+    let mut res = String::new();
+
+    for (name, url) in corpus() {
+        let puzzle = parse_url(url.to_string()).unwrap();
+
+        for &encoding in [Encoding::Log, Encoding::Direct].iter() {
+            // 衝突は期限があるときだけ数える
+            let config = SolverConfig { encoding, timeout: Some(Duration::from_secs(600)), ..SolverConfig::default() };
+            let formula = encode_formula(&puzzle, &config).unwrap();
+            let mut session = Session::new(&puzzle, &config).unwrap();
+
+            res.push_str(&format!("== {} {:?}\n", name, encoding));
+            res.push_str(&format!("vars {} clauses {}\n", formula.var_count(), formula.len()));
+
+            match session.solve() {
+                Ok(sol) => {
+                    res.push_str(&format!("conflicts {} arcs {}\n", session.stats().conflicts, sol.len()));
+                    res.push_str(&render(&puzzle, &sol, Format::Text, Theme::Plain));
+                },
+                Err(e) => res.push_str(&format!("conflicts {} {}\n", session.stats().conflicts, e)),
+            }
+        }
+    }
+
+    res
+}
+
+// 行の最長共通部分列から作る unified diff 風の差分 (- が記録, + が今回)
+fn diff(expected: &str, actual: &str) -> String {
+    let (a, b): (Vec<&str>, Vec<&str>) = (expected.lines().collect(), actual.lines().collect());
+    let mut lcs = vec![vec![0; b.len()+1]; a.len()+1];
+
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i+1][j+1]+1 } else { lcs[i+1][j].max(lcs[i][j+1]) };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push((' ', a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i+1][j] >= lcs[i][j+1]) {
+            lines.push(('-', a[i]));
+            i += 1;
+        } else {
+            lines.push(('+', b[j]));
+            j += 1;
+        }
+    }
+
+    // 変わった行とその前後だけ. 離れたところは ... で区切る
+    let near = |k: usize| lines[k.saturating_sub(CONTEXT)..(k+CONTEXT+1).min(lines.len())].iter().any(|&(mark, _)| mark != ' ');
+    let mut res = String::new();
+    let mut skipped = false;
+
+    for (k, &(mark, line)) in lines.iter().enumerate() {
+        if near(k) {
+            res.push_str(&format!("{} {}\n", mark, line));
+            skipped = false;
+        } else if !skipped {
+            res.push_str("...\n");
+            skipped = true;
+        }
+    }
+
+    res
+}
+
+#[test]
+fn corpus_snapshot() {
+    let actual = solve_corpus();
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(SNAPSHOT);
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() || !path.exists() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap();
+
+    assert!(expected == actual, "results differ from {} (rerun with UPDATE_SNAPSHOTS=1 if the change is intended):\n{}", SNAPSHOT, diff(&expected, &actual));
+}
+
+#[test]
+fn snapshot_diff() {
+    assert_eq!(diff("a\nb\nc\n", "a\nb\nc\n").trim(), "...");
+    assert_eq!(diff("a\nb\nc\nd\ne\nf\n", "a\nb\nc\nd\ne\ng\n"), "...\n  d\n  e\n- f\n+ g\n");
+    assert_eq!(diff("x\n", "x\ny\n"), "  x\n+ y\n");
+}